@@ -45,8 +45,85 @@ fn bench_scanner(c: &mut Criterion) {
 
     c.bench_function("scanner_scan_synthetic_tree", |b| {
         b.iter(|| {
-            let (items, errors, entries) = scanner.scan().expect("scan succeeds");
-            black_box((items.len(), errors.len(), entries));
+            let outcome = scanner.scan().expect("scan succeeds");
+            black_box((
+                outcome.items.len(),
+                outcome.errors.len(),
+                outcome.stats.entries_scanned,
+            ));
+        });
+    });
+}
+
+/// A tree with few directories holding many files, where scan memory used to grow with
+/// every file seen.
+fn setup_wide_tree() -> TempDir {
+    let temp = TempDir::new().expect("create temp fixture");
+
+    for project_idx in 0..4 {
+        let modules = temp
+            .path()
+            .join(format!("project_{project_idx}/node_modules/pkg"));
+        fs::create_dir_all(&modules).unwrap();
+        for file_idx in 0..5_000 {
+            fs::write(
+                modules.join(format!("file_{file_idx}.js")),
+                b"module.exports = 1;",
+            )
+            .unwrap();
+        }
+    }
+
+    temp
+}
+
+fn bench_scanner_wide_tree(c: &mut Criterion) {
+    let fixture = setup_wide_tree();
+    let config = Config::default();
+    let matcher = Arc::new(PatternMatcher::new(&config.patterns).expect("compile patterns"));
+    let scanner = Scanner::new(fixture.path().to_path_buf(), Arc::clone(&matcher))
+        .with_max_depth(config.safety.max_depth);
+
+    c.bench_function("scanner_scan_wide_tree", |b| {
+        b.iter(|| {
+            let outcome = scanner.scan().expect("scan succeeds");
+            black_box((outcome.items.len(), outcome.stats.entries_scanned));
+        });
+    });
+}
+
+/// A tree of source checkouts, mostly directories that match nothing, to compare scans
+/// before and after a change with `--save-baseline` and `--baseline`.
+fn setup_source_tree() -> TempDir {
+    let temp = TempDir::new().expect("create temp fixture");
+
+    for project_idx in 0..8 {
+        let project_root = temp.path().join(format!("project_{project_idx}"));
+        for module_idx in 0..25 {
+            let module = project_root.join(format!("src/module_{module_idx}/nested/deeper"));
+            fs::create_dir_all(&module).unwrap();
+            for file_idx in 0..4 {
+                fs::write(module.join(format!("file_{file_idx}.rs")), b"pub fn f() {}").unwrap();
+            }
+        }
+        fs::create_dir_all(project_root.join("target/debug")).unwrap();
+        fs::write(project_root.join("target/debug/app"), b"binary").unwrap();
+    }
+
+    temp
+}
+
+fn bench_scanner_source_tree(c: &mut Criterion) {
+    let fixture = setup_source_tree();
+    let config = Config::default();
+    let matcher = Arc::new(PatternMatcher::new(&config.patterns).expect("compile patterns"));
+    let scanner = Scanner::new(fixture.path().to_path_buf(), Arc::clone(&matcher))
+        .with_max_depth(config.safety.max_depth);
+
+    c.bench_function("scanner_scan_source_tree", |b| {
+        b.iter(|| {
+            let outcome = scanner.scan().expect("scan succeeds");
+            black_box((outcome.items.len(), outcome.stats.entries_scanned));
         });
     });
 }
@@ -92,6 +169,7 @@ fn make_item(path: PathBuf, category: PatternCategory, item_type: ItemType) -> C
             priority: 0,
             source: PatternSource::Config,
             category,
+            rebuild: None,
         },
     }
 }
@@ -109,7 +187,59 @@ fn bench_prune_nested_items(c: &mut Criterion) {
             BatchSize::SmallInput,
         );
     });
+
+    // 100k items, where the old ancestor-set pruning slowed down noticeably.
+    let base_items = generate_items(25_000);
+
+    c.bench_function("prune_nested_items_100k", |b| {
+        b.iter_batched(
+            || base_items.clone(),
+            |items| {
+                let pruned = prune_nested_items(items);
+                black_box(pruned);
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+/// Deletes a matched directory of many files; compare builds with and without the
+/// `uring` feature on Linux.
+fn bench_remove_wide_dir(c: &mut Criterion) {
+    c.bench_function("remove_dir_all_5k_files", |b| {
+        b.iter_batched(
+            || {
+                let temp = TempDir::new().expect("create temp fixture");
+                let modules = temp.path().join("node_modules");
+                for pkg_idx in 0..10 {
+                    let pkg = modules.join(format!("pkg_{pkg_idx}"));
+                    fs::create_dir_all(&pkg).unwrap();
+                    for file_idx in 0..500 {
+                        fs::write(
+                            pkg.join(format!("file_{file_idx}.js")),
+                            b"module.exports = 1;",
+                        )
+                        .unwrap();
+                    }
+                }
+                temp
+            },
+            |temp| {
+                mc::engine::native::remove_dir_all(&temp.path().join("node_modules"))
+                    .expect("remove succeeds");
+                black_box(temp);
+            },
+            BatchSize::PerIteration,
+        );
+    });
 }
 
-criterion_group!(performance, bench_scanner, bench_prune_nested_items);
+criterion_group!(
+    performance,
+    bench_scanner,
+    bench_scanner_wide_tree,
+    bench_scanner_source_tree,
+    bench_prune_nested_items,
+    bench_remove_wide_dir
+);
 criterion_main!(performance);
@@ -0,0 +1,57 @@
+#ifndef MC_H
+#define MC_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdbool.h>
+
+// The result of an mc call: JSON for either a value or an error.
+typedef struct McReport McReport;
+
+// Scans `root` with the configuration at `config_path`, or the default configuration
+// if it is null, and returns the scan outcome.
+//
+// # Safety
+//
+// `root` must be a valid NUL-terminated string; `config_path` must be one or null.
+struct McReport *mc_scan(const char *root, const char *config_path);
+
+// Plans a clean of `root` without deleting anything, and returns the dry-run report.
+//
+// # Safety
+//
+// As for [`mc_scan`].
+struct McReport *mc_plan(const char *root, const char *config_path);
+
+// Cleans `root`, deleting what [`mc_plan`] lists, and returns the report.
+//
+// # Safety
+//
+// As for [`mc_scan`].
+struct McReport *mc_clean(const char *root, const char *config_path);
+
+// Returns true if `report` holds an error rather than a result.
+//
+// # Safety
+//
+// `report` must be a report returned by mc and not yet freed.
+bool mc_report_is_error(const struct McReport *report);
+
+// Returns the JSON text of `report`, valid until the report is freed.
+//
+// # Safety
+//
+// `report` must be a report returned by mc and not yet freed.
+const char *mc_report_json(const struct McReport *report);
+
+// Frees `report`. Does nothing if it is null.
+//
+// # Safety
+//
+// `report` must be null or a report returned by mc and not yet freed.
+void mc_report_free(struct McReport *report);
+
+// Returns the version of mc, such as `0.3.0`.
+const char *mc_version(void);
+
+#endif  /* MC_H */
@@ -0,0 +1,204 @@
+//! This module raises alerts when the reclaimable space under a root grows past a
+//! configured threshold.
+//!
+//! Alerts are the non-destructive counterpart to cleaning: `mc check` scans a tree,
+//! compares the reclaimable bytes against `alerts.threshold_gb`, and either shows a
+//! desktop notification, runs a user hook, or both. Nothing is deleted.
+//!
+//! Desktop notifications are delivered through the tools each platform already ships
+//! (`notify-send`, `osascript`, PowerShell) so no notification daemon bindings are needed.
+
+use crate::config::AlertsConfig;
+use crate::hooks;
+use crate::types::{CleanItem, Result};
+use crate::utils::format;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// An alert describing reclaimable space that exceeded the configured threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// The root that was scanned.
+    pub root: PathBuf,
+    /// The total size of all items that would be cleaned.
+    pub reclaimable_bytes: u64,
+    /// The number of items that would be cleaned.
+    pub item_count: usize,
+    /// The threshold that was exceeded, in bytes.
+    pub threshold_bytes: u64,
+}
+
+/// Compares the reclaimable space of `items` against the configured threshold.
+///
+/// Returns `None` when alerts are disabled or the threshold has not been exceeded.
+pub fn check_threshold(config: &AlertsConfig, root: &Path, items: &[CleanItem]) -> Option<Alert> {
+    let threshold_gb = config.threshold_gb?;
+    let threshold_bytes = (threshold_gb * 1_000_000_000.0) as u64;
+    let reclaimable_bytes: u64 = items.iter().map(|item| item.size).sum();
+
+    if reclaimable_bytes <= threshold_bytes {
+        log::debug!(
+            "Reclaimable {} bytes is below alert threshold {} bytes",
+            reclaimable_bytes,
+            threshold_bytes
+        );
+        return None;
+    }
+
+    Some(Alert {
+        root: root.to_path_buf(),
+        reclaimable_bytes,
+        item_count: items.len(),
+        threshold_bytes,
+    })
+}
+
+impl Alert {
+    /// Returns a one-line, human-readable description of the alert.
+    pub fn message(&self) -> String {
+        format!(
+            "{} reclaimable in {} items under {} (threshold {})",
+            format::size(self.reclaimable_bytes),
+            self.item_count,
+            self.root.display(),
+            format::size(self.threshold_bytes)
+        )
+    }
+
+    /// Delivers the alert through the channels enabled in `config`.
+    ///
+    /// A failed desktop notification is logged and otherwise ignored, since headless
+    /// machines commonly lack a notification service. A failing hook is an error.
+    pub fn raise(&self, config: &AlertsConfig) -> Result<()> {
+        if config.notify {
+            if let Err(err) = send_desktop_notification("mc: space to reclaim", &self.message()) {
+                log::warn!("Desktop notification failed: {}", err);
+            }
+        }
+
+        if let Some(ref hook) = config.hook {
+            hooks::run_hook(hook, &self.hook_env(), None)?;
+        }
+
+        Ok(())
+    }
+
+    /// The environment variables exposed to the alert hook.
+    fn hook_env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("MC_ROOT", self.root.display().to_string()),
+            ("MC_RECLAIMABLE_BYTES", self.reclaimable_bytes.to_string()),
+            ("MC_ITEM_COUNT", self.item_count.to_string()),
+            ("MC_THRESHOLD_BYTES", self.threshold_bytes.to_string()),
+        ]
+    }
+}
+
+/// Shows a desktop notification using the platform's bundled tooling.
+fn send_desktop_notification(title: &str, body: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let script = format!(
+            "display notification {:?} with title {:?}",
+            body.replace('"', "'"),
+            title.replace('"', "'")
+        );
+        let mut cmd = Command::new("osascript");
+        cmd.args(["-e", &script]);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let script = format!(
+            "[reflection.assembly]::loadwithpartialname('System.Windows.Forms') | Out-Null; \
+             $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(10000, '{}', '{}', 'Info')",
+            title.replace('\'', "''"),
+            body.replace('\'', "''")
+        );
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command", &script]);
+        cmd
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut cmd = {
+        let mut cmd = Command::new("notify-send");
+        cmd.args([title, body]);
+        cmd
+    };
+
+    crate::hooks::keep_off_stdio(&mut cmd);
+    let status = cmd.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "notifier exited with {}",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+
+    #[test]
+    fn test_check_threshold_disabled_by_default() {
+        let config = AlertsConfig::default();
+        let items = vec![clean_item(
+            "/project/target",
+            ItemType::Directory,
+            u64::MAX / 2,
+            PatternCategory::BuildOutputs,
+        )];
+        assert!(check_threshold(&config, Path::new("/project"), &items).is_none());
+    }
+
+    #[test]
+    fn test_check_threshold_below_and_above() {
+        let config = AlertsConfig {
+            threshold_gb: Some(1.0),
+            ..AlertsConfig::default()
+        };
+        let root = Path::new("/project");
+
+        assert!(check_threshold(
+            &config,
+            root,
+            &[clean_item(
+                "/project/target",
+                ItemType::Directory,
+                500_000_000,
+                PatternCategory::BuildOutputs
+            )]
+        )
+        .is_none());
+
+        let alert = check_threshold(
+            &config,
+            root,
+            &[
+                clean_item(
+                    "/project/target",
+                    ItemType::Directory,
+                    800_000_000,
+                    PatternCategory::BuildOutputs,
+                ),
+                clean_item(
+                    "/project/target",
+                    ItemType::Directory,
+                    800_000_000,
+                    PatternCategory::BuildOutputs,
+                ),
+            ],
+        )
+        .unwrap();
+        assert_eq!(alert.reclaimable_bytes, 1_600_000_000);
+        assert_eq!(alert.item_count, 2);
+        assert_eq!(alert.threshold_bytes, 1_000_000_000);
+    }
+}
@@ -0,0 +1,142 @@
+//! This module excludes recreatable artifact directories from backups and indexing.
+//!
+//! `mc exclude-backups` reuses the scan and plan of a clean, but instead of deleting
+//! the matched directories it marks them so Time Machine skips them and Spotlight
+//! stops indexing them. Nothing is deleted, and the directories keep being usable.
+//!
+//! Time Machine exclusions are sticky (`tmutil addexclusion`): they are stored as an
+//! extended attribute on the directory and follow it if it moves. Spotlight is told to
+//! skip a directory through a `.metadata_never_index` marker file inside it. Both are
+//! only available on macOS.
+
+use crate::types::{CleanItem, ItemType};
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(target_os = "macos")]
+use std::process::Command;
+
+/// The marker file that keeps Spotlight from indexing a directory.
+pub const SPOTLIGHT_MARKER: &str = ".metadata_never_index";
+
+/// Returns the planned directories that should be excluded. Files and links are
+/// left alone, since only whole artifact directories are worth excluding.
+pub fn candidates(items: &[CleanItem]) -> Vec<&Path> {
+    items
+        .iter()
+        .filter(|item| item.item_type == ItemType::Directory)
+        .map(|item| item.path.as_path())
+        .collect()
+}
+
+/// Excludes `dir` from Time Machine backups and, if `spotlight` is set, from
+/// Spotlight indexing.
+///
+/// # Errors
+///
+/// Returns an error if `tmutil` fails or the Spotlight marker cannot be written, and
+/// an `Unsupported` error on platforms other than macOS.
+pub fn exclude(dir: &Path, spotlight: bool) -> io::Result<()> {
+    exclude_from_time_machine(dir)?;
+    if spotlight {
+        exclude_from_spotlight(dir)?;
+    }
+    Ok(())
+}
+
+/// Adds a sticky Time Machine exclusion for `dir`.
+#[cfg(target_os = "macos")]
+fn exclude_from_time_machine(dir: &Path) -> io::Result<()> {
+    let output = Command::new("tmutil")
+        .arg("addexclusion")
+        .arg(dir)
+        .output()?;
+    if output.status.success() {
+        log::debug!("Excluded {} from Time Machine", dir.display());
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "tmutil addexclusion failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Time Machine only exists on macOS.
+#[cfg(not(target_os = "macos"))]
+fn exclude_from_time_machine(_dir: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "backup exclusions are only supported on macOS",
+    ))
+}
+
+/// Drops the Spotlight marker into `dir`, keeping an existing one.
+///
+/// The directory's modification time is put back afterwards, since the age-based
+/// rules read it and excluding a directory should not make it look recently used.
+fn exclude_from_spotlight(dir: &Path) -> io::Result<()> {
+    let marker = dir.join(SPOTLIGHT_MARKER);
+    if !marker.exists() {
+        let modified = fs::metadata(dir)?.modified()?;
+        fs::File::create(&marker)?;
+        if let Err(err) = fs::File::open(dir).and_then(|dir| dir.set_modified(modified)) {
+            log::debug!("Cannot restore the mtime of {}: {}", dir.display(), err);
+        }
+        log::debug!("Excluded {} from Spotlight", dir.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_candidates_are_directories_only() {
+        let items = vec![
+            clean_item(
+                "/project/target",
+                ItemType::Directory,
+                0,
+                PatternCategory::BuildOutputs,
+            ),
+            clean_item(
+                "/project/app.log",
+                ItemType::File,
+                0,
+                PatternCategory::BuildOutputs,
+            ),
+            clean_item(
+                "/project/link",
+                ItemType::Symlink,
+                0,
+                PatternCategory::BuildOutputs,
+            ),
+        ];
+        assert_eq!(candidates(&items), vec![Path::new("/project/target")]);
+    }
+
+    #[test]
+    fn test_spotlight_marker_is_written_once() {
+        let temp = TempDir::new().unwrap();
+        let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(86_400);
+        fs::File::open(temp.path())
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        exclude_from_spotlight(temp.path()).unwrap();
+        exclude_from_spotlight(temp.path()).unwrap();
+        temp.child(SPOTLIGHT_MARKER)
+            .assert(predicates::path::is_file());
+        assert_eq!(
+            fs::metadata(temp.path()).unwrap().modified().unwrap(),
+            modified
+        );
+    }
+}
@@ -3,8 +3,12 @@
 //! It uses the `clap` crate to parse command-line arguments and subcommands,
 //! providing a structured way to configure the cleaning process at runtime.
 
-use clap::{Parser, Subcommand};
+use crate::config::SizeMode;
+use crate::report::ReportFormat;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// A high-performance build directory cleaner.
 #[derive(Parser)]
@@ -17,6 +21,12 @@ pub struct Cli {
     #[arg(default_value = ".")]
     pub path: PathBuf,
 
+    /// More root paths to clean in the same run. Each root is scanned and cleaned on
+    /// its own, and the report shows a section per root above the merged totals.
+    /// Subcommands only use the first path.
+    #[arg(value_name = "MORE_PATHS")]
+    pub more_paths: Vec<PathBuf>,
+
     /// If set, previews what would be deleted without performing any actual file operations.
     #[arg(short = 'd', long = "dry-run")]
     pub dry_run: bool,
@@ -33,27 +43,64 @@ pub struct Cli {
     #[arg(long = "no-color")]
     pub no_color: bool,
 
+    /// Shows sizes in decimal units (kB, MB, GB), powers of 1000, even if the
+    /// `binary_units` setting in the configuration file is on. This is the default.
+    #[arg(long = "si", conflicts_with = "binary")]
+    pub si: bool,
+
+    /// Shows sizes in binary units (KiB, MiB, GiB), powers of 1024, as `du -h` and
+    /// Windows do.
+    #[arg(long = "binary")]
+    pub binary: bool,
+
     /// Outputs the result as a JSON object to stdout. Implies --quiet for progress.
     #[arg(long = "json")]
     pub json: bool,
 
+    /// Prints the report of a clean as `human`, `json`, `toml`, `csv` or `line`. The
+    /// formats other than `human` imply --quiet for progress, like --json.
+    #[arg(long = "report-format", value_enum, conflicts_with = "json")]
+    pub report_format: Option<ReportFormatArg>,
+
+    /// Prints the report of a clean as one line for scripts, such as
+    /// `freed=12345 items=42 errors=0 duration_ms=830`, even with --quiet. Same as
+    /// `--report-format line`.
+    #[arg(long = "summary-line", conflicts_with_all = ["json", "report_format"])]
+    pub summary_line: bool,
+
     /// Skips any interactive confirmation prompts, useful for scripting.
     /// This overrides the `require_confirmation` setting in the configuration file.
     #[arg(short = 'y', long = "yes")]
     pub yes: bool,
 
+    /// Deletes modified and untracked files inside git repositories without asking.
+    /// Otherwise they are listed and must be confirmed separately, even with `--yes`;
+    /// `watch --clean` without a terminal, `serve`, `remote` and `fleet` leave the
+    /// items holding them alone.
+    #[arg(long = "allow-uncommitted")]
+    pub allow_uncommitted: bool,
+
     /// Specifies one or more patterns to exclude from cleaning. Can be repeated.
     /// These are merged with the exclude patterns from the configuration file.
     #[arg(short = 'e', long = "exclude")]
     pub exclude: Vec<String>,
 
     /// Specifies one or more additional patterns to include for cleaning. Can be repeated.
-    /// These are merged with the include patterns from the configuration file.
+    /// These are merged with the include patterns from the configuration file. Patterns
+    /// match both files and directories unless prefixed with `dir:` or `file:`.
     #[arg(short = 'i', long = "include")]
     pub include: Vec<String>,
 
-    /// Specifies a path to a custom configuration file (`.mc.toml`).
-    /// If not provided, `mc` searches for `.mc.toml` in the current directory and its ancestors.
+    /// Specifies additional patterns that only match directories. Can be repeated.
+    #[arg(long = "include-dir")]
+    pub include_dir: Vec<String>,
+
+    /// Specifies additional patterns that only match files. Can be repeated.
+    #[arg(long = "include-file")]
+    pub include_file: Vec<String>,
+
+    /// Specifies a path to a custom configuration file (`.mc.toml`, `.mc.yaml` or `.mc.json`).
+    /// If not provided, `mc` searches for one in the current directory and its ancestors.
     #[arg(short = 'c', long = "config")]
     pub config: Option<PathBuf>,
 
@@ -62,26 +109,244 @@ pub struct Cli {
     #[arg(short = 's', long = "stats")]
     pub stats: bool,
 
-    /// Specifies the number of parallel threads to use for cleaning.
+    /// Specifies the number of parallel threads to use for cleaning, or `auto` to pick
+    /// one for the device being cleaned.
     /// This overrides the `parallel_threads` setting in the configuration file.
     #[arg(short = 'p', long = "parallel")]
-    pub parallel: Option<usize>,
+    pub parallel: Option<crate::config::ParallelThreads>,
 
     /// Disables the safety check that prevents cleaning inside a git repository.
     /// This overrides the `check_git_repo` setting in the configuration file.
     #[arg(long = "no-git-check")]
     pub no_git_check: bool,
 
+    /// Inside git repositories, only cleans what `git clean -dX` would remove: ignored,
+    /// untracked paths. Roots inside a repository are allowed in this mode.
+    /// This overrides the `clean_mode` setting in the `[safety.git]` section.
+    #[arg(long = "git-clean-mode")]
+    pub git_clean_mode: bool,
+
+    /// Specifies how many directory levels below the target path to scan.
+    /// This overrides the `max_depth` setting in the configuration file.
+    #[arg(long = "max-depth")]
+    pub max_depth: Option<usize>,
+
+    /// Follows symbolic links while scanning.
+    /// This overrides the `symlinks` setting in the configuration file.
+    #[arg(long = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Stays on the file system of the target path, skipping mount points below it.
+    /// This overrides the `one_file_system` setting in the configuration file.
+    #[arg(long = "one-file-system")]
+    pub one_file_system: bool,
+
+    /// Overwrites file contents with random bytes before deleting them. This does not
+    /// erase old data on SSDs or copy-on-write file systems.
+    /// This overrides the `secure_delete` setting in the configuration file.
+    #[arg(long = "shred")]
+    pub shred: bool,
+
+    /// Archives matched items into a tar file in this directory before deleting them.
+    /// This overrides the `dir` setting in the `[archive]` configuration section.
+    #[arg(long = "archive", value_name = "DIR")]
+    pub archive: Option<PathBuf>,
+
+    /// Only cleans items not used (accessed or modified) for this long, e.g. `90d`,
+    /// `12w`, `6mo` or `1y`; a bare number is days. This overrides the
+    /// `unused_for_days` setting in the configuration file.
+    #[arg(long = "unused-for", value_name = "AGE", value_parser = parse_age_days)]
+    pub unused_for: Option<u64>,
+
+    /// Stops scanning each root after this long, e.g. `90s`, `10m` or `1h`; a bare
+    /// number is seconds. What was found so far is cleaned, except directories whose
+    /// contents the scan had not finished, and the report says the scan was cut short.
+    #[arg(long = "scan-timeout", value_name = "DURATION", value_parser = parse_timeout)]
+    pub scan_timeout: Option<Duration>,
+
+    /// Sizes matched directories in a dry run from a sample of their entries (`fast`),
+    /// marked with `≈`, instead of walking every file (`exact`). This overrides the
+    /// `size_mode` setting in the configuration file.
+    #[arg(long = "size-mode", value_enum, value_name = "MODE")]
+    pub size_mode: Option<SizeModeArg>,
+
+    /// Stops starting new deletions in each root after this long, e.g. `30m`. Items
+    /// not reached are reported and can be deleted later with `mc resume`.
+    #[arg(long = "clean-timeout", value_name = "DURATION", value_parser = parse_timeout)]
+    pub clean_timeout: Option<Duration>,
+
+    /// Abandons the deletion of any single item that takes longer than this, e.g.
+    /// `2m`, logs its path and moves on. This overrides the `item_timeout_secs`
+    /// setting in the configuration file.
+    #[arg(long = "item-timeout", value_name = "DURATION", value_parser = parse_timeout)]
+    pub item_timeout: Option<Duration>,
+
+    /// Lists every scan and deletion error instead of a summary grouped by cause, and
+    /// keeps all scan errors instead of the first 1000.
+    #[arg(long = "show-errors")]
+    pub show_errors: bool,
+
+    /// Adds the paths that matched a pattern but were left alone, because an exclude
+    /// covers them or a safety filter skipped them, to the JSON output with the reason.
+    #[arg(long = "include-skipped", requires = "json")]
+    pub include_skipped: bool,
+
+    /// Lists and deletes matched items in path order, one at a time, and sorts the
+    /// report by path, so that runs over the same tree print the same output.
+    /// Deleting is slower this way.
+    #[arg(long = "deterministic")]
+    pub deterministic: bool,
+
+    /// Lists this many directories and files in the dry-run listing. Defaults to 20.
+    #[arg(long = "show", value_name = "N", conflicts_with = "show_all")]
+    pub show: Option<usize>,
+
+    /// Lists every item in the dry-run listing. Long listings are paged through `$PAGER`.
+    #[arg(long = "show-all")]
+    pub show_all: bool,
+
     /// If set, `.env` files will be preserved and not deleted.
     /// This takes precedence over "nuclear" mode for `.env` files.
     #[arg(long = "preserve-env")]
     pub preserve_env: bool,
 
+    /// When deletions fail with permission errors, offers to delete exactly those paths
+    /// again with elevated privileges (`sudo`/`pkexec` on Unix, a UAC prompt on Windows).
+    /// The paths are listed and confirmed first on stderr, even with `--yes`, and left
+    /// alone without a terminal.
+    #[arg(long = "sudo-retry")]
+    pub sudo_retry: bool,
+
     /// The subcommand to execute, if any. Subcommands have their own set of options.
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Cli {
+    /// Returns the format the report of a clean is printed in, from `--report-format`,
+    /// `--json` and `--summary-line`.
+    pub fn report_format(&self) -> ReportFormat {
+        match self.report_format {
+            Some(format) => format.into(),
+            None if self.json => ReportFormat::Json,
+            None if self.summary_line => ReportFormat::Line,
+            None => ReportFormat::Human,
+        }
+    }
+
+    /// Returns whether the report is printed for another program, which keeps progress
+    /// and other output off stdout.
+    pub fn machine_report(&self) -> bool {
+        self.report_format() != ReportFormat::Human
+    }
+
+    /// Returns how many directories and files the dry-run listing should show, from
+    /// `--show` and `--show-all`. `None` shows everything.
+    pub fn listing_limit(&self) -> Option<usize> {
+        if self.show_all {
+            None
+        } else {
+            Some(
+                self.show
+                    .unwrap_or(crate::engine::cleaner::DEFAULT_LISTING_LIMIT),
+            )
+        }
+    }
+}
+
+/// Parses an age such as `90d`, `12w`, `6mo` (30 days) or `1y` (365 days) into days.
+fn parse_age_days(age: &str) -> Result<u64, String> {
+    let (number, days_per_unit) = match age.strip_suffix("mo") {
+        Some(number) => (number, 30),
+        None => match age.char_indices().last() {
+            Some((i, 'd')) => (&age[..i], 1),
+            Some((i, 'w')) => (&age[..i], 7),
+            Some((i, 'y')) => (&age[..i], 365),
+            _ => (age, 1),
+        },
+    };
+    let n = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid age `{}`; expected e.g. 90d, 12w, 6mo or 1y", age))?;
+    n.checked_mul(days_per_unit)
+        .ok_or_else(|| format!("age `{}` is too large", age))
+}
+
+/// Parses a timeout such as `90s`, `10m` (minutes) or `1h`; a bare number is seconds.
+fn parse_timeout(timeout: &str) -> Result<Duration, String> {
+    let (number, secs_per_unit) = match timeout.char_indices().last() {
+        Some((i, 's')) => (&timeout[..i], 1),
+        Some((i, 'm')) => (&timeout[..i], 60),
+        Some((i, 'h')) => (&timeout[..i], 60 * 60),
+        _ => (timeout, 1),
+    };
+    let n = number.parse::<u64>().map_err(|_| {
+        format!(
+            "invalid timeout `{}`; expected e.g. 90s, 10m or 1h",
+            timeout
+        )
+    })?;
+    n.checked_mul(secs_per_unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("timeout `{}` is too large", timeout))
+}
+
+/// The formats `--report-format` accepts, one for each [`ReportFormat`].
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormatArg {
+    /// The summary `mc` prints, with colors.
+    Human,
+    /// A JSON object.
+    Json,
+    /// A TOML document.
+    Toml,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// A single line of `key=value` pairs, without colors.
+    Line,
+}
+
+impl From<ReportFormatArg> for ReportFormat {
+    fn from(format: ReportFormatArg) -> Self {
+        match format {
+            ReportFormatArg::Human => ReportFormat::Human,
+            ReportFormatArg::Json => ReportFormat::Json,
+            ReportFormatArg::Toml => ReportFormat::Toml,
+            ReportFormatArg::Csv => ReportFormat::Csv,
+            ReportFormatArg::Line => ReportFormat::Line,
+        }
+    }
+}
+
+/// The modes `--size-mode` accepts, one for each [`SizeMode`].
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeModeArg {
+    /// Every file inside a matched directory is walked and added up.
+    Exact,
+    /// Matched directories are sized from a sample of their entries.
+    Fast,
+}
+
+impl From<SizeModeArg> for SizeMode {
+    fn from(mode: SizeModeArg) -> Self {
+        match mode {
+            SizeModeArg::Exact => SizeMode::Exact,
+            SizeModeArg::Fast => SizeMode::Fast,
+        }
+    }
+}
+
+/// The formats `mc plan` prints in.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanOutput {
+    /// One line per item, with its size.
+    Text,
+    /// The [`Plan`](crate::Plan) as JSON.
+    Json,
+    /// A POSIX shell script removing the items.
+    Sh,
+}
+
 /// Defines the available subcommands for `mc`.
 #[derive(Subcommand, Clone)]
 pub enum Commands {
@@ -92,6 +357,41 @@ pub enum Commands {
         json: bool,
     },
 
+    /// Prints what a clean of the target path would delete, without deleting it.
+    Plan {
+        /// How the plan is printed: `text`, `json`, or `sh` for a commented script of
+        /// `rm` commands an operator can review and run.
+        #[arg(long = "output", value_enum, default_value_t = PlanOutput::Text)]
+        output: PlanOutput,
+    },
+
+    /// Lists the projects below the target path with their types, sizes, what a clean
+    /// would free in each, and when each was last modified, without deleting anything.
+    Projects {
+        /// If set, formats the output as JSON.
+        #[arg(long = "json")]
+        json: bool,
+    },
+
+    /// Ranks what a clean of the target path would delete by size, time unused and
+    /// how costly each item is to get back, and prints the suggested part as a plan.
+    Recommend {
+        /// If set, cleans the suggested items.
+        #[arg(long = "apply")]
+        apply: bool,
+
+        /// If set, formats the ranking as JSON.
+        #[arg(long = "json", conflicts_with = "apply")]
+        json: bool,
+    },
+
+    /// Shows how much space the quarantine and the archive directory hold.
+    Stats {
+        /// If set, formats the output as JSON.
+        #[arg(long = "json")]
+        json: bool,
+    },
+
     /// Creates a new `.mc.toml` configuration file in the current or global directory.
     Init {
         /// If set, creates the configuration file in the global user config directory.
@@ -99,6 +399,209 @@ pub enum Commands {
         global: bool,
     },
 
+    /// Translates the settings of another cleaner (kondo, cargo-sweep or npkill) into
+    /// `.mc.toml`, or the file given with `--config`. With `--dry-run`, only prints them.
+    Import {
+        /// The cleaner the settings come from: kondo, cargo-sweep or npkill.
+        #[arg(long = "from", value_name = "TOOL")]
+        from: crate::config::import::ImportFormat,
+
+        /// The command line that runs the cleaner. Without it, commands are read from
+        /// standard input, e.g. `crontab -l | mc import --from kondo`.
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "COMMAND"
+        )]
+        command: Vec<String>,
+    },
+
     /// Displays the current configuration that `mc` would use for the given path.
-    Config,
+    Config {
+        /// If set, shows the layer (default, file or flag) that set each setting and
+        /// the configuration files that were considered.
+        #[arg(long = "show-origin")]
+        show_origin: bool,
+
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
+    },
+
+    /// Scans the target path and raises an alert when reclaimable space exceeds
+    /// `alerts.threshold_gb`. Never deletes anything; intended for scheduled runs.
+    Check,
+
+    /// Marks the directories a clean would remove as excluded from Time Machine
+    /// backups and Spotlight indexing, without deleting anything (macOS only).
+    /// Honors `--dry-run`.
+    ExcludeBackups {
+        /// Only excludes from Time Machine, leaving Spotlight indexing alone.
+        #[arg(long = "no-spotlight")]
+        no_spotlight: bool,
+    },
+
+    /// Runs as a daemon, rescanning the target path on an interval. Alerts are raised
+    /// on every cycle and metrics can be exposed for Prometheus.
+    Watch {
+        /// Seconds to wait between scans.
+        #[arg(long = "interval", default_value_t = 3600)]
+        interval: u64,
+
+        /// Address to serve Prometheus metrics on, e.g. `127.0.0.1:9898`.
+        #[arg(long = "metrics-addr")]
+        metrics_addr: Option<SocketAddr>,
+
+        /// Deletes matched items on every cycle instead of only reporting them.
+        /// Requires `--yes`, and honors `--dry-run`.
+        #[arg(long = "clean")]
+        clean: bool,
+    },
+
+    /// Retries only the items that failed to delete in the last run on the target path,
+    /// without re-scanning.
+    Retry {
+        /// Retries the most recent failed run, whatever its path.
+        #[arg(long = "last")]
+        last: bool,
+
+        /// Applies the manifest even if it was written by another mc version, on
+        /// another machine or over a week ago.
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Removes the given paths; run elevated by `--sudo-retry` on Windows.
+    #[command(name = "delete-elevated", hide = true)]
+    DeleteElevated {
+        /// The paths to remove.
+        paths: Vec<PathBuf>,
+    },
+
+    /// Finishes an interrupted clean of the target path, deleting the items it had not
+    /// reached yet without re-scanning.
+    Resume {
+        /// Resumes the most recently interrupted clean, whatever its path.
+        #[arg(long = "last")]
+        last: bool,
+
+        /// Resumes even if the clean was started by another mc version, on another
+        /// machine or over a week ago.
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Serves scans and cleans to another program, such as a GUI or an editor extension,
+    /// over JSON-RPC, or to dashboards and scripts over HTTP. Honors `--dry-run`.
+    #[command(group = clap::ArgGroup::new("transport").required(true))]
+    Serve {
+        /// Reads requests from stdin and answers on stdout, one JSON object per line.
+        #[arg(long = "stdio", group = "transport")]
+        stdio: bool,
+
+        /// Address to serve the REST API on, e.g. `127.0.0.1:7979`. Clients may act on
+        /// the roots in `[server]`, or the target path if none are configured.
+        #[arg(long = "http", group = "transport")]
+        http: Option<SocketAddr>,
+    },
+
+    /// Cleans a root on another machine over SSH, with the mc installed there and its
+    /// configuration. Honors `--dry-run`, `--yes` and `--json`.
+    Remote {
+        /// The remote root, as `[user@]host:/path`.
+        target: crate::remote::RemoteTarget,
+
+        /// The ssh command to connect with, e.g. `ssh -p 2222`.
+        #[arg(long = "ssh", default_value = "ssh")]
+        ssh: String,
+
+        /// The mc binary on the remote machine.
+        #[arg(long = "remote-mc", default_value = "mc")]
+        remote_mc: String,
+    },
+
+    /// Cleans the roots of many machines concurrently over SSH, like `mc remote`, and
+    /// prints a table of what each host freed. Honors `--dry-run`, `--yes` and `--json`.
+    Fleet {
+        /// The inventory of hosts and their roots (see `mc::orchestrator`).
+        #[arg(long = "hosts", value_name = "FILE")]
+        hosts: PathBuf,
+
+        /// How many hosts to clean at once. Overrides `concurrency` in the inventory.
+        #[arg(long = "concurrency")]
+        concurrency: Option<std::num::NonZeroUsize>,
+    },
+
+    /// Checks the configuration for patterns that can never take effect (duplicated,
+    /// shadowed by an earlier pattern, or always excluded) and suggests removals.
+    Doctor {
+        /// Also scans the target path and reports patterns that match nothing there.
+        #[arg(long = "scan")]
+        scan: bool,
+
+        /// If set, formats the issues as a JSON array.
+        #[arg(long = "json")]
+        json: bool,
+    },
+
+    /// Inspects the configured patterns against the target path.
+    Patterns {
+        #[command(subcommand)]
+        command: PatternsCommand,
+    },
+
+    /// Lists or deletes the snapshots taken with `safety.snapshot_before` on the file
+    /// system holding the target path. Lists them when no subcommand is given.
+    Snapshots {
+        #[command(subcommand)]
+        command: Option<SnapshotsCommand>,
+    },
+}
+
+/// Subcommands of `mc config`.
+#[derive(Subcommand, Clone)]
+pub enum ConfigCommand {
+    /// Prints a JSON Schema for the configuration file, for editor validation and
+    /// completion or for checking configs in CI.
+    Schema,
+
+    /// Prints the effective value of a setting, e.g. `safety.max_depth`.
+    Get {
+        /// The dotted key of the setting.
+        key: String,
+    },
+
+    /// Sets a setting in the nearest configuration file, keeping its comments and
+    /// layout, e.g. `mc config set options.parallel_threads 4`.
+    Set {
+        /// The dotted key of the setting.
+        key: String,
+        /// The new value, parsed as TOML; anything else is stored as a string.
+        value: String,
+    },
+}
+
+/// Subcommands of `mc snapshots`.
+#[derive(Subcommand, Clone)]
+pub enum SnapshotsCommand {
+    /// Lists the snapshots with the command to roll the target path back to each.
+    List,
+
+    /// Deletes a snapshot after confirmation, unless `--yes` is given. Honors
+    /// `--dry-run`.
+    Delete {
+        /// The name of the snapshot, as shown by `mc snapshots list`.
+        name: String,
+    },
+}
+
+/// Subcommands of `mc patterns`.
+#[derive(Subcommand, Clone)]
+pub enum PatternsCommand {
+    /// Shows how many items and bytes each pattern matches, including patterns that
+    /// match nothing.
+    Stats {
+        /// If set, formats the output as a JSON object keyed by pattern.
+        #[arg(long = "json")]
+        json: bool,
+    },
 }
@@ -0,0 +1,349 @@
+//! Building a [`Config`] in code, for programs that use mc as a library.
+//!
+//! [`Config::builder`] starts from the defaults and offers a typed setter for each
+//! setting, grouped like the sections of a config file:
+//!
+//! ```
+//! use mc::Config;
+//!
+//! let mut builder = Config::builder();
+//! builder.patterns().directory("target").file("*.log").exclude("keep/");
+//! builder.safety().max_depth(6);
+//! let config = builder.build()?;
+//! # Ok::<(), mc::McError>(())
+//! ```
+//!
+//! [`ConfigBuilder::build`] compiles every glob and checks every range, so a bad
+//! pattern is reported here, naming it, instead of when a matcher is first created.
+//! [`Config`] and its `patterns`, `options` and `safety` sections are
+//! `#[non_exhaustive]`, so they cannot be written as struct literals outside mc; start
+//! from the builder or `Default` and set fields on the result instead. Setters are
+//! added as settings are, so code written against the builder keeps compiling as the
+//! config structs gain fields.
+
+use super::{ByteSize, Config, ParallelThreads, Scheduling};
+use crate::patterns::PatternMatcher;
+use crate::types::{McError, Result};
+use std::path::PathBuf;
+
+/// Builds a [`Config`], see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+/// Sets the `[patterns]` of a [`ConfigBuilder`].
+#[derive(Debug)]
+pub struct PatternsBuilder<'a> {
+    config: &'a mut Config,
+}
+
+/// Sets the `[options]` of a [`ConfigBuilder`].
+#[derive(Debug)]
+pub struct OptionsBuilder<'a> {
+    config: &'a mut Config,
+}
+
+/// Sets the `[safety]` settings of a [`ConfigBuilder`].
+#[derive(Debug)]
+pub struct SafetyBuilder<'a> {
+    config: &'a mut Config,
+}
+
+impl Config {
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::from(Config::default())
+    }
+}
+
+impl From<Config> for ConfigBuilder {
+    /// Returns a builder starting from `config`, such as one loaded from a file.
+    fn from(config: Config) -> Self {
+        Self { config }
+    }
+}
+
+impl ConfigBuilder {
+    /// Returns the setters for the `[patterns]` section.
+    pub fn patterns(&mut self) -> PatternsBuilder<'_> {
+        PatternsBuilder {
+            config: &mut self.config,
+        }
+    }
+
+    /// Returns the setters for the `[options]` section.
+    pub fn options(&mut self) -> OptionsBuilder<'_> {
+        OptionsBuilder {
+            config: &mut self.config,
+        }
+    }
+
+    /// Returns the setters for the `[safety]` section.
+    pub fn safety(&mut self) -> SafetyBuilder<'_> {
+        SafetyBuilder {
+            config: &mut self.config,
+        }
+    }
+
+    /// Checks the configuration and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`McError::Config`] naming the first invalid glob, in the patterns, the
+    /// repository policies or the secret protection, or the first setting out of
+    /// range.
+    pub fn build(self) -> Result<Config> {
+        let config = self.config;
+        check_patterns(&config)?;
+        PatternMatcher::from_config(&config)?;
+        crate::safety::git::GitPolicies::from_config(&config.safety.git)?;
+        crate::safety::SecretDetector::from_config(&config.safety.secrets)?;
+
+        let max_threads = crate::utils::available_parallelism();
+        if let ParallelThreads::Count(threads) = config.options.parallel_threads {
+            if threads == 0 || threads > max_threads {
+                return Err(out_of_range(
+                    "options.parallel_threads",
+                    threads,
+                    max_threads,
+                ));
+            }
+        }
+        if config.options.chunk_size == 0 {
+            return Err(out_of_range("options.chunk_size", 0, usize::MAX));
+        }
+        if config.safety.max_depth == 0 {
+            return Err(out_of_range("safety.max_depth", 0, usize::MAX));
+        }
+        let free_space = config.safety.min_free_space_gb;
+        if !free_space.is_finite() || free_space < 0.0 {
+            return Err(McError::Config(format!(
+                "safety.min_free_space_gb must be a size in GB, not {}",
+                free_space
+            )));
+        }
+        Ok(config)
+    }
+}
+
+impl PatternsBuilder<'_> {
+    /// Removes every pattern and exclude, including the built-in ones, so only those
+    /// added afterwards apply.
+    pub fn clear(self) -> Self {
+        self.config.patterns.directories.clear();
+        self.config.patterns.files.clear();
+        self.config.patterns.exclude.clear();
+        self
+    }
+
+    /// Adds a glob matching directories to clean.
+    pub fn directory(self, pattern: impl Into<String>) -> Self {
+        self.config.patterns.directories.push(pattern.into());
+        self
+    }
+
+    /// Adds a glob matching files to clean.
+    pub fn file(self, pattern: impl Into<String>) -> Self {
+        self.config.patterns.files.push(pattern.into());
+        self
+    }
+
+    /// Adds a glob for paths never to clean.
+    pub fn exclude(self, pattern: impl Into<String>) -> Self {
+        self.config.patterns.exclude.push(pattern.into());
+        self
+    }
+
+    /// Limits the walk depth at which `pattern` may match.
+    pub fn max_depth(self, pattern: impl Into<String>, depth: usize) -> Self {
+        self.config.patterns.max_depth.insert(pattern.into(), depth);
+        self
+    }
+
+    /// Sets the priority of `pattern` when several patterns match the same path.
+    pub fn priority(self, pattern: impl Into<String>, priority: u32) -> Self {
+        self.config
+            .patterns
+            .priority
+            .insert(pattern.into(), priority);
+        self
+    }
+}
+
+impl OptionsBuilder<'_> {
+    /// Sets the number of threads that delete in parallel.
+    pub fn parallel_threads(self, threads: ParallelThreads) -> Self {
+        self.config.options.parallel_threads = threads;
+        self
+    }
+
+    /// Sets how deletions are scheduled across threads.
+    pub fn scheduling(self, scheduling: Scheduling) -> Self {
+        self.config.options.scheduling = scheduling;
+        self
+    }
+
+    /// Sets the number of items a thread takes at once with chunked scheduling.
+    pub fn chunk_size(self, chunk_size: usize) -> Self {
+        self.config.options.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets whether the CLI asks for confirmation before cleaning.
+    pub fn require_confirmation(self, require: bool) -> Self {
+        self.config.options.require_confirmation = require;
+        self
+    }
+
+    /// Sets the planned size below which the CLI cleans without asking.
+    pub fn auto_yes_under(self, size: Option<ByteSize>) -> Self {
+        self.config.options.auto_yes_under = size;
+        self
+    }
+
+    /// Sets whether scanning stays on the file system of the root.
+    pub fn one_file_system(self, one_file_system: bool) -> Self {
+        self.config.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Only cleans items unused for at least this many days.
+    pub fn unused_for_days(self, days: Option<u64>) -> Self {
+        self.config.options.unused_for_days = days;
+        self
+    }
+
+    /// Sets whether file contents are overwritten before they are deleted.
+    pub fn secure_delete(self, secure_delete: bool) -> Self {
+        self.config.options.secure_delete = secure_delete;
+        self
+    }
+
+    /// Sets how many seconds a single deletion may take before it is abandoned.
+    pub fn item_timeout_secs(self, secs: Option<u64>) -> Self {
+        self.config.options.item_timeout_secs = secs;
+        self
+    }
+
+    /// Sets the rhai script deciding which planned items are cleaned.
+    pub fn policy_script(self, script: Option<PathBuf>) -> Self {
+        self.config.options.policy_script = script;
+        self
+    }
+}
+
+impl SafetyBuilder<'_> {
+    /// Sets whether cleaning a path inside a git repository is refused.
+    pub fn check_git_repo(self, check: bool) -> Self {
+        self.config.safety.check_git_repo = check;
+        self
+    }
+
+    /// Sets the deepest directory level below the root that is scanned.
+    pub fn max_depth(self, depth: usize) -> Self {
+        self.config.safety.max_depth = depth;
+        self
+    }
+
+    /// Sets the free space, in GB, required before cleaning.
+    pub fn min_free_space_gb(self, gb: f64) -> Self {
+        self.config.safety.min_free_space_gb = gb;
+        self
+    }
+
+    /// Sets whether matched items owned by another user are skipped.
+    pub fn only_own_files(self, only_own_files: bool) -> Self {
+        self.config.safety.only_own_files = only_own_files;
+        self
+    }
+
+    /// Sets whether directories containing excluded paths are cleaned around them.
+    pub fn partial_delete(self, partial_delete: bool) -> Self {
+        self.config.safety.partial_delete = partial_delete;
+        self
+    }
+}
+
+/// Compiles each pattern on its own, so an error can name the pattern.
+fn check_patterns(config: &Config) -> Result<()> {
+    let patterns = &config.patterns;
+    let lists = [
+        ("directories", &patterns.directories),
+        ("files", &patterns.files),
+        ("exclude", &patterns.exclude),
+    ];
+    for (list, raws) in lists {
+        for raw in raws {
+            let mut single = patterns.clone();
+            single.directories.clear();
+            single.files.clear();
+            single.exclude.clear();
+            match list {
+                "directories" => single.directories.push(raw.clone()),
+                "files" => single.files.push(raw.clone()),
+                _ => single.exclude.push(raw.clone()),
+            }
+            if let Err(err) = PatternMatcher::new(&single) {
+                return Err(McError::Config(format!(
+                    "invalid pattern `{}` in patterns.{}: {}",
+                    raw, list, err
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn out_of_range(setting: &str, value: usize, max: usize) -> McError {
+    if max == usize::MAX {
+        McError::Config(format!("{} must be at least 1, not {}", setting, value))
+    } else {
+        McError::Config(format!(
+            "{} must be between 1 and {}, not {}",
+            setting, max, value
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_applies_setters() {
+        let mut builder = Config::builder();
+        builder.patterns().clear().directory("target").file("*.log");
+        builder.safety().max_depth(6);
+        builder.options().chunk_size(10);
+        let config = builder.build().unwrap();
+
+        assert_eq!(config.patterns.directories, ["target"]);
+        assert_eq!(config.patterns.files, ["*.log"]);
+        assert!(config.patterns.exclude.is_empty());
+        assert_eq!(config.safety.max_depth, 6);
+        assert_eq!(config.options.chunk_size, 10);
+    }
+
+    #[test]
+    fn test_build_rejects_bad_patterns_and_ranges() {
+        let mut builder = Config::builder();
+        builder.patterns().directory("build[");
+        let err = builder.build().unwrap_err().to_string();
+        assert!(err.contains("`build[` in patterns.directories"), "{}", err);
+
+        let mut builder = Config::builder();
+        builder.safety().max_depth(0);
+        assert!(builder.build().is_err());
+
+        let mut builder = Config::builder();
+        builder
+            .options()
+            .parallel_threads(ParallelThreads::Count(0));
+        assert!(builder.build().is_err());
+
+        let mut builder = Config::builder();
+        builder.safety().min_free_space_gb(f64::NAN);
+        assert!(builder.build().is_err());
+    }
+}
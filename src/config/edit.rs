@@ -0,0 +1,143 @@
+//! Reading and writing single settings for `mc config get` and `mc config set`.
+//!
+//! Keys are dotted TOML keys such as `options.parallel_threads` or
+//! `patterns.priority."*.log"`. Edits go through `toml_edit`, so the comments and
+//! layout of the rest of the file are preserved.
+
+use super::{extends, Config, ConfigFormat};
+use crate::types::{McError, Result};
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Key, TableLike};
+
+/// Looks up `key` in the effective configuration.
+///
+/// # Errors
+///
+/// Returns an error if `key` is malformed or is not a setting of `config`.
+pub fn get(config: &Config, key: &str) -> Result<toml::Value> {
+    let keys = parse_key(key)?;
+    let mut value = toml::Value::try_from(config)?;
+    for part in &keys {
+        value = value
+            .as_table_mut()
+            .and_then(|table| table.remove(part.get()))
+            .ok_or_else(|| unknown_key(key))?;
+    }
+    Ok(value)
+}
+
+/// Sets `key` to `raw` in the TOML configuration file at `path`, creating the file
+/// from the defaults if it does not exist.
+///
+/// `raw` is parsed as a TOML value (`4`, `true`, `["a", "b"]`); anything that does
+/// not parse is stored as a string. The edited file must still load, and `key` must
+/// name a setting `mc` knows about, otherwise nothing is written.
+///
+/// # Errors
+///
+/// Returns an error if the file is not TOML, cannot be read or written, or if the
+/// edit does not produce a valid configuration.
+pub fn set(path: &Path, key: &str, raw: &str) -> Result<()> {
+    if ConfigFormat::from_path(path) != ConfigFormat::Toml {
+        return Err(McError::Config(format!(
+            "{} is not a TOML file; only TOML configuration can be edited",
+            path.display()
+        )));
+    }
+    let keys = parse_key(key)?;
+    let contents = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        toml::to_string_pretty(&Config::default())?
+    };
+    let mut doc: DocumentMut = contents
+        .parse()
+        .map_err(|e| McError::Config(format!("{}: {}", path.display(), e)))?;
+
+    let (last, parents) = keys.split_last().ok_or_else(|| unknown_key(key))?;
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for part in parents {
+        table = table
+            .entry_format(part)
+            .or_insert(Item::Table(toml_edit::Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| McError::Config(format!("`{}` is not a table", part.get())))?;
+    }
+    let mut value = raw
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| raw.into());
+    match table.get_mut(last.get()) {
+        Some(item) => {
+            if let Some(old) = item.as_value() {
+                *value.decor_mut() = old.decor().clone();
+            }
+            *item = Item::Value(value);
+        }
+        None => {
+            value.decor_mut().clear();
+            table.insert(last.get(), Item::Value(value));
+        }
+    }
+
+    let edited = doc.to_string();
+    let table = ConfigFormat::Toml.parse(&edited, path)?;
+    let config: Config = toml::Value::Table(extends::layer(table, path)?).try_into()?;
+    get(&config, key)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, edited)?;
+    Ok(())
+}
+
+fn parse_key(key: &str) -> Result<Vec<Key>> {
+    Key::parse(key).map_err(|e| McError::Config(format!("invalid key `{}`: {}", key, e)))
+}
+
+fn unknown_key(key: &str) -> McError {
+    McError::Config(format!("unknown setting `{}`", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_set_preserves_comments() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child(".mc.toml");
+        let original = toml::to_string_pretty(&Config::default())
+            .unwrap()
+            .replace("[options]\n", "[options]\n# tuned for CI\n");
+        file.write_str(&original).unwrap();
+
+        set(file.path(), "options.parallel_threads", "3").unwrap();
+        set(file.path(), "patterns.priority.\"*.log\"", "350").unwrap();
+
+        let edited = fs::read_to_string(file.path()).unwrap();
+        assert!(edited.contains("# tuned for CI\n"));
+        let config: Config = toml::from_str(&edited).unwrap();
+        assert_eq!(config.options.parallel_threads, 3);
+        assert_eq!(config.patterns.priority["*.log"], 350);
+        assert_eq!(
+            get(&config, "options.parallel_threads")
+                .unwrap()
+                .as_integer(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_set_rejects_unknown_and_invalid_settings() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child(".mc.toml");
+
+        assert!(set(file.path(), "options.paralel_threads", "3").is_err());
+        assert!(set(file.path(), "safety.max_depth", "deep").is_err());
+        file.assert(predicates::path::missing());
+    }
+}
@@ -0,0 +1,251 @@
+//! Layering of configuration files through the `extends` directive.
+//!
+//! A file may list base files to inherit from:
+//!
+//! ```toml
+//! extends = ["~/.config/mc/base.toml", "./ci-overrides.toml"]
+//! ```
+//!
+//! Bases are merged in the order listed, then the extending file on top of them:
+//! tables merge key by key, lists append (skipping entries already present), and
+//! any other value overrides the one inherited. Relative paths are resolved from
+//! the directory of the file that names them, and `~/` from the home directory.
+//! Each file may be in any supported [`ConfigFormat`].
+
+use super::ConfigFormat;
+use crate::types::{McError, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// The key naming the files a configuration extends.
+const EXTENDS_KEY: &str = "extends";
+
+/// A record of the files a layered configuration was read from.
+#[derive(Debug, Default)]
+pub(crate) struct Trace {
+    /// Every file read, each followed by the bases it extends, with the file that
+    /// extends it.
+    pub(crate) files: Vec<(PathBuf, Option<PathBuf>)>,
+    /// The files that set each setting, keyed by dotted key. Lists name every file
+    /// that contributed entries.
+    pub(crate) origins: BTreeMap<String, Vec<PathBuf>>,
+    /// The file that first listed each string entry of a list, keyed by dotted key and
+    /// then by entry. Bases are read first, so an entry a base lists belongs to it.
+    pub(crate) entries: BTreeMap<String, BTreeMap<String, PathBuf>>,
+}
+
+impl Trace {
+    /// Records `file` as the origin of every setting in `table`, under `prefix`.
+    fn record(&mut self, table: &Table, file: &Path, prefix: &str) {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, toml_edit::Key::new(key.as_str()));
+            match value {
+                Value::Table(table) => self.record(table, file, &format!("{}.", key)),
+                Value::Array(values) => {
+                    let entries = self.entries.entry(key.clone()).or_default();
+                    for entry in values.iter().filter_map(Value::as_str) {
+                        entries
+                            .entry(entry.to_string())
+                            .or_insert_with(|| file.to_path_buf());
+                    }
+                    let files = self.origins.entry(key).or_default();
+                    if !files.iter().any(|f| f == file) {
+                        files.push(file.to_path_buf());
+                    }
+                }
+                _ => {
+                    self.origins.insert(key, vec![file.to_path_buf()]);
+                }
+            }
+        }
+    }
+}
+
+/// Reads the configuration file at `path` with everything it extends merged in,
+/// recording where each setting came from in `trace`.
+///
+/// # Errors
+///
+/// Returns an error if any file in the chain cannot be read or parsed, if an
+/// `extends` entry is not a path, or if files extend each other in a cycle.
+pub(crate) fn load_traced(path: &Path, trace: &mut Trace) -> Result<Table> {
+    load_with_chain(path, &mut Vec::new(), trace)
+}
+
+/// Merges everything `table` extends under it, resolving relative paths as if it
+/// had been read from `path`.
+///
+/// # Errors
+///
+/// Returns the same errors as [`load_traced`].
+pub(crate) fn layer(table: Table, path: &Path) -> Result<Table> {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    apply_extends(table, canonical, &mut Vec::new(), &mut Trace::default())
+}
+
+/// Loads `path`, with `chain` holding the files currently being loaded, outermost first.
+fn load_with_chain(path: &Path, chain: &mut Vec<PathBuf>, trace: &mut Trace) -> Result<Table> {
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| McError::Config(format!("cannot read config {}: {}", path.display(), e)))?;
+    if chain.contains(&canonical) {
+        let cycle: Vec<String> = chain
+            .iter()
+            .skip_while(|p| **p != canonical)
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(McError::Config(format!(
+            "`extends` cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    log::debug!("Loading config layer: {}", canonical.display());
+    let contents = fs::read_to_string(&canonical)?;
+    let table = ConfigFormat::from_path(&canonical).parse(&contents, &canonical)?;
+    trace.files.push((canonical.clone(), chain.last().cloned()));
+    apply_extends(table, canonical, chain, trace)
+}
+
+/// Merges the bases `table` extends under it; `canonical` is the file it was read from.
+fn apply_extends(
+    mut table: Table,
+    canonical: PathBuf,
+    chain: &mut Vec<PathBuf>,
+    trace: &mut Trace,
+) -> Result<Table> {
+    let Some(extends) = table.remove(EXTENDS_KEY) else {
+        trace.record(&table, &canonical, "");
+        return Ok(table);
+    };
+    let bases = base_paths(&extends, &canonical)?;
+
+    chain.push(canonical.clone());
+    let mut merged = Table::new();
+    for base in &bases {
+        merge(&mut merged, load_with_chain(base, chain, trace)?);
+    }
+    chain.pop();
+
+    trace.record(&table, &canonical, "");
+    trace
+        .origins
+        .insert(EXTENDS_KEY.to_string(), vec![canonical]);
+    merge(&mut merged, table);
+    let extends = match extends {
+        Value::String(entry) => Value::Array(vec![Value::String(entry)]),
+        extends => extends,
+    };
+    merged.insert(EXTENDS_KEY.to_string(), extends);
+    Ok(merged)
+}
+
+/// Resolves the `extends` entries of the file at `file`.
+fn base_paths(extends: &Value, file: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match extends {
+        Value::String(entry) => vec![entry.as_str()],
+        Value::Array(entries) => entries
+            .iter()
+            .map(|entry| {
+                entry.as_str().ok_or_else(|| {
+                    McError::Config(format!(
+                        "`extends` in {} must list paths, found {}",
+                        file.display(),
+                        entry
+                    ))
+                })
+            })
+            .collect::<Result<_>>()?,
+        other => {
+            return Err(McError::Config(format!(
+                "`extends` in {} must be a path or a list of paths, found {}",
+                file.display(),
+                other
+            )))
+        }
+    };
+    let dir = file.parent().unwrap_or(Path::new("."));
+    Ok(entries
+        .into_iter()
+        .map(|entry| resolve(entry, dir))
+        .collect())
+}
+
+/// Resolves an `extends` entry relative to `dir`, expanding a leading `~/`.
+fn resolve(entry: &str, dir: &Path) -> PathBuf {
+    if let Some(rest) = entry.strip_prefix("~/") {
+        if let Some(dirs) = directories::BaseDirs::new() {
+            return dirs.home_dir().join(rest);
+        }
+    }
+    dir.join(entry)
+}
+
+/// Merges `overlay` into `base`: tables recursively, lists by appending new
+/// entries, and everything else by replacing.
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            (Some(Value::Array(base)), Value::Array(overlay)) => {
+                for entry in overlay {
+                    if !base.contains(&entry) {
+                        base.push(entry);
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_extends_appends_lists_and_overrides_scalars() {
+        let temp = TempDir::new().unwrap();
+        temp.child("base.toml")
+            .write_str(
+                "[patterns]\ndirectories = [\"target\"]\n\
+                 [options]\nparallel_threads = 2\nshow_statistics = true\n",
+            )
+            .unwrap();
+        let child = temp.child("repo/.mc.toml");
+        child
+            .write_str(
+                "extends = [\"../base.toml\"]\n\
+                 [patterns]\ndirectories = [\"target\", \"dist\"]\n\
+                 [options]\nparallel_threads = 8\n",
+            )
+            .unwrap();
+
+        let table = load_traced(child.path(), &mut Trace::default()).unwrap();
+
+        let dirs = table["patterns"]["directories"].as_array().unwrap();
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(table["options"]["parallel_threads"].as_integer(), Some(8));
+        assert_eq!(table["options"]["show_statistics"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_extends_cycle_is_an_error() {
+        let temp = TempDir::new().unwrap();
+        temp.child("a.toml")
+            .write_str("extends = \"b.toml\"\n")
+            .unwrap();
+        temp.child("b.toml")
+            .write_str("extends = [\"a.toml\"]\n")
+            .unwrap();
+
+        let err = load_traced(temp.child("a.toml").path(), &mut Trace::default()).unwrap_err();
+        assert!(err.to_string().contains("cycle"), "{}", err);
+    }
+}
@@ -0,0 +1,145 @@
+//! Configuration file formats.
+//!
+//! TOML, YAML and JSON files share the same serde model; the format is chosen by
+//! the file extension, with TOML as the fallback. TOML has no null, so a `null` in a
+//! YAML or JSON file leaves its setting unset.
+
+use crate::types::{McError, Result};
+use serde::Serialize;
+use std::path::Path;
+use toml::Table;
+
+/// The names searched for a project configuration file, in order of preference.
+pub const PROJECT_FILE_NAMES: [&str; 4] = [".mc.toml", ".mc.yaml", ".mc.yml", ".mc.json"];
+
+/// The names searched for the global configuration file, in order of preference.
+pub const GLOBAL_FILE_NAMES: [&str; 4] =
+    ["config.toml", "config.yaml", "config.yml", "config.json"];
+
+/// A supported configuration file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// TOML (`.toml`, and any unrecognized extension).
+    Toml,
+    /// YAML (`.yaml` or `.yml`).
+    Yaml,
+    /// JSON (`.json`).
+    Json,
+}
+
+impl ConfigFormat {
+    /// Detects the format of a configuration file from its extension.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// Parses the contents of the file at `path` into a table.
+    pub(crate) fn parse(self, contents: &str, path: &Path) -> Result<Table> {
+        let parse_error =
+            |e: &dyn std::fmt::Display| McError::Config(format!("{}: {}", path.display(), e));
+        let value: serde_json::Value = match self {
+            ConfigFormat::Toml => return Ok(contents.parse()?),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| parse_error(&e))?,
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| parse_error(&e))?,
+        };
+        serde_json::from_value(strip_nulls(value)).map_err(|e| parse_error(&e))
+    }
+
+    /// Renders `value` in this format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` cannot be represented in this format.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        match self {
+            ConfigFormat::Toml => Ok(toml::to_string_pretty(value)?),
+            ConfigFormat::Yaml => {
+                serde_yaml::to_string(value).map_err(|e| McError::Config(e.to_string()))
+            }
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+        }
+    }
+}
+
+/// Removes `null` values, and the keys holding them, so they read as unset.
+fn strip_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key, strip_nulls(value)))
+            .collect(),
+        serde_json::Value::Array(values) => values
+            .into_iter()
+            .filter(|value| !value.is_null())
+            .map(strip_nulls)
+            .collect(),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_formats_share_the_model() {
+        let toml = "[patterns]\ndirectories = [\"target\"]\nfiles = []\nexclude = []\n\
+                    [options]\nparallel_threads = 4\n[safety]\n";
+        let yaml = "patterns:\n  directories: [target]\n  files: []\n  exclude: []\n\
+                    options:\n  parallel_threads: 4\nsafety: {}\n";
+        let json = r#"{"patterns": {"directories": ["target"], "files": [], "exclude": []},
+                       "options": {"parallel_threads": 4}, "safety": {}}"#;
+
+        for (name, contents) in [("a.toml", toml), ("a.yaml", yaml), ("a.json", json)] {
+            let path = Path::new(name);
+            let table = ConfigFormat::from_path(path).parse(contents, path).unwrap();
+            let config: Config = toml::Value::Table(table).try_into().unwrap();
+            assert_eq!(config.patterns.directories, vec!["target"], "{}", name);
+            assert_eq!(config.options.parallel_threads, 4, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_null_reads_as_unset() {
+        let yaml = "patterns:\n  directories: [target, ~]\n  files: []\n  exclude: []\n\
+                    options:\n  parallel_threads: ~\n  size_mode: null\nsafety: {}\n";
+        let json = r#"{"patterns": {"directories": ["target"], "files": [], "exclude": []},
+                       "options": {"parallel_threads": null}, "safety": {}, "alerts": null}"#;
+        let default = Config::default();
+
+        for (name, contents) in [("a.yaml", yaml), ("a.json", json)] {
+            let path = Path::new(name);
+            let table = ConfigFormat::from_path(path).parse(contents, path).unwrap();
+            let config: Config = toml::Value::Table(table).try_into().unwrap();
+            assert_eq!(
+                config.options.parallel_threads, default.options.parallel_threads,
+                "{}",
+                name
+            );
+            assert_eq!(config.patterns.directories, vec!["target"], "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_render_round_trips() {
+        let config = Config::default();
+        for name in ["a.toml", "a.yaml", "a.json"] {
+            let path = Path::new(name);
+            let format = ConfigFormat::from_path(path);
+            let rendered = format.render(&config).unwrap();
+            let table = format.parse(&rendered, path).unwrap();
+            let parsed: Config = toml::Value::Table(table).try_into().unwrap();
+            assert_eq!(
+                parsed.patterns.directories, config.patterns.directories,
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Translating the settings of other cleaners into mc configuration, for `mc import`.
+//!
+//! kondo, cargo-sweep and npkill are configured on their command lines, so their
+//! settings are read from the commands that run them, such as crontab entries or
+//! shell aliases. Each option with an mc equivalent becomes that setting:
+//!
+//! | Tool        | Option                  | Setting                                       |
+//! |-------------|-------------------------|-----------------------------------------------|
+//! | kondo       | `-I, --ignored-dirs`    | `patterns.exclude`                            |
+//! | kondo       | `-o, --older`           | `options.unused_for_days`                     |
+//! | kondo       | `-s, --same-filesystem` | `options.one_file_system`                     |
+//! | kondo       | `-L, --follow-symlinks` | `options.symlinks = "follow"`                 |
+//! | cargo-sweep | `-t, --time`            | `rules.cargo.mode = "sweep"`, `max_age_days`  |
+//! | npkill      | `-x, --exclude`         | `patterns.exclude`                            |
+//! | npkill      | `-t, --target`          | `patterns.directories`, no `patterns.files`   |
+//!
+//! Options that only affect a single run, such as output or prompting, are ignored.
+//! Other options without an equivalent are reported as notes.
+
+use super::{edit, Config};
+use crate::types::{McError, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A cleaner whose settings can be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// [kondo](https://github.com/tbillington/kondo).
+    Kondo,
+    /// [cargo-sweep](https://github.com/holmgr/cargo-sweep).
+    CargoSweep,
+    /// [npkill](https://github.com/voidcosmos/npkill).
+    Npkill,
+}
+
+impl ImportFormat {
+    /// Returns the arguments of the tool's command in `words`, if they run the tool.
+    fn arguments(self, words: &[String]) -> Option<&[String]> {
+        let program = |word: &String| {
+            let name = Path::new(word).file_name().and_then(|name| name.to_str());
+            name.unwrap_or(word).to_string()
+        };
+        let position = match self {
+            ImportFormat::Kondo => words.iter().position(|word| program(word) == "kondo")?,
+            ImportFormat::Npkill => words.iter().position(|word| program(word) == "npkill")?,
+            // `cargo sweep`, or the binary run directly as `cargo-sweep sweep`.
+            ImportFormat::CargoSweep => {
+                words.windows(2).position(|pair| {
+                    matches!(program(&pair[0]).as_str(), "cargo" | "cargo-sweep")
+                        && pair[1] == "sweep"
+                })? + 1
+            }
+        };
+        Some(&words[position + 1..])
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = McError;
+
+    fn from_str(tool: &str) -> Result<Self> {
+        match tool {
+            "kondo" => Ok(ImportFormat::Kondo),
+            "cargo-sweep" => Ok(ImportFormat::CargoSweep),
+            "npkill" => Ok(ImportFormat::Npkill),
+            _ => Err(McError::InvalidArgument(format!(
+                "cannot import from `{}`; expected kondo, cargo-sweep or npkill",
+                tool
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ImportFormat::Kondo => "kondo",
+            ImportFormat::CargoSweep => "cargo-sweep",
+            ImportFormat::Npkill => "npkill",
+        })
+    }
+}
+
+/// The mc settings translated from another cleaner.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Import {
+    /// Settings to set, by dotted key.
+    pub settings: BTreeMap<String, toml::Value>,
+    /// Entries to add to `patterns.exclude`.
+    pub excludes: Vec<String>,
+    /// Options that were not imported, and why.
+    pub notes: Vec<String>,
+}
+
+impl Import {
+    /// Translates the commands running the tool in `script`, one per line, such as a
+    /// crontab. Lines that do not run the tool are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no line runs the tool.
+    pub fn from_script(format: ImportFormat, script: &str) -> Result<Self> {
+        let mut import = Self::default();
+        let mut found = false;
+        for line in script.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Ok(words) = shell_words::split(line) else {
+                import
+                    .notes
+                    .push(format!("skipped a line that is not valid shell: {}", line));
+                continue;
+            };
+            if let Some(args) = format.arguments(&words) {
+                import.add_command(format, args);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(McError::InvalidArgument(format!(
+                "found no {} command to import",
+                format
+            )));
+        }
+        Ok(import)
+    }
+
+    /// Translates one command line of the tool. The tool itself may be left out.
+    pub fn from_args(format: ImportFormat, args: &[String]) -> Self {
+        let mut import = Self::default();
+        import.add_command(format, format.arguments(args).unwrap_or(args));
+        import
+    }
+
+    /// Returns whether nothing was translated.
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty() && self.excludes.is_empty()
+    }
+
+    /// Writes the settings to the TOML configuration file at `path`, creating it from
+    /// the defaults if it does not exist. Excludes are added to those of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or written, or a setting is invalid.
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        for (key, value) in &self.settings {
+            edit::set(path, key, &value.to_string())?;
+        }
+        if !self.excludes.is_empty() {
+            let mut excludes = if path.exists() {
+                Config::load(Some(&path.to_path_buf()))?.patterns.exclude
+            } else {
+                Config::default().patterns.exclude
+            };
+            for exclude in &self.excludes {
+                if !excludes.contains(exclude) {
+                    excludes.push(exclude.clone());
+                }
+            }
+            edit::set(
+                path,
+                "patterns.exclude",
+                &toml::Value::from(excludes).to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn add_command(&mut self, format: ImportFormat, args: &[String]) {
+        // The command ends where the shell starts another.
+        let mut args = args
+            .iter()
+            .map(String::as_str)
+            .take_while(|arg| !matches!(*arg, "&&" | "||" | ";" | "|"))
+            .peekable();
+        while let Some(arg) = args.next() {
+            let (option, mut inline) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+                _ => (arg, None),
+            };
+            if !option.starts_with('-') {
+                continue; // The directories to clean.
+            }
+            let mut value = || {
+                inline
+                    .take()
+                    .or_else(|| args.next_if(|arg| !arg.starts_with('-')))
+            };
+            match (format, option) {
+                (ImportFormat::Kondo, "-I" | "--ignored-dirs") => {
+                    while let Some(dir) = value() {
+                        self.excludes.push(dir.trim_end_matches('/').to_string());
+                    }
+                }
+                (ImportFormat::Kondo, "-o" | "--older") => match value().map(kondo_age_days) {
+                    Some(Some(days)) => self.set("options.unused_for_days", days),
+                    _ => self.note(format, option, "its age was not understood"),
+                },
+                (ImportFormat::Kondo, "-s" | "--same-filesystem") => {
+                    self.set("options.one_file_system", true);
+                }
+                (ImportFormat::Kondo, "-L" | "--follow-symlinks") => {
+                    self.set("options.symlinks", "follow");
+                }
+                (ImportFormat::Kondo, "-a" | "--all" | "-q" | "--quiet") => {}
+                (ImportFormat::CargoSweep, "-t" | "--time") => {
+                    match value().and_then(|days| days.parse::<u32>().ok()) {
+                        Some(days) => {
+                            self.set("rules.cargo.mode", "sweep");
+                            self.set("rules.cargo.max_age_days", days);
+                        }
+                        None => self.note(format, option, "its number of days was not understood"),
+                    }
+                }
+                (ImportFormat::CargoSweep, "-i" | "--installed" | "--toolchains" | "--maxsize") => {
+                    value();
+                    self.note(format, option, "mc only sweeps artifacts by age");
+                }
+                (
+                    ImportFormat::CargoSweep,
+                    "-r" | "--recursive" | "-d" | "--dry-run" | "-v" | "--verbose",
+                ) => {}
+                (ImportFormat::Npkill, "-x" | "--exclude") => {
+                    self.excludes.extend(comma_list(value()));
+                }
+                (ImportFormat::Npkill, "-t" | "--target") => {
+                    let targets: Vec<String> = comma_list(value()).collect();
+                    self.set("patterns.directories", targets);
+                    self.set("patterns.files", Vec::<String>::new());
+                }
+                (ImportFormat::Npkill, "-d" | "--directory" | "-s" | "--sort" | "-c") => {
+                    value();
+                }
+                (ImportFormat::Npkill, "-D" | "--delete-all" | "-y" | "--dry-run" | "-f") => {}
+                _ => self.note(format, option, "it has no mc equivalent"),
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: impl Into<toml::Value>) {
+        self.settings.insert(key.to_string(), value.into());
+    }
+
+    fn note(&mut self, format: ImportFormat, option: &str, reason: &str) {
+        self.notes.push(format!(
+            "ignored {} option `{}`: {}",
+            format, option, reason
+        ));
+    }
+}
+
+/// Splits an npkill list such as `node_modules,.next` into its entries.
+fn comma_list(list: Option<&str>) -> impl Iterator<Item = String> + '_ {
+    list.into_iter()
+        .flat_map(|list| list.split(','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+}
+
+/// Converts a kondo age such as `3M` into whole days, rounding up. kondo's units are
+/// `m` minutes, `h` hours, `d` days, `w` weeks, `M` months and `y` years.
+fn kondo_age_days(age: &str) -> Option<u32> {
+    let unit = age.chars().last()?;
+    let number: u64 = age[..age.len() - unit.len_utf8()].parse().ok()?;
+    let minutes = match unit {
+        'm' => 1,
+        'h' => 60,
+        'd' => 60 * 24,
+        'w' => 60 * 24 * 7,
+        'M' => 60 * 24 * 30,
+        'y' => 60 * 24 * 365,
+        _ => return None,
+    };
+    u32::try_from(number.checked_mul(minutes)?.div_ceil(60 * 24)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_import_from_crontab() {
+        let crontab = "\
+            # nightly cleanup\n\
+            0 3 * * * /usr/local/bin/kondo -a -o 3M -I vendor third_party/ --bogus ~/code\n\
+            0 4 * * * cargo sweep -r -t 14 ~/code && echo done\n";
+
+        let kondo = Import::from_script(ImportFormat::Kondo, crontab).unwrap();
+        assert_eq!(
+            kondo.settings["options.unused_for_days"],
+            toml::Value::Integer(90)
+        );
+        assert_eq!(kondo.excludes, ["vendor", "third_party"]);
+        assert_eq!(kondo.notes.len(), 1);
+
+        let sweep = Import::from_script(ImportFormat::CargoSweep, crontab).unwrap();
+        assert_eq!(
+            sweep.settings["rules.cargo.mode"],
+            toml::Value::from("sweep")
+        );
+        assert_eq!(
+            sweep.settings["rules.cargo.max_age_days"],
+            toml::Value::Integer(14)
+        );
+
+        assert!(Import::from_script(ImportFormat::Npkill, crontab).is_err());
+    }
+
+    #[test]
+    fn test_import_writes_config() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child(".mc.toml");
+        let args: Vec<String> = ["--target", "node_modules,.next", "-x", "legacy"]
+            .map(String::from)
+            .to_vec();
+
+        let import = Import::from_args(ImportFormat::Npkill, &args);
+        import.write_to(file.path()).unwrap();
+
+        let config = Config::load(Some(&file.path().to_path_buf())).unwrap();
+        assert_eq!(config.patterns.directories, ["node_modules", ".next"]);
+        assert!(config.patterns.files.is_empty());
+        assert!(config.patterns.exclude.contains(&"legacy".to_string()));
+        assert!(config.patterns.exclude.len() > 1);
+    }
+}
@@ -3,33 +3,84 @@
 //! It defines the structure of the `.mc.toml` configuration file and provides
 //! functionality for loading, parsing, and merging configurations from files
 //! and command-line arguments. The configuration is deserialized using `serde`
-//! and `toml`.
+//! from TOML, YAML (`.mc.yaml`) or JSON (`.mc.json`).
+
+mod builder;
+pub mod edit;
+mod extends;
+mod format;
+pub mod import;
+pub mod provenance;
+
+pub use builder::{ConfigBuilder, OptionsBuilder, PatternsBuilder, SafetyBuilder};
+pub use format::{ConfigFormat, GLOBAL_FILE_NAMES, PROJECT_FILE_NAMES};
+pub use provenance::Provenance;
 
 use crate::patterns::BUILTIN_PATTERNS;
-use crate::types::Result;
+use crate::types::{AutoThreads, PatternCategory, PatternSource, Result};
 use directories::ProjectDirs;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 /// The main configuration structure for `mc`.
 ///
 /// This struct aggregates all configuration settings, including patterns for matching,
 /// general options, and safety guardrails. It is designed to be deserialized
 /// from a TOML file.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+#[non_exhaustive]
 pub struct Config {
+    /// Configuration files this one is layered on; see [`Config::load`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<String>,
     /// Configuration for file and directory matching patterns.
     pub patterns: PatternConfig,
     /// Configuration for operational options, like parallelism and confirmations.
     pub options: OptionsConfig,
     /// Configuration for safety checks, like git repository detection.
     pub safety: SafetyConfig,
+    /// Configuration for reclaimable-space alerts raised by `mc check` and `mc watch`.
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    /// Commands to run before and after cleaning.
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// Whether cleaned items go to the trash instead of being deleted.
+    #[serde(default)]
+    pub trash: TrashConfig,
+    /// Rules that handle matched items with a command instead of deleting them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<ActionRule>,
+    /// Rules that keep the newest versioned children of a matched directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retention: Vec<RetentionRule>,
+    /// Ecosystem-specific cleaning rules.
+    #[serde(default)]
+    pub rules: RulesConfig,
+    /// Where matched items are archived before they are deleted, if anywhere.
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    /// What `mc serve --http` may clean and how clients authenticate.
+    #[serde(default)]
+    pub server: ServerConfig,
+    /// Which WASM plugins are loaded, with the `plugins` feature.
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+    /// Where cleaned items are moved instead of being deleted, if anywhere.
+    #[serde(default)]
+    pub quarantine: QuarantineConfig,
 }
 
 /// Defines the patterns used for matching items to be cleaned.
 /// These are interpreted as glob patterns.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[non_exhaustive]
 pub struct PatternConfig {
     /// A list of glob patterns for matching directories to be cleaned.
     pub directories: Vec<String>,
@@ -37,14 +88,128 @@ pub struct PatternConfig {
     pub files: Vec<String>,
     /// A list of glob patterns for excluding items from being cleaned.
     pub exclude: Vec<String>,
+    /// The deepest walk depth (the scan root is 0) at which a pattern may match,
+    /// keyed by pattern.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_depth: BTreeMap<String, usize>,
+    /// Explicit match priorities, keyed by pattern. When several patterns match a
+    /// path, the one with the highest priority wins; see
+    /// [`PatternSource::default_priority`] for the defaults.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub priority: BTreeMap<String, u32>,
+    /// Paths to keep inside the items a pattern matches, keyed by pattern. Entries are
+    /// globs relative to the matched directory; the rest of its contents is deleted.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scoped_exclude: BTreeMap<String, Vec<String>>,
+    /// Notes on what getting deleted items back takes, such as `npm ci ~2min`, keyed by
+    /// pattern or by category name (`Dependencies`, `BuildOutputs`, ...). A pattern's
+    /// own note wins over its category's.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rebuild: BTreeMap<String, String>,
+    /// Where each pattern came from, recorded by [`Config::load`] and
+    /// [`Config::merge_cli_args`]. Patterns without an entry are built-in.
+    #[serde(skip)]
+    pub sources: BTreeMap<String, PatternSource>,
+}
+
+/// Which kinds of items a command-line include pattern matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncludeKind {
+    /// Only directories (`dir:` prefix or `--include-dir`).
+    Dir,
+    /// Only files (`file:` prefix or `--include-file`).
+    File,
+    /// Both, for patterns without a prefix or trailing `/`.
+    Both,
+}
+
+impl IncludeKind {
+    /// Splits an optional `dir:` or `file:` prefix off `pattern`. Unprefixed patterns
+    /// with a trailing `/` only match directories, as in .gitignore.
+    pub fn parse(pattern: &str) -> (Self, &str) {
+        if let Some(rest) = pattern.strip_prefix("dir:") {
+            (IncludeKind::Dir, rest)
+        } else if let Some(rest) = pattern.strip_prefix("file:") {
+            (IncludeKind::File, rest)
+        } else if pattern.len() > 1 && pattern.ends_with('/') {
+            (IncludeKind::Dir, pattern)
+        } else {
+            (IncludeKind::Both, pattern)
+        }
+    }
+
+    /// Returns true if patterns of this kind match directories.
+    pub fn dirs(self) -> bool {
+        self != IncludeKind::File
+    }
+
+    /// Returns true if patterns of this kind match files.
+    pub fn files(self) -> bool {
+        self != IncludeKind::Dir
+    }
+}
+
+impl PatternConfig {
+    /// Returns where `pattern` came from.
+    pub fn source_of(&self, pattern: &str) -> PatternSource {
+        self.sources
+            .get(pattern)
+            .copied()
+            .unwrap_or(PatternSource::BuiltIn)
+    }
+
+    /// Returns the match priority of `pattern`: its explicit priority if set, otherwise
+    /// the default for its source.
+    pub fn priority_of(&self, pattern: &str) -> u32 {
+        self.priority
+            .get(pattern)
+            .copied()
+            .unwrap_or_else(|| self.source_of(pattern).default_priority())
+    }
+
+    /// Records where each include pattern came from: `source`, the source of the file
+    /// loaded, or [`PatternSource::Global`] for a pattern that `trace` shows was first
+    /// listed by the `global` configuration file, which the loaded file extends.
+    fn mark_sources(
+        &mut self,
+        source: PatternSource,
+        trace: &extends::Trace,
+        global: Option<&Path>,
+    ) {
+        for (key, patterns) in [
+            ("patterns.directories", &self.directories),
+            ("patterns.files", &self.files),
+        ] {
+            let files = trace.entries.get(key);
+            for pattern in patterns {
+                let file = files.and_then(|files| files.get(pattern));
+                let source = match (file, global) {
+                    (Some(file), Some(global)) if file == global => PatternSource::Global,
+                    _ => source,
+                };
+                self.sources.insert(pattern.clone(), source);
+            }
+        }
+    }
 }
 
 /// Defines operational options for the cleaner.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[non_exhaustive]
 pub struct OptionsConfig {
-    /// The number of parallel threads to use for cleaning. Defaults to the number of CPU cores.
+    /// The number of parallel threads to use for cleaning, or `"auto"` to pick one for
+    /// the device being cleaned. Defaults to the number of CPU cores.
     #[serde(default = "default_parallel_threads")]
-    pub parallel_threads: usize,
+    pub parallel_threads: ParallelThreads,
+
+    /// How deletions are scheduled across threads. Defaults to `work_stealing`.
+    #[serde(default)]
+    pub scheduling: Scheduling,
+
+    /// The number of items a thread takes at once with `chunked` scheduling.
+    /// Defaults to 100.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
 
     /// Whether to require user confirmation before cleaning. Defaults to `true`.
     #[serde(default = "default_true")]
@@ -54,14 +219,385 @@ pub struct OptionsConfig {
     #[serde(default = "default_true")]
     pub show_statistics: bool,
 
-    /// Whether to preserve symbolic links. Defaults to `true`.
+    /// Legacy symlink switch, used when `symlinks` is unset: `true` maps to
+    /// `delete_link` and `false` to `follow`. Defaults to `true`.
     #[serde(default = "default_true")]
     pub preserve_symlinks: bool,
+
+    /// How symbolic links are treated during scanning and cleaning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlinks: Option<SymlinkPolicy>,
+
+    /// Per-pattern symlink policies for matched links, keyed by pattern.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub symlink_overrides: BTreeMap<String, SymlinkPolicy>,
+
+    /// Whether to count the entries to scan in the background so scan progress can show
+    /// a total. It costs a second walk of the tree, so it defaults to `false`; the count
+    /// is cancelled when the scan finishes.
+    #[serde(default)]
+    pub precount: bool,
+
+    /// Whether scanning stays on the file system of the scan root instead of
+    /// descending into other mounts. Defaults to `false`.
+    #[serde(default)]
+    pub one_file_system: bool,
+
+    /// Only cleans items that have not been used for at least this many days, judged
+    /// by access and modification times. Items whose last use is unknown are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unused_for_days: Option<u64>,
+
+    /// Names of directories scanning never descends into, such as VCS internals.
+    /// Their contents are neither matched nor counted.
+    #[serde(default = "default_skip_traversal")]
+    pub skip_traversal: Vec<String>,
+
+    /// Whether file contents are overwritten with one pass of random bytes before
+    /// they are deleted. This does not erase old data on SSDs or copy-on-write file
+    /// systems. Defaults to `false`.
+    #[serde(default)]
+    pub secure_delete: bool,
+
+    /// Cleans without asking for confirmation when the planned total is below this
+    /// size, such as `"1GB"`. Larger cleans are still confirmed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_yes_under: Option<ByteSize>,
+
+    /// Abandons the deletion of any single item that takes longer than this many
+    /// seconds, such as one stuck on a stale network mount, and moves on. Unset waits
+    /// for every deletion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_timeout_secs: Option<u64>,
+
+    /// A rhai script whose `policy(item)` function decides whether each planned item is
+    /// deleted, kept or deferred (see `mc::engine::policy`). Needs mc built with the
+    /// `scripting` feature; otherwise every item is kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_script: Option<PathBuf>,
+
+    /// What mc does when deleting a file takes long enough to suggest on-access
+    /// antivirus scanning, as with Windows Defender. Defaults to `warn`, which only
+    /// times deletes on Windows.
+    #[serde(default)]
+    pub slow_deletes: SlowDeletePolicy,
+
+    /// How many of the largest deleted, or planned, items the report lists. Defaults
+    /// to 5; 0 lists none.
+    #[serde(default = "default_largest_items")]
+    pub largest_items: usize,
+
+    /// How a dry run sizes matched directories. Defaults to `exact`; `fast` estimates
+    /// them from a sample instead of walking them.
+    #[serde(default)]
+    pub size_mode: SizeMode,
+
+    /// Whether sizes are shown in binary units (KiB, MiB, GiB), as with `--binary`,
+    /// rather than decimal ones. Defaults to `false`; `--si` overrides it.
+    #[serde(default)]
+    pub binary_units: bool,
+}
+
+impl OptionsConfig {
+    /// Returns the per-item deletion timeout, if one is set.
+    pub fn item_timeout(&self) -> Option<std::time::Duration> {
+        self.item_timeout_secs.map(std::time::Duration::from_secs)
+    }
+
+    /// Returns the effective symlink policy, falling back to `preserve_symlinks`.
+    pub fn symlink_policy(&self) -> SymlinkPolicy {
+        self.symlinks.unwrap_or(if self.preserve_symlinks {
+            SymlinkPolicy::DeleteLink
+        } else {
+            SymlinkPolicy::Follow
+        })
+    }
+}
+
+/// How many worker threads clean in parallel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParallelThreads {
+    /// Picks a count from the kind of device being cleaned: one worker per core on
+    /// SSDs, two on spinning disks and more than the cores on network shares.
+    Auto,
+    /// Uses this many threads.
+    Count(usize),
+}
+
+impl ParallelThreads {
+    /// Returns the thread count to clean `root` with, along with the decision when the
+    /// count was picked automatically.
+    pub fn resolve(self, root: &Path) -> (usize, Option<AutoThreads>) {
+        match self {
+            ParallelThreads::Count(threads) => (threads, None),
+            ParallelThreads::Auto => {
+                let decision = crate::utils::device::auto_threads(root);
+                log::info!(
+                    "Using {} threads for {} ({})",
+                    decision.threads,
+                    root.display(),
+                    decision.device
+                );
+                (decision.threads, Some(decision))
+            }
+        }
+    }
+}
+
+impl PartialEq<usize> for ParallelThreads {
+    fn eq(&self, other: &usize) -> bool {
+        *self == ParallelThreads::Count(*other)
+    }
+}
+
+impl fmt::Display for ParallelThreads {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParallelThreads::Auto => f.write_str("auto"),
+            ParallelThreads::Count(threads) => write!(f, "{}", threads),
+        }
+    }
+}
+
+impl FromStr for ParallelThreads {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(ParallelThreads::Auto);
+        }
+        s.parse()
+            .map(ParallelThreads::Count)
+            .map_err(|_| format!("expected a thread count or `auto`, got `{}`", s))
+    }
+}
+
+impl Serialize for ParallelThreads {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            ParallelThreads::Auto => serializer.serialize_str("auto"),
+            ParallelThreads::Count(threads) => serializer.serialize_u64(*threads as u64),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ParallelThreads {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Count(usize),
+            Name(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Count(threads) => Ok(ParallelThreads::Count(threads)),
+            Raw::Name(name) => name.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl JsonSchema for ParallelThreads {
+    fn schema_name() -> String {
+        "ParallelThreads".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let auto = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(vec!["auto".into()]),
+            ..Default::default()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![gen.subschema_for::<usize>(), auto.into()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// A size in bytes, written in the configuration as `"500MB"`, `"1.5GiB"` or a number
+/// of bytes. Units without `i` are decimal, like the sizes mc prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+/// The units a [`ByteSize`] may be written in, largest first within each base.
+const BYTE_UNITS: &[(&str, u64)] = &[
+    ("TB", 1_000_000_000_000),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1_000),
+    ("TIB", 1 << 40),
+    ("GIB", 1 << 30),
+    ("MIB", 1 << 20),
+    ("KIB", 1 << 10),
+    ("T", 1_000_000_000_000),
+    ("G", 1_000_000_000),
+    ("M", 1_000_000),
+    ("K", 1_000),
+    ("B", 1),
+];
+
+impl ByteSize {
+    /// Returns the size in bytes.
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The largest decimal unit that divides the size exactly, so it reads back
+        // unchanged.
+        match BYTE_UNITS[..4]
+            .iter()
+            .find(|(_, unit)| self.0 > 0 && self.0.is_multiple_of(*unit))
+        {
+            Some((name, unit)) => write!(f, "{}{}", self.0 / unit, name),
+            None => write!(f, "{}B", self.0),
+        }
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("invalid size `{}`; expected e.g. 500MB, 1GB or 2GiB", s);
+        let upper = s.trim().to_ascii_uppercase();
+        let (number, unit) = BYTE_UNITS
+            .iter()
+            .find_map(|(name, unit)| upper.strip_suffix(name).map(|n| (n, *unit)))
+            .unwrap_or((upper.as_str(), 1));
+        let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+        if !number.is_finite() || number < 0.0 {
+            return Err(invalid());
+        }
+        Ok(ByteSize((number * unit as f64).round() as u64))
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(bytes) => Ok(ByteSize(bytes)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl JsonSchema for ByteSize {
+    fn schema_name() -> String {
+        "ByteSize".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![
+                    gen.subschema_for::<u64>(),
+                    gen.subschema_for::<String>(),
+                ]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// How the cleaner hands items out to its threads.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Scheduling {
+    /// Idle threads take single items from busy ones, so a huge directory never keeps
+    /// other items waiting behind it.
+    #[default]
+    WorkStealing,
+    /// Each thread takes `chunk_size` items at a time and deletes them in order.
+    Chunked,
+}
+
+/// How symbolic links are treated.
+///
+/// The cleaner never deletes through a link: removing a matched link only ever
+/// removes the link itself.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Links are neither traversed nor cleaned.
+    Skip,
+    /// Links are not traversed; a matched link is removed, its target is kept.
+    #[default]
+    DeleteLink,
+    /// Linked directories are traversed, and a matched link cleans its target.
+    /// Targets outside the scan root are never traversed or cleaned.
+    Follow,
+}
+
+/// What a real clean does about slow deletes (see `mc::engine::slow_delete`).
+///
+/// Before deleting, mc times deleting a few files it creates next to the items. A
+/// per-file latency far above that of an idle disk usually means an on-access
+/// scanner inspects every deletion.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowDeletePolicy {
+    /// Deletes are not timed.
+    Ignore,
+    /// Slow deletes are reported, with advice on excluding the tree from scanning.
+    /// Deletes are only timed on Windows.
+    #[default]
+    Warn,
+    /// Slow deletes are reported, and each matched directory is renamed aside before
+    /// it is deleted, retrying the files a scanner briefly holds open.
+    Mitigate,
+}
+
+/// How matched directories are sized in a dry run.
+///
+/// A real clean always walks what it deletes, so it reports exact sizes either way.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeMode {
+    /// Every file inside a matched directory is walked and added up.
+    #[default]
+    Exact,
+    /// Matched directories are not walked; their sizes are estimated from a sample of
+    /// their entries (see `mc::engine::estimate`) and marked as estimates.
+    Fast,
 }
 
 /// Defines safety-related configurations for the cleaner.
 /// These checks are performed before the scanning phase.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[non_exhaustive]
 pub struct SafetyConfig {
     /// Whether to check if the target path is inside a git repository. Defaults to `true`.
     #[serde(default = "default_true")]
@@ -74,54 +610,557 @@ pub struct SafetyConfig {
     /// The minimum required free disk space in GB before cleaning. Defaults to 1.0.
     #[serde(default = "default_min_free_space")]
     pub min_free_space_gb: f64,
+
+    /// Whether to skip (and report) matched items owned by another user. Defaults to `false`.
+    #[serde(default)]
+    pub only_own_files: bool,
+
+    /// Whether a matched directory that contains excluded paths is cleaned by deleting
+    /// everything else in it, instead of being skipped whole. Defaults to `false`.
+    #[serde(default)]
+    pub partial_delete: bool,
+
+    /// Whether to snapshot the cleaned file system before a real clean, on Btrfs, ZFS
+    /// and APFS. Cleaning is aborted if the snapshot fails. Defaults to `false`.
+    #[serde(default)]
+    pub snapshot_before: bool,
+
+    /// Protection of files that look like secrets.
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+
+    /// How matched items inside git repositories are treated.
+    #[serde(default)]
+    pub git: GitSafetyConfig,
+}
+
+/// Defines how matched items inside git repositories are treated.
+///
+/// Repositories are found below the scanned root during the scan, including linked
+/// worktrees and submodules, whose `.git` is a file. The repository containing the
+/// root counts too when `check_git_repo` is off. A matched item is governed by the
+/// repository it is in, and by every repository inside it, since deleting it deletes
+/// those as well.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct GitSafetyConfig {
+    /// The policy for repositories without an entry in `repos`. Defaults to `clean`.
+    #[serde(default)]
+    pub policy: GitRepoPolicy,
+
+    /// Policies for particular repositories, keyed by a glob matched against the
+    /// repository's path, or against its directory name when the glob has no `/`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub repos: BTreeMap<String, GitRepoPolicy>,
+
+    /// Whether mc cleans like `git clean -dX` inside repositories: every repository is
+    /// treated as `clean_only_ignored` at least, and the root may be inside one even
+    /// with `check_git_repo`. Defaults to `false`.
+    #[serde(default)]
+    pub clean_mode: bool,
+}
+
+/// What happens to matched items inside a git repository.
+#[derive(
+    Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum GitRepoPolicy {
+    /// Items are cleaned like any other.
+    #[default]
+    Clean,
+    /// Items are cleaned, and the repositories they are in are reported.
+    Warn,
+    /// Only items the repository's gitignore rules ignore are cleaned.
+    CleanOnlyIgnored,
+    /// No item inside the repository is cleaned.
+    Skip,
+}
+
+/// Defines which files are treated as secrets and never cleaned.
+///
+/// Matched items are checked by file name and, when `sniff_bytes` is non-zero, by the
+/// start of their contents; matched directories are searched entirely. An item that
+/// is or contains a secret is skipped and reported as protected. Disabled by default,
+/// since checking every file below matched directories slows scans down.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct SecretsConfig {
+    /// Whether secret protection is enabled. Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Glob patterns for the names of secret files.
+    #[serde(default = "default_secret_names")]
+    pub names: Vec<String>,
+
+    /// Strings whose presence in a file's contents marks it as a secret.
+    #[serde(default = "default_secret_markers")]
+    pub markers: Vec<String>,
+
+    /// How many leading bytes of each file are searched for markers; 0 disables content
+    /// sniffing. Defaults to 4096.
+    #[serde(default = "default_sniff_bytes")]
+    pub sniff_bytes: usize,
+}
+
+/// Defines when and how `mc check` raises an alert about reclaimable space.
+///
+/// Alerts never delete anything; they are meant for scheduled runs (cron, launchd,
+/// Task Scheduler) that should tell the user about growth instead of cleaning.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct AlertsConfig {
+    /// Reclaimable space in GB above which an alert is raised. Alerts are disabled when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold_gb: Option<f64>,
+
+    /// Whether to show a desktop notification when the threshold is exceeded. Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub notify: bool,
+
+    /// A shell command to run when the threshold is exceeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hook: Option<String>,
+}
+
+/// Defines shell commands run around the cleaning phase.
+///
+/// Hooks are skipped in dry-run mode. They receive `MC_ROOT`, `MC_PLANNED_BYTES`, and
+/// `MC_PLANNED_ITEMS`; the post-clean hook additionally receives `MC_REPORT_JSON`, the
+/// path of a file containing the JSON report.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct HooksConfig {
+    /// A command to run after confirmation and before any item is deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_clean: Option<String>,
+
+    /// A command to run after cleaning has finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_clean: Option<String>,
+
+    /// Seconds after which a hook is killed and treated as failed. Defaults to 300.
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+
+    /// What to do when a hook fails or times out. Defaults to `abort`.
+    #[serde(default)]
+    pub on_failure: HookFailurePolicy,
+}
+
+/// Defines the archive that matched items are written to before deletion.
+///
+/// When `dir` is set, every item of a real clean is packed into one tar archive in
+/// that directory before anything is deleted, for artifacts that are probably junk
+/// but may still be wanted. Items handled by actions are not archived.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct ArchiveConfig {
+    /// The directory archives are written to. Archiving is disabled when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+
+    /// How archives are compressed. Defaults to `zstd`.
+    #[serde(default)]
+    pub compression: ArchiveCompression,
+}
+
+/// Defines the quarantine that cleaned items are moved to instead of being deleted.
+///
+/// When `dir` is set, every item of a real clean is moved into a new batch directory
+/// there, so nothing is lost until the batch is purged. The space only becomes free
+/// then: `max_total` purges the oldest batches after each clean, and `mc stats` shows
+/// what the quarantine holds. Secure deletion turns quarantine off.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct QuarantineConfig {
+    /// The directory items are moved to. Items on its file system are renamed into
+    /// it, others are copied and then deleted. Scans never enter it. Quarantine is
+    /// disabled when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+
+    /// The most the quarantine may hold, such as `"50GB"`. After each clean, the
+    /// oldest batches are purged until it fits. Unlimited when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total: Option<ByteSize>,
+}
+
+/// Configures the HTTP API served by `mc serve --http`.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct ServerConfig {
+    /// The roots clients may scan and clean. Only the target path when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<PathBuf>,
+
+    /// A file holding the token clients must send as `Authorization: Bearer <token>`.
+    /// `MC_SERVE_TOKEN` takes precedence; without either, a token is generated at
+    /// startup and printed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+}
+
+/// Configures the WASM plugins of [`crate::plugins`].
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct PluginsConfig {
+    /// Loads every `*.wasm` file in `dir`. mc refuses to run when this is set but it
+    /// was built without the `plugins` feature.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// The directory plugins are loaded from. Defaults to `plugins` in the mc
+    /// configuration directory, such as `~/.config/mc/plugins/`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// The compression applied to archives.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveCompression {
+    /// A zstd-compressed tar archive (`.tar.zst`).
+    #[default]
+    Zstd,
+    /// A plain tar archive (`.tar`).
+    None,
+}
+
+/// The action taken when a hook fails.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HookFailurePolicy {
+    /// Stop the run with an error. A failed pre-clean hook prevents any deletion.
+    #[default]
+    Abort,
+    /// Log a warning and continue.
+    Warn,
+}
+
+/// Defines whether cleaned items are moved to the trash instead of being deleted.
+///
+/// With `enabled`, every item of a real clean goes to the Recycle Bin on Windows, to
+/// `~/.Trash` on macOS and to the home trash elsewhere, and the space is only freed
+/// when the trash is emptied. A Recycle Bin holds items up to its maximum size and
+/// Windows deletes larger ones for good without asking, so those are found before the
+/// clean and handled as `fallback` says. Trash takes the place of the quarantine,
+/// whose `dir` is then only used for the `quarantine` fallback. Secure deletion turns
+/// the trash off.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct TrashConfig {
+    /// Whether items go to the trash. Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// What happens to items too large for the Recycle Bin. Defaults to `ask`.
+    #[serde(default)]
+    pub fallback: TrashFallback,
+}
+
+/// What happens to an item too large for the Recycle Bin.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashFallback {
+    /// Ask before the clean whether to delete them permanently (or quarantine them,
+    /// with `quarantine.dir`); they are kept when nobody can answer.
+    #[default]
+    Ask,
+    /// Delete them permanently.
+    Delete,
+    /// Move them into the quarantine, which needs `quarantine.dir`.
+    Quarantine,
+    /// Leave them alone.
+    Keep,
+}
+
+/// A rule that handles matched items by running a command instead of deleting them.
+///
+/// A rule applies to an item when its `category` and/or `pattern` match; when both are
+/// given, both must match. For example, a `Cache` rule running `pnpm store prune` lets
+/// the package manager clean its own store. The first matching rule wins.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct ActionRule {
+    /// The pattern category this rule applies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<PatternCategory>,
+
+    /// The pattern this rule applies to, compared with the pattern that matched the item.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// The shell command to run.
+    pub command: String,
+
+    /// Whether the command runs once per clean or once per matched item. Defaults to `once`.
+    #[serde(default)]
+    pub scope: CommandScope,
+}
+
+/// How often an action command is run.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandScope {
+    /// Run the command once if any item matched the rule.
+    #[default]
+    Once,
+    /// Run the command for every matched item, in the item's parent directory, with
+    /// `MC_ITEM_PATH` set.
+    Item,
+}
+
+/// A keep-latest retention rule for directories holding versioned builds.
+///
+/// Instead of deleting a matched directory whole, only its older children are removed:
+///
+/// ```toml
+/// [[retention]]
+/// pattern = "target"
+/// subdir = "package"
+/// children = "*.crate"
+/// keep_latest = 2
+/// ```
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct RetentionRule {
+    /// The pattern whose matched directories hold versioned children.
+    pub pattern: String,
+
+    /// A path inside the matched directory that holds the versioned children.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+
+    /// A glob selecting the versioned children. Children that do not match are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<String>,
+
+    /// The number of newest children, by modification time, to keep.
+    pub keep_latest: usize,
+}
+
+/// Ecosystem-specific rules that refine how matched items are cleaned.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default)]
+pub struct RulesConfig {
+    /// Rules for Cargo `target/` directories.
+    #[serde(default)]
+    pub cargo: CargoRuleConfig,
+
+    /// Rules for Node.js projects.
+    #[serde(default)]
+    pub node: NodeRuleConfig,
+
+    /// Rules for container build caches.
+    #[serde(default)]
+    pub docker: DockerRuleConfig,
+
+    /// Rules for Python virtualenvs.
+    #[serde(default)]
+    pub python: PythonRuleConfig,
+
+    /// Rules for machine learning artifacts.
+    #[serde(default)]
+    pub ml: MlRuleConfig,
+}
+
+/// Configures how Cargo `target/` directories are cleaned.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct CargoRuleConfig {
+    /// `full` removes the whole directory; `sweep` removes only stale artifacts.
+    /// Defaults to `full`.
+    #[serde(default)]
+    pub mode: CargoMode,
+
+    /// In `sweep` mode, artifacts not modified for this many days are removed.
+    /// Defaults to 30.
+    #[serde(default = "default_cargo_max_age_days")]
+    pub max_age_days: u64,
+}
+
+/// The cleaning strategy for Cargo `target/` directories.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CargoMode {
+    /// Remove the whole `target/` directory.
+    #[default]
+    Full,
+    /// Remove only artifacts older than `max_age_days`, like `cargo-sweep`.
+    Sweep,
+}
+
+/// Configures package-manager-aware cleaning of Node.js projects.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct NodeRuleConfig {
+    /// Run `pnpm store prune` after cleaning pnpm `node_modules`. Defaults to `false`.
+    #[serde(default)]
+    pub pnpm_store_prune: bool,
+
+    /// Never delete lockfiles of detected projects, so `npm ci` and frozen installs
+    /// can restore `node_modules`. Defaults to `false`.
+    #[serde(default)]
+    pub keep_lockfiles: bool,
+
+    /// Keep `.yarn/cache` in Yarn Plug'n'Play projects. Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub keep_yarn_cache: bool,
+}
+
+/// Configures cleaning of container build caches.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct DockerRuleConfig {
+    /// Match `buildx`, `kaniko`, and in-repo `.docker` build caches. Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Run `docker builder prune --force` after cleaning. Defaults to `false`.
+    #[serde(default)]
+    pub builder_prune: bool,
+}
+
+/// Configures cleaning of Python virtualenvs.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct PythonRuleConfig {
+    /// Which virtualenvs to clean. Defaults to `all`.
+    #[serde(default)]
+    pub venvs: VenvPolicy,
+
+    /// With `venvs = "stale"`, a virtualenv whose interpreter has not been used for
+    /// this many days is stale. Defaults to 30.
+    #[serde(default = "default_venv_unused_days")]
+    pub venv_unused_days: u64,
+}
+
+/// Selects which matched virtualenvs are cleaned.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VenvPolicy {
+    /// Clean every matched virtualenv.
+    #[default]
+    All,
+    /// Clean only virtualenvs whose interpreter is gone or unused.
+    Stale,
+}
+
+/// Configures cleaning of machine learning artifacts.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct MlRuleConfig {
+    /// Match `wandb/`, `mlruns/`, `lightning_logs/`, `.cache/huggingface`, and
+    /// checkpoints. Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Checkpoint files (`*.ckpt`, `*.safetensors`) smaller than this are kept.
+    /// Defaults to 1024.
+    #[serde(default = "default_ml_min_checkpoint_mb")]
+    pub min_checkpoint_mb: u64,
+
+    /// ML artifacts modified within this many days are kept. Defaults to 7.
+    #[serde(default = "default_ml_min_age_days")]
+    pub min_age_days: u64,
 }
 
 impl Config {
     /// Loads the configuration from a file.
     ///
-    /// It searches for a `.mc.toml` file in the current directory and its ancestors. If not found, it
-    /// checks for a global config file. If a path is provided, it attempts to load
+    /// It searches for a `.mc.toml` (or `.mc.yaml`, `.mc.yml`, `.mc.json`) file in the current
+    /// directory and its ancestors. If not found, it checks for a global config file. The format
+    /// is detected from the file extension. If a path is provided, it attempts to load
     /// from that path. If no file is found, it falls back to the default configuration.
     ///
+    /// A file may name base files under `extends`. These are loaded first, in order,
+    /// and the file is merged on top: tables merge key by key, lists append entries
+    /// not already present, and other values override. Relative paths are resolved
+    /// from the naming file's directory, and cycles are reported as errors.
+    ///
     /// # Arguments
     ///
     /// * `path` - An optional path to a specific configuration file.
     pub fn load(path: Option<&PathBuf>) -> Result<Self> {
-        let config_path = path
-            .cloned()
-            .or_else(Self::find_config_file)
-            .unwrap_or_else(Self::default_config_path);
+        Self::load_with_provenance(path).map(|(config, _)| config)
+    }
+
+    /// Loads the configuration like [`Config::load`], also reporting which files were
+    /// considered and which file set each setting.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Config::load`].
+    pub fn load_with_provenance(path: Option<&PathBuf>) -> Result<(Self, Provenance)> {
+        let (config_path, source) = Self::locate(path);
 
         if config_path.exists() {
             log::debug!("Loading config from: {}", config_path.display());
-            let contents = fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&contents)?;
-            Ok(config)
+            let mut trace = extends::Trace::default();
+            let mut config: Config =
+                toml::Value::Table(extends::load_traced(&config_path, &mut trace)?).try_into()?;
+            let global = std::fs::canonicalize(Self::default_config_path()).ok();
+            config
+                .patterns
+                .mark_sources(source, &trace, global.as_deref());
+            let candidates = match path {
+                Some(path) => vec![path.clone()],
+                None => Self::candidate_files(),
+            };
+            Ok((config, Provenance::new(candidates, trace)))
         } else {
             log::debug!("No config file found, using defaults");
-            Ok(Self::default())
+            Ok((Self::default(), Provenance::default()))
+        }
+    }
+
+    /// Lists the existing project and global configuration files in the order they are
+    /// searched; the first one is the file [`Config::load`] reads.
+    fn candidate_files() -> Vec<PathBuf> {
+        let project = std::env::current_dir().into_iter().flat_map(|current| {
+            current
+                .ancestors()
+                .flat_map(|ancestor| {
+                    PROJECT_FILE_NAMES
+                        .iter()
+                        .map(move |name| ancestor.join(name))
+                })
+                .collect::<Vec<_>>()
+        });
+        let global = ProjectDirs::from("com", "mc", "mc")
+            .into_iter()
+            .flat_map(|dirs| {
+                GLOBAL_FILE_NAMES
+                    .iter()
+                    .map(|name| dirs.config_dir().join(name))
+                    .collect::<Vec<_>>()
+            });
+        project.chain(global).filter(|path| path.exists()).collect()
+    }
+
+    /// Returns the configuration file [`Config::load`] reads, which may not exist yet.
+    pub fn file_path(path: Option<&PathBuf>) -> PathBuf {
+        Self::locate(path).0
+    }
+
+    /// Resolves the configuration file to load and the source its patterns count as.
+    fn locate(path: Option<&PathBuf>) -> (PathBuf, PatternSource) {
+        match path.cloned().or_else(Self::find_config_file) {
+            Some(path) => (path, PatternSource::Config),
+            None => (Self::default_config_path(), PatternSource::Global),
         }
     }
 
     /// Finds the configuration file by searching upward from the current directory.
     fn find_config_file() -> Option<PathBuf> {
-        // Look for .mc.toml in current directory and parents
+        // Look for a project config in current directory and parents
         let current = std::env::current_dir().ok()?;
 
-        for ancestor in current.ancestors() {
-            let config = ancestor.join(".mc.toml");
-            if config.exists() {
-                return Some(config);
-            }
-        }
-
-        None
+        current.ancestors().find_map(|ancestor| {
+            PROJECT_FILE_NAMES
+                .iter()
+                .map(|name| ancestor.join(name))
+                .find(|config| config.exists())
+        })
     }
 
-    /// Determines the default path for the global configuration file.
+    /// Determines the default path for the global configuration file, preferring
+    /// whichever supported name exists.
     fn default_config_path() -> PathBuf {
         ProjectDirs::from("com", "mc", "mc")
-            .map(|dirs| dirs.config_dir().join("config.toml"))
+            .map(|dirs| {
+                let dir = dirs.config_dir();
+                GLOBAL_FILE_NAMES
+                    .iter()
+                    .map(|name| dir.join(name))
+                    .find(|config| config.exists())
+                    .unwrap_or_else(|| dir.join(GLOBAL_FILE_NAMES[0]))
+            })
             .unwrap_or_else(|| PathBuf::from(".mc.toml"))
     }
 
@@ -133,7 +1172,8 @@ impl Config {
     /// # Arguments
     ///
     /// * `exclude` - A list of patterns to add to the exclude list.
-    /// * `include` - A list of patterns to add to the include lists (files or directories).
+    /// * `include` - A list of patterns to add to the include lists. A `dir:` or `file:`
+    ///   prefix restricts a pattern to directories or files; without one it matches both.
     /// * `preserve_env` - A flag to control the preservation of `.env` files.
     pub fn merge_cli_args(
         &mut self,
@@ -149,14 +1189,16 @@ impl Config {
         }
 
         // Add CLI includes
-        for pattern in include {
-            // Determine if it's a file or directory pattern
-            if pattern.contains('.') || pattern.contains('*') {
-                if !self.patterns.files.contains(&pattern) {
-                    self.patterns.files.push(pattern);
-                }
-            } else if !self.patterns.directories.contains(&pattern) {
-                self.patterns.directories.push(pattern);
+        for raw in &include {
+            let (kind, pattern) = IncludeKind::parse(raw);
+            self.patterns
+                .sources
+                .insert(pattern.to_string(), PatternSource::CLI);
+            if kind.dirs() && !self.patterns.directories.iter().any(|p| p == pattern) {
+                self.patterns.directories.push(pattern.to_string());
+            }
+            if kind.files() && !self.patterns.files.iter().any(|p| p == pattern) {
+                self.patterns.files.push(pattern.to_string());
             }
         }
 
@@ -171,36 +1213,60 @@ impl Config {
         }
     }
 
-    /// Validates configuration values, clamping out-of-range settings.
+    /// Validates configuration values, clamping out-of-range settings and warning
+    /// about rebuild notes that no pattern or category would show.
     pub fn validate(&mut self) {
-        self.options.parallel_threads =
-            crate::utils::clamp_parallelism(self.options.parallel_threads);
-        log::debug!("Config validated: parallel_threads={}", self.options.parallel_threads);
+        if let ParallelThreads::Count(threads) = &mut self.options.parallel_threads {
+            *threads = crate::utils::clamp_parallelism(*threads);
+        }
+        for key in self.patterns.rebuild.keys() {
+            let known = PatternCategory::from_name(key).is_some()
+                || key.starts_with("plugin:")
+                || self
+                    .patterns
+                    .directories
+                    .iter()
+                    .chain(&self.patterns.files)
+                    .any(|p| p == key);
+            if !known {
+                log::warn!(
+                    "[patterns.rebuild] key `{}` is neither an include pattern nor a category; \
+                     its note is never shown",
+                    key
+                );
+            }
+        }
+        log::debug!(
+            "Config validated: parallel_threads={}",
+            self.options.parallel_threads
+        );
     }
 }
 
-impl Default for Config {
+impl Default for PatternConfig {
+    /// The built-in patterns.
     fn default() -> Self {
         Self {
-            patterns: PatternConfig {
-                directories: BUILTIN_PATTERNS
-                    .directories()
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                files: BUILTIN_PATTERNS
-                    .files()
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                exclude: BUILTIN_PATTERNS
-                    .exclude
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-            },
-            options: OptionsConfig::default(),
-            safety: SafetyConfig::default(),
+            directories: BUILTIN_PATTERNS
+                .directories()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            files: BUILTIN_PATTERNS
+                .files()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            exclude: BUILTIN_PATTERNS
+                .exclude
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            max_depth: BTreeMap::new(),
+            priority: BTreeMap::new(),
+            scoped_exclude: BTreeMap::new(),
+            rebuild: BTreeMap::new(),
+            sources: BTreeMap::new(),
         }
     }
 }
@@ -209,25 +1275,132 @@ impl Default for OptionsConfig {
     fn default() -> Self {
         Self {
             parallel_threads: default_parallel_threads(),
+            scheduling: Scheduling::default(),
+            chunk_size: default_chunk_size(),
             require_confirmation: true,
             show_statistics: true,
             preserve_symlinks: true,
+            symlinks: None,
+            symlink_overrides: BTreeMap::new(),
+            precount: false,
+            one_file_system: false,
+            unused_for_days: None,
+            skip_traversal: default_skip_traversal(),
+            secure_delete: false,
+            auto_yes_under: None,
+            item_timeout_secs: None,
+            policy_script: None,
+            slow_deletes: SlowDeletePolicy::default(),
+            largest_items: default_largest_items(),
+            size_mode: SizeMode::default(),
+            binary_units: false,
         }
     }
 }
 
+impl SafetyConfig {
+    /// Returns whether cleaning a root inside a git repository is refused: with
+    /// `check_git_repo`, unless `git.clean_mode` limits the clean to ignored files.
+    pub fn refuses_git_root(&self) -> bool {
+        self.check_git_repo && !self.git.clean_mode
+    }
+}
+
 impl Default for SafetyConfig {
     fn default() -> Self {
         Self {
             check_git_repo: true,
             max_depth: default_max_depth(),
             min_free_space_gb: default_min_free_space(),
+            only_own_files: false,
+            partial_delete: false,
+            snapshot_before: false,
+            secrets: SecretsConfig::default(),
+            git: GitSafetyConfig::default(),
+        }
+    }
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            names: default_secret_names(),
+            markers: default_secret_markers(),
+            sniff_bytes: default_sniff_bytes(),
+        }
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            threshold_gb: None,
+            notify: true,
+            hook: None,
+        }
+    }
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            pre_clean: None,
+            post_clean: None,
+            timeout_secs: default_hook_timeout(),
+            on_failure: HookFailurePolicy::default(),
+        }
+    }
+}
+
+impl Default for CargoRuleConfig {
+    fn default() -> Self {
+        Self {
+            mode: CargoMode::default(),
+            max_age_days: default_cargo_max_age_days(),
+        }
+    }
+}
+
+impl Default for NodeRuleConfig {
+    fn default() -> Self {
+        Self {
+            pnpm_store_prune: false,
+            keep_lockfiles: false,
+            keep_yarn_cache: true,
+        }
+    }
+}
+
+impl Default for PythonRuleConfig {
+    fn default() -> Self {
+        Self {
+            venvs: VenvPolicy::default(),
+            venv_unused_days: default_venv_unused_days(),
         }
     }
 }
 
-fn default_parallel_threads() -> usize {
-    crate::utils::available_parallelism()
+impl Default for MlRuleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_checkpoint_mb: default_ml_min_checkpoint_mb(),
+            min_age_days: default_ml_min_age_days(),
+        }
+    }
+}
+
+fn default_parallel_threads() -> ParallelThreads {
+    ParallelThreads::Count(crate::utils::available_parallelism())
+}
+
+fn default_chunk_size() -> usize {
+    crate::engine::cleaner::DEFAULT_CHUNK_SIZE
+}
+
+fn default_largest_items() -> usize {
+    crate::engine::cleaner::DEFAULT_LARGEST_ITEMS
 }
 
 fn default_true() -> bool {
@@ -242,6 +1415,55 @@ fn default_min_free_space() -> f64 {
     1.0
 }
 
+fn default_skip_traversal() -> Vec<String> {
+    [".git", ".hg", ".svn", crate::snapshots::BTRFS_SNAPSHOT_DIR]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_secret_names() -> Vec<String> {
+    [
+        ".env*",
+        "*.pem",
+        "*.key",
+        "id_rsa",
+        "id_dsa",
+        "id_ecdsa",
+        "id_ed25519",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_secret_markers() -> Vec<String> {
+    vec!["PRIVATE KEY".to_string()]
+}
+
+fn default_sniff_bytes() -> usize {
+    4096
+}
+
+fn default_hook_timeout() -> u64 {
+    300
+}
+
+fn default_cargo_max_age_days() -> u64 {
+    30
+}
+
+fn default_venv_unused_days() -> u64 {
+    30
+}
+
+fn default_ml_min_checkpoint_mb() -> u64 {
+    1024
+}
+
+fn default_ml_min_age_days() -> u64 {
+    7
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -269,7 +1491,10 @@ mod tests {
         let initial_dirs_len = config.patterns.directories.len();
 
         let excludes = vec!["custom_exclude".to_string()];
-        let includes = vec!["custom_include.file".to_string(), "custom_dir".to_string()];
+        let includes = vec![
+            "file:custom_include.file".to_string(),
+            "dir:custom_dir".to_string(),
+        ];
 
         config.merge_cli_args(excludes.clone(), includes.clone(), false);
 
@@ -280,34 +1505,138 @@ mod tests {
         assert!(config.patterns.exclude.contains(&excludes[0]));
 
         assert_eq!(config.patterns.files.len(), initial_files_len + 1);
-        assert!(config.patterns.files.contains(&includes[0]));
+        assert!(config
+            .patterns
+            .files
+            .contains(&"custom_include.file".to_string()));
 
         assert_eq!(config.patterns.directories.len(), initial_dirs_len + 1);
-        assert!(config.patterns.directories.contains(&includes[1]));
+        assert!(config
+            .patterns
+            .directories
+            .contains(&"custom_dir".to_string()));
+    }
+
+    #[test]
+    fn test_merge_cli_args_unprefixed_include_matches_both() {
+        let mut config = Config::default();
+
+        config.merge_cli_args(
+            vec![],
+            vec![".cache".to_string(), "build*".to_string()],
+            false,
+        );
+
+        for pattern in [".cache", "build*"] {
+            assert!(config.patterns.directories.iter().any(|p| p == pattern));
+            assert!(config.patterns.files.iter().any(|p| p == pattern));
+        }
+    }
+
+    #[test]
+    fn test_pattern_sources_follow_the_file_that_listed_them() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let global = temp.path().join("global.toml");
+        std::fs::write(&global, "[patterns]\ndirectories = [\"vendor\"]\n").unwrap();
+        let project = temp.path().join(".mc.toml");
+        std::fs::write(
+            &project,
+            "extends = [\"global.toml\"]\n[patterns]\ndirectories = [\"vendor\", \"target\"]\n",
+        )
+        .unwrap();
+        let mut trace = extends::Trace::default();
+        extends::load_traced(&project, &mut trace).unwrap();
+
+        let mut patterns = Config::default().patterns;
+        patterns.directories = vec!["vendor".to_string(), "target".to_string()];
+        let global = std::fs::canonicalize(&global).unwrap();
+        patterns.mark_sources(PatternSource::Config, &trace, Some(&global));
+
+        assert_eq!(patterns.source_of("vendor"), PatternSource::Global);
+        assert_eq!(patterns.source_of("target"), PatternSource::Config);
+        assert_eq!(patterns.priority_of("target"), 300);
     }
 
     #[test]
     fn test_validate_clamps_zero_threads() {
         let mut config = Config::default();
-        config.options.parallel_threads = 0;
+        config.options.parallel_threads = ParallelThreads::Count(0);
         config.validate();
-        assert!(config.options.parallel_threads >= 1);
+        assert_eq!(config.options.parallel_threads, 1);
     }
 
     #[test]
     fn test_validate_clamps_excessive_threads() {
         let mut config = Config::default();
-        config.options.parallel_threads = 99999;
+        config.options.parallel_threads = ParallelThreads::Count(99999);
         config.validate();
-        assert!(config.options.parallel_threads <= crate::utils::available_parallelism());
+        assert_eq!(
+            config.options.parallel_threads,
+            crate::utils::available_parallelism()
+        );
     }
 
     #[test]
     fn test_validate_preserves_valid_threads() {
         let mut config = Config::default();
-        config.options.parallel_threads = 2;
+        config.options.parallel_threads = ParallelThreads::Count(2);
         config.validate();
         // Only valid if machine has ≥2 cores, which is true for any modern system
         assert_eq!(config.options.parallel_threads, 2);
     }
+
+    #[test]
+    fn test_parallel_threads_accepts_auto() {
+        let options: OptionsConfig = toml::from_str("parallel_threads = \"auto\"").unwrap();
+        assert_eq!(options.parallel_threads, ParallelThreads::Auto);
+        let serialized = toml::to_string(&options).unwrap();
+        assert!(
+            serialized.contains("parallel_threads = \"auto\""),
+            "{}",
+            serialized
+        );
+
+        let mut config = Config::default();
+        config.options.parallel_threads = ParallelThreads::Auto;
+        config.validate();
+        assert_eq!(config.options.parallel_threads, ParallelThreads::Auto);
+        assert!(toml::from_str::<OptionsConfig>("parallel_threads = \"many\"").is_err());
+    }
+
+    #[test]
+    fn test_byte_size_parses_units() {
+        assert_eq!("1GB".parse(), Ok(ByteSize(1_000_000_000)));
+        assert_eq!("1.5 GiB".parse(), Ok(ByteSize(3 << 29)));
+        assert_eq!("500mb".parse(), Ok(ByteSize(500_000_000)));
+        assert_eq!("4096".parse(), Ok(ByteSize(4096)));
+        assert!("lots".parse::<ByteSize>().is_err());
+        assert!("-1GB".parse::<ByteSize>().is_err());
+
+        let options: OptionsConfig = toml::from_str("auto_yes_under = \"1GB\"").unwrap();
+        assert_eq!(options.auto_yes_under, Some(ByteSize(1_000_000_000)));
+        let serialized = toml::to_string(&options).unwrap();
+        assert!(
+            serialized.contains("auto_yes_under = \"1GB\""),
+            "{}",
+            serialized
+        );
+        let options: OptionsConfig = toml::from_str("auto_yes_under = 1500").unwrap();
+        assert_eq!(options.auto_yes_under.unwrap().to_string(), "1500B");
+    }
+
+    #[test]
+    fn test_schema_covers_serialized_sections() {
+        let schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap();
+        let mut config = Config::default();
+        config.extends.push("base.toml".to_string());
+        let serialized = serde_json::to_value(&config).unwrap();
+
+        for key in serialized.as_object().unwrap().keys() {
+            assert!(
+                schema["properties"].get(key).is_some(),
+                "{} missing from schema",
+                key
+            );
+        }
+    }
 }
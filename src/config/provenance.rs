@@ -0,0 +1,211 @@
+//! Where the effective configuration came from, for `mc config --show-origin`.
+
+use super::extends::Trace;
+use super::Config;
+use crate::types::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A layer that can set a configuration value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The built-in default.
+    Default,
+    /// A configuration file.
+    File(PathBuf),
+    /// A command-line flag, such as `--no-git-check`.
+    Cli(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Cli(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+/// What became of a configuration file `mc` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file was loaded.
+    Loaded,
+    /// The file was loaded as a base of another file through `extends`.
+    Extended {
+        /// The file that extends it.
+        by: PathBuf,
+    },
+    /// The file exists but was not read because a file found earlier took precedence.
+    Ignored {
+        /// The file that was loaded instead.
+        for_file: PathBuf,
+    },
+}
+
+impl fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileStatus::Loaded => write!(f, "loaded"),
+            FileStatus::Extended { by } => write!(f, "loaded (extended by {})", by.display()),
+            FileStatus::Ignored { for_file } => {
+                write!(f, "ignored ({} takes precedence)", for_file.display())
+            }
+        }
+    }
+}
+
+/// The configuration files that were considered and the layers that set each
+/// effective setting.
+#[derive(Debug, Default)]
+pub struct Provenance {
+    /// Every configuration file found, in the order they were considered.
+    pub files: Vec<(PathBuf, FileStatus)>,
+    /// The layers that set each setting, keyed by dotted key. Settings without an
+    /// entry have their default value.
+    origins: BTreeMap<String, Vec<Origin>>,
+}
+
+impl Provenance {
+    /// Builds the provenance of a load that found `candidates`, loading the first of
+    /// them as recorded in `trace`.
+    pub(crate) fn new(candidates: Vec<PathBuf>, trace: Trace) -> Self {
+        let mut files: Vec<(PathBuf, FileStatus)> = trace
+            .files
+            .into_iter()
+            .map(|(path, by)| {
+                let status = match by {
+                    Some(by) => FileStatus::Extended { by },
+                    None => FileStatus::Loaded,
+                };
+                (path, status)
+            })
+            .collect();
+        if let Some((loaded, _)) = files.first().cloned() {
+            files.extend(candidates.into_iter().skip(1).map(|path| {
+                (
+                    path,
+                    FileStatus::Ignored {
+                        for_file: loaded.clone(),
+                    },
+                )
+            }));
+        }
+        let origins = trace
+            .origins
+            .into_iter()
+            .map(|(key, files)| (key, files.into_iter().map(Origin::File).collect()))
+            .collect();
+        Self { files, origins }
+    }
+
+    /// Returns the layers that set `key`, most recent last.
+    pub fn origin(&self, key: &str) -> &[Origin] {
+        const DEFAULT: &[Origin] = &[Origin::Default];
+        self.origins.get(key).map_or(DEFAULT, Vec::as_slice)
+    }
+
+    /// Records that `flag` replaced the value of `key`.
+    pub fn set_by_cli(&mut self, key: &str, flag: &str) {
+        self.origins
+            .insert(key.to_string(), vec![Origin::Cli(flag.to_string())]);
+    }
+
+    /// Records that `flag` added entries to the list at `key`.
+    pub fn extended_by_cli(&mut self, key: &str, flag: &str) {
+        let origins = self
+            .origins
+            .entry(key.to_string())
+            .or_insert_with(|| vec![Origin::Default]);
+        let origin = Origin::Cli(flag.to_string());
+        if !origins.contains(&origin) {
+            origins.push(origin);
+        }
+    }
+
+    /// Lists every effective setting of `config` as a dotted key, its value, and the
+    /// layers that set it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `config` cannot be represented as TOML.
+    pub fn settings(&self, config: &Config) -> Result<Vec<(String, toml::Value, &[Origin])>> {
+        let mut settings = Vec::new();
+        if let toml::Value::Table(table) = toml::Value::try_from(config)? {
+            self.collect(table, "", &mut settings);
+        }
+        Ok(settings)
+    }
+
+    fn collect<'a>(
+        &'a self,
+        table: toml::Table,
+        prefix: &str,
+        settings: &mut Vec<(String, toml::Value, &'a [Origin])>,
+    ) {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, toml_edit::Key::new(key.as_str()));
+            match value {
+                toml::Value::Table(table) => self.collect(table, &format!("{}.", key), settings),
+                value => {
+                    let origin = self.origin(&key);
+                    settings.push((key, value, origin));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_origins_follow_layers() {
+        let temp = TempDir::new().unwrap();
+        temp.child("base.toml")
+            .write_str(
+                "[patterns]\ndirectories = [\"target\"]\nfiles = []\nexclude = []\n\
+                 [options]\nparallel_threads = 2\n[safety]\n",
+            )
+            .unwrap();
+        let child = temp.child(".mc.toml");
+        child
+            .write_str("extends = \"base.toml\"\n[patterns]\ndirectories = [\"dist\"]\n")
+            .unwrap();
+
+        let (mut config, mut provenance) =
+            Config::load_with_provenance(Some(&child.path().to_path_buf())).unwrap();
+        config.safety.check_git_repo = false;
+        provenance.set_by_cli("safety.check_git_repo", "--no-git-check");
+
+        let child = child.path().canonicalize().unwrap();
+        let base = temp.child("base.toml").path().canonicalize().unwrap();
+        assert_eq!(provenance.files[0], (child.clone(), FileStatus::Loaded));
+        assert_eq!(
+            provenance.files[1],
+            (base.clone(), FileStatus::Extended { by: child.clone() })
+        );
+        assert_eq!(
+            provenance.origin("patterns.directories"),
+            [Origin::File(base.clone()), Origin::File(child)]
+        );
+        assert_eq!(
+            provenance.origin("options.parallel_threads"),
+            [Origin::File(base)]
+        );
+        assert_eq!(provenance.origin("options.precount"), [Origin::Default]);
+
+        let settings = provenance.settings(&config).unwrap();
+        let (_, value, origin) = settings
+            .iter()
+            .find(|(key, _, _)| key == "safety.check_git_repo")
+            .unwrap();
+        assert_eq!(value.as_bool(), Some(false));
+        assert_eq!(*origin, [Origin::Cli("--no-git-check".to_string())]);
+    }
+}
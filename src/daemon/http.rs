@@ -0,0 +1,704 @@
+//! A REST API over HTTP, for dashboards and scripts that trigger cleans on a machine
+//! without a shell on it.
+//!
+//! Clients may only act on the roots configured in `[server]`
+//! ([`ServerConfig`](crate::ServerConfig)), and every request must carry
+//! `Authorization: Bearer <token>`. Scans and cleans run as jobs in the background, at
+//! most one at a time per root.
+//!
+//! # Endpoints
+//!
+//! - `GET /roots` lists the roots as `{id, path, busy}`.
+//! - `POST /roots/{id}/scan` starts a job planning a clean of the root, without
+//!   deleting anything. Its result is the [`Plan`](crate::Plan).
+//! - `POST /roots/{id}/clean` starts a job cleaning the root. Its result is the
+//!   [`CleanReport`](crate::CleanReport).
+//! - `GET /jobs` lists the last [`HISTORY_LIMIT`] jobs, newest first, without results.
+//! - `GET /jobs/{id}` returns a job, with its `result` or `error` once it finished.
+//! - `GET /jobs/{id}/events` streams the progress of a job as server-sent events:
+//!   `progress` events, `{phase, done, total}`, at most every [`PROGRESS_INTERVAL`],
+//!   then a `done` event holding the finished job.
+//!
+//! Starting a job answers `202 Accepted` with the job, or `409 Conflict` if the root
+//! already has one running. Errors are answered as `{"error": "..."}`. No endpoint
+//! reads a body; bodies over 1 MiB are refused with `413 Content Too Large`, and an
+//! unreadable `Content-Length` with `400 Bad Request`. A client that stops reading its
+//! response, or its events, for 10 seconds is disconnected.
+
+use super::check_root;
+use super::rpc::PROGRESS_INTERVAL;
+use crate::config::ServerConfig;
+use crate::types::{McError, Result};
+use crate::utils::progress::Progress;
+use crate::Cleaner;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How many jobs `GET /jobs` remembers. Running jobs are never forgotten.
+pub const HISTORY_LIMIT: usize = 100;
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a write may wait on a client that does not read, such as an idle events
+/// stream, before the connection is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most header lines read from a request.
+const MAX_HEADERS: usize = 64;
+
+/// The most bytes read of a request line and its headers.
+const MAX_HEAD_BYTES: u64 = 16 * 1024;
+
+/// The most bytes of a request body read; larger bodies are answered with `413`.
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+/// The most connections served at once; further ones are closed unanswered.
+const MAX_CONNECTIONS: usize = 64;
+
+/// Binds `addr` and serves the API for `roots` on a background thread. Every scan and
+/// clean is run by `cleaner`, which should be quiet.
+///
+/// Returns the bound address, which differs from `addr` when port `0` was requested.
+///
+/// # Errors
+///
+/// Returns an error if a root does not exist or `addr` cannot be bound.
+pub fn serve_http(
+    addr: SocketAddr,
+    cleaner: Cleaner,
+    roots: Vec<PathBuf>,
+    token: String,
+) -> Result<SocketAddr> {
+    let roots = roots
+        .iter()
+        .map(|root| root.canonicalize())
+        .collect::<io::Result<_>>()?;
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    log::info!("Serving the mc API on http://{}", local_addr);
+    if !local_addr.ip().is_loopback() {
+        log::warn!(
+            "The mc API on {} is reachable from other machines over plain HTTP, so its \
+             token can be read on the network; put it behind a TLS proxy or bind a \
+             loopback address",
+            local_addr
+        );
+    }
+
+    let server = Arc::new(Server {
+        cleaner,
+        roots,
+        token,
+        jobs: Mutex::new(VecDeque::new()),
+        next_job: AtomicU64::new(1),
+        write_timeout: WRITE_TIMEOUT,
+    });
+    let connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::Relaxed);
+                        log::debug!("API connection refused: {} already open", MAX_CONNECTIONS);
+                        continue;
+                    }
+                    let server = Arc::clone(&server);
+                    let connections = Arc::clone(&connections);
+                    thread::spawn(move || {
+                        if let Err(err) = server.handle(stream) {
+                            log::debug!("API connection error: {}", err);
+                        }
+                        connections.fetch_sub(1, Ordering::Relaxed);
+                    });
+                }
+                Err(err) => log::debug!("API accept error: {}", err),
+            }
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// Returns the token clients must present: `MC_SERVE_TOKEN` if set, else the contents
+/// of `server.token_file`, else `None`.
+///
+/// # Errors
+///
+/// Returns an error if the token file cannot be read or is empty.
+pub fn load_token(server: &ServerConfig) -> Result<Option<String>> {
+    if let Ok(token) = std::env::var("MC_SERVE_TOKEN") {
+        if !token.trim().is_empty() {
+            return Ok(Some(token.trim().to_string()));
+        }
+    }
+    let Some(path) = &server.token_file else {
+        return Ok(None);
+    };
+    let token = std::fs::read_to_string(path)?.trim().to_string();
+    if token.is_empty() {
+        let message = format!("token file {} is empty", path.display());
+        return Err(McError::InvalidArgument(message));
+    }
+    Ok(Some(token))
+}
+
+/// Generates a random token of 32 hex digits from the operating system's random
+/// number generator.
+///
+/// # Errors
+///
+/// Returns an error if the operating system cannot provide random bytes.
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|err| io::Error::other(err.to_string()))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The state shared by all connections.
+struct Server {
+    cleaner: Cleaner,
+    roots: Vec<PathBuf>,
+    token: String,
+    /// The jobs, newest first.
+    jobs: Mutex<VecDeque<Arc<Job>>>,
+    next_job: AtomicU64,
+    /// How long a write may wait on a client; [`WRITE_TIMEOUT`] outside tests.
+    write_timeout: Duration,
+}
+
+/// A response: the status code and the JSON body.
+type Response = (u16, Value);
+
+impl Server {
+    /// Answers the request on `stream`.
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.set_write_timeout(Some(self.write_timeout))?;
+        let request = match Request::read(&mut stream)? {
+            Ok(request) => request,
+            Err(refusal) => return respond(&mut stream, refusal),
+        };
+        if !self.authorized(request.authorization.as_deref()) {
+            return respond(&mut stream, (401, error("missing or invalid token")));
+        }
+
+        let path = request.target.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let response = match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["roots"]) => (200, self.roots_json()),
+            ("POST", ["roots", id, kind @ ("scan" | "clean")]) => {
+                let kind = if *kind == "scan" {
+                    JobKind::Scan
+                } else {
+                    JobKind::Clean
+                };
+                match id.parse().ok().filter(|&id: &usize| id < self.roots.len()) {
+                    Some(root) => self.start(root, kind),
+                    None => (404, error(&format!("no root {}", id))),
+                }
+            }
+            ("GET", ["jobs"]) => {
+                let jobs = self
+                    .jobs()
+                    .iter()
+                    .map(|job| job.to_json(self, false))
+                    .collect();
+                (200, Value::Array(jobs))
+            }
+            ("GET", ["jobs", id]) => match self.job(id) {
+                Some(job) => (200, job.to_json(self, true)),
+                None => (404, error(&format!("no job {}", id))),
+            },
+            ("GET", ["jobs", id, "events"]) => match self.job(id) {
+                Some(job) => return self.stream_events(stream, &job),
+                None => (404, error(&format!("no job {}", id))),
+            },
+            (_, ["roots"] | ["roots", _, "scan" | "clean"] | ["jobs", ..]) => {
+                (405, error("method not allowed"))
+            }
+            _ => (404, error("not found")),
+        };
+        respond(&mut stream, response)
+    }
+
+    fn authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        // Compares every byte, so the time taken does not tell how much of it matched.
+        let token = token.trim().as_bytes();
+        token.len() == self.token.len()
+            && token
+                .iter()
+                .zip(self.token.as_bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    fn roots_json(&self) -> Value {
+        let jobs = self.jobs();
+        let roots = self.roots.iter().enumerate().map(|(id, path)| {
+            let busy = jobs
+                .iter()
+                .any(|job| job.root == id && job.state().outcome.is_none());
+            json!({ "id": id, "path": path, "busy": busy })
+        });
+        Value::Array(roots.collect())
+    }
+
+    /// Starts a job on root `root`, unless one is running there.
+    fn start(&self, root: usize, kind: JobKind) -> Response {
+        let job = {
+            let mut jobs = self.jobs();
+            if jobs
+                .iter()
+                .any(|job| job.root == root && job.state().outcome.is_none())
+            {
+                return (409, error("a job is already running on this root"));
+            }
+            let job = Arc::new(Job {
+                id: self.next_job.fetch_add(1, Ordering::Relaxed),
+                root,
+                kind,
+                started_at: unix_now(),
+                state: Mutex::new(JobState::default()),
+            });
+            jobs.push_front(Arc::clone(&job));
+            while jobs.len() > HISTORY_LIMIT {
+                match jobs.iter().rposition(|job| job.state().outcome.is_some()) {
+                    Some(oldest) => jobs.remove(oldest),
+                    None => break,
+                };
+            }
+            job
+        };
+
+        let cleaner = self.cleaner.clone();
+        let path = self.roots[root].clone();
+        let runner = Arc::clone(&job);
+        thread::spawn(move || {
+            let outcome = runner.run(cleaner, path).map_err(|err| err.to_string());
+            let mut state = runner.state();
+            state.finished_at = Some(unix_now());
+            state.outcome = Some(outcome);
+        });
+        (202, job.to_json(self, false))
+    }
+
+    /// Sends the progress of `job` as server-sent events until it finishes.
+    fn stream_events(&self, mut stream: TcpStream, job: &Job) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+        )?;
+        let mut last = None;
+        loop {
+            let (progress, finished) = {
+                let state = job.state();
+                let progress = json!({
+                    "phase": state.phase,
+                    "done": state.done,
+                    "total": state.total,
+                });
+                (progress, state.outcome.is_some())
+            };
+            if finished {
+                write!(stream, "event: done\ndata: {}\n\n", job.to_json(self, true))?;
+                return stream.flush();
+            }
+            if last.as_ref() != Some(&progress) {
+                // The client is gone, or stopped reading, if the write fails or times
+                // out, which ends the stream and frees its connection.
+                write!(stream, "event: progress\ndata: {}\n\n", progress)?;
+                stream.flush()?;
+                last = Some(progress);
+            }
+            thread::sleep(PROGRESS_INTERVAL);
+        }
+    }
+
+    fn job(&self, id: &str) -> Option<Arc<Job>> {
+        let id: u64 = id.parse().ok()?;
+        self.jobs().iter().find(|job| job.id == id).cloned()
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, VecDeque<Arc<Job>>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The parts of an HTTP request the API looks at.
+struct Request {
+    method: String,
+    target: String,
+    authorization: Option<String>,
+}
+
+impl Request {
+    /// Reads the request line and headers, and discards the body. A body over
+    /// [`MAX_BODY_BYTES`], or one whose `Content-Length` cannot be parsed, is not read;
+    /// the response refusing the request is returned instead.
+    fn read(stream: &mut TcpStream) -> io::Result<std::result::Result<Self, Response>> {
+        let mut reader = BufReader::new(stream).take(MAX_HEAD_BYTES);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default().to_string();
+
+        let mut authorization = None;
+        let mut content_length = Some(0);
+        for _ in 0..MAX_HEADERS {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<u64>().ok();
+            }
+        }
+        if reader.limit() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request headers are too large",
+            ));
+        }
+        let content_length = match content_length {
+            Some(length) if length > MAX_BODY_BYTES => {
+                return Ok(Err((413, error("request body is too large"))));
+            }
+            Some(length) => length,
+            None => return Ok(Err((400, error("invalid Content-Length")))),
+        };
+        io::copy(
+            &mut reader.into_inner().take(content_length),
+            &mut io::sink(),
+        )?;
+        Ok(Ok(Self {
+            method,
+            target,
+            authorization,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobKind {
+    Scan,
+    Clean,
+}
+
+/// A scan or clean started through the API.
+struct Job {
+    id: u64,
+    /// The index of the root in the server's roots.
+    root: usize,
+    kind: JobKind,
+    started_at: u64,
+    state: Mutex<JobState>,
+}
+
+#[derive(Default)]
+struct JobState {
+    phase: String,
+    done: u64,
+    total: Option<u64>,
+    finished_at: Option<u64>,
+    /// The result or error, once finished.
+    outcome: Option<std::result::Result<Value, String>>,
+}
+
+impl Job {
+    fn run(self: &Arc<Self>, cleaner: Cleaner, root: PathBuf) -> Result<Value> {
+        let root = check_root(cleaner.config(), &root)?;
+        let plan = cleaner
+            .clone()
+            .with_progress(self.progress("scan", None))
+            .plan(root)?;
+        if self.kind == JobKind::Scan {
+            return Ok(serde_json::to_value(plan)?);
+        }
+        let progress = self.progress("clean", Some(plan.items.len() as u64));
+        let report = cleaner.with_progress(progress).apply(plan)?;
+        Ok(serde_json::to_value(report)?)
+    }
+
+    /// Starts phase `phase` of the job, returning the progress to report it to.
+    fn progress(self: &Arc<Self>, phase: &str, total: Option<u64>) -> Arc<dyn Progress> {
+        let progress = JobProgress(Arc::clone(self));
+        progress.begin_phase(phase, total);
+        Arc::new(progress)
+    }
+
+    fn to_json(&self, server: &Server, with_outcome: bool) -> Value {
+        let state = self.state();
+        let status = match &state.outcome {
+            None => "running",
+            Some(Ok(_)) => "succeeded",
+            Some(Err(_)) => "failed",
+        };
+        let mut job = json!({
+            "id": self.id,
+            "root": server.roots[self.root],
+            "kind": self.kind,
+            "status": status,
+            "started_at": self.started_at,
+            "finished_at": state.finished_at,
+            "progress": { "phase": state.phase, "done": state.done, "total": state.total },
+        });
+        match &state.outcome {
+            Some(Ok(result)) if with_outcome => job["result"] = result.clone(),
+            Some(Err(message)) => job["error"] = json!(message),
+            _ => {}
+        }
+        job
+    }
+
+    fn state(&self) -> MutexGuard<'_, JobState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Counts the items handled by a job.
+struct JobProgress(Arc<Job>);
+
+impl Progress for JobProgress {
+    fn increment(&self, delta: u64) {
+        self.0.state().done += delta;
+    }
+
+    fn set_message(&self, _msg: &str) {}
+
+    fn finish(&self) {}
+
+    fn begin_phase(&self, name: &str, total: Option<u64>) {
+        let mut state = self.0.state();
+        state.phase = name.to_string();
+        state.done = 0;
+        state.total = total;
+    }
+
+    fn set_total(&self, total: u64) {
+        self.0.state().total = Some(total);
+    }
+}
+
+fn respond(stream: &mut TcpStream, (status, body): Response) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Content Too Large",
+        _ => "Internal Server Error",
+    };
+    let challenge = if status == 401 {
+        "WWW-Authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        challenge,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn error(message: &str) -> Value {
+    json!({ "error": message })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn request(addr: SocketAddr, method: &str, path: &str, token: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\r\n",
+            method, path, token
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn test_clean_a_root_over_http() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        let cleaner = Cleaner::new(Config::default()).with_quiet(true);
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let roots = vec![temp.path().to_path_buf()];
+        let addr = serve_http(localhost, cleaner, roots, "secret".to_string()).unwrap();
+
+        assert_eq!(request(addr, "GET", "/roots", "wrong").0, 401);
+        assert_eq!(request(addr, "POST", "/roots/1/clean", "secret").0, 404);
+        let (status, roots) = request(addr, "GET", "/roots", "secret");
+        assert_eq!(status, 200);
+        assert!(roots.contains("\"busy\":false"));
+
+        let (status, job) = request(addr, "POST", "/roots/0/clean", "secret");
+        assert_eq!(status, 202);
+        let job: Value = serde_json::from_str(&job).unwrap();
+        let events_path = format!("/jobs/{}/events", job["id"]);
+        let (status, events) = request(addr, "GET", &events_path, "secret");
+        assert_eq!(status, 200);
+        let done = events.split("event: done\ndata: ").nth(1).unwrap();
+        let done: Value = serde_json::from_str(done.trim()).unwrap();
+        assert_eq!(done["status"], "succeeded");
+        assert_eq!(done["result"]["items_deleted"], 1);
+        assert!(!temp.child("app/target").exists());
+
+        let (_, jobs) = request(addr, "GET", "/jobs", "secret");
+        let jobs: Value = serde_json::from_str(&jobs).unwrap();
+        assert_eq!(jobs.as_array().unwrap().len(), 1);
+        assert!(jobs[0]["result"].is_null());
+    }
+
+    #[test]
+    fn test_oversized_headers_are_not_answered() {
+        let temp = TempDir::new().unwrap();
+        let cleaner = Cleaner::new(Config::default()).with_quiet(true);
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let roots = vec![temp.path().to_path_buf()];
+        let addr = serve_http(localhost, cleaner, roots, "secret".to_string()).unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let padding = "x".repeat(MAX_HEAD_BYTES as usize);
+        let _ = write!(
+            stream,
+            "GET /roots HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+            padding
+        );
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert!(response.is_empty(), "{}", response);
+        assert_eq!(request(addr, "GET", "/roots", "secret").0, 200);
+    }
+
+    #[test]
+    fn test_bodies_too_large_or_of_unknown_length_are_refused() {
+        let temp = TempDir::new().unwrap();
+        let cleaner = Cleaner::new(Config::default()).with_quiet(true);
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let roots = vec![temp.path().to_path_buf()];
+        let addr = serve_http(localhost, cleaner, roots, "secret".to_string()).unwrap();
+
+        let status = |content_length: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST /roots/0/scan HTTP/1.1\r\nAuthorization: Bearer secret\r\n\
+                 Content-Length: {}\r\n\r\n",
+                content_length
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response[9..12].parse::<u16>().unwrap()
+        };
+        assert_eq!(status(&(MAX_BODY_BYTES + 1).to_string()), 413);
+        assert_eq!(status("lots"), 400);
+        assert_eq!(status("-1"), 400);
+    }
+
+    #[test]
+    fn test_clients_that_stop_reading_are_dropped() {
+        let temp = TempDir::new().unwrap();
+        let server = Server {
+            cleaner: Cleaner::new(Config::default()).with_quiet(true),
+            roots: vec![temp.path().to_path_buf()],
+            token: "secret".to_string(),
+            jobs: Mutex::new(VecDeque::new()),
+            next_job: AtomicU64::new(2),
+            write_timeout: Duration::from_secs(1),
+        };
+        // A result far larger than the socket buffers hold.
+        let job = Job {
+            id: 1,
+            root: 0,
+            kind: JobKind::Scan,
+            started_at: unix_now(),
+            state: Mutex::new(JobState {
+                outcome: Some(Ok(json!("x".repeat(64 << 20)))),
+                ..JobState::default()
+            }),
+        };
+        server.jobs().push_front(Arc::new(job));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        write!(
+            client,
+            "GET /jobs/1/events HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n"
+        )
+        .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (done, finished) = std::sync::mpsc::channel();
+        thread::spawn(move || done.send(server.handle(stream)).unwrap());
+
+        // The client never reads, so the done event cannot be sent and the connection
+        // is given up on instead of blocking its thread.
+        let handled = finished.recv_timeout(Duration::from_secs(60)).unwrap();
+        let err = handled.unwrap_err();
+        assert!(
+            matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            "{}",
+            err
+        );
+        drop(client);
+    }
+
+    #[test]
+    fn test_generated_tokens_are_32_hex_digits() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token().unwrap());
+    }
+}
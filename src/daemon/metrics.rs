@@ -0,0 +1,224 @@
+//! Prometheus metrics for daemon mode.
+//!
+//! [`Metrics`] holds lock-free counters and gauges updated after every watch cycle, and
+//! [`serve_metrics`] exposes them on `GET /metrics`. The listener is intentionally
+//! minimal (one thread, one request per connection) since scrapes are infrequent and
+//! the endpoint should never compete with scanning for resources.
+
+use crate::types::{CleanReport, Result};
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Thread-safe metrics updated by the watch loop.
+#[derive(Default)]
+pub struct Metrics {
+    /// Bytes that would be freed by cleaning right now (gauge).
+    pub reclaimable_bytes: AtomicU64,
+    /// Bytes freed across all runs (counter).
+    pub cleaned_bytes_total: AtomicU64,
+    /// Items deleted across all runs (counter).
+    pub items_deleted_total: AtomicU64,
+    /// Deletion and scan errors across all runs (counter).
+    pub errors_total: AtomicU64,
+    /// Unix timestamp of the last completed run (gauge).
+    pub last_run_timestamp: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the reclaimable space found by the latest scan.
+    pub fn record_scan(&self, reclaimable_bytes: u64, scan_errors: usize) {
+        self.reclaimable_bytes
+            .store(reclaimable_bytes, Ordering::Relaxed);
+        self.errors_total
+            .fetch_add(scan_errors as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// Records the outcome of a clean. Dry-run reports do not count as cleaned bytes.
+    pub fn record_clean(&self, report: &CleanReport) {
+        if !report.dry_run {
+            self.cleaned_bytes_total
+                .fetch_add(report.bytes_freed, Ordering::Relaxed);
+            self.items_deleted_total
+                .fetch_add(report.items_deleted as u64, Ordering::Relaxed);
+            self.reclaimable_bytes.fetch_sub(
+                report
+                    .bytes_freed
+                    .min(self.reclaimable_bytes.load(Ordering::Relaxed)),
+                Ordering::Relaxed,
+            );
+        }
+        self.errors_total
+            .fetch_add(report.errors.len() as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// Updates the last-run timestamp to the current time.
+    fn touch(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.last_run_timestamp.store(now, Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let metrics = [
+            (
+                "mc_reclaimable_bytes",
+                "gauge",
+                "Bytes that would be freed by cleaning now.",
+                &self.reclaimable_bytes,
+            ),
+            (
+                "mc_cleaned_bytes_total",
+                "counter",
+                "Bytes freed since the daemon started.",
+                &self.cleaned_bytes_total,
+            ),
+            (
+                "mc_items_deleted_total",
+                "counter",
+                "Items deleted since the daemon started.",
+                &self.items_deleted_total,
+            ),
+            (
+                "mc_errors_total",
+                "counter",
+                "Scan and deletion errors since the daemon started.",
+                &self.errors_total,
+            ),
+            (
+                "mc_last_run_timestamp_seconds",
+                "gauge",
+                "Unix time of the last completed run.",
+                &self.last_run_timestamp,
+            ),
+        ];
+
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+        out
+    }
+}
+
+/// How long a metrics client may take to send its request or read the answer, so an
+/// idle connection cannot stall the server.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Binds `addr` and serves `GET /metrics` on a background thread.
+///
+/// Returns the bound address, which differs from `addr` when port `0` was requested.
+pub fn serve_metrics(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    log::info!("Serving metrics on http://{}/metrics", local_addr);
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(err) = handle_connection(stream, &metrics) {
+                        log::debug!("Metrics connection error: {}", err);
+                    }
+                }
+                Err(err) => log::debug!("Metrics accept error: {}", err),
+            }
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// Answers a single HTTP request on `stream`.
+fn handle_connection(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut buf = [0u8; 1024];
+    let read = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..read]);
+    let mut parts = request.split_whitespace();
+
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_all_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_scan(1234, 2);
+        let text = metrics.render();
+
+        assert!(text.contains("# TYPE mc_reclaimable_bytes gauge"));
+        assert!(text.contains("mc_reclaimable_bytes 1234"));
+        assert!(text.contains("mc_errors_total 2"));
+        assert!(text.contains("mc_cleaned_bytes_total 0"));
+        assert!(text.contains("mc_items_deleted_total 0"));
+        assert!(!text.contains("mc_last_run_timestamp_seconds 0\n"));
+    }
+
+    #[test]
+    fn test_record_clean_ignores_dry_run_bytes() {
+        let metrics = Metrics::new();
+        metrics.record_scan(500, 0);
+        metrics.record_clean(&CleanReport {
+            dry_run: true,
+            bytes_freed: 500,
+            items_deleted: 3,
+            ..Default::default()
+        });
+        assert_eq!(metrics.cleaned_bytes_total.load(Ordering::Relaxed), 0);
+
+        metrics.record_clean(&CleanReport {
+            bytes_freed: 400,
+            items_deleted: 2,
+            ..Default::default()
+        });
+        assert_eq!(metrics.cleaned_bytes_total.load(Ordering::Relaxed), 400);
+        assert_eq!(metrics.items_deleted_total.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.reclaimable_bytes.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn test_serve_metrics_responds() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_scan(42, 0);
+        let addr = serve_metrics("127.0.0.1:0".parse().unwrap(), metrics).unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("mc_reclaimable_bytes 42"));
+    }
+}
@@ -0,0 +1,33 @@
+//! This module contains the building blocks for running `mc` as a long-lived process.
+//!
+//! `mc watch` rescans a root on an interval, raising alerts and optionally cleaning.
+//! The counters it maintains are exposed in the Prometheus text exposition format
+//! through a small HTTP listener so fleet operators can graph artifact growth.
+//!
+//! `mc serve --stdio` answers JSON-RPC requests from a GUI or editor extension that
+//! runs mc as a subprocess, and `mc serve --http` exposes the configured roots to
+//! dashboards and scripts over a REST API.
+
+pub mod http;
+pub mod metrics;
+pub mod rpc;
+
+pub use metrics::{serve_metrics, Metrics};
+
+use crate::config::Config;
+use crate::safety::SafetyGuard;
+use crate::types::Result;
+use std::path::{Path, PathBuf};
+
+/// Runs the checks `mc` runs before cleaning a root from the command line, and returns
+/// the canonical root.
+pub(crate) fn check_root(config: &Config, root: &Path) -> Result<PathBuf> {
+    let root = root.canonicalize()?;
+    let guard = SafetyGuard::new(
+        config.safety.refuses_git_root(),
+        config.safety.max_depth,
+        config.safety.min_free_space_gb,
+    );
+    guard.validate(&root)?;
+    Ok(root)
+}
@@ -0,0 +1,516 @@
+//! A JSON-RPC 2.0 server over stdio, for GUIs and editor extensions that drive mc as a
+//! subprocess.
+//!
+//! Messages are one JSON object per line in each direction. Requests are handled on
+//! threads of their own, so a long scan does not hold up a `cancel`; responses may
+//! therefore arrive out of order and are matched to requests by `id`.
+//!
+//! # Methods
+//!
+//! - `scan {root}` returns the [`ScanOutcome`](crate::ScanOutcome) of `root`. The root
+//!   is checked like `mc <path>` checks it.
+//! - `plan {root}` returns `{plan_id, plan}`, the [`Plan`] for cleaning `root`, kept by
+//!   the server until it is applied. The root is checked as for `scan`.
+//! - `apply {plan_id}` deletes the items of a plan, without scanning again, and returns
+//!   the [`CleanReport`](crate::CleanReport). A plan is applied at most once.
+//! - `cancel {id}` stops the running request with that `id`. A cancelled `scan` or
+//!   `plan` fails with code [`REQUEST_CANCELLED`]; a cancelled `apply` returns the
+//!   report of what it deleted, counting the items it left in `items_not_attempted`.
+//!
+//! Nothing is confirmed, so items holding modified or untracked files in a git
+//! repository are left out of plans and listed in their `skipped`, unless the
+//! [`Cleaner`] was built [allowing them](Cleaner::with_allow_uncommitted).
+//!
+//! While a request runs, the server sends `progress` notifications,
+//! `{"id", "phase", "done", "total"}`, at most every [`PROGRESS_INTERVAL`]: the items
+//! matched so far while scanning and planning, and the items handled while applying.
+//! `total` is `null` while unknown.
+//!
+//! The server exits when its input ends, after the running requests finish.
+
+use super::check_root;
+use crate::types::Result;
+use crate::utils::progress::Progress;
+use crate::{Cleaner, Plan};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The error code of a request stopped by `cancel`.
+pub const REQUEST_CANCELLED: i64 = -32800;
+/// The error code of a request whose method failed.
+pub const REQUEST_FAILED: i64 = -32000;
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// How often progress notifications are sent at most, per request.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Serves JSON-RPC requests read from stdin, answering on stdout.
+///
+/// Hooks, action commands and other programs mc runs from then on get an empty stdin
+/// and write their output to stderr, so they cannot disturb the stream.
+///
+/// # Errors
+///
+/// Returns an error if stdin cannot be read.
+pub fn serve_stdio(cleaner: Cleaner) -> Result<()> {
+    crate::hooks::reserve_stdio();
+    serve(cleaner, std::io::stdin().lock(), std::io::stdout())
+}
+
+/// Serves JSON-RPC requests read from `input`, answering on `output`, until `input`
+/// ends. Every scan and clean is run by `cleaner`, which should be quiet.
+///
+/// # Errors
+///
+/// Returns an error if `input` cannot be read.
+pub fn serve(
+    cleaner: Cleaner,
+    input: impl BufRead,
+    output: impl Write + Send + 'static,
+) -> Result<()> {
+    let server = Server::new(cleaner, Box::new(output));
+    thread::scope(|scope| {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Value = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(err) => {
+                    server
+                        .output
+                        .error(&Value::Null, PARSE_ERROR, &err.to_string());
+                    continue;
+                }
+            };
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            let Some(method) = request.get("method").and_then(Value::as_str) else {
+                server.output.error(&id, INVALID_REQUEST, "missing method");
+                continue;
+            };
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            match method {
+                // Cancelling is answered at once, not queued behind the request.
+                "cancel" => server.cancel(&id, &params),
+                "scan" | "plan" | "apply" => {
+                    let cancel = Arc::new(AtomicBool::new(false));
+                    server.running().insert(id.to_string(), Arc::clone(&cancel));
+                    let method = method.to_string();
+                    let server = &server;
+                    scope.spawn(move || {
+                        let result = server.call(&method, &id, params, &cancel);
+                        server.running().remove(&id.to_string());
+                        match result {
+                            Ok(result) => server.output.result(&id, result),
+                            Err(_) if cancel.load(Ordering::Relaxed) => {
+                                server.output.error(&id, REQUEST_CANCELLED, "cancelled")
+                            }
+                            Err((code, message)) => server.output.error(&id, code, &message),
+                        }
+                    });
+                }
+                _ => {
+                    let message = format!("unknown method `{}`", method);
+                    server.output.error(&id, METHOD_NOT_FOUND, &message);
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The state shared by the requests of one connection.
+struct Server {
+    cleaner: Cleaner,
+    output: Arc<Output>,
+    /// Plans waiting to be applied, by id.
+    plans: Mutex<HashMap<u64, Plan>>,
+    next_plan: AtomicU64,
+    /// The cancel flags of the running requests, by request id.
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// A failed call: the JSON-RPC error code and message.
+type CallError = (i64, String);
+
+impl Server {
+    fn new(cleaner: Cleaner, output: Box<dyn Write + Send>) -> Self {
+        Self {
+            cleaner,
+            output: Arc::new(Output(Mutex::new(output))),
+            plans: Mutex::new(HashMap::new()),
+            next_plan: AtomicU64::new(1),
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        id: &Value,
+        params: Value,
+        cancel: &Arc<AtomicBool>,
+    ) -> std::result::Result<Value, CallError> {
+        let failed = |err: crate::McError| (REQUEST_FAILED, err.to_string());
+        match method {
+            "scan" => {
+                let root = string_param(&params, "root")?;
+                let root = check_root(self.cleaner.config(), Path::new(root)).map_err(failed)?;
+                let progress = self.progress(id, "scan", None);
+                let outcome = self
+                    .cleaner
+                    .scanner(root)
+                    .map_err(failed)?
+                    .with_progress(progress.clone())
+                    .with_cancel(Arc::clone(cancel))
+                    .scan()
+                    .map_err(failed)?;
+                progress.finish();
+                cancelled(cancel)?;
+                serde_json::to_value(outcome).map_err(|err| failed(err.into()))
+            }
+            "plan" => {
+                let root = string_param(&params, "root")?;
+                let root = check_root(self.cleaner.config(), Path::new(root)).map_err(failed)?;
+                let progress = self.progress(id, "scan", None);
+                let plan = self
+                    .cleaner
+                    .clone()
+                    .with_progress(progress.clone())
+                    .with_cancel(Arc::clone(cancel))
+                    .plan(root)
+                    .map_err(failed)?;
+                progress.finish();
+                cancelled(cancel)?;
+                let plan_id = self.next_plan.fetch_add(1, Ordering::Relaxed);
+                let response = json!({ "plan_id": plan_id, "plan": &plan });
+                self.plans
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(plan_id, plan);
+                Ok(response)
+            }
+            "apply" => {
+                let plan_id = params
+                    .get("plan_id")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| (INVALID_PARAMS, "missing or invalid `plan_id`".to_string()))?;
+                let plan = self
+                    .plans
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&plan_id);
+                let plan =
+                    plan.ok_or_else(|| (INVALID_PARAMS, format!("no plan {} to apply", plan_id)))?;
+                let progress = self.progress(id, "clean", Some(plan.items.len() as u64));
+                let report = self
+                    .cleaner
+                    .clone()
+                    .with_progress(progress.clone())
+                    .with_cancel(Arc::clone(cancel))
+                    .apply(plan)
+                    .map_err(failed)?;
+                progress.finish();
+                serde_json::to_value(report).map_err(|err| failed(err.into()))
+            }
+            _ => unreachable!("only known methods are called"),
+        }
+    }
+
+    fn cancel(&self, id: &Value, params: &Value) {
+        let Some(target) = params.get("id") else {
+            self.output.error(id, INVALID_PARAMS, "missing `id`");
+            return;
+        };
+        if let Some(cancel) = self.running().get(&target.to_string()) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        // Cancelling a request that already finished is not an error.
+        self.output.result(id, Value::Null);
+    }
+
+    fn progress(&self, id: &Value, phase: &str, total: Option<u64>) -> Arc<dyn Progress> {
+        Arc::new(RpcProgress {
+            output: Arc::clone(&self.output),
+            id: id.clone(),
+            phase: Mutex::new(phase.to_string()),
+            total: Mutex::new(total),
+            done: AtomicU64::new(0),
+            last_sent: Mutex::new(None),
+        })
+    }
+
+    fn running(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<AtomicBool>>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Fails a call whose request was cancelled, even if it returned a partial result.
+fn cancelled(cancel: &AtomicBool) -> std::result::Result<(), CallError> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err((REQUEST_CANCELLED, "cancelled".to_string()));
+    }
+    Ok(())
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> std::result::Result<&'a str, CallError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| (INVALID_PARAMS, format!("missing or invalid `{}`", name)))
+}
+
+/// The output stream, shared by the request threads.
+struct Output(Mutex<Box<dyn Write + Send>>);
+
+impl Output {
+    fn send(&self, message: Value) {
+        let mut writer = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        // The client is gone if the write fails; the requests still finish.
+        let _ = writeln!(writer, "{}", message).and_then(|()| writer.flush());
+    }
+
+    fn result(&self, id: &Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+
+    fn error(&self, id: &Value, code: i64, message: &str) {
+        let error = json!({ "code": code, "message": message });
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "error": error }));
+    }
+}
+
+/// Sends `progress` notifications for one request.
+struct RpcProgress {
+    output: Arc<Output>,
+    id: Value,
+    phase: Mutex<String>,
+    total: Mutex<Option<u64>>,
+    done: AtomicU64,
+    last_sent: Mutex<Option<Instant>>,
+}
+
+impl RpcProgress {
+    fn notify(&self) {
+        let params = json!({
+            "id": self.id,
+            "phase": *self.phase.lock().unwrap_or_else(|e| e.into_inner()),
+            "done": self.done.load(Ordering::Relaxed),
+            "total": *self.total.lock().unwrap_or_else(|e| e.into_inner()),
+        });
+        self.output
+            .send(json!({ "jsonrpc": "2.0", "method": "progress", "params": params }));
+    }
+}
+
+impl Progress for RpcProgress {
+    fn increment(&self, delta: u64) {
+        self.done.fetch_add(delta, Ordering::Relaxed);
+        let mut last_sent = self.last_sent.lock().unwrap_or_else(|e| e.into_inner());
+        if last_sent.is_none_or(|sent| sent.elapsed() >= PROGRESS_INTERVAL) {
+            *last_sent = Some(Instant::now());
+            self.notify();
+        }
+    }
+
+    fn set_message(&self, _msg: &str) {}
+
+    fn finish(&self) {
+        self.notify();
+    }
+
+    fn begin_phase(&self, name: &str, total: Option<u64>) {
+        *self.phase.lock().unwrap_or_else(|e| e.into_inner()) = name.to_string();
+        *self.total.lock().unwrap_or_else(|e| e.into_inner()) = total;
+        self.done.store(0, Ordering::Relaxed);
+    }
+
+    fn set_total(&self, total: u64) {
+        *self.total.lock().unwrap_or_else(|e| e.into_inner()) = Some(total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::io::Cursor;
+
+    /// Collects what the server writes.
+    #[derive(Clone, Default)]
+    struct Sink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(requests: &[Value]) -> Vec<Value> {
+        let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+        let sink = Sink::default();
+        let cleaner = Cleaner::new(Config::default()).with_quiet(true);
+        serve(cleaner, Cursor::new(input), sink.clone()).unwrap();
+        let output = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn response(messages: &[Value], id: u64) -> &Value {
+        let is_response = |message: &&Value| message["id"] == id && message["method"].is_null();
+        messages.iter().find(is_response).unwrap()
+    }
+
+    #[test]
+    fn test_scan_and_plan_over_json_rpc() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        let root = temp.path().to_str().unwrap();
+
+        let messages = run(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "scan", "params": { "root": root } }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "plan", "params": { "root": root } }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "bogus" }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "apply", "params": { "plan_id": 9 } }),
+        ]);
+
+        assert!(response(&messages, 1)["result"]["items"].is_array());
+        let plan = &response(&messages, 2)["result"];
+        assert_eq!(plan["plan_id"], 1);
+        assert_eq!(plan["plan"]["items"].as_array().unwrap().len(), 1);
+        assert_eq!(response(&messages, 3)["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(response(&messages, 4)["error"]["code"], INVALID_PARAMS);
+        assert!(messages
+            .iter()
+            .any(|message| message["method"] == "progress"));
+        assert!(temp.child("app/target").exists());
+    }
+
+    #[test]
+    fn test_apply_deletes_a_plan_once() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        let root = temp.path().to_str().unwrap();
+        let cleaner = Cleaner::new(Config::default()).with_quiet(true);
+        let server = Server::new(cleaner, Box::new(Sink::default()));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let id = json!(1);
+
+        let plan = server
+            .call("plan", &id, json!({ "root": root }), &cancel)
+            .unwrap();
+        let params = json!({ "plan_id": plan["plan_id"] });
+        let report = server.call("apply", &id, params.clone(), &cancel).unwrap();
+        assert_eq!(report["items_deleted"], 1);
+        assert!(!temp.child("app/target").exists());
+        assert!(server.call("apply", &id, params, &cancel).is_err());
+
+        cancel.store(true, Ordering::Relaxed);
+        let err = server
+            .call("plan", &id, json!({ "root": root }), &cancel)
+            .unwrap_err();
+        assert_eq!(err.0, REQUEST_CANCELLED);
+    }
+
+    /// Plans and applies a clean of `root` over RPC, returning the report.
+    fn plan_and_apply(cleaner: Cleaner, root: &Path) -> Value {
+        let server = Server::new(cleaner, Box::new(Sink::default()));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let id = json!(1);
+        let plan = server
+            .call("plan", &id, json!({ "root": root }), &cancel)
+            .unwrap();
+        let params = json!({ "plan_id": plan["plan_id"] });
+        server.call("apply", &id, params, &cancel).unwrap()
+    }
+
+    /// Creates `app`, a git repository below `temp`, with an untracked file under
+    /// `app/target`, or returns `None` if git is not installed.
+    fn repo_with_untracked_target(temp: &TempDir) -> Option<()> {
+        temp.child("app/target/dev.sqlite")
+            .write_str("rows")
+            .unwrap();
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(temp.child("app").path())
+            .args(["init", "-q"])
+            .status()
+            .ok()?
+            .success()
+            .then_some(())
+    }
+
+    #[test]
+    fn test_apply_leaves_uncommitted_work_alone() {
+        let temp = TempDir::new().unwrap();
+        if repo_with_untracked_target(&temp).is_none() {
+            return;
+        }
+
+        let report = plan_and_apply(
+            Cleaner::new(Config::default()).with_quiet(true),
+            temp.path(),
+        );
+        assert_eq!(report["items_deleted"], 0);
+        let skipped = report["skipped"].as_array().unwrap();
+        assert_eq!(skipped.len(), 1, "{:?}", skipped);
+        assert_eq!(skipped[0]["reason"]["kind"], "uncommitted");
+        assert_eq!(skipped[0]["reason"]["files"], 1);
+        assert!(temp.child("app/target/dev.sqlite").exists());
+    }
+
+    #[test]
+    fn test_apply_cleans_uncommitted_work_when_allowed() {
+        let temp = TempDir::new().unwrap();
+        if repo_with_untracked_target(&temp).is_none() {
+            return;
+        }
+
+        let cleaner = Cleaner::new(Config::default())
+            .with_quiet(true)
+            .with_allow_uncommitted(true);
+        let report = plan_and_apply(cleaner, temp.path());
+        assert_eq!(report["items_deleted"], 1);
+        assert!(!temp.child("app/target").exists());
+    }
+
+    #[test]
+    fn test_scan_runs_the_root_checks() {
+        let server = Server::new(
+            Cleaner::new(Config::default()).with_quiet(true),
+            Box::new(Sink::default()),
+        );
+        let cancel = Arc::new(AtomicBool::new(false));
+        let repo = TempDir::new().unwrap();
+        repo.child(".git").create_dir_all().unwrap();
+
+        for root in [repo.path(), Path::new("/nonexistent/mc-root")] {
+            let err = server
+                .call("scan", &json!(1), json!({ "root": root }), &cancel)
+                .unwrap_err();
+            assert_eq!(err.0, REQUEST_FAILED, "{}", root.display());
+        }
+    }
+}
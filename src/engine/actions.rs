@@ -0,0 +1,205 @@
+//! This module decides what happens to each matched item: deletion, or a command
+//! configured through `[[actions]]`.
+//!
+//! Some tools manage their own caches better than a blind `remove_dir_all` can, e.g.
+//! `pnpm store prune` or `uv cache clean`. Items covered by an action rule are grouped by
+//! rule and handed to the command instead of the deletion pool.
+
+use crate::config::{ActionRule, CommandScope};
+use crate::hooks;
+use crate::types::{CleanError, CleanItem};
+use std::path::Path;
+
+/// The action to take for a matched item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action<'a> {
+    /// Delete the item from the file system.
+    Delete,
+    /// Run the rule's command instead of deleting.
+    Command(&'a ActionRule),
+}
+
+/// A group of items handled by the same action rule.
+pub struct CommandGroup<'a> {
+    /// The rule whose command handles these items.
+    pub rule: &'a ActionRule,
+    /// The items covered by the rule.
+    pub items: Vec<CleanItem>,
+}
+
+impl ActionRule {
+    /// Returns true if this rule applies to `item`.
+    pub fn applies_to(&self, item: &CleanItem) -> bool {
+        if self.category.is_none() && self.pattern.is_none() {
+            return false;
+        }
+        let category_ok = self
+            .category
+            .is_none_or(|category| category == item.pattern.category);
+        let pattern_ok = self
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| *pattern == item.pattern.pattern);
+        category_ok && pattern_ok
+    }
+}
+
+/// Resolves the action for `item`: the first matching rule, or deletion.
+pub fn resolve<'a>(rules: &'a [ActionRule], item: &CleanItem) -> Action<'a> {
+    rules
+        .iter()
+        .find(|rule| rule.applies_to(item))
+        .map_or(Action::Delete, Action::Command)
+}
+
+/// Splits `items` into those to delete and groups handled by action rules.
+///
+/// Groups are returned in rule order so command execution is deterministic.
+pub fn partition<'a>(
+    rules: &'a [ActionRule],
+    items: Vec<CleanItem>,
+) -> (Vec<CleanItem>, Vec<CommandGroup<'a>>) {
+    if rules.is_empty() {
+        return (items, Vec::new());
+    }
+
+    let mut to_delete = Vec::new();
+    let mut grouped: Vec<Vec<CleanItem>> = vec![Vec::new(); rules.len()];
+
+    for item in items {
+        match rules.iter().position(|rule| rule.applies_to(&item)) {
+            Some(idx) => grouped[idx].push(item),
+            None => to_delete.push(item),
+        }
+    }
+
+    let groups = rules
+        .iter()
+        .zip(grouped)
+        .filter(|(_, items)| !items.is_empty())
+        .map(|(rule, items)| CommandGroup { rule, items })
+        .collect();
+
+    (to_delete, groups)
+}
+
+impl CommandGroup<'_> {
+    /// The number of command invocations this group results in.
+    pub fn invocations(&self) -> usize {
+        match self.rule.scope {
+            CommandScope::Once => 1,
+            CommandScope::Item => self.items.len(),
+        }
+    }
+
+    /// Runs the group's command, returning one error per failed invocation.
+    pub fn run(&self) -> Vec<CleanError> {
+        self.run_items(|_, _| {})
+    }
+
+    /// Runs the command like [`CommandGroup::run`], calling `each` for every item with
+    /// the error of the invocation that covered it, if that invocation failed.
+    pub fn run_items<'a>(
+        &'a self,
+        mut each: impl FnMut(&'a CleanItem, Option<&CleanError>),
+    ) -> Vec<CleanError> {
+        match self.rule.scope {
+            CommandScope::Once => {
+                let env = [
+                    ("MC_ITEM_COUNT", self.items.len().to_string()),
+                    (
+                        "MC_PLANNED_BYTES",
+                        self.items.iter().map(|i| i.size).sum::<u64>().to_string(),
+                    ),
+                ];
+                let error = self.invoke(&env, None);
+                self.items
+                    .iter()
+                    .for_each(|item| each(item, error.as_ref()));
+                error.into_iter().collect()
+            }
+            CommandScope::Item => self
+                .items
+                .iter()
+                .filter_map(|item| {
+                    let env = [
+                        ("MC_ITEM_PATH", item.path.display().to_string()),
+                        ("MC_ITEM_SIZE", item.size.to_string()),
+                    ];
+                    let error = self.invoke(&env, item.path.parent());
+                    each(item, error.as_ref());
+                    error
+                })
+                .collect(),
+        }
+    }
+
+    fn invoke(&self, env: &[(&str, String)], cwd: Option<&Path>) -> Option<CleanError> {
+        hooks::run_command(&self.rule.command, env, None, cwd)
+            .err()
+            .map(|err| CleanError::CommandFailed {
+                command: self.rule.command.clone(),
+                message: err.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+
+    fn rule(category: Option<PatternCategory>, pattern: Option<&str>) -> ActionRule {
+        ActionRule {
+            category,
+            pattern: pattern.map(String::from),
+            command: "true".to_string(),
+            scope: CommandScope::Once,
+        }
+    }
+
+    #[test]
+    fn test_resolve_defaults_to_delete() {
+        let item = clean_item(
+            "/p/target",
+            ItemType::Directory,
+            10,
+            PatternCategory::BuildOutputs,
+        );
+        assert_eq!(resolve(&[], &item), Action::Delete);
+        let rules = [rule(Some(PatternCategory::Cache), None)];
+        assert_eq!(resolve(&rules, &item), Action::Delete);
+    }
+
+    #[test]
+    fn test_rule_requires_all_given_criteria() {
+        let item = clean_item("/p/.turbo", ItemType::Directory, 10, PatternCategory::Cache);
+        assert!(rule(Some(PatternCategory::Cache), None).applies_to(&item));
+        assert!(rule(None, Some(".turbo")).applies_to(&item));
+        assert!(!rule(Some(PatternCategory::Cache), Some("coverage")).applies_to(&item));
+        assert!(!rule(None, None).applies_to(&item));
+    }
+
+    #[test]
+    fn test_partition_groups_by_rule() {
+        let rules = [rule(Some(PatternCategory::Cache), None)];
+        let items = vec![
+            clean_item("/p/.turbo", ItemType::Directory, 10, PatternCategory::Cache),
+            clean_item(
+                "/p/target",
+                ItemType::Directory,
+                10,
+                PatternCategory::BuildOutputs,
+            ),
+            clean_item("/q/.turbo", ItemType::Directory, 10, PatternCategory::Cache),
+        ];
+
+        let (to_delete, groups) = partition(&rules, items);
+
+        assert_eq!(to_delete.len(), 1);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].items.len(), 2);
+        assert_eq!(groups[0].invocations(), 1);
+    }
+}
@@ -0,0 +1,285 @@
+//! Archiving matched items before they are deleted, for `[archive]`.
+//!
+//! Every item of a clean goes into a single tar archive, optionally zstd-compressed,
+//! named `mc-<unix seconds>.tar.zst` in the configured directory. Entries are stored
+//! under their absolute path without the leading `/`, the way `tar` itself stores
+//! absolute paths, so an archive can be restored with `tar -xf <archive> -C /`.
+//! Symbolic links are stored as links, never followed.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::ArchiveCompression;
+use crate::types::{CleanError, CleanItem, ItemType};
+use crate::utils::fs as fs_utils;
+
+/// The zstd compression level; the library default, which favors speed.
+const ZSTD_LEVEL: i32 = 3;
+
+/// An archive written by [`write`].
+#[derive(Debug, Clone)]
+pub struct Written {
+    /// The archive file.
+    pub path: PathBuf,
+    /// The items that could not be read into the archive, with why. They must not be
+    /// deleted, since the archive does not hold all of them.
+    pub failed: Vec<CleanError>,
+}
+
+/// Writes `items` to a new archive in `dir` and returns the archive's path, with the
+/// items that could not be archived.
+///
+/// `dir` may start with `~/` and is created if it does not exist. The archive is
+/// synced to disk before this returns, so the archived items can be deleted safely
+/// afterwards. Items that are gone are left out without an error.
+///
+/// # Errors
+///
+/// Returns the first I/O error creating or writing the archive. A partly written
+/// archive is removed.
+pub fn write(
+    items: &[CleanItem],
+    dir: &Path,
+    compression: ArchiveCompression,
+) -> io::Result<Written> {
+    let dir = fs_utils::expand_home(dir);
+    fs::create_dir_all(&dir)?;
+    let (path, file) = create_archive_file(&dir, compression)?;
+    let mut failed = Vec::new();
+    match write_archive(file, items, compression, &mut failed) {
+        Ok(()) => {
+            log::info!(
+                "Archived {} items to {}",
+                items.len() - failed.len(),
+                path.display()
+            );
+            Ok(Written { path, failed })
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&path);
+            Err(err)
+        }
+    }
+}
+
+/// Counts the archives in `dir` and the bytes they hold. A missing directory holds
+/// none.
+pub fn usage(dir: &Path) -> io::Result<(usize, u64)> {
+    let dir = fs_utils::expand_home(dir);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(err) => return Err(err),
+    };
+    let (mut count, mut bytes) = (0, 0);
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("mc-") && name.contains(".tar") && entry.file_type()?.is_file() {
+            count += 1;
+            bytes += entry.metadata()?.len();
+        }
+    }
+    Ok((count, bytes))
+}
+
+/// Writes the archive of `items` to `file` and syncs it.
+fn write_archive(
+    file: File,
+    items: &[CleanItem],
+    compression: ArchiveCompression,
+    failed: &mut Vec<CleanError>,
+) -> io::Result<()> {
+    let file = match compression {
+        ArchiveCompression::Zstd => {
+            let encoder = zstd::Encoder::new(file, ZSTD_LEVEL)?;
+            append_items(encoder, items, failed)?.finish()?
+        }
+        ArchiveCompression::None => append_items(file, items, failed)?,
+    };
+    file.sync_all()
+}
+
+/// Creates a new, uniquely named archive file in `dir`.
+fn create_archive_file(dir: &Path, compression: ArchiveCompression) -> io::Result<(PathBuf, File)> {
+    let extension = match compression {
+        ArchiveCompression::Zstd => "tar.zst",
+        ArchiveCompression::None => "tar",
+    };
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Two cleans within the same second get numbered archives.
+    let mut attempt = 0u32;
+    loop {
+        let name = match attempt {
+            0 => format!("mc-{}.{}", secs, extension),
+            n => format!("mc-{}-{}.{}", secs, n, extension),
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Appends every item to a tar stream written to `out` and returns `out`. Items that
+/// cannot be read are added to `failed`.
+fn append_items<W: Write>(
+    out: W,
+    items: &[CleanItem],
+    failed: &mut Vec<CleanError>,
+) -> io::Result<W> {
+    let mut builder = tar::Builder::new(Output {
+        inner: out,
+        failed: false,
+    });
+    builder.follow_symlinks(false);
+    for item in items {
+        let Err(err) = append_item(&mut builder, item) else {
+            continue;
+        };
+        if builder.get_ref().failed {
+            return Err(err);
+        }
+        if fs::symlink_metadata(&item.path).is_err() {
+            log::debug!("Not archived, already gone: {}", item.path.display());
+            continue;
+        }
+        log::warn!("Cannot archive {}: {}", item.path.display(), err);
+        failed.push(CleanError::IoError {
+            path: item.path.clone(),
+            message: format!("cannot archive it, so it was not deleted: {}", err),
+        });
+    }
+    let mut out = builder.into_inner()?.inner;
+    out.flush()?;
+    Ok(out)
+}
+
+/// Appends `item` to the tar stream of `builder`.
+fn append_item<W: Write>(builder: &mut tar::Builder<W>, item: &CleanItem) -> io::Result<()> {
+    let name = entry_name(&item.path);
+    let metadata = fs::symlink_metadata(&item.path)?;
+    if item.item_type == ItemType::Directory && metadata.is_dir() {
+        builder.append_dir_all(&name, &item.path)
+    } else {
+        builder.append_path_with_name(&item.path, &name)
+    }
+}
+
+/// A writer that remembers whether writing to it failed, to tell errors writing the
+/// archive from errors reading an item.
+struct Output<W> {
+    inner: W,
+    failed: bool,
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).inspect_err(|_| self.failed = true)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().inspect_err(|_| self.failed = true)
+    }
+}
+
+/// Returns the name `path` is stored under: the path without its root or prefix.
+fn entry_name(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::Prefix(_) | Component::RootDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_archive_holds_every_item() {
+        let temp = TempDir::new().unwrap();
+        temp.child("project/experiments/run1/out.csv")
+            .write_str("a,b")
+            .unwrap();
+        temp.child("project/debug.log").write_str("log").unwrap();
+        let items = [
+            clean_item(
+                temp.child("project/experiments").path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::Other,
+            ),
+            clean_item(
+                temp.child("project/debug.log").path(),
+                ItemType::File,
+                0,
+                PatternCategory::Other,
+            ),
+        ];
+
+        for compression in [ArchiveCompression::Zstd, ArchiveCompression::None] {
+            let written = write(&items, temp.child("archive").path(), compression).unwrap();
+            assert!(written.failed.is_empty());
+            let path = written.path;
+            let file = File::open(&path).unwrap();
+            let names: Vec<PathBuf> = match compression {
+                ArchiveCompression::Zstd => entries(zstd::Decoder::new(file).unwrap()),
+                ArchiveCompression::None => entries(file),
+            };
+
+            let log = entry_name(temp.child("project/debug.log").path());
+            let csv = entry_name(temp.child("project/experiments/run1/out.csv").path());
+            assert!(names.contains(&log), "{:?}", names);
+            assert!(names.contains(&csv), "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn test_archive_leaves_out_items_that_are_gone() {
+        let temp = TempDir::new().unwrap();
+        temp.child("project/debug.log").write_str("log").unwrap();
+        let items = [
+            clean_item(
+                temp.child("project/gone.log").path(),
+                ItemType::File,
+                0,
+                PatternCategory::Other,
+            ),
+            clean_item(
+                temp.child("project/debug.log").path(),
+                ItemType::File,
+                0,
+                PatternCategory::Other,
+            ),
+        ];
+
+        let written = write(
+            &items,
+            temp.child("archive").path(),
+            ArchiveCompression::None,
+        )
+        .unwrap();
+        assert!(written.failed.is_empty(), "{:?}", written.failed);
+        let names = entries(File::open(&written.path).unwrap());
+        assert_eq!(names, [entry_name(temp.child("project/debug.log").path())]);
+    }
+
+    fn entries<R: io::Read>(reader: R) -> Vec<PathBuf> {
+        tar::Archive::new(reader)
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect()
+    }
+}
@@ -6,23 +6,61 @@
 //!
 //! # Performance
 //!
-//! The cleaning process is parallelized by chunking the list of items to be deleted
-//! and processing each chunk on a separate thread in a `rayon` thread pool. This
-//! approach is effective for I/O-bound tasks like file deletion, as it allows the
-//! OS to handle multiple deletion requests simultaneously.
+//! The cleaning process is parallelized by handing the items to be deleted to the
+//! threads of a `rayon` thread pool, either one at a time with work stealing or in
+//! fixed-size chunks (see [`Scheduling`]). This approach is effective for I/O-bound
+//! tasks like file deletion, as it allows the OS to handle multiple deletion requests
+//! simultaneously.
 
+use crate::utils::format;
 use colored::*;
-use humansize::{format_size, DECIMAL};
 use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
 use std::fs;
 use std::io;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::types::{CleanError, CleanItem, CleanReport, ItemType, McError};
+use crate::config::{
+    ActionRule, ArchiveConfig, QuarantineConfig, Scheduling, SlowDeletePolicy, TrashConfig,
+    TrashFallback,
+};
+use crate::engine::actions::{self, CommandGroup};
+use crate::engine::partial::{self, Removal};
+use crate::engine::{archive, native, quarantine, shred, slow_delete, trash};
+use crate::patterns::PatternMatcher;
+use crate::plugins::Plugins;
+use crate::resume::Journal;
+use crate::safety::kill_switch;
+use crate::types::{
+    CleanError, CleanItem, CleanReport, ItemOutcome, ItemStatus, ItemType, LargestItem, McError,
+    PhaseTimings, PlannedItem, RoutedItem, SkipReason, TrashRoute,
+};
+use crate::utils::fs as fs_utils;
 use crate::utils::progress::Progress;
 
+/// The number of directories and files the dry-run listing shows by default.
+pub const DEFAULT_LISTING_LIMIT: usize = 20;
+
+/// The number of items per chunk with chunked scheduling by default.
+pub const DEFAULT_CHUNK_SIZE: usize = 100;
+
+/// The number of largest items a report lists by default.
+pub const DEFAULT_LARGEST_ITEMS: usize = 5;
+
+/// The number of abandoned deletions, still blocked on their threads, at which no
+/// further supervised deletion is started.
+const MAX_STUCK_DELETIONS: usize = 16;
+
+/// The abandoned deletions of this process whose threads have not returned yet. They
+/// outlive the clean that started them, so the count is shared by every cleaner.
+static STUCK_DELETIONS: AtomicUsize = AtomicUsize::new(0);
+
 /// A parallel cleaner that deletes items concurrently using a thread pool.
 ///
 /// `ParallelCleaner` is responsible for the actual deletion of files and directories.
@@ -31,10 +69,14 @@ use crate::utils::progress::Progress;
 pub struct ParallelCleaner {
     /// The number of threads to spawn in the `rayon` thread pool.
     thread_count: usize,
-    /// The number of items to process in each parallel chunk.
+    /// How items are handed out to the threads.
+    scheduling: Scheduling,
+    /// The number of items each thread takes at once with chunked scheduling.
     chunk_size: usize,
-    /// Reusable thread pool for file operations.
-    thread_pool: Arc<ThreadPool>,
+    /// The thread pool for file operations: one given by the caller, or one shared by
+    /// every cleaner with the same thread count. `None` until a pool is chosen; the
+    /// shared pool for `thread_count` is then used.
+    thread_pool: Option<Arc<ThreadPool>>,
     /// If true, no file system modifications will be made.
     dry_run: bool,
     /// If true, suppress human-readable output (for --json or --quiet).
@@ -43,6 +85,91 @@ pub struct ParallelCleaner {
     progress: Option<Arc<dyn Progress>>,
     /// A container for atomically updated statistics.
     stats: Arc<Statistics>,
+    /// Rules that hand matched items to a command instead of deleting them.
+    actions: Vec<ActionRule>,
+    /// How many directories and files the dry-run listing shows; `None` shows all.
+    listing_limit: Option<usize>,
+    /// The matcher whose excludes are kept when deleting directories, if any.
+    protection: Option<Arc<PatternMatcher>>,
+    /// Whether items are moved to the trash instead, and where those too large for the
+    /// Recycle Bin go.
+    trash: TrashConfig,
+    /// If true, file contents are overwritten with random bytes before removal.
+    secure_delete: bool,
+    /// Where items are archived before deletion; no archive is written without a `dir`.
+    archive: ArchiveConfig,
+    /// Where items are moved instead of being deleted; they are deleted without a `dir`.
+    quarantine: QuarantineConfig,
+    /// The journal deleted items are recorded in for `mc resume`, if any.
+    journal: Option<Arc<Journal>>,
+    /// How long deleting may take before the remaining items are left alone.
+    timeout: Option<Duration>,
+    /// How long a single deletion may take before it is abandoned.
+    item_timeout: Option<Duration>,
+    /// Called with the outcome of each item, if set.
+    on_outcome: Option<Arc<OutcomeHandler>>,
+    /// Stops the clean once set.
+    cancel: Option<Arc<AtomicBool>>,
+    /// Plugins offered each item before mc deletes it, if any.
+    plugins: Option<Arc<Plugins>>,
+    /// Whether deletes are timed before a clean, and what is done when they are slow.
+    slow_deletes: SlowDeletePolicy,
+    /// How many of the largest items the report lists.
+    largest_items: usize,
+    /// Whether items are deleted one at a time in path order and the report is sorted
+    /// by path.
+    deterministic: bool,
+    /// The items whose size is an estimate rather than measured.
+    estimated_sizes: HashSet<PathBuf>,
+}
+
+/// How [`remove_item`] removes the items of one clean.
+#[derive(Clone, Default)]
+struct RemoveOptions {
+    /// Whether file contents are overwritten before removal.
+    secure_delete: bool,
+    /// The matcher whose excludes are kept when deleting directories, if any.
+    protection: Option<Arc<PatternMatcher>>,
+    /// Plugins offered each item first, if any.
+    plugins: Option<Arc<Plugins>>,
+    /// The quarantine batch items are moved into instead of being deleted, if any.
+    batch: Option<PathBuf>,
+    /// Whether items are moved to the trash instead of being deleted.
+    trash: bool,
+    /// Whether directories are renamed aside before they are deleted.
+    rename_first: bool,
+}
+
+/// The items of a clean with trash on that do not fit in the Recycle Bin, by path:
+/// where they go instead and the most their Recycle Bin holds.
+type TrashFallbacks = HashMap<PathBuf, (TrashRoute, u64)>;
+
+/// A callback receiving the outcome of each item of a clean.
+type OutcomeHandler = dyn Fn(ItemOutcome) + Send + Sync;
+
+/// Returns the process-wide thread pool with `threads` threads, building it on first use.
+///
+/// Pools are kept for the life of the process, so cleaners created over and over, as
+/// in watch mode or by library users, reuse the same threads instead of spawning new
+/// ones each time.
+fn shared_pool(threads: usize) -> std::result::Result<Arc<ThreadPool>, McError> {
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<ThreadPool>>>> = OnceLock::new();
+
+    let mut pools = POOLS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = Arc::new(
+        ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| McError::ThreadPool(e.to_string()))?,
+    );
+    pools.insert(threads, Arc::clone(&pool));
+    Ok(pool)
 }
 
 /// Thread-safe counters updated during parallel deletion.
@@ -57,44 +184,88 @@ pub struct Statistics {
     pub dirs_deleted: AtomicUsize,
     /// The number of files successfully deleted.
     pub files_deleted: AtomicUsize,
+    /// The number of items that were already gone when their deletion was attempted.
+    pub already_gone: AtomicUsize,
+    /// The number of protected paths left in directories that were otherwise deleted.
+    pub paths_kept: AtomicUsize,
 }
 
 impl ParallelCleaner {
     /// Creates a new `ParallelCleaner`.
     ///
-    /// Returns an error if the thread pool cannot be created (e.g., resource exhaustion).
+    /// No thread pool is created here: unless one is set with [`Self::with_threads`] or
+    /// [`Self::with_thread_pool`], the shared pool with one thread per core is built on
+    /// the first clean.
     pub fn new() -> std::result::Result<Self, McError> {
         let thread_count = crate::utils::available_parallelism();
         log::debug!("ParallelCleaner: {} threads", thread_count);
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(thread_count)
-            .build()
-            .map_err(|e| McError::ThreadPool(e.to_string()))?;
         Ok(Self {
             thread_count,
-            chunk_size: 1,
-            thread_pool: Arc::new(thread_pool),
+            scheduling: Scheduling::default(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            thread_pool: None,
             dry_run: false,
             quiet: false,
             progress: None,
             stats: Arc::new(Statistics::default()),
+            actions: Vec::new(),
+            listing_limit: Some(DEFAULT_LISTING_LIMIT),
+            protection: None,
+            trash: TrashConfig::default(),
+            secure_delete: false,
+            archive: ArchiveConfig::default(),
+            quarantine: QuarantineConfig::default(),
+            journal: None,
+            timeout: None,
+            item_timeout: None,
+            on_outcome: None,
+            cancel: None,
+            plugins: None,
+            slow_deletes: SlowDeletePolicy::default(),
+            largest_items: DEFAULT_LARGEST_ITEMS,
+            deterministic: false,
+            estimated_sizes: HashSet::new(),
         })
     }
 
     /// Sets the number of threads to use for cleaning.
     ///
-    /// Returns an error if the thread pool cannot be rebuilt.
+    /// Returns an error if the thread pool cannot be created.
     pub fn with_threads(mut self, count: usize) -> std::result::Result<Self, McError> {
         self.thread_count = count;
-        self.thread_pool = Arc::new(
-            ThreadPoolBuilder::new()
-                .num_threads(count)
-                .build()
-                .map_err(|e| McError::ThreadPool(e.to_string()))?,
-        );
+        self.thread_pool = Some(shared_pool(count)?);
         Ok(self)
     }
 
+    /// Cleans on `pool` instead of a thread pool of mc's own.
+    ///
+    /// This lets an application that embeds mc next to other parallel work keep every
+    /// thread in one budget. The thread count becomes the pool's, and the pool is
+    /// used as it is, so progress and deletions share it with whatever else it runs.
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.thread_count = pool.current_num_threads();
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Sets how items are handed out to the threads.
+    ///
+    /// Work stealing, the default, lets idle threads take single items from busy ones,
+    /// so one huge directory never holds up the rest. Chunked scheduling hands out
+    /// `chunk_size` items at a time, which costs less coordination when there are
+    /// many small items of similar size.
+    pub fn with_scheduling(mut self, scheduling: Scheduling) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    /// Sets the number of items a thread takes at once with chunked scheduling.
+    /// Values below 1 are treated as 1.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
     /// Sets the dry run mode.
     ///
     /// In dry run mode, the cleaner will report what it would delete but will not
@@ -118,6 +289,149 @@ impl ParallelCleaner {
         self
     }
 
+    /// Sets how many directories and files the dry-run listing shows. `None` shows all.
+    pub fn with_listing_limit(mut self, limit: Option<usize>) -> Self {
+        self.listing_limit = limit;
+        self
+    }
+
+    /// Sets the action rules used to route items to commands instead of deletion.
+    pub fn with_actions(mut self, actions: Vec<ActionRule>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /// Keeps the paths `matcher` excludes when deleting directories.
+    ///
+    /// A directory item is then removed entry by entry, leaving excluded paths and the
+    /// directories containing them in place. `None` deletes directories whole.
+    pub fn with_protection(mut self, matcher: Option<Arc<PatternMatcher>>) -> Self {
+        self.protection = matcher;
+        self
+    }
+
+    /// Moves the items of each real clean to the trash instead of deleting them, if
+    /// `trash.enabled` is set. Items too large for the Recycle Bin are deleted, moved
+    /// into the quarantine or kept as `trash.fallback` says; `ask` keeps them, since
+    /// the cleaner asks nobody. Ignored with secure deletion.
+    pub fn with_trash(mut self, trash: TrashConfig) -> Self {
+        self.trash = trash;
+        self
+    }
+
+    /// Offers each item to the removal backends of `plugins` before deleting it.
+    ///
+    /// Plugins are skipped while secure deletion or protection is in effect, whose
+    /// guarantees a plugin would not keep.
+    pub fn with_plugins(mut self, plugins: Option<Arc<Plugins>>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// Overwrites file contents with one pass of random bytes before removing them.
+    ///
+    /// See [`shred`] for when this does not erase the old contents.
+    pub fn with_secure_delete(mut self, secure_delete: bool) -> Self {
+        self.secure_delete = secure_delete;
+        self
+    }
+
+    /// Archives the items of each real clean before deleting them, if `archive.dir`
+    /// is set. Nothing is deleted when the archive cannot be written.
+    pub fn with_archive(mut self, archive: ArchiveConfig) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Moves the items of each real clean into a new batch in `quarantine.dir`
+    /// instead of deleting them, if it is set, and then purges the oldest batches
+    /// beyond `quarantine.max_total`. Ignored with secure deletion.
+    pub fn with_quarantine(mut self, quarantine: QuarantineConfig) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Sets what a real clean does about deletes slowed down by on-access scanning;
+    /// see [`slow_delete`]. Defaults to warning.
+    pub fn with_slow_deletes(mut self, slow_deletes: SlowDeletePolicy) -> Self {
+        self.slow_deletes = slow_deletes;
+        self
+    }
+
+    /// Sets how many of the largest deleted, or planned, items the report lists in
+    /// `largest_items`. Defaults to [`DEFAULT_LARGEST_ITEMS`].
+    pub fn with_largest_items(mut self, count: usize) -> Self {
+        self.largest_items = count;
+        self
+    }
+
+    /// Makes the clean reproducible: items are listed and deleted one at a time in path
+    /// order, on the calling thread, and every list in the report is sorted by path
+    /// with [`CleanReport::sort_by_path`]. Deleting is slower this way.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Marks the sizes of the items at `paths` as estimates, such as those of
+    /// [`ScanOutcome::estimated_sizes`](crate::ScanOutcome::estimated_sizes). A dry run
+    /// shows them with `≈` and counts them in the report's `bytes_estimated`.
+    pub fn with_estimated_sizes(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.estimated_sizes = paths.into_iter().collect();
+        self
+    }
+
+    /// Records every item of a real clean in `journal` once it is gone, so an
+    /// interrupted clean can be resumed with the rest. `None` keeps no journal.
+    pub fn with_journal(mut self, journal: Option<Arc<Journal>>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Stops starting new deletions once a real clean has run for `timeout`.
+    ///
+    /// Deletions already under way are finished; the items not reached are counted
+    /// in the report's `items_not_attempted` and `clean_timed_out` is set. Action
+    /// commands are not run once the timeout has passed. `None`, the default, never
+    /// stops.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Abandons any single deletion that has not finished after `timeout`, such as a
+    /// `remove_dir_all` stuck on a stale NFS handle, and moves on to the next item.
+    ///
+    /// Each deletion then runs on its own watchdog-supervised thread. An abandoned
+    /// deletion is logged and reported as [`CleanError::TimedOut`], but its thread
+    /// cannot be interrupted: it stays blocked until the call returns or the process
+    /// exits, and may still remove the item later. While 16 such threads are blocked,
+    /// further items fail without being attempted. `None`, the default, waits as long
+    /// as each deletion takes.
+    pub fn with_item_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.item_timeout = timeout;
+        self
+    }
+
+    /// Leaves the remaining items alone once `cancel` is set, from any thread. Items
+    /// being deleted at that moment are finished; the others are counted in
+    /// `items_not_attempted`.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Calls `handler` with the outcome of each item as soon as it is known, so a caller
+    /// such as an IDE plugin can show per-item results while the clean goes on.
+    ///
+    /// Deletions report from the worker threads, in no particular order unless the
+    /// clean is [deterministic](Self::with_deterministic). A dry run
+    /// reports nothing, since it changes nothing.
+    pub fn with_outcomes(mut self, handler: impl Fn(ItemOutcome) + Send + Sync + 'static) -> Self {
+        self.on_outcome = Some(Arc::new(handler));
+        self
+    }
+
     /// Cleans the given list of `CleanItem`s.
     ///
     /// This is the main method that executes the cleaning process. It distributes
@@ -134,111 +448,862 @@ impl ParallelCleaner {
     /// A `CleanReport` summarizing the results of the operation. Errors that occur
     /// during file deletion are collected and included in the report, but they do
     /// not stop the entire cleaning process.
-    pub fn clean(&self, mut items: Vec<CleanItem>) -> crate::types::Result<CleanReport> {
+    pub fn clean(&self, items: Vec<CleanItem>) -> crate::types::Result<CleanReport> {
+        let on_outcome = self.on_outcome.as_deref();
+        self.run(
+            items,
+            on_outcome.map(|handler| handler as &(dyn Fn(ItemOutcome) + Sync)),
+        )
+    }
+
+    /// Cleans like [`ParallelCleaner::clean`], also returning the outcome of every item,
+    /// in the order they finished. A handler set with
+    /// [`with_outcomes`](Self::with_outcomes) still receives each outcome.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`ParallelCleaner::clean`] would.
+    pub fn clean_with_outcomes(
+        &self,
+        items: Vec<CleanItem>,
+    ) -> crate::types::Result<(CleanReport, Vec<ItemOutcome>)> {
+        let outcomes = Mutex::new(Vec::new());
+        let collect = |outcome: ItemOutcome| {
+            if let Some(handler) = &self.on_outcome {
+                handler(outcome.clone());
+            }
+            outcomes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(outcome);
+        };
+        let report = self.run(items, Some(&collect))?;
+        let outcomes = outcomes.into_inner().unwrap_or_else(|e| e.into_inner());
+        Ok((report, outcomes))
+    }
+
+    /// Cleans `items`, passing the outcome of each to `on_outcome`.
+    fn run(
+        &self,
+        items: Vec<CleanItem>,
+        on_outcome: Option<&(dyn Fn(ItemOutcome) + Sync)>,
+    ) -> crate::types::Result<CleanReport> {
+        // Outcomes are only built when someone receives them.
+        let report = |item: &CleanItem, status: &dyn Fn() -> ItemStatus| {
+            if let Some(on_outcome) = on_outcome {
+                on_outcome(ItemOutcome {
+                    item: item.clone(),
+                    status: status(),
+                });
+            }
+        };
         log::debug!("Cleaning {} items (dry_run={})", items.len(), self.dry_run);
+        let mut items = items;
+        if self.deterministic {
+            items.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        let (mut items, command_groups) = actions::partition(&self.actions, items);
+        if self.trash.enabled && self.secure_delete && !self.dry_run {
+            log::warn!("Secure deletion is on; deleting instead of moving to the trash");
+        }
         if self.dry_run {
-            return self.dry_run_clean(items);
+            return self.dry_run_clean(items, &command_groups);
+        }
+        if let Some(reason) = kill_switch::engaged() {
+            log::warn!("Deleting is disabled ({}); cleaning as a dry run", reason);
+            return self.dry_run_clean(items, &command_groups);
         }
 
         // Sort by size descending so large directories start processing first.
         // This improves parallelization by avoiding the scenario where one thread
         // grinds through a huge directory at the end while others sit idle.
-        items.sort_by(|a, b| b.size.cmp(&a.size));
+        if !self.deterministic {
+            #[allow(clippy::unnecessary_sort_by)]
+            items.sort_by(|a, b| b.size.cmp(&a.size));
+        }
+        let trashes = self.trashes();
+        let fallbacks = if trashes {
+            self.trash_fallbacks(&items)
+        } else {
+            HashMap::new()
+        };
+        let route_of = |item: &CleanItem| {
+            trashes.then(|| {
+                fallbacks
+                    .get(&item.path)
+                    .map_or(TrashRoute::Trash, |(route, _)| *route)
+            })
+        };
+        let routes = Mutex::new(Vec::new());
+        let add_route = |item: &CleanItem, route: TrashRoute| {
+            let routed = RoutedItem {
+                path: item.path.clone(),
+                route,
+                bytes: item.size,
+            };
+            routes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(routed);
+        };
+        items.retain(|item| match fallbacks.get(&item.path) {
+            Some(&(TrashRoute::Kept, capacity)) => {
+                log::info!(
+                    "Keeping {}: larger than the {} its Recycle Bin holds",
+                    item.path.display(),
+                    format::size(capacity)
+                );
+                add_route(item, TrashRoute::Kept);
+                report(item, &|| {
+                    ItemStatus::Skipped(SkipReason::TooLargeForTrash { capacity })
+                });
+                false
+            }
+            _ => true,
+        });
+        // With trash on, a batch is only needed for the fallback.
+        let quarantines = if trashes {
+            fallbacks
+                .values()
+                .any(|(route, _)| *route == TrashRoute::Quarantined)
+        } else {
+            self.quarantine.dir.is_some()
+        };
+        if quarantines && self.quarantine.dir.is_none() {
+            return Err(McError::Quarantine(
+                "trash.fallback is \"quarantine\" but quarantine.dir is not set".to_string(),
+            ));
+        }
+
+        // Items left to action commands are not counted as steps.
+        if let Some(ref progress) = self.progress {
+            progress.begin_phase("clean", Some(items.len() as u64));
+        }
+
+        // Items that could not be archived are not deleted.
+        let mut archive_errors = Vec::new();
+        let archive = match &self.archive.dir {
+            Some(dir) => {
+                let written = archive::write(&items, dir, self.archive.compression)
+                    .map_err(|err| McError::Archive(format!("{}: {}", dir.display(), err)))?;
+                for error in written.failed {
+                    if let Some(item) = items.iter().find(|item| error.path() == Some(&item.path)) {
+                        report(item, &|| ItemStatus::Failed(error.clone()));
+                    }
+                    archive_errors.push(error);
+                }
+                let failed: HashSet<&Path> =
+                    archive_errors.iter().filter_map(CleanError::path).collect();
+                items.retain(|item| !failed.contains(item.path.as_path()));
+                Some(written.path)
+            }
+            None => None,
+        };
+        let batch = match &self.quarantine.dir {
+            Some(_) if self.secure_delete => {
+                log::warn!("Secure deletion is on; deleting instead of quarantining");
+                None
+            }
+            Some(dir) if quarantines => Some(
+                quarantine::create_batch(dir)
+                    .map_err(|err| McError::Quarantine(format!("{}: {}", dir.display(), err)))?,
+            ),
+            _ => None,
+        };
+        let options = RemoveOptions {
+            secure_delete: self.secure_delete,
+            protection: self.protection.clone(),
+            plugins: self.plugins.clone(),
+            rename_first: batch.is_none() && !trashes && self.check_slow_deletes(&items),
+            batch,
+            trash: false,
+        };
+        let to_trash = RemoveOptions {
+            trash: true,
+            batch: None,
+            ..options.clone()
+        };
+        let to_delete = RemoveOptions {
+            batch: None,
+            ..options.clone()
+        };
 
         self.stats.items_deleted.store(0, Ordering::Relaxed);
         self.stats.bytes_freed.store(0, Ordering::Relaxed);
+        self.stats.dirs_deleted.store(0, Ordering::Relaxed);
+        self.stats.files_deleted.store(0, Ordering::Relaxed);
+        self.stats.already_gone.store(0, Ordering::Relaxed);
+        self.stats.paths_kept.store(0, Ordering::Relaxed);
 
         let start = Instant::now();
+        let deadline = self.timeout.map(|timeout| start + timeout);
+        let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        let timed_out = AtomicBool::new(false);
+        let not_attempted = AtomicUsize::new(0);
         let progress = self.progress.clone();
         let stats = Arc::clone(&self.stats);
-        let errors = Mutex::new(Vec::new());
-        let chunk_size = self.chunk_size;
+        let bytes_trashed = AtomicU64::new(0);
+        let bytes_quarantined = AtomicU64::new(0);
+        let errors = Mutex::new(archive_errors);
+        // Each item with the bytes it freed, or no bytes if it failed.
+        let totals = Mutex::new(Vec::new());
+        let add_total = |item, bytes: Option<u64>| {
+            totals
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push((item, bytes));
+        };
 
-        self.thread_pool.install(|| {
-            items.par_iter().with_min_len(chunk_size).for_each(|item| {
-                match self.delete_item(item) {
-                    Ok(()) => {
+        let cancelled = || {
+            self.cancel
+                .as_ref()
+                .is_some_and(|c| c.load(Ordering::Relaxed))
+        };
+        // Why items are no longer attempted, if they are not.
+        let stop = || {
+            if expired() {
+                timed_out.store(true, Ordering::Relaxed);
+                Some(SkipReason::TimedOut)
+            } else {
+                cancelled().then_some(SkipReason::Cancelled)
+            }
+        };
+
+        let delete = |item| {
+            if let Some(reason) = stop() {
+                not_attempted.fetch_add(1, Ordering::Relaxed);
+                report(item, &|| ItemStatus::Skipped(reason.clone()));
+                return;
+            }
+            let route = route_of(item);
+            let options = match route {
+                Some(TrashRoute::Trash) => &to_trash,
+                Some(TrashRoute::Deleted) => &to_delete,
+                _ => &options,
+            };
+            match self.delete_item(item, options) {
+                Ok(removal) => {
+                    let bytes_freed = removal.bytes_freed;
+                    stats.bytes_freed.fetch_add(bytes_freed, Ordering::Relaxed);
+                    if options.trash {
+                        bytes_trashed.fetch_add(bytes_freed, Ordering::Relaxed);
+                    } else if options.batch.is_some() {
+                        bytes_quarantined.fetch_add(bytes_freed, Ordering::Relaxed);
+                    }
+                    if let Some(route) = route {
+                        add_route(item, route);
+                    }
+                    // A directory that still holds protected paths was not deleted.
+                    if removal.kept > 0 {
+                        stats.paths_kept.fetch_add(removal.kept, Ordering::Relaxed);
+                    } else {
                         stats.items_deleted.fetch_add(1, Ordering::Relaxed);
-                        stats.bytes_freed.fetch_add(item.size, Ordering::Relaxed);
                         match item.item_type {
-                            ItemType::Directory => { stats.dirs_deleted.fetch_add(1, Ordering::Relaxed); }
-                            _ => { stats.files_deleted.fetch_add(1, Ordering::Relaxed); }
-                        }
-                        if let Some(ref progress) = progress {
-                            progress.increment(1);
+                            ItemType::Directory => {
+                                stats.dirs_deleted.fetch_add(1, Ordering::Relaxed);
+                            }
+                            _ => {
+                                stats.files_deleted.fetch_add(1, Ordering::Relaxed);
+                            }
                         }
                     }
-                    Err(err) => {
-                        log::debug!("Delete failed: {}: {}", item.path.display(), err);
-                        let clean_error = CleanError::IoError {
-                            path: item.path.clone(),
-                            message: err.to_string(),
-                        };
-                        errors
-                            .lock()
-                            .unwrap_or_else(|e| e.into_inner())
-                            .push(clean_error);
+                    if let Some(ref progress) = progress {
+                        progress.item_completed(item);
+                    }
+                    if let Some(ref journal) = self.journal {
+                        journal.record(&item.path);
                     }
+                    add_total(item, Some(bytes_freed));
+                    report(item, &|| ItemStatus::Deleted { bytes_freed });
                 }
+                // Another process (or a racing ancestor deletion) got there first.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    log::debug!("Already gone: {}", item.path.display());
+                    stats.already_gone.fetch_add(1, Ordering::Relaxed);
+                    if let Some(ref progress) = progress {
+                        progress.item_completed(item);
+                    }
+                    if let Some(ref journal) = self.journal {
+                        journal.record(&item.path);
+                    }
+                    report(item, &|| ItemStatus::AlreadyGone);
+                }
+                Err(err) => {
+                    log::debug!("Delete failed: {}: {}", item.path.display(), err);
+                    let clean_error = CleanError::from_io(item.path.clone(), &err);
+                    add_total(item, None);
+                    report(item, &|| ItemStatus::Failed(clean_error.clone()));
+                    errors
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push(clean_error);
+                }
+            }
+        };
+
+        if self.deterministic {
+            items.iter().for_each(delete);
+        } else {
+            let pool = match &self.thread_pool {
+                Some(pool) => Arc::clone(pool),
+                None => shared_pool(self.thread_count)?,
+            };
+            pool.install(|| match self.scheduling {
+                Scheduling::WorkStealing => items.par_iter().for_each(delete),
+                Scheduling::Chunked => items
+                    .par_chunks(self.chunk_size)
+                    .for_each(|chunk| chunk.iter().for_each(delete)),
             });
-        });
+        }
 
-        let errors = match errors.into_inner() {
+        let mut errors = match errors.into_inner() {
             Ok(list) => list,
             Err(poisoned) => poisoned.into_inner(),
         };
 
-        log::debug!("Clean done: {} deleted, {} errors",
-            stats.items_deleted.load(Ordering::Relaxed), errors.len());
+        let mut commands_run = 0;
+        for group in &command_groups {
+            if let Some(reason) = stop() {
+                not_attempted.fetch_add(group.items.len(), Ordering::Relaxed);
+                for item in &group.items {
+                    report(item, &|| ItemStatus::Skipped(reason.clone()));
+                }
+                continue;
+            }
+            commands_run += group.invocations();
+            errors.extend(group.run_items(|item, error| {
+                if error.is_some() {
+                    add_total(item, None);
+                }
+                report(item, &|| match error {
+                    Some(error) => ItemStatus::Failed(error.clone()),
+                    None => ItemStatus::Handled {
+                        command: group.rule.command.clone(),
+                    },
+                })
+            }));
+            // Commands are not run twice, even the ones that failed.
+            if let Some(ref journal) = self.journal {
+                group
+                    .items
+                    .iter()
+                    .for_each(|item| journal.record(&item.path));
+            }
+        }
+
+        log::debug!(
+            "Clean done: {} deleted, {} errors",
+            stats.items_deleted.load(Ordering::Relaxed),
+            errors.len()
+        );
+        let timed_out = timed_out.into_inner();
+        // Moved items free nothing until the trash is emptied or their batch is purged.
+        let bytes_trashed = bytes_trashed.into_inner();
+        let bytes_quarantined = bytes_quarantined.into_inner();
+        let mut bytes_freed = stats
+            .bytes_freed
+            .load(Ordering::Relaxed)
+            .saturating_sub(bytes_trashed + bytes_quarantined);
+        if let Some(batch) = &options.batch {
+            if let Err(err) = quarantine::record_size(batch) {
+                log::warn!("Cannot record the size of {}: {}", batch.display(), err);
+            }
+            bytes_freed += self.purge_quarantine();
+        }
+        if timed_out {
+            log::warn!(
+                "Clean stopped after {:.1}s by its timeout; {} items not attempted",
+                start.elapsed().as_secs_f64(),
+                not_attempted.load(Ordering::Relaxed)
+            );
+        }
 
-        Ok(CleanReport {
+        let duration = start.elapsed();
+        let mut report = CleanReport {
             items_deleted: stats.items_deleted.load(Ordering::Relaxed),
-            bytes_freed: stats.bytes_freed.load(Ordering::Relaxed),
+            bytes_freed,
+            bytes_estimated: 0,
             errors,
             scan_errors: Vec::new(),
-            duration: start.elapsed(),
+            scan_error_groups: Vec::new(),
+            scan_errors_omitted: 0,
+            duration,
             scan_duration: std::time::Duration::ZERO,
+            timings: PhaseTimings {
+                clean: duration,
+                ..Default::default()
+            },
             dry_run: false,
             dirs_deleted: stats.dirs_deleted.load(Ordering::Relaxed),
             files_deleted: stats.files_deleted.load(Ordering::Relaxed),
-            entries_scanned: 0, // Set by caller
-        })
+            entries_scanned: 0,       // Set by caller
+            scan: Default::default(), // Set by caller
+            depth_limited_dirs: 0,
+            commands_run,
+            items_already_gone: stats.already_gone.load(Ordering::Relaxed),
+            paths_kept: stats.paths_kept.load(Ordering::Relaxed),
+            bytes_trashed,
+            routes: routes.into_inner().unwrap_or_else(|e| e.into_inner()),
+            scan_timed_out: false, // Set by caller
+            clean_timed_out: timed_out,
+            items_not_attempted: not_attempted.into_inner(),
+            plan: Vec::new(),
+            pattern_stats: Default::default(),
+            auto_threads: None, // Set by caller
+            free_space_before: None,
+            free_space_after: None,
+            archive,
+            quarantine: options.batch,
+            bytes_quarantined,
+            skipped: Vec::new(),
+            by_category: Vec::new(),
+            by_project: Vec::new(),
+            largest_items: Vec::new(),
+            roots: Vec::new(),
+        };
+        let mut totals = totals.into_inner().unwrap_or_else(|e| e.into_inner());
+        totals.sort_by(|(a, a_bytes): &(&CleanItem, _), (b, b_bytes)| {
+            b_bytes.cmp(a_bytes).then_with(|| a.path.cmp(&b.path))
+        });
+        report.largest_items = totals
+            .iter()
+            .filter_map(|&(item, bytes)| Some(LargestItem::new(item, bytes?)))
+            .take(self.largest_items)
+            .collect();
+        // Looked up once the deletions are done, and once per parent directory.
+        let mut projects = HashMap::new();
+        report.set_totals(totals.into_iter().map(|(item, bytes)| {
+            let project = projects
+                .entry(item.path.parent())
+                .or_insert_with(|| fs_utils::project_root(&item.path));
+            (item.pattern.category, project.clone(), bytes)
+        }));
+        if self.deterministic {
+            report.sort_by_path();
+        }
+        Ok(report)
     }
 
-    /// Deletes a single `CleanItem` from the file system.
-    ///
-    /// This function handles the logic for deleting directories, files, and symlinks
-    /// appropriately.
-    fn delete_item(&self, item: &CleanItem) -> io::Result<()> {
-        match item.item_type {
-            ItemType::Directory => {
-                fs::remove_dir_all(&item.path)?;
-            }
-            ItemType::File => {
-                fs::remove_file(&item.path)?;
+    /// Returns whether the items of a real clean go to the trash.
+    fn trashes(&self) -> bool {
+        self.trash.enabled && !self.secure_delete
+    }
+
+    /// Returns the items of `items` too large for the Recycle Bin, with where
+    /// `trash.fallback` sends them.
+    fn trash_fallbacks(&self, items: &[CleanItem]) -> TrashFallbacks {
+        let route = match self.trash.fallback {
+            TrashFallback::Delete => TrashRoute::Deleted,
+            TrashFallback::Quarantine => TrashRoute::Quarantined,
+            // Callers that can ask pass on the answer instead.
+            TrashFallback::Ask | TrashFallback::Keep => TrashRoute::Kept,
+        };
+        trash::oversized(items)
+            .into_iter()
+            .map(|(path, capacity)| (path, (route, capacity)))
+            .collect()
+    }
+
+    /// Returns where each item of `items` would go with trash on, and removes those
+    /// `trash.fallback` keeps. Returns nothing when trash is off.
+    fn route_for_trash(&self, items: &mut Vec<CleanItem>) -> Vec<RoutedItem> {
+        if !self.trashes() {
+            return Vec::new();
+        }
+        let fallbacks = self.trash_fallbacks(items);
+        let routes = items
+            .iter()
+            .map(|item| RoutedItem {
+                path: item.path.clone(),
+                route: fallbacks
+                    .get(&item.path)
+                    .map_or(TrashRoute::Trash, |(route, _)| *route),
+                bytes: item.size,
+            })
+            .collect();
+        items.retain(|item| !matches!(fallbacks.get(&item.path), Some((TrashRoute::Kept, _))));
+        routes
+    }
+
+    /// Purges the oldest quarantine batches beyond `max_total`, if it is set, and
+    /// returns the bytes freed. Failures are logged; the items stay quarantined.
+    fn purge_quarantine(&self) -> u64 {
+        let (Some(dir), Some(max_total)) = (&self.quarantine.dir, self.quarantine.max_total) else {
+            return 0;
+        };
+        match quarantine::purge(dir, max_total.bytes()) {
+            Ok(purged) => purged.iter().map(|batch| batch.bytes).sum(),
+            Err(err) => {
+                log::warn!("Cannot purge the quarantine {}: {}", dir.display(), err);
+                0
             }
-            ItemType::Symlink => {
-                // Handle symlinks specially
-                #[cfg(unix)]
-                {
-                    fs::remove_file(&item.path)?;
+        }
+    }
+
+    /// Times deletes in the largest directory of `items`, unless `slow_deletes` is
+    /// `ignore`, and warns when they are slow. Returns whether directories are to be
+    /// renamed aside before they are deleted.
+    fn check_slow_deletes(&self, items: &[CleanItem]) -> bool {
+        // On-access scanners that slow deletes this much are a Windows matter; elsewhere
+        // only `mitigate` asks for the probe.
+        let probes = match self.slow_deletes {
+            SlowDeletePolicy::Ignore => false,
+            SlowDeletePolicy::Warn => cfg!(windows),
+            SlowDeletePolicy::Mitigate => true,
+        };
+        if !probes {
+            return false;
+        }
+        // Items are sorted largest first. Probing inside a linked directory would
+        // write outside the item.
+        let Some(dir) = items
+            .iter()
+            .map(|item| item.path.as_path())
+            .find(|path| fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir()))
+        else {
+            return false;
+        };
+        let latency = match slow_delete::probe(dir) {
+            Ok(latency) => latency,
+            Err(err) => {
+                log::debug!("Cannot time deletes in {}: {}", dir.display(), err);
+                return false;
+            }
+        };
+        log::debug!("Deleting a file in {} takes {:?}", dir.display(), latency);
+        if !slow_delete::is_slow(latency) {
+            return false;
+        }
+        let mitigate = self.slow_deletes == SlowDeletePolicy::Mitigate;
+        log::warn!(
+            "Deleting a file takes {:.1}ms in {}, which suggests on-access antivirus \
+             scanning. Excluding the tree from real-time scanning speeds cleaning up{}",
+            latency.as_secs_f64() * 1000.0,
+            dir.display(),
+            if mitigate {
+                "; renaming directories aside before deleting them"
+            } else {
+                ", as may options.slow_deletes = \"mitigate\""
+            }
+        );
+        mitigate
+    }
+
+    /// Deletes a single `CleanItem` from the file system, or moves it into the
+    /// quarantine or to the trash, on a supervised thread if an item timeout is set.
+    /// Returns what was removed.
+    fn delete_item(&self, item: &CleanItem, options: &RemoveOptions) -> io::Result<Removal> {
+        match self.item_timeout {
+            Some(timeout) => self.delete_supervised(item, options, timeout),
+            None => remove_item(item, options),
+        }
+    }
+
+    /// Deletes `item` on a thread of its own and waits at most `timeout` for it.
+    ///
+    /// Once [`MAX_STUCK_DELETIONS`] abandoned deletions are still blocked, no more are
+    /// started, so a hung mount does not pile up threads; the item fails instead.
+    fn delete_supervised(
+        &self,
+        item: &CleanItem,
+        options: &RemoveOptions,
+        timeout: Duration,
+    ) -> io::Result<Removal> {
+        if STUCK_DELETIONS.load(Ordering::Relaxed) >= MAX_STUCK_DELETIONS {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "not attempted: {} earlier deletions are still hung",
+                    MAX_STUCK_DELETIONS
+                ),
+            ));
+        }
+        let (done, result) = mpsc::channel();
+        // Set once the deletion is abandoned; the lock orders that against the thread
+        // handing its result over, so each stuck thread is counted exactly once.
+        let abandoned = Arc::new(Mutex::new(false));
+        let owned = item.clone();
+        let options = options.clone();
+        let thread_abandoned = Arc::clone(&abandoned);
+        thread::Builder::new()
+            .name("mc-delete".to_string())
+            .spawn(move || {
+                let removal = remove_item(&owned, &options);
+                let abandoned = thread_abandoned.lock().unwrap_or_else(|e| e.into_inner());
+                if *abandoned {
+                    STUCK_DELETIONS.fetch_sub(1, Ordering::Relaxed);
+                } else {
+                    let _ = done.send(removal);
                 }
-                #[cfg(windows)]
-                {
-                    if item.path.is_dir() {
-                        fs::remove_dir(&item.path)?;
-                    } else {
-                        fs::remove_file(&item.path)?;
-                    }
+            })?;
+        match result.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                let mut abandoned = abandoned.lock().unwrap_or_else(|e| e.into_inner());
+                // The deletion may have finished while the lock was taken.
+                if let Ok(result) = result.try_recv() {
+                    return result;
+                }
+                *abandoned = true;
+                STUCK_DELETIONS.fetch_add(1, Ordering::Relaxed);
+                log::warn!(
+                    "Deleting {} did not finish within {:.1}s; moving on",
+                    item.path.display(),
+                    timeout.as_secs_f64()
+                );
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "deletion did not finish within {:.1}s",
+                        timeout.as_secs_f64()
+                    ),
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::other("the deletion thread panicked"))
+            }
+        }
+    }
+
+    /// Returns the human-readable listing a dry run of `items` prints, for callers that
+    /// show it themselves, such as through a pager, and run the cleaner quiet.
+    pub fn dry_run_listing(&self, items: &[CleanItem]) -> String {
+        let mut items = items.to_vec();
+        self.measure_protected(&mut items);
+        if self.deterministic {
+            items.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        let (mut items, command_groups) = actions::partition(&self.actions, items);
+        let routes = self.route_for_trash(&mut items);
+        let total_size = items.iter().map(|item| item.size).sum();
+        let (directories, files): (Vec<&CleanItem>, Vec<&CleanItem>) = items
+            .iter()
+            .partition(|item| item.item_type == ItemType::Directory);
+        self.render_dry_run(&directories, &files, &command_groups, &routes, total_size)
+    }
+
+    /// Renders the human-readable dry-run listing, showing at most `listing_limit`
+    /// directories and files.
+    fn render_dry_run(
+        &self,
+        directories: &[&CleanItem],
+        files: &[&CleanItem],
+        command_groups: &[CommandGroup<'_>],
+        routes: &[RoutedItem],
+        total_size: u64,
+    ) -> String {
+        let limit = self.listing_limit.unwrap_or(usize::MAX);
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "\n{}",
+            "DRY RUN MODE - No files will be deleted".yellow().bold()
+        );
+        let _ = writeln!(out, "{}", "─".repeat(50).bright_black());
+
+        if !directories.is_empty() {
+            let _ = writeln!(out, "\n{}:", "Directories to remove".cyan().bold());
+            for dir in directories.iter().take(limit) {
+                let _ = writeln!(
+                    out,
+                    "  {} {} ({}){}{}",
+                    "📁".bright_blue(),
+                    dir.path.display(),
+                    self.approx(dir, format::size(dir.size)).bright_yellow(),
+                    self.unverified_note(dir),
+                    rebuild_note(dir)
+                );
+            }
+            if directories.len() > limit {
+                let _ = writeln!(
+                    out,
+                    "  ... and {} more directories (--show-all lists everything)",
+                    directories.len() - limit
+                );
+            }
+        }
+
+        if !files.is_empty() {
+            let _ = writeln!(out, "\n{}:", "Files to remove".cyan().bold());
+            for file in files.iter().take(limit) {
+                let _ = writeln!(
+                    out,
+                    "  {} {} ({}){}",
+                    "📄".bright_green(),
+                    file.path.display(),
+                    self.approx(file, format::size(file.size)).bright_yellow(),
+                    rebuild_note(file)
+                );
+            }
+            if files.len() > limit {
+                let _ = writeln!(
+                    out,
+                    "  ... and {} more files (--show-all lists everything)",
+                    files.len() - limit
+                );
+            }
+        }
+
+        if !command_groups.is_empty() {
+            let _ = writeln!(out, "\n{}:", "Commands to run instead".cyan().bold());
+            for group in command_groups {
+                let _ = writeln!(
+                    out,
+                    "  {} {} ({} items, {}x)",
+                    "▶".bright_magenta(),
+                    group.rule.command,
+                    group.items.len(),
+                    group.invocations()
+                );
+            }
+        }
+
+        let fallbacks: Vec<&RoutedItem> = routes
+            .iter()
+            .filter(|routed| routed.route != TrashRoute::Trash)
+            .collect();
+        if !fallbacks.is_empty() {
+            let _ = writeln!(out, "\n{}:", "Too large for the Recycle Bin".cyan().bold());
+            for routed in fallbacks.iter().take(limit) {
+                let _ = writeln!(
+                    out,
+                    "  {} {} ({}): {}",
+                    "⚠".yellow(),
+                    routed.path.display(),
+                    format::size(routed.bytes).bright_yellow(),
+                    routed.route.describe()
+                );
+            }
+            if fallbacks.len() > limit {
+                let _ = writeln!(
+                    out,
+                    "  ... and {} more (--show-all lists everything)",
+                    fallbacks.len() - limit
+                );
+            }
+        }
+
+        let _ = writeln!(out, "\n{}", "─".repeat(50).bright_black());
+        let _ = writeln!(
+            out,
+            "{}: {} items",
+            "Total".bold(),
+            format::count((directories.len() + files.len()) as u64)
+        );
+        let estimated = directories
+            .iter()
+            .chain(files)
+            .any(|item| self.is_estimated(item));
+        let _ = writeln!(
+            out,
+            "{}: {}{}",
+            "Space to free".bold(),
+            if estimated { "≈" } else { "" },
+            format::size(total_size).bright_green()
+        );
+        if estimated {
+            let _ = writeln!(
+                out,
+                "{}",
+                "Unverified directories were sized from a sample without checking their \
+                 contents; a clean may keep some of them or parts of them"
+                    .bright_black()
+            );
+        }
+        let trashed = routes
+            .iter()
+            .filter(|routed| routed.route == TrashRoute::Trash);
+        let (count, bytes) = trashed.fold((0, 0), |(count, bytes), routed| {
+            (count + 1, bytes + routed.bytes)
+        });
+        if count > 0 {
+            let _ = writeln!(
+                out,
+                "{}: {} items ({}), freed when the trash is emptied",
+                "To the trash".bold(),
+                format::count(count),
+                format::size(bytes)
+            );
+        }
+        out
+    }
+
+    /// Returns whether the size of `item` is an estimate.
+    fn is_estimated(&self, item: &CleanItem) -> bool {
+        self.estimated_sizes.contains(&item.path)
+    }
+
+    /// Prefixes `size`, the formatted size of `item`, with `≈` if it is an estimate.
+    fn approx(&self, item: &CleanItem, size: String) -> String {
+        if self.is_estimated(item) {
+            format!("≈{}", size)
+        } else {
+            size
+        }
+    }
+
+    /// Returns the note marking `item` in a listing as not walked, so that the checks
+    /// of its contents a clean makes, such as for secrets, scoped excludes and git
+    /// policies, have not been made.
+    fn unverified_note(&self, item: &CleanItem) -> String {
+        if self.is_estimated(item) {
+            " · unverified".bright_black().to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Sets the size of each directory in `items` that holds paths the protection keeps
+    /// to what deleting the rest would free. Returns how many such directories there
+    /// are and how many paths they keep.
+    fn measure_protected(&self, items: &mut [CleanItem]) -> (usize, usize) {
+        let Some(matcher) = &self.protection else {
+            return (0, 0);
+        };
+        let protected = |path: &Path| matcher.is_excluded(path);
+        let mut partly_deleted = 0;
+        let mut paths_kept = 0;
+        for item in items.iter_mut() {
+            let is_dir = fs::symlink_metadata(&item.path).is_ok_and(|m| m.is_dir());
+            if item.item_type != ItemType::Directory || !is_dir {
+                continue;
+            }
+            match partial::measure_dir_except(&item.path, &protected) {
+                Ok(removal) if removal.kept > 0 => {
+                    item.size = removal.bytes_freed;
+                    partly_deleted += 1;
+                    paths_kept += removal.kept;
                 }
+                Ok(_) => {}
+                Err(err) => log::debug!("Cannot measure {}: {}", item.path.display(), err),
             }
         }
-        Ok(())
+        (partly_deleted, paths_kept)
     }
 
     /// Performs a dry run, reporting what would be cleaned without deleting anything.
-    fn dry_run_clean(&self, items: Vec<CleanItem>) -> crate::types::Result<CleanReport> {
+    fn dry_run_clean(
+        &self,
+        mut items: Vec<CleanItem>,
+        command_groups: &[CommandGroup<'_>],
+    ) -> crate::types::Result<CleanReport> {
+        let (partly_deleted, paths_kept) = self.measure_protected(&mut items);
+        let routes = self.route_for_trash(&mut items);
         let total_size: u64 = items.iter().map(|i| i.size).sum();
+        let routed_bytes = |route: TrashRoute| -> u64 {
+            let routed: HashSet<&Path> = routes
+                .iter()
+                .filter(|routed| routed.route == route)
+                .map(|routed| routed.path.as_path())
+                .collect();
+            items
+                .iter()
+                .filter(|item| routed.contains(item.path.as_path()))
+                .map(|i| i.size)
+                .sum()
+        };
+        let bytes_trashed = routed_bytes(TrashRoute::Trash);
+        let bytes_quarantined = routed_bytes(TrashRoute::Quarantined);
 
         // Group items by type
         let mut directories = Vec::new();
@@ -252,90 +1317,227 @@ impl ParallelCleaner {
         }
 
         if !self.quiet {
-            println!(
-                "\n{}",
-                "DRY RUN MODE - No files will be deleted".yellow().bold()
-            );
-            println!("{}", "─".repeat(50).bright_black());
-
-            if !directories.is_empty() {
-                println!("\n{}:", "Directories to remove".cyan().bold());
-                for dir in directories.iter().take(20) {
-                    println!(
-                        "  {} {} ({})",
-                        "📁".bright_blue(),
-                        dir.path.display(),
-                        format_size(dir.size, DECIMAL).bright_yellow()
-                    );
-                }
-                if directories.len() > 20 {
-                    println!("  ... and {} more directories", directories.len() - 20);
-                }
-            }
-
-            if !files.is_empty() {
-                println!("\n{}:", "Files to remove".cyan().bold());
-                for file in files.iter().take(20) {
-                    println!(
-                        "  {} {} ({})",
-                        "📄".bright_green(),
-                        file.path.display(),
-                        format_size(file.size, DECIMAL).bright_yellow()
-                    );
-                }
-                if files.len() > 20 {
-                    println!("  ... and {} more files", files.len() - 20);
-                }
-            }
-
-            println!("\n{}", "─".repeat(50).bright_black());
-            println!("{}: {} items", "Total".bold(), items.len());
-            println!(
-                "{}: {}",
-                "Space to free".bold(),
-                format_size(total_size, DECIMAL).bright_green()
+            print!(
+                "{}",
+                self.render_dry_run(&directories, &files, command_groups, &routes, total_size)
             );
         }
 
         let dir_count = directories.len();
         let file_count = files.len();
 
-        Ok(CleanReport {
-            items_deleted: items.len(),
-            bytes_freed: total_size,
+        let plan: Vec<PlannedItem> = items
+            .iter()
+            .map(|item| plan_item(item, None, self.is_estimated(item)))
+            .chain(command_groups.iter().flat_map(|group| {
+                group
+                    .items
+                    .iter()
+                    .map(|item| plan_item(item, Some(&group.rule.command), self.is_estimated(item)))
+            }))
+            .collect();
+        let bytes_estimated = items
+            .iter()
+            .filter(|item| self.is_estimated(item))
+            .map(|item| item.size)
+            .sum();
+
+        let mut largest: Vec<&CleanItem> = items.iter().collect();
+        largest.sort_by_key(|item| std::cmp::Reverse(item.size));
+        let largest_items = largest
+            .into_iter()
+            .take(self.largest_items)
+            .map(|item| LargestItem::new(item, item.size))
+            .collect();
+        // Items left to action commands are not deleted, so they are not counted.
+        let totals: Vec<_> = plan[..items.len()]
+            .iter()
+            .map(|planned| {
+                (
+                    planned.category,
+                    planned.project.clone(),
+                    Some(planned.size),
+                )
+            })
+            .collect();
+
+        let mut report = CleanReport {
+            items_deleted: items.len() - partly_deleted,
+            bytes_freed: total_size - bytes_trashed - bytes_quarantined,
+            bytes_estimated,
             errors: Vec::new(),
             scan_errors: Vec::new(),
+            scan_error_groups: Vec::new(),
+            scan_errors_omitted: 0,
             duration: std::time::Duration::ZERO,
             scan_duration: std::time::Duration::ZERO,
+            timings: PhaseTimings::default(),
             dry_run: true,
-            dirs_deleted: dir_count,
+            dirs_deleted: dir_count - partly_deleted,
             files_deleted: file_count,
-            entries_scanned: 0, // Set by caller
-        })
+            entries_scanned: 0,       // Set by caller
+            scan: Default::default(), // Set by caller
+            depth_limited_dirs: 0,
+            commands_run: command_groups.iter().map(|g| g.invocations()).sum(),
+            items_already_gone: 0,
+            paths_kept,
+            bytes_trashed,
+            routes,
+            scan_timed_out: false, // Set by caller
+            clean_timed_out: false,
+            items_not_attempted: 0,
+            plan,
+            pattern_stats: Default::default(),
+            auto_threads: None, // Set by caller
+            free_space_before: None,
+            free_space_after: None,
+            archive: None,
+            quarantine: None,
+            bytes_quarantined,
+            skipped: Vec::new(),
+            by_category: Vec::new(),
+            by_project: Vec::new(),
+            largest_items,
+            roots: Vec::new(),
+        };
+        report.set_totals(totals);
+        if self.deterministic {
+            report.sort_by_path();
+        }
+        Ok(report)
+    }
+}
+
+/// Returns the rebuild note of `item` to append to its line in a listing, if it has one.
+fn rebuild_note(item: &CleanItem) -> String {
+    match &item.pattern.rebuild {
+        Some(note) => format!(" · rebuild: {}", note).bright_black().to_string(),
+        None => String::new(),
+    }
+}
+
+/// Describes what a real run would do with `item`, whose size may be an estimate.
+fn plan_item(item: &CleanItem, command: Option<&str>, estimated: bool) -> PlannedItem {
+    PlannedItem {
+        path: item.path.clone(),
+        size: item.size,
+        estimated,
+        item_type: item.item_type.clone(),
+        category: item.pattern.category,
+        pattern: item.pattern.pattern.clone(),
+        source: item.pattern.source,
+        age_days: fs_utils::age_days(&item.path),
+        unused_days: fs_utils::unused_days(&item.path),
+        project: fs_utils::project_root(&item.path),
+        command: command.map(str::to_string),
+        rebuild: item.pattern.rebuild.clone(),
+    }
+}
+
+/// Deletes a single `CleanItem` from the file system.
+///
+/// This function handles the logic for deleting directories, files, and symlinks
+/// appropriately. It never deletes through a link: if the path is a symbolic link
+/// on disk, only the link is removed, whatever the item type says. Directories are
+/// removed keeping the paths `protection` excludes, if it is set. Otherwise, the item
+/// is first offered to the removal backends of `plugins`. With a quarantine `batch`,
+/// or with `trash`, items and unprotected files are moved into the batch or to the
+/// trash instead, and plugins are not asked. With `rename_first`, other directories
+/// are renamed aside before they are deleted.
+/// Returns the bytes freed and, for a directory with protected paths, how many were kept.
+fn remove_item(item: &CleanItem, options: &RemoveOptions) -> io::Result<Removal> {
+    let secure_delete = options.secure_delete;
+    let protection = options.protection.as_deref();
+    let batch = options.batch.as_deref();
+    let moves = batch.is_some() || options.trash;
+    if !secure_delete && protection.is_none() && !moves {
+        if let Some(removal) = options
+            .plugins
+            .as_ref()
+            .and_then(|plugins| plugins.remove(item))
+        {
+            return removal.map(removed);
+        }
+    }
+    let is_link = fs::symlink_metadata(&item.path).is_ok_and(|m| m.file_type().is_symlink());
+    let move_aside = |path: &Path| match batch {
+        Some(batch) => quarantine::move_into(batch, path),
+        None => trash::move_to_trash(path),
+    };
+    // Directories with protected paths are moved file by file below.
+    if moves && (is_link || item.item_type != ItemType::Directory || protection.is_none()) {
+        move_aside(&item.path)?;
+        return Ok(removed(item.size));
+    }
+    let remove_file = |path: &Path| {
+        if moves {
+            move_aside(path)
+        } else if secure_delete {
+            shred::remove_file(path)
+        } else {
+            native::remove_file(path)
+        }
+    };
+    match item.item_type {
+        ItemType::Directory if !is_link => match protection {
+            Some(matcher) => {
+                let protected = |path: &Path| matcher.is_excluded(path);
+                let removal = partial::remove_dir_except(&item.path, &protected, &remove_file)?;
+                if removal.kept > 0 {
+                    log::debug!(
+                        "Kept {} protected paths in {}",
+                        removal.kept,
+                        item.path.display()
+                    );
+                }
+                return Ok(removal);
+            }
+            None if secure_delete => shred::remove_dir_all(&item.path)?,
+            None if options.rename_first => slow_delete::remove_dir_all(&item.path)?,
+            None => native::remove_dir_all(&item.path)?,
+        },
+        ItemType::File if !is_link => {
+            remove_file(&item.path)?;
+        }
+        _ => {
+            // Handle symlinks specially
+            #[cfg(unix)]
+            {
+                fs::remove_file(&item.path)?;
+            }
+            #[cfg(windows)]
+            {
+                if item.path.is_dir() {
+                    fs::remove_dir(&item.path)?;
+                } else {
+                    fs::remove_file(&item.path)?;
+                }
+            }
+        }
+    }
+    Ok(removed(item.size))
+}
+
+/// The removal of a whole item of `bytes`.
+fn removed(bytes: u64) -> Removal {
+    Removal {
+        bytes_freed: bytes,
+        kept: 0,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{PatternCategory, PatternMatch, PatternSource};
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
     use assert_fs::prelude::*;
     use assert_fs::TempDir;
 
     fn make_clean_items(paths: &[&std::path::Path], item_type: ItemType) -> Vec<CleanItem> {
         paths
             .iter()
-            .map(|p| CleanItem {
-                path: p.to_path_buf(),
-                size: 100,
-                item_type: item_type.clone(),
-                pattern: PatternMatch {
-                    pattern: "test".to_string(),
-                    priority: 0,
-                    source: PatternSource::BuiltIn,
-                    category: PatternCategory::Other,
-                },
-            })
+            .map(|p| clean_item(p, item_type.clone(), 100, PatternCategory::Other))
             .collect()
     }
 
@@ -351,6 +1553,32 @@ mod tests {
         assert!(cleaner.is_ok());
     }
 
+    #[test]
+    fn test_cleaners_share_thread_pools() {
+        let first = ParallelCleaner::new().unwrap().with_threads(3).unwrap();
+        let second = ParallelCleaner::new().unwrap().with_threads(3).unwrap();
+        let (first, second) = (first.thread_pool.unwrap(), second.thread_pool.unwrap());
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.current_num_threads(), 3);
+    }
+
+    #[test]
+    fn test_clean_runs_on_given_thread_pool() {
+        let temp = TempDir::new().unwrap();
+        temp.child("a.log").touch().unwrap();
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+
+        let items = make_clean_items(&[temp.child("a.log").path()], ItemType::File);
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_quiet(true)
+            .with_thread_pool(Arc::clone(&pool));
+        assert_eq!(cleaner.thread_count, 1);
+        let report = cleaner.clean(items).unwrap();
+
+        assert_eq!(report.items_deleted, 1);
+    }
+
     #[test]
     fn test_clean_deletes_files() {
         let temp = TempDir::new().unwrap();
@@ -361,14 +1589,9 @@ mod tests {
         f2.touch().unwrap();
         f3.touch().unwrap();
 
-        let items = make_clean_items(
-            &[f1.path(), f2.path(), f3.path()],
-            ItemType::File,
-        );
+        let items = make_clean_items(&[f1.path(), f2.path(), f3.path()], ItemType::File);
 
-        let cleaner = ParallelCleaner::new()
-            .unwrap()
-            .with_dry_run(false);
+        let cleaner = ParallelCleaner::new().unwrap().with_dry_run(false);
         let report = cleaner.clean(items).unwrap();
 
         assert_eq!(report.items_deleted, 3);
@@ -378,6 +1601,27 @@ mod tests {
         assert!(!f3.path().exists());
     }
 
+    #[test]
+    fn test_chunked_scheduling_deletes_every_item() {
+        let temp = TempDir::new().unwrap();
+        let files: Vec<_> = (0..5).map(|i| temp.child(format!("{}.log", i))).collect();
+        for file in &files {
+            file.touch().unwrap();
+        }
+        let paths: Vec<_> = files.iter().map(|file| file.path()).collect();
+
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_scheduling(Scheduling::Chunked)
+            .with_chunk_size(2);
+        let report = cleaner
+            .clean(make_clean_items(&paths, ItemType::File))
+            .unwrap();
+
+        assert_eq!(report.items_deleted, 5);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
     #[test]
     fn test_clean_dry_run_preserves_files() {
         let temp = TempDir::new().unwrap();
@@ -386,9 +1630,7 @@ mod tests {
 
         let items = make_clean_items(&[f1.path()], ItemType::File);
 
-        let cleaner = ParallelCleaner::new()
-            .unwrap()
-            .with_dry_run(true);
+        let cleaner = ParallelCleaner::new().unwrap().with_dry_run(true);
         let report = cleaner.clean(items).unwrap();
 
         assert!(report.dry_run);
@@ -397,24 +1639,530 @@ mod tests {
     }
 
     #[test]
-    fn test_clean_collects_errors() {
+    fn test_dry_run_returns_full_plan() {
+        use crate::config::{ActionRule, CommandScope};
+
         let temp = TempDir::new().unwrap();
-        // Point to a non-existent file so deletion fails
-        let missing = temp.path().join("does_not_exist.log");
-        let items = make_clean_items(&[missing.as_path()], ItemType::File);
+        temp.child("app/package.json").touch().unwrap();
+        let log = temp.child("app/debug.log");
+        let cache = temp.child("app/cache.log");
+        log.touch().unwrap();
+        cache.touch().unwrap();
+
+        let mut items = make_clean_items(&[log.path(), cache.path()], ItemType::File);
+        items[1].pattern.category = PatternCategory::Cache;
 
         let cleaner = ParallelCleaner::new()
             .unwrap()
-            .with_dry_run(false);
+            .with_dry_run(true)
+            .with_quiet(true)
+            .with_actions(vec![ActionRule {
+                category: Some(PatternCategory::Cache),
+                pattern: None,
+                command: "true".to_string(),
+                scope: CommandScope::Once,
+            }]);
+        let report = cleaner.clean(items).unwrap();
+
+        assert_eq!(report.plan.len(), 2);
+        let planned = &report.plan[0];
+        assert_eq!(planned.path, log.path());
+        assert_eq!(planned.pattern, "debug.log");
+        assert_eq!(planned.age_days, Some(0));
+        assert_eq!(planned.project.as_deref(), Some(temp.child("app").path()));
+        assert_eq!(planned.command, None);
+        assert_eq!(report.plan[1].command.as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn test_dry_run_with_trash_counts_trashed_bytes_as_not_freed() {
+        let temp = TempDir::new().unwrap();
+        let log = temp.child("debug.log");
+        log.touch().unwrap();
+
+        let items = make_clean_items(&[log.path()], ItemType::File);
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_dry_run(true)
+            .with_quiet(true)
+            .with_trash(TrashConfig {
+                enabled: true,
+                fallback: TrashFallback::Keep,
+            });
+        let report = cleaner.clean(items).unwrap();
+
+        assert_eq!(report.bytes_trashed, 100);
+        assert_eq!(report.bytes_freed, 0);
+        assert_eq!(
+            report.routes,
+            vec![RoutedItem {
+                path: log.path().to_path_buf(),
+                route: TrashRoute::Trash,
+                bytes: 100,
+            }]
+        );
+        assert!(log.path().exists());
+    }
+
+    #[test]
+    fn test_dry_run_listing_shows_up_to_the_limit() {
+        let temp = TempDir::new().unwrap();
+        let paths = ["a.log", "b.log", "c.log"].map(|name| temp.child(name));
+        let items = make_clean_items(&paths.each_ref().map(|p| p.path()), ItemType::File);
+        let listing = |limit| {
+            ParallelCleaner::new()
+                .unwrap()
+                .with_dry_run(true)
+                .with_deterministic(true)
+                .with_listing_limit(limit)
+                .dry_run_listing(&items)
+        };
+        let listed = |listing: &str| {
+            paths
+                .iter()
+                .filter(|p| listing.contains(&*p.path().to_string_lossy()))
+                .count()
+        };
+
+        let limited = listing(Some(2));
+        assert_eq!(listed(&limited), 2, "{}", limited);
+        assert!(limited.contains("... and 1 more files (--show-all lists everything)"));
+        assert!(limited.contains("3 items"), "{}", limited);
+
+        let all = listing(None);
+        assert_eq!(listed(&all), 3, "{}", all);
+        assert!(!all.contains("more files"));
+    }
+
+    #[test]
+    fn test_estimated_directories_are_listed_as_unverified() {
+        let temp = TempDir::new().unwrap();
+        let sampled = temp.child("app/node_modules");
+        let walked = temp.child("app/target");
+        sampled.create_dir_all().unwrap();
+        walked.create_dir_all().unwrap();
+        let items = make_clean_items(&[sampled.path(), walked.path()], ItemType::Directory);
+
+        let listing = ParallelCleaner::new()
+            .unwrap()
+            .with_dry_run(true)
+            .with_estimated_sizes([sampled.path().to_path_buf()])
+            .dry_run_listing(&items);
+
+        let line = |path: &Path| {
+            listing
+                .lines()
+                .find(|line| line.contains(&*path.to_string_lossy()))
+        };
+        assert!(line(sampled.path()).unwrap().contains("unverified"));
+        assert!(!line(walked.path()).unwrap().contains("unverified"));
+        assert!(listing.contains("Unverified directories were sized from a sample"));
+    }
+
+    #[test]
+    fn test_clean_collects_errors() {
+        let temp = TempDir::new().unwrap();
+        // Remove a directory as if it were a file so deletion fails
+        let not_a_file = temp.path().join("not_a_file.log");
+        std::fs::create_dir(&not_a_file).unwrap();
+        let items = make_clean_items(&[not_a_file.as_path()], ItemType::File);
+
+        let cleaner = ParallelCleaner::new().unwrap().with_dry_run(false);
         let report = cleaner.clean(items).unwrap();
 
         assert_eq!(report.errors.len(), 1);
-        match &report.errors[0] {
-            CleanError::IoError { path, .. } => {
-                assert_eq!(path, &missing);
+        assert_eq!(report.errors[0].path(), Some(not_a_file.as_path()));
+    }
+
+    #[test]
+    fn test_report_lists_largest_items() {
+        let temp = TempDir::new().unwrap();
+        let paths: Vec<_> = (0..4).map(|n| temp.child(format!("{}.log", n))).collect();
+        let mut items = Vec::new();
+        for (n, path) in paths.iter().enumerate() {
+            path.touch().unwrap();
+            let mut item = make_clean_items(&[path.path()], ItemType::File).remove(0);
+            item.size = n as u64 * 10;
+            items.push(item);
+        }
+
+        let cleaner = || ParallelCleaner::new().unwrap().with_largest_items(2);
+        let planned = cleaner()
+            .with_dry_run(true)
+            .with_quiet(true)
+            .clean(items.clone());
+        let cleaned = cleaner().clean(items).unwrap();
+
+        for report in [planned.unwrap(), cleaned] {
+            let sizes: Vec<u64> = report.largest_items.iter().map(|item| item.bytes).collect();
+            assert_eq!(sizes, vec![30, 20]);
+            assert_eq!(report.largest_items[0].path, paths[3].path());
+        }
+    }
+
+    #[test]
+    fn test_progress_gets_phase_and_each_item() {
+        /// Records the events a clean reports.
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl Progress for Recorder {
+            fn increment(&self, delta: u64) {
+                self.0.lock().unwrap().push(format!("+{}", delta));
+            }
+            fn set_message(&self, _msg: &str) {}
+            fn finish(&self) {}
+            fn begin_phase(&self, name: &str, total: Option<u64>) {
+                self.0.lock().unwrap().push(format!("{} {:?}", name, total));
+            }
+            fn item_completed(&self, item: &CleanItem) {
+                let name = item
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned();
+                self.0.lock().unwrap().push(name);
             }
-            other => panic!("Expected IoError, got {:?}", other),
         }
+
+        let temp = TempDir::new().unwrap();
+        let log = temp.child("a.log");
+        log.write_str("x").unwrap();
+        let recorder = Arc::new(Recorder::default());
+        ParallelCleaner::new()
+            .unwrap()
+            .with_progress(Arc::clone(&recorder) as Arc<dyn Progress>)
+            .clean(make_clean_items(&[log.path()], ItemType::File))
+            .unwrap();
+
+        assert_eq!(*recorder.0.lock().unwrap(), ["clean Some(1)", "a.log"]);
     }
-}
 
+    #[test]
+    fn test_deterministic_clean_goes_in_path_order() {
+        let temp = TempDir::new().unwrap();
+        let paths = ["c.log", "a.log", "b/z.log"].map(|name| temp.child(name));
+        for path in &paths {
+            path.write_str("x").unwrap();
+        }
+        let items = make_clean_items(&paths.each_ref().map(|p| p.path()), ItemType::File);
+        let sorted = vec![paths[1].path(), paths[2].path(), paths[0].path()];
+
+        let cleaner = || ParallelCleaner::new().unwrap().with_deterministic(true);
+        let planned = cleaner()
+            .with_dry_run(true)
+            .with_quiet(true)
+            .clean(items.clone());
+        let planned: Vec<_> = planned
+            .unwrap()
+            .plan
+            .into_iter()
+            .map(|item| item.path)
+            .collect();
+        assert_eq!(planned, sorted);
+        let (_, outcomes) = cleaner().clean_with_outcomes(items).unwrap();
+        let cleaned: Vec<_> = outcomes
+            .iter()
+            .map(|outcome| outcome.item.path.as_path())
+            .collect();
+        assert_eq!(cleaned, sorted);
+    }
+
+    #[test]
+    fn test_report_totals_by_category_and_project() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/Cargo.toml").touch().unwrap();
+        let build = temp.child("app/build.log");
+        build.write_str("12345").unwrap();
+        let blocked = temp.child("app/blocked.log");
+        blocked.create_dir_all().unwrap();
+        let stray = temp.child("stray.log");
+        stray.write_str("123").unwrap();
+
+        let mut items = make_clean_items(&[build.path(), blocked.path()], ItemType::File);
+        items.extend(make_clean_items(&[stray.path()], ItemType::File));
+        items[0].pattern.category = PatternCategory::Logs;
+        items[1].pattern.category = PatternCategory::Logs;
+        let report = ParallelCleaner::new().unwrap().clean(items).unwrap();
+
+        let logs = &report.by_category[0];
+        assert_eq!(logs.category, PatternCategory::Logs);
+        assert_eq!((logs.items, logs.bytes, logs.errors), (1, 100, 1));
+        assert_eq!(report.by_category[1].category, PatternCategory::Other);
+        let app_dir = temp.path().join("app");
+        let app = report
+            .by_project
+            .iter()
+            .find(|t| t.project == Some(app_dir.clone()));
+        let app = app.unwrap();
+        assert_eq!((app.items, app.errors), (1, 1));
+        let outside = report
+            .by_project
+            .iter()
+            .find(|t| t.project.is_none())
+            .unwrap();
+        assert_eq!(outside.items, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_runs_action_command_instead_of_deleting() {
+        use crate::config::{ActionRule, CommandScope};
+
+        let temp = TempDir::new().unwrap();
+        let cache = temp.child("cache.log");
+        let other = temp.child("other.log");
+        cache.touch().unwrap();
+        other.touch().unwrap();
+
+        let mut items = make_clean_items(&[cache.path(), other.path()], ItemType::File);
+        items[0].pattern.category = PatternCategory::Cache;
+
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_actions(vec![ActionRule {
+                category: Some(PatternCategory::Cache),
+                pattern: None,
+                command: "touch \"$MC_ITEM_PATH.handled\"".to_string(),
+                scope: CommandScope::Item,
+            }]);
+        let report = cleaner.clean(items).unwrap();
+
+        assert_eq!(report.items_deleted, 1);
+        assert_eq!(report.commands_run, 1);
+        assert!(report.errors.is_empty());
+        assert!(cache.path().exists(), "action items must not be deleted");
+        assert!(temp.child("cache.log.handled").path().exists());
+        assert!(!other.path().exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_never_deletes_through_link() {
+        let temp = TempDir::new().unwrap();
+        temp.child("real/keep.txt").touch().unwrap();
+        let link = temp.path().join("linked");
+        std::os::unix::fs::symlink(temp.path().join("real"), &link).unwrap();
+
+        let items = make_clean_items(&[link.as_path()], ItemType::Directory);
+        let cleaner = ParallelCleaner::new().unwrap().with_quiet(true);
+        let report = cleaner.clean(items).unwrap();
+
+        assert_eq!(report.items_deleted, 1);
+        assert!(!link.exists());
+        assert!(temp.child("real/keep.txt").path().exists());
+    }
+
+    #[test]
+    fn test_clean_treats_missing_items_as_already_gone() {
+        let temp = TempDir::new().unwrap();
+        let missing = temp.path().join("vanished");
+
+        let items = make_clean_items(&[missing.as_path()], ItemType::Directory);
+        let cleaner = ParallelCleaner::new().unwrap().with_quiet(true);
+        let report = cleaner.clean(items).unwrap();
+
+        assert!(report.errors.is_empty());
+        assert_eq!(report.items_deleted, 0);
+        assert_eq!(report.items_already_gone, 1);
+    }
+
+    #[test]
+    fn test_clean_with_outcomes_reports_each_item() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child("app.log");
+        file.write_str("log").unwrap();
+        let missing = temp.path().join("vanished.log");
+        let items = make_clean_items(&[file.path(), missing.as_path()], ItemType::File);
+
+        let streamed = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&streamed);
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_quiet(true)
+            .with_outcomes(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        let (report, outcomes) = cleaner.clean_with_outcomes(items).unwrap();
+
+        assert_eq!(report.items_deleted, 1);
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(streamed.load(Ordering::Relaxed), 2);
+        let status = |path: &Path| {
+            let outcome = outcomes.iter().find(|outcome| outcome.item.path == path);
+            serde_json::to_value(&outcome.unwrap().status).unwrap()
+        };
+        assert_eq!(status(file.path())["status"], "deleted");
+        assert!(
+            status(file.path())["detail"]["bytes_freed"]
+                .as_u64()
+                .unwrap()
+                > 0
+        );
+        assert_eq!(status(&missing)["status"], "already_gone");
+    }
+
+    #[test]
+    fn test_clean_timeout_leaves_remaining_items() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.child("a.log");
+        let b = temp.child("b.log");
+        a.touch().unwrap();
+        b.touch().unwrap();
+
+        let items = make_clean_items(&[a.path(), b.path()], ItemType::File);
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_quiet(true)
+            .with_timeout(Some(Duration::ZERO));
+        let report = cleaner.clean(items).unwrap();
+
+        assert!(report.clean_timed_out);
+        assert_eq!(report.items_deleted, 0);
+        assert_eq!(report.items_not_attempted, 2);
+        assert!(a.path().exists() && b.path().exists());
+    }
+
+    #[test]
+    fn test_item_timeout_supervises_deletions() {
+        let temp = TempDir::new().unwrap();
+        temp.child("dist/bundle.js").touch().unwrap();
+        temp.child("debug.log").touch().unwrap();
+
+        let mut items = make_clean_items(&[temp.child("dist").path()], ItemType::Directory);
+        items.extend(make_clean_items(
+            &[temp.child("debug.log").path()],
+            ItemType::File,
+        ));
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_quiet(true)
+            .with_item_timeout(Some(Duration::from_secs(60)));
+        let report = cleaner.clean(items).unwrap();
+
+        assert!(report.errors.is_empty());
+        assert_eq!(report.items_deleted, 2);
+        assert!(!temp.child("dist").path().exists());
+        assert!(!temp.child("debug.log").path().exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_errors_are_classified() {
+        let path = std::path::PathBuf::from("/locked");
+        let err = CleanError::from_io(path.clone(), &io::Error::from_raw_os_error(libc::EACCES));
+        assert!(matches!(
+            err,
+            CleanError::PermissionDenied {
+                code: Some(libc::EACCES),
+                ..
+            }
+        ));
+
+        let err = CleanError::from_io(path.clone(), &io::Error::from_raw_os_error(libc::EBUSY));
+        assert!(matches!(err, CleanError::Busy { .. }));
+
+        let err = CleanError::from_io(path.clone(), &io::Error::from_raw_os_error(libc::EROFS));
+        assert!(matches!(err, CleanError::ReadOnlyFs { .. }));
+
+        let err = CleanError::from_io(path.clone(), &io::Error::from_raw_os_error(libc::EMFILE));
+        assert!(matches!(err, CleanError::TooManyOpenFiles { .. }));
+
+        let err = CleanError::from_io(path.clone(), &io::Error::from(io::ErrorKind::TimedOut));
+        assert!(matches!(err, CleanError::TimedOut { .. }));
+
+        let err = CleanError::from_io(path, &io::Error::other("boom"));
+        assert!(matches!(err, CleanError::IoError { .. }));
+    }
+
+    #[test]
+    fn test_each_clean_counts_only_its_own_items() {
+        let temp = TempDir::new().unwrap();
+        let dist = temp.child("dist");
+        dist.child("bundle.js").touch().unwrap();
+        let log = temp.child("debug.log");
+        log.touch().unwrap();
+
+        let cleaner = ParallelCleaner::new().unwrap().with_quiet(true);
+        let first = cleaner
+            .clean(make_clean_items(&[dist.path()], ItemType::Directory))
+            .unwrap();
+        assert_eq!(first.dirs_deleted, 1);
+        assert_eq!(first.files_deleted, 0);
+
+        let second = cleaner
+            .clean(make_clean_items(&[log.path()], ItemType::File))
+            .unwrap();
+        assert_eq!(second.items_deleted, 1);
+        assert_eq!(second.bytes_freed, 100);
+        assert_eq!(second.dirs_deleted, 0);
+        assert_eq!(second.files_deleted, 1);
+    }
+
+    #[test]
+    fn test_kept_paths_are_not_counted() {
+        let temp = TempDir::new().unwrap();
+        let dist = temp.child("dist");
+        dist.child("bundle.js").write_str("12345").unwrap();
+        dist.child(".env").write_str("SECRET=1").unwrap();
+        let mut config = crate::config::Config::default();
+        config.patterns.exclude = vec!["**/.env".to_string()];
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let mut items = make_clean_items(&[dist.path()], ItemType::Directory);
+        items[0].size = 13;
+
+        let cleaner = |dry_run| {
+            ParallelCleaner::new()
+                .unwrap()
+                .with_dry_run(dry_run)
+                .with_quiet(true)
+                .with_protection(Some(Arc::clone(&matcher)))
+        };
+        for dry_run in [true, false] {
+            let report = cleaner(dry_run).clean(items.clone()).unwrap();
+            assert_eq!(report.items_deleted, 0, "dry run: {}", dry_run);
+            assert_eq!(report.dirs_deleted, 0, "dry run: {}", dry_run);
+            assert_eq!(report.bytes_freed, 5, "dry run: {}", dry_run);
+            assert_eq!(report.paths_kept, 1, "dry run: {}", dry_run);
+        }
+        dist.child(".env").assert(predicates::path::exists());
+    }
+
+    #[test]
+    fn test_dry_run_routes_what_partial_deletes_free() {
+        let temp = TempDir::new().unwrap();
+        let dist = temp.child("dist");
+        dist.child("bundle.js").write_str("12345").unwrap();
+        dist.child(".env").write_str("SECRET=1").unwrap();
+        let mut config = crate::config::Config::default();
+        config.patterns.exclude = vec!["**/.env".to_string()];
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let mut items = make_clean_items(&[dist.path()], ItemType::Directory);
+        items[0].size = 13;
+
+        let cleaner = ParallelCleaner::new()
+            .unwrap()
+            .with_dry_run(true)
+            .with_quiet(true)
+            .with_protection(Some(matcher))
+            .with_trash(TrashConfig {
+                enabled: true,
+                fallback: TrashFallback::Keep,
+            });
+        let report = cleaner.clean(items.clone()).unwrap();
+
+        assert_eq!(report.bytes_trashed, 5);
+        assert_eq!(report.bytes_freed, 0);
+        assert_eq!(report.paths_kept, 1);
+        assert_eq!(
+            report.routes,
+            vec![RoutedItem {
+                path: dist.path().to_path_buf(),
+                route: TrashRoute::Trash,
+                bytes: 5,
+            }]
+        );
+        assert!(cleaner.dry_run_listing(&items).contains("5 B"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Estimating the size of a directory from a sample of its entries, for
+//! `options.size_mode = "fast"`.
+//!
+//! Walking every file of each matched `node_modules` or `target` only to add up their
+//! sizes is most of the work of a dry run over a large tree. [`dir_size`] lists at most
+//! a budget of entries instead. What is left of a directory's budget once it is listed
+//! is shared among as many of its subdirectories as can get a useful part of it,
+//! picked evenly across the listing, and the others are assumed to hold as much as
+//! the average one that was measured. Directories with fewer entries than the budget
+//! are measured exactly.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many entries sizing one matched directory lists at most.
+pub const SAMPLE_ENTRIES: usize = 5_000;
+
+/// The smallest part of a budget a subdirectory is measured with, so a sample reaches
+/// the files below a few subdirectories rather than listing only the tops of many.
+const MIN_SHARE: usize = 16;
+
+/// The size of a directory from [`dir_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    /// The size of the files in the directory, in bytes.
+    pub bytes: u64,
+    /// Whether every entry was listed, so `bytes` is measured rather than estimated.
+    pub exact: bool,
+}
+
+/// Estimates the size of the files below `path`, listing at most `budget` entries.
+/// Symbolic links are not followed, and entries that cannot be read count as empty.
+pub fn dir_size(path: &Path, budget: usize) -> Estimate {
+    let mut sampler = Sampler::default();
+    let bytes = sampler.measure(path, budget.max(1));
+    Estimate {
+        bytes,
+        exact: sampler.exact,
+    }
+}
+
+/// What sizing one directory has seen.
+struct Sampler {
+    /// Whether no subdirectory was left out so far.
+    exact: bool,
+    /// The directories listed so far.
+    dirs_listed: u64,
+    /// The bytes of the files directly inside them.
+    file_bytes: u64,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            exact: true,
+            dirs_listed: 0,
+            file_bytes: 0,
+        }
+    }
+}
+
+impl Sampler {
+    /// Returns the estimated size of `dir`, listing about `budget` entries.
+    fn measure(&mut self, dir: &Path, budget: usize) -> u64 {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return 0;
+        };
+        let mut bytes = 0;
+        let mut listed = 0;
+        let mut subdirs: Vec<PathBuf> = Vec::new();
+        for entry in entries.flatten() {
+            listed += 1;
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => subdirs.push(entry.path()),
+                Ok(file_type) if file_type.is_file() => {
+                    bytes += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                }
+                _ => {}
+            }
+        }
+        self.dirs_listed += 1;
+        self.file_bytes += bytes;
+        if subdirs.is_empty() {
+            return bytes;
+        }
+
+        let remaining = budget.saturating_sub(listed);
+        let room = subdirs.len().min(remaining.div_ceil(MIN_SHARE));
+        let mut measured = 0;
+        let mut measured_bytes = 0;
+        if let Some(share) = remaining.checked_div(room) {
+            for subdir in subdirs.iter().step_by(subdirs.len() / room).take(room) {
+                measured_bytes += self.measure(subdir, share);
+                measured += 1;
+            }
+        }
+        let unmeasured = (subdirs.len() - measured) as u64;
+        if unmeasured > 0 {
+            self.exact = false;
+            // Without a sibling to go by, a subdirectory is taken to hold as much as
+            // the directories listed so far hold directly.
+            let average = match measured {
+                0 => self.file_bytes / self.dirs_listed,
+                n => measured_bytes / n as u64,
+            };
+            bytes += average * unmeasured;
+        }
+        bytes + measured_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_small_trees_are_exact_and_large_ones_estimated() {
+        let temp = TempDir::new().unwrap();
+        for pkg in 0..20 {
+            for file in 0..4 {
+                let path = format!("node_modules/pkg{}/lib/f{}.js", pkg, file);
+                temp.child(path).write_binary(&[0; 100]).unwrap();
+            }
+        }
+        let modules = temp.child("node_modules");
+
+        let exact = dir_size(modules.path(), SAMPLE_ENTRIES);
+        assert_eq!(
+            exact,
+            Estimate {
+                bytes: 8_000,
+                exact: true
+            }
+        );
+
+        let estimate = dir_size(modules.path(), 50);
+        assert!(!estimate.exact);
+        assert_eq!(estimate.bytes, 8_000);
+    }
+}
@@ -1,11 +1,89 @@
+pub mod actions;
+pub mod archive;
 pub mod cleaner;
+pub mod estimate;
+pub mod native;
+pub mod partial;
+pub mod planner;
+pub mod policy;
+pub mod precount;
+pub mod quarantine;
+pub mod recommend;
+pub mod scan_iter;
 pub mod scanner;
+pub mod script;
+pub mod shred;
+pub mod slow_delete;
+pub mod trash;
 
 pub use cleaner::{ParallelCleaner, Statistics};
+pub use planner::{plan, plan_with_skipped};
+pub use precount::PreCount;
+pub use scan_iter::ScanIter;
 pub use scanner::Scanner;
 
-use crate::types::CleanItem;
-use std::collections::HashSet;
+use crate::config::Config;
+use crate::patterns::PatternMatcher;
+use crate::safety::SecretDetector;
+use crate::types::{CleanItem, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Returns a scanner for `path` that matches with `matcher` and follows the depth,
+/// symlink, ownership, secret, file-system and traversal settings of `config`. The
+/// quarantine and archive directories are never entered, so what mc keeps of earlier
+/// cleans is not matched again.
+///
+/// # Errors
+///
+/// Returns an error if a secret rule in the configuration is invalid.
+pub fn scanner_for(
+    config: &Config,
+    path: PathBuf,
+    matcher: Arc<PatternMatcher>,
+) -> Result<Scanner> {
+    Ok(Scanner::new(path, matcher)
+        .with_max_depth(config.safety.max_depth)
+        .with_symlink_policy(config.options.symlink_policy())
+        .with_symlink_overrides(config.options.symlink_overrides.clone())
+        .with_only_own_files(config.safety.only_own_files)
+        .with_secrets(SecretDetector::from_config(&config.safety.secrets)?.map(Arc::new))
+        .with_one_file_system(config.options.one_file_system)
+        .with_skip_traversal(config.options.skip_traversal.clone())
+        .with_skip_dirs(storage_dirs(config)))
+}
+
+/// Returns the directories mc moves or archives cleaned items into.
+fn storage_dirs(config: &Config) -> Vec<PathBuf> {
+    [&config.quarantine.dir, &config.archive.dir]
+        .into_iter()
+        .flatten()
+        .map(|dir| crate::utils::fs::expand_home(dir))
+        .collect()
+}
+
+/// Returns a cleaner that follows the scheduling, action, deletion, archive,
+/// quarantine, trash and reporting settings of `config`.
+///
+/// The thread count, protection and everything else that depends on the run are left
+/// to the caller.
+///
+/// # Errors
+///
+/// Returns an error if the cleaner cannot be created.
+pub fn cleaner_for(config: &Config) -> Result<ParallelCleaner> {
+    Ok(ParallelCleaner::new()?
+        .with_scheduling(config.options.scheduling)
+        .with_chunk_size(config.options.chunk_size)
+        .with_actions(config.actions.clone())
+        .with_secure_delete(config.options.secure_delete)
+        .with_item_timeout(config.options.item_timeout())
+        .with_archive(config.archive.clone())
+        .with_quarantine(config.quarantine.clone())
+        .with_trash(config.trash.clone())
+        .with_slow_deletes(config.options.slow_deletes)
+        .with_largest_items(config.options.largest_items))
+}
 
 /// Prunes nested items from a list of CleanItems.
 ///
@@ -18,9 +96,16 @@ use std::collections::HashSet;
 ///
 /// # Algorithm
 ///
-/// 1. Sort items by path length (shortest first)
-/// 2. For each item, check if any ancestor path is already in the kept list
-/// 3. Keep only items that don't have an ancestor marked for deletion
+/// 1. Sort items by path. Paths compare component by component, so every item comes
+///    right after the items containing it
+/// 2. Walk the sorted items, keeping an item only if it does not start with the path
+///    of the last kept item
+/// 3. Return the kept items shallowest first
+///
+/// This takes O(n log n) time for n items.
+///
+/// Dropping repeated paths matters when followed links resolve to the same target:
+/// its bytes would otherwise be planned, and reported as freed, once per link.
 ///
 /// # Example
 ///
@@ -43,53 +128,41 @@ pub fn prune_nested_items(mut items: Vec<CleanItem>) -> Vec<CleanItem> {
         return items;
     }
 
-    // Sort by path depth (component count) - shortest paths first
-    items.sort_by(|a, b| {
-        let a_depth = a.path.components().count();
-        let b_depth = b.path.components().count();
-        a_depth.cmp(&b_depth).then_with(|| a.path.cmp(&b.path))
-    });
-
-    let mut pruned = Vec::new();
-    let mut kept_paths: HashSet<std::path::PathBuf> = HashSet::new();
+    items.sort_by(|a, b| a.path.cmp(&b.path));
 
+    // Anything inside a kept item sorts directly after it, before any unrelated path,
+    // so only the last kept item needs checking.
+    let mut pruned: Vec<CleanItem> = Vec::new();
     for item in items {
-        let has_ancestor = item
-            .path
-            .ancestors()
-            .skip(1)
-            .any(|ancestor| kept_paths.contains(ancestor));
-
-        if !has_ancestor {
-            kept_paths.insert(item.path.clone());
+        let nested = pruned
+            .last()
+            .is_some_and(|kept| item.path.starts_with(&kept.path));
+        if !nested {
             pruned.push(item);
         }
     }
 
+    // Sort by path depth (component count) - shortest paths first
+    pruned.sort_by(|a, b| {
+        let a_depth = a.path.components().count();
+        let b_depth = b.path.components().count();
+        a_depth.cmp(&b_depth).then_with(|| a.path.cmp(&b.path))
+    });
     pruned
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{ItemType, PatternCategory, PatternMatch, PatternSource};
-    use std::path::PathBuf;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
 
     fn make_item(path: &str, size: u64) -> CleanItem {
-        CleanItem {
-            path: PathBuf::from(path),
-            size,
-            item_type: ItemType::Directory,
-            pattern: PatternMatch {
-                pattern: "test".to_string(),
-                priority: 0,
-                source: PatternSource::BuiltIn,
-                category: PatternCategory::Other,
-            },
-        }
+        clean_item(path, ItemType::Directory, size, PatternCategory::Other)
     }
 
     #[test]
+    #[allow(clippy::cmp_owned)]
     fn test_prune_nested_items() {
         let items = vec![
             make_item("/project/node_modules/pkg1/dist", 50_000_000),
@@ -135,6 +208,51 @@ mod tests {
         assert_eq!(pruned.len(), 3);
     }
 
+    #[test]
+    fn test_prune_drops_repeated_paths() {
+        let items = vec![
+            make_item("/project/shared/dist", 1000),
+            make_item("/project/shared/dist", 1000),
+        ];
+
+        let pruned = prune_nested_items(items);
+
+        assert_eq!(pruned.len(), 1);
+    }
+
+    /// The original ancestor-set implementation, kept as a reference.
+    fn prune_with_ancestor_set(mut items: Vec<CleanItem>) -> Vec<CleanItem> {
+        items.sort_by(|a, b| {
+            let a_depth = a.path.components().count();
+            let b_depth = b.path.components().count();
+            a_depth.cmp(&b_depth).then_with(|| a.path.cmp(&b.path))
+        });
+        let mut kept_paths = std::collections::HashSet::new();
+        items
+            .into_iter()
+            .filter(|item| {
+                let nested = item.path.ancestors().any(|a| kept_paths.contains(a));
+                !nested && kept_paths.insert(item.path.clone())
+            })
+            .collect()
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_prune_matches_reference(
+            paths in proptest::collection::vec("/r(/[ab]{1,2}|/a-b|/a.b){1,4}", 0..40)
+        ) {
+            let items: Vec<_> = paths.iter().map(|path| make_item(path, 1)).collect();
+            let paths = |items: Vec<CleanItem>| -> Vec<PathBuf> {
+                items.into_iter().map(|item| item.path).collect()
+            };
+            proptest::prop_assert_eq!(
+                paths(prune_nested_items(items.clone())),
+                paths(prune_with_ancestor_set(items))
+            );
+        }
+    }
+
     #[test]
     fn test_prune_empty_list() {
         let items: Vec<CleanItem> = vec![];
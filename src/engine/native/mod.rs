@@ -0,0 +1,147 @@
+//! Removing files and directory trees, and stat'ing files, with the fastest primitives
+//! the platform offers.
+//!
+//! The cleaner deletes through these functions instead of `std::fs::remove_file` and
+//! `std::fs::remove_dir_all`. On Windows they use handle-based deletion with POSIX
+//! semantics (see [`windows`]), falling back to the standard library where the file
+//! system does not support it. On Linux with the `uring` feature, directory trees are
+//! removed with batched io_uring unlinks, and the scanner's file stats are batched
+//! io_uring `statx` calls (see [`uring`]). Elsewhere they are the standard library
+//! functions, which already remove trees relative to directory file descriptors on
+//! Unix.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The number of files the scanner collects before stat'ing them with [`stat_files`].
+pub const STAT_BATCH: usize = 64;
+
+/// What a scan needs to know about a file: its size, and what tells hard links to it
+/// apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    /// The size in bytes.
+    pub len: u64,
+    /// The number of hard links to the file; 1 where they are not counted.
+    pub nlink: u64,
+    /// The device the file is on; 0 where unknown.
+    pub dev: u64,
+    /// The inode of the file; 0 where unknown.
+    pub ino: u64,
+}
+
+impl From<&fs::Metadata> for FileStat {
+    #[cfg(unix)]
+    fn from(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        Self {
+            len: metadata.len(),
+            nlink: metadata.nlink(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(metadata: &fs::Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            nlink: 1,
+            dev: 0,
+            ino: 0,
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "uring"))]
+mod uring;
+#[cfg(windows)]
+mod windows;
+
+/// Removes the file or link at `path`.
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    return windows::remove_file(path);
+    #[cfg(not(windows))]
+    std::fs::remove_file(path)
+}
+
+/// Removes the directory `path` and everything in it, without following links.
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    return windows::remove_dir_all(path);
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    return uring::remove_dir_all(path);
+    #[cfg(not(any(windows, all(target_os = "linux", feature = "uring"))))]
+    std::fs::remove_dir_all(path)
+}
+
+/// Stats each of `paths`, following links if `follow`, and returns the results in the
+/// same order.
+pub fn stat_files(paths: &[&Path], follow: bool) -> Vec<io::Result<FileStat>> {
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    return uring::stat_files(paths, follow);
+    #[cfg(not(all(target_os = "linux", feature = "uring")))]
+    paths.iter().map(|path| stat_file(path, follow)).collect()
+}
+
+/// Stats `path` by itself, following a link if `follow`.
+fn stat_file(path: &Path, follow: bool) -> io::Result<FileStat> {
+    let metadata = if follow {
+        fs::metadata(path)?
+    } else {
+        fs::symlink_metadata(path)?
+    };
+    Ok(FileStat::from(&metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_remove_tree_with_read_only_file() {
+        let temp = TempDir::new().unwrap();
+        let tree = temp.child("node_modules");
+        let locked = tree.child("pkg/lib/index.js");
+        locked.write_str("x").unwrap();
+        let mut permissions = fs::metadata(locked.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(locked.path(), permissions).unwrap();
+        let log = temp.child("debug.log");
+        log.write_str("x").unwrap();
+
+        remove_dir_all(tree.path()).unwrap();
+        remove_file(log.path()).unwrap();
+        tree.assert(predicates::path::missing());
+        log.assert(predicates::path::missing());
+        assert_eq!(
+            remove_dir_all(tree.path()).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_stat_files_keeps_order_and_errors() {
+        let temp = TempDir::new().unwrap();
+        let small = temp.child("small.o");
+        small.write_str("x").unwrap();
+        let large = temp.child("large.o");
+        large.write_str("xxxx").unwrap();
+        let missing = temp.child("missing.o");
+
+        let stats = stat_files(&[large.path(), missing.path(), small.path()], false);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].as_ref().unwrap().len, 4);
+        assert_eq!(
+            stats[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(stats[2].as_ref().unwrap().len, 1);
+    }
+}
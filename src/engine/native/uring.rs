@@ -0,0 +1,631 @@
+//! Removing directory trees on Linux with batched io_uring unlinks, and stat'ing files
+//! with batched io_uring `statx` calls, with the `uring` feature.
+//!
+//! The tree is walked through directory file descriptors, so no path is resolved
+//! twice, and every entry is unlinked relative to the descriptor of its directory.
+//! Directories holding at least [`MIN_BATCH`] files have them unlinked as batches of
+//! `IORING_OP_UNLINKAT` requests, which the kernel works through with one system call
+//! per batch instead of one per file. Smaller directories use `unlinkat(2)`, as does
+//! everything where io_uring is unavailable: kernels before 5.11, or sandboxes and
+//! containers whose seccomp policy forbids it.
+//!
+//! [`stat_files`] queues an `IORING_OP_STATX` request for each file, so a batch of
+//! [`STAT_BATCH`](super::STAT_BATCH) files costs one system call instead of one per
+//! file. Where io_uring is unavailable, or on kernels before 5.6, files are stat'ed
+//! one by one.
+
+use super::FileStat;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// The fewest files a directory must hold for them to be unlinked through io_uring.
+pub const MIN_BATCH: usize = 64;
+
+/// The number of submission queue entries of a ring, and so the largest batch.
+const RING_ENTRIES: u32 = 256;
+
+const IORING_OP_STATX: u8 = 21;
+const IORING_OP_UNLINKAT: u8 = 36;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+
+thread_local! {
+    /// Each deleting thread's ring: `None` until first needed, and `Some(None)` once
+    /// io_uring turned out to be unusable.
+    static RING: RefCell<Option<Option<Ring>>> = const { RefCell::new(None) };
+}
+
+pub(super) fn remove_dir_all(path: &Path) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot remove a root"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent = open_dir(libc::AT_FDCWD, &c_string(parent.as_os_str())?)?;
+    remove_tree(parent.as_raw_fd(), &c_string(name)?)
+}
+
+/// Removes the entry `name` of the directory `parent`: a directory with everything in
+/// it, or anything else by itself. Links are never followed.
+fn remove_tree(parent: RawFd, name: &CStr) -> io::Result<()> {
+    let dir = match open_dir(parent, name) {
+        Ok(dir) => dir,
+        // Not a directory, or a link to one.
+        Err(err) if matches!(err.raw_os_error(), Some(libc::ENOTDIR | libc::ELOOP)) => {
+            return unlink_at(parent, name, 0);
+        }
+        Err(err) => return Err(err),
+    };
+    let (files, dirs) = list(&dir)?;
+    for sub in &dirs {
+        match remove_tree(dir.as_raw_fd(), sub) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+    unlink_files(dir.as_raw_fd(), &files)?;
+    unlink_at(parent, name, libc::AT_REMOVEDIR)
+}
+
+pub(super) fn stat_files(paths: &[&Path], follow: bool) -> Vec<io::Result<FileStat>> {
+    let names: Option<Vec<CString>> = paths
+        .iter()
+        .map(|path| c_string(path.as_os_str()).ok())
+        .collect();
+    let batched = names.and_then(|names| {
+        with_ring(|ring| {
+            let result = ring.stat_all(names, follow);
+            if result.is_none() {
+                log::debug!("io_uring cannot stat here, stat'ing one by one");
+            }
+            result
+        })
+    });
+    batched.unwrap_or_else(|| {
+        paths
+            .iter()
+            .map(|path| super::stat_file(path, follow))
+            .collect()
+    })
+}
+
+/// Runs `f` with this thread's ring, creating it first if needed. Returns `None`
+/// without running it if io_uring is unavailable, and forgets the ring if `f` returns
+/// `None`, as it found the ring unusable.
+fn with_ring<T>(f: impl FnOnce(&mut Ring) -> Option<T>) -> Option<T> {
+    RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        let ring = ring.get_or_insert_with(|| match Ring::new(RING_ENTRIES) {
+            Ok(ring) => Some(ring),
+            Err(err) => {
+                log::debug!(
+                    "io_uring is unavailable, working one call at a time: {}",
+                    err
+                );
+                None
+            }
+        });
+        let result = f(ring.as_mut()?);
+        if result.is_none() {
+            *ring = None;
+        }
+        result
+    })
+}
+
+/// Unlinks `names` in the directory `dir`, in batches through io_uring if there are
+/// enough of them and it is usable. Names already gone are skipped.
+fn unlink_files(dir: RawFd, names: &[CString]) -> io::Result<()> {
+    if names.len() >= MIN_BATCH {
+        let batched = with_ring(|ring| {
+            let result = ring.unlink_all(dir, names);
+            if result.is_none() {
+                log::debug!("io_uring cannot unlink here, unlinking one by one");
+            }
+            result
+        });
+        if let Some(result) = batched {
+            return result;
+        }
+    }
+    for name in names {
+        match unlink_at(dir, name, 0) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Lists the entries of `dir`, split into what is not a directory and what may be one.
+fn list(dir: &OwnedFd) -> io::Result<(Vec<CString>, Vec<CString>)> {
+    // `fdopendir` takes over the descriptor it is given, so it gets a duplicate.
+    // SAFETY: `dir` is an open descriptor.
+    let fd = unsafe { libc::fcntl(dir.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is an open directory descriptor owned by nobody else.
+    let stream = unsafe { libc::fdopendir(fd) };
+    if stream.is_null() {
+        let err = io::Error::last_os_error();
+        // SAFETY: `fdopendir` failed, so `fd` is still ours to close.
+        unsafe { libc::close(fd) };
+        return Err(err);
+    }
+    let (mut files, mut dirs) = (Vec::new(), Vec::new());
+    let result = loop {
+        // SAFETY: `stream` is open; errors are told apart from the end by `errno`.
+        let entry = unsafe {
+            *libc::__errno_location() = 0;
+            libc::readdir(stream)
+        };
+        if entry.is_null() {
+            let err = io::Error::last_os_error();
+            break if err.raw_os_error() == Some(0) {
+                Ok(())
+            } else {
+                Err(err)
+            };
+        }
+        // SAFETY: `readdir` returned a valid entry with a NUL-terminated name.
+        let (name, kind) = unsafe { (CStr::from_ptr((*entry).d_name.as_ptr()), (*entry).d_type) };
+        if matches!(name.to_bytes(), b"." | b"..") {
+            continue;
+        }
+        match kind {
+            // An unknown type is found out by trying to open it as a directory.
+            libc::DT_DIR | libc::DT_UNKNOWN => dirs.push(name.to_owned()),
+            _ => files.push(name.to_owned()),
+        }
+    };
+    // SAFETY: `stream` is open and closed only here, which also closes `fd`.
+    unsafe { libc::closedir(stream) };
+    result.map(|()| (files, dirs))
+}
+
+/// Opens the directory `name` relative to `parent`, without following a link.
+fn open_dir(parent: RawFd, name: &CStr) -> io::Result<OwnedFd> {
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    // SAFETY: `name` is NUL-terminated.
+    let fd = unsafe { libc::openat(parent, name.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just opened and is owned by nobody else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn unlink_at(dir: RawFd, name: &CStr, flags: libc::c_int) -> io::Result<()> {
+    // SAFETY: `name` is NUL-terminated.
+    if unsafe { libc::unlinkat(dir, name.as_ptr(), flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn c_string(text: &OsStr) -> io::Result<CString> {
+    CString::new(text.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+/// `struct io_uring_sqe`, with the fields an unlink and a `statx` use.
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+/// `struct io_uring_cqe`.
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// `struct io_sqring_offsets` and `struct io_cqring_offsets`, which share a layout.
+#[repr(C)]
+#[derive(Default)]
+struct RingOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags_or_overflow: u32,
+    dropped_or_cqes: u32,
+    array_or_flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+/// `struct io_uring_params`.
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: RingOffsets,
+    cq_off: RingOffsets,
+}
+
+const _: () = assert!(mem::size_of::<Sqe>() == 64);
+const _: () = assert!(mem::size_of::<Cqe>() == 16);
+const _: () = assert!(mem::size_of::<Params>() == 120);
+
+/// A region of the ring mapped into memory, unmapped when dropped.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: &OwnedFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        // SAFETY: mapping a ring region of the length the kernel reported.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+
+    /// Returns the `u32` at `offset`, as the kernel shares it.
+    fn atomic(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: the kernel placed an aligned `u32` at this offset of the region.
+        unsafe { &*self.ptr.add(offset as usize).cast::<AtomicU32>() }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the region was mapped by `Mapping::new` and is unmapped only here.
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+/// An io_uring instance owned by one thread.
+struct Ring {
+    sq: Mapping,
+    cq: Mapping,
+    sqes: Mapping,
+    params: Params,
+    // Dropped last, after the mappings.
+    fd: OwnedFd,
+}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: `params` is a valid, zeroed `io_uring_params`.
+        let fd =
+            unsafe { libc::syscall(libc::SYS_io_uring_setup, entries, ptr::addr_of_mut!(params)) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the call returned a new descriptor owned by nobody else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        let sq_len = params.sq_off.array_or_flags as usize + params.sq_entries as usize * 4;
+        let cq_len = params.cq_off.dropped_or_cqes as usize
+            + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+        Ok(Self {
+            sq: Mapping::new(&fd, sq_len, IORING_OFF_SQ_RING)?,
+            cq: Mapping::new(&fd, cq_len, IORING_OFF_CQ_RING)?,
+            sqes: Mapping::new(&fd, sqes_len, IORING_OFF_SQES)?,
+            params,
+            fd,
+        })
+    }
+
+    /// Unlinks `names` in `dir`, a batch at a time. Returns `None` if the kernel does
+    /// not support unlinking through io_uring, or submitting or waiting fails; the
+    /// names are then left to `unlinkat`, which skips those already gone.
+    fn unlink_all(&mut self, dir: RawFd, names: &[CString]) -> Option<io::Result<()>> {
+        let mut first_error = None;
+        for (batch, chunk) in names.chunks(self.params.sq_entries as usize).enumerate() {
+            let sqes = chunk.iter().enumerate().map(|(index, name)| Sqe {
+                opcode: IORING_OP_UNLINKAT,
+                fd: dir,
+                addr: name.as_ptr() as u64,
+                user_data: index as u64,
+                ..Sqe::default()
+            });
+            if let Err(err) = self.submit(sqes) {
+                // The kernel copied the names of whatever it took, so the rest can
+                // safely be unlinked one by one.
+                log::debug!("io_uring submission failed: {}", err);
+                return None;
+            }
+            let completed = match self.reap(chunk.len()) {
+                Ok(completed) => completed,
+                Err(err) => {
+                    // Unlinks still in flight race the fallback harmlessly, as it
+                    // skips names already gone.
+                    log::debug!("io_uring wait failed: {}", err);
+                    return None;
+                }
+            };
+            for (index, res) in completed {
+                match -res {
+                    0 | libc::ENOENT => {}
+                    // Kernels without the opcode fail the whole first batch this way.
+                    libc::EINVAL if batch == 0 => return None,
+                    errno => {
+                        let name = chunk.get(index).map(|name| name.to_string_lossy());
+                        log::debug!("io_uring unlink of {:?} failed: errno {}", name, errno);
+                        first_error.get_or_insert(io::Error::from_raw_os_error(errno));
+                    }
+                }
+            }
+        }
+        Some(first_error.map_or(Ok(()), Err))
+    }
+
+    /// Stats `names`, a batch at a time, following links if `follow`. Returns `None` if
+    /// the kernel does not support `statx` through io_uring, or submitting or waiting
+    /// fails; the files are then left to be stat'ed one by one.
+    fn stat_all(&mut self, names: Vec<CString>, follow: bool) -> Option<Vec<io::Result<FileStat>>> {
+        let flags = if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+        let mask = libc::STATX_SIZE | libc::STATX_NLINK | libc::STATX_INO;
+        let size = self.params.sq_entries as usize;
+        let mut stats = Vec::with_capacity(names.len());
+        for (batch, start) in (0..names.len()).step_by(size).enumerate() {
+            let chunk = &names[start..names.len().min(start + size)];
+            // SAFETY: `statx` is plain data, for which all zeroes is valid.
+            let mut buffers: Vec<libc::statx> = vec![unsafe { mem::zeroed() }; chunk.len()];
+            let sqes =
+                chunk
+                    .iter()
+                    .zip(buffers.iter_mut())
+                    .enumerate()
+                    .map(|(index, (name, buffer))| Sqe {
+                        opcode: IORING_OP_STATX,
+                        fd: libc::AT_FDCWD,
+                        off: ptr::addr_of_mut!(*buffer) as u64,
+                        addr: name.as_ptr() as u64,
+                        len: mask,
+                        op_flags: flags as u32,
+                        user_data: index as u64,
+                        ..Sqe::default()
+                    });
+            let completed = self.submit(sqes).and_then(|()| self.reap(chunk.len()));
+            let completed = match completed {
+                Ok(completed) => completed,
+                Err(err) => {
+                    // Requests still in flight may yet write to their buffers and read
+                    // their names, so both are leaked rather than freed.
+                    log::debug!("io_uring stat batch failed: {}", err);
+                    mem::forget(buffers);
+                    mem::forget(names);
+                    return None;
+                }
+            };
+            let mut results: Vec<Option<io::Result<FileStat>>> =
+                (0..chunk.len()).map(|_| None).collect();
+            for (index, res) in completed {
+                let result = match -res {
+                    0 => {
+                        let buffer = &buffers[index];
+                        Ok(FileStat {
+                            len: buffer.stx_size,
+                            nlink: u64::from(buffer.stx_nlink),
+                            dev: libc::makedev(buffer.stx_dev_major, buffer.stx_dev_minor),
+                            ino: buffer.stx_ino,
+                        })
+                    }
+                    // Kernels without the opcode fail the whole first batch this way.
+                    libc::EINVAL if batch == 0 => return None,
+                    errno => Err(io::Error::from_raw_os_error(errno)),
+                };
+                results[index] = Some(result);
+            }
+            stats.extend(
+                results
+                    .into_iter()
+                    .map(|result| result.expect("every request of a batch completes")),
+            );
+        }
+        Some(stats)
+    }
+
+    /// Queues `sqes` and submits them. The memory they point to must outlive them.
+    fn submit(&mut self, sqes: impl ExactSizeIterator<Item = Sqe>) -> io::Result<()> {
+        let off = &self.params.sq_off;
+        let mask = self.sq.atomic(off.ring_mask).load(Ordering::Relaxed);
+        let tail = self.sq.atomic(off.tail);
+        let array = self
+            .sq
+            .ptr
+            .wrapping_add(off.array_or_flags as usize)
+            .cast::<u32>();
+        let mut next = tail.load(Ordering::Relaxed);
+        let count = sqes.len() as u32;
+        for sqe in sqes {
+            let slot = next & mask;
+            // SAFETY: `slot` indexes the mapped entry arrays, and the kernel reads no
+            // entry past the tail published below.
+            unsafe {
+                self.sqes.ptr.cast::<Sqe>().add(slot as usize).write(sqe);
+                array.add(slot as usize).write(slot);
+            }
+            next = next.wrapping_add(1);
+        }
+        tail.store(next, Ordering::Release);
+
+        let mut pending = count;
+        while pending > 0 {
+            match self.enter(pending, 0, 0) {
+                Ok(submitted) => pending -= submitted.min(pending),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for `count` completions and returns the index and result of each.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of a wait that failed other than by being interrupted, such
+    /// as with `ENOMEM`, as retrying it would likely fail forever.
+    fn reap(&mut self, count: usize) -> io::Result<Vec<(usize, i32)>> {
+        let off = &self.params.cq_off;
+        let mask = self.cq.atomic(off.ring_mask).load(Ordering::Relaxed);
+        let cqes = self
+            .cq
+            .ptr
+            .wrapping_add(off.dropped_or_cqes as usize)
+            .cast::<Cqe>();
+        let mut results = Vec::with_capacity(count);
+        while results.len() < count {
+            let head = self
+                .cq
+                .atomic(self.params.cq_off.head)
+                .load(Ordering::Relaxed);
+            let tail = self
+                .cq
+                .atomic(self.params.cq_off.tail)
+                .load(Ordering::Acquire);
+            if head == tail {
+                match self.enter(0, 1, IORING_ENTER_GETEVENTS) {
+                    Err(err) if err.kind() != io::ErrorKind::Interrupted => return Err(err),
+                    _ => continue,
+                }
+            }
+            // SAFETY: entries between head and tail were written by the kernel.
+            let cqe = unsafe { cqes.add((head & mask) as usize).read() };
+            results.push((cqe.user_data as usize, cqe.res));
+            self.cq
+                .atomic(self.params.cq_off.head)
+                .store(head.wrapping_add(1), Ordering::Release);
+        }
+        Ok(results)
+    }
+
+    fn enter(&self, to_submit: u32, min_complete: u32, flags: u32) -> io::Result<u32> {
+        // SAFETY: no argument structure is passed.
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd.as_raw_fd(),
+                to_submit,
+                min_complete,
+                flags,
+                ptr::null::<libc::c_void>(),
+                0usize,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(result as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_remove_tree_in_batches() {
+        let temp = TempDir::new().unwrap();
+        let kept = temp.child("kept.txt");
+        kept.write_str("x").unwrap();
+        let tree = temp.child("node_modules");
+        for n in 0..MIN_BATCH * 5 {
+            tree.child(format!("pkg/file_{}.js", n))
+                .write_str("x")
+                .unwrap();
+        }
+        tree.child("pkg/deep/er/index.js").write_str("x").unwrap();
+        std::os::unix::fs::symlink(temp.path(), tree.child("pkg/up").path()).unwrap();
+
+        remove_dir_all(tree.path()).unwrap();
+        tree.assert(predicates::path::missing());
+        kept.assert(predicates::path::exists());
+        assert_eq!(
+            remove_dir_all(tree.path()).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_stat_in_batches_matches_stat_one_by_one() {
+        let Ok(mut ring) = Ring::new(4) else {
+            return;
+        };
+        let temp = TempDir::new().unwrap();
+        let mut paths = Vec::new();
+        for n in 0..10 {
+            let file = temp.child(format!("file_{}.o", n));
+            file.write_str(&"x".repeat(n)).unwrap();
+            paths.push(file.path().to_path_buf());
+        }
+        std::fs::hard_link(&paths[3], temp.child("again.o").path()).unwrap();
+        let link = temp.child("link.o");
+        std::os::unix::fs::symlink(&paths[9], link.path()).unwrap();
+        paths.push(link.path().to_path_buf());
+        paths.push(temp.child("missing.o").path().to_path_buf());
+
+        for follow in [false, true] {
+            let names = paths.iter().map(|p| c_string(p.as_os_str()).unwrap());
+            let Some(stats) = ring.stat_all(names.collect(), follow) else {
+                return;
+            };
+            assert_eq!(stats.len(), paths.len());
+            for (path, stat) in paths.iter().zip(stats) {
+                let expected = super::super::stat_file(path, follow);
+                match (stat, expected) {
+                    (Ok(stat), Ok(expected)) => assert_eq!(stat, expected, "{:?}", path),
+                    (Err(err), Err(expected)) => assert_eq!(err.kind(), expected.kind()),
+                    (stat, expected) => panic!("{:?}: {:?} != {:?}", path, stat, expected),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Deleting with handle-based Windows primitives.
+//!
+//! Every entry is opened once, through a verbatim `\\?\` path so paths longer than
+//! `MAX_PATH` work, and marked for deletion with `FILE_DISPOSITION_FLAG_POSIX_SEMANTICS`.
+//! Its name then disappears as soon as mc closes the handle, even while a scanner or
+//! indexer still holds the file open, so the parent directory can be removed right
+//! away instead of failing as not empty. Read-only files are deleted without their
+//! attribute being cleared first, and directories are listed through the handle mc
+//! already holds, many entries per call.
+//!
+//! POSIX semantics need Windows 10 1607 or later and NTFS. Where they are refused, the
+//! item is removed with the standard library instead.
+
+use std::ffi::OsString;
+use std::io;
+use std::mem;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{self, Path, PathBuf};
+use std::ptr;
+
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_INVALID_FUNCTION, ERROR_INVALID_PARAMETER, ERROR_NOT_SUPPORTED,
+    ERROR_NO_MORE_FILES, HANDLE, INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, FileDispositionInfoEx, FileFullDirectoryInfo, GetFileInformationByHandleEx,
+    SetFileInformationByHandle, DELETE, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_REPARSE_POINT,
+    FILE_DISPOSITION_FLAG_DELETE, FILE_DISPOSITION_FLAG_IGNORE_READONLY_ATTRIBUTE,
+    FILE_DISPOSITION_FLAG_POSIX_SEMANTICS, FILE_DISPOSITION_INFO_EX, FILE_FLAG_BACKUP_SEMANTICS,
+    FILE_FLAG_OPEN_REPARSE_POINT, FILE_FULL_DIR_INFO, FILE_LIST_DIRECTORY, FILE_SHARE_DELETE,
+    FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING, SYNCHRONIZE,
+};
+
+/// The size of the buffer directory entries are read into.
+const LIST_BUFFER: usize = 64 * 1024;
+
+/// An open handle, closed when dropped.
+struct Handle(HANDLE);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // SAFETY: the handle was opened by `open` and is closed only here.
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+pub(super) fn remove_file(path: &Path) -> io::Result<()> {
+    let path = verbatim(path)?;
+    let result = open(&path, DELETE).and_then(|file| dispose(&file));
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => std::fs::remove_file(&path),
+        result => result,
+    }
+}
+
+pub(super) fn remove_dir_all(path: &Path) -> io::Result<()> {
+    let path = verbatim(path)?;
+    match remove_tree(&path) {
+        // The standard library finishes whatever was not removed yet.
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => std::fs::remove_dir_all(&path),
+        result => result,
+    }
+}
+
+/// Removes the directory `path` and its contents. Links and junctions inside it are
+/// removed themselves, never followed.
+fn remove_tree(path: &Path) -> io::Result<()> {
+    let dir = open(path, DELETE | FILE_LIST_DIRECTORY | SYNCHRONIZE)?;
+    for (name, attributes) in list(&dir)? {
+        let child = path.join(name);
+        let result = if attributes & FILE_ATTRIBUTE_DIRECTORY != 0
+            && attributes & FILE_ATTRIBUTE_REPARSE_POINT == 0
+        {
+            remove_tree(&child)
+        } else {
+            open(&child, DELETE).and_then(|file| dispose(&file))
+        };
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+    dispose(&dir)
+}
+
+/// Opens `path` itself, not the target of a link, for `access`.
+fn open(path: &Path, access: u32) -> io::Result<Handle> {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `wide` is NUL-terminated; no security attributes or template are passed.
+    let handle = unsafe {
+        CreateFileW(
+            wide.as_ptr(),
+            access,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            ptr::null(),
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(Handle(handle))
+    }
+}
+
+/// Marks the open file or empty directory for deletion with POSIX semantics. Returns
+/// an error of kind `Unsupported` where the file system refuses them.
+fn dispose(handle: &Handle) -> io::Result<()> {
+    let info = FILE_DISPOSITION_INFO_EX {
+        Flags: FILE_DISPOSITION_FLAG_DELETE
+            | FILE_DISPOSITION_FLAG_POSIX_SEMANTICS
+            | FILE_DISPOSITION_FLAG_IGNORE_READONLY_ATTRIBUTE,
+    };
+    // SAFETY: `info` is a valid `FILE_DISPOSITION_INFO_EX` of the size passed.
+    let ok = unsafe {
+        SetFileInformationByHandle(
+            handle.0,
+            FileDispositionInfoEx,
+            ptr::addr_of!(info).cast(),
+            mem::size_of::<FILE_DISPOSITION_INFO_EX>() as u32,
+        )
+    };
+    if ok != 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error().map(|code| code as u32) {
+        Some(ERROR_INVALID_PARAMETER | ERROR_NOT_SUPPORTED | ERROR_INVALID_FUNCTION) => {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "POSIX delete semantics are unsupported",
+            ))
+        }
+        _ => Err(err),
+    }
+}
+
+/// Lists the names and attributes of the entries of the open directory `dir`.
+fn list(dir: &Handle) -> io::Result<Vec<(OsString, u32)>> {
+    let mut entries = Vec::new();
+    // `u64`s keep the records aligned.
+    let mut buffer = vec![0u64; LIST_BUFFER / mem::size_of::<u64>()];
+    loop {
+        // SAFETY: the buffer is writable for the length passed.
+        let ok = unsafe {
+            GetFileInformationByHandleEx(
+                dir.0,
+                FileFullDirectoryInfo,
+                buffer.as_mut_ptr().cast(),
+                LIST_BUFFER as u32,
+            )
+        };
+        if ok == 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(ERROR_NO_MORE_FILES as i32) {
+                return Ok(entries);
+            }
+            return Err(err);
+        }
+        let mut record = buffer.as_ptr().cast::<u8>();
+        loop {
+            let info = record.cast::<FILE_FULL_DIR_INFO>();
+            // SAFETY: the call filled the buffer with a chain of `FILE_FULL_DIR_INFO`
+            // records, each followed by its name and found `NextEntryOffset` bytes
+            // after the previous one.
+            let (name, attributes, next) = unsafe {
+                let len = (*info).FileNameLength as usize / mem::size_of::<u16>();
+                let name = ptr::addr_of!((*info).FileName).cast::<u16>();
+                let name = std::slice::from_raw_parts(name, len);
+                (
+                    name,
+                    (*info).FileAttributes,
+                    (*info).NextEntryOffset as usize,
+                )
+            };
+            if !is_dot(name) {
+                entries.push((OsString::from_wide(name), attributes));
+            }
+            if next == 0 {
+                break;
+            }
+            // SAFETY: `next` stays within the records the call wrote.
+            record = unsafe { record.add(next) };
+        }
+    }
+}
+
+/// Returns whether `name` is `.` or `..`.
+fn is_dot(name: &[u16]) -> bool {
+    const DOT: u16 = b'.' as u16;
+    matches!(name, [DOT] | [DOT, DOT])
+}
+
+/// Returns `path` as an absolute `\\?\` path, which is not limited to `MAX_PATH`.
+fn verbatim(path: &Path) -> io::Result<PathBuf> {
+    let absolute = path::absolute(path)?;
+    let wide: Vec<u16> = absolute.as_os_str().encode_wide().collect();
+    let prefix = |text: &str| wide.starts_with(&text.encode_utf16().collect::<Vec<_>>());
+    if prefix(r"\\?\") || prefix(r"\\.\") {
+        return Ok(absolute);
+    }
+    let mut verbatim: Vec<u16> = r"\\?\".encode_utf16().collect();
+    if prefix(r"\\") {
+        // A share, `\\server\share`, becomes `\\?\UNC\server\share`.
+        verbatim.extend("UNC".encode_utf16());
+        verbatim.extend_from_slice(&wide[1..]);
+    } else {
+        verbatim.extend_from_slice(&wide);
+    }
+    Ok(PathBuf::from(OsString::from_wide(&verbatim)))
+}
@@ -0,0 +1,268 @@
+//! Deleting the contents of a directory except some paths inside it.
+//!
+//! A matched directory with paths to keep is not removed as a whole. For scoped
+//! excludes the planner lists its children recursively with [`contents_except`]: kept
+//! paths are left alone, directories that may contain a kept path are descended into,
+//! and everything else is deleted whole. With `[safety] partial_delete`, the cleaner
+//! removes matched directories with [`remove_dir_except`], which keeps every path
+//! the excludes protect along with the directories containing it.
+
+use glob::{MatchOptions, Pattern, PatternError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Globs naming the paths to keep inside a directory, relative to it.
+pub struct KeepPaths {
+    /// Each glob, compiled whole and split into path components.
+    globs: Vec<(Pattern, Vec<Pattern>)>,
+}
+
+impl KeepPaths {
+    /// Compiles `globs`, such as `.cache/webpack-persistent` or `**/.env`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PatternError` if any glob is invalid.
+    pub fn new(globs: &[String]) -> Result<Self, PatternError> {
+        let globs = globs
+            .iter()
+            .map(|glob| {
+                let glob = glob.trim_matches('/');
+                let components = glob
+                    .split('/')
+                    .map(Pattern::new)
+                    .collect::<Result<_, _>>()?;
+                Ok((Pattern::new(glob)?, components))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { globs })
+    }
+
+    /// Returns true if `relative` is kept.
+    fn keeps(&self, relative: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.globs
+            .iter()
+            .any(|(glob, _)| glob.matches_path_with(relative, options))
+    }
+
+    /// Returns true if a kept path may lie below the directory `relative`.
+    fn may_contain(&self, relative: &Path) -> bool {
+        self.globs.iter().any(|(_, components)| {
+            let mut names = relative.iter();
+            for component in components {
+                if component.as_str() == "**" {
+                    return true;
+                }
+                match names.next().and_then(|name| name.to_str()) {
+                    Some(name) if component.matches(name) => {}
+                    Some(_) => return false,
+                    None => return true,
+                }
+            }
+            false
+        })
+    }
+}
+
+/// Lists what to delete to clean `dir` while leaving the paths `keep` names.
+///
+/// Returns `None` when nothing inside `dir` is kept, so the directory can be deleted
+/// whole. Otherwise returns the largest paths that can be deleted without touching a
+/// kept path. A directory that cannot be read is left untouched.
+pub fn contents_except(dir: &Path, keep: &KeepPaths) -> Option<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    collect(dir, dir, keep, &mut paths).then_some(paths)
+}
+
+/// Adds the deletable contents of `dir` to `paths`; returns true if anything in it is kept.
+fn collect(root: &Path, dir: &Path, keep: &KeepPaths, paths: &mut Vec<PathBuf>) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Cannot read {}: {}", dir.display(), err);
+            return true;
+        }
+    };
+
+    let mut kept = false;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        if keep.keeps(relative) {
+            log::debug!("Keeping {}", path.display());
+            kept = true;
+            continue;
+        }
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        if is_dir && keep.may_contain(relative) {
+            let mut inner = Vec::new();
+            if collect(root, &path, keep, &mut inner) {
+                kept = true;
+                paths.extend(inner);
+                continue;
+            }
+        }
+        paths.push(path);
+    }
+    kept
+}
+
+/// Removes a single file for [`remove_dir_except`].
+type RemoveFile<'a> = &'a dyn Fn(&Path) -> io::Result<()>;
+
+/// What [`remove_dir_except`] removed, or [`measure_dir_except`] would remove.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Removal {
+    /// The bytes of the files and links removed.
+    pub bytes_freed: u64,
+    /// The number of protected paths left in place.
+    pub kept: usize,
+}
+
+/// Removes `dir` and everything in it except the paths `protected` accepts.
+///
+/// Protected paths are left untouched, as are the directories that contain them,
+/// including `dir`. Symbolic links are removed, never followed. Files are removed
+/// with `remove_file`, so callers can overwrite them first or move them to the
+/// trash instead.
+///
+/// # Errors
+///
+/// Returns the first I/O error; what was removed before it stays removed.
+pub fn remove_dir_except(
+    dir: &Path,
+    protected: &dyn Fn(&Path) -> bool,
+    remove_file: &dyn Fn(&Path) -> io::Result<()>,
+) -> io::Result<Removal> {
+    walk_dir_except(dir, protected, Some(remove_file))
+}
+
+/// Returns what [`remove_dir_except`] would remove from `dir`, without removing it.
+///
+/// # Errors
+///
+/// Returns the first I/O error.
+pub fn measure_dir_except(dir: &Path, protected: &dyn Fn(&Path) -> bool) -> io::Result<Removal> {
+    walk_dir_except(dir, protected, None)
+}
+
+/// Walks `dir` for [`remove_dir_except`], removing what is not protected if
+/// `remove_file` is set and only adding it up otherwise.
+fn walk_dir_except(
+    dir: &Path,
+    protected: &dyn Fn(&Path) -> bool,
+    remove_file: Option<RemoveFile<'_>>,
+) -> io::Result<Removal> {
+    let mut removal = Removal::default();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if protected(&path) {
+            log::debug!("Keeping protected {}", path.display());
+            removal.kept += 1;
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let inner = walk_dir_except(&path, protected, remove_file)?;
+            removal.bytes_freed += inner.bytes_freed;
+            removal.kept += inner.kept;
+            continue;
+        }
+        removal.bytes_freed += metadata.len();
+        let Some(remove_file) = remove_file else {
+            continue;
+        };
+        #[cfg(windows)]
+        if metadata.is_symlink() && path.is_dir() {
+            fs::remove_dir(&path)?;
+            continue;
+        }
+        if metadata.is_symlink() {
+            fs::remove_file(&path)?;
+        } else {
+            remove_file(&path)?;
+        }
+    }
+    if removal.kept == 0 && remove_file.is_some() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(removal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_contents_except_keeps_protected_paths_and_ancestors() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.child("node_modules");
+        modules.child("react/index.js").touch().unwrap();
+        modules.child(".cache/babel/a.json").touch().unwrap();
+        modules
+            .child(".cache/webpack-persistent/0.pack")
+            .touch()
+            .unwrap();
+
+        let keep = KeepPaths::new(&[".cache/webpack-persistent".to_string()]).unwrap();
+        let mut paths = contents_except(modules.path(), &keep).unwrap();
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                modules.child(".cache/babel").path().to_path_buf(),
+                modules.child("react").path().to_path_buf(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contents_except_without_kept_paths_is_none() {
+        let temp = TempDir::new().unwrap();
+        temp.child("node_modules/react/index.js").touch().unwrap();
+
+        let keep = KeepPaths::new(&[".cache/*".to_string()]).unwrap();
+        assert!(contents_except(temp.child("node_modules").path(), &keep).is_none());
+    }
+
+    #[test]
+    fn test_remove_dir_except_keeps_protected_paths() {
+        let temp = TempDir::new().unwrap();
+        let dist = temp.child("dist");
+        dist.child("bundle.js").write_str("12345").unwrap();
+        dist.child("config/.env").write_str("SECRET=1").unwrap();
+        dist.child("config/app.json").touch().unwrap();
+        let empty = temp.child("empty/assets/logo.png");
+        empty.touch().unwrap();
+
+        let protected = |path: &Path| path.file_name().is_some_and(|name| name == ".env");
+        let remove = |path: &Path| fs::remove_file(path);
+        let measured = measure_dir_except(dist.path(), &protected).unwrap();
+        let removal = remove_dir_except(dist.path(), &protected, &remove).unwrap();
+
+        assert_eq!(measured, removal);
+        assert_eq!(
+            removal,
+            Removal {
+                bytes_freed: 5,
+                kept: 1
+            }
+        );
+        dist.child("config/.env").assert(predicates::path::exists());
+        dist.child("bundle.js").assert(predicates::path::missing());
+        dist.child("config/app.json")
+            .assert(predicates::path::missing());
+
+        remove_dir_except(temp.child("empty").path(), &protected, &remove).unwrap();
+        temp.child("empty").assert(predicates::path::missing());
+    }
+}
@@ -0,0 +1,353 @@
+//! This module turns the raw scan matches into the list of items that will actually
+//! be cleaned.
+//!
+//! Scanning reports every path that matched a pattern. Planning then removes
+//! redundant nested items and applies rules that replace a whole-directory deletion
+//! with a finer-grained one, such as keeping the newest N versioned children of a
+//! `dist/` directory, or deleting the contents of a directory except the paths a
+//! scoped exclude keeps.
+
+use crate::config::{Config, RetentionRule};
+use crate::engine::partial::{self, KeepPaths};
+use crate::engine::{policy, prune_nested_items};
+use crate::rules;
+use crate::types::{CleanItem, ItemType, SkipReason, SkippedItem};
+use crate::utils::fs as fs_utils;
+use glob::Pattern;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Builds the clean plan for `items` according to `config`.
+///
+/// This prunes nested items, applies ecosystem rules, retention rules and scoped
+/// excludes, drops items used more recently than `unused_for_days`, and finally
+/// drops the items the policy script keeps or defers.
+pub fn plan(items: Vec<CleanItem>, config: &Config) -> Vec<CleanItem> {
+    plan_with_skipped(items, config).0
+}
+
+/// Builds the clean plan like [`plan`], and also returns the items it left out because
+/// they are not virtualenvs despite their name, were used more recently than
+/// `unused_for_days`, or the policy script kept them.
+pub fn plan_with_skipped(
+    items: Vec<CleanItem>,
+    config: &Config,
+) -> (Vec<CleanItem>, Vec<SkippedItem>) {
+    let items = prune_nested_items(items);
+    let items = rules::cargo::apply(items, &config.rules.cargo);
+    let items = rules::node::apply(items, &config.rules.node);
+    let items = rules::docker::apply(items, &config.rules.docker);
+    let (items, mut skipped) = rules::python::apply(items, &config.rules.python);
+    let items = rules::ml::apply(items, &config.rules.ml);
+    let items = apply_retention(items, &config.retention);
+    let items = apply_scoped_excludes(items, &config.patterns.scoped_exclude);
+    let items = match config.options.unused_for_days {
+        Some(days) => {
+            let (items, recently_used) = partition_unused(items, days);
+            skipped.extend(recently_used);
+            items
+        }
+        None => items,
+    };
+    let (items, kept_by_policy) = policy::apply(items, config.options.policy_script.as_deref());
+    skipped.extend(kept_by_policy);
+    (items, skipped)
+}
+
+/// Keeps the items that have not been used for at least `days` days.
+///
+/// Items whose last use cannot be determined are dropped rather than risked.
+pub fn keep_unused(items: Vec<CleanItem>, days: u64) -> Vec<CleanItem> {
+    partition_unused(items, days).0
+}
+
+/// Splits `items` into those unused for at least `days` days and the rest, which are
+/// returned as skipped.
+fn partition_unused(items: Vec<CleanItem>, days: u64) -> (Vec<CleanItem>, Vec<SkippedItem>) {
+    let mut kept = Vec::with_capacity(items.len());
+    let mut skipped = Vec::new();
+    for item in items {
+        let unused = fs_utils::unused_days(&item.path);
+        log::debug!("{} unused for {:?} days", item.path.display(), unused);
+        if unused.is_some_and(|unused| unused >= days) {
+            kept.push(item);
+        } else {
+            skipped.push(SkippedItem {
+                path: item.path,
+                pattern: item.pattern.pattern,
+                reason: SkipReason::RecentlyUsed {
+                    unused_days: unused,
+                    unused_for_days: days,
+                },
+            });
+        }
+    }
+    (kept, skipped)
+}
+
+/// Replaces matched directories covered by a retention rule with their stale children.
+///
+/// For each item whose pattern matches a rule, the children of the item (or of its
+/// `subdir`) that match the rule's `children` glob are sorted newest first by
+/// modification time. The first `keep_latest` are kept and the rest become individual
+/// clean items. Everything else inside the directory is left untouched.
+pub fn apply_retention(items: Vec<CleanItem>, rules: &[RetentionRule]) -> Vec<CleanItem> {
+    if rules.is_empty() {
+        return items;
+    }
+
+    let mut planned = Vec::with_capacity(items.len());
+    for item in items {
+        match rules
+            .iter()
+            .find(|rule| rule.pattern == item.pattern.pattern)
+        {
+            Some(rule) => planned.extend(stale_children(&item, rule)),
+            None => planned.push(item),
+        }
+    }
+    planned
+}
+
+/// Replaces matched directories that contain a path kept by a scoped exclude with the
+/// rest of their contents.
+///
+/// `scoped` maps an include pattern to globs relative to the directories it matches.
+/// A directory with none of those paths inside it is deleted whole as usual.
+pub fn apply_scoped_excludes(
+    items: Vec<CleanItem>,
+    scoped: &BTreeMap<String, Vec<String>>,
+) -> Vec<CleanItem> {
+    if scoped.is_empty() {
+        return items;
+    }
+
+    let mut planned = Vec::with_capacity(items.len());
+    for item in items {
+        let keep = match scoped.get(&item.pattern.pattern) {
+            Some(globs) if item.item_type == ItemType::Directory => globs,
+            _ => {
+                planned.push(item);
+                continue;
+            }
+        };
+        let keep = match KeepPaths::new(keep) {
+            Ok(keep) => keep,
+            Err(err) => {
+                log::warn!(
+                    "Ignoring invalid scoped exclude for {:?}: {}",
+                    item.pattern.pattern,
+                    err
+                );
+                planned.push(item);
+                continue;
+            }
+        };
+        match partial::contents_except(&item.path, &keep) {
+            Some(paths) => planned.extend(
+                paths
+                    .into_iter()
+                    .filter_map(|path| contained_item(path, &item)),
+            ),
+            None => planned.push(item),
+        }
+    }
+    planned
+}
+
+/// Returns the children of `item` that fall outside the rule's `keep_latest` window.
+fn stale_children(item: &CleanItem, rule: &RetentionRule) -> Vec<CleanItem> {
+    let dir = match rule.subdir {
+        Some(ref subdir) => item.path.join(subdir),
+        None => item.path.clone(),
+    };
+    let filter = rule
+        .children
+        .as_deref()
+        .and_then(|glob| match Pattern::new(glob) {
+            Ok(pattern) => Some(pattern),
+            Err(err) => {
+                log::warn!("Ignoring invalid retention glob {:?}: {}", glob, err);
+                None
+            }
+        });
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Retention: cannot read {}: {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+
+    let mut children: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            filter.as_ref().is_none_or(|pattern| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| pattern.matches(name))
+            })
+        })
+        .collect();
+    children.sort_by_key(|path| Reverse(fs_utils::modified(path)));
+
+    log::debug!(
+        "Retention: keeping {} of {} children in {}",
+        rule.keep_latest.min(children.len()),
+        children.len(),
+        dir.display()
+    );
+
+    children
+        .into_iter()
+        .skip(rule.keep_latest)
+        .filter_map(|path| contained_item(path, item))
+        .collect()
+}
+
+/// Builds the clean item for `path` inside `item`, matched by the same pattern.
+fn contained_item(path: PathBuf, item: &CleanItem) -> Option<CleanItem> {
+    let item_type = fs_utils::item_type_of(&path)?;
+    Some(CleanItem {
+        size: fs_utils::item_size(&path, &item_type),
+        path,
+        item_type,
+        pattern: item.pattern.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::time::{Duration, SystemTime};
+
+    fn set_age(path: &std::path::Path, days_ago: u64) {
+        let time = SystemTime::now() - Duration::from_secs(days_ago * 86_400);
+        fs::File::open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn test_keep_latest_deletes_older_children() {
+        let temp = TempDir::new().unwrap();
+        for (name, age) in [
+            ("2024-01", 30),
+            ("2024-02", 20),
+            ("2024-03", 10),
+            ("2024-04", 1),
+        ] {
+            let child = temp.child(format!("dist/{}", name));
+            child.child("bundle.js").write_str("x").unwrap();
+            set_age(child.path(), age);
+        }
+        temp.child("dist/README").write_str("keep me").unwrap();
+
+        let rules = vec![RetentionRule {
+            pattern: "dist".to_string(),
+            subdir: None,
+            children: Some("2024-*".to_string()),
+            keep_latest: 2,
+        }];
+        let planned = apply_retention(
+            vec![clean_item(
+                temp.path().join("dist"),
+                ItemType::Directory,
+                0,
+                PatternCategory::BuildOutputs,
+            )],
+            &rules,
+        );
+
+        let mut names: Vec<_> = planned
+            .iter()
+            .map(|i| i.path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["2024-01", "2024-02"]);
+        assert!(planned.iter().all(|i| i.size == 1));
+    }
+
+    #[test]
+    fn test_items_without_rule_are_untouched() {
+        let item = clean_item(
+            std::path::Path::new("/project/dist"),
+            ItemType::Directory,
+            0,
+            PatternCategory::BuildOutputs,
+        );
+        let rules = vec![RetentionRule {
+            pattern: "target".to_string(),
+            subdir: Some("package".to_string()),
+            children: Some("*.crate".to_string()),
+            keep_latest: 1,
+        }];
+        assert_eq!(apply_retention(vec![item.clone()], &rules), vec![item]);
+    }
+
+    #[test]
+    fn test_scoped_exclude_keeps_subpath() {
+        let temp = TempDir::new().unwrap();
+        let dist = temp.child("dist");
+        dist.child("bundle.js").touch().unwrap();
+        dist.child("cache/persistent.bin").touch().unwrap();
+        let scoped = BTreeMap::from([("dist".to_string(), vec!["cache".to_string()])]);
+
+        let planned = apply_scoped_excludes(
+            vec![clean_item(
+                dist.path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::BuildOutputs,
+            )],
+            &scoped,
+        );
+
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].path, dist.child("bundle.js").path());
+        assert_eq!(planned[0].item_type, ItemType::File);
+    }
+
+    #[test]
+    fn test_keep_unused_drops_recently_used_items() {
+        let temp = TempDir::new().unwrap();
+        let old = temp.child("old/dist");
+        old.child("bundle.js").touch().unwrap();
+        let long_ago = SystemTime::now() - Duration::from_secs(100 * 86_400);
+        let times = fs::FileTimes::new()
+            .set_accessed(long_ago)
+            .set_modified(long_ago);
+        fs::File::open(old.child("bundle.js").path())
+            .unwrap()
+            .set_times(times)
+            .unwrap();
+        set_age(old.path(), 100);
+        let fresh = temp.child("fresh/dist");
+        fresh.child("bundle.js").touch().unwrap();
+
+        let items = vec![
+            clean_item(
+                old.path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::BuildOutputs,
+            ),
+            clean_item(
+                fresh.path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::BuildOutputs,
+            ),
+        ];
+        let kept = keep_unused(items, 90);
+
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path, old.path());
+    }
+}
@@ -0,0 +1,400 @@
+//! This module runs `options.policy_script`, a [rhai](https://rhai.rs) script that
+//! decides item by item whether a planned clean goes ahead, for site rules too
+//! involved for the configuration.
+//!
+//! The script defines `fn policy(item)`. `item` is a map with the item's `path`,
+//! `size`, `item_type` (`"directory"`, `"file"` or `"symlink"`), `category` (such as
+//! `"BuildOutputs"`), `pattern`, `age_days`, `unused_days` and `project`, the last
+//! three `()` when unknown. Those three take file system work, so they are only
+//! gathered, in parallel, when the script's source mentions them. It returns `"delete"`, `"keep"` or `"defer"`, or a map
+//! such as `#{ decision: "keep", reason: "release build" }`:
+//!
+//! ```text
+//! fn policy(item) {
+//!     if item.path.contains("/release/") {
+//!         return #{ decision: "keep", reason: "release build" };
+//!     }
+//!     if item.category == "Dependencies" && item.unused_days < 14 {
+//!         return "defer";
+//!     }
+//!     "delete"
+//! }
+//! ```
+//!
+//! Kept and deferred items are left alone and reported as skipped: `keep` marks items
+//! the site wants protected, `defer` items a later run may clean. Scripts have no
+//! access to the file system, and each call may run at most [`MAX_OPERATIONS`]
+//! operations. Items are kept rather than risked when the script cannot be loaded or
+//! fails on them, and when mc was built without the `scripting` feature.
+
+use crate::types::{CleanItem, PolicyDecision, SkipReason, SkippedItem};
+use crate::utils::fs as fs_utils;
+use std::path::Path;
+
+/// The most operations one call of the script may run, which stops endless loops.
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Splits `items` into those the script at `script` lets be cleaned and the rest,
+/// which are returned as skipped. Without a script, every item is cleaned.
+pub fn apply(items: Vec<CleanItem>, script: Option<&Path>) -> (Vec<CleanItem>, Vec<SkippedItem>) {
+    let Some(script) = script else {
+        return (items, Vec::new());
+    };
+    if items.is_empty() {
+        return (items, Vec::new());
+    }
+    let script = fs_utils::expand_home(script);
+    match Policy::load(&script) {
+        Ok(policy) => policy.partition(items),
+        Err(error) => {
+            log::warn!(
+                "Keeping all {} planned items, policy script {} failed: {}",
+                items.len(),
+                script.display(),
+                error
+            );
+            let reason = SkipReason::PolicyFailed { error };
+            let skipped = items
+                .into_iter()
+                .map(|item| skip(item, reason.clone()))
+                .collect();
+            (Vec::new(), skipped)
+        }
+    }
+}
+
+fn skip(item: CleanItem, reason: SkipReason) -> SkippedItem {
+    SkippedItem {
+        path: item.path,
+        pattern: item.pattern.pattern,
+        reason,
+    }
+}
+
+/// A loaded policy script.
+#[cfg(feature = "scripting")]
+struct Policy {
+    engine: rhai::Engine,
+    ast: rhai::AST,
+    /// The item fields that take file system work and that the script mentions.
+    fields: Fields,
+}
+
+/// What the script decided for an item, with its reason, or why it failed.
+type Decision = Result<(PolicyDecision, Option<String>), String>;
+
+/// Without the `scripting` feature no script can be loaded.
+#[cfg(not(feature = "scripting"))]
+enum Policy {}
+
+impl Policy {
+    fn partition(&self, items: Vec<CleanItem>) -> (Vec<CleanItem>, Vec<SkippedItem>) {
+        let decisions = self.decide_all(&items);
+        let mut kept = Vec::with_capacity(items.len());
+        let mut skipped = Vec::new();
+        for (item, decision) in items.into_iter().zip(decisions) {
+            match decision {
+                Ok((PolicyDecision::Delete, _)) => kept.push(item),
+                Ok((decision, reason)) => {
+                    log::debug!("Policy: {:?} {}", decision, item.path.display());
+                    skipped.push(skip(item, SkipReason::Policy { decision, reason }));
+                }
+                Err(error) => {
+                    log::warn!(
+                        "Keeping {}, policy script failed: {}",
+                        item.path.display(),
+                        error
+                    );
+                    skipped.push(skip(item, SkipReason::PolicyFailed { error }));
+                }
+            }
+        }
+        (kept, skipped)
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+impl Policy {
+    fn load(_script: &Path) -> Result<Self, String> {
+        Err("mc was built without the `scripting` feature".to_string())
+    }
+
+    fn decide_all(&self, _items: &[CleanItem]) -> Vec<Decision> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl Policy {
+    fn load(script: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(script).map_err(|err| err.to_string())?;
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine.compile(&source).map_err(|err| err.to_string())?;
+        let fields = Fields {
+            age_days: source.contains("age_days"),
+            unused_days: source.contains("unused_days"),
+            project: source.contains("project"),
+        };
+        Ok(Self {
+            engine,
+            ast,
+            fields,
+        })
+    }
+
+    /// Decides every item. The file system facts are gathered in parallel first, as
+    /// the engine itself runs on one thread.
+    fn decide_all(&self, items: &[CleanItem]) -> Vec<Decision> {
+        use rayon::prelude::*;
+
+        let fields = &self.fields;
+        let facts: Vec<Facts> = items.par_iter().map(|item| fields.gather(item)).collect();
+        items
+            .iter()
+            .zip(facts)
+            .map(|(item, facts)| self.decide(item, facts))
+            .collect()
+    }
+
+    fn decide(&self, item: &CleanItem, facts: Facts) -> Decision {
+        let mut scope = rhai::Scope::new();
+        let value: rhai::Dynamic = self
+            .engine
+            .call_fn(&mut scope, &self.ast, "policy", (item_map(item, facts),))
+            .map_err(|err| err.to_string())?;
+        parse_decision(value)
+    }
+}
+
+/// Which of the costly item fields are gathered. One the script never mentions is
+/// left `()`, saving a walk of every item for `unused_days`.
+#[cfg(feature = "scripting")]
+struct Fields {
+    age_days: bool,
+    unused_days: bool,
+    project: bool,
+}
+
+/// The costly item fields of one item.
+#[cfg(feature = "scripting")]
+struct Facts {
+    age_days: Option<u64>,
+    unused_days: Option<u64>,
+    project: Option<std::path::PathBuf>,
+}
+
+#[cfg(feature = "scripting")]
+impl Fields {
+    fn gather(&self, item: &CleanItem) -> Facts {
+        Facts {
+            age_days: self
+                .age_days
+                .then(|| fs_utils::age_days(&item.path))
+                .flatten(),
+            unused_days: self
+                .unused_days
+                .then(|| fs_utils::unused_days(&item.path))
+                .flatten(),
+            project: self
+                .project
+                .then(|| fs_utils::project_root(&item.path))
+                .flatten(),
+        }
+    }
+}
+
+/// Describes `item` to the script.
+#[cfg(feature = "scripting")]
+fn item_map(item: &CleanItem, facts: Facts) -> rhai::Map {
+    use crate::types::ItemType;
+    use rhai::Dynamic;
+
+    let days = |days: Option<u64>| {
+        days.map_or(Dynamic::UNIT, |days| {
+            Dynamic::from_int(days.min(i64::MAX as u64) as i64)
+        })
+    };
+    let item_type = match item.item_type {
+        ItemType::Directory => "directory",
+        ItemType::File => "file",
+        ItemType::Symlink => "symlink",
+    };
+    let project = facts.project.map_or(Dynamic::UNIT, |project| {
+        project.display().to_string().into()
+    });
+
+    let mut map = rhai::Map::new();
+    map.insert("path".into(), item.path.display().to_string().into());
+    map.insert(
+        "size".into(),
+        Dynamic::from_int(item.size.min(i64::MAX as u64) as i64),
+    );
+    map.insert("item_type".into(), item_type.into());
+    map.insert(
+        "category".into(),
+        format!("{:?}", item.pattern.category).into(),
+    );
+    map.insert("pattern".into(), item.pattern.pattern.clone().into());
+    map.insert("age_days".into(), days(facts.age_days));
+    map.insert("unused_days".into(), days(facts.unused_days));
+    map.insert("project".into(), project);
+    map
+}
+
+/// Reads the decision returned by the script.
+#[cfg(feature = "scripting")]
+fn parse_decision(value: rhai::Dynamic) -> Decision {
+    let (decision, reason) = if value.is_map() {
+        let map = value.cast::<rhai::Map>();
+        let decision = map
+            .get("decision")
+            .and_then(|decision| decision.clone().into_string().ok());
+        let Some(decision) = decision else {
+            return Err("the returned map has no `decision` string".to_string());
+        };
+        let reason = map
+            .get("reason")
+            .and_then(|reason| reason.clone().into_string().ok());
+        (decision, reason)
+    } else {
+        let decision = value
+            .into_string()
+            .map_err(|type_name| format!("`policy` returned {}, not a decision", type_name))?;
+        (decision, None)
+    };
+    let decision = match decision.as_str() {
+        "delete" => PolicyDecision::Delete,
+        "keep" => PolicyDecision::Keep,
+        "defer" => PolicyDecision::Defer,
+        other => {
+            return Err(format!(
+                "unknown decision `{}`; expected delete, keep or defer",
+                other
+            ))
+        }
+    };
+    Ok((decision, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn test_script_decides_per_item() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.child("policy.rhai");
+        script
+            .write_str(
+                r#"
+                fn policy(item) {
+                    if item.path.ends_with("keep") { return #{ decision: "keep", reason: "pinned" }; }
+                    if item.category == "Dependencies" { return "defer"; }
+                    if item.path.ends_with("broken") { return 42; }
+                    "delete"
+                }
+                "#,
+            )
+            .unwrap();
+        let items = vec![
+            clean_item(
+                temp.path().join("a/dist"),
+                ItemType::Directory,
+                10,
+                PatternCategory::BuildOutputs,
+            ),
+            clean_item(
+                temp.path().join("keep"),
+                ItemType::Directory,
+                10,
+                PatternCategory::BuildOutputs,
+            ),
+            clean_item(
+                temp.path().join("node_modules"),
+                ItemType::Directory,
+                10,
+                PatternCategory::Dependencies,
+            ),
+            clean_item(
+                temp.path().join("broken"),
+                ItemType::Directory,
+                10,
+                PatternCategory::BuildOutputs,
+            ),
+        ];
+
+        let (kept, skipped) = apply(items, Some(script.path()));
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].path.ends_with("a/dist"));
+        assert_eq!(
+            skipped[0].reason,
+            SkipReason::Policy {
+                decision: PolicyDecision::Keep,
+                reason: Some("pinned".to_string())
+            }
+        );
+        assert_eq!(
+            skipped[1].reason,
+            SkipReason::Policy {
+                decision: PolicyDecision::Defer,
+                reason: None
+            }
+        );
+        assert!(matches!(skipped[2].reason, SkipReason::PolicyFailed { .. }));
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn test_fields_the_script_never_reads_are_not_gathered() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/Cargo.toml").touch().unwrap();
+        temp.child("app/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        let script = temp.child("policy.rhai");
+        script
+            .write_str(
+                r#"fn policy(item) { if item.unused_days == () { "keep" } else { "delete" } }"#,
+            )
+            .unwrap();
+        let item = clean_item(
+            temp.path().join("app/target"),
+            ItemType::Directory,
+            6,
+            PatternCategory::BuildOutputs,
+        );
+
+        let policy = Policy::load(script.path()).unwrap();
+        assert!(policy.fields.unused_days);
+        assert!(!policy.fields.age_days && !policy.fields.project);
+        let facts = policy.fields.gather(&item);
+        assert!(facts.unused_days.is_some());
+        assert!(facts.age_days.is_none() && facts.project.is_none());
+        let (kept, _) = apply(vec![item], Some(script.path()));
+        assert_eq!(kept.len(), 1);
+    }
+
+    #[test]
+    fn test_unusable_script_keeps_everything() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.child("policy.rhai");
+        script.write_str("fn policy(item) {").unwrap();
+        let items = vec![clean_item(
+            temp.path().join("dist"),
+            ItemType::Directory,
+            10,
+            PatternCategory::BuildOutputs,
+        )];
+
+        let (kept, skipped) = apply(items.clone(), Some(script.path()));
+        assert!(kept.is_empty());
+        assert!(matches!(skipped[0].reason, SkipReason::PolicyFailed { .. }));
+        assert_eq!(apply(items, None).0.len(), 1);
+    }
+}
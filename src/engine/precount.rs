@@ -0,0 +1,153 @@
+//! A fast pre-count of the entries a scan will visit.
+//!
+//! The count walks the same tree as the [`Scanner`](super::Scanner) but reads only
+//! directory entries, never file metadata, so it usually finishes well before the scan.
+//! It runs on a background thread next to the scan and publishes its result to the
+//! shared [`ScanStats`], which lets progress displays show a total and a percentage.
+//! The count is cancelled as soon as the scan finishes, so it never delays a run.
+
+use super::scanner::skips_traversal;
+use crate::utils::progress::ScanStats;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use walkdir::WalkDir;
+
+/// Counts the entries under `root`, including `root` itself, without entering the
+/// directories named in `skip`.
+///
+/// Returns `None` if `cancel` was set before the walk completed.
+pub fn count_entries(
+    root: &Path,
+    max_depth: usize,
+    follow_links: bool,
+    same_file_system: bool,
+    skip: &[String],
+    cancel: &AtomicBool,
+) -> Option<usize> {
+    let mut count = 0;
+    let walk = WalkDir::new(root)
+        .max_depth(max_depth)
+        .follow_links(follow_links)
+        .same_file_system(same_file_system)
+        .into_iter()
+        .filter_entry(|entry| !skips_traversal(entry, skip));
+    for _ in walk {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        count += 1;
+    }
+    Some(count)
+}
+
+/// A pre-count running in the background.
+///
+/// Dropping the handle cancels the count and waits for its thread to exit.
+pub struct PreCount {
+    cancel: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PreCount {
+    /// Starts counting the entries under `root`, storing the total in `stats` when done.
+    pub fn spawn(
+        root: PathBuf,
+        max_depth: usize,
+        follow_links: bool,
+        same_file_system: bool,
+        skip: Vec<String>,
+        stats: Arc<ScanStats>,
+    ) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let cancel = Arc::clone(&cancel);
+            std::thread::spawn(move || {
+                let total = count_entries(
+                    &root,
+                    max_depth,
+                    follow_links,
+                    same_file_system,
+                    &skip,
+                    &cancel,
+                );
+                if let Some(total) = total {
+                    stats.set_total(total);
+                }
+            })
+        };
+        Self {
+            cancel,
+            handle: Some(handle),
+        }
+    }
+
+    /// Cancels the count if it is still running.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for PreCount {
+    fn drop(&mut self) {
+        self.cancel();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_count_entries_and_cancel() {
+        let temp = TempDir::new().unwrap();
+        temp.child("a/b/c.txt").touch().unwrap();
+        temp.child("d.txt").touch().unwrap();
+
+        let cancel = AtomicBool::new(false);
+        // root, a, a/b, a/b/c.txt, d.txt
+        assert_eq!(
+            count_entries(temp.path(), 10, false, false, &[], &cancel),
+            Some(5)
+        );
+        assert_eq!(
+            count_entries(temp.path(), 1, false, false, &[], &cancel),
+            Some(3)
+        );
+        let skip = ["b".to_string()];
+        assert_eq!(
+            count_entries(temp.path(), 10, false, false, &skip, &cancel),
+            Some(3)
+        );
+
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(
+            count_entries(temp.path(), 10, false, false, &[], &cancel),
+            None
+        );
+    }
+
+    #[test]
+    fn test_precount_publishes_total() {
+        let temp = TempDir::new().unwrap();
+        temp.child("a.txt").touch().unwrap();
+
+        let stats = Arc::new(ScanStats::new());
+        let mut precount = PreCount::spawn(
+            temp.path().to_path_buf(),
+            10,
+            false,
+            false,
+            Vec::new(),
+            Arc::clone(&stats),
+        );
+        precount.handle.take().unwrap().join().unwrap();
+        assert_eq!(stats.total(), Some(2));
+    }
+}
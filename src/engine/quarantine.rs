@@ -0,0 +1,290 @@
+//! Moving cleaned items into a quarantine directory instead of deleting them, for
+//! `[quarantine]`.
+//!
+//! Each clean gets a batch directory named `mc-<unix seconds>` in the quarantine
+//! directory. Items are stored in it under their absolute path without the leading
+//! `/`, like archive entries, so `mv <batch>/home/me/app/target /home/me/app/` puts
+//! one back. Items are renamed where they can be; an item on another file system than
+//! the quarantine is copied in and then deleted. The space is only freed when a batch
+//! is purged, oldest first, once the quarantine holds more than `max_total`. Each
+//! batch records its size once it is written, so sizing the quarantine after a clean
+//! does not walk every batch.
+
+use std::fs;
+use std::io;
+use std::path::{self, Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::utils::fs as fs_utils;
+
+/// The file in a batch recording the bytes moved into it.
+const BYTES_FILE: &str = ".mc-bytes";
+
+/// A batch of items moved into the quarantine by one clean.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// The batch directory.
+    pub path: PathBuf,
+    /// When the clean that wrote the batch started, from its name.
+    pub created: SystemTime,
+    /// The bytes the batch holds.
+    pub bytes: u64,
+}
+
+/// What a quarantine directory holds.
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    /// The batches, oldest first.
+    pub batches: Vec<Batch>,
+}
+
+impl Usage {
+    /// Returns the bytes all batches hold.
+    pub fn total_bytes(&self) -> u64 {
+        self.batches.iter().map(|batch| batch.bytes).sum()
+    }
+}
+
+/// Creates a new, uniquely named batch directory in `dir` and returns its path.
+///
+/// `dir` may start with `~/` and is created if it does not exist.
+pub fn create_batch(dir: &Path) -> io::Result<PathBuf> {
+    let dir = fs_utils::expand_home(dir);
+    fs::create_dir_all(&dir)?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Two cleans within the same second get numbered batches.
+    let mut attempt = 0u32;
+    loop {
+        let name = match attempt {
+            0 => format!("mc-{}", secs),
+            n => format!("mc-{}-{}", secs, n),
+        };
+        let path = dir.join(name);
+        match fs::create_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Moves `path` into `batch`, under its absolute path. When `path` is on another file
+/// system than the batch, it is copied in and then deleted.
+///
+/// # Errors
+///
+/// Returns the I/O error of the rename, or of the copy, in which case `path` is left
+/// in place and the partial copy removed.
+pub fn move_into(batch: &Path, path: &Path) -> io::Result<()> {
+    let absolute = path::absolute(path)?;
+    let target: PathBuf = absolute
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    let target = batch.join(target);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(path, &target) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            log::debug!("Copying {} into the quarantine", path.display());
+            if let Err(err) = copy_all(path, &target) {
+                let _ = remove_all(&target);
+                return Err(err);
+            }
+            remove_all(path)
+        }
+        result => result,
+    }
+}
+
+/// Records the bytes `batch` holds, once a clean is done moving items into it, for
+/// [`usage`].
+pub fn record_size(batch: &Path) -> io::Result<()> {
+    fs::write(
+        batch.join(BYTES_FILE),
+        fs_utils::dir_size(batch).to_string(),
+    )
+}
+
+/// Copies `from` to `to`, recreating symbolic links rather than following them.
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        let link = fs::read_link(from)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(link, to);
+        #[cfg(windows)]
+        return if from.is_dir() {
+            std::os::windows::fs::symlink_dir(link, to)
+        } else {
+            std::os::windows::fs::symlink_file(link, to)
+        };
+    }
+    if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return fs::set_permissions(to, metadata.permissions());
+    }
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Removes `path`, a directory with its contents or anything else.
+fn remove_all(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Lists the batches in the quarantine directory `dir`. A missing directory holds
+/// nothing. A batch is sized by the bytes recorded in it, or by walking it if it has
+/// no record.
+pub fn usage(dir: &Path) -> io::Result<Usage> {
+    let dir = fs_utils::expand_home(dir);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Usage::default()),
+        Err(err) => return Err(err),
+    };
+    let mut batches = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let Some(order) = entry.file_name().to_str().and_then(batch_order) else {
+            continue;
+        };
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        let batch = Batch {
+            bytes: recorded_bytes(&path).unwrap_or_else(|| fs_utils::dir_size(&path)),
+            created: UNIX_EPOCH + Duration::from_secs(order.0),
+            path,
+        };
+        batches.push((order, batch));
+    }
+    batches.sort_by_key(|(order, _)| *order);
+    Ok(Usage {
+        batches: batches.into_iter().map(|(_, batch)| batch).collect(),
+    })
+}
+
+/// Purges the oldest batches in `dir` until it holds at most `max_total` bytes, even
+/// the newest one if it alone holds more. Returns the purged batches.
+///
+/// A batch that cannot be removed entirely is logged and counted as it was before.
+pub fn purge(dir: &Path, max_total: u64) -> io::Result<Vec<Batch>> {
+    let usage = usage(dir)?;
+    let mut total = usage.total_bytes();
+    let mut purged = Vec::new();
+    for batch in usage.batches {
+        if total <= max_total {
+            break;
+        }
+        match fs::remove_dir_all(&batch.path) {
+            Ok(()) => {
+                log::info!("Purged quarantine batch {}", batch.path.display());
+                total -= batch.bytes;
+                purged.push(batch);
+            }
+            Err(err) => {
+                log::warn!("Cannot purge {}: {}", batch.path.display(), err);
+            }
+        }
+    }
+    Ok(purged)
+}
+
+/// Reads the bytes [`record_size`] recorded in `batch`.
+fn recorded_bytes(batch: &Path) -> Option<u64> {
+    fs::read_to_string(batch.join(BYTES_FILE))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Parses a batch name such as `mc-1700000000-2` into its seconds and number.
+fn batch_order(name: &str) -> Option<(u64, u32)> {
+    let rest = name.strip_prefix("mc-")?;
+    match rest.split_once('-') {
+        Some((secs, n)) => Some((secs.parse().ok()?, n.parse().ok()?)),
+        None => Some((rest.parse().ok()?, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_move_and_purge_oldest_first() {
+        let temp = TempDir::new().unwrap();
+        let quarantine = temp.child("quarantine");
+        let target = temp.child("app/target");
+        target.child("out.bin").write_binary(&[0; 100]).unwrap();
+
+        let batch = create_batch(quarantine.path()).unwrap();
+        move_into(&batch, target.path()).unwrap();
+        target.assert(predicates::path::missing());
+        let moved: PathBuf = target.path().components().skip(1).collect();
+        assert!(batch.join(moved).join("out.bin").is_file());
+
+        let old = quarantine.child("mc-1000");
+        old.child("old.bin").write_binary(&[0; 50]).unwrap();
+        quarantine
+            .child("notes.txt")
+            .write_str("not a batch")
+            .unwrap();
+
+        record_size(&batch).unwrap();
+        fs::write(batch.join("unrecorded.bin"), [0; 100]).unwrap();
+        let usage = usage(quarantine.path()).unwrap();
+        assert_eq!(usage.batches.len(), 2);
+        assert_eq!(usage.batches[0].path, old.path());
+        assert_eq!(usage.total_bytes(), 150);
+
+        let purged = purge(quarantine.path(), 120).unwrap();
+        assert_eq!(purged.len(), 1);
+        old.assert(predicates::path::missing());
+        assert!(batch.exists());
+        quarantine
+            .child("notes.txt")
+            .assert(predicates::path::exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_keeps_links_as_links() {
+        let temp = TempDir::new().unwrap();
+        let target = temp.child("app/target");
+        target
+            .child("debug/out.bin")
+            .write_binary(&[0; 10])
+            .unwrap();
+        std::os::unix::fs::symlink("debug", target.child("latest").path()).unwrap();
+
+        let copy = temp.child("copy");
+        copy_all(target.path(), copy.path()).unwrap();
+        remove_all(target.path()).unwrap();
+        target.assert(predicates::path::missing());
+        assert_eq!(
+            fs::read(copy.child("debug/out.bin").path()).unwrap(),
+            [0; 10]
+        );
+        assert_eq!(
+            fs::read_link(copy.child("latest").path()).unwrap(),
+            Path::new("debug")
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Ranking planned items by how worthwhile deleting each is, for `mc recommend`.
+//!
+//! An item's score grows with its size, with how long it has gone unused, and with how
+//! cheap it is to get back. Sizes count logarithmically, so one large item does not
+//! drown out everything else; age counts half after a week unused and approaches full
+//! weight after a few months, so what a project is using right now scores near zero.
+//! Dependencies and caches come back with an install or on their own, build outputs
+//! need a rebuild, and machine learning artifacts may need days of training, so they
+//! only score high once they are both large and long unused.
+
+use crate::types::{CleanItem, PatternCategory};
+use crate::utils::format;
+use crate::utils::fs as fs_utils;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+/// The score from which an item is suggested for deletion.
+pub const SUGGEST_SCORE: f64 = 1.0;
+
+/// The days unused at which an item's age counts half.
+const HALF_AGE_DAYS: f64 = 7.0;
+
+/// How costly it is to get an item back once deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RebuildCost {
+    /// Restored by an install, or recreated by the tools on their own.
+    Cheap,
+    /// Restored by a build.
+    Moderate,
+    /// Restored by rerunning experiments or training, if at all.
+    Expensive,
+}
+
+impl RebuildCost {
+    /// Returns the rebuild cost of the items of `category`.
+    pub fn of(category: PatternCategory) -> Self {
+        match category {
+            PatternCategory::Dependencies
+            | PatternCategory::Cache
+            | PatternCategory::IDE
+            | PatternCategory::Logs => Self::Cheap,
+            PatternCategory::BuildOutputs | PatternCategory::Other => Self::Moderate,
+            PatternCategory::ML => Self::Expensive,
+        }
+    }
+
+    /// Returns a lowercase label for the cost.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Cheap => "cheap",
+            Self::Moderate => "moderate",
+            Self::Expensive => "expensive",
+        }
+    }
+
+    /// The factor the score of an item with this cost is weighted by.
+    fn weight(self) -> f64 {
+        match self {
+            Self::Cheap => 1.0,
+            Self::Moderate => 0.6,
+            Self::Expensive => 0.15,
+        }
+    }
+}
+
+/// A planned item with its score, from [`rank`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recommendation {
+    /// The planned item.
+    pub item: CleanItem,
+    /// The whole days since the item was last used, if known.
+    pub age_days: Option<u64>,
+    /// How costly the item is to get back.
+    pub rebuild: RebuildCost,
+    /// The item's score; higher is more worth deleting.
+    pub score: f64,
+    /// Whether the score reaches [`SUGGEST_SCORE`].
+    pub suggested: bool,
+}
+
+impl Recommendation {
+    /// Scores `item`, unused for `age_days`. An item of unknown age scores zero.
+    pub fn new(item: CleanItem, age_days: Option<u64>) -> Self {
+        let rebuild = RebuildCost::of(item.pattern.category);
+        let score = score(item.size, age_days.unwrap_or(0), rebuild);
+        Self {
+            item,
+            age_days,
+            rebuild,
+            score,
+            suggested: score >= SUGGEST_SCORE,
+        }
+    }
+}
+
+/// Returns the score of an item of `bytes`, unused for `age_days`, with `rebuild` cost.
+pub fn score(bytes: u64, age_days: u64, rebuild: RebuildCost) -> f64 {
+    let size = (bytes as f64 / 1_000_000.0 + 1.0).ln();
+    let age = age_days as f64 / (age_days as f64 + HALF_AGE_DAYS);
+    size * age * rebuild.weight()
+}
+
+/// Scores `items` by their size, the days since they were last used and their
+/// category, highest first.
+///
+/// Finding when an item was last used walks all of it, so items too small or too
+/// costly to rebuild to be suggested however long unused are not walked; their age
+/// is unknown and they score zero.
+pub fn rank(items: &[CleanItem]) -> Vec<Recommendation> {
+    let mut ranked: Vec<Recommendation> = items
+        .par_iter()
+        .map(|item| {
+            // The age weighs at most 1, which the longest possible age comes close to.
+            let rebuild = RebuildCost::of(item.pattern.category);
+            let age_days = (score(item.size, u64::MAX, rebuild) >= SUGGEST_SCORE)
+                .then(|| fs_utils::unused_days(&item.path))
+                .flatten();
+            Recommendation::new(item.clone(), age_days)
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.item.path.cmp(&b.item.path))
+    });
+    ranked
+}
+
+/// Renders `ranked` as a table with a row per item.
+pub fn render_table(ranked: &[Recommendation]) -> String {
+    let mut rows: Vec<[String; 6]> = vec![[
+        "SCORE".to_string(),
+        "SIZE".to_string(),
+        "UNUSED".to_string(),
+        "REBUILD".to_string(),
+        "CATEGORY".to_string(),
+        "PATH".to_string(),
+    ]];
+    for recommendation in ranked {
+        rows.push([
+            format!("{:.1}", recommendation.score),
+            format::size(recommendation.item.size),
+            match recommendation.age_days {
+                Some(days) => format!("{}d", format::count(days)),
+                None => "-".to_string(),
+            },
+            recommendation.rebuild.label().to_string(),
+            recommendation.item.pattern.category.label().to_string(),
+            recommendation.item.path.display().to_string(),
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..5)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut table = String::new();
+    let notes = ranked
+        .iter()
+        .map(|recommendation| recommendation.item.pattern.rebuild.as_ref());
+    let notes = std::iter::once(None).chain(notes);
+    for (row, note) in rows.iter().zip(notes) {
+        for (cell, width) in row.iter().zip(&widths).take(3) {
+            let _ = write!(table, "{:>width$}  ", cell, width = width);
+        }
+        for (cell, width) in row.iter().zip(&widths).skip(3) {
+            let _ = write!(table, "{:<width$}  ", cell, width = width);
+        }
+        let _ = writeln!(table, "{}", row[5]);
+        if let Some(note) = note {
+            let indent: usize = widths.iter().map(|width| width + 2).sum();
+            let _ = writeln!(table, "{:indent$}↳ rebuild: {}", "", note, indent = indent);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::ItemType;
+
+    #[test]
+    fn test_scores_weigh_size_age_and_rebuild_cost() {
+        let deps = clean_item(
+            "node_modules",
+            ItemType::Directory,
+            500_000_000,
+            PatternCategory::Dependencies,
+        );
+        let checkpoints = clean_item(
+            "checkpoints",
+            ItemType::Directory,
+            2_000_000_000,
+            PatternCategory::ML,
+        );
+
+        // Old dependencies are suggested; larger checkpoints of the same age are not yet.
+        assert!(Recommendation::new(deps.clone(), Some(30)).suggested);
+        assert!(!Recommendation::new(checkpoints.clone(), Some(30)).suggested);
+        assert!(Recommendation::new(checkpoints, Some(365)).suggested);
+        // What was used today, or whose age is unknown, is never suggested.
+        assert!(!Recommendation::new(deps.clone(), Some(0)).suggested);
+        assert!(!Recommendation::new(deps, None).suggested);
+
+        let base = score(100_000_000, 30, RebuildCost::Cheap);
+        assert!(score(1_000_000, 30, RebuildCost::Cheap) < base);
+        assert!(score(100_000_000, 3, RebuildCost::Cheap) < base);
+        assert!(score(100_000_000, 30, RebuildCost::Moderate) < base);
+    }
+
+    #[test]
+    fn test_items_that_cannot_be_suggested_are_not_walked() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().to_str().unwrap();
+        let small = clean_item(path, ItemType::Directory, 1_000, PatternCategory::Cache);
+        let large = clean_item(
+            path,
+            ItemType::Directory,
+            500_000_000,
+            PatternCategory::Cache,
+        );
+
+        let ranked = rank(&[small, large]);
+        let age = |size| {
+            ranked
+                .iter()
+                .find(|r| r.item.size == size)
+                .unwrap()
+                .age_days
+        };
+        assert_eq!(age(500_000_000), Some(0));
+        assert_eq!(age(1_000), None);
+    }
+}
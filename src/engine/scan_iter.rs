@@ -0,0 +1,228 @@
+//! This module lets scan results be consumed as they are found.
+//!
+//! [`Scanner::iter`] runs the scan on a background thread and returns a [`ScanIter`],
+//! which yields each matched [`CleanItem`] as soon as a worker matches it, so a caller
+//! such as a GUI can show the first results while the walk goes on. With the `async`
+//! feature, `ScanIter` is also a [`Stream`](futures_core::Stream) of the same items.
+
+use crate::engine::Scanner;
+use crate::types::{CleanItem, Result, ScanOutcome};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::Waker;
+use std::thread::{self, JoinHandle};
+
+/// An iterator over the items of a scan running on a background thread.
+///
+/// Items arrive in the order workers match them, not sorted, and a matched directory
+/// is yielded before its contents are walked, so its `size` is 0; [`ScanIter::finish`]
+/// returns the complete [`ScanOutcome`], with directory sizes, errors and totals.
+///
+/// Dropping the iterator stops the walk early.
+pub struct ScanIter {
+    queue: Arc<ItemQueue>,
+    handle: Option<JoinHandle<Result<ScanOutcome>>>,
+}
+
+impl ScanIter {
+    /// Starts `scanner` on a background thread.
+    pub(crate) fn spawn(scanner: Scanner) -> Self {
+        let queue = Arc::new(ItemQueue::default());
+        let worker_queue = Arc::clone(&queue);
+        let handle = thread::spawn(move || {
+            // The iterator ends even if the scan panics.
+            let _done = Finished(&worker_queue);
+            scanner.scan_into(Some(&worker_queue))
+        });
+        Self {
+            queue,
+            handle: Some(handle),
+        }
+    }
+
+    /// Waits for the scan to finish and returns its outcome, as [`Scanner::scan`] would
+    /// have. Items not yet taken from the iterator are in the outcome too.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the scan failed with, if any.
+    pub fn finish(mut self) -> Result<ScanOutcome> {
+        let handle = self.handle.take().expect("scan thread is joined only once");
+        handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+impl Iterator for ScanIter {
+    type Item = CleanItem;
+
+    fn next(&mut self) -> Option<CleanItem> {
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                return Some(item);
+            }
+            if state.done {
+                return None;
+            }
+            state = self
+                .queue
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for ScanIter {
+    type Item = CleanItem;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<CleanItem>> {
+        use std::task::Poll;
+
+        let mut state = self.queue.lock();
+        if let Some(item) = state.items.pop_front() {
+            return Poll::Ready(Some(item));
+        }
+        if state.done {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ScanIter {
+    fn drop(&mut self) {
+        self.queue.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The items matched so far by a scan, waiting to be taken by its [`ScanIter`].
+#[derive(Default)]
+pub(crate) struct ItemQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    cancelled: AtomicBool,
+}
+
+#[derive(Default)]
+struct QueueState {
+    items: VecDeque<CleanItem>,
+    done: bool,
+    waker: Option<Waker>,
+}
+
+impl ItemQueue {
+    /// Adds a matched item, waking the consumer.
+    pub(crate) fn push(&self, item: CleanItem) {
+        let mut state = self.lock();
+        state.items.push_back(item);
+        self.notify(state);
+    }
+
+    /// Returns true once the consumer has gone, so the scan can stop.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self, mut state: MutexGuard<'_, QueueState>) {
+        let waker = state.waker.take();
+        drop(state);
+        self.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Marks the queue as complete when the scan thread ends.
+struct Finished<'a>(&'a ItemQueue);
+
+impl Drop for Finished<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.done = true;
+        self.0.notify(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::patterns::PatternMatcher;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn scanner(temp: &TempDir) -> Scanner {
+        let matcher = PatternMatcher::from_config(&Config::default()).unwrap();
+        Scanner::new(temp.path().to_path_buf(), Arc::new(matcher))
+    }
+
+    #[test]
+    fn test_iter_yields_the_items_of_scan() {
+        let temp = TempDir::new().unwrap();
+        temp.child("a/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        temp.child("b/node_modules/x/index.js")
+            .write_str("js")
+            .unwrap();
+        temp.child("c/build.log").write_str("log").unwrap();
+
+        let mut iter = scanner(&temp).iter();
+        let mut streamed: Vec<_> = iter.by_ref().map(|item| item.path).collect();
+        let outcome = iter.finish().unwrap();
+        let mut scanned: Vec<_> = outcome.items.iter().map(|item| item.path.clone()).collect();
+        streamed.sort();
+        scanned.sort();
+
+        assert_eq!(streamed, scanned);
+        assert_eq!(
+            scanner(&temp).scan().unwrap().items.len(),
+            outcome.items.len()
+        );
+        let target = outcome
+            .items
+            .iter()
+            .find(|item| item.path.ends_with("target"));
+        assert!(target.unwrap().size > 0);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_stream_yields_the_items_of_scan() {
+        use futures_core::Stream;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        let temp = TempDir::new().unwrap();
+        temp.child("a/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        temp.child("c/build.log").write_str("log").unwrap();
+
+        let mut stream = scanner(&temp).iter();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut items = 0;
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(_)) => items += 1,
+                Poll::Ready(None) => break,
+                Poll::Pending => thread::yield_now(),
+            }
+        }
+        assert_eq!(items, stream.finish().unwrap().items.len());
+    }
+}
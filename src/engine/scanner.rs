@@ -9,23 +9,47 @@
 //! The scanning process streams directory entries using `walkdir` and the
 //! `rayon::par_bridge` adaptor so pattern matching and metadata collection can
 //! proceed in parallel without first materialising the entire tree in memory.
+//!
+//! File sizes are not kept per file: each worker folds them into running totals for
+//! the directory containing them, and matched directory sizes are summed from those
+//! totals after the walk. Memory therefore stays proportional to the number of
+//! directories walked (plus the matched items), however many files they hold.
 
+use crate::config::{SizeMode, SymlinkPolicy};
+use crate::engine::estimate;
+use crate::engine::native::{self, FileStat};
+use crate::engine::prune_nested_items;
+use crate::engine::scan_iter::{ItemQueue, ScanIter};
 use crate::patterns::PatternMatcher;
-use crate::types::{CleanItem, ItemType, ScanError};
+use crate::safety::SecretDetector;
+use crate::types::{
+    CleanItem, DepthHistogram, ItemType, PatternMatch, PatternStats, ScanError, ScanErrorGroup,
+    ScanOutcome, ScanSummary, SkipReason, SkippedItem,
+};
+use crate::utils::fs as fs_utils;
 use crate::utils::progress::{CategoryTracker, Progress, ScanStats};
+use dashmap::DashSet;
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use rayon::ThreadPool;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
+/// A predicate deciding which matches a [`Scanner`] keeps.
+type ItemFilter = Arc<dyn Fn(&CleanItem) -> bool + Send + Sync>;
+
+/// How many scan errors are kept by default; the rest are only counted.
+pub const DEFAULT_ERROR_LIMIT: usize = 1000;
+
 /// A file system scanner that identifies items to be cleaned.
 ///
 /// The `Scanner` walks the directory tree, applying matching rules to find
 /// files and directories that are candidates for deletion. It can be configured
-/// with a maximum scan depth and whether to follow symbolic links.
+/// with a maximum scan depth and a [`SymlinkPolicy`].
 pub struct Scanner {
     /// The starting point of the scan.
     root: PathBuf,
@@ -33,8 +57,34 @@ pub struct Scanner {
     matcher: Arc<PatternMatcher>,
     /// The maximum directory depth to traverse.
     max_depth: usize,
-    /// Whether to follow symbolic links during the scan.
-    follow_symlinks: bool,
+    /// How symbolic links are traversed and matched.
+    symlinks: SymlinkPolicy,
+    /// Per-pattern policies for matched symbolic links.
+    symlink_overrides: BTreeMap<String, SymlinkPolicy>,
+    /// Whether matched items owned by another user are skipped.
+    only_own_files: bool,
+    /// Detects matched items that are or contain secrets, which are skipped.
+    secrets: Option<Arc<SecretDetector>>,
+    /// Whether the walk stays on the file system of the root.
+    one_file_system: bool,
+    /// Names of directories the walk never descends into.
+    skip_traversal: Vec<String>,
+    /// Directories the walk never descends into, by path.
+    skip_dirs: Vec<PathBuf>,
+    /// How many errors the outcome keeps; `None` keeps all.
+    error_limit: Option<usize>,
+    /// Whether matches that were excluded or skipped by a safety filter are recorded.
+    include_skipped: bool,
+    /// How long the walk may take before it stops with what it found so far.
+    timeout: Option<Duration>,
+    /// How matched directories are sized.
+    size_mode: SizeMode,
+    /// The thread pool entries are processed on; rayon's global pool if `None`.
+    thread_pool: Option<Arc<ThreadPool>>,
+    /// Decides whether a match is kept; all are if `None`.
+    filter: Option<ItemFilter>,
+    /// Stops the walk once set.
+    cancel: Option<Arc<AtomicBool>>,
     /// An optional progress reporter.
     progress: Option<Arc<dyn Progress>>,
     /// An optional category tracker for aggregating statistics.
@@ -55,13 +105,86 @@ impl Scanner {
             root,
             matcher,
             max_depth: 10,
-            follow_symlinks: false,
+            symlinks: SymlinkPolicy::default(),
+            symlink_overrides: BTreeMap::new(),
+            only_own_files: false,
+            secrets: None,
+            one_file_system: false,
+            skip_traversal: Vec::new(),
+            skip_dirs: Vec::new(),
+            error_limit: Some(DEFAULT_ERROR_LIMIT),
+            include_skipped: false,
+            timeout: None,
+            size_mode: SizeMode::default(),
+            thread_pool: None,
+            filter: None,
+            cancel: None,
             progress: None,
             category_tracker: None,
             scan_stats: None,
         }
     }
 
+    /// Sets how many errors the outcome keeps. `None` keeps all.
+    ///
+    /// Errors past the limit are only counted, in `errors_omitted`, though they are
+    /// still included in the outcome's `error_groups`. Defaults to 1000.
+    pub fn with_error_limit(mut self, limit: Option<usize>) -> Self {
+        self.error_limit = limit;
+        self
+    }
+
+    /// Records the paths that matched an include pattern but were excluded or skipped
+    /// by a safety filter in the outcome's `skipped`, with the reason.
+    ///
+    /// Excluded paths are checked against the include patterns a second time, so this
+    /// makes the scan slightly slower. Defaults to `false`.
+    pub fn with_include_skipped(mut self, include_skipped: bool) -> Self {
+        self.include_skipped = include_skipped;
+        self
+    }
+
+    /// Stops the walk once it has run for `timeout`, returning the items found so far
+    /// with `stats.timed_out` set. Entries already being processed are finished, and
+    /// matched directories whose contents the walk had not finished are left out.
+    ///
+    /// Directories matched before the walk stopped are still cleaned whole, but their
+    /// sizes count only the files walked so far. `None`, the default, never stops.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Processes entries on `pool` instead of rayon's global thread pool, so an
+    /// application embedding mc can keep scanning within its own thread budget.
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Keeps only the matches `filter` returns true for, so an application embedding mc
+    /// can apply its own policy, such as an age limit or a project allowlist, as the walk
+    /// goes rather than on the full result.
+    ///
+    /// The filter runs on worker threads as each item is matched, before a matched
+    /// directory's contents are walked, so the `size` of a directory is 0. Rejected
+    /// items are recorded as [`SkipReason::Filtered`] with
+    /// [`with_include_skipped`](Self::with_include_skipped).
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(&CleanItem) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Stops the walk once `cancel` is set, from any thread, returning what was found
+    /// so far like a timeout does, though without flagging it as timed out.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
     /// Sets the maximum depth for the directory traversal.
     pub fn with_max_depth(mut self, depth: usize) -> Self {
         self.max_depth = depth;
@@ -69,8 +192,80 @@ impl Scanner {
     }
 
     /// Sets whether the scanner should follow symbolic links.
+    ///
+    /// This is shorthand for [`SymlinkPolicy::Follow`] or [`SymlinkPolicy::DeleteLink`].
     pub fn with_symlinks(mut self, follow: bool) -> Self {
-        self.follow_symlinks = follow;
+        self.symlinks = if follow {
+            SymlinkPolicy::Follow
+        } else {
+            SymlinkPolicy::DeleteLink
+        };
+        self
+    }
+
+    /// Sets how symbolic links are traversed and matched.
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Sets per-pattern policies for matched symbolic links, keyed by pattern.
+    pub fn with_symlink_overrides(mut self, overrides: BTreeMap<String, SymlinkPolicy>) -> Self {
+        self.symlink_overrides = overrides;
+        self
+    }
+
+    /// Sets whether matched items owned by another user are skipped and reported as
+    /// [`ScanError::NotOwned`] instead of being returned.
+    pub fn with_only_own_files(mut self, only_own_files: bool) -> Self {
+        self.only_own_files = only_own_files;
+        self
+    }
+
+    /// Sets the detector for secrets. Matched items that are or contain a secret are
+    /// skipped and reported as [`ScanError::Secret`] instead of being returned.
+    pub fn with_secrets(mut self, secrets: Option<Arc<SecretDetector>>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Sets whether the walk stays on the file system of the root, skipping the
+    /// contents of other mounts below it.
+    pub fn with_one_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+
+    /// Sets the names of directories the walk never enters, such as `.git`. They are
+    /// neither matched nor counted, and their contents are not visited.
+    pub fn with_skip_traversal(mut self, names: Vec<String>) -> Self {
+        self.skip_traversal = names;
+        self
+    }
+
+    /// Sets directories the walk never enters, by path, such as where mc keeps what it
+    /// cleaned. A path is compared both as given and canonicalized.
+    pub fn with_skip_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.skip_dirs = dirs
+            .into_iter()
+            .flat_map(|dir| {
+                let canonical = fs::canonicalize(&dir)
+                    .ok()
+                    .filter(|canonical| *canonical != dir);
+                std::iter::once(dir).chain(canonical)
+            })
+            .collect();
+        self
+    }
+
+    /// Sets how matched directories are sized. With [`SizeMode::Fast`], the walk does
+    /// not enter matched directories, and their sizes are estimated from a sample of
+    /// their entries afterwards; the outcome's `estimated_sizes` lists those that are
+    /// not exact. Matches nested inside a matched directory are not found then, which
+    /// only matters if a safety filter skips the outer one. Defaults to
+    /// [`SizeMode::Exact`].
+    pub fn with_size_mode(mut self, size_mode: SizeMode) -> Self {
+        self.size_mode = size_mode;
         self
     }
 
@@ -95,97 +290,276 @@ impl Scanner {
     /// Performs the file system scan.
     ///
     /// This method walks the directory tree from the root, processes entries in parallel,
-    /// and returns a vector of `CleanItem`s that match the cleaning patterns.
+    /// and returns a [`ScanOutcome`] with the matching `CleanItem`s, the errors met along
+    /// the way, and totals for the walk.
     ///
     /// # Performance Considerations
     ///
     /// The use of `rayon` for parallel processing can significantly speed up the scanning
     /// of large directories with many entries, as the pattern matching for each entry
     /// can happen concurrently.
-    pub fn scan(&self) -> crate::types::Result<(Vec<CleanItem>, Vec<ScanError>, usize)> {
-        log::debug!("Starting scan from {} (max_depth={})", self.root.display(), self.max_depth);
+    pub fn scan(&self) -> crate::types::Result<ScanOutcome> {
+        self.scan_into(None)
+    }
+
+    /// Scans the directory tree on a background thread, returning an iterator over the
+    /// matched items as they are found.
+    ///
+    /// See [`ScanIter`] for how its items differ from those of [`Scanner::scan`].
+    pub fn iter(self) -> ScanIter {
+        ScanIter::spawn(self)
+    }
+
+    /// Scans like [`Scanner::scan`], also handing each match to `queue` as it is found.
+    pub(crate) fn scan_into(&self, queue: Option<&ItemQueue>) -> crate::types::Result<ScanOutcome> {
+        match &self.thread_pool {
+            Some(pool) => pool.install(|| self.walk(queue)),
+            None => self.walk(queue),
+        }
+    }
+
+    /// Walks the tree on the current rayon thread pool; see [`Scanner::scan`].
+    fn walk(&self, queue: Option<&ItemQueue>) -> crate::types::Result<ScanOutcome> {
+        let started = Instant::now();
+        let deadline = self.timeout.map(|timeout| started + timeout);
+        let timed_out = AtomicBool::new(false);
+        log::debug!(
+            "Starting scan from {} (max_depth={})",
+            self.root.display(),
+            self.max_depth
+        );
+        if let Some(progress) = &self.progress {
+            progress.begin_phase("scan", None);
+        }
         let matcher = Arc::clone(&self.matcher);
-        let progress = self.progress.clone();
         let category_tracker = self.category_tracker.clone();
         let scan_stats = self.scan_stats.clone();
         let root = self.root.clone();
         let entries_counter = Arc::new(AtomicUsize::new(0));
         let entries_counter_clone = Arc::clone(&entries_counter);
+        let follow = self.symlinks == SymlinkPolicy::Follow;
+        let canonical_root = fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        let outside_links = Mutex::new(Vec::new());
+        let git_repos = Mutex::new(Vec::new());
+        let hard_links = DashSet::new();
+        // Matched directories the walk does not enter, with fast sizes.
+        let fast = self.size_mode == SizeMode::Fast;
+        let mut sized_later = HashSet::new();
+        // The entry the walk stopped at when the timeout hit.
+        let mut stopped_at = None;
 
         let accumulator = WalkDir::new(&self.root)
             .max_depth(self.max_depth)
-            .follow_links(self.follow_symlinks)
+            .follow_links(follow)
+            .same_file_system(self.one_file_system)
             .into_iter()
+            // Skip directories on the skip list and never traverse links that lead out
+            // of the scan root.
+            .filter_entry(|entry| {
+                if fast
+                    && entry
+                        .path()
+                        .parent()
+                        .is_some_and(|dir| sized_later.contains(dir))
+                {
+                    return false;
+                }
+                // Repositories are noted even though `.git` itself is usually skipped.
+                if entry.depth() > 0 && entry.file_name() == ".git" {
+                    if let Some(repo) = entry.path().parent() {
+                        git_repos
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .push(repo.to_path_buf());
+                    }
+                }
+                if skips_traversal(entry, &self.skip_traversal) {
+                    return false;
+                }
+                if entry.file_type().is_dir()
+                    && self.skip_dirs.iter().any(|dir| dir == entry.path())
+                {
+                    return false;
+                }
+                if fast && entry.depth() > 0 && entry.file_type().is_dir() {
+                    let depth = Some(entry.depth());
+                    let file_type = Some(entry.file_type());
+                    if self
+                        .matcher
+                        .matches_at_depth(entry.path(), file_type, depth)
+                        .is_some()
+                    {
+                        sized_later.insert(entry.path().to_path_buf());
+                    }
+                }
+                if !follow || !entry.path_is_symlink() || entry.path() == root {
+                    return true;
+                }
+                let inside = fs::canonicalize(entry.path())
+                    .is_ok_and(|target| target.starts_with(&canonical_root));
+                if !inside {
+                    outside_links
+                        .lock()
+                        .unwrap()
+                        .push(entry.path().to_path_buf());
+                }
+                inside
+            })
+            // Entries are handed to the workers one by one, so no new entry is taken
+            // once the deadline has passed.
+            .take_while(|entry| {
+                // Cancelling, or dropping the iterator, stops the walk like a timeout,
+                // without flagging it.
+                let cancelled = self
+                    .cancel
+                    .as_ref()
+                    .is_some_and(|c| c.load(Ordering::Relaxed));
+                if cancelled || queue.is_some_and(ItemQueue::is_cancelled) {
+                    return false;
+                }
+                let expired = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                if expired {
+                    timed_out.store(true, Ordering::Relaxed);
+                    stopped_at = match entry {
+                        Ok(entry) => Some(entry.path().to_path_buf()),
+                        Err(err) => err.path().map(Path::to_path_buf),
+                    };
+                }
+                !expired
+            })
             .par_bridge()
-            .fold(
-                ScanAccumulator::default,
-                |mut acc, entry_result| {
-                    // Track entries scanned
-                    entries_counter_clone.fetch_add(1, Ordering::Relaxed);
-
-                    match entry_result {
-                        Ok(entry) => {
-                            let path = entry.path();
-                            if path == root {
-                                return acc;
-                            }
+            .fold(ScanAccumulator::default, |mut acc, entry_result| {
+                // Track entries scanned
+                entries_counter_clone.fetch_add(1, Ordering::Relaxed);
 
-                            let file_type = entry.file_type();
-
-                            // Update scan stats for live progress
-                            if let Some(ref stats) = scan_stats {
-                                stats.inc_entry();
-                                if file_type.is_dir() {
-                                    stats.inc_dir();
-                                } else {
-                                    stats.inc_file();
-                                }
-                            }
+                match entry_result {
+                    Ok(entry) => {
+                        acc.add_depth(entry.depth());
+                        let path = entry.path();
+                        if path == root {
+                            return acc;
+                        }
 
-                            let path_buf = path.to_path_buf();
-                            let pattern_match = matcher.matches_with_type(path, Some(file_type));
+                        let file_type = entry.file_type();
+                        let is_link = entry.path_is_symlink();
+                        if is_link {
+                            acc.symlinks_visited += 1;
+                        } else if file_type.is_file() {
+                            acc.files_visited += 1;
+                        }
 
-                            let mut file_size = None;
-                            let mut metadata_available = true;
-                            let mut contributes_to_dir = false;
-                            let mut dir_base_size = None;
+                        // Update scan stats for live progress
+                        if let Some(ref stats) = scan_stats {
+                            stats.inc_entry();
+                            if is_link {
+                                stats.inc_symlink();
+                            } else if file_type.is_dir() {
+                                stats.inc_dir();
+                            } else {
+                                stats.inc_file();
+                            }
+                        }
+                        if file_type.is_dir() && !is_link {
+                            if let Some(ref progress) = self.progress {
+                                progress.dir_visited(path);
+                            }
+                        }
 
-                            if file_type.is_file() {
-                                match entry.metadata() {
-                                    Ok(metadata) => {
-                                        let size = metadata.len();
-                                        file_size = Some(size);
-                                        contributes_to_dir = true;
-                                    }
-                                    Err(err) => {
-                                        metadata_available = false;
-                                        acc.errors.push(ScanError::IoError {
-                                            path: path_buf.clone(),
-                                            message: err.to_string(),
-                                        });
+                        let path_buf = path.to_path_buf();
+                        let depth = Some(entry.depth());
+                        let pattern_match = matcher.matches_at_depth(path, Some(file_type), depth);
+                        if pattern_match.is_none() && self.include_skipped {
+                            if let Some((pattern_match, exclude)) =
+                                matcher.excluded_match(path, Some(file_type), depth)
+                            {
+                                let reason = SkipReason::Excluded { exclude };
+                                acc.skip(path, &pattern_match, reason);
+                            }
+                        }
+                        let pattern_match = pattern_match
+                            .filter(|pattern_match| {
+                                let owned = !self.only_own_files
+                                    || fs_utils::is_owned_by_current_user(path);
+                                if !owned {
+                                    acc.errors.push(ScanError::NotOwned {
+                                        path: path_buf.clone(),
+                                    });
+                                    if self.include_skipped {
+                                        acc.skip(path, pattern_match, SkipReason::NotOwned);
                                     }
                                 }
-                            } else if file_type.is_dir() {
-                                match entry.metadata() {
-                                    Ok(metadata) => {
-                                        dir_base_size = Some(metadata.len());
-                                    }
-                                    Err(err) => {
-                                        metadata_available = false;
-                                        acc.errors.push(ScanError::IoError {
+                                owned
+                            })
+                            .filter(|pattern_match| {
+                                let secret =
+                                    self.secrets.as_ref().and_then(|secrets| secrets.find(path));
+                                match secret {
+                                    Some((secret, reason)) => {
+                                        if self.include_skipped {
+                                            let reason = SkipReason::Secret {
+                                                secret: secret.clone(),
+                                                reason: reason.clone(),
+                                            };
+                                            acc.skip(path, pattern_match, reason);
+                                        }
+                                        acc.errors.push(ScanError::Secret {
                                             path: path_buf.clone(),
-                                            message: err.to_string(),
+                                            secret,
+                                            reason,
                                         });
+                                        false
                                     }
+                                    None => true,
                                 }
-                            } else if file_type.is_symlink() {
+                            });
+
+                        // Matched links are resolved according to the symlink policy
+                        // rather than as the entry's own type.
+                        if is_link {
+                            if let Some(pattern_match) = pattern_match {
+                                let policy = self
+                                    .symlink_overrides
+                                    .get(&pattern_match.pattern)
+                                    .copied()
+                                    .unwrap_or(self.symlinks);
+                                match resolve_link(path, pattern_match, policy, &canonical_root) {
+                                    Ok(Some(item)) => self.add_match(&mut acc, item, queue),
+                                    Ok(None) => {}
+                                    Err(error) => acc.errors.push(error),
+                                }
+                                return acc;
+                            }
+                        }
+
+                        // The entry's type comes from the directory listing, so only
+                        // files need a stat, for their size, and those are stat'ed in
+                        // batches. Links reaching this point are unmatched and not
+                        // followed, and add nothing.
+                        if file_type.is_file() {
+                            acc.pending.push(PendingFile {
+                                path: path_buf,
+                                pattern: pattern_match,
+                            });
+                            if acc.pending.len() >= native::STAT_BATCH {
+                                self.stat_pending(&mut acc, &hard_links, follow, queue);
+                            }
+                            return acc;
+                        }
+
+                        let mut dir_base_size = None;
+                        if file_type.is_dir() {
+                            acc.dirs_visited += 1;
+                            if entry.depth() == self.max_depth {
+                                acc.depth_limited.push(path_buf.clone());
+                            }
+                            // Only a matched directory's own entry size is
+                            // counted, so the others are not stat'ed.
+                            if pattern_match.is_some() {
                                 match entry.metadata() {
                                     Ok(metadata) => {
-                                        file_size = Some(metadata.len());
-                                        contributes_to_dir = metadata.is_file();
+                                        dir_base_size = Some(metadata.len());
                                     }
                                     Err(err) => {
-                                        metadata_available = false;
                                         acc.errors.push(ScanError::IoError {
                                             path: path_buf.clone(),
                                             message: err.to_string(),
@@ -193,84 +567,100 @@ impl Scanner {
                                     }
                                 }
                             }
+                        }
 
-                            let item_type = determine_type(&file_type);
-
-                            if let Some(pattern_match) = pattern_match {
-                                if !matches!(item_type, ItemType::File | ItemType::Symlink)
-                                    || metadata_available
-                                {
-                                    if let Some(ref progress) = progress {
-                                        progress.increment(1);
-                                    }
-
-                                    let size = match item_type {
-                                        ItemType::File | ItemType::Symlink => {
-                                            file_size.unwrap_or(0)
-                                        }
-                                        ItemType::Directory => 0,
-                                    };
-
-                                    // Track matched item in scan stats
-                                    if let Some(ref stats) = scan_stats {
-                                        stats.inc_matched(size);
-                                    }
-
-                                    acc.items.push(CleanItem {
-                                        path: path_buf,
-                                        size,
-                                        item_type,
-                                        pattern: pattern_match,
-                                    });
-                                }
-                            }
-
-                            if let Some(size) = dir_base_size {
-                                acc.dir_bases.push((path.to_path_buf(), size));
-                            }
+                        // Directories are sized once the walk is done, and other
+                        // entries, such as sockets, take no space.
+                        if let Some(pattern_match) = pattern_match {
+                            let item = CleanItem {
+                                path: path_buf,
+                                size: 0,
+                                item_type: determine_type(&file_type),
+                                pattern: pattern_match,
+                            };
+                            self.add_match(&mut acc, item, queue);
+                        }
 
-                            // Record file sizes for directory aggregation even when the file
-                            // itself does not match a pattern.
-                            if contributes_to_dir {
-                                if let Some(size) = file_size {
-                                    acc.file_sizes.push((path.to_path_buf(), size));
-                                }
-                            }
+                        if let Some(size) = dir_base_size {
+                            acc.add_dir_base(path, size);
                         }
-                        Err(err) => {
-                            let path = err.path().unwrap_or(&root).to_path_buf();
-                            let error = if err.loop_ancestor().is_some() {
-                                ScanError::SymlinkCycle { path }
-                            } else {
-                                ScanError::IoError {
-                                    path,
-                                    message: err.to_string(),
-                                }
-                            };
-                            acc.errors.push(error);
+                    }
+                    Err(err) => {
+                        if let Some(ref stats) = scan_stats {
+                            stats.inc_error();
                         }
+                        let path = err.path().unwrap_or(&root).to_path_buf();
+                        let error = if err.loop_ancestor().is_some() {
+                            ScanError::SymlinkCycle { path }
+                        } else {
+                            ScanError::IoError {
+                                path,
+                                message: err.to_string(),
+                            }
+                        };
+                        acc.errors.push(error);
                     }
+                }
 
-                    acc
-                },
-            )
-            .reduce(
-                ScanAccumulator::default,
-                |mut acc, mut other| {
-                    acc.items.append(&mut other.items);
-                    acc.errors.append(&mut other.errors);
-                    acc.file_sizes.append(&mut other.file_sizes);
-                    acc.dir_bases.append(&mut other.dir_bases);
-                    acc
-                },
-            );
+                acc
+            })
+            .map(|mut acc| {
+                self.stat_pending(&mut acc, &hard_links, follow, queue);
+                acc
+            })
+            .reduce(ScanAccumulator::default, |acc, other| acc.merge(other));
 
         let ScanAccumulator {
             mut items,
-            errors,
-            file_sizes,
-            dir_bases,
+            mut errors,
+            dir_totals,
+            dirs_visited,
+            files_visited,
+            symlinks_visited,
+            bytes_examined,
+            depth_histogram,
+            depth_limited,
+            mut skipped,
+            // Every worker stat'ed what it held back before the results were merged.
+            pending: _,
         } = accumulator;
+        // The walk is depth first, so the directories it had not finished when the
+        // timeout hit are the ones holding the entry it stopped at. Their contents were
+        // not all seen, so the checks that look inside them did not run.
+        if let Some(stopped_at) = &stopped_at {
+            items.retain(|item| {
+                let unfinished = is_unfinished(item, stopped_at);
+                if unfinished && self.include_skipped {
+                    skipped.push(SkippedItem {
+                        path: item.path.clone(),
+                        pattern: item.pattern.pattern.clone(),
+                        reason: SkipReason::ScanTimedOut,
+                    });
+                }
+                !unfinished
+            });
+        }
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut git_repos = git_repos.into_inner().unwrap_or_else(|e| e.into_inner());
+        git_repos.sort();
+        errors.extend(
+            outside_links
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|path| ScanError::SymlinkOutsideRoot { path }),
+        );
+        let error_groups = ScanErrorGroup::summarize(&errors);
+        errors.sort_by(|a, b| a.path().cmp(b.path()));
+        let errors_omitted = match self.error_limit {
+            Some(limit) if errors.len() > limit => {
+                let omitted = errors.len() - limit;
+                errors.truncate(limit);
+                omitted
+            }
+            _ => 0,
+        };
+        let error_count = errors.len() + errors_omitted;
 
         if !items.is_empty() {
             let matched_dirs: HashSet<PathBuf> = items
@@ -288,19 +678,16 @@ impl Scanner {
                 let mut dir_sizes: HashMap<PathBuf, u64> =
                     matched_dirs.into_iter().map(|path| (path, 0)).collect();
 
-                for (dir_path, base) in dir_bases {
+                for (dir_path, totals) in &dir_totals {
                     if let Some(total) = dir_sizes.get_mut(dir_path.as_path()) {
-                        *total += base;
+                        *total += totals.base;
                     }
-                }
-
-                for (file_path, size) in file_sizes {
-                    for ancestor in file_path.ancestors().skip(1) {
+                    for ancestor in dir_path.ancestors() {
                         if !ancestor.starts_with(&root) {
                             break;
                         }
                         if let Some(total) = dir_sizes.get_mut(ancestor) {
-                            *total += size;
+                            *total += totals.files;
                         }
                     }
                 }
@@ -315,24 +702,342 @@ impl Scanner {
             }
         }
 
+        // Matched directories the walk did not enter are sized from a sample.
+        let mut estimated_sizes = Vec::new();
+        if fast {
+            let estimates: Vec<_> = items
+                .par_iter_mut()
+                .filter(|item| sized_later.contains(&item.path))
+                .map(|item| {
+                    let estimate = estimate::dir_size(&item.path, estimate::SAMPLE_ENTRIES);
+                    item.size += estimate.bytes;
+                    (!estimate.exact).then(|| item.path.clone())
+                })
+                .collect();
+            estimated_sizes = estimates.into_iter().flatten().collect();
+            estimated_sizes.sort();
+        }
+
+        // Directories inside matched items do not hide anything, since the whole item
+        // is cleaned.
+        let matched_dirs: HashSet<&Path> = items
+            .iter()
+            .filter(|item| item.item_type == ItemType::Directory)
+            .map(|item| item.path.as_path())
+            .collect();
+        let depth_limited_dirs = depth_limited
+            .iter()
+            .filter(|dir| !dir.ancestors().any(|a| matched_dirs.contains(a)))
+            .count();
+        if depth_limited_dirs > 0 {
+            log::debug!(
+                "Depth limit {} reached in {} directories",
+                self.max_depth,
+                depth_limited_dirs
+            );
+        }
+
+        // Categories only count outermost items, so bytes inside nested matches are
+        // counted once, as they will be freed once.
         if let Some(tracker) = category_tracker {
-            for item in &items {
+            for item in prune_nested_items(items.clone()) {
                 tracker.add_item(item.pattern.category, item.size);
             }
         }
 
+        let mut pattern_stats: BTreeMap<String, PatternStats> = self
+            .matcher
+            .include_patterns()
+            .map(|pattern| (pattern.to_string(), PatternStats::default()))
+            .collect();
+        for item in &items {
+            let stats = pattern_stats
+                .entry(item.pattern.pattern.clone())
+                .or_default();
+            stats.items += 1;
+            stats.bytes += item.size;
+        }
+
         let entries_scanned = entries_counter.load(Ordering::Relaxed);
-        log::debug!("Scan complete: {} entries scanned, {} items matched", entries_scanned, items.len());
-        Ok((items, errors, entries_scanned))
+        let timed_out = timed_out.into_inner();
+        if timed_out {
+            log::warn!(
+                "Scan of {} stopped after {:.1}s by its timeout",
+                self.root.display(),
+                started.elapsed().as_secs_f64()
+            );
+        }
+        log::debug!(
+            "Scan complete: {} entries scanned, {} items matched",
+            entries_scanned,
+            items.len()
+        );
+        Ok(ScanOutcome {
+            items,
+            errors,
+            error_groups,
+            errors_omitted,
+            stats: ScanSummary {
+                entries_scanned,
+                dirs_visited,
+                files_visited,
+                symlinks_visited,
+                errors: error_count,
+                bytes_examined,
+                depth_histogram,
+                depth_limited_dirs,
+                timed_out,
+                duration: started.elapsed(),
+            },
+            pattern_stats,
+            git_repos,
+            skipped,
+            estimated_sizes,
+        })
+    }
+
+    /// Stats the files `acc` holds back, in one batch, and records them: their size goes
+    /// to their directory, and matched ones become items.
+    fn stat_pending(
+        &self,
+        acc: &mut ScanAccumulator,
+        hard_links: &DashSet<(u64, u64)>,
+        follow: bool,
+        queue: Option<&ItemQueue>,
+    ) {
+        let pending = std::mem::take(&mut acc.pending);
+        let paths: Vec<&Path> = pending.iter().map(|file| file.path.as_path()).collect();
+        let stats = native::stat_files(&paths, follow);
+        for (file, stat) in pending.into_iter().zip(stats) {
+            let stat = match stat {
+                Ok(stat) => stat,
+                Err(err) => {
+                    acc.errors.push(ScanError::IoError {
+                        path: file.path,
+                        message: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            acc.bytes_examined += stat.len;
+            if let Some(ref stats) = self.scan_stats {
+                stats.add_bytes(stat.len);
+            }
+            // Further links to a hard-linked file add no bytes.
+            let size = if first_link(hard_links, &stat) {
+                stat.len
+            } else {
+                0
+            };
+            // Recorded for directory sizes even when the file itself matches nothing.
+            acc.add_file(&file.path, size);
+            if let Some(pattern) = file.pattern {
+                let item = CleanItem {
+                    path: file.path,
+                    size,
+                    item_type: ItemType::File,
+                    pattern,
+                };
+                self.add_match(acc, item, queue);
+            }
+        }
+    }
+
+    /// Adds a matched item to the results, unless the filter rejects it.
+    fn add_match(&self, acc: &mut ScanAccumulator, item: CleanItem, queue: Option<&ItemQueue>) {
+        if let Some(filter) = &self.filter {
+            if !filter(&item) {
+                if self.include_skipped {
+                    acc.skip(&item.path, &item.pattern, SkipReason::Filtered);
+                }
+                return;
+            }
+        }
+        if let Some(progress) = &self.progress {
+            progress.item_completed(&item);
+        }
+        if let Some(stats) = &self.scan_stats {
+            stats.inc_matched(item.size);
+        }
+        if let Some(queue) = queue {
+            queue.push(item.clone());
+        }
+        acc.items.push(item);
+    }
+}
+
+/// Returns true if `item` is a directory holding `stopped_at`, the entry a depth-first
+/// walk stopped at, so the walk had not finished its contents.
+fn is_unfinished(item: &CleanItem, stopped_at: &Path) -> bool {
+    item.item_type == ItemType::Directory && stopped_at.starts_with(&item.path)
+}
+
+/// Returns true unless `stat` is of a hard-linked file whose inode was already seen in
+/// this scan, recording the inode on first sight.
+#[cfg(unix)]
+fn first_link(seen: &DashSet<(u64, u64)>, stat: &FileStat) -> bool {
+    stat.nlink <= 1 || seen.insert((stat.dev, stat.ino))
+}
+
+/// Hard links are only told apart on Unix.
+#[cfg(not(unix))]
+fn first_link(_seen: &DashSet<(u64, u64)>, _stat: &FileStat) -> bool {
+    true
+}
+
+/// Returns true if `entry` is a directory below the root whose name is in `skip`, or a
+/// snapshot directory of `mc snapshots`, which is never entered whatever `skip` says.
+pub(crate) fn skips_traversal(entry: &walkdir::DirEntry, skip: &[String]) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_dir()
+        && entry.file_name().to_str().is_some_and(|name| {
+            name == crate::snapshots::BTRFS_SNAPSHOT_DIR || skip.iter().any(|s| s == name)
+        })
+}
+
+/// Turns a matched symbolic link into a clean item according to `policy`.
+///
+/// Returns `Ok(None)` when the link is skipped. With `Follow`, the item is the link's
+/// target, which must lie inside `canonical_root`.
+fn resolve_link(
+    path: &Path,
+    pattern: PatternMatch,
+    policy: SymlinkPolicy,
+    canonical_root: &Path,
+) -> Result<Option<CleanItem>, ScanError> {
+    match policy {
+        SymlinkPolicy::Skip => Ok(None),
+        SymlinkPolicy::DeleteLink => {
+            let size = fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0);
+            Ok(Some(CleanItem {
+                path: path.to_path_buf(),
+                size,
+                item_type: ItemType::Symlink,
+                pattern,
+            }))
+        }
+        SymlinkPolicy::Follow => {
+            let target = fs::canonicalize(path).map_err(|err| ScanError::IoError {
+                path: path.to_path_buf(),
+                message: err.to_string(),
+            })?;
+            if !target.starts_with(canonical_root) {
+                return Err(ScanError::SymlinkOutsideRoot {
+                    path: path.to_path_buf(),
+                });
+            }
+            let Some(item_type) = fs_utils::item_type_of(&target) else {
+                return Ok(None);
+            };
+            Ok(Some(CleanItem {
+                size: fs_utils::item_size(&target, &item_type),
+                path: target,
+                item_type,
+                pattern,
+            }))
+        }
     }
 }
 
+/// What one worker collected during the walk.
+///
+/// File sizes are folded into the totals of their parent directory as they are seen,
+/// so memory grows with the number of directories walked rather than the number of
+/// files. Matched directory sizes are summed from these totals once the walk is done.
 #[derive(Default)]
 struct ScanAccumulator {
     items: Vec<CleanItem>,
     errors: Vec<ScanError>,
-    file_sizes: Vec<(PathBuf, u64)>,
-    dir_bases: Vec<(PathBuf, u64)>,
+    dir_totals: HashMap<PathBuf, DirTotals>,
+    dirs_visited: usize,
+    files_visited: usize,
+    symlinks_visited: usize,
+    bytes_examined: u64,
+    /// The number of entries seen at each depth.
+    depth_histogram: DepthHistogram,
+    /// Directories at the depth limit, whose contents were not walked.
+    depth_limited: Vec<PathBuf>,
+    /// Matches that were left alone, if they are recorded.
+    skipped: Vec<SkippedItem>,
+    /// Files seen but not stat'ed yet, fewer than [`native::STAT_BATCH`].
+    pending: Vec<PendingFile>,
+}
+
+/// A file the walk saw, waiting to be stat'ed with others.
+struct PendingFile {
+    path: PathBuf,
+    /// The pattern it matched, if any.
+    pattern: Option<PatternMatch>,
+}
+
+/// The sizes recorded for a single directory.
+#[derive(Default, Clone, Copy)]
+struct DirTotals {
+    /// The size of the directory entry itself.
+    base: u64,
+    /// The total size of the files directly inside it.
+    files: u64,
+}
+
+impl ScanAccumulator {
+    /// Counts an entry at `depth`.
+    fn add_depth(&mut self, depth: usize) {
+        self.depth_histogram.add(depth);
+    }
+
+    /// Records the entry size of the directory at `dir`.
+    fn add_dir_base(&mut self, dir: &Path, size: u64) {
+        self.dir_totals.entry(dir.to_path_buf()).or_default().base += size;
+    }
+
+    /// Adds the size of the file at `file` to its parent directory. The parent's key is
+    /// only allocated for the first file seen in it.
+    fn add_file(&mut self, file: &Path, size: u64) {
+        let Some(parent) = file.parent() else {
+            return;
+        };
+        match self.dir_totals.get_mut(parent) {
+            Some(totals) => totals.files += size,
+            None => {
+                let totals = DirTotals {
+                    base: 0,
+                    files: size,
+                };
+                self.dir_totals.insert(parent.to_path_buf(), totals);
+            }
+        }
+    }
+
+    /// Records that the match of `path` by `pattern_match` was left alone.
+    fn skip(&mut self, path: &Path, pattern_match: &PatternMatch, reason: SkipReason) {
+        self.skipped.push(SkippedItem {
+            path: path.to_path_buf(),
+            pattern: pattern_match.pattern.clone(),
+            reason,
+        });
+    }
+
+    /// Combines the results of two workers.
+    fn merge(mut self, mut other: Self) -> Self {
+        if self.dir_totals.len() < other.dir_totals.len() {
+            std::mem::swap(&mut self.dir_totals, &mut other.dir_totals);
+        }
+        for (dir, totals) in other.dir_totals {
+            let entry = self.dir_totals.entry(dir).or_default();
+            entry.base += totals.base;
+            entry.files += totals.files;
+        }
+        self.items.append(&mut other.items);
+        self.errors.append(&mut other.errors);
+        self.depth_limited.append(&mut other.depth_limited);
+        self.skipped.append(&mut other.skipped);
+        self.dirs_visited += other.dirs_visited;
+        self.files_visited += other.files_visited;
+        self.symlinks_visited += other.symlinks_visited;
+        self.bytes_examined += other.bytes_examined;
+        self.depth_histogram.merge(&other.depth_histogram);
+        self
+    }
 }
 
 fn determine_type(file_type: &fs::FileType) -> ItemType {
@@ -349,6 +1054,8 @@ fn determine_type(file_type: &fs::FileType) -> ItemType {
 mod tests {
     use super::*;
     use crate::config::Config;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
     use assert_fs::prelude::*;
     use assert_fs::TempDir;
     use std::fs;
@@ -373,11 +1080,21 @@ mod tests {
         let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
         let scanner = Scanner::new(temp.path().to_path_buf(), matcher);
 
-        let (items, errors, entries_scanned) = scanner.scan().unwrap();
+        let ScanOutcome {
+            items,
+            errors,
+            stats,
+            pattern_stats,
+            ..
+        } = scanner.scan().unwrap();
 
         assert_eq!(items.len(), 3);
         assert!(errors.is_empty());
-        assert!(entries_scanned > 0);
+        assert!(stats.entries_scanned > 0);
+        assert!(stats.dirs_visited >= 5);
+        assert_eq!(pattern_stats["node_modules"].items, 1);
+        assert_eq!(pattern_stats["*.log"].items, 1);
+        assert_eq!(pattern_stats["dist"], PatternStats::default());
         assert!(items.iter().any(|item| item.path.ends_with("node_modules")));
         assert!(items.iter().any(|item| item.path.ends_with("target")));
         assert!(items.iter().any(|item| item.path.ends_with("app.log")));
@@ -407,7 +1124,7 @@ mod tests {
         let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
         let scanner = Scanner::new(temp.path().to_path_buf(), matcher);
 
-        let (_, errors, _) = scanner.scan().unwrap();
+        let errors = scanner.scan().unwrap().errors;
 
         assert!(!errors.is_empty());
         assert!(matches!(errors[0], ScanError::IoError { .. }));
@@ -428,9 +1145,521 @@ mod tests {
         let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
         let scanner = Scanner::new(temp.path().to_path_buf(), matcher).with_symlinks(true);
 
-        let (_, errors, _) = scanner.scan().unwrap();
+        let errors = scanner.scan().unwrap().errors;
 
         assert!(!errors.is_empty());
         assert!(matches!(errors[0], ScanError::SymlinkCycle { .. }));
     }
+
+    #[cfg(unix)]
+    fn scan_with(
+        root: &std::path::Path,
+        policy: SymlinkPolicy,
+    ) -> (Vec<CleanItem>, Vec<ScanError>) {
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(root.to_path_buf(), matcher).with_symlink_policy(policy);
+        let ScanOutcome { items, errors, .. } = scanner.scan().unwrap();
+        (items, errors)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy_dangling_link() {
+        let temp = TempDir::new().unwrap();
+        unix_fs::symlink(
+            temp.path().join("missing"),
+            temp.path().join("node_modules"),
+        )
+        .unwrap();
+
+        let (items, _) = scan_with(temp.path(), SymlinkPolicy::DeleteLink);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].item_type, ItemType::Symlink);
+
+        let (items, _) = scan_with(temp.path(), SymlinkPolicy::Skip);
+        assert!(items.is_empty());
+
+        let (items, errors) = scan_with(temp.path(), SymlinkPolicy::Follow);
+        assert!(items.is_empty());
+        assert!(!errors.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_errors_are_capped_and_grouped() {
+        let temp = TempDir::new().unwrap();
+        for i in 0..5 {
+            let dir = temp.child(format!("checkouts/{}", i));
+            dir.create_dir_all().unwrap();
+            unix_fs::symlink(temp.path().join("missing"), dir.path().join("node_modules")).unwrap();
+        }
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_symlink_policy(SymlinkPolicy::Follow)
+            .with_error_limit(Some(2));
+
+        let outcome = scanner.scan().unwrap();
+
+        assert_eq!(outcome.errors.len(), 2);
+        assert_eq!(outcome.errors_omitted, 3);
+        assert_eq!(outcome.error_groups.len(), 1);
+        assert_eq!(outcome.error_groups[0].count, 5);
+        assert_eq!(
+            outcome.error_groups[0].ancestor,
+            temp.path().join("checkouts")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_never_leaves_root() {
+        let temp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        outside.child("node_modules/pkg/index.js").touch().unwrap();
+        outside.child("project/dist/app.js").touch().unwrap();
+        unix_fs::symlink(outside.child("project").path(), temp.path().join("linked")).unwrap();
+        unix_fs::symlink(
+            outside.child("node_modules").path(),
+            temp.path().join("node_modules"),
+        )
+        .unwrap();
+
+        let (items, errors) = scan_with(temp.path(), SymlinkPolicy::Follow);
+        assert!(items.is_empty(), "got {:?}", items);
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|e| matches!(e, ScanError::SymlinkOutsideRoot { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_cleans_target_inside_root() {
+        let temp = TempDir::new().unwrap();
+        temp.child("shared/cache/data.bin")
+            .write_str("12345")
+            .unwrap();
+        unix_fs::symlink(temp.path().join("shared/cache"), temp.path().join(".turbo")).unwrap();
+
+        let (items, _) = scan_with(temp.path(), SymlinkPolicy::Follow);
+        let canonical = fs::canonicalize(temp.path().join("shared/cache")).unwrap();
+        assert!(items
+            .iter()
+            .any(|item| item.path == canonical && item.item_type == ItemType::Directory));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_override_per_pattern() {
+        let temp = TempDir::new().unwrap();
+        temp.child("real").create_dir_all().unwrap();
+        unix_fs::symlink(temp.path().join("real"), temp.path().join("node_modules")).unwrap();
+        unix_fs::symlink(temp.path().join("real"), temp.path().join("dist")).unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let overrides = BTreeMap::from([("node_modules".to_string(), SymlinkPolicy::Skip)]);
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_symlink_policy(SymlinkPolicy::DeleteLink)
+            .with_symlink_overrides(overrides);
+        let items = scanner.scan().unwrap().items;
+
+        assert_eq!(items.len(), 1);
+        assert!(items[0].path.ends_with("dist"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_own_files_skips_foreign_items() {
+        let temp = TempDir::new().unwrap();
+        temp.child("mine.log").touch().unwrap();
+        temp.child("theirs.log").touch().unwrap();
+        // Handing a file to another user needs root; elsewhere there is nothing to check.
+        let theirs =
+            std::ffi::CString::new(temp.path().join("theirs.log").to_str().unwrap()).unwrap();
+        if unsafe { libc::chown(theirs.as_ptr(), libc::geteuid() + 1, libc::getegid()) } != 0 {
+            return;
+        }
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher).with_only_own_files(true);
+        let ScanOutcome { items, errors, .. } = scanner.scan().unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert!(items[0].path.ends_with("mine.log"));
+        assert!(
+            matches!(&errors[..], [ScanError::NotOwned { path }] if path.ends_with("theirs.log"))
+        );
+    }
+
+    #[test]
+    fn test_include_skipped_records_excluded_matches() {
+        let temp = setup_test_dir();
+        let mut config = Config::default();
+        config.patterns.exclude.push("target".to_string());
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher).with_include_skipped(true);
+        let ScanOutcome { items, skipped, .. } = scanner.scan().unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].path.ends_with("target"));
+        assert_eq!(skipped[0].pattern, "target");
+        assert_eq!(
+            skipped[0].reason,
+            SkipReason::Excluded {
+                exclude: "target".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_filter_drops_rejected_matches() {
+        let temp = setup_test_dir();
+        let matcher = Arc::new(PatternMatcher::from_config(&Config::default()).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_filter(|item| item.item_type != ItemType::Directory)
+            .with_include_skipped(true);
+        let ScanOutcome { items, skipped, .. } = scanner.scan().unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert!(items.iter().all(|item| item.item_type == ItemType::File));
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped
+            .iter()
+            .all(|skip| skip.reason == SkipReason::Filtered));
+    }
+
+    #[test]
+    fn test_scan_timeout_returns_partial_outcome() {
+        let temp = setup_test_dir();
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), Arc::clone(&matcher))
+            .with_timeout(Some(Duration::ZERO));
+        let ScanOutcome { items, stats, .. } = scanner.scan().unwrap();
+        assert!(stats.timed_out);
+        assert!(items.is_empty());
+
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_timeout(Some(Duration::from_secs(60)));
+        assert!(!scanner.scan().unwrap().stats.timed_out);
+    }
+
+    #[test]
+    fn test_directories_holding_the_stopping_entry_are_unfinished() {
+        let dir = |path: &str| {
+            clean_item(
+                Path::new(path),
+                ItemType::Directory,
+                0,
+                PatternCategory::Other,
+            )
+        };
+        let stopped_at = Path::new("/p/target/debug/build/x.o");
+
+        assert!(is_unfinished(&dir("/p/target"), stopped_at));
+        assert!(is_unfinished(&dir("/p/target/debug"), stopped_at));
+        assert!(!is_unfinished(&dir("/p/node_modules"), stopped_at));
+        assert!(!is_unfinished(&dir("/p/target-old"), stopped_at));
+        let file = clean_item(
+            Path::new("/p/target"),
+            ItemType::File,
+            0,
+            PatternCategory::Other,
+        );
+        assert!(!is_unfinished(&file, stopped_at));
+    }
+
+    #[test]
+    fn test_scan_runs_on_given_thread_pool() {
+        let temp = setup_test_dir();
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap(),
+        );
+        let scanner =
+            Scanner::new(temp.path().to_path_buf(), Arc::clone(&matcher)).with_thread_pool(pool);
+        let mut pooled = scanner.scan().unwrap().items;
+        let mut global = Scanner::new(temp.path().to_path_buf(), matcher)
+            .scan()
+            .unwrap()
+            .items;
+
+        pooled.sort_by(|a, b| a.path.cmp(&b.path));
+        global.sort_by(|a, b| a.path.cmp(&b.path));
+        assert!(!pooled.is_empty());
+        assert_eq!(pooled, global);
+    }
+
+    #[test]
+    fn test_skip_traversal_does_not_enter_directories() {
+        let temp = TempDir::new().unwrap();
+        temp.child(".git/objects/debug.log").touch().unwrap();
+        temp.child("app/debug.log").touch().unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_skip_traversal(config.options.skip_traversal);
+        let ScanOutcome { items, stats, .. } = scanner.scan().unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert!(items[0].path.ends_with("app/debug.log"));
+        // root, app, app/debug.log
+        assert_eq!(stats.entries_scanned, 3);
+
+        // Snapshots are never entered, even when skip_traversal leaves them out.
+        temp.child(".mc-snapshots/mc-1/app/debug.log")
+            .touch()
+            .unwrap();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_skip_traversal(vec![".git".to_string()]);
+        let items = scanner.scan().unwrap().items;
+        assert_eq!(items.len(), 1);
+        assert!(items[0].path.ends_with("app/debug.log"));
+    }
+
+    #[test]
+    fn test_quarantine_and_archive_dirs_are_not_entered() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/debug.log").touch().unwrap();
+        temp.child("kept/mc-1/app/debug.log").touch().unwrap();
+        temp.child("archives/debug.log").touch().unwrap();
+
+        let mut config = Config::default();
+        config.quarantine.dir = Some(temp.path().join("kept"));
+        config.archive.dir = Some(temp.path().join("archives"));
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = crate::engine::scanner_for(&config, temp.path().to_path_buf(), matcher);
+        let items = scanner.unwrap().scan().unwrap().items;
+        assert_eq!(items.len(), 1);
+        assert!(items[0].path.ends_with("app/debug.log"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_summary_counts_entries_by_kind() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/src/main.rs")
+            .write_str("fn main() {}")
+            .unwrap();
+        temp.child("app/debug.log").write_str("log").unwrap();
+        std::os::unix::fs::symlink("src", temp.child("app/code").path()).unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher);
+        let stats = scanner.scan().unwrap().stats;
+
+        assert_eq!(stats.entries_scanned, 6);
+        assert_eq!(stats.dirs_visited, 2);
+        assert_eq!(stats.files_visited, 2);
+        assert_eq!(stats.symlinks_visited, 1);
+        assert_eq!(stats.errors, 0);
+        assert_eq!(stats.bytes_examined, 15);
+        // root; app; src, debug.log, code; main.rs
+        assert_eq!(stats.depth_histogram.counts(), [1, 1, 3, 1]);
+    }
+
+    #[test]
+    fn test_pattern_stats_count_nested_matches_and_their_bytes() {
+        let temp = TempDir::new().unwrap();
+        temp.child("node_modules/pkg/debug.log")
+            .write_str("12345")
+            .unwrap();
+        temp.child("app.log").write_str("123").unwrap();
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+
+        let outcome = Scanner::new(temp.path().to_path_buf(), matcher)
+            .scan()
+            .unwrap();
+
+        let stats = &outcome.pattern_stats;
+        assert_eq!(stats["*.log"], PatternStats { items: 2, bytes: 8 });
+        assert_eq!(stats["node_modules"].items, 1);
+        assert!(stats["node_modules"].bytes >= 5);
+        assert_eq!(stats["target"], PatternStats::default());
+        assert_eq!(
+            stats.values().map(|s| s.items).sum::<usize>(),
+            outcome.items.len()
+        );
+    }
+
+    #[test]
+    fn test_progress_sees_directories_and_matches() {
+        /// Counts the directories entered and the items matched.
+        #[derive(Default)]
+        struct Counter(AtomicUsize, AtomicUsize);
+
+        impl Progress for Counter {
+            fn increment(&self, _delta: u64) {}
+            fn set_message(&self, _msg: &str) {}
+            fn finish(&self) {}
+            fn item_completed(&self, _item: &CleanItem) {
+                self.1.fetch_add(1, Ordering::Relaxed);
+            }
+            fn dir_visited(&self, _path: &Path) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let temp = setup_test_dir();
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let counter = Arc::new(Counter::default());
+        let stats = Arc::new(ScanStats::new());
+        let outcome = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_scan_stats(Arc::clone(&stats))
+            .with_progress(Arc::clone(&counter) as Arc<dyn Progress>)
+            .scan()
+            .unwrap();
+
+        assert_eq!(counter.0.load(Ordering::Relaxed), stats.dirs());
+        assert!(stats.dirs() > 0);
+        assert_eq!(counter.1.load(Ordering::Relaxed), outcome.items.len());
+    }
+
+    #[test]
+    fn test_fast_size_mode_does_not_walk_matched_directories() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.child("node_modules");
+        modules.child("a/index.js").write_str("12345").unwrap();
+        modules.child("a/debug.log").write_str("123").unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let stats = Arc::new(ScanStats::new());
+        let outcome = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_size_mode(SizeMode::Fast)
+            .with_scan_stats(Arc::clone(&stats))
+            .scan()
+            .unwrap();
+
+        assert_eq!(outcome.items.len(), 1);
+        assert_eq!(outcome.items[0].path, modules.path());
+        assert!(outcome.items[0].size >= 8);
+        // A directory this small is sampled whole, so its size is exact.
+        assert!(outcome.estimated_sizes.is_empty());
+        assert_eq!(stats.dirs(), 1);
+    }
+
+    #[test]
+    fn test_directory_size_sums_nested_files() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.child("node_modules");
+        modules.child("a/index.js").write_str("12345").unwrap();
+        modules
+            .child("a/lib/deep/util.js")
+            .write_str("123")
+            .unwrap();
+        modules.child("b.js").write_str("12").unwrap();
+        temp.child("src/main.js").write_str("not counted").unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher);
+        let ScanOutcome { items, .. } = scanner.scan().unwrap();
+
+        let base = fs::metadata(modules.path()).unwrap().len();
+        let item = items
+            .iter()
+            .find(|item| item.path == modules.path())
+            .unwrap();
+        assert_eq!(item.size, base + 10);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_files_add_examined_bytes() {
+        let temp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        outside.child("big.bin").write_binary(&[0; 1000]).unwrap();
+        temp.child("app/src/main.rs").write_str("12345").unwrap();
+        // Unmatched links are neither followed nor stat'ed, and unmatched
+        // directories hold no bytes of their own.
+        std::os::unix::fs::symlink(
+            outside.child("big.bin").path(),
+            temp.child("app/big").path(),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(outside.path(), temp.child("app/elsewhere").path()).unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher);
+        let ScanOutcome { items, stats, .. } = scanner.scan().unwrap();
+
+        assert!(items.is_empty());
+        assert_eq!(stats.symlinks_visited, 2);
+        assert_eq!(stats.bytes_examined, 5);
+        assert_eq!(stats.errors, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links_inside_matched_directories_add_no_bytes() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.child("node_modules");
+        modules.child("pkg/index.js").write_str("12345").unwrap();
+        temp.child("big.bin").write_binary(&[0; 1000]).unwrap();
+        // Deleting a link frees neither its target outside the item nor, twice, one
+        // inside it.
+        std::os::unix::fs::symlink(temp.child("big.bin").path(), modules.child("big").path())
+            .unwrap();
+        std::os::unix::fs::symlink("pkg/index.js", modules.child("main.js").path()).unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher);
+        let ScanOutcome { items, .. } = scanner.scan().unwrap();
+
+        let base = fs::metadata(modules.path()).unwrap().len();
+        let item = items
+            .iter()
+            .find(|item| item.path == modules.path())
+            .unwrap();
+        assert_eq!(item.size, base + 5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_nested_matches_and_hard_links_count_once() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.child("node_modules");
+        modules
+            .child("pkg/dist/bundle.js")
+            .write_str("12345")
+            .unwrap();
+        fs::hard_link(
+            modules.child("pkg/dist/bundle.js").path(),
+            modules.child("pkg/bundle.js").path(),
+        )
+        .unwrap();
+
+        let config = Config::default();
+        let matcher = Arc::new(PatternMatcher::new(&config.patterns).unwrap());
+        let tracker = Arc::new(CategoryTracker::new());
+        let scanner = Scanner::new(temp.path().to_path_buf(), matcher)
+            .with_category_tracker(Arc::clone(&tracker));
+        let ScanOutcome { items, .. } = scanner.scan().unwrap();
+
+        let item = items
+            .iter()
+            .find(|item| item.path == modules.path())
+            .unwrap();
+        assert_eq!(item.size, fs::metadata(modules.path()).unwrap().len() + 5);
+        assert!(items.len() > 1, "dist should also match");
+        assert_eq!(tracker.total_count(), 1);
+        assert_eq!(tracker.total_size(), item.size);
+    }
 }
@@ -0,0 +1,200 @@
+//! This module writes a [`Plan`] as a POSIX shell script, for `mc plan --output sh`.
+//!
+//! The script is meant to be reviewed and then run by someone allowed to delete what
+//! mc itself may not. It removes each planned item with `rm`, single-quoting every
+//! path, and stops at the first failure. Only deletion is scripted: archives, hooks,
+//! `[[actions]]` commands and the safety checks mc makes while cleaning do not run.
+//! Paths kept by scoped excludes are never in the plan, and a directory holding paths
+//! `[safety] partial_delete` keeps is left commented out, as `rm -rf` would take them
+//! too.
+
+use crate::engine::partial;
+use crate::patterns::PatternMatcher;
+use crate::types::{CleanItem, ItemType};
+use crate::utils::format;
+use crate::Plan;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Renders `plan` as a commented shell script removing its items, except the
+/// directories holding paths `protection` excludes.
+pub fn shell_script(plan: &Plan, protection: Option<&PatternMatcher>) -> String {
+    let total: u64 = plan.items.iter().map(|item| item.size).sum();
+    let mut script = String::new();
+    let _ = writeln!(script, "#!/bin/sh");
+    let _ = writeln!(
+        script,
+        "# Cleanup plan for {}, written by mc {}.",
+        comment(&plan.root.display().to_string()),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(
+        script,
+        "# Removes {} items, {}. Review every command before running it; if the tree",
+        plan.items.len(),
+        format::size(total)
+    );
+    let _ = writeln!(script, "# may have changed since, plan again instead.");
+    let _ = writeln!(script, "set -eu");
+
+    for item in &plan.items {
+        let _ = writeln!(
+            script,
+            "\n# {}: {:?} via `{}`, {}",
+            item_type(item),
+            item.pattern.category,
+            comment(&item.pattern.pattern),
+            format::size(item.size)
+        );
+        let Some(path) = item.path.to_str() else {
+            // A lossy path could name another file, so it is left to the reviewer.
+            let _ = writeln!(
+                script,
+                "# Skipped, as its path is not valid UTF-8: {}",
+                comment(&item.path.to_string_lossy())
+            );
+            continue;
+        };
+        let skipped = match protection.map(|matcher| protected_paths(item, matcher)) {
+            Some(Ok(0)) | None => None,
+            Some(Ok(1)) => Some("it holds a path mc keeps".to_string()),
+            Some(Ok(kept)) => Some(format!("it holds {} paths mc keeps", kept)),
+            Some(Err(err)) => Some(format!("it cannot be checked for paths mc keeps ({})", err)),
+        };
+        if let Some(why) = skipped {
+            let _ = writeln!(
+                script,
+                "# Skipped, as {}; clean it with mc instead:",
+                comment(&why)
+            );
+            let _ = writeln!(script, "# rm -rf -- {}", comment(&shell_words::quote(path)));
+            continue;
+        }
+        let flags = match item.item_type {
+            ItemType::Directory if !is_link(item) => "-rf",
+            _ => "-f",
+        };
+        let _ = writeln!(script, "rm {} -- {}", flags, shell_words::quote(path));
+    }
+    script
+}
+
+fn item_type(item: &CleanItem) -> &'static str {
+    match item.item_type {
+        _ if is_link(item) => "symlink",
+        ItemType::Directory => "directory",
+        ItemType::File => "file",
+        ItemType::Symlink => "symlink",
+    }
+}
+
+/// Whether the item is a symbolic link on disk, which `rm -f` removes without
+/// touching its target.
+fn is_link(item: &CleanItem) -> bool {
+    item.path
+        .symlink_metadata()
+        .is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Counts the paths inside a directory item that `matcher` excludes.
+fn protected_paths(item: &CleanItem, matcher: &PatternMatcher) -> std::io::Result<usize> {
+    if item.item_type != ItemType::Directory || is_link(item) {
+        return Ok(0);
+    }
+    let protected = |path: &Path| matcher.is_excluded(path);
+    partial::measure_dir_except(&item.path, &protected).map(|removal| removal.kept)
+}
+
+/// Keeps `text` on one comment line.
+fn comment(text: &str) -> String {
+    text.replace(['\n', '\r'], "?")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::process::Command;
+
+    #[test]
+    fn test_script_removes_planned_items() {
+        let temp = TempDir::new().unwrap();
+        let odd = temp.child("it's a\ntarget");
+        odd.child("out.o").write_str("obj").unwrap();
+        let log = temp.child("$(touch pwned).log");
+        log.write_str("log").unwrap();
+        let item = |path, item_type| clean_item(path, item_type, 3, PatternCategory::BuildOutputs);
+        let plan = Plan {
+            root: temp.path().to_path_buf(),
+            items: vec![
+                item(odd.path(), ItemType::Directory),
+                item(log.path(), ItemType::File),
+            ],
+            skipped: Vec::new(),
+            scan_errors: Vec::new(),
+            scan_error_groups: Vec::new(),
+            scan_errors_omitted: 0,
+            scan: Default::default(),
+            timings: Default::default(),
+            pattern_stats: Default::default(),
+            estimated_sizes: Vec::new(),
+        };
+
+        let script = shell_script(&plan, None);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&script)
+            .current_dir(temp.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        odd.assert(predicates::path::missing());
+        log.assert(predicates::path::missing());
+        temp.child("pwned").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn test_script_leaves_kept_paths_alone() {
+        let temp = TempDir::new().unwrap();
+        temp.child("web/dist/bundle.js").write_str("js").unwrap();
+        temp.child("web/dist/cache/persistent.bin")
+            .write_str("bin")
+            .unwrap();
+        temp.child("app/node_modules/pkg/index.js")
+            .write_str("js")
+            .unwrap();
+        temp.child("app/node_modules/.cache/keep")
+            .write_str("keep")
+            .unwrap();
+        let mut config = Config::default();
+        config
+            .patterns
+            .scoped_exclude
+            .insert("dist".to_string(), vec!["cache".to_string()]);
+        config.patterns.exclude.push(
+            temp.path()
+                .join("app/node_modules/.cache/keep")
+                .display()
+                .to_string(),
+        );
+        config.safety.partial_delete = true;
+        let cleaner = crate::Cleaner::new(config).with_quiet(true);
+        let plan = cleaner.plan(temp.path()).unwrap();
+
+        let script = cleaner.shell_script(&plan).unwrap();
+        assert!(script.contains("# Skipped, as it holds a path mc keeps"));
+        let status = Command::new("sh").arg("-c").arg(&script).status().unwrap();
+        assert!(status.success());
+        temp.child("web/dist/bundle.js")
+            .assert(predicates::path::missing());
+        temp.child("web/dist/cache/persistent.bin")
+            .assert(predicates::path::exists());
+        temp.child("app/node_modules/.cache/keep")
+            .assert(predicates::path::exists());
+    }
+}
@@ -0,0 +1,158 @@
+//! Overwriting file contents before they are unlinked, for `options.secure_delete`.
+//!
+//! Each regular file gets a single pass of random bytes, flushed to disk, before it is
+//! removed. This only helps where a write lands on the blocks that held the old data.
+//! Copy-on-write file systems (Btrfs, ZFS, APFS) write elsewhere and SSDs remap blocks
+//! for wear leveling, so on those the old contents may survive; [`limitation`] tells
+//! callers when to warn. Files with other hard links are unlinked without being
+//! overwritten, since their contents are still in use.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::types::DeviceKind;
+
+/// The size of each write of random bytes.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Overwrites the file at `path` with random bytes and removes it.
+///
+/// Links are removed without touching their target.
+///
+/// # Errors
+///
+/// Returns the first I/O error; a file that could not be overwritten is not removed.
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_file() && link_count(&metadata) <= 1 {
+        overwrite(path, metadata.len())?;
+    }
+    fs::remove_file(path)
+}
+
+/// Overwrites every regular file under `dir` with random bytes and removes the tree.
+///
+/// # Errors
+///
+/// Returns the first I/O error; nothing is removed if a file could not be overwritten.
+pub fn remove_dir_all(dir: &Path) -> io::Result<()> {
+    for entry in WalkDir::new(dir).follow_links(false) {
+        let entry = entry.map_err(io::Error::from)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(io::Error::from)?;
+        if link_count(&metadata) <= 1 {
+            overwrite(entry.path(), metadata.len())?;
+        }
+    }
+    fs::remove_dir_all(dir)
+}
+
+/// Returns why overwriting may not erase old contents under `root`, if it may not.
+pub fn limitation(root: &Path) -> Option<&'static str> {
+    if crate::snapshots::detect(root).is_some() {
+        return Some("copy-on-write file systems write new data elsewhere");
+    }
+    match crate::utils::device::detect(root) {
+        DeviceKind::Ssd => Some("SSDs remap overwritten blocks for wear leveling"),
+        _ => None,
+    }
+}
+
+/// Writes `len` random bytes over the start of the file at `path` and syncs it.
+fn overwrite(path: &Path, len: u64) -> io::Result<()> {
+    let mut file = open_writable(path)?;
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let mut left = len;
+    while left > 0 {
+        let chunk = left.min(BLOCK_SIZE as u64) as usize;
+        fastrand::fill(&mut block[..chunk]);
+        file.write_all(&block[..chunk])?;
+        left -= chunk as u64;
+    }
+    file.sync_all()?;
+    log::debug!("Overwrote {} bytes of {}", len, path.display());
+    Ok(())
+}
+
+/// Opens the file at `path` for writing, clearing its read-only bit first if it has
+/// one. The bit is not put back, since the file is removed next, unless opening fails.
+fn open_writable(path: &Path) -> io::Result<fs::File> {
+    let original = fs::symlink_metadata(path)?.permissions();
+    if !original.readonly() {
+        return OpenOptions::new().write(true).open(path);
+    }
+    let mut permissions = original.clone();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)?;
+    OpenOptions::new().write(true).open(path).inspect_err(|_| {
+        let _ = fs::set_permissions(path, original);
+    })
+}
+
+#[cfg(unix)]
+fn link_count(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn link_count(_metadata: &fs::Metadata) -> u64 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_overwrite_replaces_contents() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child("secret.bin");
+        let original = vec![0u8; BLOCK_SIZE + 10];
+        file.write_binary(&original).unwrap();
+
+        overwrite(file.path(), original.len() as u64).unwrap();
+
+        let overwritten = fs::read(file.path()).unwrap();
+        assert_eq!(overwritten.len(), original.len());
+        assert_ne!(overwritten, original);
+    }
+
+    #[test]
+    fn test_remove_dir_all_removes_tree() {
+        let temp = TempDir::new().unwrap();
+        temp.child("dist/a/bundle.js").write_str("secret").unwrap();
+        temp.child("dist/b.map").write_str("secret").unwrap();
+
+        remove_dir_all(temp.child("dist").path()).unwrap();
+
+        temp.child("dist").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn test_remove_file_shreds_read_only_files() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child("secret.key");
+        file.write_str("secret").unwrap();
+        let mut permissions = fs::metadata(file.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(file.path(), permissions).unwrap();
+
+        remove_file(file.path()).unwrap();
+
+        file.assert(predicates::path::missing());
+    }
+}
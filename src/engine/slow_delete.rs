@@ -0,0 +1,140 @@
+//! Detecting deletes slowed down by on-access scanning, and working around them, for
+//! `options.slow_deletes`.
+//!
+//! Antivirus software such as Windows Defender inspects files as they are opened and
+//! deleted, which can make removing a `node_modules` tree of tens of thousands of
+//! files take minutes instead of seconds. [`probe`] times deleting a few new files in
+//! a directory about to be cleaned, and [`is_slow`] judges the result. To mitigate,
+//! [`remove_dir_all`] renames a directory to a hidden sibling before deleting it, so
+//! its path is free at once and scanners watching the project stop reacting to it,
+//! and retries removals that fail while a scanner still holds a file open.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::engine::native;
+
+/// The number of files a probe creates and deletes.
+const PROBE_FILES: usize = 16;
+
+/// The median per-file delete latency from which deletes count as slow. Deleting a
+/// small file takes well under a millisecond on an idle local disk.
+pub const SLOW_LATENCY: Duration = Duration::from_millis(5);
+
+/// How often a removal that failed the way a held-open file does is retried.
+const RETRIES: u32 = 3;
+
+/// Returns the median time deleting a small new file in `dir` takes. The files are
+/// created in a hidden directory of their own, which is removed afterwards.
+pub fn probe(dir: &Path) -> io::Result<Duration> {
+    let probe_dir = dir.join(format!(".mc-probe-{}", process::id()));
+    fs::create_dir(&probe_dir)?;
+    let latency = time_deletes(&probe_dir);
+    let _ = fs::remove_dir_all(&probe_dir);
+    latency
+}
+
+/// Returns whether a delete latency measured by [`probe`] points at on-access scanning.
+pub fn is_slow(latency: Duration) -> bool {
+    latency >= SLOW_LATENCY
+}
+
+/// Removes the directory `path` after renaming it to a hidden sibling, retrying
+/// removals that fail while a scanner holds a file open. A directory that cannot be
+/// renamed is removed where it is.
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    let target = match aside_path(path) {
+        Some(aside) => match fs::rename(path, &aside) {
+            Ok(()) => aside,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(err),
+            Err(err) => {
+                log::debug!("Cannot rename {} aside: {}", path.display(), err);
+                path.to_path_buf()
+            }
+        },
+        None => path.to_path_buf(),
+    };
+    let mut attempt = 0;
+    loop {
+        match native::remove_dir_all(&target) {
+            Err(err) if attempt < RETRIES && is_transient(&err) => {
+                attempt += 1;
+                thread::sleep(Duration::from_millis(50 << attempt));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Deletes freshly written files in `dir` one by one and returns the median latency.
+fn time_deletes(dir: &Path) -> io::Result<Duration> {
+    let mut latencies = Vec::with_capacity(PROBE_FILES);
+    for n in 0..PROBE_FILES {
+        // Scanners skip many kinds of files, but rarely scripts.
+        let path = dir.join(format!("probe-{}.js", n));
+        fs::write(&path, "module.exports = {};\n")?;
+        let start = Instant::now();
+        fs::remove_file(&path)?;
+        latencies.push(start.elapsed());
+    }
+    latencies.sort();
+    Ok(latencies[PROBE_FILES / 2])
+}
+
+/// Returns the hidden sibling `path` is renamed to before it is deleted.
+fn aside_path(path: &Path) -> Option<PathBuf> {
+    let mut name = OsString::from(".mc-deleting-");
+    name.push(path.file_name()?);
+    name.push(format!("-{}", process::id()));
+    Some(path.with_file_name(name))
+}
+
+/// Returns whether `err` is how a removal fails while a scanner holds a file open: the
+/// directory is not empty yet, or, on Windows, access is denied to a file pending
+/// deletion.
+fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::DirectoryNotEmpty => true,
+        io::ErrorKind::PermissionDenied => cfg!(windows),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_probe_and_remove_aside() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.child("node_modules");
+        modules.child("pkg/index.js").write_str("x").unwrap();
+
+        probe(modules.path()).unwrap();
+        assert_eq!(fs::read_dir(modules.path()).unwrap().count(), 1);
+
+        remove_dir_all(modules.path()).unwrap();
+        modules.assert(predicates::path::missing());
+        assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 0);
+        let gone = remove_dir_all(modules.path()).unwrap_err();
+        assert_eq!(gone.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_leftovers_match_a_builtin_pattern() {
+        use crate::config::Config;
+        use crate::patterns::PatternMatcher;
+
+        let matcher = PatternMatcher::from_config(&Config::default()).unwrap();
+        let aside = aside_path(Path::new("/work/app/node_modules")).unwrap();
+        let found = matcher.matches_with_type(&aside, None).unwrap();
+        assert_eq!(found.pattern, ".mc-deleting-*");
+    }
+}
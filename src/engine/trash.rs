@@ -0,0 +1,394 @@
+//! Moving cleaned items to the trash instead of deleting them, for `[trash]`.
+//!
+//! On Windows items go to the Recycle Bin of their drive through the shell. A Recycle
+//! Bin only holds items up to its maximum size, and Windows deletes larger ones for
+//! good without telling the caller, so [`oversized`] finds them before the clean and
+//! the cleaner sends them where `trash.fallback` says instead. On macOS items are
+//! renamed into `~/.Trash`, and on other Unix systems into the home trash of the
+//! freedesktop.org trash specification, with the `.trashinfo` file that lets a file
+//! manager restore them. Those trashes have no size limit, but as renames they fail
+//! for items on another file system than the home directory.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::types::CleanItem;
+
+/// Returns the items of `items` that do not fit in the Recycle Bin of their drive,
+/// with the most that Recycle Bin holds. Always empty off Windows.
+///
+/// An item whose Recycle Bin is turned off ("Don't move files to the Recycle Bin")
+/// fits in none, so it is returned with a capacity of 0.
+pub fn oversized(items: &[CleanItem]) -> HashMap<PathBuf, u64> {
+    // Items are mostly on a few drives; each is looked up once.
+    let mut capacities: HashMap<PathBuf, Option<u64>> = HashMap::new();
+    let mut oversized = HashMap::new();
+    for item in items {
+        let Some(volume) = volume_of(&item.path) else {
+            continue;
+        };
+        let capacity = *capacities
+            .entry(volume)
+            .or_insert_with_key(|volume| capacity(volume));
+        if let Some(capacity) = capacity.filter(|capacity| item.size > *capacity) {
+            oversized.insert(item.path.clone(), capacity);
+        }
+    }
+    oversized
+}
+
+/// Moves `path` to the trash.
+///
+/// # Errors
+///
+/// Returns the I/O error of the move. Nothing is deleted in that case.
+#[cfg(windows)]
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::UI::Shell::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NO_UI, FO_DELETE, SHFILEOPSTRUCTW,
+    };
+
+    let absolute = std::path::absolute(path)?;
+    // The shell takes a list of paths, each NUL-terminated, ending with an empty one.
+    let from: Vec<u16> = absolute.as_os_str().encode_wide().chain([0, 0]).collect();
+    let mut operation = SHFILEOPSTRUCTW {
+        hwnd: std::ptr::null_mut(),
+        wFunc: FO_DELETE,
+        pFrom: from.as_ptr(),
+        pTo: std::ptr::null(),
+        fFlags: (FOF_ALLOWUNDO | FOF_NO_UI) as u16,
+        fAnyOperationsAborted: 0,
+        hNameMappings: std::ptr::null_mut(),
+        lpszProgressTitle: std::ptr::null(),
+    };
+    // SAFETY: `from` is double-NUL-terminated and outlives the call, and the other
+    // pointers are null, which the shell accepts for a delete without UI.
+    let status = unsafe { SHFileOperationW(&mut operation) };
+    if status != 0 {
+        return Err(io::Error::other(format!(
+            "the shell could not move it to the Recycle Bin (error {:#x})",
+            status
+        )));
+    }
+    if operation.fAnyOperationsAborted != 0 {
+        return Err(io::Error::other("moving it to the Recycle Bin was aborted"));
+    }
+    Ok(())
+}
+
+/// Moves `path` to the trash.
+///
+/// # Errors
+///
+/// Returns the I/O error of the move, such as when `path` is on another file system
+/// than the trash. Nothing is copied in that case.
+#[cfg(target_os = "macos")]
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    let dirs = directories::BaseDirs::new()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    let trash = dirs.home_dir().join(".Trash");
+    std::fs::create_dir_all(&trash)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    // Finder numbers clashing names the same way.
+    let mut attempt = 1u32;
+    loop {
+        let target = match attempt {
+            1 => trash.join(name),
+            n => trash.join(format!("{} {}", name.to_string_lossy(), n)),
+        };
+        if std::fs::symlink_metadata(&target).is_err() {
+            return rename(path, &target);
+        }
+        attempt += 1;
+    }
+}
+
+/// Moves `path` to the trash.
+///
+/// # Errors
+///
+/// Returns the I/O error of the move, such as when `path` is on another file system
+/// than the trash. Nothing is copied in that case.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    let dirs = directories::BaseDirs::new()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    move_to_home_trash(&dirs.data_dir().join("Trash"), path)
+}
+
+/// Trash is not supported on this platform.
+#[cfg(not(any(unix, windows)))]
+pub fn move_to_trash(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "there is no trash on this platform",
+    ))
+}
+
+/// Moves `path` into the freedesktop.org trash directory `trash`, writing the
+/// `.trashinfo` file a file manager needs to restore it.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn move_to_home_trash(trash: &Path, path: &Path) -> io::Result<()> {
+    use std::io::Write;
+
+    let absolute = std::path::absolute(path)?;
+    let name = absolute
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    let files = trash.join("files");
+    let info = trash.join("info");
+    std::fs::create_dir_all(&files)?;
+    std::fs::create_dir_all(&info)?;
+    // Creating the info file claims the name, as the specification asks.
+    let mut attempt = 1u32;
+    let (target, info_path, mut info_file) = loop {
+        let trashed = match attempt {
+            1 => name.to_os_string(),
+            n => format!("{}.{}", name.to_string_lossy(), n).into(),
+        };
+        let mut info_name = trashed.clone();
+        info_name.push(".trashinfo");
+        let info_path = info.join(info_name);
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) if std::fs::symlink_metadata(files.join(&trashed)).is_err() => {
+                break (files.join(trashed), info_path, file);
+            }
+            Ok(_) => {
+                let _ = std::fs::remove_file(&info_path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+        attempt += 1;
+    };
+    let written = write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(&crate::utils::fs::path_to_bytes(&absolute)),
+        deletion_date()
+    );
+    let moved = written.and_then(|()| rename(&absolute, &target));
+    if moved.is_err() {
+        let _ = std::fs::remove_file(&info_path);
+    }
+    moved
+}
+
+/// Renames `from` to `to`, explaining the error when they are on different file
+/// systems.
+#[cfg(unix)]
+fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::rename(from, to).map_err(|err| {
+        if err.raw_os_error() == Some(libc::EXDEV) {
+            io::Error::new(
+                err.kind(),
+                format!("the trash {} is on another file system", to.display()),
+            )
+        } else {
+            err
+        }
+    })
+}
+
+/// Escapes `bytes` for the `Path` key of a `.trashinfo` file, as in a URL.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Returns the local time now as `YYYY-MM-DDThh:mm:ss`, for a `.trashinfo` file.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn deletion_date() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0) as libc::time_t;
+    let mut tm = std::mem::MaybeUninit::<libc::tm>::uninit();
+    // SAFETY: `now` and `tm` are valid for the call, which fills `tm` when it
+    // returns non-null.
+    if unsafe { libc::localtime_r(&now, tm.as_mut_ptr()) }.is_null() {
+        return String::new();
+    }
+    // SAFETY: localtime_r returned non-null, so `tm` is initialized.
+    let tm = unsafe { tm.assume_init() };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// Returns the root of the volume holding `path`, such as `C:\`.
+#[cfg(windows)]
+fn volume_of(path: &Path) -> Option<PathBuf> {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use windows_sys::Win32::Storage::FileSystem::GetVolumePathNameW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut volume = [0u16; 261];
+    // SAFETY: `wide` is NUL-terminated and `volume` holds the length passed.
+    if unsafe { GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as u32) } == 0 {
+        return None;
+    }
+    let len = volume.iter().position(|&c| c == 0).unwrap_or(volume.len());
+    Some(PathBuf::from(std::ffi::OsString::from_wide(&volume[..len])))
+}
+
+/// Only Windows limits the trash, so no volume needs looking up.
+#[cfg(not(windows))]
+fn volume_of(_path: &Path) -> Option<PathBuf> {
+    None
+}
+
+/// Returns the most the Recycle Bin of the volume rooted at `volume` holds, 0 if it is
+/// turned off, or `None` if that cannot be determined.
+///
+/// Explorer stores the settings per volume GUID under the current user's `BitBucket`
+/// key. A volume whose settings were never changed has no `MaxCapacity`, and Windows
+/// then allows 5% of the volume.
+#[cfg(windows)]
+fn capacity(volume: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetVolumeNameForVolumeMountPointW,
+    };
+
+    let wide: Vec<u16> = volume.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut name = [0u16; 50];
+    // SAFETY: `wide` is NUL-terminated and `name` holds the length passed.
+    let found = unsafe {
+        GetVolumeNameForVolumeMountPointW(wide.as_ptr(), name.as_mut_ptr(), name.len() as u32)
+    };
+    if found != 0 {
+        // The name is `\\?\Volume{GUID}\`; the key is named after the `{GUID}`.
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        let name = String::from_utf16_lossy(&name[..len]);
+        if let Some(guid) = name
+            .find('{')
+            .map(|start| name[start..].trim_end_matches('\\'))
+        {
+            let key = format!(
+                r"Software\Microsoft\Windows\CurrentVersion\Explorer\BitBucket\Volume\{}",
+                guid
+            );
+            if registry_dword(&key, "NukeOnDelete") == Some(1) {
+                return Some(0);
+            }
+            if let Some(megabytes) = registry_dword(&key, "MaxCapacity") {
+                return Some(u64::from(megabytes) * 1024 * 1024);
+            }
+        }
+    }
+    let mut total = 0u64;
+    // SAFETY: `wide` is NUL-terminated and the out-pointers are valid or null.
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            std::ptr::null_mut(),
+            &mut total,
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(total / 20)
+}
+
+#[cfg(not(windows))]
+fn capacity(_volume: &Path) -> Option<u64> {
+    None
+}
+
+/// Reads the DWORD `value` of the current user's registry key `key`.
+#[cfg(windows)]
+fn registry_dword(key: &str, value: &str) -> Option<u32> {
+    use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+    let key: Vec<u16> = key.encode_utf16().chain(Some(0)).collect();
+    let value: Vec<u16> = value.encode_utf16().chain(Some(0)).collect();
+    let mut data = 0u32;
+    let mut len = std::mem::size_of::<u32>() as u32;
+    // SAFETY: the names are NUL-terminated and `data` holds the `len` bytes passed.
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            (&mut data as *mut u32).cast(),
+            &mut len,
+        )
+    };
+    (status == 0).then_some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn test_move_to_home_trash_writes_trash_info() {
+        let temp = TempDir::new().unwrap();
+        let trash = temp.child("Trash");
+        let first = temp.child("a/my target");
+        first.child("out.bin").write_binary(&[0; 10]).unwrap();
+        let second = temp.child("b/my target");
+        second.create_dir_all().unwrap();
+
+        move_to_home_trash(trash.path(), first.path()).unwrap();
+        move_to_home_trash(trash.path(), second.path()).unwrap();
+        first.assert(predicates::path::missing());
+        second.assert(predicates::path::missing());
+        trash
+            .child("files/my target/out.bin")
+            .assert(predicates::path::is_file());
+        trash
+            .child("files/my target.2")
+            .assert(predicates::path::is_dir());
+
+        let info = std::fs::read_to_string(trash.child("info/my target.trashinfo").path()).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=/"));
+        assert!(info.contains("/a/my%20target\n"));
+        assert!(info.contains("\nDeletionDate=20"));
+        trash
+            .child("info/my target.2.trashinfo")
+            .assert(predicates::path::is_file());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_nothing_is_oversized_without_a_recycle_bin() {
+        let size = u64::MAX;
+        let items = [clean_item(
+            "/tmp/target",
+            ItemType::Directory,
+            size,
+            PatternCategory::BuildOutputs,
+        )];
+        assert!(oversized(&items).is_empty());
+    }
+}
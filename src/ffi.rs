@@ -0,0 +1,266 @@
+//! A C ABI for embedding mc in programs not written in Rust, enabled by the `ffi`
+//! feature.
+//!
+//! Build the shared library with
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+//!
+//! and include `include/mc.h`, which is generated from this module by cbindgen (see
+//! `cbindgen.toml`).
+//!
+//! # Results
+//!
+//! [`mc_scan`], [`mc_plan`] and [`mc_clean`] each return an opaque `McReport`, never
+//! null, holding either the result or the error as JSON. [`mc_report_is_error`] tells
+//! which, and [`mc_report_json`] returns the text: the [`ScanOutcome`], the dry-run
+//! [`CleanReport`] (with its `plan`) or the `CleanReport` of the clean, or
+//! `{"error": "..."}`.
+//!
+//! [`ScanOutcome`]: crate::ScanOutcome
+//! [`CleanReport`]: crate::CleanReport
+//!
+//! # Memory ownership
+//!
+//! - Strings passed in are borrowed for the duration of the call; mc keeps no pointer
+//!   to them. They must be NUL-terminated UTF-8.
+//! - A returned `McReport` is owned by the caller, who must release it exactly once
+//!   with [`mc_report_free`]; passing null is allowed and does nothing.
+//! - The string returned by [`mc_report_json`] is owned by its report and stays valid
+//!   until the report is freed. It must not be freed on its own.
+//! - The string returned by [`mc_version`] is static.
+//!
+//! Calls may be made from any thread, concurrently, as long as a report is not freed
+//! while another thread reads it. A panic inside [`mc_scan`], [`mc_plan`] or
+//! [`mc_clean`] is caught before it reaches the C ABI and returned as an error report.
+//! That takes unwinding, which the `release` profile turns off, so release builds of
+//! the library use the `release-ffi` profile:
+//!
+//! ```bash
+//! cargo rustc --profile release-ffi --lib --features ffi --crate-type cdylib
+//! ```
+
+// With panic = "abort", catch_unwind never sees a panic and the process dies in the
+// middle of a C call instead.
+#[cfg(panic = "abort")]
+compile_error!("the ffi feature needs panic = \"unwind\"; build with --profile release-ffi");
+
+use crate::config::Config;
+use crate::types::{McError, Result};
+use crate::Cleaner;
+use serde::Serialize;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+/// The result of an mc call: JSON for either a value or an error.
+pub struct McReport {
+    json: CString,
+    is_error: bool,
+}
+
+impl McReport {
+    fn new(result: Result<String>) -> Box<Self> {
+        let (json, is_error) = match result {
+            Ok(json) => (json, false),
+            Err(err) => (error_json(&err.to_string()), true),
+        };
+        // JSON escapes control characters, so it holds no NUL byte.
+        let json = CString::new(json).expect("JSON contains no NUL byte");
+        Box::new(Self { json, is_error })
+    }
+}
+
+/// Scans `root` with the configuration at `config_path`, or the default configuration
+/// if it is null, and returns the scan outcome.
+///
+/// # Safety
+///
+/// `root` must be a valid NUL-terminated string; `config_path` must be one or null.
+#[no_mangle]
+pub unsafe extern "C" fn mc_scan(root: *const c_char, config_path: *const c_char) -> *mut McReport {
+    run(|| {
+        let (root, config) = arguments(root, config_path)?;
+        let outcome = Cleaner::new(config).scanner(root)?.scan()?;
+        to_json(&outcome)
+    })
+}
+
+/// Plans a clean of `root` without deleting anything, and returns the dry-run report.
+///
+/// # Safety
+///
+/// As for [`mc_scan`].
+#[no_mangle]
+pub unsafe extern "C" fn mc_plan(root: *const c_char, config_path: *const c_char) -> *mut McReport {
+    run(|| {
+        let (root, config) = arguments(root, config_path)?;
+        let report = Cleaner::new(config)
+            .with_quiet(true)
+            .with_dry_run(true)
+            .clean(root)?;
+        to_json(&report)
+    })
+}
+
+/// Cleans `root`, deleting what [`mc_plan`] lists, and returns the report.
+///
+/// # Safety
+///
+/// As for [`mc_scan`].
+#[no_mangle]
+pub unsafe extern "C" fn mc_clean(
+    root: *const c_char,
+    config_path: *const c_char,
+) -> *mut McReport {
+    run(|| {
+        let (root, config) = arguments(root, config_path)?;
+        let report = Cleaner::new(config).with_quiet(true).clean(root)?;
+        to_json(&report)
+    })
+}
+
+/// Returns true if `report` holds an error rather than a result.
+///
+/// # Safety
+///
+/// `report` must be a report returned by mc and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn mc_report_is_error(report: *const McReport) -> bool {
+    (*report).is_error
+}
+
+/// Returns the JSON text of `report`, valid until the report is freed.
+///
+/// # Safety
+///
+/// `report` must be a report returned by mc and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn mc_report_json(report: *const McReport) -> *const c_char {
+    (*report).json.as_ptr()
+}
+
+/// Frees `report`. Does nothing if it is null.
+///
+/// # Safety
+///
+/// `report` must be null or a report returned by mc and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn mc_report_free(report: *mut McReport) {
+    if !report.is_null() {
+        drop(Box::from_raw(report));
+    }
+}
+
+/// Returns the version of mc, such as `0.3.0`.
+#[no_mangle]
+pub extern "C" fn mc_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Runs `call`, turning its result, or its panic, into a report for the caller.
+fn run(call: impl FnOnce() -> Result<String>) -> *mut McReport {
+    let result = panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(McError::Panicked(message.to_string()))
+    });
+    Box::into_raw(McReport::new(result))
+}
+
+/// Reads the root and loads the configuration of a call.
+unsafe fn arguments(root: *const c_char, config_path: *const c_char) -> Result<(PathBuf, Config)> {
+    let root = PathBuf::from(string(root, "root")?);
+    let mut config = if config_path.is_null() {
+        Config::default()
+    } else {
+        let path = PathBuf::from(string(config_path, "config_path")?);
+        // Unlike the CLI, a file that was named but is missing is an error.
+        if !path.exists() {
+            let message = format!("config file {} does not exist", path.display());
+            return Err(McError::InvalidArgument(message));
+        }
+        Config::load(Some(&path))?
+    };
+    config.validate();
+    Ok((root, config))
+}
+
+unsafe fn string<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(McError::InvalidArgument(format!("{} is null", name)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| McError::InvalidArgument(format!("{} is not valid UTF-8", name)))
+}
+
+fn to_json(value: &impl Serialize) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+fn error_json(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn json(report: *mut McReport) -> (bool, serde_json::Value) {
+        unsafe {
+            let is_error = mc_report_is_error(report);
+            let text = CStr::from_ptr(mc_report_json(report))
+                .to_str()
+                .unwrap()
+                .to_string();
+            mc_report_free(report);
+            (is_error, serde_json::from_str(&text).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_plan_and_clean_through_the_c_abi() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/target/debug/app")
+            .write_str("binary")
+            .unwrap();
+        let root = CString::new(temp.path().to_str().unwrap()).unwrap();
+
+        let (is_error, plan) = json(unsafe { mc_plan(root.as_ptr(), std::ptr::null()) });
+        assert!(!is_error);
+        assert_eq!(plan["dry_run"], true);
+        assert!(temp.child("app/target").exists());
+
+        let (is_error, report) = json(unsafe { mc_clean(root.as_ptr(), std::ptr::null()) });
+        assert!(!is_error);
+        assert_eq!(report["items_deleted"], 1);
+        assert!(!temp.child("app/target").exists());
+    }
+
+    #[test]
+    fn test_errors_are_reported_as_json() {
+        let root = CString::new("/nonexistent").unwrap();
+        let config = CString::new("/nonexistent/mc.toml").unwrap();
+        let (is_error, report) = json(unsafe { mc_scan(root.as_ptr(), config.as_ptr()) });
+        assert!(is_error);
+        assert!(report["error"].is_string());
+
+        let (is_error, _) = json(unsafe { mc_scan(std::ptr::null(), std::ptr::null()) });
+        assert!(is_error);
+        unsafe { mc_report_free(std::ptr::null_mut()) };
+    }
+
+    #[test]
+    fn test_panics_are_reported_as_errors() {
+        let (is_error, report) = json(run(|| panic!("boom")));
+        assert!(is_error);
+        assert_eq!(report["error"], "mc panicked: boom");
+    }
+}
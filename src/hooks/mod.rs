@@ -0,0 +1,253 @@
+//! This module runs user-configured shell commands ("hooks") at well-defined points
+//! of an `mc` run.
+//!
+//! Hooks are executed through the platform shell (`sh -c` on Unix, `cmd /C` on Windows)
+//! and receive their context through `MC_*` environment variables, so a hook can be a
+//! one-liner in the configuration file rather than a dedicated script.
+//!
+//! The pre- and post-clean hooks configured under `[hooks]` are run with a timeout and
+//! a failure policy; see [`HooksConfig`].
+
+use crate::config::{HookFailurePolicy, HooksConfig};
+use crate::types::{CleanReport, McError, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set while mc's own stdin and stdout carry a protocol, as with `mc serve --stdio`.
+static STDIO_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Context describing the clean a hook is attached to.
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    /// The root being cleaned.
+    pub root: PathBuf,
+    /// The total size of the items planned for deletion.
+    pub planned_bytes: u64,
+    /// The number of items planned for deletion.
+    pub planned_items: usize,
+}
+
+impl HookContext {
+    /// The environment variables shared by every clean hook.
+    fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("MC_ROOT", self.root.display().to_string()),
+            ("MC_PLANNED_BYTES", self.planned_bytes.to_string()),
+            ("MC_PLANNED_ITEMS", self.planned_items.to_string()),
+        ]
+    }
+}
+
+/// Runs the configured pre-clean hook, if any.
+///
+/// # Errors
+///
+/// Returns the hook's error when it fails and the failure policy is `abort`.
+pub fn run_pre_clean(config: &HooksConfig, ctx: &HookContext) -> Result<()> {
+    match config.pre_clean {
+        Some(ref command) => apply_policy(
+            config.on_failure,
+            run_hook(command, &ctx.env(), Some(timeout(config))),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Runs the configured post-clean hook, if any, exposing `report` as a JSON file.
+///
+/// The report file is created with a random name in the system temp directory, readable
+/// only by the current user, and removed after the hook exits.
+///
+/// # Errors
+///
+/// Returns the hook's error when it fails and the failure policy is `abort`.
+pub fn run_post_clean(config: &HooksConfig, ctx: &HookContext, report: &CleanReport) -> Result<()> {
+    let Some(ref command) = config.post_clean else {
+        return Ok(());
+    };
+
+    let mut report_file = tempfile::Builder::new()
+        .prefix("mc-report-")
+        .suffix(".json")
+        .tempfile()?;
+    report_file.write_all(&serde_json::to_vec_pretty(report)?)?;
+    report_file.flush()?;
+
+    let mut env = ctx.env();
+    env.push(("MC_REPORT_JSON", report_file.path().display().to_string()));
+    let result = run_hook(command, &env, Some(timeout(config)));
+
+    if let Err(err) = report_file.close() {
+        log::debug!("Failed to remove hook report: {}", err);
+    }
+    apply_policy(config.on_failure, result)
+}
+
+/// Runs `command` through the platform shell with the given environment variables.
+///
+/// When `timeout` is set, the command is killed once it elapses and the hook is
+/// treated as failed.
+///
+/// # Errors
+///
+/// Returns [`McError::Io`] if the shell cannot be spawned, or [`McError::Hook`] if the
+/// command exits unsuccessfully or times out.
+pub fn run_hook(command: &str, env: &[(&str, String)], timeout: Option<Duration>) -> Result<()> {
+    run_command(command, env, timeout, None)
+}
+
+/// Like [`run_hook`], but runs the command from `cwd` when given.
+pub fn run_command(
+    command: &str,
+    env: &[(&str, String)],
+    timeout: Option<Duration>,
+    cwd: Option<&Path>,
+) -> Result<()> {
+    log::debug!("Running command: {}", command);
+    let mut cmd = shell_command(command);
+    keep_off_stdio(&mut cmd);
+    cmd.envs(env.iter().map(|(key, value)| (*key, value)));
+    if let Some(cwd) = cwd {
+        cmd.current_dir(cwd);
+    }
+    let mut child = cmd.spawn()?;
+
+    let status = match timeout {
+        Some(timeout) => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    break status;
+                }
+                if Instant::now() >= deadline {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(McError::Hook(format!(
+                        "`{}` timed out after {}s",
+                        command,
+                        timeout.as_secs()
+                    )));
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        None => child.wait()?,
+    };
+
+    if !status.success() {
+        return Err(McError::Hook(format!(
+            "`{}` exited with {}",
+            command, status
+        )));
+    }
+    Ok(())
+}
+
+/// Applies the configured failure policy to a hook result.
+fn apply_policy(policy: HookFailurePolicy, result: Result<()>) -> Result<()> {
+    match (policy, result) {
+        (HookFailurePolicy::Warn, Err(err)) => {
+            log::warn!("{}", err);
+            Ok(())
+        }
+        (_, result) => result,
+    }
+}
+
+fn timeout(config: &HooksConfig) -> Duration {
+    Duration::from_secs(config.timeout_secs)
+}
+
+/// Keeps every command mc runs from now on off its stdin and stdout: the command reads
+/// nothing and its output goes to stderr. For serving over stdio, where a command
+/// inheriting them would consume requests or corrupt responses.
+pub fn reserve_stdio() {
+    STDIO_RESERVED.store(true, Ordering::Relaxed);
+}
+
+/// Gives `cmd` an empty stdin and sends its stdout to stderr if stdio is reserved.
+pub(crate) fn keep_off_stdio(cmd: &mut Command) {
+    if STDIO_RESERVED.load(Ordering::Relaxed) {
+        cmd.stdin(Stdio::null()).stdout(std::io::stderr());
+    }
+}
+
+/// Builds a `Command` that runs `command` through the platform shell.
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn context() -> HookContext {
+        HookContext {
+            root: PathBuf::from("/project"),
+            planned_bytes: 2048,
+            planned_items: 3,
+        }
+    }
+
+    #[test]
+    fn test_run_hook_success() {
+        assert!(run_hook("true", &[], None).is_ok());
+    }
+
+    #[test]
+    fn test_run_hook_failure_is_error() {
+        let err = run_hook("exit 3", &[], None).unwrap_err();
+        assert!(matches!(err, McError::Hook(_)));
+    }
+
+    #[test]
+    fn test_run_hook_passes_env() {
+        let env = [("MC_TEST_VALUE", "42".to_string())];
+        assert!(run_hook("test \"$MC_TEST_VALUE\" = 42", &env, None).is_ok());
+    }
+
+    #[test]
+    fn test_run_hook_times_out() {
+        let err = run_hook("sleep 5", &[], Some(Duration::from_millis(100))).unwrap_err();
+        assert!(err.to_string().contains("timed out"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_pre_clean_failure_policy() {
+        let mut config = HooksConfig {
+            pre_clean: Some("test \"$MC_PLANNED_ITEMS\" = 4".to_string()),
+            ..HooksConfig::default()
+        };
+        assert!(run_pre_clean(&config, &context()).is_err());
+
+        config.on_failure = HookFailurePolicy::Warn;
+        assert!(run_pre_clean(&config, &context()).is_ok());
+    }
+
+    #[test]
+    fn test_post_clean_exposes_report() {
+        let config = HooksConfig {
+            post_clean: Some("grep -q '\"bytes_freed\": 99' \"$MC_REPORT_JSON\"".to_string()),
+            ..HooksConfig::default()
+        };
+        let report = CleanReport {
+            bytes_freed: 99,
+            ..Default::default()
+        };
+        assert!(run_post_clean(&config, &context(), &report).is_ok());
+    }
+}
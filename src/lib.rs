@@ -56,28 +56,100 @@
 //! }
 //! ```
 
+pub mod alerts;
+pub mod backups;
 pub mod cli;
 pub mod config;
+pub mod daemon;
 pub mod engine;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod hooks;
+pub mod orchestrator;
 pub mod patterns;
+pub mod plugins;
+pub mod projects;
+pub mod remote;
+pub mod report;
+pub mod resume;
+pub mod retry;
+pub mod rules;
 pub mod safety;
+pub mod snapshots;
+#[cfg(test)]
+mod test_support;
 pub mod types;
 pub mod utils;
 
-pub use config::{Config, OptionsConfig, PatternConfig, SafetyConfig};
-pub use engine::{prune_nested_items, ParallelCleaner, Scanner};
+pub use config::{
+    ActionRule, AlertsConfig, ArchiveCompression, ArchiveConfig, ByteSize, CommandScope, Config,
+    ConfigBuilder, GitRepoPolicy, GitSafetyConfig, HookFailurePolicy, HooksConfig, OptionsConfig,
+    ParallelThreads, PatternConfig, PluginsConfig, QuarantineConfig, RetentionRule, SafetyConfig,
+    Scheduling, ServerConfig, SizeMode, SlowDeletePolicy, SymlinkPolicy, TrashConfig,
+    TrashFallback,
+};
+pub use engine::{prune_nested_items, ParallelCleaner, ScanIter, Scanner};
 pub use patterns::{PatternMatcher, BUILTIN_PATTERNS};
-pub use safety::SafetyGuard;
+pub use report::{HumanReport, ReportFormat};
+pub use safety::{SafetyGuard, SecretDetector};
 pub use types::{
-    CleanError, CleanItem, CleanReport, ItemType, McError, PatternCategory, PatternMatch,
-    PatternSource, Result,
+    AutoThreads, CategoryTotal, CleanError, CleanItem, CleanReport, DeviceKind, ItemOutcome,
+    ItemStatus, ItemType, LargestItem, McError, PatternCategory, PatternMatch, PatternSource,
+    PatternStats, PhaseTimings, PlannedItem, ProjectTotal, Result, RootReport, RoutedItem,
+    ScanErrorGroup, ScanOutcome, ScanSummary, SkipReason, SkippedItem, TrashRoute,
 };
 pub use utils::{
-    CategoryTracker, CompactDisplay, NoOpProgress, Progress, ProgressReporter, ScanStats,
+    CategoryTracker, CompactDisplay, MultiRootProgress, NoOpProgress, PlainProgress, Progress,
+    ProgressReporter, RootProgress, ScanStats,
 };
 
-use std::path::Path;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// The items a clean of one root would delete, from [`Cleaner::plan`], with what the
+/// scan found along the way. [`Cleaner::apply`] deletes them.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    /// The root that was scanned.
+    pub root: PathBuf,
+    /// The items to delete, outermost only.
+    pub items: Vec<CleanItem>,
+    /// The matched items left alone by a git policy, or for holding uncommitted work.
+    pub skipped: Vec<SkippedItem>,
+    /// Errors met while scanning, capped like [`ScanOutcome::errors`].
+    pub scan_errors: Vec<types::ScanError>,
+    /// Every scan error, grouped by cause.
+    pub scan_error_groups: Vec<ScanErrorGroup>,
+    /// The number of scan errors left out of `scan_errors`.
+    pub scan_errors_omitted: usize,
+    /// Totals for the walk.
+    pub scan: ScanSummary,
+    /// How long scanning and pruning took; the later phases are zero.
+    pub timings: PhaseTimings,
+    /// What each include pattern matched.
+    pub pattern_stats: BTreeMap<String, PatternStats>,
+    /// The items whose size is an estimate, as in [`ScanOutcome::estimated_sizes`].
+    pub estimated_sizes: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// Ranks the items with [`engine::recommend::rank`] and keeps only the suggested
+    /// ones, so [`Cleaner::apply`] deletes what `mc recommend` suggests. Returns the
+    /// ranking of every item.
+    pub fn recommend(&mut self) -> Vec<engine::recommend::Recommendation> {
+        let ranked = engine::recommend::rank(&self.items);
+        self.items = ranked
+            .iter()
+            .filter(|recommendation| recommendation.suggested)
+            .map(|recommendation| recommendation.item.clone())
+            .collect();
+        ranked
+    }
+}
 
 /// The primary interface for cleaning operations.
 ///
@@ -93,6 +165,11 @@ pub struct Cleaner {
     dry_run: bool,
     quiet: bool,
     verbose: bool,
+    deterministic: bool,
+    allow_uncommitted: bool,
+    thread_pool: Option<Arc<rayon::ThreadPool>>,
+    progress: Option<Arc<dyn Progress>>,
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl Cleaner {
@@ -108,9 +185,23 @@ impl Cleaner {
             dry_run: false,
             quiet: false,
             verbose: false,
+            deterministic: false,
+            allow_uncommitted: false,
+            thread_pool: None,
+            progress: None,
+            cancel: None,
         }
     }
 
+    /// Sets whether items holding modified or untracked files that git does not
+    /// ignore are cleaned. They are left alone by default, and listed in
+    /// [`Plan::skipped`] and [`CleanReport::skipped`], since a library clean cannot
+    /// ask first as `mc` does.
+    pub fn with_allow_uncommitted(mut self, allow: bool) -> Self {
+        self.allow_uncommitted = allow;
+        self
+    }
+
     /// Sets the dry run mode for the cleaner.
     ///
     /// When dry run is enabled, the cleaner will only report what it would delete,
@@ -140,6 +231,71 @@ impl Cleaner {
         self
     }
 
+    /// Makes runs over the same tree reproducible.
+    ///
+    /// Planned items are listed and deleted in path order, one at a time, and the
+    /// report is sorted with [`CleanReport::sort_by_path`]. Deleting is slower this way.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Scans and cleans on `pool` instead of thread pools of mc's own.
+    ///
+    /// Server processes that run mc next to other parallel work can pass their pool to
+    /// keep one thread budget. `options.parallel_threads` is then ignored.
+    pub fn with_thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Returns the configuration the cleaner works with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Reports progress to `progress` instead of drawing progress bars: matched items
+    /// while scanning, then handled items while cleaning.
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Stops scanning or cleaning once `cancel` is set, from any thread.
+    ///
+    /// A cancelled scan plans what it found so far; a cancelled clean leaves the items
+    /// it had not started, counting them in `items_not_attempted`.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Returns a scanner for `path` set up from the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern or secret rule in the configuration is invalid, or
+    /// the enabled plugins cannot be loaded.
+    pub fn scanner<P: AsRef<Path>>(&self, path: P) -> Result<Scanner> {
+        let path = path.as_ref();
+        let matcher = PatternMatcher::from_config(&self.config)?
+            .with_root(path)?
+            .with_plugins(plugins::Plugins::from_config(&self.config.plugins)?);
+        let matcher = Arc::new(matcher);
+        let mut scanner = engine::scanner_for(&self.config, path.to_path_buf(), matcher)?;
+        // Only a dry run's sizes may be estimated; a clean reports what it freed.
+        if self.dry_run {
+            scanner = scanner.with_size_mode(self.config.options.size_mode);
+        }
+        if let Some(pool) = &self.thread_pool {
+            scanner = scanner.with_thread_pool(Arc::clone(pool));
+        }
+        if let Some(cancel) = &self.cancel {
+            scanner = scanner.with_cancel(Arc::clone(cancel));
+        }
+        Ok(scanner)
+    }
+
     /// Performs the cleaning operation on the specified path.
     ///
     /// This method will:
@@ -147,6 +303,8 @@ impl Cleaner {
     /// 2. If items are found, it will clean them in parallel.
     /// 3. Returns a `CleanReport` summarizing the operation.
     ///
+    /// This is [`Cleaner::plan`] followed by [`Cleaner::apply`].
+    ///
     /// # Arguments
     ///
     /// * `path` - The root path to start cleaning from. It must be a generic that
@@ -157,66 +315,324 @@ impl Cleaner {
     /// This function can return [`McError`] for issues like I/O errors during scanning,
     /// pattern compilation problems, or configuration loading failures.
     pub fn clean<P: AsRef<Path>>(&self, path: P) -> Result<CleanReport> {
+        let plan = self.plan(path)?;
+        self.apply(plan)
+    }
+
+    /// Scans `path` and returns the items a clean would delete, after nested items are
+    /// pruned and the planning rules and git policies are applied. Nothing is deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as the scanning part of [`Cleaner::clean`].
+    pub fn plan<P: AsRef<Path>>(&self, path: P) -> Result<Plan> {
         let path = path.as_ref();
+        let mut scanner = self.scanner(path)?;
 
-        // Create pattern matcher
-        let matcher = Arc::new(PatternMatcher::new(&self.config.patterns)?);
+        // Scan for items, with a live spinner unless quiet, or plain progress lines when
+        // stderr is not a terminal
+        let interactive = utils::progress::is_interactive();
+        let scan_display = (!self.quiet && self.progress.is_none() && interactive).then(|| {
+            Arc::new(CompactDisplay::new_for_scanning(Arc::new(
+                CategoryTracker::new(),
+            )))
+        });
+        let plain = (!self.quiet && self.progress.is_none() && !interactive)
+            .then(|| Arc::new(PlainProgress::new_for_scanning()));
+        let mut precount = None;
+        if let Some(progress) = &self.progress {
+            scanner = scanner.with_progress(Arc::clone(progress));
+        }
+        if let Some(plain) = &plain {
+            scanner = scanner.with_progress(Arc::clone(plain) as Arc<dyn Progress>);
+        }
+        if let Some(display) = &scan_display {
+            scanner = scanner
+                .with_category_tracker(display.get_tracker())
+                .with_scan_stats(display.get_scan_stats())
+                .with_progress(Arc::clone(display) as Arc<dyn Progress>);
+            if self.config.options.precount {
+                precount = Some(engine::PreCount::spawn(
+                    path.to_path_buf(),
+                    self.config.safety.max_depth,
+                    self.config.options.symlink_policy() == SymlinkPolicy::Follow,
+                    self.config.options.one_file_system,
+                    self.config.options.skip_traversal.clone(),
+                    display.get_scan_stats(),
+                ));
+            }
+        }
 
-        // Create scanner
-        let scanner = Scanner::new(path.to_path_buf(), matcher.clone())
-            .with_max_depth(self.config.safety.max_depth)
-            .with_symlinks(!self.config.options.preserve_symlinks);
+        let outcome = scanner.scan()?;
+        drop(precount);
+        if let Some(display) = scan_display {
+            display.force_update();
+            display.finish();
+        }
+        if let Some(plain) = plain {
+            plain.finish();
+        }
+        if outcome.stats.depth_limited_dirs > 0 {
+            log::warn!(
+                "Scan depth limit reached in {} places; artifacts below were not considered; \
+                 raise safety.max_depth",
+                outcome.stats.depth_limited_dirs
+            );
+        }
 
-        // Scan for items
-        if !self.quiet {
-            println!("🔍 Scanning for files to clean...");
+        // Prune nested items and apply planning rules
+        let prune_start = Instant::now();
+        let items = engine::plan(outcome.items, &self.config);
+        let enclosing = !self.config.safety.refuses_git_root();
+        let repos = safety::git::repos_for(path, outcome.git_repos, enclosing);
+        let policies = safety::git::GitPolicies::from_config(&self.config.safety.git)?;
+        let git = policies.apply(items, &repos);
+        if !git.warned.is_empty() {
+            log::warn!(
+                "Matched items are inside {} git repositories",
+                git.warned.len()
+            );
         }
 
-        let scan_start = std::time::Instant::now();
-        let (items, scan_errors, entries_scanned) = scanner.scan()?;
-        let scan_duration = scan_start.elapsed();
+        let timings = PhaseTimings {
+            scan: outcome.stats.duration,
+            prune: prune_start.elapsed(),
+            ..Default::default()
+        };
+        let mut items = git.items;
+        let mut skipped = git.skipped;
+        // There is no one to ask before deleting work that is not in git yet, so the
+        // items holding it are left alone unless the caller allows it.
+        if !self.allow_uncommitted {
+            let uncommitted = safety::git::uncommitted_files(&items, &repos);
+            if !uncommitted.is_empty() {
+                let (kept, held) = safety::git::hold_back_uncommitted(items, &uncommitted);
+                for item in &held {
+                    log::warn!(
+                        "Not cleaning {}: it holds files not committed to git",
+                        item.path.display()
+                    );
+                }
+                items = kept;
+                skipped.extend(held);
+            }
+        }
+        let mut scan_errors = outcome.errors;
+        if self.deterministic {
+            items.sort_by(|a, b| a.path.cmp(&b.path));
+            skipped.sort_by(|a, b| a.path.cmp(&b.path));
+            scan_errors.sort_by(|a, b| a.path().cmp(b.path()));
+        }
+        Ok(Plan {
+            root: path.to_path_buf(),
+            items,
+            skipped,
+            scan_errors,
+            scan_error_groups: outcome.error_groups,
+            scan_errors_omitted: outcome.errors_omitted,
+            scan: outcome.stats,
+            timings,
+            pattern_stats: outcome.pattern_stats,
+            estimated_sizes: outcome.estimated_sizes,
+        })
+    }
 
-        // Prune nested items to avoid redundant deletions
-        let items = prune_nested_items(items);
+    /// Cleans the items of `plan`, without scanning again, running the configured
+    /// snapshot, hooks and follow-up rules around the deletion.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as the cleaning part of [`Cleaner::clean`].
+    pub fn apply(&self, plan: Plan) -> Result<CleanReport> {
+        let Plan {
+            root,
+            items,
+            skipped,
+            scan_errors,
+            scan_error_groups,
+            scan_errors_omitted,
+            scan,
+            mut timings,
+            pattern_stats,
+            estimated_sizes,
+        } = plan;
+        let path = root.as_path();
 
+        // The kill switch may have been engaged since the plan was made.
+        let dry_run = self.dry_run || safety::kill_switch::engaged().is_some();
         if items.is_empty() {
             if !self.quiet {
                 println!("✅ No files to clean!");
             }
             return Ok(CleanReport {
+                skipped,
                 scan_errors,
-                scan_duration,
-                entries_scanned,
+                scan_error_groups,
+                scan_errors_omitted,
+                scan_duration: scan.duration,
+                timings,
+                entries_scanned: scan.entries_scanned,
+                depth_limited_dirs: scan.depth_limited_dirs,
+                scan_timed_out: scan.timed_out,
+                pattern_stats,
+                scan,
                 ..Default::default()
             });
         }
 
         // Create progress reporter
-        let progress = if self.quiet {
-            Arc::new(NoOpProgress) as Arc<dyn Progress>
-        } else {
-            Arc::new(ProgressReporter::new(items.len() as u64)) as Arc<dyn Progress>
+        let progress = match &self.progress {
+            Some(progress) => Arc::clone(progress),
+            None if self.quiet => Arc::new(NoOpProgress) as Arc<dyn Progress>,
+            None if !utils::progress::is_interactive() => {
+                Arc::new(PlainProgress::new(items.len() as u64)) as Arc<dyn Progress>
+            }
+            None => Arc::new(ProgressReporter::new(items.len() as u64)) as Arc<dyn Progress>,
+        };
+
+        let hook_ctx = hooks::HookContext {
+            root: path.to_path_buf(),
+            planned_bytes: items.iter().map(|i| i.size).sum(),
+            planned_items: items.len(),
         };
+        if self.config.options.secure_delete {
+            if let Some(reason) = engine::shred::limitation(path) {
+                log::warn!("Shredding may leave old contents recoverable: {}", reason);
+            }
+        }
+        if !dry_run && self.config.safety.snapshot_before {
+            let snapshot = snapshots::create(path).map_err(|err| {
+                McError::Safety(format!(
+                    "cannot snapshot {} before cleaning: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+            log::info!(
+                "Created snapshot {}; roll back with: {}",
+                snapshot.location,
+                snapshot.rollback_command(path)
+            );
+        }
+        if !dry_run {
+            hooks::run_pre_clean(&self.config.hooks, &hook_ctx)?;
+        }
+        let follow_ups = rules::follow_ups(&self.config.rules, &items);
 
-        // Create cleaner
-        let cleaner = ParallelCleaner::new()?
-            .with_threads(self.config.options.parallel_threads)?
-            .with_dry_run(self.dry_run)
+        let plugins = plugins::Plugins::from_config(&self.config.plugins)?;
+        let protection = self.protection(path)?;
+
+        // Create cleaner, on the caller's pool if one was given
+        let cleaner = engine::cleaner_for(&self.config)?;
+        let (cleaner, auto_threads) = match &self.thread_pool {
+            Some(pool) => (cleaner.with_thread_pool(Arc::clone(pool)), None),
+            None => {
+                let (threads, auto_threads) = self.config.options.parallel_threads.resolve(path);
+                (cleaner.with_threads(threads)?, auto_threads)
+            }
+        };
+        let mut cleaner = cleaner
+            .with_dry_run(dry_run)
             .with_quiet(self.quiet)
+            .with_protection(protection)
+            .with_deterministic(self.deterministic)
+            .with_estimated_sizes(estimated_sizes)
+            .with_plugins(plugins.clone())
             .with_progress(progress.clone());
+        if let Some(cancel) = &self.cancel {
+            cleaner = cleaner.with_cancel(Arc::clone(cancel));
+        }
 
         // Perform cleaning
+        let free_space_before = utils::fs::free_space(path);
         let mut report = cleaner.clean(items)?;
+        report.skipped.extend(skipped);
+        if !dry_run {
+            report.free_space_before = free_space_before;
+            report.free_space_after = utils::fs::free_space(path);
+        }
         report.scan_errors = scan_errors;
-        report.scan_duration = scan_duration;
-        report.entries_scanned = entries_scanned;
+        report.scan_error_groups = scan_error_groups;
+        report.scan_errors_omitted = scan_errors_omitted;
+        report.scan_duration = scan.duration;
+        timings.clean = report.timings.clean;
+        report.timings = timings;
+        report.entries_scanned = scan.entries_scanned;
+        report.depth_limited_dirs = scan.depth_limited_dirs;
+        report.scan_timed_out = scan.timed_out;
+        report.scan = scan;
+        report.pattern_stats = pattern_stats;
+        report.auto_threads = auto_threads;
+        if self.deterministic {
+            report.sort_by_path();
+        }
 
         // Finish progress
         progress.finish();
 
+        if !dry_run {
+            rules::run_follow_ups(&follow_ups);
+            hooks::run_post_clean(&self.config.hooks, &hook_ctx, &report)?;
+            if let Some(plugins) = &plugins {
+                plugins.report(&report);
+            }
+        }
+
         Ok(report)
     }
 
+    /// Renders `plan` as a shell script for an operator to run, as
+    /// [`engine::script::shell_script`] does, leaving out the directories that hold
+    /// paths `[safety] partial_delete` keeps.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern in the configuration is invalid.
+    pub fn shell_script(&self, plan: &Plan) -> Result<String> {
+        let protection = self.protection(&plan.root)?;
+        Ok(engine::script::shell_script(plan, protection.as_deref()))
+    }
+
+    /// Returns the matcher for the paths kept inside matched directories, with
+    /// `[safety] partial_delete`.
+    fn protection(&self, root: &Path) -> Result<Option<Arc<PatternMatcher>>> {
+        if !self.config.safety.partial_delete {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(
+            PatternMatcher::from_config(&self.config)?.with_root(root)?,
+        )))
+    }
+
+    /// Cleans several roots one after another and returns their merged report.
+    ///
+    /// Each root is scanned and cleaned as by [`Cleaner::clean`], and appears as a
+    /// section in the report's `roots`; the other fields hold the totals. A root that
+    /// fails does not stop the others: it is left out of `roots`, and its error is
+    /// added to the report's `errors` as [`CleanError::RootFailed`].
+    ///
+    /// # Errors
+    ///
+    /// Errors of single roots are reported in the merged report rather than returned,
+    /// so this does not currently fail.
+    pub fn clean_all<P: AsRef<Path>>(&self, roots: &[P]) -> Result<CleanReport> {
+        let mut merged = CleanReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        for root in roots {
+            let root = root.as_ref();
+            match self.clean(root) {
+                Ok(report) => merged.merge(root, &report),
+                Err(err) => merged.errors.push(CleanError::RootFailed {
+                    root: root.to_path_buf(),
+                    message: err.to_string(),
+                }),
+            }
+        }
+        Ok(merged)
+    }
+
     /// Performs a dry run of the cleaning operation.
     ///
     /// This is a convenience method that is equivalent to calling `with_dry_run(true)`
@@ -263,6 +679,7 @@ mod tests {
         assert!(report.dry_run);
         assert_eq!(report.items_deleted, 3);
         assert!(report.bytes_freed > 0);
+        assert!(report.space_reclaimed().is_none());
 
         // Verify that files still exist
         temp.child("node_modules")
@@ -282,6 +699,11 @@ mod tests {
         assert!(!report.dry_run);
         assert_eq!(report.items_deleted, 3);
         assert!(report.bytes_freed > 0);
+        #[cfg(unix)]
+        assert!(report.space_reclaimed().is_some());
+        assert_eq!(report.timings.scan, report.scan_duration);
+        assert_eq!(report.timings.clean, report.duration);
+        assert!(report.timings.confirmation.is_zero());
 
         // Verify that files are deleted
         temp.child("node_modules")
@@ -289,4 +711,128 @@ mod tests {
         temp.child("target").assert(predicates::path::missing());
         temp.child("app.log").assert(predicates::path::missing());
     }
+
+    #[test]
+    fn test_progress_follows_the_scan_then_the_clean() {
+        /// Records the phases, visited directories and handled items a clean reports.
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+
+        impl Progress for Recorder {
+            fn increment(&self, _delta: u64) {}
+            fn set_message(&self, _msg: &str) {}
+            fn finish(&self) {}
+            fn begin_phase(&self, name: &str, _total: Option<u64>) {
+                self.0.lock().unwrap().push(name.to_string());
+            }
+            fn item_completed(&self, _item: &CleanItem) {
+                self.0.lock().unwrap().push("item".to_string());
+            }
+            fn dir_visited(&self, _path: &Path) {
+                self.0.lock().unwrap().push("dir".to_string());
+            }
+        }
+
+        let temp = setup_test_dir();
+        let recorder = Arc::new(Recorder::default());
+        Cleaner::new(Config::default())
+            .with_progress(Arc::clone(&recorder) as Arc<dyn Progress>)
+            .clean(temp.path())
+            .unwrap();
+
+        let events = recorder.0.lock().unwrap();
+        let clean = events.iter().position(|e| e == "clean").unwrap();
+        let (scan, clean) = (&events[..clean], &events[clean + 1..]);
+        let count = |events: &[String], name: &str| events.iter().filter(|e| *e == name).count();
+        assert_eq!(scan[0], "scan");
+        assert!(count(scan, "dir") > 0, "{:?}", scan);
+        assert_eq!(count(scan, "item"), 3);
+        assert_eq!(clean, ["item", "item", "item"]);
+    }
+
+    #[test]
+    fn test_reports_count_the_entries_scanned() {
+        let cleaner = Cleaner::new(Config::default())
+            .with_dry_run(true)
+            .with_quiet(true);
+        let found = setup_test_dir();
+        let empty = TempDir::new().unwrap();
+        empty.child("src/main.rs").touch().unwrap();
+
+        for temp in [&found, &empty] {
+            let report = cleaner.clean(temp.path()).unwrap();
+            assert!(report.entries_scanned > 0);
+            assert_eq!(report.entries_scanned, report.scan.entries_scanned);
+        }
+        let plan = cleaner.plan(empty.path()).unwrap();
+        assert!(plan.items.is_empty());
+        assert_eq!(plan.scan.entries_scanned, 3);
+    }
+
+    #[test]
+    fn test_clean_all_reports_each_root() {
+        let first = setup_test_dir();
+        let second = setup_test_dir();
+        let cleaner = Cleaner::new(Config::default()).with_dry_run(true);
+
+        let report = cleaner.clean_all(&[first.path(), second.path()]).unwrap();
+
+        assert_eq!(report.roots.len(), 2);
+        assert_eq!(report.roots[0].root, first.path());
+        assert_eq!(report.items_deleted, 6);
+        assert_eq!(
+            report.bytes_freed,
+            report
+                .roots
+                .iter()
+                .map(|root| root.bytes_freed)
+                .sum::<u64>()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_clean_all_goes_on_after_a_failing_root() {
+        let failing = setup_test_dir();
+        failing.child("fail").touch().unwrap();
+        let second = setup_test_dir();
+        let mut config = Config::default();
+        config.hooks.pre_clean = Some(r#"test ! -e "$MC_ROOT/fail""#.to_string());
+        let cleaner = Cleaner::new(config).with_quiet(true);
+
+        let report = cleaner.clean_all(&[failing.path(), second.path()]).unwrap();
+
+        assert_eq!(report.roots.len(), 1);
+        assert_eq!(report.roots[0].root, second.path());
+        assert!(matches!(
+            &report.errors[..],
+            [CleanError::RootFailed { root, .. }] if root == failing.path()
+        ));
+        failing.child("app.log").assert(predicates::path::exists());
+        second.child("app.log").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn test_scoped_excludes_leave_kept_paths_out_of_the_totals() {
+        let temp = TempDir::new().unwrap();
+        temp.child("target/app").write_binary(&[0; 100]).unwrap();
+        temp.child("target/keep/cache")
+            .write_binary(&[0; 1000])
+            .unwrap();
+        let mut config = Config::default();
+        config.patterns.scoped_exclude =
+            BTreeMap::from([("target".to_string(), vec!["keep".to_string()])]);
+        let cleaner = Cleaner::new(config).with_quiet(true);
+
+        let plan = cleaner.dry_run(temp.path()).unwrap();
+        let report = cleaner.clean(temp.path()).unwrap();
+
+        for report in [plan, report] {
+            assert_eq!(report.items_deleted, 1);
+            assert_eq!(report.bytes_freed, 100);
+        }
+        temp.child("target/app").assert(predicates::path::missing());
+        temp.child("target/keep/cache")
+            .assert(predicates::path::exists());
+    }
 }
@@ -18,19 +18,35 @@
 
 use clap::Parser;
 use colored::*;
-use humansize::{format_size, DECIMAL};
-use std::io::{self, Write};
+use mc::utils::format;
+use std::collections::HashSet;
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use mc::{
-    cli::{Cli, Commands},
-    config::Config,
-    engine::{ParallelCleaner, Scanner},
+    cli::{Cli, Commands, ConfigCommand, PatternsCommand, PlanOutput, SnapshotsCommand},
+    config::{
+        import::{Import, ImportFormat},
+        Config, IncludeKind, Provenance,
+    },
+    daemon::{serve_metrics, Metrics},
+    engine::{archive, cleaner_for, quarantine, scanner_for},
+    orchestrator::{Fleet, FleetConfig},
     patterns::PatternMatcher,
-    safety::SafetyGuard,
-    utils::{CategoryTracker, CompactDisplay, NoOpProgress, Progress},
-    Result,
+    remote::{RemoteOptions, RemoteSession, RemoteTarget},
+    resume::Checkpoint,
+    retry::{elevate, RetryManifest},
+    safety::{git, SafetyGuard},
+    utils::{
+        fs as fs_utils, CategoryTracker, CompactDisplay, MultiRootProgress, NoOpProgress,
+        PlainProgress, Progress, RootProgress,
+    },
+    ReportFormat, Result,
 };
 
 /// The main entry point for the `mc` command-line application.
@@ -91,12 +107,26 @@ fn apply_color_settings(no_color: bool) {
 /// Returns `Ok(())` on success. If an error occurs, it is propagated up to `main`
 /// for handling. The specific error types are defined in `mc::McError`.
 fn run() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
     // Initialize logger and color settings
     init_logger(cli.verbose, cli.quiet);
     apply_color_settings(cli.no_color);
-    let effective_quiet = cli.quiet || cli.json;
+    if cli.binary {
+        format::set_units(format::SizeUnits::Binary);
+    }
+
+    if let Some(reason) = mc::safety::kill_switch::engaged() {
+        if !cli.dry_run && !cli.quiet {
+            eprintln!(
+                "{} Deleting is disabled ({}); running as a dry run",
+                "⚠".yellow(),
+                reason
+            );
+        }
+        cli.dry_run = true;
+    }
+    let effective_quiet = cli.quiet || cli.machine_report();
 
     // Handle subcommands
     if let Some(command) = &cli.command {
@@ -106,71 +136,653 @@ fn run() -> Result<()> {
     // Load configuration
     let mut config = Config::load(cli.config.as_ref())?;
 
-    // Merge CLI arguments
-    config.merge_cli_args(cli.exclude, cli.include, cli.preserve_env);
+    // Merge CLI arguments and override config with CLI flags
+    apply_cli_overrides(&mut config, &cli, None);
 
-    // Override config with CLI flags
-    if cli.no_git_check {
-        config.safety.check_git_repo = false;
+    // Validate configuration
+    config.validate();
+    log::debug!("Configuration loaded: {:?}", config);
+    let plugins = mc::plugins::Plugins::from_config(&config.plugins)?;
+
+    let mut runs = Vec::new();
+    let mut failed_roots = Vec::new();
+    // Several roots get a bar each, kept above an overall bar, shown while they scan
+    // and clean.
+    let multi =
+        (!cli.more_paths.is_empty() && !effective_quiet && mc::utils::progress::is_interactive())
+            .then(|| MultiRootProgress::new(1 + cli.more_paths.len()));
+    if let Some(multi) = &multi {
+        multi.hide();
+    }
+    for root in std::iter::once(&cli.path).chain(&cli.more_paths) {
+        if !cli.more_paths.is_empty() && !effective_quiet {
+            println!(
+                "\n{} {}",
+                "▸".bright_cyan(),
+                root.display().to_string().bold()
+            );
+        }
+        let bar = multi
+            .as_ref()
+            .map(|multi| (multi, Arc::new(multi.add_root(&root.display().to_string()))));
+        let result = clean_root(
+            &cli,
+            &config,
+            plugins.as_ref(),
+            root,
+            bar.as_ref().map(|(multi, bar)| (*multi, bar)),
+            None,
+        );
+        if let Some((multi, bar)) = &bar {
+            bar.finish();
+            multi.hide();
+        }
+        match result {
+            Ok(Some(run)) => runs.push(run),
+            Ok(None) => {}
+            // One failing root does not stop the others, or lose what they did.
+            Err(err) if !cli.more_paths.is_empty() => {
+                eprintln!("{} {}: {}", "Error:".red().bold(), root.display(), err);
+                failed_roots.push(mc::CleanError::RootFailed {
+                    root: root.clone(),
+                    message: err.to_string(),
+                });
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if let Some(multi) = multi {
+        multi.finish();
     }
 
-    if let Some(threads) = cli.parallel {
-        config.options.parallel_threads = threads;
+    // Show results
+    let merged;
+    let report = match runs.as_slice() {
+        // Scripts reading the summary line get one even when nothing was found.
+        [] if failed_roots.is_empty() && cli.report_format() == ReportFormat::Line => {
+            return print_report(&cli, &mc::CleanReport::default(), false, false);
+        }
+        [] if failed_roots.is_empty() => return Ok(()),
+        [run] if cli.more_paths.is_empty() => &run.report,
+        _ => {
+            let mut report = mc::CleanReport {
+                dry_run: cli.dry_run,
+                errors: failed_roots.clone(),
+                ..Default::default()
+            };
+            for run in &runs {
+                report.merge(&run.path, &run.report);
+            }
+            report.largest_items.truncate(config.options.largest_items);
+            merged = report;
+            &merged
+        }
+    };
+    let human = cli.stats || config.options.show_statistics || !effective_quiet;
+    print_report(&cli, report, human, cli.stats)?;
+
+    if !cli.dry_run {
+        // Runs without items only carry skipped matches; nothing was cleaned.
+        for run in runs.iter().filter(|run| !run.items.is_empty()) {
+            after_clean(&config, run, effective_quiet)?;
+        }
+        if let Some(plugins) = &plugins {
+            plugins.report(report);
+        }
     }
 
-    // Validate configuration
+    if !failed_roots.is_empty() {
+        return Err(mc::McError::RootsFailed(failed_roots.len()));
+    }
+    Ok(())
+}
+
+/// Prints the plan for the target path for `mc plan`.
+fn run_plan(cli: &Cli, output: PlanOutput) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    let path = cli.path.canonicalize()?;
+    SafetyGuard::new(
+        config.safety.refuses_git_root(),
+        config.safety.max_depth,
+        config.safety.min_free_space_gb,
+    )
+    .validate(&path)?;
+
+    let cleaner = mc::Cleaner::new(config)
+        .with_quiet(true)
+        .with_deterministic(cli.deterministic)
+        .with_allow_uncommitted(cli.allow_uncommitted);
+    let plan = cleaner.plan(&path)?;
+    match output {
+        PlanOutput::Text => {
+            for item in &plan.items {
+                println!("{} ({})", item.path.display(), format::size(item.size));
+            }
+            let total: u64 = plan.items.iter().map(|item| item.size).sum();
+            let count = format::count(plan.items.len() as u64);
+            println!("\n{} items, {}", count, format::size(total));
+        }
+        PlanOutput::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+        PlanOutput::Sh => print!("{}", cleaner.shell_script(&plan)?),
+    }
+    Ok(())
+}
+
+/// Lists the projects below the target path and what a clean would free in each, for
+/// `mc projects`.
+fn run_projects(cli: &Cli, json: bool) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    let path = cli.path.canonicalize()?;
+
+    let plan = mc::Cleaner::new(config.clone())
+        .with_quiet(true)
+        .with_allow_uncommitted(true)
+        .plan(&path)?;
+    let projects = mc::projects::survey(&path, &config, &plan.items)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&projects)?);
+    } else if projects.is_empty() {
+        println!("No projects found under {}", path.display());
+    } else {
+        print!("{}", mc::projects::render_table(&projects));
+    }
+    Ok(())
+}
+
+/// Ranks the items a clean would delete and prints the suggested ones as a plan, for
+/// `mc recommend`, cleaning them with `--apply`.
+fn run_recommend(cli: &Cli, apply: bool, json: bool) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    let path = cli.path.canonicalize()?;
+    SafetyGuard::new(
+        config.safety.refuses_git_root(),
+        config.safety.max_depth,
+        config.safety.min_free_space_gb,
+    )
+    .validate(&path)?;
+
+    let quiet = cli.quiet || cli.machine_report();
+    let confirm = apply && !cli.yes && !cli.dry_run && config.options.require_confirmation;
+    // Uncommitted work is confirmed by the clean that applies the suggestions.
+    let mut plan = mc::Cleaner::new(config.clone())
+        .with_quiet(true)
+        .with_deterministic(cli.deterministic)
+        .with_allow_uncommitted(true)
+        .plan(&path)?;
+    let ranked = plan.recommend();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&ranked)?);
+        return Ok(());
+    }
+
+    // What a prompt asks about is listed even with --quiet.
+    if !quiet || confirm {
+        if ranked.is_empty() {
+            println!("No files to clean under {}", path.display());
+            return Ok(());
+        }
+        let (suggested, kept): (Vec<_>, Vec<_>) = ranked.into_iter().partition(|r| r.suggested);
+        let total: u64 = suggested.iter().map(|r| r.item.size).sum();
+        println!(
+            "{} Suggested plan: {} of {} items, {}",
+            "💡".bright_yellow(),
+            format::count(suggested.len() as u64),
+            format::count((suggested.len() + kept.len()) as u64),
+            format::size(total).bright_yellow()
+        );
+        if !suggested.is_empty() {
+            print!("\n{}", mc::engine::recommend::render_table(&suggested));
+        }
+        if !kept.is_empty() {
+            println!("\nNot suggested, as recently used, small or costly to rebuild:");
+            print!("{}", mc::engine::recommend::render_table(&kept));
+        }
+        if !apply && !suggested.is_empty() {
+            let path = format::shell_quote(&path.display().to_string());
+            println!(
+                "\nRun `mc {} recommend --apply` to clean the suggested items.",
+                path
+            );
+        }
+    }
+    if !apply || plan.items.is_empty() {
+        return Ok(());
+    }
+
+    // The suggested items are cleaned as `mc <path>` would clean them, with its
+    // confirmations, checkpoint and retry manifest.
+    let only: HashSet<PathBuf> = plan.items.into_iter().map(|item| item.path).collect();
+    let plugins = mc::plugins::Plugins::from_config(&config.plugins)?;
+    let Some(run) = clean_root(cli, &config, plugins.as_ref(), &path, None, Some(&only))? else {
+        return Ok(());
+    };
+    print_report(cli, &run.report, cli.stats || !quiet, cli.stats)?;
+    if !cli.dry_run && !run.items.is_empty() {
+        after_clean(&config, &run, quiet)?;
+        if let Some(plugins) = &plugins {
+            plugins.report(&run.report);
+        }
+    }
+    Ok(())
+}
+
+/// Saves the retry manifest of `run`, a real clean, and runs the follow-up rules and
+/// post-clean hooks for it.
+fn after_clean(config: &Config, run: &RootRun, quiet: bool) -> Result<()> {
+    save_retry_manifest(&run.path, &run.items, &run.report, quiet);
+    mc::rules::run_follow_ups(&mc::rules::follow_ups(&config.rules, &run.items));
+    mc::hooks::run_post_clean(&config.hooks, &run.hook_ctx, &run.report)
+}
+
+/// Lists the items a clean of the target path would delete, with when each was last
+/// used, planned as `clean --dry-run` plans them.
+fn run_list(cli: &Cli, json: bool) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
     config.validate();
-    log::debug!("Configuration loaded: {:?}", config);
+    let path = cli.path.canonicalize()?;
+
+    let plan = mc::Cleaner::new(config)
+        .with_quiet(true)
+        .with_deterministic(cli.deterministic)
+        .with_allow_uncommitted(true)
+        .plan(&path)?;
+
+    if json {
+        let items: Vec<JsonListedItem> = plan
+            .items
+            .iter()
+            .map(|item| JsonListedItem {
+                item,
+                unused_days: mc::utils::fs::unused_days(&item.path),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&items)?);
+    } else {
+        for item in plan.items {
+            match mc::utils::fs::unused_days(&item.path) {
+                Some(days) => println!(
+                    "{} ({}, last used {} days ago)",
+                    item.path.display(),
+                    format::size(item.size),
+                    days
+                ),
+                None => println!("{} ({})", item.path.display(), format::size(item.size)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shows what the quarantine holds against `quarantine.max_total`, and what the
+/// archive directory holds, for `mc stats`.
+fn run_stats(cli: &Cli, json: bool) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    let quarantine = match &config.quarantine.dir {
+        Some(dir) => Some((dir, quarantine::usage(dir)?)),
+        None => None,
+    };
+    let archive = match &config.archive.dir {
+        Some(dir) => Some((dir, archive::usage(dir)?)),
+        None => None,
+    };
+    let max_total = config.quarantine.max_total.map(|max| max.bytes());
+
+    if json {
+        let stats = JsonStats {
+            quarantine: quarantine.as_ref().map(|(dir, usage)| JsonQuarantineStats {
+                dir: dir.to_path_buf(),
+                bytes: usage.total_bytes(),
+                max_total,
+                batches: usage
+                    .batches
+                    .iter()
+                    .map(|batch| JsonQuarantineBatch {
+                        path: batch.path.clone(),
+                        bytes: batch.bytes,
+                        age_days: age_days(batch.created),
+                    })
+                    .collect(),
+            }),
+            archive: archive
+                .as_ref()
+                .map(|(dir, (archives, bytes))| JsonArchiveStats {
+                    dir: dir.to_path_buf(),
+                    archives: *archives,
+                    bytes: *bytes,
+                }),
+        };
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    if quarantine.is_none() && archive.is_none() {
+        println!("Neither quarantine.dir nor archive.dir is set; mc keeps nothing it cleans.");
+    }
+    if let Some((dir, usage)) = &quarantine {
+        let limit = match max_total {
+            Some(max) => format!(" of {}", format::size(max)),
+            None => String::new(),
+        };
+        println!(
+            "{} {}: {} batches, {}{}",
+            "Quarantine".bold(),
+            dir.display(),
+            usage.batches.len(),
+            format::size(usage.total_bytes()).bright_yellow(),
+            limit
+        );
+        for batch in &usage.batches {
+            println!(
+                "  {} {} ({} days old)",
+                batch.path.display(),
+                format::size(batch.bytes),
+                age_days(batch.created)
+            );
+        }
+    }
+    if let Some((dir, (archives, bytes))) = &archive {
+        println!(
+            "{} {}: {} archives, {}",
+            "Archives".bold(),
+            dir.display(),
+            archives,
+            format::size(*bytes).bright_yellow()
+        );
+    }
+    Ok(())
+}
+
+/// Prints what getting the items back takes, per pattern with a note under
+/// `[patterns.rebuild]`, so the cost of a clean is known before confirming it.
+fn print_rebuild_notes(items: &[mc::CleanItem]) {
+    let mut notes: std::collections::BTreeMap<(&str, &str), (u64, u64)> = Default::default();
+    for item in items {
+        if let Some(note) = &item.pattern.rebuild {
+            let entry = notes
+                .entry((item.pattern.pattern.as_str(), &**note))
+                .or_default();
+            entry.0 += 1;
+            entry.1 += item.size;
+        }
+    }
+    for ((pattern, note), (count, bytes)) in notes {
+        println!(
+            "  {} {} ({} items, {}): {}",
+            "↳ Rebuild".dimmed(),
+            pattern,
+            format::count(count),
+            format::size(bytes),
+            note
+        );
+    }
+}
+
+/// Returns the whole days since `time`.
+fn age_days(time: std::time::SystemTime) -> u64 {
+    time.elapsed().map_or(0, |age| age.as_secs() / 86_400)
+}
+
+/// Translates another cleaner's settings for `mc import` and writes them to the
+/// configuration file, or only prints them with `--dry-run`.
+fn run_import(cli: &Cli, from: ImportFormat, command: &[String]) -> Result<()> {
+    let import = if command.is_empty() {
+        let mut script = String::new();
+        io::stdin().read_to_string(&mut script)?;
+        Import::from_script(from, &script)?
+    } else {
+        Import::from_args(from, command)
+    };
+    for note in &import.notes {
+        eprintln!("{} {}", "⚠".yellow(), note);
+    }
+    if import.is_empty() {
+        println!("Nothing to import from {}", from);
+        return Ok(());
+    }
+
+    for (key, value) in &import.settings {
+        println!("  {} = {}", key.bright_cyan(), value);
+    }
+    if !import.excludes.is_empty() {
+        let excludes = toml::Value::from(import.excludes.clone());
+        println!("  {} += {}", "patterns.exclude".bright_cyan(), excludes);
+    }
+    if cli.dry_run {
+        println!("\nDry run: nothing was written");
+        return Ok(());
+    }
+    let path = match &cli.config {
+        Some(path) => path.clone(),
+        None => std::env::current_dir()?.join(".mc.toml"),
+    };
+    import.write_to(&path)?;
+    println!("\nImported the {} settings into {}", from, path.display());
+    Ok(())
+}
+
+/// The outcome of cleaning one root, kept until the report has been shown.
+struct RootRun {
+    /// The canonical root path.
+    path: PathBuf,
+    /// The planned items, used for the retry manifest and rule follow-ups.
+    items: Vec<mc::CleanItem>,
+    /// What the hooks are told about the root.
+    hook_ctx: mc::hooks::HookContext,
+    /// The report for the root.
+    report: mc::CleanReport,
+}
+
+/// Scans and cleans a single root. Returns `None` if there was nothing to clean or
+/// the user cancelled, unless `--include-skipped` found matches that were left alone.
+///
+/// One of several roots reports to `bar`, shown in `multi` only while it scans and
+/// cleans. With `only`, items at other paths are left out, as for
+/// `mc recommend --apply`.
+fn clean_root(
+    cli: &Cli,
+    config: &Config,
+    plugins: Option<&Arc<mc::plugins::Plugins>>,
+    root: &Path,
+    bar: Option<(&MultiRootProgress, &Arc<RootProgress>)>,
+    only: Option<&HashSet<PathBuf>>,
+) -> Result<Option<RootRun>> {
+    let effective_quiet = cli.quiet || cli.machine_report();
+    let listing_limit = cli.listing_limit();
 
     // Validate path
-    let path = cli.path.canonicalize().map_err(mc::McError::Io)?;
+    let path = root.canonicalize().map_err(mc::McError::Io)?;
 
     // Safety checks (always run — git check respects config, disk space is unconditional)
     let guard = SafetyGuard::new(
-        config.safety.check_git_repo,
+        config.safety.refuses_git_root(),
         config.safety.max_depth,
         config.safety.min_free_space_gb,
     );
     guard.validate(&path)?;
     log::debug!("Safety checks passed for {}", path.display());
 
+    if !effective_quiet && Checkpoint::path_for(&path).exists() {
+        println!(
+            "{} An earlier clean of {} was interrupted; {} finishes it without scanning",
+            "↻".yellow(),
+            path.display(),
+            "mc resume".bright_cyan()
+        );
+    }
+
     // Create pattern matcher
-    let matcher = Arc::new(PatternMatcher::new(&config.patterns)?);
+    let matcher = Arc::new(
+        PatternMatcher::from_config(config)?
+            .with_root(&path)?
+            .with_plugins(plugins.cloned()),
+    );
 
-    // Create category tracker and compact display for scanning
+    // Create category tracker and compact display for scanning, or plain progress lines
+    // when stderr cannot show it
+    let error_limit = (!cli.show_errors).then_some(mc::engine::scanner::DEFAULT_ERROR_LIMIT);
+    // Only a dry run's sizes may be estimated; a clean reports what it freed.
+    let size_mode = if cli.dry_run {
+        config.options.size_mode
+    } else {
+        mc::SizeMode::Exact
+    };
     let category_tracker = Arc::new(CategoryTracker::new());
-    let scan_start = std::time::Instant::now();
-    let (items, scan_errors, entries_scanned) = if !effective_quiet {
-        let display = CompactDisplay::new_for_scanning(Arc::clone(&category_tracker));
+    let outcome = if bar.is_none() && !effective_quiet && mc::utils::progress::is_interactive() {
+        let display = Arc::new(CompactDisplay::new_for_scanning(Arc::clone(
+            &category_tracker,
+        )));
         let scan_stats = display.get_scan_stats();
+        let precount = config.options.precount.then(|| {
+            mc::engine::PreCount::spawn(
+                path.clone(),
+                config.safety.max_depth,
+                config.options.symlink_policy() == mc::SymlinkPolicy::Follow,
+                config.options.one_file_system,
+                config.options.skip_traversal.clone(),
+                Arc::clone(&scan_stats),
+            )
+        });
 
-        let scanner = Scanner::new(path.clone(), matcher)
-            .with_max_depth(config.safety.max_depth)
-            .with_symlinks(!config.options.preserve_symlinks)
+        let scanner = scanner_for(config, path.clone(), Arc::clone(&matcher))?
+            .with_error_limit(error_limit)
+            .with_include_skipped(cli.include_skipped)
+            .with_timeout(cli.scan_timeout)
+            .with_size_mode(size_mode)
             .with_category_tracker(Arc::clone(&category_tracker))
-            .with_scan_stats(scan_stats);
+            .with_scan_stats(scan_stats)
+            .with_progress(Arc::clone(&display) as Arc<dyn mc::Progress>);
 
         let result = scanner.scan()?;
+        drop(precount);
         display.force_update();
         display.finish();
         result
     } else {
-        let scanner = Scanner::new(path.clone(), matcher)
-            .with_max_depth(config.safety.max_depth)
-            .with_symlinks(!config.options.preserve_symlinks);
-        scanner.scan()?
+        let mut scanner = scanner_for(config, path.clone(), Arc::clone(&matcher))?
+            .with_error_limit(error_limit)
+            .with_include_skipped(cli.include_skipped)
+            .with_timeout(cli.scan_timeout)
+            .with_size_mode(size_mode);
+        let plain = (bar.is_none() && !effective_quiet)
+            .then(|| Arc::new(PlainProgress::new_for_scanning()));
+        if let Some(plain) = &plain {
+            scanner = scanner.with_progress(Arc::clone(plain) as Arc<dyn mc::Progress>);
+        }
+        if let Some((multi, bar)) = bar {
+            scanner = scanner.with_progress(Arc::clone(bar) as Arc<dyn mc::Progress>);
+            multi.show();
+        }
+        let result = scanner.scan();
+        if let Some((multi, _)) = bar {
+            multi.hide();
+        }
+        let result = result?;
+        if let Some(plain) = plain {
+            plain.finish();
+        }
+        result
+    };
+    let scan_errors = outcome.errors;
+    let scan_error_groups = outcome.error_groups;
+    let scan_errors_omitted = outcome.errors_omitted;
+    let scan_duration = outcome.stats.duration;
+    let entries_scanned = outcome.stats.entries_scanned;
+    let depth_limited_dirs = outcome.stats.depth_limited_dirs;
+    let scan_timed_out = outcome.stats.timed_out;
+    let pattern_stats = outcome.pattern_stats;
+    let scan_summary = outcome.stats;
+    let mut skipped = outcome.skipped;
+    let estimated_sizes = outcome.estimated_sizes;
+
+    // Prune nested items and apply planning rules
+    let prune_start = Instant::now();
+    let (items, plan_skipped) = mc::engine::plan_with_skipped(outcome.items, config);
+    let repos = git::repos_for(&path, outcome.git_repos, !config.safety.refuses_git_root());
+    let git = git::GitPolicies::from_config(&config.safety.git)?.apply(items, &repos);
+    let mut items = git.items;
+    if let Some(only) = only {
+        items.retain(|item| only.contains(&item.path));
+    }
+    if cli.include_skipped {
+        skipped.extend(plan_skipped);
+        skipped.extend(git.skipped);
+    }
+    let mut timings = mc::PhaseTimings {
+        scan: scan_duration,
+        prune: prune_start.elapsed(),
+        ..Default::default()
     };
-    let scan_duration = scan_start.elapsed();
+    if !git.warned.is_empty() && !effective_quiet {
+        print_git_warning(&git.warned);
+    }
+    log::info!(
+        "Scan complete: {} items found in {:.2}s",
+        items.len(),
+        scan_duration.as_secs_f64()
+    );
+
+    if depth_limited_dirs > 0 && !effective_quiet {
+        println!(
+            "\n{} Scan depth limit reached in {} places; artifacts below were not considered; \
+             raise safety.max_depth (now {}) or pass --max-depth",
+            "⚠".yellow(),
+            depth_limited_dirs,
+            config.safety.max_depth
+        );
+    }
 
-    // Prune nested items to avoid redundant deletions
-    let items = mc::prune_nested_items(items);
-    log::info!("Scan complete: {} items found in {:.2}s", items.len(), scan_duration.as_secs_f64());
+    if scan_timed_out && !effective_quiet {
+        println!(
+            "\n{} Scan timeout reached after {:.0}s; only the part of {} scanned so far is \
+             considered",
+            "⚠".yellow(),
+            scan_duration.as_secs_f64(),
+            path.display()
+        );
+    }
 
     if items.is_empty() {
         if !effective_quiet {
             println!("\nNo files to clean!");
         }
-        return Ok(());
+        // Matches that were all left alone still need explaining.
+        if cli.include_skipped && !skipped.is_empty() {
+            let mut report = mc::CleanReport {
+                dry_run: cli.dry_run,
+                scan_errors,
+                scan_error_groups,
+                scan_errors_omitted,
+                scan_duration,
+                timings,
+                entries_scanned,
+                scan: scan_summary,
+                depth_limited_dirs,
+                scan_timed_out,
+                pattern_stats,
+                skipped,
+                ..Default::default()
+            };
+            if cli.deterministic {
+                report.sort_by_path();
+            }
+            let hook_ctx = mc::hooks::HookContext {
+                root: path.clone(),
+                planned_bytes: 0,
+                planned_items: 0,
+            };
+            return Ok(Some(RootRun {
+                path,
+                items,
+                hook_ctx,
+                report,
+            }));
+        }
+        return Ok(None);
     }
 
     // Calculate total size and count files/dirs
@@ -202,49 +814,120 @@ fn run() -> Result<()> {
         println!(
             "{} {} entries in {:.2}s ({:.0}/s)",
             "Scanned".dimmed(),
-            entries_scanned.to_string().dimmed(),
+            format::count(entries_scanned as u64).dimmed(),
             scan_secs,
             scan_rate
         );
 
         // Show found items breakdown
         println!(
-            "\n{} {} ({} dirs, {} files) • {}",
+            "\n{} {} ({} dirs, {} files) • {}{}",
             "Found".dimmed(),
-            items.len().to_string().bright_white(),
-            dir_count.to_string().bright_cyan(),
-            file_count.to_string().bright_cyan(),
-            format_size(total_size, DECIMAL).bright_green()
+            format::count(items.len() as u64).bright_white(),
+            format::count(dir_count as u64).bright_cyan(),
+            format::count(file_count as u64).bright_cyan(),
+            if estimated_sizes.is_empty() {
+                ""
+            } else {
+                "≈"
+            },
+            format::size(total_size).bright_green()
         );
 
         // Show category breakdown
         if pruned_category_tracker.total_count() > 0 {
             println!("  {}", pruned_category_tracker.format_breakdown());
         }
+        print_rebuild_notes(&items);
 
         println!();
     }
 
-    // Confirmation prompt (unless --yes or dry-run)
-    if !cli.yes && !cli.dry_run && config.options.require_confirmation {
-        print!("\nProceed with cleaning? [y/N]: ");
-        io::stdout().flush()?;
+    if config.options.secure_delete && !effective_quiet {
+        if let Some(reason) = mc::engine::shred::limitation(&path) {
+            println!(
+                "{} Shredding may leave old contents recoverable here: {}",
+                "⚠".yellow(),
+                reason
+            );
+        }
+    }
+
+    // Work that is not in git yet is confirmed separately, even with --yes.
+    if !cli.dry_run && !cli.allow_uncommitted {
+        let uncommitted = git::uncommitted_files(&items, &repos);
+        if !uncommitted.is_empty() {
+            let wait_start = Instant::now();
+            let confirmed = confirm_uncommitted(&uncommitted)?;
+            timings.confirmation += wait_start.elapsed();
+            if !confirmed {
+                eprintln!("Cleaning cancelled");
+                return Ok(None);
+            }
+        }
+    }
+
+    // Confirmation prompt (unless --yes, dry-run or a clean below auto_yes_under)
+    let mut confirm = !cli.yes && !cli.dry_run && config.options.require_confirmation;
+    if let Some(limit) = config.options.auto_yes_under {
+        if confirm && total_size < limit.bytes() {
+            confirm = false;
+            if !effective_quiet {
+                eprintln!(
+                    "{} is under auto_yes_under ({}), cleaning without confirmation",
+                    format::size(total_size),
+                    limit
+                );
+            }
+        }
+    }
+    if confirm {
+        let wait_start = Instant::now();
+        eprint!("\nProceed with cleaning? [y/N]: ");
+        io::stderr().flush()?;
 
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
+        timings.confirmation += wait_start.elapsed();
 
         if !input.trim().eq_ignore_ascii_case("y") {
-            println!("Cleaning cancelled");
-            return Ok(());
+            eprintln!("Cleaning cancelled");
+            return Ok(None);
         }
     }
 
+    // Items too large for the Recycle Bin are asked about before anything is moved.
+    let mut trash = config.trash.clone();
+    if trash.enabled && trash.fallback == mc::TrashFallback::Ask && !cli.dry_run {
+        let wait_start = Instant::now();
+        trash.fallback = ask_trash_fallback(cli, config, &items)?;
+        timings.confirmation += wait_start.elapsed();
+    }
+
+    if config.safety.snapshot_before {
+        snapshot_before_clean(&path, cli.dry_run, effective_quiet)?;
+    }
+
+    let hook_ctx = mc::hooks::HookContext {
+        root: path.clone(),
+        planned_bytes: total_size,
+        planned_items: items.len(),
+    };
+    if !cli.dry_run {
+        mc::hooks::run_pre_clean(&config.hooks, &hook_ctx)?;
+    }
+
     // Create progress reporter
-    let progress = if effective_quiet {
+    let (threads, auto_threads) = config.options.parallel_threads.resolve(&path);
+    let progress = if let Some((_, bar)) = bar {
+        Arc::clone(bar) as Arc<dyn mc::Progress>
+    } else if effective_quiet {
         Arc::new(NoOpProgress) as Arc<dyn mc::Progress>
+    } else if !mc::utils::progress::is_interactive() {
+        Arc::new(PlainProgress::new(items.len() as u64)) as Arc<dyn mc::Progress>
     } else {
         let display = CompactDisplay::new_for_cleaning(items.len() as u64);
-        let worker_count = config.options.parallel_threads;
+        let worker_count = threads;
         display.set_message(&format!(
             "Cleaning ({} workers)",
             worker_count.to_string().bright_cyan()
@@ -252,29 +935,190 @@ fn run() -> Result<()> {
         Arc::new(display) as Arc<dyn mc::Progress>
     };
 
-    let cleaner = ParallelCleaner::new()?
-        .with_threads(config.options.parallel_threads)?
+    // A dry run's listing is paged here rather than printed by the cleaner.
+    let page_listing = cli.dry_run && !effective_quiet;
+    let cleaner = cleaner_for(config)?
+        .with_threads(threads)?
         .with_dry_run(cli.dry_run)
-        .with_quiet(effective_quiet)
+        .with_quiet(effective_quiet || page_listing)
+        .with_listing_limit(listing_limit)
+        .with_trash(trash)
+        .with_protection(config.safety.partial_delete.then_some(matcher))
+        .with_deterministic(cli.deterministic)
+        .with_estimated_sizes(estimated_sizes)
+        .with_plugins(plugins.cloned())
+        .with_journal(start_checkpoint(&path, &items, cli.dry_run))
+        .with_timeout(cli.clean_timeout)
         .with_progress(progress.clone());
 
-    let mut report = cleaner.clean(items.clone())?;
+    if page_listing {
+        page(&cleaner.dry_run_listing(&items));
+    }
+    let free_space_before = fs_utils::free_space(&path);
+    if let Some((multi, _)) = bar {
+        multi.show();
+    }
+    let result = cleaner.clean(items.clone());
+    if let Some((multi, _)) = bar {
+        multi.hide();
+    }
+    // A clean stopped by its timeout keeps its checkpoint for `mc resume`.
+    let timed_out = result.as_ref().is_ok_and(|report| report.clean_timed_out);
+    if !cli.dry_run && !timed_out {
+        Checkpoint::clear(&path);
+    }
+    let mut report = result?;
+    if cli.sudo_retry && !cli.dry_run {
+        sudo_retry(&mut report, &items)?;
+    }
+    if !cli.dry_run {
+        report.free_space_before = free_space_before;
+        report.free_space_after = fs_utils::free_space(&path);
+    }
     report.scan_errors = scan_errors;
+    report.scan_error_groups = scan_error_groups;
+    report.scan_errors_omitted = scan_errors_omitted;
     report.scan_duration = scan_duration;
+    timings.clean = report.timings.clean;
+    report.timings = timings;
     report.entries_scanned = entries_scanned;
+    report.scan = scan_summary;
+    report.depth_limited_dirs = depth_limited_dirs;
+    report.scan_timed_out = scan_timed_out;
+    report.pattern_stats = pattern_stats;
+    report.skipped = skipped;
+    report.auto_threads = auto_threads;
+    if cli.deterministic {
+        report.sort_by_path();
+    }
 
     progress.finish();
-    log::info!("Clean complete: {} items, {} bytes freed", report.items_deleted, report.bytes_freed);
+    log::info!(
+        "Clean complete: {} items, {} bytes freed",
+        report.items_deleted,
+        report.bytes_freed
+    );
 
-    // Show results
-    if cli.json {
-        let json_report = JsonReport::from(&report);
-        println!("{}", serde_json::to_string_pretty(&json_report)?);
-    } else if cli.stats || config.options.show_statistics || !effective_quiet {
-        print_report(&report);
+    Ok(Some(RootRun {
+        path,
+        items,
+        hook_ctx,
+        report,
+    }))
+}
+
+/// Lists `files`, the uncommitted files a clean would delete, and asks whether to
+/// delete them anyway. Both go to stderr, so machine-readable output on stdout stays
+/// intact.
+///
+/// # Errors
+///
+/// Returns an error when stdin is not a terminal, since nobody can answer then.
+fn confirm_uncommitted(files: &[PathBuf]) -> Result<bool> {
+    eprintln!(
+        "\n{} {} modified or untracked {} in git would be deleted:",
+        "⚠".yellow(),
+        files.len(),
+        if files.len() == 1 { "file" } else { "files" }
+    );
+    for file in files.iter().take(10) {
+        eprintln!("  {}", file.display());
     }
+    if files.len() > 10 {
+        eprintln!("  … and {} more", files.len() - 10);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(mc::McError::Safety(
+            "uncommitted files would be deleted and stdin is not a terminal to confirm; \
+             pass --allow-uncommitted to delete them anyway"
+                .to_string(),
+        ));
+    }
+    eprint!("Delete them anyway? [y/N]: ");
+    io::stderr().flush()?;
 
-    Ok(())
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Warns that matched items are in or contain the git repositories `repos`.
+fn print_git_warning(repos: &[PathBuf]) {
+    println!(
+        "\n{} Matched items are inside {} git {}; set [safety.git] to skip them or clean \
+         only ignored files:",
+        "⚠".yellow(),
+        repos.len(),
+        if repos.len() == 1 {
+            "repository"
+        } else {
+            "repositories"
+        }
+    );
+    for repo in repos.iter().take(5) {
+        let kind = git::repo_kind(repo)
+            .filter(|kind| *kind != git::RepoKind::Repository)
+            .map(|kind| format!(" ({})", kind))
+            .unwrap_or_default();
+        println!("  {}{}", repo.display(), kind.dimmed());
+    }
+    if repos.len() > 5 {
+        println!("  … and {} more", repos.len() - 5);
+    }
+}
+
+/// Asks what to do with the items of `items` too large for the Recycle Bin, for
+/// `trash.fallback = "ask"`: delete them permanently, or move them into the quarantine
+/// when `quarantine.dir` is set. They are kept when the answer is no, and without
+/// asking under `--yes` or when stdin is not a terminal.
+fn ask_trash_fallback(
+    cli: &Cli,
+    config: &Config,
+    items: &[mc::CleanItem],
+) -> Result<mc::TrashFallback> {
+    let oversized = mc::engine::trash::oversized(items);
+    if oversized.is_empty() {
+        return Ok(mc::TrashFallback::Keep);
+    }
+    let bytes: u64 = items
+        .iter()
+        .filter(|item| oversized.contains_key(&item.path))
+        .map(|item| item.size)
+        .sum();
+    let (fallback, action) = match config.quarantine.dir {
+        Some(_) => (
+            mc::TrashFallback::Quarantine,
+            "Move them into the quarantine instead?",
+        ),
+        None => (
+            mc::TrashFallback::Delete,
+            "Delete them permanently instead?",
+        ),
+    };
+    if cli.yes || !io::stdin().is_terminal() {
+        eprintln!(
+            "{} Keeping {} item(s) ({}) too large for the Recycle Bin; set trash.fallback to \
+             clean them",
+            "⚠".yellow(),
+            oversized.len(),
+            format::size(bytes)
+        );
+        return Ok(mc::TrashFallback::Keep);
+    }
+    eprint!(
+        "\n{} item(s) ({}) are too large for the Recycle Bin. {} [y/N]: ",
+        oversized.len(),
+        format::size(bytes),
+        action
+    );
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(if input.trim().eq_ignore_ascii_case("y") {
+        fallback
+    } else {
+        mc::TrashFallback::Keep
+    })
 }
 
 /// Handles the execution of `mc` subcommands.
@@ -290,26 +1134,11 @@ fn run() -> Result<()> {
 /// operations or configuration parsing.
 fn handle_command(command: Commands, cli: &Cli) -> Result<()> {
     match command {
-        Commands::List { json } => {
-            let config = Config::load(cli.config.as_ref())?;
-            let path = cli.path.canonicalize()?;
-
-            let matcher = Arc::new(PatternMatcher::new(&config.patterns)?);
-            let scanner = Scanner::new(path, matcher);
-            let (items, _scan_errors, _entries_scanned) = scanner.scan()?;
-
-            if json {
-                println!("{}", serde_json::to_string_pretty(&items)?);
-            } else {
-                for item in items {
-                    println!(
-                        "{} ({})",
-                        item.path.display(),
-                        format_size(item.size, DECIMAL)
-                    );
-                }
-            }
-        }
+        Commands::List { json } => run_list(cli, json)?,
+        Commands::Plan { output } => run_plan(cli, output)?,
+        Commands::Projects { json } => run_projects(cli, json)?,
+        Commands::Recommend { apply, json } => run_recommend(cli, apply, json)?,
+        Commands::Stats { json } => run_stats(cli, json)?,
         Commands::Init { global } => {
             let config = Config::default();
             let toml = toml::to_string_pretty(&config)?;
@@ -335,164 +1164,1217 @@ fn handle_command(command: Commands, cli: &Cli) -> Result<()> {
             std::fs::write(&config_path, toml)?;
             println!("Created configuration file: {}", config_path.display());
         }
-        Commands::Config => {
+        Commands::Import { from, command } => run_import(cli, from, &command)?,
+        Commands::Config {
+            show_origin: false,
+            command: None,
+        } => {
             let config = Config::load(cli.config.as_ref())?;
-            println!("{}", toml::to_string_pretty(&config)?);
+            let format =
+                mc::config::ConfigFormat::from_path(&Config::file_path(cli.config.as_ref()));
+            print!("{}", format.render(&config)?);
         }
-    }
+        Commands::Config {
+            show_origin: true,
+            command: None,
+        } => {
+            let (mut config, mut provenance) = Config::load_with_provenance(cli.config.as_ref())?;
+            apply_cli_overrides(&mut config, cli, Some(&mut provenance));
+            print_config_origins(&config, &provenance)?;
+        }
+        Commands::Config {
+            command: Some(ConfigCommand::Schema),
+            ..
+        } => {
+            let schema = schemars::schema_for!(Config);
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+        }
+        Commands::Config {
+            command: Some(ConfigCommand::Get { key }),
+            ..
+        } => {
+            let config = Config::load(cli.config.as_ref())?;
+            match mc::config::edit::get(&config, &key)? {
+                toml::Value::String(value) => println!("{}", value),
+                toml::Value::Table(table) => print!("{}", toml::to_string_pretty(&table)?),
+                value => println!("{}", value),
+            }
+        }
+        Commands::Config {
+            command: Some(ConfigCommand::Set { key, value }),
+            ..
+        } => {
+            let config_path = Config::file_path(cli.config.as_ref());
+            mc::config::edit::set(&config_path, &key, &value)?;
+            println!("Set {} in {}", key, config_path.display());
+        }
+        Commands::Check => {
+            let mut config = Config::load(cli.config.as_ref())?;
+            apply_cli_overrides(&mut config, cli, None);
+            let path = cli.path.canonicalize()?;
 
-    Ok(())
-}
+            let matcher = Arc::new(PatternMatcher::from_config(&config)?.with_root(&path)?);
+            let scanner = scanner_for(&config, path.clone(), matcher)?;
+            let items = scanner.scan()?.items;
+            let items = mc::engine::plan(items, &config);
 
-/// Prints a formatted report of the cleaning operation.
-///
-/// # Arguments
-///
-/// * `report` - A reference to the `CleanReport` generated by the cleaner.
-///
-/// # Output
-///
-/// The report is printed to stdout with colors and formatting for readability.
-/// It distinguishes between a dry run and an actual cleaning operation.
-fn print_report(report: &mc::CleanReport) {
+            if config.alerts.threshold_gb.is_none() {
+                eprintln!(
+                    "{} No alert threshold configured; set alerts.threshold_gb",
+                    "⚠".yellow()
+                );
+            }
+
+            match mc::alerts::check_threshold(&config.alerts, &path, &items) {
+                Some(alert) => {
+                    if !cli.quiet {
+                        println!("{} {}", "⚠".yellow(), alert.message());
+                    }
+                    alert.raise(&config.alerts)?;
+                }
+                None => {
+                    if !cli.quiet {
+                        let total: u64 = items.iter().map(|i| i.size).sum();
+                        println!(
+                            "{} {} reclaimable in {} items",
+                            "✓".bright_green(),
+                            format::size(total),
+                            items.len()
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Watch {
+            interval,
+            metrics_addr,
+            clean,
+        } => run_watch(cli, interval, metrics_addr, clean)?,
+        Commands::ExcludeBackups { no_spotlight } => run_exclude_backups(cli, !no_spotlight)?,
+        Commands::Retry { last, force } => run_retry(cli, last, force)?,
+        Commands::Resume { last, force } => run_resume(cli, last, force)?,
+        Commands::DeleteElevated { paths } => elevate::remove_paths(&paths)?,
+        Commands::Serve { stdio: _, http } => run_serve(cli, http)?,
+        Commands::Remote {
+            target,
+            ssh,
+            remote_mc,
+        } => run_remote(cli, &target, ssh, remote_mc)?,
+        Commands::Fleet { hosts, concurrency } => run_fleet(cli, &hosts, concurrency)?,
+        Commands::Doctor { scan, json } => run_doctor(cli, scan, json)?,
+        Commands::Snapshots { command } => {
+            run_snapshots(cli, command.unwrap_or(SnapshotsCommand::List))?
+        }
+        Commands::Patterns {
+            command: PatternsCommand::Stats { json },
+        } => {
+            let mut config = Config::load(cli.config.as_ref())?;
+            apply_cli_overrides(&mut config, cli, None);
+            let path = cli.path.canonicalize()?;
+
+            let matcher = Arc::new(PatternMatcher::from_config(&config)?.with_root(&path)?);
+            let scanner = scanner_for(&config, path, matcher)?;
+            let pattern_stats = scanner.scan()?.pattern_stats;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&pattern_stats)?);
+            } else {
+                print_pattern_stats(&pattern_stats);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the CLI flags that override configuration settings, recording each one in
+/// `provenance` when given.
+fn apply_cli_overrides(config: &mut Config, cli: &Cli, provenance: Option<&mut Provenance>) {
+    let mut includes = Vec::new();
+    includes.extend(cli.include.iter().map(|p| (p.clone(), "--include")));
+    includes.extend(
+        cli.include_dir
+            .iter()
+            .map(|p| (format!("dir:{}", p), "--include-dir")),
+    );
+    includes.extend(
+        cli.include_file
+            .iter()
+            .map(|p| (format!("file:{}", p), "--include-file")),
+    );
+    let include = includes.iter().map(|(p, _)| p.clone()).collect();
+    config.merge_cli_args(cli.exclude.clone(), include, cli.preserve_env);
+    if cli.git_clean_mode {
+        config.safety.git.clean_mode = true;
+    }
+    if cli.no_git_check {
+        config.safety.check_git_repo = false;
+    }
+    if let Some(threads) = cli.parallel {
+        config.options.parallel_threads = threads;
+    }
+    if let Some(depth) = cli.max_depth {
+        config.safety.max_depth = depth;
+    }
+    if cli.follow_symlinks {
+        config.options.symlinks = Some(mc::SymlinkPolicy::Follow);
+    }
+    if cli.one_file_system {
+        config.options.one_file_system = true;
+    }
+    if cli.shred {
+        config.options.secure_delete = true;
+    }
+    if let Some(dir) = &cli.archive {
+        config.archive.dir = Some(dir.clone());
+    }
+    if let Some(days) = cli.unused_for {
+        config.options.unused_for_days = Some(days);
+    }
+    if let Some(timeout) = cli.item_timeout {
+        config.options.item_timeout_secs = Some(timeout.as_secs().max(1));
+    }
+    if let Some(size_mode) = cli.size_mode {
+        config.options.size_mode = size_mode.into();
+    }
+    if cli.binary {
+        config.options.binary_units = true;
+    } else if cli.si {
+        config.options.binary_units = false;
+    }
+    // Sizes are printed in the units the configuration ends up with.
+    format::set_units(if config.options.binary_units {
+        format::SizeUnits::Binary
+    } else {
+        format::SizeUnits::Decimal
+    });
+
+    let Some(provenance) = provenance else {
+        return;
+    };
+    if !cli.exclude.is_empty() {
+        provenance.extended_by_cli("patterns.exclude", "--exclude");
+    }
+    if cli.preserve_env {
+        provenance.extended_by_cli("patterns.exclude", "--preserve-env");
+    }
+    for (raw, flag) in &includes {
+        let (kind, _) = IncludeKind::parse(raw);
+        if kind.dirs() {
+            provenance.extended_by_cli("patterns.directories", flag);
+        }
+        if kind.files() {
+            provenance.extended_by_cli("patterns.files", flag);
+        }
+    }
+    if cli.git_clean_mode {
+        provenance.set_by_cli("safety.git.clean_mode", "--git-clean-mode");
+    }
+    if cli.no_git_check {
+        provenance.set_by_cli("safety.check_git_repo", "--no-git-check");
+    }
+    if cli.parallel.is_some() {
+        provenance.set_by_cli("options.parallel_threads", "--parallel");
+    }
+    if cli.max_depth.is_some() {
+        provenance.set_by_cli("safety.max_depth", "--max-depth");
+    }
+    if cli.follow_symlinks {
+        provenance.set_by_cli("options.symlinks", "--follow-symlinks");
+    }
+    if cli.one_file_system {
+        provenance.set_by_cli("options.one_file_system", "--one-file-system");
+    }
+    if cli.shred {
+        provenance.set_by_cli("options.secure_delete", "--shred");
+    }
+    if cli.archive.is_some() {
+        provenance.set_by_cli("archive.dir", "--archive");
+    }
+    if cli.unused_for.is_some() {
+        provenance.set_by_cli("options.unused_for_days", "--unused-for");
+    }
+    if cli.item_timeout.is_some() {
+        provenance.set_by_cli("options.item_timeout_secs", "--item-timeout");
+    }
+    if cli.size_mode.is_some() {
+        provenance.set_by_cli("options.size_mode", "--size-mode");
+    }
+    if cli.binary {
+        provenance.set_by_cli("options.binary_units", "--binary");
+    } else if cli.si {
+        provenance.set_by_cli("options.binary_units", "--si");
+    }
+}
+
+/// Prints the configuration files that were considered and every effective setting
+/// with the layers that set it.
+fn print_config_origins(config: &Config, provenance: &Provenance) -> Result<()> {
+    if provenance.files.is_empty() {
+        println!("No configuration file found; using the defaults.");
+    } else {
+        println!("Configuration files:");
+        for (path, status) in &provenance.files {
+            println!("  {}  {}", path.display(), status);
+        }
+    }
     println!();
 
-    if report.dry_run {
-        // Show breakdown for dry run
-        println!(
-            "{} {} items ({} dirs, {} files)",
-            "✓".bright_green(),
-            report.items_deleted.to_string().bright_white(),
-            report.dirs_deleted.to_string().bright_cyan(),
-            report.files_deleted.to_string().bright_cyan()
-        );
-        println!(
-            "{} {} would be freed",
-            "✓".bright_green(),
-            format_size(report.bytes_freed, DECIMAL).bright_green()
-        );
-        println!("\n{}", "Dry run complete!".yellow());
+    let settings = provenance.settings(config)?;
+    let origins: Vec<String> = settings
+        .iter()
+        .map(|(_, _, origin)| {
+            origin
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+    let width = origins.iter().map(String::len).max().unwrap_or(0);
+    for ((key, value, _), origin) in settings.iter().zip(&origins) {
+        println!("{:<width$}  {} = {}", origin, key, value, width = width);
+    }
+    Ok(())
+}
+
+/// Serves JSON-RPC requests on stdin and stdout until stdin ends, or the REST API on
+/// `http` until interrupted.
+fn run_serve(cli: &Cli, http: Option<SocketAddr>) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    let Some(addr) = http else {
+        let cleaner = mc::Cleaner::new(config)
+            .with_dry_run(cli.dry_run)
+            .with_quiet(true)
+            .with_allow_uncommitted(cli.allow_uncommitted);
+        return mc::daemon::rpc::serve_stdio(cleaner);
+    };
+
+    let token = match mc::daemon::http::load_token(&config.server)? {
+        Some(token) => token,
+        None => {
+            let token = mc::daemon::http::generate_token()?;
+            eprintln!("{} API token: {}", "🔑".yellow(), token);
+            token
+        }
+    };
+    let roots = if config.server.roots.is_empty() {
+        vec![cli.path.clone()]
     } else {
-        // Calculate throughput metrics
-        let clean_secs = report.duration.as_secs_f64();
-        let total_secs = report.scan_duration.as_secs_f64() + clean_secs;
-        let mb_per_sec = if clean_secs > 0.0 {
-            (report.bytes_freed as f64 / clean_secs) / 1_000_000.0
-        } else {
-            0.0
-        };
-        let items_per_sec = if clean_secs > 0.0 {
-            report.items_deleted as f64 / clean_secs
-        } else {
-            0.0
-        };
+        config.server.roots.clone()
+    };
+    let cleaner = mc::Cleaner::new(config)
+        .with_dry_run(cli.dry_run)
+        .with_quiet(true)
+        .with_allow_uncommitted(cli.allow_uncommitted);
+    let addr = mc::daemon::http::serve_http(addr, cleaner, roots, token)?;
+    eprintln!("Serving the mc API on http://{}", addr);
+    loop {
+        std::thread::park();
+    }
+}
+
+/// Plans a clean of a root on another machine and, unless `--dry-run`, applies it after
+/// confirmation.
+fn run_remote(cli: &Cli, target: &RemoteTarget, ssh: String, remote_mc: String) -> Result<()> {
+    check_json_report(cli, "remote")?;
+    let effective_quiet = cli.quiet || cli.machine_report();
+    let options = RemoteOptions {
+        ssh,
+        remote_mc,
+        dry_run: cli.dry_run,
+        allow_uncommitted: cli.allow_uncommitted,
+        deadline: None,
+    };
+    let mut session = RemoteSession::connect(&target.host, &options)?;
 
-        // Show breakdown
+    let spinner = (!effective_quiet).then(|| {
+        let spinner = indicatif::ProgressBar::new_spinner();
+        spinner.enable_steady_tick(Duration::from_millis(80));
+        spinner.set_message(format!("Scanning {}", target));
+        spinner
+    });
+    let plan = session.plan(&target.path, |progress| {
+        if let Some(spinner) = &spinner {
+            spinner.set_message(format!("Scanning {}: {} found", target, progress.done));
+        }
+    });
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    let plan = plan?;
+
+    if !effective_quiet {
         println!(
-            "{} Cleaned {} items ({} dirs, {} files)",
-            "✓".bright_green(),
-            report.items_deleted.to_string().bright_white(),
-            report.dirs_deleted.to_string().bright_cyan(),
-            report.files_deleted.to_string().bright_cyan()
+            "{} {} items • {} on {}",
+            "Found".dimmed(),
+            format::count(plan.items as u64).bright_white(),
+            format::size(plan.bytes).bright_green(),
+            target
         );
+    }
+    if plan.items == 0 || cli.dry_run {
+        if cli.machine_report() {
+            println!("{}", serde_json::to_string_pretty(&plan.plan)?);
+        } else if !cli.quiet {
+            let done = if plan.items == 0 {
+                "No files to clean!"
+            } else {
+                "Dry run complete!"
+            };
+            println!("\n{}", done.yellow());
+        }
+        return Ok(());
+    }
+
+    // Asked on stderr, so `--json` output stays parseable.
+    if !cli.yes {
+        eprint!("\nClean {}? [y/N]: ", target);
+        io::stderr().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            eprintln!("Cleaning cancelled");
+            return Ok(());
+        }
+    }
+
+    let progress = if effective_quiet {
+        Arc::new(NoOpProgress) as Arc<dyn Progress>
+    } else if !mc::utils::progress::is_interactive() {
+        Arc::new(PlainProgress::new(plan.items as u64)) as Arc<dyn Progress>
+    } else {
+        let display = CompactDisplay::new_for_cleaning(plan.items as u64);
+        display.set_message(&format!("Cleaning {}", target));
+        Arc::new(display) as Arc<dyn Progress>
+    };
+    let mut handled = 0;
+    let report = session.apply(&plan, |update| {
+        if let Some(total) = update.total {
+            progress.set_total(total);
+        }
+        progress.increment(update.done.saturating_sub(handled));
+        handled = handled.max(update.done);
+    })?;
+    progress.finish();
+
+    if cli.machine_report() {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if !cli.quiet {
+        let errors = report["errors"].as_array().map_or(0, Vec::len);
         println!(
-            "{} Freed {}",
+            "\n{} Freed {} ({} items) on {}",
             "✓".bright_green(),
-            format_size(report.bytes_freed, DECIMAL).bright_green()
+            format::size(report["bytes_freed"].as_u64().unwrap_or(0)).bright_green(),
+            format::count(report["items_deleted"].as_u64().unwrap_or(0)),
+            target
         );
+        if errors > 0 {
+            let errors = format::count(errors as u64);
+            println!("{} {} items could not be deleted", "⚠".yellow(), errors);
+        }
+    }
+    Ok(())
+}
 
-        // Show timing breakdown
-        println!(
-            "{} Scan: {:.2}s • Clean: {:.2}s • Total: {:.2}s",
-            "⏱".dimmed(),
-            report.scan_duration.as_secs_f64(),
-            clean_secs,
-            total_secs
+/// Cleans every root of the inventory at `hosts` and prints a table per host.
+fn run_fleet(cli: &Cli, hosts: &Path, concurrency: Option<NonZeroUsize>) -> Result<()> {
+    check_json_report(cli, "fleet")?;
+    let mut inventory = FleetConfig::load(hosts)?;
+    if let Some(concurrency) = concurrency {
+        inventory.concurrency = concurrency.get();
+    }
+
+    // Asked on stderr, so `--json` output stays parseable.
+    if !cli.yes && !cli.dry_run {
+        eprint!(
+            "Clean {} roots on {} hosts without further confirmation? [y/N]: ",
+            inventory.root_count(),
+            inventory.hosts.len()
         );
+        io::stderr().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            eprintln!("Cleaning cancelled");
+            return Ok(());
+        }
+    }
 
-        // Show throughput
-        if mb_per_sec > 0.0 || items_per_sec > 0.0 {
-            println!(
-                "  {} {:.1} MB/s • {:.0} items/s",
-                "↳".dimmed(),
-                mb_per_sec,
-                items_per_sec
-            );
+    let effective_quiet = cli.quiet || cli.machine_report();
+    let roots = inventory.root_count();
+    let mut fleet = Fleet::new(inventory)
+        .with_dry_run(cli.dry_run)
+        .with_allow_uncommitted(cli.allow_uncommitted);
+    let progress = (!effective_quiet).then(|| Arc::new(MultiRootProgress::new(roots)));
+    if let Some(progress) = &progress {
+        fleet = fleet.with_progress(Arc::clone(progress));
+    }
+    let reports = fleet.run(|report| {
+        let Some(progress) = &progress else {
+            return;
+        };
+        for root in &report.roots {
+            progress.println(&match &root.error {
+                Some(err) => format!("{} {}:{}: {}", "✗".red(), report.host, root.root, err),
+                None => format!(
+                    "{} {}:{}: {}",
+                    "✓".bright_green(),
+                    report.host,
+                    root.root,
+                    format::size(root.bytes_freed)
+                ),
+            });
         }
+    });
+    if let Some(progress) = progress {
+        progress.finish();
+    }
 
-        println!("\n{}", "Done!".green());
+    if cli.machine_report() {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else if !cli.quiet {
+        println!();
+        print!("{}", mc::orchestrator::render_table(&reports));
+        if cli.dry_run {
+            println!("\n{}", "Dry run complete!".yellow());
+        }
     }
+    Ok(())
+}
 
-    print_error_details(report);
+/// Rejects the report formats other than `human` and `json`, which `command` does not
+/// print its reports in.
+fn check_json_report(cli: &Cli, command: &str) -> Result<()> {
+    match cli.report_format() {
+        ReportFormat::Human | ReportFormat::Json => Ok(()),
+        format => Err(mc::McError::InvalidArgument(format!(
+            "mc {} prints its report as text or JSON, not {}",
+            command,
+            format!("{:?}", format).to_lowercase()
+        ))),
+    }
 }
 
-/// Prints error details when there are deletion or scan failures.
-fn print_error_details(report: &mc::CleanReport) {
-    let total_errors = report.scan_errors.len() + report.errors.len();
-    if total_errors == 0 {
-        return;
+/// Reports include patterns that can never take effect and, with `scan`, patterns that
+/// match nothing under the target path.
+fn run_doctor(cli: &Cli, scan: bool, json: bool) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+
+    let matcher = PatternMatcher::from_config(&config)?;
+    let mut issues = mc::patterns::lint::find_issues(&matcher);
+
+    if scan {
+        let path = cli.path.canonicalize()?;
+        let scanner = scanner_for(&config, path.clone(), Arc::new(matcher.with_root(&path)?))?;
+        for (pattern, stats) in scanner.scan()?.pattern_stats {
+            if stats.items == 0 && !issues.iter().any(|issue| issue.pattern == pattern) {
+                issues.push(mc::patterns::lint::PatternIssue {
+                    pattern,
+                    kind: mc::patterns::lint::PatternIssueKind::NoMatches,
+                });
+            }
+        }
     }
 
-    println!();
-    println!(
-        "{} {} errors occurred:",
-        "⚠".yellow(),
-        total_errors.to_string().yellow()
+    if json {
+        println!("{}", serde_json::to_string_pretty(&issues)?);
+    } else if issues.is_empty() {
+        println!("{} No pattern issues found", "✓".bright_green());
+    } else {
+        for issue in &issues {
+            println!("{} {}", "⚠".yellow(), issue);
+        }
+    }
+    Ok(())
+}
+
+/// Excludes the directories a clean of the target path would remove from Time Machine
+/// backups and, with `spotlight`, from Spotlight indexing.
+fn run_exclude_backups(cli: &Cli, spotlight: bool) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    let path = cli.path.canonicalize()?;
+
+    let matcher = Arc::new(PatternMatcher::from_config(&config)?.with_root(&path)?);
+    let scanner = scanner_for(&config, path, matcher)?;
+    let items = mc::engine::plan(scanner.scan()?.items, &config);
+
+    let mut excluded = 0;
+    for dir in mc::backups::candidates(&items) {
+        if cli.dry_run {
+            println!("Would exclude {}", dir.display());
+            continue;
+        }
+        match mc::backups::exclude(dir, spotlight) {
+            Ok(()) => {
+                excluded += 1;
+                if !cli.quiet {
+                    println!("{} {}", "✓".bright_green(), dir.display());
+                }
+            }
+            // Nothing else will succeed either.
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => return Err(err.into()),
+            Err(err) => eprintln!("{} {}: {}", "✗".red(), dir.display(), err),
+        }
+    }
+    if !cli.dry_run && !cli.quiet {
+        println!("Excluded {} directories from backups", excluded);
+    }
+    Ok(())
+}
+
+/// Snapshots the file system holding `path` for `safety.snapshot_before` and prints how
+/// to roll back. A failed snapshot aborts the clean.
+fn snapshot_before_clean(path: &Path, dry_run: bool, quiet: bool) -> Result<()> {
+    if dry_run {
+        if mc::snapshots::detect(path).is_none() {
+            return Err(mc::McError::Safety(format!(
+                "safety.snapshot_before is set but {} is not on Btrfs, ZFS or APFS",
+                path.display()
+            )));
+        }
+        if !quiet {
+            println!("Would snapshot {} before cleaning", path.display());
+        }
+        return Ok(());
+    }
+
+    let snapshot = mc::snapshots::create(path).map_err(|err| {
+        mc::McError::Safety(format!(
+            "cannot snapshot {} before cleaning: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    let rollback = snapshot.rollback_command(path);
+    log::info!(
+        "Created snapshot {}; roll back with: {}",
+        snapshot.location,
+        rollback
     );
+    if !quiet {
+        println!("{} Snapshot {}", "✓".bright_green(), snapshot.location);
+        println!("  {} Roll back with: {}", "↳".dimmed(), rollback);
+    }
+    Ok(())
+}
 
-    for (i, err) in report.errors.iter().enumerate() {
-        if i >= 10 {
-            println!(
-                "  {} ... and {} more",
-                "↳".dimmed(),
-                report.errors.len() - 10
-            );
-            break;
+/// Runs `mc snapshots` for the file system holding the target path.
+fn run_snapshots(cli: &Cli, command: SnapshotsCommand) -> Result<()> {
+    let path = cli.path.canonicalize()?;
+    match command {
+        SnapshotsCommand::List => {
+            let snapshots = mc::snapshots::list(&path)?;
+            if snapshots.is_empty() {
+                println!("No snapshots");
+            }
+            for snapshot in snapshots {
+                println!(
+                    "{}  {}",
+                    snapshot.name.bright_white(),
+                    snapshot.location.dimmed()
+                );
+                println!("  {} {}", "↳".dimmed(), snapshot.rollback_command(&path));
+            }
+        }
+        SnapshotsCommand::Delete { name } => {
+            if cli.dry_run {
+                println!("Would delete snapshot {}", name);
+            } else {
+                if !cli.yes {
+                    eprint!("\nDelete snapshot {}? It cannot be restored. [y/N]: ", name);
+                    io::stderr().flush()?;
+                    let mut input = String::new();
+                    io::stdin().read_line(&mut input)?;
+                    if !input.trim().eq_ignore_ascii_case("y") {
+                        eprintln!("Deleting cancelled");
+                        return Ok(());
+                    }
+                }
+                mc::snapshots::delete(&path, &name)?;
+                if !cli.quiet {
+                    println!("{} Deleted snapshot {}", "✓".bright_green(), name);
+                }
+            }
         }
-        println!("  {} {}", "✗".red(), err);
     }
+    Ok(())
+}
 
-    for (i, err) in report.scan_errors.iter().enumerate() {
-        if i >= 5 {
+/// Prints per-pattern match counts, largest first, flagging patterns that matched nothing.
+fn print_pattern_stats(pattern_stats: &std::collections::BTreeMap<String, mc::PatternStats>) {
+    let mut rows: Vec<_> = pattern_stats.iter().collect();
+    rows.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(b.1.items.cmp(&a.1.items)));
+    let width = rows
+        .iter()
+        .map(|(pattern, _)| pattern.len())
+        .max()
+        .unwrap_or(0);
+
+    for (pattern, stats) in rows {
+        if stats.items == 0 {
             println!(
-                "  {} ... and {} more scan errors",
-                "↳".dimmed(),
-                report.scan_errors.len() - 5
+                "  {:width$}  {}",
+                pattern,
+                "no matches".dimmed(),
+                width = width
+            );
+        } else {
+            println!(
+                "  {:width$}  {} items, {}",
+                pattern,
+                stats.items,
+                format::size(stats.bytes).bright_yellow(),
+                width = width
             );
-            break;
         }
-        println!("  {} {}", "↳".dimmed(), err);
     }
 }
 
-/// JSON-serializable version of CleanReport with durations as milliseconds.
-#[derive(serde::Serialize)]
-struct JsonReport {
+/// Writes the checkpoint `mc resume` continues from if the clean of `items` under
+/// `root` is interrupted, and returns the journal to record deletions in.
+///
+/// Dry runs keep no checkpoint, and a checkpoint that cannot be written only costs the
+/// ability to resume, so the clean goes ahead without one.
+fn start_checkpoint(
+    root: &Path,
+    items: &[mc::CleanItem],
     dry_run: bool,
-    items_deleted: usize,
-    bytes_freed: u64,
-    dirs_deleted: usize,
-    files_deleted: usize,
-    entries_scanned: usize,
-    duration_ms: u64,
-    scan_duration_ms: u64,
-    errors: Vec<mc::CleanError>,
-    scan_errors: Vec<mc::types::ScanError>,
-}
-
-impl From<&mc::CleanReport> for JsonReport {
-    fn from(r: &mc::CleanReport) -> Self {
-        Self {
-            dry_run: r.dry_run,
-            items_deleted: r.items_deleted,
-            bytes_freed: r.bytes_freed,
-            dirs_deleted: r.dirs_deleted,
-            files_deleted: r.files_deleted,
-            entries_scanned: r.entries_scanned,
-            duration_ms: r.duration.as_millis() as u64,
-            scan_duration_ms: r.scan_duration.as_millis() as u64,
-            errors: r.errors.clone(),
-            scan_errors: r.scan_errors.clone(),
+) -> Option<Arc<mc::resume::Journal>> {
+    if dry_run {
+        return None;
+    }
+    match Checkpoint::new(root, items.to_vec()).save() {
+        Ok(journal) => Some(Arc::new(journal)),
+        Err(err) => {
+            log::warn!("Failed to write resume checkpoint: {}", err);
+            None
+        }
+    }
+}
+
+/// Records the items that failed to delete for `mc retry`, or clears the root's
+/// manifest when everything succeeded.
+fn save_retry_manifest(
+    root: &std::path::Path,
+    items: &[mc::CleanItem],
+    report: &mc::CleanReport,
+    quiet: bool,
+) {
+    let Some(manifest) = RetryManifest::from_failures(root, items, &report.errors) else {
+        RetryManifest::clear(root);
+        return;
+    };
+    match manifest.save() {
+        Ok(path) => {
+            log::debug!("Retry manifest written to {}", path.display());
+            if !quiet {
+                println!(
+                    "{} {} failed item(s) saved; run {} to try them again",
+                    "↻".yellow(),
+                    manifest.items.len(),
+                    "mc retry".bright_cyan()
+                );
+            }
+        }
+        Err(err) => log::warn!("Failed to write retry manifest: {}", err),
+    }
+}
+
+/// Offers to delete the items that failed with permission errors again with elevated
+/// privileges, listing exactly which paths are affected, and folds the outcome into
+/// `report`. It always asks, even with `--yes`, on stderr so stdout keeps only the
+/// report. Without a terminal to answer on, the paths are listed and left alone.
+fn sudo_retry(report: &mut mc::CleanReport, items: &[mc::CleanItem]) -> Result<()> {
+    let paths = elevate::permission_denied_paths(&report.errors);
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "\n{} {} path(s) could not be deleted due to permissions:",
+        "⚠".yellow(),
+        paths.len()
+    );
+    for path in &paths {
+        eprintln!("  {}", path.display());
+    }
+    if !io::stdin().is_terminal() {
+        eprintln!("Not retrying them: stdin is not a terminal to confirm on");
+        return Ok(());
+    }
+    eprint!("Delete these paths with elevated privileges? [y/N]: ");
+    io::stderr().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        return Ok(());
+    }
+
+    let removed = elevate::delete_elevated(&paths).unwrap_or_else(|err| {
+        eprintln!("{} {}", "✗".red(), err);
+        Vec::new()
+    });
+    let removed = elevate::reconcile(report, items, &removed);
+    eprintln!(
+        "{} {} path(s) deleted with elevated privileges",
+        "✓".green(),
+        removed
+    );
+    Ok(())
+}
+
+/// Retries the items recorded in a retry manifest, after confirmation.
+///
+/// Items that have disappeared since are counted as already gone. The manifest is
+/// rewritten with the items that still fail, or removed when all succeed.
+fn run_retry(cli: &Cli, last: bool, force: bool) -> Result<()> {
+    let manifest_path = if last {
+        RetryManifest::latest()
+    } else {
+        Some(RetryManifest::path_for(&cli.path.canonicalize()?))
+    };
+    let Some(manifest_path) = manifest_path.filter(|path| path.exists()) else {
+        println!("Nothing to retry");
+        return Ok(());
+    };
+    let manifest = RetryManifest::load(&manifest_path, force)?;
+    let config = Config::load(cli.config.as_ref())?;
+    if !confirm_recorded(cli, &config, &manifest.root, manifest.items.len())? {
+        return Ok(());
+    }
+
+    if !cli.quiet && !cli.machine_report() {
+        println!(
+            "Retrying {} item(s) under {}",
+            manifest.items.len(),
+            manifest.root.display()
+        );
+    }
+
+    let protection = if config.safety.partial_delete {
+        Some(Arc::new(
+            PatternMatcher::from_config(&config)?.with_root(&manifest.root)?,
+        ))
+    } else {
+        None
+    };
+    let (threads, auto_threads) = config.options.parallel_threads.resolve(&manifest.root);
+    let cleaner = cleaner_for(&config)?
+        .with_threads(threads)?
+        .with_dry_run(cli.dry_run)
+        .with_quiet(cli.quiet || cli.machine_report())
+        .with_protection(protection)
+        .with_deterministic(cli.deterministic);
+    let free_space_before = fs_utils::free_space(&manifest.root);
+    let mut report = cleaner.clean(manifest.items.clone())?;
+    report.auto_threads = auto_threads;
+    if cli.sudo_retry && !cli.dry_run {
+        sudo_retry(&mut report, &manifest.items)?;
+    }
+    if !cli.dry_run {
+        report.free_space_before = free_space_before;
+        report.free_space_after = fs_utils::free_space(&manifest.root);
+    }
+
+    print_report(cli, &report, !cli.quiet, false)?;
+
+    if !cli.dry_run {
+        let quiet = cli.quiet || cli.machine_report();
+        save_retry_manifest(&manifest.root, &manifest.items, &report, quiet);
+    }
+    Ok(())
+}
+
+/// Runs the safety checks a clean of `root` runs, for a retry manifest or checkpoint
+/// recorded for it, then asks before deleting its `count` items unless `--yes` or
+/// `--dry-run` is given. Returns whether to go ahead.
+fn confirm_recorded(cli: &Cli, config: &Config, root: &Path, count: usize) -> Result<bool> {
+    SafetyGuard::new(
+        config.safety.refuses_git_root(),
+        config.safety.max_depth,
+        config.safety.min_free_space_gb,
+    )
+    .validate(root)?;
+    if cli.yes || cli.dry_run {
+        return Ok(true);
+    }
+
+    // On stderr, so stdout keeps only the report.
+    eprint!(
+        "\nDelete {} item(s) under {}? [y/N]: ",
+        count,
+        root.display()
+    );
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        eprintln!("Cleaning cancelled");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Deletes the items an interrupted clean had not reached, from its checkpoint, after
+/// confirmation.
+///
+/// The resumed clean keeps the checkpoint's journal up to date, so it can itself be
+/// interrupted and resumed again.
+fn run_resume(cli: &Cli, last: bool, force: bool) -> Result<()> {
+    let checkpoint_path = if last {
+        Checkpoint::latest()
+    } else {
+        Some(Checkpoint::path_for(&cli.path.canonicalize()?))
+    };
+    let Some(checkpoint_path) = checkpoint_path.filter(|path| path.exists()) else {
+        println!("Nothing to resume");
+        return Ok(());
+    };
+    let checkpoint = Checkpoint::load(&checkpoint_path, force)?;
+    if checkpoint.items.is_empty() {
+        Checkpoint::clear(&checkpoint.root);
+        println!("Nothing to resume");
+        return Ok(());
+    }
+    let config = Config::load(cli.config.as_ref())?;
+    if !confirm_recorded(cli, &config, &checkpoint.root, checkpoint.items.len())? {
+        return Ok(());
+    }
+
+    if !cli.quiet && !cli.machine_report() {
+        println!(
+            "Resuming with {} remaining item(s) under {}",
+            checkpoint.items.len(),
+            checkpoint.root.display()
+        );
+    }
+
+    let protection = if config.safety.partial_delete {
+        Some(Arc::new(
+            PatternMatcher::from_config(&config)?.with_root(&checkpoint.root)?,
+        ))
+    } else {
+        None
+    };
+    let journal = if cli.dry_run {
+        None
+    } else {
+        Some(Arc::new(checkpoint.journal()?))
+    };
+    let (threads, auto_threads) = config.options.parallel_threads.resolve(&checkpoint.root);
+    let cleaner = cleaner_for(&config)?
+        .with_threads(threads)?
+        .with_dry_run(cli.dry_run)
+        .with_quiet(cli.quiet || cli.machine_report())
+        .with_protection(protection)
+        .with_deterministic(cli.deterministic)
+        .with_journal(journal)
+        .with_timeout(cli.clean_timeout);
+    let free_space_before = fs_utils::free_space(&checkpoint.root);
+    let mut report = cleaner.clean(checkpoint.items.clone())?;
+    report.auto_threads = auto_threads;
+    if !cli.dry_run {
+        if !report.clean_timed_out {
+            Checkpoint::clear(&checkpoint.root);
+        }
+        report.free_space_before = free_space_before;
+        report.free_space_after = fs_utils::free_space(&checkpoint.root);
+    }
+
+    print_report(cli, &report, !cli.quiet, false)?;
+
+    if !cli.dry_run {
+        let quiet = cli.quiet || cli.machine_report();
+        save_retry_manifest(&checkpoint.root, &checkpoint.items, &report, quiet);
+    }
+    Ok(())
+}
+
+/// Runs the `watch` daemon loop until the process is terminated.
+///
+/// Each cycle scans the target path, updates the metrics, raises an alert if the
+/// configured threshold is exceeded, and cleans only when `clean` is set, running
+/// the pre- and post-clean hooks around each real clean. Failures within a cycle are
+/// logged rather than returned so a transient error does not stop the daemon.
+fn run_watch(
+    cli: &Cli,
+    interval: u64,
+    metrics_addr: Option<SocketAddr>,
+    clean: bool,
+) -> Result<()> {
+    let mut config = Config::load(cli.config.as_ref())?;
+    apply_cli_overrides(&mut config, cli, None);
+    config.validate();
+
+    let path = cli.path.canonicalize()?;
+    // Each cycle deletes unattended, so it is never implied.
+    if clean && !cli.yes && !cli.dry_run {
+        return Err(mc::McError::Safety(
+            "watch --clean deletes on every cycle without asking; pass --yes to allow it"
+                .to_string(),
+        ));
+    }
+    if clean {
+        SafetyGuard::new(
+            config.safety.refuses_git_root(),
+            config.safety.max_depth,
+            config.safety.min_free_space_gb,
+        )
+        .validate(&path)?;
+    }
+
+    let metrics = Arc::new(Metrics::new());
+    if let Some(addr) = metrics_addr {
+        let bound = serve_metrics(addr, Arc::clone(&metrics))?;
+        if !cli.quiet {
+            println!("Serving metrics on http://{}/metrics", bound);
         }
     }
+
+    let matcher = Arc::new(PatternMatcher::from_config(&config)?.with_root(&path)?);
+    let git_policies = git::GitPolicies::from_config(&config.safety.git)?;
+    let cleaner = if clean {
+        let (threads, _) = config.options.parallel_threads.resolve(&path);
+        Some(
+            cleaner_for(&config)?
+                .with_threads(threads)?
+                .with_dry_run(cli.dry_run)
+                .with_quiet(true)
+                .with_protection(config.safety.partial_delete.then(|| Arc::clone(&matcher)))
+                .with_deterministic(cli.deterministic),
+        )
+    } else {
+        None
+    };
+    let interval = Duration::from_secs(interval.max(1));
+
+    loop {
+        let scanner = scanner_for(&config, path.clone(), Arc::clone(&matcher))?;
+
+        match scanner.scan() {
+            Ok(outcome) => {
+                let prune_start = Instant::now();
+                let items = mc::engine::plan(outcome.items, &config);
+                let enclosing = !config.safety.refuses_git_root();
+                let repos = git::repos_for(&path, outcome.git_repos, enclosing);
+                let items = git_policies.apply(items, &repos).items;
+                let prune = prune_start.elapsed();
+                let reclaimable: u64 = items.iter().map(|i| i.size).sum();
+                metrics.record_scan(reclaimable, outcome.errors.len());
+
+                if !cli.quiet {
+                    println!(
+                        "{} {} reclaimable in {} items",
+                        "watch:".dimmed(),
+                        format::size(reclaimable),
+                        items.len()
+                    );
+                }
+
+                if let Some(alert) = mc::alerts::check_threshold(&config.alerts, &path, &items) {
+                    if let Err(err) = alert.raise(&config.alerts) {
+                        log::warn!("Alert delivery failed: {}", err);
+                    }
+                }
+
+                if let Some(ref cleaner) = cleaner {
+                    let items = if cli.dry_run || cli.allow_uncommitted {
+                        items
+                    } else {
+                        hold_back_uncommitted(items, &repos)
+                    };
+                    let hook_ctx = mc::hooks::HookContext {
+                        root: path.clone(),
+                        planned_bytes: items.iter().map(|i| i.size).sum(),
+                        planned_items: items.len(),
+                    };
+                    // A real clean runs the hooks a clean does; an aborting pre-clean
+                    // hook skips this cycle's clean.
+                    let hooks = !cli.dry_run && !items.is_empty();
+                    let allowed = !hooks
+                        || mc::hooks::run_pre_clean(&config.hooks, &hook_ctx)
+                            .inspect_err(|err| log::warn!("Skipping this watch clean: {}", err))
+                            .is_ok();
+                    if allowed && !items.is_empty() {
+                        match cleaner.clean(items) {
+                            Ok(mut report) => {
+                                report.scan_errors = outcome.errors;
+                                report.scan_error_groups = outcome.error_groups;
+                                report.scan_errors_omitted = outcome.errors_omitted;
+                                report.scan_duration = outcome.stats.duration;
+                                report.timings.scan = outcome.stats.duration;
+                                report.timings.prune = prune;
+                                report.entries_scanned = outcome.stats.entries_scanned;
+                                report.depth_limited_dirs = outcome.stats.depth_limited_dirs;
+                                report.pattern_stats = outcome.pattern_stats;
+                                report.scan = outcome.stats;
+                                metrics.record_clean(&report);
+                                if hooks {
+                                    if let Err(err) =
+                                        mc::hooks::run_post_clean(&config.hooks, &hook_ctx, &report)
+                                    {
+                                        log::warn!("Post-clean hook failed: {}", err);
+                                    }
+                                }
+                            }
+                            Err(err) => log::warn!("Watch clean failed: {}", err),
+                        }
+                    }
+                }
+            }
+            Err(err) => log::warn!("Watch scan failed: {}", err),
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// Drops the items of a watch clean that would delete work not in git yet, the
+/// same check a clean confirms. They are only cleaned if someone at a terminal
+/// allows it; otherwise they are logged and left for the next cycle.
+fn hold_back_uncommitted(items: Vec<mc::CleanItem>, repos: &[PathBuf]) -> Vec<mc::CleanItem> {
+    let uncommitted = git::uncommitted_files(&items, repos);
+    if uncommitted.is_empty() {
+        return items;
+    }
+    if io::stdin().is_terminal() {
+        match confirm_uncommitted(&uncommitted) {
+            Ok(true) => return items,
+            Ok(false) => {}
+            Err(err) => log::warn!("Cannot confirm deleting uncommitted files: {}", err),
+        }
+    }
+    let (items, held) = git::hold_back_uncommitted(items, &uncommitted);
+    for item in &held {
+        log::warn!(
+            "Not cleaning {}: it holds files not committed to git; pass --allow-uncommitted \
+             to clean it anyway",
+            item.path.display()
+        );
+    }
+    items
+}
+
+/// Prints the report of a clean in the format asked for on the command line. The
+/// human-readable form is only printed if `human` is set.
+fn print_report(cli: &Cli, report: &mc::CleanReport, human: bool, scan_stats: bool) -> Result<()> {
+    match cli.report_format() {
+        ReportFormat::Human if human => print!(
+            "{}",
+            report
+                .human()
+                .with_show_errors(cli.show_errors)
+                .with_scan_stats(scan_stats)
+        ),
+        ReportFormat::Human => {}
+        format => report.write_to(&mut io::stdout().lock(), format)?,
+    }
+    Ok(())
+}
+
+/// Prints `text`, through `$PAGER` when stdout is a terminal and `text` is taller than
+/// the terminal window.
+///
+/// Without `$PAGER`, `less -R` is used (`more` on Windows). If the pager cannot be
+/// started or fails, as when it is not installed, the text is printed directly.
+fn page(text: &str) {
+    let stdout = io::stdout();
+    let rows = console::Term::stdout()
+        .size_checked()
+        .map(|(rows, _)| rows as usize);
+    let fits = rows.is_none_or(|rows| text.lines().count() < rows);
+    if !stdout.is_terminal() || fits || !spawn_pager(text) {
+        print!("{}", text);
+        let _ = stdout.lock().flush();
+    }
+}
+
+/// Writes `text` to a pager and waits for it to exit. Returns `false` if the pager
+/// could not be started or exited unsuccessfully.
+fn spawn_pager(text: &str) -> bool {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| default_pager().to_string());
+    let Ok(mut child) = pager_shell(&pager).stdin(process::Stdio::piped()).spawn() else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit the pager before reading everything.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    // A shell that cannot find the pager exits with 127.
+    child.wait().is_ok_and(|status| status.success())
+}
+
+#[cfg(windows)]
+fn default_pager() -> &'static str {
+    "more"
+}
+
+#[cfg(not(windows))]
+fn default_pager() -> &'static str {
+    "less -R"
+}
+
+#[cfg(windows)]
+fn pager_shell(command: &str) -> process::Command {
+    let mut cmd = process::Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn pager_shell(command: &str) -> process::Command {
+    let mut cmd = process::Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
+
+/// An item in `mc list --json`, with whole days since it was last used.
+#[derive(serde::Serialize)]
+struct JsonListedItem<'a> {
+    #[serde(flatten)]
+    item: &'a mc::CleanItem,
+    unused_days: Option<u64>,
+}
+
+/// The JSON form of `mc stats`.
+#[derive(serde::Serialize)]
+struct JsonStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quarantine: Option<JsonQuarantineStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<JsonArchiveStats>,
+}
+
+/// What the quarantine holds, in `mc stats --json`.
+#[derive(serde::Serialize)]
+struct JsonQuarantineStats {
+    dir: PathBuf,
+    bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_total: Option<u64>,
+    batches: Vec<JsonQuarantineBatch>,
+}
+
+/// One quarantine batch, oldest first, in `mc stats --json`.
+#[derive(serde::Serialize)]
+struct JsonQuarantineBatch {
+    path: PathBuf,
+    bytes: u64,
+    age_days: u64,
+}
+
+/// What the archive directory holds, in `mc stats --json`.
+#[derive(serde::Serialize)]
+struct JsonArchiveStats {
+    dir: PathBuf,
+    archives: usize,
+    bytes: u64,
 }
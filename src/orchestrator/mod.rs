@@ -0,0 +1,503 @@
+//! This module cleans many machines at once, for `mc fleet`.
+//!
+//! A [`Fleet`] takes its hosts from an inventory file ([`FleetConfig`]) and cleans
+//! each of their roots through a [`RemoteSession`]. At most `concurrency` hosts are
+//! worked on at once, and on each host at most its own `concurrency` roots, so a small
+//! machine is not flooded with scans. A host that fails does not stop the others; what
+//! went wrong is in its [`HostReport`]. ssh never prompts, and the connections to a
+//! host are killed once it has taken `timeout_secs`.
+//!
+//! ```toml
+//! concurrency = 8
+//! timeout_secs = 3600
+//!
+//! [[hosts]]
+//! host = "builder@farm-01"
+//! roots = ["/srv/builds", "/var/cache/ci"]
+//! concurrency = 2
+//!
+//! [[hosts]]
+//! host = "farm-02"
+//! roots = ["/srv/builds"]
+//! ssh = "ssh -p 2222"
+//! ```
+
+use crate::remote::{RemoteOptions, RemoteProgress, RemoteSession};
+use crate::types::{McError, Result};
+use crate::utils::format;
+use crate::utils::progress::{MultiRootProgress, NoOpProgress, Progress};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The hosts of a fleet and how to reach them, read from `hosts.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FleetConfig {
+    /// How many hosts are cleaned at once.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// The ssh command for hosts that do not set their own.
+    #[serde(default = "default_ssh")]
+    pub ssh: String,
+    /// The mc binary for hosts that do not set their own.
+    #[serde(default = "default_remote_mc")]
+    pub remote_mc: String,
+    /// How long a host that does not set its own may take, from connecting to its
+    /// last root, before its connections are killed.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// The hosts, in the order they are reported.
+    pub hosts: Vec<FleetHost>,
+}
+
+/// One machine of a fleet.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FleetHost {
+    /// The host as `ssh` takes it, including the user if needed.
+    pub host: String,
+    /// The roots to clean on the host.
+    pub roots: Vec<String>,
+    /// How many of the roots are cleaned at once.
+    #[serde(default = "default_host_concurrency")]
+    pub concurrency: usize,
+    /// The ssh command for this host.
+    #[serde(default)]
+    pub ssh: Option<String>,
+    /// The mc binary on this host.
+    #[serde(default)]
+    pub remote_mc: Option<String>,
+    /// How long this host may take.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_host_concurrency() -> usize {
+    1
+}
+
+fn default_ssh() -> String {
+    "ssh".to_string()
+}
+
+fn default_remote_mc() -> String {
+    "mc".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    60 * 60
+}
+
+/// Added after every ssh command, so an unreachable host or a password prompt fails
+/// the host instead of blocking a worker. ssh takes the first value of an option, so
+/// the command can still set its own.
+const SSH_OPTIONS: &str = "-o BatchMode=yes -o ConnectTimeout=10";
+
+impl FleetConfig {
+    /// Reads and checks the inventory at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, lists no hosts, or has a
+    /// host starting with `-`, without roots or with a concurrency of zero.
+    pub fn load(path: &Path) -> Result<Self> {
+        let config: Self = toml::from_str(&std::fs::read_to_string(path)?)?;
+        let invalid = |message: String| -> Result<Self> {
+            Err(McError::Config(format!("{}: {}", path.display(), message)))
+        };
+        if config.hosts.is_empty() {
+            return invalid("no hosts are listed".to_string());
+        }
+        if config.concurrency == 0 {
+            return invalid("concurrency must be at least 1".to_string());
+        }
+        for host in &config.hosts {
+            if host.host.starts_with('-') {
+                return invalid(format!("host {} starts with `-`", host.host));
+            }
+            if host.roots.is_empty() {
+                return invalid(format!("host {} has no roots", host.host));
+            }
+            if host.concurrency == 0 {
+                return invalid(format!("host {} has a concurrency of 0", host.host));
+            }
+        }
+        Ok(config)
+    }
+
+    /// The number of roots across all hosts.
+    pub fn root_count(&self) -> usize {
+        self.hosts.iter().map(|host| host.roots.len()).sum()
+    }
+}
+
+/// What a fleet run did on one host.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostReport {
+    pub host: String,
+    /// The roots, in inventory order.
+    pub roots: Vec<RootReport>,
+    /// How long the host took, from connecting to its last root.
+    pub duration: Duration,
+}
+
+/// What a fleet run did on one root.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RootReport {
+    pub root: String,
+    /// Bytes freed, or that would be freed in a dry run.
+    pub bytes_freed: u64,
+    /// Items deleted, or that would be deleted in a dry run.
+    pub items_deleted: u64,
+    /// Items that could not be deleted.
+    pub item_errors: usize,
+    /// Why the root could not be cleaned at all, such as a failed connection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HostReport {
+    pub fn bytes_freed(&self) -> u64 {
+        self.roots.iter().map(|root| root.bytes_freed).sum()
+    }
+
+    pub fn items_deleted(&self) -> u64 {
+        self.roots.iter().map(|root| root.items_deleted).sum()
+    }
+
+    /// Items that could not be deleted plus roots that could not be cleaned.
+    pub fn errors(&self) -> usize {
+        self.roots
+            .iter()
+            .map(|root| root.item_errors + usize::from(root.error.is_some()))
+            .sum()
+    }
+}
+
+/// Cleans the hosts of a [`FleetConfig`] concurrently.
+pub struct Fleet {
+    config: FleetConfig,
+    dry_run: bool,
+    allow_uncommitted: bool,
+    progress: Option<Arc<MultiRootProgress>>,
+}
+
+impl Fleet {
+    pub fn new(config: FleetConfig) -> Self {
+        Self {
+            config,
+            dry_run: false,
+            allow_uncommitted: false,
+            progress: None,
+        }
+    }
+
+    /// Only plans, starting every remote mc with `--dry-run`.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Starts every remote mc with `--allow-uncommitted`, so items holding work not
+    /// in git yet are cleaned too.
+    pub fn with_allow_uncommitted(mut self, allow: bool) -> Self {
+        self.allow_uncommitted = allow;
+        self
+    }
+
+    /// Shows a bar for each root, named `host:root`, as it is worked on.
+    pub fn with_progress(mut self, progress: Arc<MultiRootProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Cleans every host, calling `on_host` as each one finishes, and returns the
+    /// reports in inventory order. Nothing is confirmed on the hosts.
+    pub fn run(&self, on_host: impl Fn(&HostReport) + Sync) -> Vec<HostReport> {
+        let hosts = &self.config.hosts;
+        let next = AtomicUsize::new(0);
+        let reports = Mutex::new(vec![None; hosts.len()]);
+        thread::scope(|scope| {
+            for _ in 0..self.config.concurrency.min(hosts.len()) {
+                scope.spawn(|| {
+                    while let Some(index) = claim(&next, hosts.len()) {
+                        let report = self.clean_host(&hosts[index]);
+                        on_host(&report);
+                        reports.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(report);
+                    }
+                });
+            }
+        });
+        let reports = reports.into_inner().unwrap_or_else(|e| e.into_inner());
+        reports
+            .into_iter()
+            .map(|report| report.expect("every host is cleaned"))
+            .collect()
+    }
+
+    fn clean_host(&self, host: &FleetHost) -> HostReport {
+        let start = Instant::now();
+        let ssh = host.ssh.as_deref().unwrap_or(&self.config.ssh);
+        let timeout = Duration::from_secs(host.timeout_secs.unwrap_or(self.config.timeout_secs));
+        let options = RemoteOptions {
+            ssh: format!("{} {}", ssh, SSH_OPTIONS),
+            remote_mc: host
+                .remote_mc
+                .clone()
+                .unwrap_or_else(|| self.config.remote_mc.clone()),
+            dry_run: self.dry_run,
+            allow_uncommitted: self.allow_uncommitted,
+            deadline: Some(start + timeout),
+        };
+        let next = AtomicUsize::new(0);
+        let roots = Mutex::new(vec![RootReport::default(); host.roots.len()]);
+        thread::scope(|scope| {
+            for _ in 0..host.concurrency.min(host.roots.len()) {
+                scope.spawn(|| {
+                    // Each worker keeps its connection for the roots it takes.
+                    let mut session = None;
+                    while let Some(index) = claim(&next, host.roots.len()) {
+                        let root = &host.roots[index];
+                        let progress: Box<dyn Progress> = match &self.progress {
+                            Some(multi) => {
+                                Box::new(multi.add_root(&format!("{}:{}", host.host, root)))
+                            }
+                            None => Box::new(NoOpProgress),
+                        };
+                        let report = self
+                            .clean_root(&mut session, &host.host, &options, root, &*progress)
+                            .unwrap_or_else(|err| {
+                                // The connection may be unusable after an error.
+                                session = None;
+                                RootReport {
+                                    root: root.clone(),
+                                    error: Some(err.to_string()),
+                                    ..Default::default()
+                                }
+                            });
+                        progress.finish();
+                        roots.lock().unwrap_or_else(|e| e.into_inner())[index] = report;
+                    }
+                });
+            }
+        });
+        HostReport {
+            host: host.host.clone(),
+            roots: roots.into_inner().unwrap_or_else(|e| e.into_inner()),
+            duration: start.elapsed(),
+        }
+    }
+
+    fn clean_root(
+        &self,
+        session: &mut Option<RemoteSession>,
+        host: &str,
+        options: &RemoteOptions,
+        root: &str,
+        progress: &dyn Progress,
+    ) -> Result<RootReport> {
+        if options
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            let message = format!("{}: the host's deadline passed before this root", host);
+            return Err(McError::Remote(message));
+        }
+        let session = match session {
+            Some(session) => session,
+            None => session.insert(RemoteSession::connect(host, options)?),
+        };
+        let plan = session.plan(root, relay(progress))?;
+        if self.dry_run || plan.items == 0 {
+            return Ok(RootReport {
+                root: root.to_string(),
+                bytes_freed: plan.bytes,
+                items_deleted: plan.items as u64,
+                ..Default::default()
+            });
+        }
+        let report = session.apply(&plan, relay(progress))?;
+        Ok(RootReport {
+            root: root.to_string(),
+            bytes_freed: report["bytes_freed"].as_u64().unwrap_or(0),
+            items_deleted: report["items_deleted"].as_u64().unwrap_or(0),
+            item_errors: report["errors"].as_array().map_or(0, Vec::len),
+            error: None,
+        })
+    }
+}
+
+/// Passes the progress notifications of a remote request on to `progress`.
+fn relay(progress: &dyn Progress) -> impl FnMut(RemoteProgress) + '_ {
+    let mut phase = String::new();
+    let mut done = 0;
+    move |update| {
+        if update.phase != phase {
+            progress.begin_phase(&update.phase, update.total);
+            phase = update.phase;
+            done = 0;
+        } else if let Some(total) = update.total {
+            progress.set_total(total);
+        }
+        progress.increment(update.done.saturating_sub(done));
+        done = done.max(update.done);
+    }
+}
+
+/// Takes the next index below `len`, if any are left.
+fn claim(next: &AtomicUsize, len: usize) -> Option<usize> {
+    Some(next.fetch_add(1, Ordering::Relaxed)).filter(|&index| index < len)
+}
+
+/// Renders `reports` as a table with a row per host and a total row.
+pub fn render_table(reports: &[HostReport]) -> String {
+    let mut rows: Vec<[String; 5]> = vec![[
+        "HOST".to_string(),
+        "FREED".to_string(),
+        "ITEMS".to_string(),
+        "ERRORS".to_string(),
+        "DURATION".to_string(),
+    ]];
+    for report in reports {
+        rows.push([
+            report.host.clone(),
+            format::size(report.bytes_freed()),
+            report.items_deleted().to_string(),
+            report.errors().to_string(),
+            format!("{:.1}s", report.duration.as_secs_f64()),
+        ]);
+    }
+    let longest = reports
+        .iter()
+        .map(|report| report.duration)
+        .max()
+        .unwrap_or_default();
+    rows.push([
+        "TOTAL".to_string(),
+        format::size(reports.iter().map(HostReport::bytes_freed).sum::<u64>()),
+        reports
+            .iter()
+            .map(HostReport::items_deleted)
+            .sum::<u64>()
+            .to_string(),
+        reports
+            .iter()
+            .map(HostReport::errors)
+            .sum::<usize>()
+            .to_string(),
+        format!("{:.1}s", longest.as_secs_f64()),
+    ]);
+
+    let widths: Vec<usize> = (0..5)
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+    let mut table = String::new();
+    for row in &rows {
+        let _ = write!(table, "{:<width$}", row[0], width = widths[0]);
+        for (cell, width) in row.iter().zip(&widths).skip(1) {
+            let _ = write!(table, "  {:>width$}", cell, width = width);
+        }
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_load_checks_the_inventory() {
+        let temp = TempDir::new().unwrap();
+        let hosts = temp.child("hosts.toml");
+        hosts
+            .write_str("[[hosts]]\nhost = \"farm-01\"\nroots = [\"/srv\"]\nconcurrency = 2\n")
+            .unwrap();
+        let config = FleetConfig::load(hosts.path()).unwrap();
+        assert_eq!(config.concurrency, 4);
+        assert_eq!(config.hosts[0].concurrency, 2);
+        assert_eq!(config.root_count(), 1);
+
+        hosts
+            .write_str("[[hosts]]\nhost = \"farm-01\"\nroots = []\n")
+            .unwrap();
+        assert!(FleetConfig::load(hosts.path()).is_err());
+        hosts.write_str("hosts = []\n").unwrap();
+        assert!(FleetConfig::load(hosts.path()).is_err());
+    }
+
+    #[test]
+    fn test_render_table_totals_hosts() {
+        let host = |name: &str, bytes_freed, error: Option<&str>| HostReport {
+            host: name.to_string(),
+            roots: vec![RootReport {
+                root: "/srv".to_string(),
+                bytes_freed,
+                items_deleted: 2,
+                item_errors: 1,
+                error: error.map(str::to_string),
+            }],
+            duration: Duration::from_millis(1500),
+        };
+        let table = render_table(&[host("farm-01", 2_000_000, None), host("b", 0, Some("x"))]);
+        let lines: Vec<&str> = table.lines().collect();
+
+        let cells = |line: &str| {
+            line.split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            cells(lines[0]),
+            ["HOST", "FREED", "ITEMS", "ERRORS", "DURATION"]
+        );
+        assert_eq!(cells(lines[1]), ["farm-01", "2", "MB", "2", "1", "1.5s"]);
+        assert_eq!(cells(lines[2]), ["b", "0", "B", "2", "2", "1.5s"]);
+        assert_eq!(cells(lines[3]), ["TOTAL", "2", "MB", "4", "3", "1.5s"]);
+        assert_eq!(lines[0].find("DURATION").unwrap() + 8, lines[1].len());
+    }
+
+    #[test]
+    fn test_relay_starts_phases_and_counts_steps() {
+        /// Records the events relayed to it.
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl Progress for Recorder {
+            fn increment(&self, delta: u64) {
+                self.0.lock().unwrap().push(format!("+{}", delta));
+            }
+            fn set_message(&self, _msg: &str) {}
+            fn finish(&self) {}
+            fn begin_phase(&self, name: &str, total: Option<u64>) {
+                self.0.lock().unwrap().push(format!("{} {:?}", name, total));
+            }
+        }
+
+        let recorder = Recorder::default();
+        let mut on_progress = relay(&recorder);
+        for (phase, done, total) in [("scan", 3, None), ("scan", 5, None), ("clean", 2, Some(5))] {
+            on_progress(RemoteProgress {
+                phase: phase.to_string(),
+                done,
+                total,
+            });
+        }
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(events, ["scan None", "+3", "+2", "clean Some(5)", "+2"]);
+    }
+}
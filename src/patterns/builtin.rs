@@ -24,15 +24,22 @@ pub static BUILTIN_PATTERNS: Lazy<PatternSet> = Lazy::new(|| {
             // Dependencies
             ("node_modules", PatternCategory::Dependencies),
             (".venv", PatternCategory::Dependencies),
+            ("venv", PatternCategory::Dependencies),
             ("vendor", PatternCategory::Dependencies),
             // Cache
             (".turbo", PatternCategory::Cache),
             (".bun", PatternCategory::Cache),
             (".pytest_cache", PatternCategory::Cache),
+            ("__pycache__", PatternCategory::Cache),
+            (".tox", PatternCategory::Cache),
+            (".mypy_cache", PatternCategory::Cache),
+            (".ruff_cache", PatternCategory::Cache),
             (".benchmark-cache", PatternCategory::Cache),
             ("coverage", PatternCategory::Cache),
             (".ropeproject", PatternCategory::Cache),
             (".ruby-lsp", PatternCategory::Cache),
+            // Left behind when a clean renaming directories aside is interrupted
+            (".mc-deleting-*", PatternCategory::Other),
             // IDE and Tools
             (".idea", PatternCategory::IDE),
             (".flock", PatternCategory::IDE),
@@ -61,6 +68,39 @@ pub static BUILTIN_PATTERNS: Lazy<PatternSet> = Lazy::new(|| {
     }
 });
 
+/// An opt-in pattern pack for container build caches, enabled by `[rules.docker]`.
+pub static DOCKER_PATTERNS: Lazy<PatternSet> = Lazy::new(|| PatternSet {
+    categorized_dirs: vec![
+        // `docker buildx build --cache-to type=local,dest=...`
+        (".buildx-cache", PatternCategory::Cache),
+        (".buildx-cache-new", PatternCategory::Cache),
+        // Build caches checked out inside repositories
+        (".docker", PatternCategory::Cache),
+        // `kaniko --cache-dir`
+        ("kaniko-cache", PatternCategory::Cache),
+        (".kaniko-cache", PatternCategory::Cache),
+    ],
+    categorized_files: vec![],
+    exclude: vec![],
+});
+
+/// An opt-in pattern pack for machine learning artifacts, enabled by `[rules.ml]`.
+pub static ML_PATTERNS: Lazy<PatternSet> = Lazy::new(|| PatternSet {
+    categorized_dirs: vec![
+        ("wandb", PatternCategory::ML),
+        ("mlruns", PatternCategory::ML),
+        ("lightning_logs", PatternCategory::ML),
+        // Project-local Hugging Face hub caches (`HF_HOME=.cache/huggingface`); a bare
+        // `huggingface` directory is as likely to be source code
+        ("**/.cache/huggingface", PatternCategory::ML),
+    ],
+    categorized_files: vec![
+        ("*.ckpt", PatternCategory::ML),
+        ("*.safetensors", PatternCategory::ML),
+    ],
+    exclude: vec![],
+});
+
 /// A structure to hold the different types of built-in patterns.
 ///
 /// These patterns are defined as string slices for maximum efficiency, as they
@@ -0,0 +1,174 @@
+//! Static checks that find include patterns which can never take effect.
+//!
+//! Include patterns are tried in priority order and the first match wins, so a pattern
+//! can be dead weight in a long-lived configuration without ever causing an error:
+//!
+//! - **Duplicate**: the same pattern appears earlier in the same list.
+//! - **Shadowed**: an earlier, broader pattern matches every name this one matches.
+//! - **Always excluded**: an exclude pattern matches every name this one matches.
+//!
+//! Only literal patterns (no `*`, `?`, or `[`) are checked for shadowing and
+//! exclusion, since for those the set of matching names is known exactly. Scan
+//! evidence (patterns that matched nothing under a given tree) comes from
+//! [`ScanOutcome::pattern_stats`](crate::types::ScanOutcome::pattern_stats).
+
+use crate::patterns::matcher::IncludePattern;
+use crate::patterns::PatternMatcher;
+use serde::Serialize;
+use std::fmt;
+
+/// A problem found with an include pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PatternIssue {
+    /// The pattern the issue is about.
+    pub pattern: String,
+    /// What is wrong with it.
+    pub kind: PatternIssueKind,
+}
+
+/// The kinds of [`PatternIssue`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "by")]
+pub enum PatternIssueKind {
+    /// The same pattern appears earlier in the list.
+    Duplicate,
+    /// An earlier pattern matches every name this pattern matches.
+    Shadowed(String),
+    /// An exclude pattern matches every name this pattern matches.
+    AlwaysExcluded(String),
+    /// The pattern matched nothing in a scan.
+    NoMatches,
+}
+
+impl fmt::Display for PatternIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PatternIssueKind::Duplicate => {
+                write!(
+                    f,
+                    "`{}` is listed more than once; remove the duplicate",
+                    self.pattern
+                )
+            }
+            PatternIssueKind::Shadowed(by) => write!(
+                f,
+                "`{}` is shadowed by the earlier pattern `{}`; remove it or move it first",
+                self.pattern, by
+            ),
+            PatternIssueKind::AlwaysExcluded(by) => write!(
+                f,
+                "`{}` can never match because `{}` is excluded; remove one of them",
+                self.pattern, by
+            ),
+            PatternIssueKind::NoMatches => write!(
+                f,
+                "`{}` matched nothing in this tree; consider removing it",
+                self.pattern
+            ),
+        }
+    }
+}
+
+/// Finds include patterns of `matcher` that can never take effect.
+pub fn find_issues(matcher: &PatternMatcher) -> Vec<PatternIssue> {
+    let mut issues = Vec::new();
+    for list in matcher.include_lists() {
+        for (idx, include) in list.iter().enumerate() {
+            if let Some(kind) = check(include, &list[..idx], matcher) {
+                issues.push(PatternIssue {
+                    pattern: include.raw.clone(),
+                    kind,
+                });
+            }
+        }
+    }
+    issues
+}
+
+fn check(
+    include: &IncludePattern,
+    earlier: &[IncludePattern],
+    matcher: &PatternMatcher,
+) -> Option<PatternIssueKind> {
+    let raw = include.pattern.as_str();
+    if earlier.iter().any(|other| {
+        other.pattern.as_str() == raw
+            && other.anchored == include.anchored
+            && covers_depth(other, include)
+    }) {
+        return Some(PatternIssueKind::Duplicate);
+    }
+
+    // Anchored and globbed patterns match sets of paths we cannot enumerate.
+    if include.anchored || !is_literal(raw) {
+        return None;
+    }
+    if let Some(exclude) = matcher.name_excludes().iter().find(|p| p.matches(raw)) {
+        return Some(PatternIssueKind::AlwaysExcluded(
+            exclude.as_str().to_string(),
+        ));
+    }
+    earlier
+        .iter()
+        .find(|other| !other.anchored && other.pattern.matches(raw) && covers_depth(other, include))
+        .map(|other| PatternIssueKind::Shadowed(other.raw.clone()))
+}
+
+/// Returns true if `other` matches at every depth `include` does.
+fn covers_depth(other: &IncludePattern, include: &IncludePattern) -> bool {
+    match (other.max_depth, include.max_depth) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(a), Some(b)) => a >= b,
+    }
+}
+
+fn is_literal(raw: &str) -> bool {
+    !raw.contains(['*', '?', '['])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PatternConfig;
+
+    fn issues(directories: &[&str], exclude: &[&str]) -> Vec<PatternIssue> {
+        let config = PatternConfig {
+            directories: directories.iter().map(|s| s.to_string()).collect(),
+            files: vec![],
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            ..PatternConfig::default()
+        };
+        find_issues(&PatternMatcher::new(&config).unwrap())
+    }
+
+    #[test]
+    fn test_finds_duplicate_shadowed_and_excluded_patterns() {
+        let found = issues(
+            &["build*", "dist", "build-cache", "dist", "out", "src/dist"],
+            &["out"],
+        );
+        assert_eq!(
+            found,
+            vec![
+                PatternIssue {
+                    pattern: "build-cache".into(),
+                    kind: PatternIssueKind::Shadowed("build*".into()),
+                },
+                PatternIssue {
+                    pattern: "dist".into(),
+                    kind: PatternIssueKind::Duplicate,
+                },
+                PatternIssue {
+                    pattern: "out".into(),
+                    kind: PatternIssueKind::AlwaysExcluded("out".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_healthy_patterns_have_no_issues() {
+        assert!(issues(&["dist", "target", "*/vendor"], &[".git"]).is_empty());
+    }
+}
@@ -9,15 +9,37 @@
 //! 1.  Check if the path matches any exclusion patterns. If it does, the path is ignored.
 //! 2.  If the item is a directory, check it against the directory patterns.
 //! 3.  If the item is a file, check it against the file patterns.
+//! 4.  If no pattern matched, offer the path to the [plugin](crate::plugins) matchers.
 //!
 //! This order of operations ensures that exclusions always take precedence.
+//!
+//! # Anchored Patterns and Depth Limits
+//!
+//! Include patterns containing a `/` are matched against the path relative to the
+//! scan root, with `*` not crossing directories: `*/target` only matches a `target`
+//! directory one level below the root. Any include pattern can also be limited to a
+//! maximum walk depth (the root is depth 0) through `[patterns.max_depth]`.
+//!
+//! # Path Excludes
+//!
+//! Exclude entries containing a `/` are matched against the whole path rather than the
+//! basename. Absolute entries (`/home/me/code/imported-vendor/**`) match as written;
+//! relative entries (`vendor/imported`) are anchored to the scan root set with
+//! [`PatternMatcher::with_root`]. A path exclude protects the matching path, everything
+//! below it, and every directory that contains its literal (glob-free) prefix, so a
+//! protected directory is never removed as part of a matched ancestor. With
+//! `[safety] partial_delete`, containing directories match again and the cleaner
+//! deletes everything in them except the protected paths.
 
-use crate::config::PatternConfig;
-use crate::patterns::BUILTIN_PATTERNS;
+use crate::config::{Config, IncludeKind, PatternConfig};
+use crate::patterns::{PatternSet, BUILTIN_PATTERNS};
+use crate::plugins::Plugins;
 use crate::types::{PatternCategory, PatternMatch, PatternSource};
-use glob::{Pattern, PatternError};
+use glob::{MatchOptions, Pattern, PatternError};
+use std::collections::HashMap;
 use std::fs::FileType;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
 /// A matcher that checks paths against compiled glob patterns.
 ///
@@ -25,11 +47,177 @@ use std::path::Path;
 /// The patterns are pre-compiled into `glob::Pattern` objects for efficient matching.
 pub struct PatternMatcher {
     /// Compiled glob patterns for matching directories with their categories.
-    directory_patterns: Vec<(Pattern, PatternCategory)>,
+    directory_patterns: Vec<IncludePattern>,
     /// Compiled glob patterns for matching files with their categories.
-    file_patterns: Vec<(Pattern, PatternCategory)>,
+    file_patterns: Vec<IncludePattern>,
     /// Compiled glob patterns for excluding items.
     exclude_patterns: Vec<Pattern>,
+    /// Exclude entries that are matched against the whole path.
+    exclude_paths: Vec<PathExclude>,
+    /// The scan root that anchored patterns are relative to.
+    root: Option<PathBuf>,
+    /// Whether directories containing a path exclude are themselves excluded.
+    protect_ancestors: bool,
+    /// Plugins offered the paths no include pattern matches, if any.
+    plugins: Option<Arc<Plugins>>,
+    /// Rebuild notes for matches, keyed by pattern.
+    rebuild_patterns: HashMap<String, Arc<str>>,
+    /// Rebuild notes for matches whose pattern has none, keyed by category.
+    rebuild_categories: HashMap<PatternCategory, Arc<str>>,
+}
+
+/// A compiled include pattern.
+#[derive(Clone)]
+pub(crate) struct IncludePattern {
+    /// The pattern as written, which is what matches report.
+    pub(crate) raw: String,
+    pub(crate) pattern: Pattern,
+    category: PatternCategory,
+    /// Whether the pattern is matched against the root-relative path instead of the
+    /// basename.
+    pub(crate) anchored: bool,
+    /// Whether the pattern was written with a trailing `/` and only matches directories.
+    dir_only: bool,
+    /// The deepest walk depth at which the pattern matches.
+    pub(crate) max_depth: Option<usize>,
+    /// Where the pattern came from.
+    source: PatternSource,
+    /// The match priority; higher wins.
+    priority: u32,
+}
+
+impl IncludePattern {
+    fn new(
+        raw: &str,
+        category: PatternCategory,
+        max_depth: Option<usize>,
+        source: PatternSource,
+    ) -> Result<Self, PatternError> {
+        // As in .gitignore, a trailing `/` restricts the pattern to directories and a
+        // leading `/` anchors it to the scan root: `/dist/` only matches the top-level
+        // dist directory.
+        let dir_pattern = raw.strip_suffix('/').filter(|rest| !rest.is_empty());
+        let unslashed = dir_pattern.unwrap_or(raw);
+        let root_only = unslashed.strip_prefix('/');
+        let glob = root_only.unwrap_or(unslashed);
+        Ok(Self {
+            raw: raw.to_string(),
+            pattern: Pattern::new(glob)?,
+            category,
+            anchored: root_only.is_some() || is_path_pattern(glob),
+            dir_only: dir_pattern.is_some(),
+            max_depth,
+            source,
+            priority: source.default_priority(),
+        })
+    }
+
+    /// Overrides the default priority of the pattern's source.
+    fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn to_match(&self) -> PatternMatch {
+        PatternMatch {
+            pattern: self.raw.clone(),
+            priority: self.priority,
+            source: self.source,
+            category: self.category,
+            rebuild: None,
+        }
+    }
+
+    fn matches(&self, name: &str, relative: &Path, depth: Option<usize>) -> bool {
+        if let (Some(max_depth), Some(depth)) = (self.max_depth, depth) {
+            if depth > max_depth {
+                return false;
+            }
+        }
+        if self.anchored {
+            self.pattern
+                .matches_path_with(relative, path_match_options())
+        } else {
+            self.pattern.matches(name)
+        }
+    }
+}
+
+/// Options for matching whole paths: `*` does not cross directory separators.
+fn path_match_options() -> MatchOptions {
+    MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    }
+}
+
+/// An exclude entry matched against whole paths rather than basenames.
+struct PathExclude {
+    /// The entry as written in the configuration.
+    raw: String,
+    /// The compiled pattern, anchored to the scan root for relative entries.
+    pattern: Pattern,
+    /// The leading part of the entry that contains no glob characters.
+    literal_prefix: PathBuf,
+}
+
+impl PathExclude {
+    /// Compiles `raw`, anchoring relative entries to `root` when given.
+    fn new(raw: &str, root: Option<&Path>) -> Result<Self, PatternError> {
+        let trimmed = raw.strip_suffix("/**").unwrap_or(raw).trim_end_matches('/');
+        let relative = trimmed.strip_prefix("./").unwrap_or(trimmed);
+        let literal: PathBuf = Path::new(relative)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+
+        let (pattern, literal_prefix) = match root {
+            Some(root) if !Path::new(relative).is_absolute() => (
+                format!("{}/{}", Pattern::escape(&root.to_string_lossy()), relative),
+                root.join(literal),
+            ),
+            _ => (relative.to_string(), literal),
+        };
+        Ok(Self {
+            raw: raw.to_string(),
+            pattern: Pattern::new(&pattern)?,
+            literal_prefix,
+        })
+    }
+
+    /// Returns true if `path` or one of its ancestors is protected by this entry, or,
+    /// with `protect_ancestors`, one of its descendants.
+    fn excludes(&self, path: &Path, protect_ancestors: bool) -> bool {
+        let options = path_match_options();
+        let contains_protected = protect_ancestors
+            && self
+                .literal_prefix
+                .components()
+                .any(|c| matches!(c, Component::Normal(_)))
+            && self.literal_prefix.starts_with(path);
+        contains_protected
+            || path
+                .ancestors()
+                .any(|ancestor| self.pattern.matches_path_with(ancestor, options))
+    }
+}
+
+/// Looks up the built-in category of an include pattern, ignoring a root anchor and a
+/// trailing `/`.
+fn category_of(raw: &str) -> PatternCategory {
+    let name = raw.strip_prefix('/').unwrap_or(raw);
+    BUILTIN_PATTERNS.get_category(name.strip_suffix('/').unwrap_or(name))
+}
+
+/// Orders include patterns by descending priority. The sort is stable, so patterns of
+/// equal priority keep their configured order.
+fn sort_by_priority(patterns: &mut [IncludePattern]) {
+    patterns.sort_by_key(|include| std::cmp::Reverse(include.priority));
+}
+
+/// Returns true if a pattern should be matched against whole paths.
+fn is_path_pattern(raw: &str) -> bool {
+    raw.contains('/') || raw.contains(std::path::MAIN_SEPARATOR)
 }
 
 impl PatternMatcher {
@@ -42,11 +230,41 @@ impl PatternMatcher {
     ///
     /// Returns a `PatternError` if any of the provided glob patterns are invalid.
     pub fn new(config: &PatternConfig) -> Result<Self, PatternError> {
-        let matcher = Self {
-            directory_patterns: Self::compile_patterns_with_categories(&config.directories, true)?,
-            file_patterns: Self::compile_patterns_with_categories(&config.files, false)?,
-            exclude_patterns: Self::compile_patterns(&config.exclude)?,
+        let (path_excludes, name_excludes): (Vec<String>, Vec<String>) = config
+            .exclude
+            .iter()
+            .cloned()
+            .partition(|raw| is_path_pattern(raw));
+        let mut directory_patterns =
+            Self::compile_patterns_with_categories(config, &config.directories)?;
+        // Patterns with a trailing `/` only match directories, whichever list they are in.
+        let (dir_only, file_patterns): (Vec<_>, Vec<_>) =
+            Self::compile_patterns_with_categories(config, &config.files)?
+                .into_iter()
+                .partition(|include| include.dir_only);
+        directory_patterns.extend(dir_only);
+        let mut matcher = Self {
+            directory_patterns,
+            file_patterns,
+            exclude_patterns: Self::compile_patterns(&name_excludes)?,
+            exclude_paths: path_excludes
+                .iter()
+                .map(|raw| PathExclude::new(raw, None))
+                .collect::<Result<_, _>>()?,
+            root: None,
+            protect_ancestors: true,
+            plugins: None,
+            rebuild_patterns: HashMap::new(),
+            rebuild_categories: HashMap::new(),
         };
+        for (key, note) in &config.rebuild {
+            let note = Arc::<str>::from(note.as_str());
+            if let Some(category) = PatternCategory::from_name(key) {
+                matcher.rebuild_categories.insert(category, note.clone());
+            }
+            matcher.rebuild_patterns.insert(key.clone(), note);
+        }
+        matcher.sort_includes();
         log::debug!(
             "Compiled {} dir, {} file, {} exclude patterns",
             matcher.directory_patterns.len(),
@@ -56,22 +274,75 @@ impl PatternMatcher {
         Ok(matcher)
     }
 
+    /// Creates a new `PatternMatcher` from a full `Config`.
+    ///
+    /// In addition to the configured patterns, this includes the opt-in pattern packs
+    /// enabled under `[rules]`. With `[safety] partial_delete`, directories that
+    /// contain a path exclude are matched rather than protected.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PatternError` if any of the provided glob patterns are invalid.
+    pub fn from_config(config: &Config) -> Result<Self, PatternError> {
+        let mut matcher = Self::new(&config.patterns)?;
+        matcher.protect_ancestors = !config.safety.partial_delete;
+        for pack in crate::rules::pattern_packs(&config.rules) {
+            matcher.add_pattern_set(pack)?;
+        }
+        Ok(matcher)
+    }
+
+    /// Anchors anchored include patterns and relative path excludes to the scan root.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PatternError` if an anchored exclude cannot be compiled.
+    pub fn with_root(mut self, root: &Path) -> Result<Self, PatternError> {
+        self.exclude_paths = self
+            .exclude_paths
+            .iter()
+            .map(|exclude| PathExclude::new(&exclude.raw, Some(root)))
+            .collect::<Result<_, _>>()?;
+        self.root = Some(root.to_path_buf());
+        Ok(self)
+    }
+
+    /// Offers the paths no include pattern matches to the matchers of `plugins`.
+    ///
+    /// Plugin matches are reported with the pattern `plugin:<name>`, and excludes
+    /// apply to them as to any other match.
+    pub fn with_plugins(mut self, plugins: Option<Arc<Plugins>>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
     /// Compiles a slice of string patterns into a vector of `glob::Pattern`s.
     fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, PatternError> {
         patterns.iter().map(|p| Pattern::new(p)).collect()
     }
 
-    /// Compiles patterns with their categories by looking them up in BUILTIN_PATTERNS.
+    /// Restores priority order after include patterns are added.
+    fn sort_includes(&mut self) {
+        sort_by_priority(&mut self.directory_patterns);
+        sort_by_priority(&mut self.file_patterns);
+    }
+
+    /// Compiles patterns with their categories by looking them up in BUILTIN_PATTERNS,
+    /// and their depth limits, sources and priorities from `config`.
     fn compile_patterns_with_categories(
+        config: &PatternConfig,
         patterns: &[String],
-        _is_dir: bool,
-    ) -> Result<Vec<(Pattern, PatternCategory)>, PatternError> {
+    ) -> Result<Vec<IncludePattern>, PatternError> {
         patterns
             .iter()
             .map(|p| {
-                let pattern = Pattern::new(p)?;
-                let category = BUILTIN_PATTERNS.get_category(p);
-                Ok((pattern, category))
+                IncludePattern::new(
+                    p,
+                    category_of(p),
+                    config.max_depth.get(p).copied(),
+                    config.source_of(p),
+                )
+                .map(|include| include.with_priority(config.priority_of(p)))
             })
             .collect()
     }
@@ -112,14 +383,61 @@ impl PatternMatcher {
         &self,
         path: &Path,
         file_type: Option<FileType>,
+    ) -> Option<PatternMatch> {
+        self.matches_at_depth(path, file_type, None)
+    }
+
+    /// Checks if a given path matches any of the cleaning patterns using a known file type
+    /// and walk depth.
+    ///
+    /// `depth` is the path's depth below the scan root (the root itself is 0), as
+    /// reported by `walkdir`. When it is `None`, it is derived from the root set with
+    /// [`PatternMatcher::with_root`], if any; depth limits are only enforced when the
+    /// depth is known.
+    pub fn matches_at_depth(
+        &self,
+        path: &Path,
+        file_type: Option<FileType>,
+        depth: Option<usize>,
     ) -> Option<PatternMatch> {
         // Check exclusions first
         if self.is_excluded(path) {
             return None;
         }
+        self.include_match(path, file_type, depth)
+    }
 
+    /// Returns the include pattern `path` matches and the exclude entry that keeps it
+    /// from being cleaned, if it matches one but is excluded.
+    ///
+    /// This explains why [`PatternMatcher::matches_at_depth`] returned `None` for a
+    /// path that looks like an artifact.
+    pub fn excluded_match(
+        &self,
+        path: &Path,
+        file_type: Option<FileType>,
+        depth: Option<usize>,
+    ) -> Option<(PatternMatch, String)> {
+        let exclude = self.exclusion(path)?;
+        let pattern_match = self.include_match(path, file_type, depth)?;
+        Some((pattern_match, exclude.to_string()))
+    }
+
+    /// Matches `path` against the include patterns only, ignoring excludes.
+    fn include_match(
+        &self,
+        path: &Path,
+        file_type: Option<FileType>,
+        depth: Option<usize>,
+    ) -> Option<PatternMatch> {
         // Get the file/dir name for matching
         let name = path.file_name()?.to_str()?;
+        let relative = self
+            .root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        let depth = depth.or_else(|| self.root.as_ref().map(|_| relative.components().count()));
 
         let (is_dir_candidate, is_file_candidate) = match file_type {
             Some(file_type) => {
@@ -134,76 +452,160 @@ impl PatternMatcher {
 
         // Check directory patterns
         if is_dir_candidate {
-            for (idx, (pattern, category)) in self.directory_patterns.iter().enumerate() {
-                if pattern.matches(name) {
-                    return Some(PatternMatch {
-                        pattern: pattern.as_str().to_string(),
-                        priority: idx as u32,
-                        source: PatternSource::Config,
-                        category: *category,
-                    });
+            for include in &self.directory_patterns {
+                if include.matches(name, relative, depth) {
+                    return Some(self.noted(include.to_match()));
                 }
             }
         }
 
         // Check file patterns
         if is_file_candidate {
-            for (idx, (pattern, category)) in self.file_patterns.iter().enumerate() {
-                if pattern.matches(name) {
-                    return Some(PatternMatch {
-                        pattern: pattern.as_str().to_string(),
-                        priority: idx as u32,
-                        source: PatternSource::Config,
-                        category: *category,
-                    });
+            for include in &self.file_patterns {
+                if include.matches(name, relative, depth) {
+                    return Some(self.noted(include.to_match()));
                 }
             }
         }
 
-        None
+        let is_dir = file_type.is_some_and(|file_type| file_type.is_dir());
+        let (plugin, category) = self.plugins.as_ref()?.match_path(path, relative, is_dir)?;
+        Some(self.noted(PatternMatch {
+            pattern: format!("plugin:{}", plugin),
+            priority: PatternSource::Config.default_priority(),
+            source: PatternSource::Config,
+            category,
+            rebuild: None,
+        }))
+    }
+
+    /// Adds the rebuild note configured for the pattern of `found`, or else for its
+    /// category, as written under `[patterns.rebuild]`.
+    fn noted(&self, mut found: PatternMatch) -> PatternMatch {
+        if self.rebuild_patterns.is_empty() {
+            return found;
+        }
+        let note = self
+            .rebuild_patterns
+            .get(&found.pattern)
+            .or_else(|| self.rebuild_categories.get(&found.category));
+        found.rebuild = note.cloned();
+        found
+    }
+
+    /// Returns the include patterns, directory patterns first, in priority order.
+    pub fn include_patterns(&self) -> impl Iterator<Item = &str> {
+        self.directory_patterns
+            .iter()
+            .chain(&self.file_patterns)
+            .map(|include| include.raw.as_str())
+    }
+
+    /// Returns the directory and file include patterns, each in priority order.
+    pub(crate) fn include_lists(&self) -> [&[IncludePattern]; 2] {
+        [&self.directory_patterns, &self.file_patterns]
+    }
+
+    /// Returns the exclude patterns matched against basenames.
+    pub(crate) fn name_excludes(&self) -> &[Pattern] {
+        &self.exclude_patterns
     }
 
     /// Checks if a path is excluded by any of the exclusion patterns.
-    fn is_excluded(&self, path: &Path) -> bool {
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        self.exclusion(path).is_some()
+    }
+
+    /// Returns the first exclude entry that excludes `path`, as written.
+    fn exclusion(&self, path: &Path) -> Option<&str> {
         if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            self.exclude_patterns.iter().any(|p| p.matches(name))
-        } else {
-            false
+            if let Some(pattern) = self.exclude_patterns.iter().find(|p| p.matches(name)) {
+                return Some(pattern.as_str());
+            }
         }
+        self.exclude_paths
+            .iter()
+            .find(|exclude| exclude.excludes(path, self.protect_ancestors))
+            .map(|exclude| exclude.raw.as_str())
     }
 
     /// Adds additional include patterns to the matcher at runtime.
     ///
-    /// This is used for merging patterns from the CLI. These patterns are added with
-    /// a lower priority than the configuration file patterns.
+    /// This is used for merging patterns from the CLI. These patterns take precedence
+    /// over configured and built-in patterns. A `dir:` or `file:` prefix restricts a
+    /// pattern to directories or files; without one it matches both.
     ///
     /// # Errors
     ///
     /// Returns a `PatternError` if any of the provided glob patterns are invalid.
     pub fn add_include_patterns(&mut self, patterns: &[String]) -> Result<(), PatternError> {
-        for pattern_str in patterns {
-            let pattern = Pattern::new(pattern_str)?;
-            let category = BUILTIN_PATTERNS.get_category(pattern_str);
-            // Try to determine if it's a file or directory pattern
-            if pattern_str.contains('.') || pattern_str.contains('*') {
-                self.file_patterns.push((pattern, category));
-            } else {
-                self.directory_patterns.push((pattern, category));
+        for raw in patterns {
+            let (kind, pattern_str) = IncludeKind::parse(raw);
+            let include = IncludePattern::new(
+                pattern_str,
+                category_of(pattern_str),
+                None,
+                PatternSource::CLI,
+            )?;
+            if kind.dirs() || include.dir_only {
+                self.directory_patterns.push(include.clone());
             }
+            if kind.files() && !include.dir_only {
+                self.file_patterns.push(include);
+            }
+        }
+        self.sort_includes();
+        Ok(())
+    }
+
+    /// Adds the patterns of a `PatternSet`, keeping the set's categories.
+    ///
+    /// Pack patterns are treated as built-in, so configured patterns take precedence.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PatternError` if any of the set's glob patterns are invalid.
+    pub fn add_pattern_set(&mut self, set: &PatternSet) -> Result<(), PatternError> {
+        for (pattern, category) in &set.categorized_dirs {
+            self.directory_patterns.push(IncludePattern::new(
+                pattern,
+                *category,
+                None,
+                PatternSource::BuiltIn,
+            )?);
+        }
+        for (pattern, category) in &set.categorized_files {
+            self.file_patterns.push(IncludePattern::new(
+                pattern,
+                *category,
+                None,
+                PatternSource::BuiltIn,
+            )?);
+        }
+        for pattern in &set.exclude {
+            self.exclude_patterns.push(Pattern::new(pattern)?);
         }
+        self.sort_includes();
         Ok(())
     }
 
     /// Adds additional exclude patterns to the matcher at runtime.
     ///
-    /// This is used for merging patterns from the CLI.
+    /// This is used for merging patterns from the CLI. Relative path excludes are
+    /// anchored to the scan root, whether it was set by [`with_root`](Self::with_root)
+    /// before or after.
     ///
     /// # Errors
     ///
     /// Returns a `PatternError` if any of the provided glob patterns are invalid.
     pub fn add_exclude_patterns(&mut self, patterns: &[String]) -> Result<(), PatternError> {
         for pattern_str in patterns {
-            self.exclude_patterns.push(Pattern::new(pattern_str)?);
+            if is_path_pattern(pattern_str) {
+                let exclude = PathExclude::new(pattern_str, self.root.as_deref())?;
+                self.exclude_paths.push(exclude);
+            } else {
+                self.exclude_patterns.push(Pattern::new(pattern_str)?);
+            }
         }
         Ok(())
     }
@@ -213,6 +615,7 @@ impl PatternMatcher {
 mod tests {
     use super::*;
     use crate::config::PatternConfig;
+    use std::collections::BTreeMap;
     use std::path::Path;
 
     fn create_matcher(
@@ -224,6 +627,7 @@ mod tests {
             directories: directories.into_iter().map(String::from).collect(),
             files: files.into_iter().map(String::from).collect(),
             exclude: exclude.into_iter().map(String::from).collect(),
+            ..PatternConfig::default()
         };
         PatternMatcher::new(&config).unwrap()
     }
@@ -247,4 +651,254 @@ mod tests {
         assert!(matcher.matches(file_path).is_some());
         assert!(matcher.matches(non_match_path).is_none());
     }
+
+    #[test]
+    fn test_from_config_adds_enabled_packs() {
+        let mut config = Config::default();
+        let path = Path::new(".buildx-cache");
+        assert!(PatternMatcher::from_config(&config)
+            .unwrap()
+            .matches_with_type(path, None)
+            .is_none());
+
+        config.rules.docker.enabled = true;
+        let matched = PatternMatcher::from_config(&config)
+            .unwrap()
+            .matches_with_type(path, None)
+            .unwrap();
+        assert_eq!(matched.category, PatternCategory::Cache);
+    }
+
+    #[test]
+    fn test_huggingface_pattern_matches_cache_layout_only() {
+        let mut config = Config::default();
+        config.rules.ml.enabled = true;
+        let matcher = PatternMatcher::from_config(&config)
+            .unwrap()
+            .with_root(Path::new("/work"))
+            .unwrap();
+
+        let cache = Path::new("/work/app/.cache/huggingface");
+        assert_eq!(
+            matcher.matches_with_type(cache, None).unwrap().category,
+            PatternCategory::ML
+        );
+        assert!(matcher
+            .matches_with_type(Path::new("/work/.cache/huggingface"), None)
+            .is_some());
+        assert!(matcher
+            .matches_with_type(Path::new("/work/app/src/huggingface"), None)
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_absolute_path_exclude() {
+        let matcher = create_matcher(
+            vec!["node_modules", "vendor"],
+            vec![],
+            vec!["/home/me/code/imported-vendor/**"],
+        );
+
+        assert!(matcher
+            .matches_with_type(
+                Path::new("/home/me/code/imported-vendor/node_modules"),
+                None
+            )
+            .is_none());
+        assert!(matcher
+            .matches_with_type(Path::new("/home/me/code/app/node_modules"), None)
+            .is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_path_exclude_is_root_anchored() {
+        let matcher = create_matcher(vec!["vendor", "node_modules"], vec![], vec!["app/vendor"])
+            .with_root(Path::new("/work"))
+            .unwrap();
+
+        assert!(matcher
+            .matches_with_type(Path::new("/work/app/vendor"), None)
+            .is_none());
+        assert!(matcher
+            .matches_with_type(Path::new("/work/lib/app/vendor"), None)
+            .is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_added_path_exclude_is_root_anchored() {
+        let mut matcher = create_matcher(vec!["vendor"], vec![], vec![])
+            .with_root(Path::new("/work"))
+            .unwrap();
+        matcher
+            .add_exclude_patterns(&["app/vendor".to_string()])
+            .unwrap();
+
+        assert!(matcher
+            .matches_with_type(Path::new("/work/app/vendor"), None)
+            .is_none());
+        assert!(matcher
+            .matches_with_type(Path::new("/work/lib/app/vendor"), None)
+            .is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_exclude_protects_containing_directory() {
+        let matcher = create_matcher(
+            vec!["node_modules"],
+            vec![],
+            vec!["/work/node_modules/.cache/keep"],
+        );
+
+        assert!(matcher
+            .matches_with_type(Path::new("/work/node_modules"), None)
+            .is_none());
+        assert!(matcher
+            .matches_with_type(Path::new("/other/node_modules"), None)
+            .is_some());
+    }
+
+    #[test]
+    fn test_partial_delete_matches_containing_directory() {
+        let mut config = Config::default();
+        config.patterns.exclude = vec!["/work/node_modules/.cache/keep".to_string()];
+        config.safety.partial_delete = true;
+        let matcher = PatternMatcher::from_config(&config).unwrap();
+
+        assert!(matcher
+            .matches_with_type(Path::new("/work/node_modules"), None)
+            .is_some());
+        assert!(matcher.is_excluded(Path::new("/work/node_modules/.cache/keep")));
+        assert!(!matcher.is_excluded(Path::new("/work/node_modules/.cache")));
+    }
+
+    #[test]
+    fn test_anchored_include_pattern() {
+        let matcher = create_matcher(vec!["*/target"], vec![], vec![])
+            .with_root(Path::new("/work"))
+            .unwrap();
+
+        assert!(matcher
+            .matches_with_type(Path::new("/work/app/target"), None)
+            .is_some());
+        assert!(matcher
+            .matches_with_type(Path::new("/work/target"), None)
+            .is_none());
+        assert!(matcher
+            .matches_with_type(Path::new("/work/app/vendor/dep/target"), None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_root_only_include_pattern() {
+        let matcher = create_matcher(vec!["/dist"], vec![], vec![])
+            .with_root(Path::new("/work"))
+            .unwrap();
+
+        let found = matcher
+            .matches_with_type(Path::new("/work/dist"), None)
+            .unwrap();
+        assert_eq!(found.pattern, "/dist");
+        assert_eq!(found.category, PatternCategory::BuildOutputs);
+        assert!(matcher
+            .matches_with_type(Path::new("/work/vendor/lib/dist"), None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_trailing_slash_matches_directories_only() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir_all(temp.path().join("build")).unwrap();
+        std::fs::create_dir_all(temp.path().join("src")).unwrap();
+        std::fs::write(temp.path().join("src/build"), "").unwrap();
+        let matcher = create_matcher(vec![], vec!["build/"], vec![]);
+
+        let found = matcher.matches(&temp.path().join("build")).unwrap();
+        assert_eq!(found.pattern, "build/");
+        assert!(matcher.matches(&temp.path().join("src/build")).is_none());
+    }
+
+    #[test]
+    fn test_pattern_max_depth() {
+        let config = PatternConfig {
+            directories: vec!["vendor".to_string()],
+            files: vec![],
+            exclude: vec![],
+            max_depth: BTreeMap::from([("vendor".to_string(), 2)]),
+            ..PatternConfig::default()
+        };
+        let matcher = PatternMatcher::new(&config).unwrap();
+        let path = Path::new("vendor");
+
+        assert!(matcher.matches_at_depth(path, None, Some(2)).is_some());
+        assert!(matcher.matches_at_depth(path, None, Some(3)).is_none());
+    }
+
+    #[test]
+    fn test_cli_pattern_takes_precedence() {
+        let mut config = Config::default().patterns;
+        config
+            .sources
+            .insert("*.log".to_string(), PatternSource::Config);
+        let mut matcher = PatternMatcher::new(&config).unwrap();
+        matcher
+            .add_include_patterns(&["debug*.log".to_string()])
+            .unwrap();
+
+        let found = matcher
+            .matches_with_type(Path::new("debug-1.log"), None)
+            .unwrap();
+        assert_eq!(found.pattern, "debug*.log");
+        assert_eq!(found.source, PatternSource::CLI);
+        assert_eq!(found.priority, PatternSource::CLI.default_priority());
+
+        let found = matcher
+            .matches_with_type(Path::new("app.log"), None)
+            .unwrap();
+        assert_eq!(found.source, PatternSource::Config);
+    }
+
+    #[test]
+    fn test_explicit_priority_override() {
+        let config = PatternConfig {
+            directories: vec![],
+            files: vec!["*.log".to_string(), "app.*".to_string()],
+            exclude: vec![],
+            priority: BTreeMap::from([("app.*".to_string(), 150)]),
+            ..PatternConfig::default()
+        };
+        let matcher = PatternMatcher::new(&config).unwrap();
+
+        let found = matcher
+            .matches_with_type(Path::new("app.log"), None)
+            .unwrap();
+        assert_eq!(found.pattern, "app.*");
+        assert_eq!(found.priority, 150);
+        assert_eq!(found.source, PatternSource::BuiltIn);
+    }
+
+    #[test]
+    fn test_rebuild_notes_by_pattern_then_category() {
+        let mut config = Config::default().patterns;
+        config.rebuild = BTreeMap::from([
+            ("node_modules".to_string(), "npm ci ~2min".to_string()),
+            ("Dependencies".to_string(), "reinstall".to_string()),
+        ]);
+        let matcher = PatternMatcher::new(&config).unwrap();
+
+        let found = matcher
+            .matches_with_type(Path::new("node_modules"), None)
+            .unwrap();
+        assert_eq!(found.rebuild.as_deref(), Some("npm ci ~2min"));
+        let found = matcher.matches_with_type(Path::new(".venv"), None).unwrap();
+        assert_eq!(found.category, PatternCategory::Dependencies);
+        assert_eq!(found.rebuild.as_deref(), Some("reinstall"));
+        let found = matcher
+            .matches_with_type(Path::new("app.log"), None)
+            .unwrap();
+        assert_eq!(found.rebuild, None);
+    }
 }
@@ -1,5 +1,6 @@
 pub mod builtin;
+pub mod lint;
 pub mod matcher;
 
-pub use builtin::{PatternSet, BUILTIN_PATTERNS};
+pub use builtin::{PatternSet, BUILTIN_PATTERNS, DOCKER_PATTERNS, ML_PATTERNS};
 pub use matcher::PatternMatcher;
@@ -0,0 +1,402 @@
+//! This module loads third-party WASM plugins, with the `plugins` feature, that add
+//! matchers, removal backends and report sinks to mc.
+//!
+//! With `[plugins] enabled = true`, every `*.wasm` file in the plugin directory
+//! (`~/.config/mc/plugins/` unless `dir` says otherwise) is loaded, in name order, as a
+//! plugin named after its file stem. A plugin is a core WebAssembly module that
+//! exports its `memory` and `mc_alloc(len) -> ptr`, through which mc hands it a JSON
+//! document, and any of these entry points, each taking the document's `(ptr, len)`
+//! and returning an `i32`:
+//!
+//! - `mc_match` is offered every path no pattern matches, as
+//!   `{"path", "relative", "is_dir"}`. It returns 0 to leave the path alone, or the
+//!   category to clean it under: 1 `Dependencies`, 2 `BuildOutputs`, 3 `Cache`,
+//!   4 `IDE`, 5 `Logs`, 6 `ML` or 7 `Other`. Excludes still apply to what it matches.
+//! - `mc_remove` is offered each item of a real clean as its [`CleanItem`] JSON. It
+//!   returns 0 to leave the item to mc, 1 once it removed the item, or a negative
+//!   number if removing it failed; an item still there after a 1 counts as failed.
+//!   Plugins are not offered items while secure deletion or `[safety]
+//!   partial_delete` is in effect.
+//! - `mc_report` receives the [`CleanReport`] JSON after each real clean.
+//!
+//! A plugin may also export `mc_free(ptr, len)`, called once mc is done with a
+//! document. Plugins run without WASI: the only functions they can import are these
+//! from the `mc` module, which make up their capabilities:
+//!
+//! - `log(ptr, len)` logs a message at the info level.
+//! - `remove_path(ptr, len) -> i32` removes a path, but only inside the item handed
+//!   to `mc_remove` and never through a symbolic link. It returns 0 on success, -1 if
+//!   the path is not the plugin's to remove and -2 if removal failed.
+//! - `write_output(ptr, len) -> i32` appends to `plugins/<name>.out` in mc's data
+//!   directory, such as `~/.local/share/mc/plugins/`, returning 0 or -2.
+//!
+//! Parallel calls run in separate instances of a plugin, so a plugin must not rely on
+//! state kept between calls. Each call may run at most [`FUEL_PER_CALL`]
+//! instructions and an instance may use at most [`MAX_MEMORY`] bytes of memory. A
+//! plugin that fails a call is logged and treated as having declined. mc refuses to
+//! run when plugins are enabled and one cannot be loaded, or when it was built
+//! without the `plugins` feature.
+
+#[cfg(feature = "plugins")]
+mod wasm;
+
+use crate::config::PluginsConfig;
+use crate::types::{CleanItem, CleanReport, McError, PatternCategory, Result};
+use crate::utils::fs as fs_utils;
+use directories::ProjectDirs;
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "plugins")]
+use wasm::Plugin;
+
+/// The instructions one call of a plugin may run, which stops endless loops.
+pub const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// The bytes of linear memory an instance of a plugin may grow to.
+pub const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// The categories `mc_match` returns, from 1.
+const CATEGORIES: [PatternCategory; 7] = [
+    PatternCategory::Dependencies,
+    PatternCategory::BuildOutputs,
+    PatternCategory::Cache,
+    PatternCategory::IDE,
+    PatternCategory::Logs,
+    PatternCategory::ML,
+    PatternCategory::Other,
+];
+
+/// An entry point a plugin may export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Export {
+    Match,
+    Remove,
+    Report,
+}
+
+impl Export {
+    fn name(self) -> &'static str {
+        match self {
+            Export::Match => "mc_match",
+            Export::Remove => "mc_remove",
+            Export::Report => "mc_report",
+        }
+    }
+}
+
+/// Without the `plugins` feature no plugin can be loaded.
+#[cfg(not(feature = "plugins"))]
+enum Plugin {}
+
+#[cfg(not(feature = "plugins"))]
+impl Plugin {
+    fn load(_path: &Path) -> std::result::Result<Self, String> {
+        Err("mc was built without the `plugins` feature".to_string())
+    }
+
+    fn name(&self) -> &str {
+        match *self {}
+    }
+
+    fn call(
+        &self,
+        _export: Export,
+        _input: &[u8],
+        _removable: Option<&Path>,
+    ) -> Option<std::result::Result<i32, String>> {
+        match *self {}
+    }
+}
+
+/// The loaded plugins, shared by the matcher and the cleaner.
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    /// Loads the plugins `config` enables. Returns `None` when plugins are disabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if mc was built without the `plugins` feature, or a plugin
+    /// cannot be loaded.
+    pub fn from_config(config: &PluginsConfig) -> Result<Option<Arc<Self>>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        if cfg!(not(feature = "plugins")) {
+            return Err(McError::Plugin(
+                "plugins.enabled is set, but mc was built without the `plugins` feature"
+                    .to_string(),
+            ));
+        }
+        let dir = match &config.dir {
+            Some(dir) => fs_utils::expand_home(dir),
+            None => default_dir().ok_or_else(|| {
+                McError::Plugin("cannot find the configuration directory".to_string())
+            })?,
+        };
+        Self::load_dir(&dir).map(|plugins| Some(Arc::new(plugins)))
+    }
+
+    /// Loads every `*.wasm` file in `dir`, in name order. A missing directory holds
+    /// no plugins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` cannot be read or a plugin cannot be loaded.
+    pub fn load_dir(dir: &Path) -> Result<Self> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+                .collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        paths.sort();
+        let plugins = paths
+            .iter()
+            .map(|path| {
+                Plugin::load(path)
+                    .map_err(|err| McError::Plugin(format!("{}: {}", path.display(), err)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        log::debug!("Loaded {} plugins from {}", plugins.len(), dir.display());
+        Ok(Self { plugins })
+    }
+
+    /// Returns the names of the loaded plugins.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.plugins.iter().map(Plugin::name)
+    }
+
+    /// Returns the first plugin that matches `path`, with the category it chose.
+    /// `relative` is the path below the scan root.
+    pub fn match_path(
+        &self,
+        path: &Path,
+        relative: &Path,
+        is_dir: bool,
+    ) -> Option<(&str, PatternCategory)> {
+        let input = json!({ "path": path, "relative": relative, "is_dir": is_dir });
+        let input = serde_json::to_vec(&input).ok()?;
+        self.plugins.iter().find_map(|plugin| {
+            let code = called(
+                plugin,
+                Export::Match,
+                plugin.call(Export::Match, &input, None),
+            )?;
+            let category = usize::try_from(code)
+                .ok()
+                .and_then(|code| code.checked_sub(1))
+                .and_then(|index| CATEGORIES.get(index));
+            match (code, category) {
+                (0, _) => None,
+                (_, Some(&category)) => Some((plugin.name(), category)),
+                _ => {
+                    log::warn!(
+                        "Plugin {} returned unknown category {}",
+                        plugin.name(),
+                        code
+                    );
+                    None
+                }
+            }
+        })
+    }
+
+    /// Offers `item` to the removal backends in turn. Returns `None` if none removed
+    /// it, and otherwise the bytes freed or the error the plugin reported.
+    pub fn remove(&self, item: &CleanItem) -> Option<io::Result<u64>> {
+        let input = serde_json::to_vec(item).ok()?;
+        self.plugins.iter().find_map(|plugin| {
+            let result = plugin.call(Export::Remove, &input, Some(&item.path));
+            match called(plugin, Export::Remove, result)? {
+                0 => None,
+                1 if fs::symlink_metadata(&item.path).is_ok() => {
+                    Some(Err(io::Error::other(format!(
+                        "plugin {} reported removing it, but it is still there",
+                        plugin.name()
+                    ))))
+                }
+                1 => {
+                    log::debug!("Plugin {} removed {}", plugin.name(), item.path.display());
+                    Some(Ok(item.size))
+                }
+                code => Some(Err(io::Error::other(format!(
+                    "plugin {} failed to remove it (code {})",
+                    plugin.name(),
+                    code
+                )))),
+            }
+        })
+    }
+
+    /// Hands `report` to every report sink.
+    pub fn report(&self, report: &CleanReport) {
+        let Ok(input) = serde_json::to_vec(report) else {
+            return;
+        };
+        for plugin in &self.plugins {
+            called(
+                plugin,
+                Export::Report,
+                plugin.call(Export::Report, &input, None),
+            );
+        }
+    }
+}
+
+/// Unwraps the result of calling `export`, logging a failed call. Returns `None` if
+/// the plugin does not export it or the call failed.
+fn called(
+    plugin: &Plugin,
+    export: Export,
+    result: Option<std::result::Result<i32, String>>,
+) -> Option<i32> {
+    match result? {
+        Ok(code) => Some(code),
+        Err(err) => {
+            log::warn!(
+                "Plugin {} failed in {}: {}",
+                plugin.name(),
+                export.name(),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Returns the default plugin directory, `plugins` in the configuration directory.
+fn default_dir() -> Option<PathBuf> {
+    ProjectDirs::from("com", "mc", "mc").map(|dirs| dirs.config_dir().join("plugins"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_plugins_are_not_loaded() {
+        let config = PluginsConfig {
+            enabled: false,
+            dir: Some(PathBuf::from("/nonexistent")),
+        };
+        assert!(Plugins::from_config(&config).unwrap().is_none());
+
+        let enabled = PluginsConfig {
+            enabled: true,
+            ..config
+        };
+        let result = Plugins::from_config(&enabled);
+        if cfg!(feature = "plugins") {
+            assert_eq!(result.unwrap().unwrap().names().count(), 0);
+        } else {
+            assert!(matches!(result, Err(McError::Plugin(_))));
+        }
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_plugins_are_sandboxed() {
+        use crate::test_support::clean_item;
+        use crate::types::ItemType;
+        use assert_fs::prelude::*;
+        use assert_fs::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let item = temp.child("item");
+        item.child("blob").write_str("data").unwrap();
+        let outside = temp.child("outside");
+        outside.write_str("keep").unwrap();
+        let (item_path, outside_path) = (item.path().display(), outside.path().display());
+        // Matches everything as a cache, and removes its item after failing to
+        // remove a path outside it.
+        let module = format!(
+            r#"(module
+                (import "mc" "remove_path" (func $remove (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{item_path}")
+                (data (i32.const 512) "{outside_path}")
+                (func (export "mc_alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "mc_match") (param i32 i32) (result i32) i32.const 3)
+                (func (export "mc_remove") (param i32 i32) (result i32)
+                    (if (i32.ne (call $remove (i32.const 512) (i32.const {outside_len}))
+                                (i32.const -1))
+                        (then (return (i32.const -5))))
+                    (if (result i32) (call $remove (i32.const 0) (i32.const {item_len}))
+                        (then (i32.const -6))
+                        (else (i32.const 1)))))"#,
+            item_len = item_path.to_string().len(),
+            outside_len = outside_path.to_string().len(),
+        );
+        let dir = temp.child("plugins");
+        dir.child("sweep.wasm")
+            .write_binary(&wat::parse_str(module).unwrap())
+            .unwrap();
+        let plugins = Plugins::load_dir(dir.path()).unwrap();
+
+        let matched = plugins.match_path(item.path(), Path::new("item"), true);
+        assert_eq!(matched, Some(("sweep", PatternCategory::Cache)));
+        let planned = clean_item(item.path(), ItemType::Directory, 4, PatternCategory::Cache);
+        assert_eq!(plugins.remove(&planned).unwrap().unwrap(), 4);
+        assert!(!item.path().exists());
+        assert!(outside.path().exists());
+
+        // Plugins get no WASI.
+        let wasi = r#"(module (import "wasi_snapshot_preview1" "proc_exit" (func (param i32))))"#;
+        dir.child("wasi.wasm")
+            .write_binary(&wat::parse_str(wasi).unwrap())
+            .unwrap();
+        assert!(matches!(
+            Plugins::load_dir(dir.path()),
+            Err(McError::Plugin(_))
+        ));
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_removals_are_verified_and_calls_run_in_parallel() {
+        use crate::test_support::clean_item;
+        use crate::types::ItemType;
+        use assert_fs::prelude::*;
+        use assert_fs::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        temp.child("item/blob").write_str("data").unwrap();
+        // Claims every item removed without touching it.
+        let module = r#"(module
+            (memory (export "memory") 1)
+            (func (export "mc_alloc") (param i32) (result i32) i32.const 0)
+            (func (export "mc_match") (param i32 i32) (result i32) i32.const 3)
+            (func (export "mc_remove") (param i32 i32) (result i32) i32.const 1))"#;
+        let dir = temp.child("plugins");
+        dir.child("liar.wasm")
+            .write_binary(&wat::parse_str(module).unwrap())
+            .unwrap();
+        let plugins = Plugins::load_dir(dir.path()).unwrap();
+
+        let item = clean_item(
+            temp.path().join("item"),
+            ItemType::Directory,
+            4,
+            PatternCategory::Cache,
+        );
+        assert!(plugins.remove(&item).unwrap().is_err());
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let matched = plugins.match_path(temp.path(), Path::new(""), true);
+                    assert_eq!(matched, Some(("liar", PatternCategory::Cache)));
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,291 @@
+//! Runs plugins with wasmtime.
+
+use super::{Export, FUEL_PER_CALL, MAX_MEMORY};
+use directories::ProjectDirs;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use wasmtime::{
+    Caller, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+/// The largest document a plugin may pass to an import.
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+/// Returned by an import refusing what the plugin asked for.
+const DENIED: i32 = -1;
+
+/// Returned by an import whose file system operation failed.
+const IO_ERROR: i32 = -2;
+
+/// A loaded plugin. Each call takes an idle instance, or starts another, so
+/// parallel scan and delete workers do not wait on one another; instances keep no
+/// state between them.
+pub(super) struct Plugin {
+    name: String,
+    module: Module,
+    /// Instances not in a call right now.
+    idle: Mutex<Vec<Instance>>,
+}
+
+/// A plugin's instance and the exports mc calls.
+struct Instance {
+    store: Store<Host>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    free: Option<TypedFunc<(i32, i32), ()>>,
+    entry_points: [Option<TypedFunc<(i32, i32), i32>>; 3],
+}
+
+/// What the imports of a plugin may do.
+struct Host {
+    name: String,
+    limits: StoreLimits,
+    /// The item handed to `mc_remove`, during that call.
+    removable: Option<PathBuf>,
+}
+
+/// Returns the engine shared by all plugins, which meters fuel.
+fn engine() -> Result<&'static Engine, String> {
+    static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+    ENGINE
+        .get_or_init(|| {
+            let mut config = wasmtime::Config::new();
+            config.consume_fuel(true);
+            Engine::new(&config).map_err(|err| err.to_string())
+        })
+        .as_ref()
+        .map_err(String::clone)
+}
+
+impl Plugin {
+    /// Compiles and instantiates the plugin at `path`.
+    pub(super) fn load(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let engine = engine()?;
+        let module = Module::from_file(engine, path).map_err(|err| format!("{:#}", err))?;
+        let instance = Instance::new(engine, &module, &name).map_err(|err| format!("{:#}", err))?;
+        Ok(Self {
+            name,
+            module,
+            idle: Mutex::new(vec![instance]),
+        })
+    }
+
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Calls `export` with `input`, letting the plugin remove paths inside
+    /// `removable`. Returns `None` if the plugin does not export it.
+    pub(super) fn call(
+        &self,
+        export: Export,
+        input: &[u8],
+        removable: Option<&Path>,
+    ) -> Option<Result<i32, String>> {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let mut instance = match idle {
+            Some(instance) => instance,
+            None => match engine().and_then(|engine| {
+                Instance::new(engine, &self.module, &self.name).map_err(|err| format!("{:#}", err))
+            }) {
+                Ok(instance) => instance,
+                Err(err) => return Some(Err(err)),
+            },
+        };
+        let func = instance.entry_points[export as usize].clone();
+        let result = func.map(|func| {
+            instance
+                .call(&func, input, removable)
+                .map_err(|err| format!("{:#}", err))
+        });
+        // An instance whose call failed may be left broken, so it is not reused.
+        if matches!(result, None | Some(Ok(_))) {
+            self.idle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(instance);
+        }
+        result
+    }
+}
+
+impl Instance {
+    fn new(engine: &Engine, module: &Module, name: &str) -> wasmtime::Result<Self> {
+        let mut linker = Linker::new(engine);
+        linker.func_wrap("mc", "log", log)?;
+        linker.func_wrap("mc", "remove_path", remove_path)?;
+        linker.func_wrap("mc", "write_output", write_output)?;
+
+        let host = Host {
+            name: name.to_string(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+            removable: None,
+        };
+        let mut store = Store::new(engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(FUEL_PER_CALL)?;
+        let instance = linker.instantiate(&mut store, module)?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("the plugin does not export its memory"))?;
+        let alloc = instance.get_typed_func(&mut store, "mc_alloc")?;
+        let free = instance.get_typed_func(&mut store, "mc_free").ok();
+        let entry_points = [Export::Match, Export::Remove, Export::Report]
+            .map(|export| instance.get_typed_func(&mut store, export.name()).ok());
+        if entry_points.iter().all(Option::is_none) {
+            log::warn!(
+                "Plugin {} exports none of mc_match, mc_remove or mc_report",
+                name
+            );
+        }
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            free,
+            entry_points,
+        })
+    }
+
+    fn call(
+        &mut self,
+        func: &TypedFunc<(i32, i32), i32>,
+        input: &[u8],
+        removable: Option<&Path>,
+    ) -> wasmtime::Result<i32> {
+        let len = i32::try_from(input.len())?;
+        self.store.set_fuel(FUEL_PER_CALL)?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, input)?;
+
+        self.store.data_mut().removable = removable.map(Path::to_path_buf);
+        let result = func.call(&mut self.store, (ptr, len));
+        self.store.data_mut().removable = None;
+        let code = result?;
+        if let Some(free) = &self.free {
+            free.call(&mut self.store, (ptr, len))?;
+        }
+        Ok(code)
+    }
+}
+
+/// Reads the `len` bytes at `ptr` from the memory of the calling plugin.
+fn read_guest(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let start = ptr as u32 as usize;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len <= MAX_MESSAGE)?;
+    memory
+        .data(&caller)
+        .get(start..start.checked_add(len)?)
+        .map(<[u8]>::to_vec)
+}
+
+/// `mc.log(ptr, len)`: logs a message from the plugin.
+fn log(mut caller: Caller<'_, Host>, ptr: i32, len: i32) {
+    if let Some(message) = read_guest(&mut caller, ptr, len) {
+        log::info!(
+            "[{}] {}",
+            caller.data().name,
+            String::from_utf8_lossy(&message)
+        );
+    }
+}
+
+/// `mc.remove_path(ptr, len) -> i32`: removes a path inside the item being removed.
+fn remove_path(mut caller: Caller<'_, Host>, ptr: i32, len: i32) -> i32 {
+    let Some(path) = read_guest(&mut caller, ptr, len)
+        .and_then(|path| String::from_utf8(path).ok())
+        .map(PathBuf::from)
+    else {
+        return DENIED;
+    };
+    let host = caller.data();
+    if !host
+        .removable
+        .as_deref()
+        .is_some_and(|item| is_inside(item, &path))
+    {
+        log::warn!("Plugin {} may not remove {}", host.name, path.display());
+        return DENIED;
+    }
+    let result = fs::symlink_metadata(&path).and_then(|metadata| {
+        if metadata.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        }
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            log::debug!(
+                "Plugin {} failed to remove {}: {}",
+                host.name,
+                path.display(),
+                err
+            );
+            IO_ERROR
+        }
+    }
+}
+
+/// `mc.write_output(ptr, len) -> i32`: appends to the plugin's output file.
+fn write_output(mut caller: Caller<'_, Host>, ptr: i32, len: i32) -> i32 {
+    let Some(data) = read_guest(&mut caller, ptr, len) else {
+        return DENIED;
+    };
+    let name = &caller.data().name;
+    let Some(path) = ProjectDirs::from("com", "mc", "mc").map(|dirs| {
+        dirs.data_local_dir()
+            .join("plugins")
+            .join(format!("{}.out", name))
+    }) else {
+        return IO_ERROR;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| file.write_all(&data));
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            log::warn!(
+                "Plugin {} failed to write {}: {}",
+                name,
+                path.display(),
+                err
+            );
+            IO_ERROR
+        }
+    }
+}
+
+/// Checks that `path` is `item` or below it without passing through a symbolic link,
+/// so a plugin cannot reach outside the item.
+fn is_inside(item: &Path, path: &Path) -> bool {
+    let Ok(rest) = path.strip_prefix(item) else {
+        return false;
+    };
+    let mut current = item.to_path_buf();
+    for component in rest.components() {
+        if !matches!(component, Component::Normal(_)) {
+            return false;
+        }
+        if fs::symlink_metadata(&current).map_or(true, |m| m.file_type().is_symlink()) {
+            return false;
+        }
+        current.push(component);
+    }
+    true
+}
@@ -0,0 +1,273 @@
+//! Finding the projects below a root and what each could give back, for `mc projects`.
+//!
+//! A project is a directory holding a marker such as `Cargo.toml`, `package.json` or
+//! `.git`; the markers also tell its types. Every file belongs to the nearest project
+//! above it, so a workspace member's files count towards the member rather than the
+//! workspace. Directories matching a cleaning pattern are not walked: they are build
+//! artifacts rather than projects, and their size comes from the plan, or is measured,
+//! for those the plan leaves alone.
+
+use crate::config::Config;
+use crate::engine::scanner::skips_traversal;
+use crate::patterns::PatternMatcher;
+use crate::types::{CleanItem, Result};
+use crate::utils::format;
+use crate::utils::fs as fs_utils;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// A project found below the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Project {
+    /// The project directory.
+    pub path: PathBuf,
+    /// The types of project its markers tell, such as `rust` and `node`, sorted.
+    /// Empty for a project only marked by `.git`.
+    pub kinds: Vec<&'static str>,
+    /// The bytes of the files in the project, build artifacts included, not counting
+    /// nested projects.
+    pub total_bytes: u64,
+    /// The bytes a clean would free in the project.
+    pub reclaimable_bytes: u64,
+    /// The number of items a clean would delete in the project.
+    pub reclaimable_items: usize,
+    /// When a file of the project outside its build artifacts was last modified, in
+    /// seconds since the Unix epoch, if any was found.
+    pub last_modified: Option<u64>,
+}
+
+/// Returns the project type a marker file tells, if any.
+pub fn kind_of(marker: &str) -> Option<&'static str> {
+    match marker {
+        "Cargo.toml" => Some("rust"),
+        "package.json" => Some("node"),
+        "pyproject.toml" | "setup.py" => Some("python"),
+        "go.mod" => Some("go"),
+        "Gemfile" => Some("ruby"),
+        "composer.json" => Some("php"),
+        "pom.xml" | "build.gradle" => Some("java"),
+        _ => None,
+    }
+}
+
+/// Finds the projects below `root`, walking it as `config` sets, and attributes the
+/// `planned` items of a clean of `root` to them. Projects are sorted by reclaimable
+/// bytes, largest first, then by path.
+///
+/// # Errors
+///
+/// Returns an error if the patterns in `config` are invalid.
+pub fn survey(root: &Path, config: &Config, planned: &[CleanItem]) -> Result<Vec<Project>> {
+    let matcher = PatternMatcher::from_config(config)?.with_root(root)?;
+    let skip = &config.options.skip_traversal;
+    let mut kinds: BTreeMap<PathBuf, BTreeSet<&'static str>> = BTreeMap::new();
+    let mut artifacts = Vec::new();
+    // The bytes and newest modification time of the files directly in each directory.
+    let mut dirs: HashMap<PathBuf, (u64, Option<SystemTime>)> = HashMap::new();
+
+    let walker = WalkDir::new(root)
+        .max_depth(config.safety.max_depth)
+        .same_file_system(config.options.one_file_system)
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_str().unwrap_or_default();
+            if fs_utils::PROJECT_MARKERS.contains(&name) {
+                if let Some(project) = entry.path().parent() {
+                    let project = kinds.entry(project.to_path_buf()).or_default();
+                    project.extend(kind_of(name));
+                }
+            }
+            if skips_traversal(entry, skip) {
+                return false;
+            }
+            let file_type = entry.file_type();
+            let is_artifact = entry.depth() > 0
+                && file_type.is_dir()
+                && matcher
+                    .matches_at_depth(entry.path(), Some(file_type), Some(entry.depth()))
+                    .is_some();
+            if is_artifact {
+                artifacts.push(entry.path().to_path_buf());
+            }
+            !is_artifact
+        });
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let (Some(parent), Ok(metadata)) = (entry.path().parent(), entry.metadata()) else {
+            continue;
+        };
+        let totals = dirs.entry(parent.to_path_buf()).or_default();
+        totals.0 += metadata.len();
+        totals.1 = totals.1.max(metadata.modified().ok());
+    }
+
+    let sizes: HashMap<&Path, u64> = planned
+        .iter()
+        .map(|item| (item.path.as_path(), item.size))
+        .collect();
+    for artifact in artifacts {
+        let bytes = match sizes.get(artifact.as_path()) {
+            Some(bytes) => *bytes,
+            None => fs_utils::dir_size(&artifact),
+        };
+        if let Some(parent) = artifact.parent() {
+            dirs.entry(parent.to_path_buf()).or_default().0 += bytes;
+        }
+    }
+
+    let mut projects: BTreeMap<&Path, Project> = kinds
+        .iter()
+        .map(|(path, kinds)| {
+            let project = Project {
+                path: path.clone(),
+                kinds: kinds.iter().copied().collect(),
+                total_bytes: 0,
+                reclaimable_bytes: 0,
+                reclaimable_items: 0,
+                last_modified: None,
+            };
+            (path.as_path(), project)
+        })
+        .collect();
+    let nearest = |path: &Path| {
+        path.ancestors()
+            .find_map(|dir| kinds.get_key_value(dir).map(|(dir, _)| dir.as_path()))
+    };
+    for (dir, (bytes, modified)) in &dirs {
+        if let Some(project) = nearest(dir).and_then(|dir| projects.get_mut(dir)) {
+            project.total_bytes += bytes;
+            let modified = modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+            project.last_modified = project.last_modified.max(modified.map(|d| d.as_secs()));
+        }
+    }
+    for item in planned {
+        let parent = item.path.parent().and_then(nearest);
+        if let Some(project) = parent.and_then(|dir| projects.get_mut(dir)) {
+            project.reclaimable_bytes += item.size;
+            project.reclaimable_items += 1;
+        }
+    }
+
+    let mut projects: Vec<Project> = projects.into_values().collect();
+    projects.sort_by(|a, b| {
+        b.reclaimable_bytes
+            .cmp(&a.reclaimable_bytes)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(projects)
+}
+
+/// Renders `projects` as a table with a row per project.
+pub fn render_table(projects: &[Project]) -> String {
+    let mut rows: Vec<[String; 5]> = vec![[
+        "PROJECT".to_string(),
+        "TYPE".to_string(),
+        "SIZE".to_string(),
+        "RECLAIMABLE".to_string(),
+        "MODIFIED".to_string(),
+    ]];
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    for project in projects {
+        rows.push([
+            project.path.display().to_string(),
+            match project.kinds.as_slice() {
+                [] => "-".to_string(),
+                kinds => kinds.join(","),
+            },
+            format::size(project.total_bytes),
+            format::size(project.reclaimable_bytes),
+            match project.last_modified {
+                Some(secs) => match now.saturating_sub(secs) / 86_400 {
+                    0 => "today".to_string(),
+                    days => format!("{}d ago", days),
+                },
+                None => "-".to_string(),
+            },
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..5)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut table = String::new();
+    for row in &rows {
+        let _ = write!(table, "{:<width$}", row[0], width = widths[0]);
+        let _ = write!(table, "  {:<width$}", row[1], width = widths[1]);
+        for (cell, width) in row.iter().zip(&widths).skip(2) {
+            let _ = write!(table, "  {:>width$}", cell, width = width);
+        }
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_survey_attributes_files_to_the_nearest_project() {
+        let temp = TempDir::new().unwrap();
+        temp.child("app/Cargo.toml").write_str("[package]").unwrap();
+        temp.child("app/package.json").write_str("{}").unwrap();
+        temp.child("app/src/main.rs")
+            .write_str("fn main() {}")
+            .unwrap();
+        temp.child("app/target/debug/app")
+            .write_binary(&[0; 1000])
+            .unwrap();
+        temp.child("app/web/package.json").write_str("{}").unwrap();
+        temp.child("app/web/node_modules/dep/package.json")
+            .write_str("{}")
+            .unwrap();
+        temp.child("notes/todo.txt")
+            .write_str("not a project")
+            .unwrap();
+
+        let target = temp.child("app/target");
+        let planned = [clean_item(
+            target.path(),
+            ItemType::Directory,
+            1000,
+            PatternCategory::BuildOutputs,
+        )];
+        let projects = survey(temp.path(), &Config::default(), &planned).unwrap();
+
+        assert_eq!(projects.len(), 2);
+        let app = &projects[0];
+        assert_eq!(app.path, temp.child("app").path());
+        assert_eq!(app.kinds, ["node", "rust"]);
+        assert_eq!(app.total_bytes, 9 + 2 + 12 + 1000);
+        assert_eq!(app.reclaimable_bytes, 1000);
+        assert_eq!(app.reclaimable_items, 1);
+        assert!(app.last_modified.is_some());
+        // The dependency inside node_modules is an artifact of `web`, not a project.
+        let web = &projects[1];
+        assert_eq!(web.path, temp.child("app/web").path());
+        assert_eq!(web.kinds, ["node"]);
+        assert_eq!(web.total_bytes, 2 + 2);
+        assert_eq!(web.reclaimable_bytes, 0);
+
+        let table = render_table(&projects);
+        assert!(table.starts_with("PROJECT"));
+        assert!(table.contains("node,rust"));
+    }
+}
@@ -0,0 +1,402 @@
+//! This module cleans roots on other machines over SSH, for `mc remote`.
+//!
+//! A [`RemoteSession`] runs `mc serve --stdio` on the remote host through `ssh` and
+//! speaks the JSON-RPC protocol of [`crate::daemon::rpc`] to it, so the remote machine
+//! needs mc installed but no open port. The remote mc scans and cleans with its own
+//! configuration, and refuses roots its safety checks reject.
+//!
+//! Results arrive as JSON: the remote mc may be a different version, and only the
+//! fields a caller reads need to match.
+
+use crate::types::{McError, Result};
+use crate::utils::format::shell_quote;
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A root on another machine, written `[user@]host:/path`, with an IPv6 address in
+/// brackets: `[user@][::1]:/path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteTarget {
+    /// The host as `ssh` takes it, including the user if given.
+    pub host: String,
+    /// The root on the remote machine.
+    pub path: String,
+}
+
+impl FromStr for RemoteTarget {
+    type Err = McError;
+
+    fn from_str(target: &str) -> Result<Self> {
+        let parsed = match target.split_once('[') {
+            Some((user, rest)) if user.is_empty() || user.ends_with('@') => rest
+                .split_once("]:")
+                .map(|(address, path)| (format!("{}{}", user, address), path)),
+            Some(_) => None,
+            None => target
+                .split_once(':')
+                .map(|(host, path)| (host.to_string(), path)),
+        };
+        match parsed {
+            Some((host, path)) if !host.is_empty() && !path.is_empty() => {
+                check_host(&host)?;
+                Ok(Self {
+                    host,
+                    path: path.to_string(),
+                })
+            }
+            _ => Err(McError::InvalidArgument(format!(
+                "`{}` is not a remote root; expected [user@]host:/path",
+                target
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for RemoteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.host.contains(':') {
+            return write!(f, "{}:{}", self.host, self.path);
+        }
+        match self.host.split_once('@') {
+            Some((user, address)) => write!(f, "{}@[{}]:{}", user, address, self.path),
+            None => write!(f, "[{}]:{}", self.host, self.path),
+        }
+    }
+}
+
+/// Rejects a host ssh would read as an option, such as `-oProxyCommand=...`.
+fn check_host(host: &str) -> Result<()> {
+    if host.starts_with('-') {
+        return Err(McError::InvalidArgument(format!(
+            "`{}` is not a host; hosts cannot start with `-`",
+            host
+        )));
+    }
+    Ok(())
+}
+
+/// How the remote mc is started.
+#[derive(Debug, Clone)]
+pub struct RemoteOptions {
+    /// The ssh command, split on whitespace, such as `ssh -p 2222`.
+    pub ssh: String,
+    /// The mc binary on the remote machine.
+    pub remote_mc: String,
+    /// Starts the remote mc with `--dry-run`, so nothing can be deleted.
+    pub dry_run: bool,
+    /// Starts the remote mc with `--allow-uncommitted`, so it also cleans items
+    /// holding work not in git yet.
+    pub allow_uncommitted: bool,
+    /// When the connection is killed if it is still open, failing the call waiting on
+    /// it; `None` never kills it.
+    pub deadline: Option<Instant>,
+}
+
+impl Default for RemoteOptions {
+    fn default() -> Self {
+        Self {
+            ssh: "ssh".to_string(),
+            remote_mc: "mc".to_string(),
+            dry_run: false,
+            allow_uncommitted: false,
+            deadline: None,
+        }
+    }
+}
+
+/// A progress notification from the remote mc.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteProgress {
+    /// `scan` while scanning and planning, `clean` while applying.
+    pub phase: String,
+    /// The items matched or handled so far.
+    pub done: u64,
+    /// The items to handle, once known.
+    pub total: Option<u64>,
+}
+
+/// A plan held by the remote mc, waiting to be applied.
+#[derive(Debug, Clone)]
+pub struct RemotePlan {
+    /// The id to apply the plan by.
+    pub id: u64,
+    /// The number of items to delete.
+    pub items: usize,
+    /// The bytes those items take up.
+    pub bytes: u64,
+    /// The whole [`Plan`](crate::Plan) as the remote mc sent it.
+    pub plan: Value,
+}
+
+/// A connection to mc running on another machine.
+pub struct RemoteSession {
+    host: String,
+    /// Shared with the watchdog killing it at the deadline, if there is one.
+    child: Arc<Mutex<Child>>,
+    /// Closed on drop, which makes the remote mc exit.
+    input: Option<ChildStdin>,
+    output: BufReader<ChildStdout>,
+    next_id: u64,
+    /// Dropped with the session, which stops the watchdog.
+    watchdog: Option<mpsc::Sender<()>>,
+    /// Set by the watchdog once it killed the connection.
+    timed_out: Arc<AtomicBool>,
+}
+
+impl RemoteSession {
+    /// Starts mc on `host` over ssh. Errors from ssh itself, such as a refused
+    /// connection, appear on stderr and as the error of the first call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `host` starts with `-` or the ssh command cannot be run.
+    pub fn connect(host: &str, options: &RemoteOptions) -> Result<Self> {
+        check_host(host)?;
+        let mut ssh = options.ssh.split_whitespace();
+        let program = ssh
+            .next()
+            .ok_or_else(|| McError::InvalidArgument("the ssh command is empty".to_string()))?;
+        let dry_run = if options.dry_run { " --dry-run" } else { "" };
+        let allow_uncommitted = if options.allow_uncommitted {
+            " --allow-uncommitted"
+        } else {
+            ""
+        };
+        let remote_command = format!(
+            "{} --quiet{}{} serve --stdio",
+            shell_quote(&options.remote_mc),
+            dry_run,
+            allow_uncommitted
+        );
+        log::debug!("Running `{}` on {}", remote_command, host);
+
+        let mut child = Command::new(program)
+            .args(ssh)
+            .arg("--")
+            .arg(host)
+            .arg(remote_command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take();
+        let output = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let child = Arc::new(Mutex::new(child));
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let watchdog = options
+            .deadline
+            .map(|deadline| watch(Arc::clone(&child), deadline, Arc::clone(&timed_out)));
+        Ok(Self {
+            host: host.to_string(),
+            child,
+            input,
+            output,
+            next_id: 1,
+            watchdog,
+            timed_out,
+        })
+    }
+
+    /// Scans `root` and returns the [`ScanOutcome`](crate::ScanOutcome).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or the remote scan fails.
+    pub fn scan(&mut self, root: &str, on_progress: impl FnMut(RemoteProgress)) -> Result<Value> {
+        self.call("scan", json!({ "root": root }), on_progress)
+    }
+
+    /// Plans a clean of `root`, which the remote mc keeps until it is applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails, or the remote mc refuses the root or
+    /// fails to scan it.
+    pub fn plan(
+        &mut self,
+        root: &str,
+        on_progress: impl FnMut(RemoteProgress),
+    ) -> Result<RemotePlan> {
+        let response = self.call("plan", json!({ "root": root }), on_progress)?;
+        let (Some(id), Some(items)) = (
+            response["plan_id"].as_u64(),
+            response["plan"]["items"].as_array(),
+        ) else {
+            return Err(self.error("the plan in the response is malformed"));
+        };
+        Ok(RemotePlan {
+            id,
+            items: items.len(),
+            bytes: items.iter().filter_map(|item| item["size"].as_u64()).sum(),
+            plan: response["plan"].clone(),
+        })
+    }
+
+    /// Deletes the items of `plan` and returns the [`CleanReport`](crate::CleanReport).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or the plan was already applied.
+    pub fn apply(
+        &mut self,
+        plan: &RemotePlan,
+        on_progress: impl FnMut(RemoteProgress),
+    ) -> Result<Value> {
+        self.call("apply", json!({ "plan_id": plan.id }), on_progress)
+    }
+
+    /// Sends a request and waits for its response, passing on progress notifications.
+    fn call(
+        &mut self,
+        method: &str,
+        params: Value,
+        mut on_progress: impl FnMut(RemoteProgress),
+    ) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let input = self.input.as_mut().expect("input is open until drop");
+        if writeln!(input, "{}", request)
+            .and_then(|()| input.flush())
+            .is_err()
+        {
+            return Err(self.error("the connection closed"));
+        }
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.output.read_line(&mut line)? == 0 {
+                if self.timed_out.load(Ordering::Relaxed) {
+                    return Err(self.error("the connection was closed at its deadline"));
+                }
+                return Err(self.error("the connection closed; is mc installed there?"));
+            }
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                // Shell start-up files may print to stdout before mc starts.
+                log::debug!("Ignoring output from {}: {}", self.host, line.trim_end());
+                continue;
+            };
+            if message["method"] == "progress" {
+                let params = &message["params"];
+                on_progress(RemoteProgress {
+                    phase: params["phase"].as_str().unwrap_or_default().to_string(),
+                    done: params["done"].as_u64().unwrap_or(0),
+                    total: params["total"].as_u64(),
+                });
+            } else if message["id"] == id {
+                if let Some(message) = message["error"]["message"].as_str() {
+                    return Err(self.error(message));
+                }
+                return Ok(message["result"].clone());
+            }
+        }
+    }
+
+    fn error(&self, message: &str) -> McError {
+        McError::Remote(format!("{}: {}", self.host, message))
+    }
+}
+
+impl Drop for RemoteSession {
+    fn drop(&mut self) {
+        // The remote mc exits once its input ends, and ssh with it.
+        drop(self.input.take());
+        if self.watchdog.is_none() {
+            let _ = self.child.lock().unwrap_or_else(|e| e.into_inner()).wait();
+            return;
+        }
+        // Polled, so the watchdog can still take the child to kill it meanwhile.
+        loop {
+            let status = self
+                .child
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .try_wait();
+            if !matches!(status, Ok(None)) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        drop(self.watchdog.take());
+    }
+}
+
+/// Starts a thread killing `child` at `deadline`, setting `timed_out`, unless the
+/// returned sender is dropped first.
+fn watch(
+    child: Arc<Mutex<Child>>,
+    deadline: Instant,
+    timed_out: Arc<AtomicBool>,
+) -> mpsc::Sender<()> {
+    let (stop, stopped) = mpsc::channel();
+    thread::spawn(move || {
+        let wait = deadline.saturating_duration_since(Instant::now());
+        if stopped.recv_timeout(wait) == Err(RecvTimeoutError::Timeout) {
+            timed_out.store(true, Ordering::Relaxed);
+            let _ = child.lock().unwrap_or_else(|e| e.into_inner()).kill();
+        }
+    });
+    stop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_target() {
+        let target: RemoteTarget = "builder@farm-01:/srv/builds".parse().unwrap();
+        assert_eq!(target.host, "builder@farm-01");
+        assert_eq!(target.path, "/srv/builds");
+        assert_eq!(target.to_string(), "builder@farm-01:/srv/builds");
+
+        assert!("farm-01".parse::<RemoteTarget>().is_err());
+        assert!(":/srv".parse::<RemoteTarget>().is_err());
+        assert!("farm-01:".parse::<RemoteTarget>().is_err());
+        assert!("-oProxyCommand=touch pwned:/srv"
+            .parse::<RemoteTarget>()
+            .is_err());
+
+        let target: RemoteTarget = "builder@[::1]:/srv/builds".parse().unwrap();
+        assert_eq!(target.host, "builder@::1");
+        assert_eq!(target.path, "/srv/builds");
+        assert_eq!(target.to_string(), "builder@[::1]:/srv/builds");
+        let target: RemoteTarget = "[fe80::1]:/srv".parse().unwrap();
+        assert_eq!(target.host, "fe80::1");
+        assert_eq!(target.to_string(), "[fe80::1]:/srv");
+        assert!("[::1]/srv".parse::<RemoteTarget>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_connection_is_killed_at_its_deadline() {
+        use assert_fs::prelude::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        // Stands in for an ssh that never connects.
+        let ssh = temp.child("hung-ssh");
+        ssh.write_str("#!/bin/sh\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(ssh.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+        let options = RemoteOptions {
+            ssh: ssh.path().display().to_string(),
+            deadline: Some(Instant::now() + Duration::from_millis(200)),
+            ..RemoteOptions::default()
+        };
+
+        let started = Instant::now();
+        let mut session = RemoteSession::connect("farm-01", &options).unwrap();
+        let err = session.plan("/srv", |_| {}).unwrap_err();
+        drop(session);
+        assert!(err.to_string().contains("deadline"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
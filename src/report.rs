@@ -0,0 +1,914 @@
+//! Rendering a [`CleanReport`] for people and for other programs.
+//!
+//! [`CleanReport::write_to`] writes a report in any [`ReportFormat`], and `mc` prints
+//! its reports the same way. The human-readable form is also the report's `Display`;
+//! [`CleanReport::human`] tunes it. Its colors follow `colored`'s settings, so
+//! `NO_COLOR`, `CLICOLOR_FORCE` and [`colored::control::set_override`] apply to it.
+//!
+//! JSON and TOML carry the same fields, with durations in milliseconds. CSV has one
+//! row per root of a multi-root report and a last row with the totals, whose `root`
+//! is empty. The one-line form, `freed=12345 items=42 errors=0 duration_ms=830`, is
+//! for shell scripts; its keys and their order do not change.
+
+use crate::types::{
+    AutoThreads, CategoryTotal, CleanError, CleanReport, LargestItem, PatternStats, PhaseTimings,
+    PlannedItem, ProjectTotal, RootReport, RoutedItem, ScanError, ScanErrorGroup, ScanSummary,
+    SkippedItem, TrashRoute, DEPTH_BUCKETS,
+};
+use crate::utils::format;
+use colored::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A format [`CleanReport::write_to`] writes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// The summary `mc` prints, with colors.
+    Human,
+    /// A JSON object.
+    Json,
+    /// A TOML document.
+    Toml,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// A single line of `key=value` pairs, without colors.
+    Line,
+}
+
+/// The human-readable form of a report, from [`CleanReport::human`].
+#[derive(Debug, Clone, Copy)]
+pub struct HumanReport<'a> {
+    report: &'a CleanReport,
+    show_errors: bool,
+    scan_stats: bool,
+}
+
+impl<'a> HumanReport<'a> {
+    /// Lists every stored scan error instead of a summary grouped by cause.
+    pub fn with_show_errors(mut self, show_errors: bool) -> Self {
+        self.show_errors = show_errors;
+        self
+    }
+
+    /// Adds what the scan walked, as `--stats` does.
+    pub fn with_scan_stats(mut self, scan_stats: bool) -> Self {
+        self.scan_stats = scan_stats;
+        self
+    }
+}
+
+impl fmt::Display for HumanReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_summary(f, self.report, self.show_errors)?;
+        if self.scan_stats {
+            write_scan_stats(f, &self.report.scan)?;
+        }
+        Ok(())
+    }
+}
+
+impl CleanReport {
+    /// Returns the human-readable form of the report, which can be adjusted before
+    /// it is displayed.
+    pub fn human(&self) -> HumanReport<'_> {
+        HumanReport {
+            report: self,
+            show_errors: false,
+            scan_stats: false,
+        }
+    }
+
+    /// Writes the report to `out` in `format`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of writing to `out`, or an `InvalidData` error if the report
+    /// cannot be represented in TOML.
+    pub fn write_to(&self, out: &mut impl Write, format: ReportFormat) -> io::Result<()> {
+        match format {
+            ReportFormat::Human => write!(out, "{}", self.human()),
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, &SerializedReport::from(self))?;
+                writeln!(out)
+            }
+            ReportFormat::Toml => {
+                let toml = toml::to_string(&SerializedReport::from(self))
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                out.write_all(toml.as_bytes())
+            }
+            ReportFormat::Csv => write_csv(out, self),
+            ReportFormat::Line => writeln!(
+                out,
+                "freed={} items={} errors={} duration_ms={}",
+                self.bytes_freed,
+                self.items_deleted,
+                self.errors.len(),
+                self.timings.total().as_millis()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for CleanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.human().fmt(f)
+    }
+}
+
+/// The columns of a CSV report.
+const CSV_HEADER: &str = "root,items_deleted,bytes_freed,errors,scan_errors,duration_ms,\
+                          space_reclaimed,archive,quarantine";
+
+fn write_csv(out: &mut impl Write, report: &CleanReport) -> io::Result<()> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    for root in &report.roots {
+        CsvRow::from(root).write(out)?;
+    }
+    CsvRow {
+        root: Path::new(""),
+        items_deleted: report.items_deleted,
+        bytes_freed: report.bytes_freed,
+        errors: report.errors.len(),
+        scan_errors: report.scan_errors.len() + report.scan_errors_omitted,
+        duration: report.scan_duration + report.duration,
+        space_reclaimed: report.space_reclaimed(),
+        archive: report.archive.as_deref(),
+        quarantine: report.quarantine.as_deref(),
+    }
+    .write(out)
+}
+
+/// One row of a CSV report.
+struct CsvRow<'a> {
+    root: &'a Path,
+    items_deleted: usize,
+    bytes_freed: u64,
+    errors: usize,
+    scan_errors: usize,
+    duration: Duration,
+    space_reclaimed: Option<i64>,
+    archive: Option<&'a Path>,
+    quarantine: Option<&'a Path>,
+}
+
+impl<'a> From<&'a RootReport> for CsvRow<'a> {
+    fn from(root: &'a RootReport) -> Self {
+        Self {
+            root: &root.root,
+            items_deleted: root.items_deleted,
+            bytes_freed: root.bytes_freed,
+            errors: root.errors.len(),
+            scan_errors: root.scan_errors.len(),
+            duration: root.duration,
+            space_reclaimed: root.space_reclaimed,
+            archive: root.archive.as_deref(),
+            quarantine: root.quarantine.as_deref(),
+        }
+    }
+}
+
+impl CsvRow<'_> {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            csv_path(self.root),
+            self.items_deleted,
+            self.bytes_freed,
+            self.errors,
+            self.scan_errors,
+            self.duration.as_millis(),
+            self.space_reclaimed
+                .map(|bytes| bytes.to_string())
+                .unwrap_or_default(),
+            self.archive.map(csv_path).unwrap_or_default(),
+            self.quarantine.map(csv_path).unwrap_or_default(),
+        )
+    }
+}
+
+/// Quotes a path for a CSV field if it needs to be.
+fn csv_path(path: &Path) -> String {
+    let field = path.to_string_lossy();
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into_owned()
+    }
+}
+
+/// Writes the items of a clean with trash on that were too large for the Recycle Bin,
+/// and where each went instead.
+fn write_trash_fallbacks(f: &mut impl fmt::Write, report: &CleanReport) -> fmt::Result {
+    let fallbacks: Vec<&RoutedItem> = report
+        .routes
+        .iter()
+        .filter(|routed| routed.route != TrashRoute::Trash)
+        .collect();
+    if fallbacks.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        f,
+        "  {} {} item(s) too large for the Recycle Bin:",
+        "⚠".yellow(),
+        fallbacks.len()
+    )?;
+    for routed in fallbacks {
+        writeln!(
+            f,
+            "      {} ({}): {}",
+            routed.path.display(),
+            format::size(routed.bytes),
+            routed.route.describe()
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the totals of a clean or dry run, followed by its errors.
+fn write_summary(f: &mut impl fmt::Write, report: &CleanReport, show_errors: bool) -> fmt::Result {
+    writeln!(f)?;
+
+    if !report.roots.is_empty() {
+        write_root_sections(f, report)?;
+    }
+
+    if report.dry_run {
+        // Show breakdown for dry run
+        writeln!(
+            f,
+            "{} {} items ({} dirs, {} files)",
+            "✓".bright_green(),
+            format::count(report.items_deleted as u64).bright_white(),
+            format::count(report.dirs_deleted as u64).bright_cyan(),
+            format::count(report.files_deleted as u64).bright_cyan()
+        )?;
+        writeln!(
+            f,
+            "{} {}{} would be freed",
+            "✓".bright_green(),
+            if report.bytes_estimated > 0 {
+                "≈"
+            } else {
+                ""
+            },
+            format::size(report.bytes_freed).bright_green()
+        )?;
+        if report.bytes_estimated > 0 {
+            writeln!(
+                f,
+                "  {} {} of it estimated from samples (size_mode = \"fast\")",
+                "↳".dimmed(),
+                format::size(report.bytes_estimated)
+            )?;
+        }
+        writeln!(f, "\n{}", "Dry run complete!".yellow())?;
+    } else {
+        // Calculate throughput metrics
+        let clean_secs = report.duration.as_secs_f64();
+        let bytes_per_sec = if clean_secs > 0.0 {
+            report.bytes_freed as f64 / clean_secs
+        } else {
+            0.0
+        };
+        let items_per_sec = if clean_secs > 0.0 {
+            report.items_deleted as f64 / clean_secs
+        } else {
+            0.0
+        };
+
+        // Show breakdown
+        writeln!(
+            f,
+            "{} Cleaned {} items ({} dirs, {} files)",
+            "✓".bright_green(),
+            format::count(report.items_deleted as u64).bright_white(),
+            format::count(report.dirs_deleted as u64).bright_cyan(),
+            format::count(report.files_deleted as u64).bright_cyan()
+        )?;
+        writeln!(
+            f,
+            "{} Freed {}",
+            "✓".bright_green(),
+            format::size(report.bytes_freed).bright_green()
+        )?;
+        if let Some(reclaimed) = report.space_reclaimed() {
+            let reclaimed = if reclaimed < 0 {
+                format!("-{}", format::size(reclaimed.unsigned_abs()))
+            } else {
+                format::size(reclaimed as u64)
+            };
+            writeln!(
+                f,
+                "  {} {} reclaimed on disk (free space before and after)",
+                "↳".dimmed(),
+                reclaimed
+            )?;
+        }
+        if let Some(archive) = &report.archive {
+            writeln!(f, "  {} Archived to {}", "↳".dimmed(), archive.display())?;
+        }
+        if let Some(batch) = &report.quarantine {
+            writeln!(
+                f,
+                "  {} {} moved to quarantine in {} (see {})",
+                "↳".dimmed(),
+                format::size(report.bytes_quarantined).bright_yellow(),
+                batch.display(),
+                "mc stats".bright_cyan()
+            )?;
+        }
+        if report.bytes_trashed > 0 {
+            writeln!(
+                f,
+                "  {} {} moved to the trash, freed when it is emptied",
+                "↳".dimmed(),
+                format::size(report.bytes_trashed).bright_yellow()
+            )?;
+        }
+        write_trash_fallbacks(f, report)?;
+        if report.items_already_gone > 0 {
+            writeln!(
+                f,
+                "  {} {} item(s) were already gone",
+                "↳".dimmed(),
+                report.items_already_gone
+            )?;
+        }
+        if report.paths_kept > 0 {
+            writeln!(
+                f,
+                "  {} {} protected path(s) kept inside partly deleted directories",
+                "↳".dimmed(),
+                report.paths_kept
+            )?;
+        }
+        if report.clean_timed_out {
+            writeln!(
+                f,
+                "  {} Clean timeout reached; {} item(s) not attempted, run {} to finish",
+                "⚠".yellow(),
+                report.items_not_attempted,
+                "mc resume".bright_cyan()
+            )?;
+        }
+
+        // Show timing breakdown, with the confirmation wait only if there was one
+        let timings = &report.timings;
+        write!(
+            f,
+            "{} Scan: {:.2}s • Prune: {:.2}s • ",
+            "⏱".dimmed(),
+            timings.scan.as_secs_f64(),
+            timings.prune.as_secs_f64()
+        )?;
+        if !timings.confirmation.is_zero() {
+            write!(f, "Confirm: {:.2}s • ", timings.confirmation.as_secs_f64())?;
+        }
+        writeln!(
+            f,
+            "Clean: {:.2}s • Total: {:.2}s",
+            timings.clean.as_secs_f64(),
+            timings.total().as_secs_f64()
+        )?;
+
+        // Show throughput
+        if bytes_per_sec > 0.0 || items_per_sec > 0.0 {
+            writeln!(
+                f,
+                "  {} {} • {:.0} items/s",
+                "↳".dimmed(),
+                format::rate(bytes_per_sec),
+                items_per_sec
+            )?;
+        }
+
+        writeln!(f, "\n{}", "Done!".green())?;
+    }
+
+    if !report.largest_items.is_empty() {
+        let heading = if report.dry_run {
+            "Largest items"
+        } else {
+            "Largest items cleaned"
+        };
+        writeln!(f, "\n{}", heading.bold())?;
+        for item in &report.largest_items {
+            writeln!(
+                f,
+                "  {:>10}  {:<14} {}",
+                format::size(item.bytes).bright_yellow(),
+                item.category.label(),
+                item.path.display()
+            )?;
+            if let Some(note) = &item.rebuild {
+                writeln!(f, "  {:>10}  ↳ rebuild: {}", "", note)?;
+            }
+        }
+    }
+
+    if let Some(auto) = report.auto_threads {
+        writeln!(
+            f,
+            "{} {} workers picked for {}",
+            "⚙".dimmed(),
+            auto.threads.to_string().bright_cyan(),
+            auto.device
+        )?;
+    }
+
+    if report.commands_run > 0 {
+        writeln!(
+            f,
+            "{} {} action command(s) {}",
+            "▶".bright_magenta(),
+            report.commands_run,
+            if report.dry_run { "would run" } else { "ran" }
+        )?;
+    }
+
+    write_error_details(f, report, show_errors)
+}
+
+/// Writes what the scan walked.
+fn write_scan_stats(f: &mut impl fmt::Write, scan: &ScanSummary) -> fmt::Result {
+    writeln!(f)?;
+    writeln!(f, "{}", "Scan statistics".bold())?;
+    writeln!(
+        f,
+        "  {} entries: {} directories, {} files, {} symlinks",
+        format::count(scan.entries_scanned as u64),
+        format::count(scan.dirs_visited as u64),
+        format::count(scan.files_visited as u64),
+        format::count(scan.symlinks_visited as u64)
+    )?;
+    writeln!(
+        f,
+        "  {} examined in {:.2}s",
+        format::size(scan.bytes_examined),
+        scan.duration.as_secs_f64()
+    )?;
+    if scan.errors > 0 {
+        writeln!(
+            f,
+            "  {} {} errors",
+            "⚠".yellow(),
+            format::count(scan.errors as u64)
+        )?;
+    }
+    let widest = scan
+        .depth_histogram
+        .counts()
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0);
+    if widest > 0 {
+        writeln!(f, "  Entries by depth:")?;
+        for (depth, &count) in scan.depth_histogram.counts().iter().enumerate() {
+            // Bars are scaled to the busiest depth, at most 30 characters wide.
+            let bar = "▇".repeat((count * 30).div_ceil(widest));
+            // The last depth also counts everything deeper.
+            let depth = if depth == DEPTH_BUCKETS - 1 {
+                format!("{}+", depth)
+            } else {
+                depth.to_string()
+            };
+            let count = format::count(count as u64);
+            writeln!(f, "  {:>5} {:>9} {}", depth, count, bar.bright_black())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes one line per root of a multi-root report, above the merged totals.
+fn write_root_sections(f: &mut impl fmt::Write, report: &CleanReport) -> fmt::Result {
+    let verb = if report.dry_run {
+        "would be freed"
+    } else {
+        "freed"
+    };
+    for root in &report.roots {
+        let errors = root.errors.len() + root.scan_errors.len();
+        writeln!(
+            f,
+            "{} {}: {} items, {} {} in {:.2}s{}",
+            "▸".bright_cyan(),
+            root.root.display(),
+            format::count(root.items_deleted as u64).bright_white(),
+            format::size(root.bytes_freed).bright_green(),
+            verb,
+            root.duration.as_secs_f64(),
+            match errors {
+                0 => String::new(),
+                n => format!(", {} errors", n).yellow().to_string(),
+            }
+        )?;
+        if let Some(archive) = &root.archive {
+            writeln!(f, "  {} Archived to {}", "↳".dimmed(), archive.display())?;
+        }
+        if let Some(batch) = &root.quarantine {
+            writeln!(f, "  {} Quarantined in {}", "↳".dimmed(), batch.display())?;
+        }
+    }
+    writeln!(
+        f,
+        "{} {} roots in total",
+        "Σ".bright_cyan(),
+        format::count(report.roots.len() as u64).bright_white()
+    )?;
+    writeln!(f)
+}
+
+/// Writes error details when there are deletion or scan failures.
+///
+/// Scan errors are shown grouped by cause unless `show_errors` is set, in which case
+/// every stored one is listed.
+fn write_error_details(
+    f: &mut impl fmt::Write,
+    report: &CleanReport,
+    show_errors: bool,
+) -> fmt::Result {
+    let scan_error_count = report.scan_errors.len() + report.scan_errors_omitted;
+    let total_errors = scan_error_count + report.errors.len();
+    if total_errors == 0 {
+        return Ok(());
+    }
+
+    writeln!(f)?;
+    writeln!(
+        f,
+        "{} {} errors occurred:",
+        "⚠".yellow(),
+        total_errors.to_string().yellow()
+    )?;
+
+    for (i, err) in report.errors.iter().enumerate() {
+        if i >= 10 && !show_errors {
+            writeln!(
+                f,
+                "  {} ... and {} more",
+                "↳".dimmed(),
+                report.errors.len() - 10
+            )?;
+            break;
+        }
+        writeln!(f, "  {} {}", "✗".red(), err)?;
+    }
+
+    if show_errors {
+        for err in &report.scan_errors {
+            writeln!(f, "  {} {}", "↳".dimmed(), err)?;
+        }
+        if report.scan_errors_omitted > 0 {
+            writeln!(
+                f,
+                "  {} ... and {} more scan errors past the limit",
+                "↳".dimmed(),
+                report.scan_errors_omitted
+            )?;
+        }
+        return Ok(());
+    }
+
+    for (i, group) in report.scan_error_groups.iter().enumerate() {
+        if i >= 5 {
+            let rest: usize = report.scan_error_groups[5..].iter().map(|g| g.count).sum();
+            writeln!(f, "  {} ... and {} more scan errors", "↳".dimmed(), rest)?;
+            break;
+        }
+        match group.count {
+            1 => writeln!(
+                f,
+                "  {} {}: {}",
+                "↳".dimmed(),
+                group.ancestor.display(),
+                group.cause
+            )?,
+            n => writeln!(
+                f,
+                "  {} {}: {} ({} paths)",
+                "↳".dimmed(),
+                group.ancestor.display(),
+                group.cause,
+                n
+            )?,
+        }
+    }
+    if scan_error_count > 1 {
+        writeln!(
+            f,
+            "  {} Run with --show-errors to list every scan error",
+            "↳".dimmed()
+        )?;
+    }
+    Ok(())
+}
+
+/// The serialized form of a report, for JSON and TOML, with durations in milliseconds.
+#[derive(Serialize)]
+struct SerializedReport {
+    dry_run: bool,
+    items_deleted: usize,
+    bytes_freed: u64,
+    bytes_estimated: u64,
+    dirs_deleted: usize,
+    files_deleted: usize,
+    entries_scanned: usize,
+    depth_limited_dirs: usize,
+    commands_run: usize,
+    items_already_gone: usize,
+    paths_kept: usize,
+    truncated: bool,
+    scan_timed_out: bool,
+    clean_timed_out: bool,
+    items_not_attempted: usize,
+    duration_ms: u64,
+    scan_duration_ms: u64,
+    errors: Vec<CleanError>,
+    scan_errors: Vec<ScanError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    scan_error_groups: Vec<ScanErrorGroup>,
+    scan_errors_omitted: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    plan: Vec<PlannedItem>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pattern_stats: BTreeMap<String, PatternStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_threads: Option<AutoThreads>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    space_reclaimed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quarantine: Option<PathBuf>,
+    bytes_quarantined: u64,
+    bytes_trashed: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    routes: Vec<RoutedItem>,
+    scan: SerializedScanStats,
+    timings: SerializedTimings,
+    by_category: Vec<CategoryTotal>,
+    by_project: Vec<ProjectTotal>,
+    largest_items: Vec<LargestItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped: Vec<SkippedItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roots: Vec<SerializedRootReport>,
+}
+
+/// The scan statistics of a serialized report.
+#[derive(Serialize)]
+struct SerializedScanStats {
+    entries: usize,
+    dirs: usize,
+    files: usize,
+    symlinks: usize,
+    errors: usize,
+    bytes_examined: u64,
+    duration_ms: u64,
+    depth_histogram: Vec<usize>,
+}
+
+impl From<&ScanSummary> for SerializedScanStats {
+    fn from(scan: &ScanSummary) -> Self {
+        Self {
+            entries: scan.entries_scanned,
+            dirs: scan.dirs_visited,
+            files: scan.files_visited,
+            symlinks: scan.symlinks_visited,
+            errors: scan.errors,
+            bytes_examined: scan.bytes_examined,
+            duration_ms: scan.duration.as_millis() as u64,
+            depth_histogram: scan.depth_histogram.counts().to_vec(),
+        }
+    }
+}
+
+/// The phase durations of a serialized report.
+#[derive(Serialize)]
+struct SerializedTimings {
+    scan_ms: u64,
+    prune_ms: u64,
+    confirmation_ms: u64,
+    clean_ms: u64,
+    total_ms: u64,
+}
+
+impl From<&PhaseTimings> for SerializedTimings {
+    fn from(timings: &PhaseTimings) -> Self {
+        Self {
+            scan_ms: timings.scan.as_millis() as u64,
+            prune_ms: timings.prune.as_millis() as u64,
+            confirmation_ms: timings.confirmation.as_millis() as u64,
+            clean_ms: timings.clean.as_millis() as u64,
+            total_ms: timings.total().as_millis() as u64,
+        }
+    }
+}
+
+/// One root's section in a serialized multi-root report.
+#[derive(Serialize)]
+struct SerializedRootReport {
+    root: PathBuf,
+    items_deleted: usize,
+    bytes_freed: u64,
+    duration_ms: u64,
+    errors: Vec<CleanError>,
+    scan_errors: Vec<ScanError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    space_reclaimed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quarantine: Option<PathBuf>,
+}
+
+impl From<&RootReport> for SerializedRootReport {
+    fn from(r: &RootReport) -> Self {
+        Self {
+            root: r.root.clone(),
+            items_deleted: r.items_deleted,
+            bytes_freed: r.bytes_freed,
+            duration_ms: r.duration.as_millis() as u64,
+            errors: r.errors.clone(),
+            scan_errors: r.scan_errors.clone(),
+            space_reclaimed: r.space_reclaimed,
+            archive: r.archive.clone(),
+            quarantine: r.quarantine.clone(),
+        }
+    }
+}
+
+impl From<&CleanReport> for SerializedReport {
+    fn from(r: &CleanReport) -> Self {
+        Self {
+            dry_run: r.dry_run,
+            items_deleted: r.items_deleted,
+            bytes_freed: r.bytes_freed,
+            bytes_estimated: r.bytes_estimated,
+            dirs_deleted: r.dirs_deleted,
+            files_deleted: r.files_deleted,
+            entries_scanned: r.entries_scanned,
+            depth_limited_dirs: r.depth_limited_dirs,
+            commands_run: r.commands_run,
+            items_already_gone: r.items_already_gone,
+            paths_kept: r.paths_kept,
+            truncated: r.scan_timed_out || r.clean_timed_out,
+            scan_timed_out: r.scan_timed_out,
+            clean_timed_out: r.clean_timed_out,
+            items_not_attempted: r.items_not_attempted,
+            duration_ms: r.duration.as_millis() as u64,
+            scan_duration_ms: r.scan_duration.as_millis() as u64,
+            errors: r.errors.clone(),
+            scan_errors: r.scan_errors.clone(),
+            scan_error_groups: r.scan_error_groups.clone(),
+            scan_errors_omitted: r.scan_errors_omitted,
+            plan: r.plan.clone(),
+            pattern_stats: r.pattern_stats.clone(),
+            auto_threads: r.auto_threads,
+            space_reclaimed: r.space_reclaimed(),
+            archive: r.archive.clone(),
+            quarantine: r.quarantine.clone(),
+            bytes_quarantined: r.bytes_quarantined,
+            bytes_trashed: r.bytes_trashed,
+            routes: r.routes.clone(),
+            scan: SerializedScanStats::from(&r.scan),
+            timings: SerializedTimings::from(&r.timings),
+            by_category: r.by_category.clone(),
+            by_project: r.by_project.clone(),
+            largest_items: r.largest_items.clone(),
+            skipped: r.skipped.clone(),
+            roots: r.roots.iter().map(SerializedRootReport::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_share_one_report() {
+        let mut report = CleanReport::default();
+        let root_report = CleanReport {
+            items_deleted: 2,
+            bytes_freed: 300,
+            ..Default::default()
+        };
+        report.merge(Path::new("/srv/a,b"), &root_report);
+
+        let mut human = Vec::new();
+        report.write_to(&mut human, ReportFormat::Human).unwrap();
+        assert_eq!(String::from_utf8(human).unwrap(), report.to_string());
+
+        let mut json = Vec::new();
+        report.write_to(&mut json, ReportFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["bytes_freed"], 300);
+
+        let mut toml = Vec::new();
+        report.write_to(&mut toml, ReportFormat::Toml).unwrap();
+        let toml: toml::Table = String::from_utf8(toml).unwrap().parse().unwrap();
+        assert_eq!(toml["roots"][0]["root"].as_str(), Some("/srv/a,b"));
+
+        let mut csv = Vec::new();
+        report.write_to(&mut csv, ReportFormat::Csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("\"/srv/a,b\",2,300,"), "{}", rows[1]);
+        assert!(rows[2].starts_with(",2,300,"), "{}", rows[2]);
+    }
+
+    #[test]
+    fn test_reports_the_free_space_delta_beside_bytes_freed() {
+        let json = |report: &CleanReport| {
+            let mut json = Vec::new();
+            report.write_to(&mut json, ReportFormat::Json).unwrap();
+            serde_json::from_slice::<serde_json::Value>(&json).unwrap()
+        };
+        let unprobed = CleanReport {
+            bytes_freed: 5000,
+            ..Default::default()
+        };
+        assert_eq!(unprobed.space_reclaimed(), None);
+        assert!(json(&unprobed).get("space_reclaimed").is_none());
+        assert!(!unprobed.to_string().contains("reclaimed on disk"));
+
+        // Hard links kept part of what was deleted on disk.
+        let linked = CleanReport {
+            bytes_freed: 5000,
+            free_space_before: Some(10_000),
+            free_space_after: Some(12_000),
+            ..Default::default()
+        };
+        assert_eq!(linked.space_reclaimed(), Some(2000));
+        assert_eq!(json(&linked)["space_reclaimed"], 2000);
+        assert_eq!(json(&linked)["bytes_freed"], 5000);
+        let human = linked.to_string();
+        assert!(human.contains("↳ 2 kB reclaimed on disk"), "{}", human);
+
+        // Something else wrote more than the clean freed meanwhile.
+        let shrunk = CleanReport {
+            free_space_before: Some(12_000),
+            free_space_after: Some(10_000),
+            ..Default::default()
+        };
+        assert_eq!(shrunk.space_reclaimed(), Some(-2000));
+        let human = shrunk.to_string();
+        assert!(human.contains("↳ -2 kB reclaimed on disk"), "{}", human);
+
+        let mut merged = CleanReport::default();
+        merged.merge(Path::new("/srv/app"), &linked);
+        assert_eq!(merged.roots[0].space_reclaimed, Some(2000));
+        assert_eq!(merged.space_reclaimed(), None);
+    }
+
+    #[test]
+    fn test_lists_items_too_large_for_the_recycle_bin() {
+        let routed = |path: &str, route| RoutedItem {
+            path: PathBuf::from(path),
+            route,
+            bytes: 1024,
+        };
+        let report = CleanReport {
+            bytes_trashed: 1024,
+            routes: vec![
+                routed("/srv/app/target", TrashRoute::Trash),
+                routed("/srv/big/target", TrashRoute::Deleted),
+                routed("/srv/huge/target", TrashRoute::Kept),
+            ],
+            ..Default::default()
+        };
+
+        let human = report.to_string();
+        assert!(
+            human.contains("2 item(s) too large for the Recycle Bin"),
+            "{}",
+            human
+        );
+        assert!(
+            human.contains("/srv/big/target (1.02 kB): deleted permanently"),
+            "{}",
+            human
+        );
+        assert!(
+            human.contains("/srv/huge/target (1.02 kB): kept"),
+            "{}",
+            human
+        );
+        assert!(!human.contains("/srv/app/target"), "{}", human);
+
+        let mut json = Vec::new();
+        report.write_to(&mut json, ReportFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["bytes_trashed"], 1024);
+        assert_eq!(json["routes"][1]["route"], "deleted");
+    }
+}
@@ -0,0 +1,204 @@
+//! This module checkpoints cleans so an interrupted one can be finished without
+//! re-scanning.
+//!
+//! Before a real clean starts deleting, the planned items are written to a checkpoint
+//! in the user's local data directory, one per scan root, next to a journal the cleaner
+//! appends every deleted item to. A clean that finishes removes both. When a clean is
+//! cut short by Ctrl-C, a crash or a reboot, they stay behind, and `mc resume` deletes
+//! the items the journal does not list.
+//!
+//! Checkpoints are written in a versioned [`Envelope`] and checked like retry
+//! manifests: one written by another version of mc, on another machine or over a week
+//! ago is refused unless `--force` is given.
+
+use crate::types::{CleanItem, McError, Result};
+use crate::utils::envelope::Envelope;
+use crate::utils::fs::{path_from_bytes, path_to_bytes};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The planned items of a clean, saved until the clean finishes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The root being cleaned.
+    pub root: PathBuf,
+    /// The items the clean set out to delete.
+    pub items: Vec<CleanItem>,
+}
+
+/// The record of items a checkpointed clean has deleted so far.
+///
+/// Each path is appended as soon as its item is gone, so the journal is complete up to
+/// the moment a clean is interrupted.
+#[derive(Debug)]
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Records that the item at `path` was deleted. Failures are logged, not returned:
+    /// an item missing from the journal is only attempted again on resume.
+    pub fn record(&self, path: &Path) {
+        let mut entry = path_to_bytes(path);
+        entry.push(0);
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(err) = file.write_all(&entry) {
+            log::debug!("Failed to journal {}: {}", path.display(), err);
+        }
+    }
+}
+
+impl Checkpoint {
+    /// Creates a checkpoint for cleaning `items` under `root`.
+    pub fn new(root: &Path, items: Vec<CleanItem>) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            items,
+        }
+    }
+
+    /// Returns the directory checkpoints are stored in.
+    pub fn dir() -> PathBuf {
+        ProjectDirs::from("com", "mc", "mc")
+            .map(|dirs| dirs.data_local_dir().join("resume"))
+            .unwrap_or_else(|| std::env::temp_dir().join("mc-resume"))
+    }
+
+    /// Returns the checkpoint path for `root`.
+    pub fn path_for(root: &Path) -> PathBuf {
+        let hash = crate::utils::fs::fnv1a(root.to_string_lossy().as_bytes());
+        Self::dir().join(format!("{:016x}.json", hash))
+    }
+
+    /// Writes the checkpoint to [`Checkpoint::path_for`] its root, starting an empty
+    /// journal, and returns the journal to record deletions in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot be serialized or written.
+    pub fn save(&self) -> Result<Journal> {
+        let path = Self::path_for(&self.root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(journal_path(&path))?;
+        let envelope = Envelope::new(&self.root, self);
+        fs::write(&path, serde_json::to_vec_pretty(&envelope)?)?;
+        self.journal()
+    }
+
+    /// Opens the journal of the checkpoint for its root to record further deletions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal cannot be opened.
+    pub fn journal(&self) -> Result<Journal> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_path(&Self::path_for(&self.root)))?;
+        Ok(Journal {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Loads the checkpoint at `path` with only the items its journal does not list,
+    /// checking that it still applies to its root.
+    ///
+    /// With `force`, a checkpoint that fails the check is loaded anyway, with a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot be read or parsed, or if it fails the
+    /// check and `force` is not set.
+    pub fn load(path: &Path, force: bool) -> Result<Self> {
+        let envelope: Envelope<Self> = serde_json::from_slice(&fs::read(path)?)?;
+        if let Err(mismatch) = envelope.check(&envelope.body.root) {
+            if !force {
+                return Err(McError::Safety(format!(
+                    "refusing to resume from checkpoint {}: {}; pass --force to resume anyway",
+                    path.display(),
+                    mismatch
+                )));
+            }
+            log::warn!(
+                "Resuming from checkpoint {} although {}",
+                path.display(),
+                mismatch
+            );
+        }
+        let mut checkpoint = envelope.body;
+        let done = read_journal(&journal_path(path))?;
+        checkpoint.items.retain(|item| !done.contains(&item.path));
+        Ok(checkpoint)
+    }
+
+    /// Returns the most recently written checkpoint, if any.
+    pub fn latest() -> Option<PathBuf> {
+        fs::read_dir(Self::dir())
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .max_by_key(|path| crate::utils::fs::modified(path))
+    }
+
+    /// Removes the checkpoint for `root` and its journal, if there are any.
+    pub fn clear(root: &Path) {
+        let path = Self::path_for(root);
+        for path in [journal_path(&path), path] {
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    log::debug!("Failed to remove checkpoint {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the journal path for the checkpoint at `path`.
+fn journal_path(path: &Path) -> PathBuf {
+    path.with_extension("done")
+}
+
+/// Reads the paths listed in the journal at `path`; a missing journal lists none.
+fn read_journal(path: &Path) -> Result<HashSet<PathBuf>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(err) => return Err(err.into()),
+    };
+    // A record cut short by the interruption has no terminator and is ignored.
+    let complete = bytes.iter().rposition(|&b| b == 0).map_or(0, |end| end + 1);
+    Ok(bytes[..complete]
+        .split(|&b| b == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_read_journal_ignores_truncated_record() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("journal.done");
+        fs::write(&path, b"/p/a.log\0/p/target\0/p/b.l").unwrap();
+
+        let done = read_journal(&path).unwrap();
+        assert_eq!(done.len(), 2);
+        assert!(done.contains(Path::new("/p/a.log")));
+        assert!(done.contains(Path::new("/p/target")));
+        assert!(read_journal(&temp.path().join("missing.done"))
+            .unwrap()
+            .is_empty());
+    }
+}
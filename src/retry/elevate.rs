@@ -0,0 +1,271 @@
+//! Re-deletes items that failed with permission errors using elevated privileges.
+//!
+//! This is only ever used behind `--sudo-retry`, after the user has confirmed the exact
+//! list of paths. On Unix the paths are removed with `sudo rm -rf` (or `pkexec` when
+//! `sudo` is not installed). On Windows `mc` itself is started elevated through a UAC
+//! prompt, with each path as its own argument to the hidden [`HELPER_COMMAND`], and
+//! removes them with [`remove_paths`]; no shell ever sees the paths.
+
+use crate::engine::native;
+use crate::types::{CleanError, CleanItem, CleanReport, ItemType, McError, Result};
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The hidden subcommand the elevated `mc` runs on Windows to remove the paths given
+/// as its arguments.
+pub const HELPER_COMMAND: &str = "delete-elevated";
+
+/// Returns the paths of the errors in `errors` that are permission failures.
+pub fn permission_denied_paths(errors: &[CleanError]) -> Vec<PathBuf> {
+    errors
+        .iter()
+        .filter_map(|err| match err {
+            CleanError::PermissionDenied { path, .. } => Some(path.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Deletes `paths` with elevated privileges, blocking until the helper exits.
+///
+/// Returns the paths that existed before and are gone afterwards. A helper that fails
+/// or is cancelled is only logged, as what it did remove is still returned.
+///
+/// # Errors
+///
+/// Returns an error if no elevation helper is available or it cannot be started.
+pub fn delete_elevated(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let present: Vec<&PathBuf> = paths
+        .iter()
+        .filter(|path| path.symlink_metadata().is_ok())
+        .collect();
+    if present.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut cmd = elevated_command(&present)?;
+    crate::hooks::keep_off_stdio(&mut cmd);
+    let status = cmd.status()?;
+    if !status.success() {
+        log::warn!("Elevated delete exited with {}", status);
+    }
+    Ok(present
+        .into_iter()
+        .filter(|path| path.symlink_metadata().is_err())
+        .cloned()
+        .collect())
+}
+
+/// Removes each of `paths`, without following links, for the elevated [`HELPER_COMMAND`].
+///
+/// Every path is attempted; paths that are already gone are not an error.
+///
+/// # Errors
+///
+/// Returns the first failure, once every path has been attempted.
+pub fn remove_paths(paths: &[PathBuf]) -> Result<()> {
+    let mut first_err = None;
+    for path in paths {
+        let result = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => native::remove_dir_all(path),
+            Ok(_) => native::remove_file(path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to remove {}: {}", path.display(), err);
+            first_err.get_or_insert(err);
+        }
+    }
+    first_err.map_or(Ok(()), |err| Err(McError::Io(err)))
+}
+
+/// Moves the permission errors on the `removed` paths, as verified by
+/// [`delete_elevated`], out of `report`, counting those that are items of the clean as
+/// deleted.
+///
+/// Returns the number of errors that were resolved.
+pub fn reconcile(report: &mut CleanReport, items: &[CleanItem], removed: &[PathBuf]) -> usize {
+    let mut resolved = 0;
+    report.errors.retain(|err| {
+        let CleanError::PermissionDenied { path, .. } = err else {
+            return true;
+        };
+        if !removed.contains(path) {
+            return true;
+        }
+        // A path below an item was only part of it; the item's other failures, if
+        // any, still stand.
+        if let Some(item) = items.iter().find(|item| &item.path == path) {
+            report.bytes_freed += item.size;
+            match item.item_type {
+                ItemType::Directory => report.dirs_deleted += 1,
+                _ => report.files_deleted += 1,
+            }
+            report.items_deleted += 1;
+        }
+        resolved += 1;
+        false
+    });
+    resolved
+}
+
+#[cfg(unix)]
+fn elevated_command(paths: &[&PathBuf]) -> Result<Command> {
+    let helper = ["sudo", "pkexec"]
+        .into_iter()
+        .find(|helper| on_path(helper))
+        .ok_or_else(|| McError::Elevation("neither sudo nor pkexec is available".to_string()))?;
+    let mut cmd = Command::new(helper);
+    cmd.args(["rm", "-rf", "--"]).args(paths);
+    Ok(cmd)
+}
+
+#[cfg(windows)]
+fn elevated_command(paths: &[&PathBuf]) -> Result<Command> {
+    let exe = std::env::current_exe()?;
+    let mut args = vec![powershell_quote(HELPER_COMMAND)];
+    args.extend(
+        paths
+            .iter()
+            .map(|path| powershell_quote(&windows_arg(&path.to_string_lossy()))),
+    );
+    let script = format!(
+        "$helper = Start-Process -FilePath {} -ArgumentList {} -Verb RunAs -Wait -PassThru \
+         -WindowStyle Hidden; exit $helper.ExitCode",
+        powershell_quote(&windows_arg(&exe.to_string_lossy())),
+        args.join(",")
+    );
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-EncodedCommand"])
+        .arg(encode_command(&script));
+    Ok(cmd)
+}
+
+/// Quotes `arg` as a PowerShell single-quoted string, in which nothing is expanded.
+#[cfg(any(windows, test))]
+fn powershell_quote(arg: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in arg.chars() {
+        // PowerShell also ends single-quoted strings at typographic single quotes.
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Quotes `arg` for a Windows command line, which `Start-Process` joins its arguments
+/// into. Windows paths cannot contain `"`, so only trailing backslashes need doubling.
+#[cfg(any(windows, test))]
+fn windows_arg(arg: &str) -> String {
+    let trailing = arg.len() - arg.trim_end_matches('\\').len();
+    format!("\"{}{}\"", arg, "\\".repeat(trailing))
+}
+
+/// Encodes `script` for `powershell -EncodedCommand`: base64 of its UTF-16LE bytes, so
+/// the script reaches PowerShell without another round of command-line quoting.
+#[cfg(any(windows, test))]
+fn encode_command(script: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let bytes: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(not(any(unix, windows)))]
+fn elevated_command(_paths: &[&PathBuf]) -> Result<Command> {
+    Err(McError::Elevation(
+        "elevated deletion is not supported on this platform".to_string(),
+    ))
+}
+
+/// Returns `true` if `program` is an executable found on `PATH`.
+#[cfg(unix)]
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use std::path::Path;
+
+    #[test]
+    fn test_reconcile_counts_removed_items() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let gone = temp.path().join("gone");
+        let kept = temp.path().join("kept");
+        std::fs::write(&kept, b"x").unwrap();
+
+        let item = |path: &Path| clean_item(path, ItemType::File, 10, PatternCategory::Other);
+        let mut report = CleanReport {
+            errors: vec![
+                CleanError::PermissionDenied {
+                    path: gone.clone(),
+                    code: None,
+                },
+                CleanError::PermissionDenied {
+                    path: kept.clone(),
+                    code: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            permission_denied_paths(&report.errors),
+            vec![gone.clone(), kept.clone()]
+        );
+        // Only a removal that was verified counts, not a path that was never there.
+        assert_eq!(reconcile(&mut report, &[item(&gone), item(&kept)], &[]), 0);
+        assert_eq!(
+            reconcile(&mut report, &[item(&gone), item(&kept)], &[gone]),
+            1
+        );
+        assert_eq!(report.items_deleted, 1);
+        assert_eq!(report.bytes_freed, 10);
+        assert_eq!(report.errors[0].path(), Some(kept.as_path()));
+    }
+
+    #[test]
+    fn test_remove_paths_takes_each_path_literally() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let dir = temp.path().join("a & b %PATH%");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        let file = temp.path().join("it's.log");
+        std::fs::write(&file, b"x").unwrap();
+
+        remove_paths(&[dir.clone(), file.clone(), temp.path().join("missing")]).unwrap();
+        assert!(!dir.exists());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn test_windows_quoting() {
+        assert_eq!(powershell_quote("it's ‘x’"), "'it''s ‘‘x’’'");
+        assert_eq!(windows_arg(r"C:\a & b\"), r#""C:\a & b\\""#);
+        // "ab" in UTF-16LE is 61 00 62 00.
+        assert_eq!(encode_command("ab"), "YQBiAA==");
+        assert_eq!(encode_command("a"), "YQA=");
+    }
+}
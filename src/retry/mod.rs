@@ -0,0 +1,178 @@
+//! This module records items that failed to delete so they can be retried without
+//! re-scanning the whole tree.
+//!
+//! When a clean ends with failures, the failed items are written to a retry manifest
+//! in the user's local data directory, one manifest per scan root. `mc retry` loads
+//! the manifest for a root (or, with `--last`, the most recent one) and attempts only
+//! those items again.
+//!
+//! Manifests are written in a versioned [`Envelope`], and one that was written by
+//! another version of mc, on another machine or over a week ago is refused unless
+//! `--force` is given.
+
+pub mod elevate;
+
+use crate::types::{CleanError, CleanItem, McError, Result};
+use crate::utils::envelope::Envelope;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The failed items of a clean, saved for a later retry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryManifest {
+    /// The root that was cleaned.
+    pub root: PathBuf,
+    /// The items that failed to delete.
+    pub items: Vec<CleanItem>,
+}
+
+impl RetryManifest {
+    /// Builds a manifest of the `items` whose deletion produced one of `errors`.
+    ///
+    /// Returns `None` when no item failed.
+    pub fn from_failures(root: &Path, items: &[CleanItem], errors: &[CleanError]) -> Option<Self> {
+        let failed: HashSet<&Path> = errors.iter().filter_map(CleanError::path).collect();
+        let items: Vec<CleanItem> = items
+            .iter()
+            .filter(|item| failed.contains(item.path.as_path()))
+            .cloned()
+            .collect();
+        if items.is_empty() {
+            return None;
+        }
+        Some(Self {
+            root: root.to_path_buf(),
+            items,
+        })
+    }
+
+    /// Returns the directory retry manifests are stored in.
+    pub fn dir() -> PathBuf {
+        ProjectDirs::from("com", "mc", "mc")
+            .map(|dirs| dirs.data_local_dir().join("retry"))
+            .unwrap_or_else(|| std::env::temp_dir().join("mc-retry"))
+    }
+
+    /// Returns the manifest path for `root`.
+    pub fn path_for(root: &Path) -> PathBuf {
+        let hash = crate::utils::fs::fnv1a(root.to_string_lossy().as_bytes());
+        Self::dir().join(format!("{:016x}.json", hash))
+    }
+
+    /// Writes the manifest to [`RetryManifest::path_for`] its root and returns the path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest cannot be serialized or written.
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = Self::path_for(&self.root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let envelope = Envelope::new(&self.root, self);
+        fs::write(&path, serde_json::to_vec_pretty(&envelope)?)?;
+        Ok(path)
+    }
+
+    /// Loads a manifest from `path`, checking that it still applies to its root.
+    ///
+    /// With `force`, a manifest that fails the check is loaded anyway, with a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or if it fails the
+    /// check and `force` is not set.
+    pub fn load(path: &Path, force: bool) -> Result<Self> {
+        let envelope: Envelope<Self> = serde_json::from_slice(&fs::read(path)?)?;
+        if let Err(mismatch) = envelope.check(&envelope.body.root) {
+            if !force {
+                return Err(McError::Safety(format!(
+                    "refusing to apply retry manifest {}: {}; pass --force to apply it anyway",
+                    path.display(),
+                    mismatch
+                )));
+            }
+            log::warn!(
+                "Applying retry manifest {} although {}",
+                path.display(),
+                mismatch
+            );
+        }
+        Ok(envelope.body)
+    }
+
+    /// Returns the most recently written manifest, if any.
+    pub fn latest() -> Option<PathBuf> {
+        fs::read_dir(Self::dir())
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .max_by_key(|path| crate::utils::fs::modified(path))
+    }
+
+    /// Removes the manifest for `root`, if there is one.
+    pub fn clear(root: &Path) {
+        let path = Self::path_for(root);
+        if let Err(err) = fs::remove_file(&path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                log::debug!(
+                    "Failed to remove retry manifest {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+
+    #[test]
+    fn test_manifest_keeps_only_failed_items() {
+        let items = vec![
+            clean_item("/p/a.log", ItemType::File, 1, PatternCategory::Logs),
+            clean_item("/p/b.log", ItemType::File, 1, PatternCategory::Logs),
+        ];
+        let errors = vec![CleanError::Busy {
+            path: PathBuf::from("/p/b.log"),
+            code: None,
+        }];
+
+        let manifest = RetryManifest::from_failures(Path::new("/p"), &items, &errors).unwrap();
+        assert_eq!(
+            manifest.items,
+            vec![clean_item(
+                "/p/b.log",
+                ItemType::File,
+                1,
+                PatternCategory::Logs
+            )]
+        );
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        let parsed: RetryManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.items, manifest.items);
+
+        assert!(RetryManifest::from_failures(Path::new("/p"), &items, &[]).is_none());
+    }
+
+    #[test]
+    fn test_manifest_path_is_stable_per_root() {
+        assert_eq!(
+            RetryManifest::path_for(Path::new("/p")),
+            RetryManifest::path_for(Path::new("/p"))
+        );
+        assert_ne!(
+            RetryManifest::path_for(Path::new("/p")),
+            RetryManifest::path_for(Path::new("/q"))
+        );
+    }
+}
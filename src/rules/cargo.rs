@@ -0,0 +1,171 @@
+//! Cargo-aware pruning of `target/` directories.
+//!
+//! In `sweep` mode, in the spirit of `cargo-sweep`, a matched Cargo target directory is
+//! not removed whole. Instead, entries inside each profile's `deps`, `build`,
+//! `.fingerprint`, and `incremental` directories that have not been modified for
+//! `max_age_days` are planned for deletion. Cargo touches the artifacts it reuses, so
+//! artifacts left behind by old toolchains or removed dependencies age out while the
+//! current build stays warm.
+
+use crate::config::{CargoMode, CargoRuleConfig};
+use crate::types::CleanItem;
+use crate::utils::fs as fs_utils;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directories inside a profile directory that hold per-crate artifacts.
+const ARTIFACT_DIRS: [&str; 4] = ["deps", "build", ".fingerprint", "incremental"];
+
+/// How deep below `target/` to look for profile directories
+/// (`target/debug`, `target/<triple>/release`, ...).
+const PROFILE_SEARCH_DEPTH: usize = 2;
+
+/// Applies the Cargo rule to the planned items.
+///
+/// In `full` mode, or for items that are not Cargo target directories, items pass
+/// through unchanged.
+pub fn apply(items: Vec<CleanItem>, config: &CargoRuleConfig) -> Vec<CleanItem> {
+    if config.mode == CargoMode::Full {
+        return items;
+    }
+
+    let cutoff = fs_utils::days_ago(config.max_age_days);
+    let mut planned = Vec::with_capacity(items.len());
+    for item in items {
+        if is_cargo_target(&item.path) {
+            planned.extend(stale_artifacts(&item, cutoff));
+        } else {
+            planned.push(item);
+        }
+    }
+    planned
+}
+
+/// Returns true if `path` looks like a Cargo target directory.
+pub fn is_cargo_target(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "target")
+        && (path.join(".rustc_info.json").exists()
+            || path.join("CACHEDIR.TAG").exists()
+            || path.parent().is_some_and(|p| p.join("Cargo.toml").exists()))
+}
+
+/// Collects artifacts under `target` last modified before `cutoff`.
+fn stale_artifacts(target: &CleanItem, cutoff: SystemTime) -> Vec<CleanItem> {
+    let mut stale = Vec::new();
+    for profile in profile_dirs(&target.path) {
+        for artifact_dir in ARTIFACT_DIRS {
+            let Ok(entries) = fs::read_dir(profile.join(artifact_dir)) else {
+                continue;
+            };
+            for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+                if fs_utils::newest_modified(&path) >= cutoff {
+                    continue;
+                }
+                if let Some(item_type) = fs_utils::item_type_of(&path) {
+                    stale.push(CleanItem {
+                        size: fs_utils::item_size(&path, &item_type),
+                        path,
+                        item_type,
+                        pattern: target.pattern.clone(),
+                    });
+                }
+            }
+        }
+    }
+    log::debug!(
+        "Cargo sweep: {} stale artifacts in {}",
+        stale.len(),
+        target.path.display()
+    );
+    stale
+}
+
+/// Finds profile directories (those containing `.fingerprint`) below `target`.
+fn profile_dirs(target: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(target)
+        .min_depth(1)
+        .max_depth(PROFILE_SEARCH_DEPTH)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir() && e.path().join(".fingerprint").is_dir())
+        .map(|e| e.into_path())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::time::Duration;
+
+    fn age(path: &Path, days: u64) {
+        let time = SystemTime::now() - Duration::from_secs(days * 86_400);
+        fs::File::open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn test_sweep_keeps_fresh_artifacts() {
+        let temp = TempDir::new().unwrap();
+        temp.child("Cargo.toml").touch().unwrap();
+        let fresh = temp.child("target/debug/deps/libfresh.rlib");
+        let old = temp.child("target/debug/deps/libold.rlib");
+        let old_fp = temp.child("target/debug/.fingerprint/old-1234/lib");
+        fresh.write_str("fresh").unwrap();
+        old.write_str("old").unwrap();
+        old_fp.write_str("fp").unwrap();
+        age(old.path(), 60);
+        age(old_fp.path(), 60);
+
+        let config = CargoRuleConfig {
+            mode: CargoMode::Sweep,
+            max_age_days: 30,
+        };
+        let planned = apply(
+            vec![clean_item(
+                temp.path().join("target"),
+                ItemType::Directory,
+                0,
+                PatternCategory::BuildOutputs,
+            )],
+            &config,
+        );
+
+        let paths: Vec<_> = planned.iter().map(|i| i.path.clone()).collect();
+        assert_eq!(planned.len(), 2, "got {:?}", paths);
+        assert!(paths.contains(&old.path().to_path_buf()));
+        assert!(paths.contains(&temp.path().join("target/debug/.fingerprint/old-1234")));
+    }
+
+    #[test]
+    fn test_full_mode_passes_through() {
+        let item = clean_item(
+            Path::new("/project/target"),
+            ItemType::Directory,
+            0,
+            PatternCategory::BuildOutputs,
+        );
+        let planned = apply(vec![item.clone()], &CargoRuleConfig::default());
+        assert_eq!(planned, vec![item]);
+    }
+
+    #[test]
+    fn test_non_cargo_target_untouched_in_sweep() {
+        let temp = TempDir::new().unwrap();
+        temp.child("target/output.bin").touch().unwrap();
+        let item = clean_item(
+            temp.path().join("target"),
+            ItemType::Directory,
+            0,
+            PatternCategory::BuildOutputs,
+        );
+        let config = CargoRuleConfig {
+            mode: CargoMode::Sweep,
+            max_age_days: 30,
+        };
+        assert_eq!(apply(vec![item.clone()], &config), vec![item]);
+    }
+}
@@ -0,0 +1,91 @@
+//! Container build cache rules.
+//!
+//! With `[rules.docker] enabled = true`, the matcher also picks up local `buildx`
+//! cache directories, `.docker` build caches inside repositories, and `kaniko` cache
+//! directories (see [`crate::patterns::DOCKER_PATTERNS`]). On CI hosts these routinely
+//! dwarf `node_modules`.
+//!
+//! `.docker` is also the name of the Docker CLI configuration directory, which holds
+//! registry credentials, so matches that look like one are dropped from the plan.
+
+use crate::config::DockerRuleConfig;
+use crate::rules::FollowUp;
+use crate::types::CleanItem;
+use std::path::Path;
+
+/// Applies the Docker rule to the planned items.
+pub fn apply(items: Vec<CleanItem>, config: &DockerRuleConfig) -> Vec<CleanItem> {
+    if !config.enabled {
+        return items;
+    }
+    items
+        .into_iter()
+        .filter(|item| {
+            let keep = !is_cli_config_dir(&item.path);
+            if !keep {
+                log::debug!("Keeping Docker CLI config {}", item.path.display());
+            }
+            keep
+        })
+        .collect()
+}
+
+/// Returns a `docker builder prune` follow-up if it is enabled.
+pub fn builder_prune(config: &DockerRuleConfig) -> Option<FollowUp> {
+    config.builder_prune.then_some(FollowUp {
+        command: "docker builder prune --force",
+        cwd: None,
+    })
+}
+
+/// Returns true if `path` is a Docker CLI configuration directory rather than a
+/// build cache.
+fn is_cli_config_dir(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == ".docker")
+        && (path.join("config.json").exists()
+            || path.parent() == directories::BaseDirs::new().as_ref().map(|d| d.home_dir()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_keeps_docker_cli_config() {
+        let temp = TempDir::new().unwrap();
+        temp.child("repo/.docker/cache/blob").touch().unwrap();
+        temp.child("home/.docker/config.json").touch().unwrap();
+        let cache = clean_item(
+            temp.path().join("repo/.docker"),
+            ItemType::Directory,
+            0,
+            PatternCategory::Cache,
+        );
+        let cli = clean_item(
+            temp.path().join("home/.docker"),
+            ItemType::Directory,
+            0,
+            PatternCategory::Cache,
+        );
+
+        let config = DockerRuleConfig {
+            enabled: true,
+            ..DockerRuleConfig::default()
+        };
+        assert_eq!(apply(vec![cache.clone(), cli], &config), vec![cache]);
+    }
+
+    #[test]
+    fn test_builder_prune_follow_up() {
+        assert_eq!(builder_prune(&DockerRuleConfig::default()), None);
+        let config = DockerRuleConfig {
+            builder_prune: true,
+            ..DockerRuleConfig::default()
+        };
+        assert!(builder_prune(&config).is_some());
+    }
+}
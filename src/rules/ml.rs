@@ -0,0 +1,93 @@
+//! Machine learning artifact rules.
+//!
+//! Experiment trackers, training logs, and model checkpoints are often the largest
+//! items on a data-science machine, but a checkpoint can also be the only copy of a
+//! week of training. The ML pattern pack is therefore opt-in, and matches in the `ML`
+//! category are only cleaned when they are old enough and, for checkpoint files,
+//! large enough to be worth it.
+
+use crate::config::MlRuleConfig;
+use crate::types::{CleanItem, ItemType, PatternCategory};
+use crate::utils::fs as fs_utils;
+
+/// Applies the ML rule to the planned items.
+pub fn apply(items: Vec<CleanItem>, config: &MlRuleConfig) -> Vec<CleanItem> {
+    if !config.enabled {
+        return items;
+    }
+
+    let cutoff = fs_utils::days_ago(config.min_age_days);
+    let min_checkpoint_bytes = config.min_checkpoint_mb.saturating_mul(1024 * 1024);
+    items
+        .into_iter()
+        .filter(|item| {
+            if item.pattern.category != PatternCategory::ML {
+                return true;
+            }
+            if item.item_type == ItemType::File && item.size < min_checkpoint_bytes {
+                log::debug!("Keeping small checkpoint {}", item.path.display());
+                return false;
+            }
+            if fs_utils::newest_modified(&item.path) >= cutoff {
+                log::debug!("Keeping recent ML artifact {}", item.path.display());
+                return false;
+            }
+            true
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    fn age(path: &Path, days: u64) {
+        let time = SystemTime::now() - Duration::from_secs(days * 86_400);
+        std::fs::File::open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_keeps_small_and_recent_artifacts() {
+        let temp = TempDir::new().unwrap();
+        let big_old = temp.child("big_old.ckpt");
+        let big_new = temp.child("big_new.ckpt");
+        let small_old = temp.child("small_old.ckpt");
+        let old_runs = temp.child("wandb/run-1/logs.txt");
+        for file in [&big_old, &big_new, &small_old, &old_runs] {
+            file.touch().unwrap();
+        }
+        for file in [&big_old, &small_old, &old_runs] {
+            age(file.path(), 30);
+        }
+
+        let gib = 1024 * 1024 * 1024;
+        let big_old = clean_item(big_old.path(), ItemType::File, 2 * gib, PatternCategory::ML);
+        let wandb = clean_item(
+            temp.path().join("wandb"),
+            ItemType::Directory,
+            10,
+            PatternCategory::ML,
+        );
+        let items = vec![
+            big_old.clone(),
+            clean_item(big_new.path(), ItemType::File, 2 * gib, PatternCategory::ML),
+            clean_item(small_old.path(), ItemType::File, 1024, PatternCategory::ML),
+            wandb.clone(),
+        ];
+
+        let config = MlRuleConfig {
+            enabled: true,
+            ..MlRuleConfig::default()
+        };
+        assert_eq!(apply(items, &config), vec![big_old, wandb]);
+    }
+}
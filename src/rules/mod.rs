@@ -0,0 +1,72 @@
+//! This module holds ecosystem-specific cleaning rules.
+//!
+//! Pattern matching treats every matched directory the same way. Rules refine the
+//! plan for ecosystems where a smarter strategy exists, e.g. sweeping only stale
+//! artifacts out of a Cargo `target/` directory. Rules run during planning, after
+//! nested items have been pruned; see [`crate::engine::planner`].
+//!
+//! Rules can also contribute opt-in pattern packs to the matcher and follow-up
+//! commands that run after a successful clean, such as `pnpm store prune`.
+
+pub mod cargo;
+pub mod docker;
+pub mod ml;
+pub mod node;
+pub mod python;
+
+use crate::config::RulesConfig;
+use crate::patterns::{PatternSet, DOCKER_PATTERNS, ML_PATTERNS};
+use crate::types::CleanItem;
+use std::path::PathBuf;
+
+/// A command a rule asks to run after cleaning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowUp {
+    /// The shell command to run.
+    pub command: &'static str,
+    /// The directory to run it from, if it matters.
+    pub cwd: Option<PathBuf>,
+}
+
+/// Returns the opt-in pattern packs enabled by `config`.
+pub fn pattern_packs(config: &RulesConfig) -> Vec<&'static PatternSet> {
+    let mut packs = Vec::new();
+    if config.docker.enabled {
+        packs.push(&*DOCKER_PATTERNS);
+    }
+    if config.ml.enabled {
+        packs.push(&*ML_PATTERNS);
+    }
+    packs
+}
+
+/// Collects the follow-up commands requested by the rules for a clean of `items`.
+pub fn follow_ups(config: &RulesConfig, items: &[CleanItem]) -> Vec<FollowUp> {
+    [
+        node::pnpm_store_prune_projects(&config.node, items)
+            .into_iter()
+            .next()
+            .map(|project| FollowUp {
+                command: "pnpm store prune",
+                cwd: Some(project),
+            }),
+        docker::builder_prune(&config.docker),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Runs `follow_ups` in order.
+///
+/// Follow-ups are best-effort housekeeping, so failures are logged rather than
+/// returned.
+pub fn run_follow_ups(follow_ups: &[FollowUp]) {
+    for follow_up in follow_ups {
+        if let Err(err) =
+            crate::hooks::run_command(follow_up.command, &[], None, follow_up.cwd.as_deref())
+        {
+            log::warn!("Follow-up command failed: {}", err);
+        }
+    }
+}
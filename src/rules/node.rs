@@ -0,0 +1,269 @@
+//! Node.js package manager awareness.
+//!
+//! Each matched item is attributed to the project directory that contains it, and the
+//! project's package manager is detected from its lockfile:
+//!
+//! - **pnpm** links `node_modules` files into a content-addressed store with hard
+//!   links, so deleting them frees only the files nobody else links to. Sizes are
+//!   recomputed to count only unshared files, and `pnpm store prune` can optionally be
+//!   run afterwards to release store packages that are no longer referenced.
+//! - **Yarn PnP** projects install from `.yarn/cache`. With zero-installs that cache is
+//!   the install source, so it is kept unless `keep_yarn_cache` is disabled.
+//! - **npm** reinstalls with `npm ci`, which requires `package-lock.json`. With
+//!   `keep_lockfiles`, lockfiles are dropped from the plan so a removed `node_modules`
+//!   can always be restored exactly.
+
+use crate::config::NodeRuleConfig;
+use crate::types::{CleanItem, ItemType, Result};
+use crate::utils::fs as fs_utils;
+use std::path::{Path, PathBuf};
+
+/// Lockfiles, in detection order, and the package manager that writes them.
+const LOCKFILES: [(&str, PackageManager); 5] = [
+    ("pnpm-lock.yaml", PackageManager::Pnpm),
+    ("yarn.lock", PackageManager::Yarn),
+    ("package-lock.json", PackageManager::Npm),
+    ("bun.lockb", PackageManager::Bun),
+    ("bun.lock", PackageManager::Bun),
+];
+
+/// The package manager used by a Node.js project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Npm,
+    Yarn,
+    /// Yarn with Plug'n'Play installs (`.pnp.cjs`).
+    YarnPnp,
+    Pnpm,
+    Bun,
+}
+
+impl PackageManager {
+    /// Detects the package manager of the project in `dir` from its lockfile.
+    ///
+    /// Returns `None` when `dir` has no `package.json`.
+    pub fn detect(dir: &Path) -> Option<Self> {
+        if !dir.join("package.json").is_file() {
+            return None;
+        }
+        let manager = LOCKFILES
+            .iter()
+            .find(|(lockfile, _)| dir.join(lockfile).is_file())
+            .map_or(PackageManager::Npm, |(_, manager)| *manager);
+        if manager == PackageManager::Yarn && dir.join(".pnp.cjs").is_file() {
+            return Some(PackageManager::YarnPnp);
+        }
+        Some(manager)
+    }
+}
+
+/// Applies the Node.js rule to the planned items.
+pub fn apply(items: Vec<CleanItem>, config: &NodeRuleConfig) -> Vec<CleanItem> {
+    items
+        .into_iter()
+        .filter_map(|mut item| {
+            let Some((project, manager)) = project_of(&item.path) else {
+                return Some(item);
+            };
+            let relative = item.path.strip_prefix(&project).unwrap_or(&item.path);
+
+            if config.keep_lockfiles && is_lockfile(relative) {
+                log::debug!("Keeping lockfile {}", item.path.display());
+                return None;
+            }
+            if config.keep_yarn_cache
+                && manager == PackageManager::YarnPnp
+                && relative.starts_with(".yarn/cache")
+            {
+                log::debug!("Keeping Yarn PnP cache {}", item.path.display());
+                return None;
+            }
+            if manager == PackageManager::Pnpm
+                && relative.starts_with("node_modules")
+                && item.item_type == ItemType::Directory
+            {
+                item.size = fs_utils::unshared_size(&item.path);
+            }
+            Some(item)
+        })
+        .collect()
+}
+
+/// Returns the pnpm projects whose `node_modules` is planned for deletion, if
+/// `pnpm store prune` is enabled.
+pub fn pnpm_store_prune_projects(config: &NodeRuleConfig, items: &[CleanItem]) -> Vec<PathBuf> {
+    if !config.pnpm_store_prune {
+        return Vec::new();
+    }
+    let mut projects: Vec<PathBuf> = items
+        .iter()
+        .filter(|item| {
+            item.path
+                .file_name()
+                .is_some_and(|name| name == "node_modules")
+        })
+        .filter_map(|item| {
+            let project = item.path.parent()?;
+            (PackageManager::detect(project)? == PackageManager::Pnpm)
+                .then(|| project.to_path_buf())
+        })
+        .collect();
+    projects.sort();
+    projects.dedup();
+    projects
+}
+
+/// Runs `pnpm store prune` once, from the first of `projects`.
+///
+/// The store is shared, so a single prune releases the packages of every cleaned
+/// project.
+///
+/// # Errors
+///
+/// Returns an error if `pnpm` cannot be run or exits unsuccessfully.
+pub fn prune_pnpm_store(projects: &[PathBuf]) -> Result<()> {
+    match projects.first() {
+        Some(project) => crate::hooks::run_command("pnpm store prune", &[], None, Some(project)),
+        None => Ok(()),
+    }
+}
+
+/// Finds the nearest ancestor of `path` that is a Node.js project.
+fn project_of(path: &Path) -> Option<(PathBuf, PackageManager)> {
+    path.ancestors()
+        .skip(1)
+        .find_map(|dir| Some((dir.to_path_buf(), PackageManager::detect(dir)?)))
+}
+
+fn is_lockfile(relative: &Path) -> bool {
+    LOCKFILES
+        .iter()
+        .any(|(lockfile, _)| relative == Path::new(lockfile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_detect_package_manager() {
+        let temp = TempDir::new().unwrap();
+        assert_eq!(PackageManager::detect(temp.path()), None);
+
+        temp.child("package.json").touch().unwrap();
+        assert_eq!(
+            PackageManager::detect(temp.path()),
+            Some(PackageManager::Npm)
+        );
+
+        temp.child("yarn.lock").touch().unwrap();
+        assert_eq!(
+            PackageManager::detect(temp.path()),
+            Some(PackageManager::Yarn)
+        );
+
+        temp.child(".pnp.cjs").touch().unwrap();
+        assert_eq!(
+            PackageManager::detect(temp.path()),
+            Some(PackageManager::YarnPnp)
+        );
+
+        temp.child("pnpm-lock.yaml").touch().unwrap();
+        assert_eq!(
+            PackageManager::detect(temp.path()),
+            Some(PackageManager::Pnpm)
+        );
+    }
+
+    #[test]
+    fn test_keeps_lockfiles_and_yarn_cache() {
+        let temp = TempDir::new().unwrap();
+        temp.child("package.json").touch().unwrap();
+        temp.child("yarn.lock").touch().unwrap();
+        temp.child(".pnp.cjs").touch().unwrap();
+
+        let lockfile = clean_item(
+            temp.path().join("yarn.lock"),
+            ItemType::File,
+            10,
+            PatternCategory::Dependencies,
+        );
+        let cache = clean_item(
+            temp.path().join(".yarn/cache"),
+            ItemType::Directory,
+            10,
+            PatternCategory::Dependencies,
+        );
+        let logs = clean_item(
+            temp.path().join("debug.log"),
+            ItemType::File,
+            10,
+            PatternCategory::Dependencies,
+        );
+        let items = vec![lockfile.clone(), cache.clone(), logs.clone()];
+
+        let planned = apply(items.clone(), &NodeRuleConfig::default());
+        assert_eq!(planned, vec![lockfile.clone(), logs.clone()]);
+
+        let config = NodeRuleConfig {
+            keep_lockfiles: true,
+            keep_yarn_cache: false,
+            ..NodeRuleConfig::default()
+        };
+        assert_eq!(apply(items, &config), vec![cache, logs]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pnpm_node_modules_excludes_hard_linked_files() {
+        let temp = TempDir::new().unwrap();
+        temp.child("package.json").touch().unwrap();
+        temp.child("pnpm-lock.yaml").touch().unwrap();
+        let stored = temp.child("store/pkg.js");
+        stored.write_str("shared contents").unwrap();
+        temp.child("node_modules/.pnpm").create_dir_all().unwrap();
+        std::fs::hard_link(stored.path(), temp.path().join("node_modules/.pnpm/pkg.js")).unwrap();
+        temp.child("node_modules/.modules.yaml")
+            .write_str("own")
+            .unwrap();
+
+        let planned = apply(
+            vec![clean_item(
+                temp.path().join("node_modules"),
+                ItemType::Directory,
+                10,
+                PatternCategory::Dependencies,
+            )],
+            &NodeRuleConfig::default(),
+        );
+        assert_eq!(planned[0].size, 3);
+    }
+
+    #[test]
+    fn test_pnpm_store_prune_projects() {
+        let temp = TempDir::new().unwrap();
+        temp.child("package.json").touch().unwrap();
+        temp.child("pnpm-lock.yaml").touch().unwrap();
+        let items = vec![clean_item(
+            temp.path().join("node_modules"),
+            ItemType::Directory,
+            10,
+            PatternCategory::Dependencies,
+        )];
+
+        assert!(pnpm_store_prune_projects(&NodeRuleConfig::default(), &items).is_empty());
+
+        let config = NodeRuleConfig {
+            pnpm_store_prune: true,
+            ..NodeRuleConfig::default()
+        };
+        assert_eq!(
+            pnpm_store_prune_projects(&config, &items),
+            vec![temp.path().to_path_buf()]
+        );
+    }
+}
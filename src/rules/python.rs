@@ -0,0 +1,175 @@
+//! Python virtualenv awareness.
+//!
+//! `venv` is a common name for unrelated directories, so matched `venv`/`.venv`
+//! directories are only cleaned when they are virtualenvs, i.e. contain `pyvenv.cfg`.
+//!
+//! With `venvs = "stale"`, a virtualenv is only cleaned when its interpreter no longer
+//! exists (for example after a system Python upgrade) or when neither the interpreter
+//! link nor the activation script has been accessed or modified for
+//! `venv_unused_days`. Actively used environments are left alone.
+
+use crate::config::{PythonRuleConfig, VenvPolicy};
+use crate::types::{CleanItem, SkipReason, SkippedItem};
+use crate::utils::fs as fs_utils;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directory names treated as virtualenvs.
+const VENV_NAMES: [&str; 2] = [".venv", "venv"];
+
+/// Applies the Python rule to the planned items.
+///
+/// Returns the items to clean, and the `venv`/`.venv` directories left alone because
+/// they are not virtualenvs, as skipped.
+pub fn apply(
+    items: Vec<CleanItem>,
+    config: &PythonRuleConfig,
+) -> (Vec<CleanItem>, Vec<SkippedItem>) {
+    let cutoff = fs_utils::days_ago(config.venv_unused_days);
+    let mut planned = Vec::with_capacity(items.len());
+    let mut skipped = Vec::new();
+    for item in items {
+        let is_venv_name = item
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| VENV_NAMES.contains(&name));
+        if !is_venv_name {
+            planned.push(item);
+            continue;
+        }
+        if !item.path.join("pyvenv.cfg").is_file() {
+            log::debug!("Skipping {}: not a virtualenv", item.path.display());
+            skipped.push(SkippedItem {
+                path: item.path,
+                pattern: item.pattern.pattern,
+                reason: SkipReason::NotVirtualenv,
+            });
+            continue;
+        }
+        if config.venvs == VenvPolicy::Stale && !is_stale(&item.path, cutoff) {
+            log::debug!("Keeping active virtualenv {}", item.path.display());
+            continue;
+        }
+        planned.push(item);
+    }
+    (planned, skipped)
+}
+
+/// Returns true if the virtualenv at `venv` has a missing interpreter or has not been
+/// used since `cutoff`.
+fn is_stale(venv: &Path, cutoff: SystemTime) -> bool {
+    let interpreter = interpreter_path(venv);
+    // `fs::metadata` follows the link, so this fails when the base interpreter is gone.
+    if fs::metadata(&interpreter).is_err() {
+        return true;
+    }
+    let last_used = [interpreter, activate_script_path(venv)]
+        .iter()
+        .filter_map(|path| fs::symlink_metadata(path).ok())
+        .flat_map(|metadata| [metadata.accessed().ok(), metadata.modified().ok()])
+        .flatten()
+        .max();
+    last_used.is_none_or(|time| time < cutoff)
+}
+
+fn interpreter_path(venv: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
+
+fn activate_script_path(venv: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv.join("Scripts").join("activate.bat")
+    } else {
+        venv.join("bin").join("activate")
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::{ItemType, PatternCategory};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn stale_config() -> PythonRuleConfig {
+        PythonRuleConfig {
+            venvs: VenvPolicy::Stale,
+            venv_unused_days: 30,
+        }
+    }
+
+    #[test]
+    fn test_skips_non_virtualenv_dirs() {
+        let temp = TempDir::new().unwrap();
+        temp.child("venv/notes.txt").touch().unwrap();
+        temp.child("app/__pycache__/mod.pyc").touch().unwrap();
+        let cache = clean_item(
+            temp.path().join("app/__pycache__"),
+            ItemType::Directory,
+            0,
+            PatternCategory::Dependencies,
+        );
+
+        let venv = temp.path().join("venv");
+        let (planned, skipped) = apply(
+            vec![
+                clean_item(&venv, ItemType::Directory, 0, PatternCategory::Dependencies),
+                cache.clone(),
+            ],
+            &PythonRuleConfig::default(),
+        );
+        assert_eq!(planned, vec![cache]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].path, venv);
+        assert_eq!(skipped[0].reason, SkipReason::NotVirtualenv);
+    }
+
+    #[test]
+    fn test_stale_policy_keeps_active_and_cleans_broken_venvs() {
+        let temp = TempDir::new().unwrap();
+        let interpreter = temp.child("python3");
+        interpreter.touch().unwrap();
+
+        temp.child("active/.venv/pyvenv.cfg").touch().unwrap();
+        temp.child("active/.venv/bin").create_dir_all().unwrap();
+        std::os::unix::fs::symlink(
+            interpreter.path(),
+            temp.path().join("active/.venv/bin/python"),
+        )
+        .unwrap();
+
+        temp.child("broken/.venv/pyvenv.cfg").touch().unwrap();
+        temp.child("broken/.venv/bin").create_dir_all().unwrap();
+        std::os::unix::fs::symlink(
+            temp.path().join("missing-python"),
+            temp.path().join("broken/.venv/bin/python"),
+        )
+        .unwrap();
+
+        let active = clean_item(
+            temp.path().join("active/.venv"),
+            ItemType::Directory,
+            0,
+            PatternCategory::Dependencies,
+        );
+        let broken = clean_item(
+            temp.path().join("broken/.venv"),
+            ItemType::Directory,
+            0,
+            PatternCategory::Dependencies,
+        );
+        let (planned, skipped) = apply(vec![active.clone(), broken.clone()], &stale_config());
+        assert_eq!(planned, vec![broken]);
+        assert!(skipped.is_empty());
+
+        let (planned, _) = apply(vec![active.clone()], &PythonRuleConfig::default());
+        assert_eq!(planned, vec![active]);
+    }
+}
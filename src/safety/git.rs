@@ -0,0 +1,813 @@
+//! Git repositories around and below a scan root, for `[safety.git]`.
+//!
+//! A repository is a directory with a `.git` entry: a directory for ordinary clones,
+//! or a file pointing at the real git directory for linked worktrees and submodules.
+//! The scanner records the repositories it walks past, and [`GitPolicies::apply`]
+//! then decides for each matched item whether it is cleaned, according to the policy
+//! of the repository it is in and of every repository inside it.
+//!
+//! The `clean_only_ignored` policy keeps what `git clean -dX` would remove, without
+//! running git: it evaluates the repository's `.gitignore` files, `info/exclude` and
+//! the user's global excludes file, and reads the index so tracked files are kept.
+//! [`uncommitted_files`] does run `git status`, to find work that a clean would lose,
+//! and [`hold_back_uncommitted`] sets aside the items holding it.
+
+use crate::config::{GitRepoPolicy, GitSafetyConfig};
+use crate::types::{CleanItem, ItemType, SkipReason, SkippedItem};
+use crate::utils::fs::path_from_bytes;
+use glob::{Pattern, PatternError};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How a repository's working tree is attached to its git directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoKind {
+    /// An ordinary repository with a `.git` directory.
+    Repository,
+    /// A linked worktree, created by `git worktree add`.
+    Worktree,
+    /// A submodule of another repository.
+    Submodule,
+}
+
+impl fmt::Display for RepoKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoKind::Repository => write!(f, "repository"),
+            RepoKind::Worktree => write!(f, "worktree"),
+            RepoKind::Submodule => write!(f, "submodule"),
+        }
+    }
+}
+
+/// Returns the kind of repository `dir` is the top of, if it is one.
+pub fn repo_kind(dir: &Path) -> Option<RepoKind> {
+    let git = dir.join(".git");
+    let metadata = fs::metadata(&git).ok()?;
+    if metadata.is_dir() {
+        return Some(RepoKind::Repository);
+    }
+    // A `.git` file holds `gitdir: <path>`; submodules point into the superproject's
+    // `.git/modules`.
+    let contents = fs::read_to_string(&git).ok()?;
+    let gitdir = contents.strip_prefix("gitdir:")?.trim();
+    if Path::new(gitdir)
+        .components()
+        .any(|c| c.as_os_str() == "modules")
+    {
+        Some(RepoKind::Submodule)
+    } else {
+        Some(RepoKind::Worktree)
+    }
+}
+
+/// Returns the top of the repository `path` is in, if any: `path` itself or its
+/// nearest ancestor with a `.git` entry.
+pub fn enclosing_repo(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Returns the repositories the policies apply to for a scan of `root`: `found`, the
+/// ones the scan found, and with `enclosing` the one containing `root`, if any.
+///
+/// The containing repository only matters when the git check was overridden, since
+/// the check refuses to clean inside it otherwise.
+pub fn repos_for(root: &Path, mut found: Vec<PathBuf>, enclosing: bool) -> Vec<PathBuf> {
+    if enclosing {
+        if let Some(repo) = enclosing_repo(root) {
+            if !found.contains(&repo) {
+                found.insert(0, repo);
+            }
+        }
+    }
+    found
+}
+
+/// The compiled `[safety.git]` policies.
+pub struct GitPolicies {
+    /// The policy for repositories no entry matches.
+    default: GitRepoPolicy,
+    /// Per-repository entries, with whether the glob matches whole paths.
+    repos: Vec<(Pattern, bool, GitRepoPolicy)>,
+    /// The least strict policy any repository gets.
+    floor: GitRepoPolicy,
+}
+
+/// The items [`GitPolicies::apply`] kept, and what it did with the others.
+#[derive(Debug, Default)]
+pub struct GitPlan {
+    /// The items that may be cleaned.
+    pub items: Vec<CleanItem>,
+    /// The items left alone because of a repository's policy.
+    pub skipped: Vec<SkippedItem>,
+    /// The repositories with a `warn` policy that kept items are in or inside of,
+    /// sorted.
+    pub warned: Vec<PathBuf>,
+}
+
+impl GitPolicies {
+    /// Compiles the policies in `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PatternError` if a repository glob is invalid.
+    pub fn from_config(config: &GitSafetyConfig) -> Result<Self, PatternError> {
+        let repos = config
+            .repos
+            .iter()
+            .map(|(glob, policy)| Ok((Pattern::new(glob)?, glob.contains('/'), *policy)))
+            .collect::<Result<_, PatternError>>()?;
+        let floor = match config.clean_mode {
+            true => GitRepoPolicy::CleanOnlyIgnored,
+            false => GitRepoPolicy::Clean,
+        };
+        Ok(Self {
+            default: config.policy,
+            repos,
+            floor,
+        })
+    }
+
+    /// Returns the policy for the repository at `repo`. When several entries match,
+    /// the strictest wins; in `clean_mode`, none is less strict than
+    /// `clean_only_ignored`.
+    pub fn policy_for(&self, repo: &Path) -> GitRepoPolicy {
+        let name = repo
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        self.repos
+            .iter()
+            .filter(|(glob, by_path, _)| match by_path {
+                true => glob.matches_path(repo),
+                false => glob.matches(name),
+            })
+            .map(|(_, _, policy)| *policy)
+            .max()
+            .unwrap_or(self.default)
+            .max(self.floor)
+    }
+
+    /// Applies the policies of `repos`, the tops of the repositories found, to `items`.
+    ///
+    /// An item inside a repository follows that repository's policy. An item that
+    /// contains repositories would delete them whole, so it is kept only if all of
+    /// them have the `clean` or `warn` policy.
+    pub fn apply(&self, items: Vec<CleanItem>, repos: &[PathBuf]) -> GitPlan {
+        let all_clean =
+            self.default.max(self.floor) == GitRepoPolicy::Clean && self.repos.is_empty();
+        if repos.is_empty() || all_clean {
+            return GitPlan {
+                items,
+                ..Default::default()
+            };
+        }
+
+        let mut plan = GitPlan::default();
+        let mut warned = BTreeSet::new();
+        let mut ignore_rules: HashMap<&Path, IgnoreRules> = HashMap::new();
+        'items: for item in items {
+            let mut touched = Vec::new();
+            for repo in repos.iter().filter(|repo| repo.starts_with(&item.path)) {
+                match self.policy_for(repo) {
+                    GitRepoPolicy::Clean => {}
+                    GitRepoPolicy::Warn => touched.push(repo),
+                    _ => {
+                        let reason = SkipReason::GitRepo { repo: repo.clone() };
+                        plan.skipped.push(skip(item, reason));
+                        continue 'items;
+                    }
+                }
+            }
+
+            let containing = repos
+                .iter()
+                .filter(|repo| item.path.starts_with(repo) && item.path != **repo)
+                .max_by_key(|repo| repo.components().count());
+            if let Some(repo) = containing {
+                let policy = self.policy_for(repo);
+                let reason = match policy {
+                    GitRepoPolicy::Clean | GitRepoPolicy::Warn => None,
+                    GitRepoPolicy::Skip => Some(SkipReason::GitRepo { repo: repo.clone() }),
+                    GitRepoPolicy::CleanOnlyIgnored => {
+                        let rules = ignore_rules
+                            .entry(repo.as_path())
+                            .or_insert_with(|| IgnoreRules::new(repo));
+                        let is_dir = item.item_type == ItemType::Directory;
+                        (!rules.is_removable(&item.path, is_dir))
+                            .then(|| SkipReason::NotIgnored { repo: repo.clone() })
+                    }
+                };
+                if let Some(reason) = reason {
+                    plan.skipped.push(skip(item, reason));
+                    continue;
+                }
+                if policy == GitRepoPolicy::Warn {
+                    touched.push(repo);
+                }
+            }
+
+            warned.extend(touched.into_iter().cloned());
+            plan.items.push(item);
+        }
+        if !plan.skipped.is_empty() {
+            log::info!(
+                "Left {} items inside git repositories alone",
+                plan.skipped.len()
+            );
+        }
+        plan.warned = warned.into_iter().collect();
+        plan
+    }
+}
+
+/// Returns the modified and untracked files that cleaning `items` would delete, for
+/// the items in or containing one of `repos`, sorted.
+///
+/// Ignored files are not included, since git does not track them. A repository
+/// whose status cannot be read is logged and left out.
+pub fn uncommitted_files(items: &[CleanItem], repos: &[PathBuf]) -> Vec<PathBuf> {
+    let item_paths: HashSet<&Path> = items.iter().map(|item| item.path.as_path()).collect();
+    let affected: BTreeSet<&Path> = repos
+        .iter()
+        .filter(|repo| {
+            items
+                .iter()
+                .any(|item| item.path.starts_with(repo) || repo.starts_with(&item.path))
+        })
+        .map(PathBuf::as_path)
+        .collect();
+
+    let mut files = Vec::new();
+    for repo in affected {
+        match status(repo) {
+            Ok(changed) => files.extend(changed.into_iter().filter(|file| {
+                file.ancestors()
+                    .any(|ancestor| item_paths.contains(ancestor))
+            })),
+            Err(err) => log::warn!(
+                "Cannot check {} for uncommitted changes: {}",
+                repo.display(),
+                err
+            ),
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Splits `items` into those holding none of `uncommitted`, the files from
+/// [`uncommitted_files`], and those holding some, returned as skipped.
+pub fn hold_back_uncommitted(
+    items: Vec<CleanItem>,
+    uncommitted: &[PathBuf],
+) -> (Vec<CleanItem>, Vec<SkippedItem>) {
+    let mut kept = Vec::new();
+    let mut held = Vec::new();
+    for item in items {
+        let files = uncommitted
+            .iter()
+            .filter(|file| file.starts_with(&item.path))
+            .count();
+        if files == 0 {
+            kept.push(item);
+        } else {
+            held.push(skip(item, SkipReason::Uncommitted { files }));
+        }
+    }
+    (kept, held)
+}
+
+/// Runs `git status` in `repo` and returns the modified and untracked files that
+/// still exist in the working tree.
+fn status(repo: &Path) -> io::Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["status", "--porcelain=v1", "-z", "--untracked-files=all"])
+        // A status check must not take the index lock from a running git command.
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    Ok(parse_status(repo, &output.stdout))
+}
+
+/// Parses `git status --porcelain=v1 -z` output, skipping files deleted from the
+/// working tree.
+fn parse_status(repo: &Path, output: &[u8]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut entries = output.split(|&b| b == 0).filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (x, y) = (entry[0], entry[1]);
+        // Renames and copies are followed by the original path.
+        if matches!(x, b'R' | b'C') || matches!(y, b'R' | b'C') {
+            entries.next();
+        }
+        if y == b'D' || (x == b'D' && y == b' ') {
+            continue;
+        }
+        files.push(repo.join(path_from_bytes(&entry[3..])));
+    }
+    files
+}
+
+/// Records that `item` was left alone for `reason`.
+fn skip(item: CleanItem, reason: SkipReason) -> SkippedItem {
+    SkippedItem {
+        path: item.path,
+        pattern: item.pattern.pattern,
+        reason,
+    }
+}
+
+/// The gitignore rules and tracked files of one repository, loaded as they are needed.
+struct IgnoreRules {
+    /// The top of the repository.
+    repo: PathBuf,
+    /// `info/exclude` and the global excludes file, which rank below every
+    /// `.gitignore`.
+    excludes: Vec<Gitignore>,
+    /// The `.gitignore` of each directory looked at so far.
+    dirs: HashMap<PathBuf, Gitignore>,
+    /// The tracked paths relative to the top, sorted, or `None` if the index could
+    /// not be read.
+    tracked: Option<Vec<PathBuf>>,
+}
+
+impl IgnoreRules {
+    fn new(repo: &Path) -> Self {
+        let git_dir = git_dir(repo);
+        let info_dir = git_dir
+            .as_deref()
+            .map(common_dir)
+            .unwrap_or_else(|| repo.join(".git"));
+        let tracked = match git_dir.as_deref().map(|dir| read_index(&dir.join("index"))) {
+            Some(Ok(tracked)) => Some(tracked),
+            // A repository without commits or staged files has no index yet.
+            Some(Err(err)) if err.kind() == io::ErrorKind::NotFound => Some(Vec::new()),
+            Some(Err(err)) => {
+                log::warn!("Cannot read the git index of {}: {}", repo.display(), err);
+                None
+            }
+            None => None,
+        };
+        let (global, _) = Gitignore::global();
+        Self {
+            repo: repo.to_path_buf(),
+            excludes: vec![load(repo, &info_dir.join("info/exclude")), global],
+            dirs: HashMap::new(),
+            tracked,
+        }
+    }
+
+    /// Returns true if `git clean -dX` would remove `path`: git ignores it, or a
+    /// directory containing it, and nothing at or below it is tracked.
+    ///
+    /// When the index cannot be read, nothing counts as removable.
+    fn is_removable(&mut self, path: &Path, is_dir: bool) -> bool {
+        self.is_ignored(path, is_dir)
+            && self.tracked.as_ref().is_some_and(|tracked| {
+                let relative = path.strip_prefix(&self.repo).unwrap_or(path);
+                let first = tracked.partition_point(|tracked| tracked.as_path() < relative);
+                !tracked
+                    .get(first)
+                    .is_some_and(|tracked| tracked.starts_with(relative))
+            })
+    }
+
+    /// Returns true if git ignores `path` or a directory containing it.
+    ///
+    /// As in git, a path inside an ignored directory stays ignored whatever the
+    /// rules say about the path itself.
+    fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.repo) else {
+            return false;
+        };
+        let mut prefix = self.repo.clone();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            let prefix_is_dir = is_dir || components.peek().is_some();
+            if self.matched(&prefix, prefix_is_dir) == Some(true) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns whether the rules ignore (`true`) or re-include (`false`) `path`
+    /// itself, or `None` if no rule mentions it.
+    ///
+    /// The `.gitignore` closest to `path` decides; a path none of them mentions
+    /// falls back to the repository-wide excludes.
+    fn matched(&mut self, path: &Path, is_dir: bool) -> Option<bool> {
+        let dirs = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.repo));
+        for dir in dirs {
+            let rules = self
+                .dirs
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| load(dir, &dir.join(".gitignore")));
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            match rules.matched(relative, is_dir) {
+                Match::Ignore(_) => return Some(true),
+                Match::Whitelist(_) => return Some(false),
+                Match::None => {}
+            }
+        }
+        let relative = path.strip_prefix(&self.repo).unwrap_or(path);
+        self.excludes
+            .iter()
+            .map(|rules| rules.matched(relative, is_dir))
+            .find(|matched| !matched.is_none())
+            .map(|matched| matched.is_ignore())
+    }
+}
+
+/// Loads the gitignore `file` of `dir`; a missing or unreadable file ignores nothing.
+fn load(dir: &Path, file: &Path) -> Gitignore {
+    if !file.is_file() {
+        return Gitignore::empty();
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(err) = builder.add(file) {
+        log::debug!("Problem in {}: {}", file.display(), err);
+    }
+    builder.build().unwrap_or_else(|err| {
+        log::warn!("Ignoring {}: {}", file.display(), err);
+        Gitignore::empty()
+    })
+}
+
+/// Returns the git directory of the repository at `repo`: `.git` itself, or where a
+/// `.git` file points.
+fn git_dir(repo: &Path) -> Option<PathBuf> {
+    let git = repo.join(".git");
+    if git.is_dir() {
+        return Some(git);
+    }
+    let contents = fs::read_to_string(&git).ok()?;
+    let gitdir = contents.strip_prefix("gitdir:")?.trim();
+    Some(repo.join(gitdir))
+}
+
+/// Returns the directory holding what the worktrees of a repository share, such as
+/// `info/exclude`, for the git directory `git_dir`.
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Reads the paths tracked in the git index at `path`, sorted.
+///
+/// Versions 2 to 4 of the index format with SHA-1 object names are understood.
+fn read_index(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let data = fs::read(path)?;
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    if data.len() < 12 || &data[..4] != b"DIRC" {
+        return Err(invalid("not a git index"));
+    }
+    let version = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    if !(2..=4).contains(&version) {
+        return Err(invalid("unsupported index version"));
+    }
+    let count = u32::from_be_bytes([data[8], data[9], data[10], data[11]]) as usize;
+
+    // Each entry has 40 bytes of stat data, a 20-byte object name and 2 bytes of
+    // flags, an extra 2 bytes of flags in version 3 and up when flagged, then the path.
+    const FIXED: usize = 62;
+    const EXTENDED: u16 = 0x4000;
+    let mut paths = Vec::with_capacity(count);
+    let mut previous: Vec<u8> = Vec::new();
+    let mut pos = 12;
+    for _ in 0..count {
+        let start = pos;
+        let flags_at = pos + FIXED - 2;
+        let flags = data
+            .get(flags_at..flags_at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| invalid("truncated index"))?;
+        pos += FIXED;
+        if version >= 3 && flags & EXTENDED != 0 {
+            pos += 2;
+        }
+        let name = if version == 4 {
+            // The path is stored as how much of the previous path to drop and what to
+            // append.
+            let mut strip = 0usize;
+            loop {
+                let byte = *data.get(pos).ok_or_else(|| invalid("truncated index"))?;
+                pos += 1;
+                strip = (strip << 7) | usize::from(byte & 0x7f);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                strip += 1;
+            }
+            let end = nul(&data, pos).ok_or_else(|| invalid("truncated index"))?;
+            let keep = previous
+                .len()
+                .checked_sub(strip)
+                .ok_or_else(|| invalid("bad path"))?;
+            let mut name = previous[..keep].to_vec();
+            name.extend_from_slice(&data[pos..end]);
+            pos = end + 1;
+            name
+        } else {
+            let end = nul(&data, pos).ok_or_else(|| invalid("truncated index"))?;
+            let name = data[pos..end].to_vec();
+            // Entries are padded with NULs to a multiple of 8 bytes.
+            pos = start + (end - start + 8) / 8 * 8;
+            name
+        };
+        paths.push(path_from_bytes(&name));
+        previous = name;
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Returns the position of the first NUL byte in `data` at or after `from`.
+fn nul(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .iter()
+        .position(|&b| b == 0)
+        .map(|i| from + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::clean_item;
+    use crate::types::PatternCategory;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_repo_kind_tells_worktrees_and_submodules_apart() {
+        let temp = TempDir::new().unwrap();
+        temp.child("clone/.git").create_dir_all().unwrap();
+        temp.child("tree/.git")
+            .write_str("gitdir: /src/app/.git/worktrees/tree\n")
+            .unwrap();
+        temp.child("sub/.git")
+            .write_str("gitdir: ../.git/modules/sub\n")
+            .unwrap();
+
+        assert_eq!(
+            repo_kind(temp.child("clone").path()),
+            Some(RepoKind::Repository)
+        );
+        assert_eq!(
+            repo_kind(temp.child("tree").path()),
+            Some(RepoKind::Worktree)
+        );
+        assert_eq!(
+            repo_kind(temp.child("sub").path()),
+            Some(RepoKind::Submodule)
+        );
+        assert_eq!(repo_kind(temp.path()), None);
+    }
+
+    #[test]
+    fn test_parse_status_skips_deleted_files() {
+        let repo = Path::new("/src/app");
+        let output = b"?? build/dev.sqlite\0 M src/main.rs\0 D gone.txt\0R  new.rs\0old.rs\0";
+
+        let files = parse_status(repo, output);
+
+        assert_eq!(
+            files,
+            [
+                repo.join("build/dev.sqlite"),
+                repo.join("src/main.rs"),
+                repo.join("new.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_follows_repo_policies() {
+        let temp = TempDir::new().unwrap();
+        let app = temp.child("app");
+        app.child(".git").create_dir_all().unwrap();
+        app.child(".gitignore").write_str("/target/\n").unwrap();
+        app.child("build").create_dir_all().unwrap();
+        app.child("target").create_dir_all().unwrap();
+        let vendored = temp.child("vendor/lib");
+        vendored
+            .child(".git")
+            .write_str("gitdir: ../../.git/modules/lib\n")
+            .unwrap();
+        vendored.child("dist").create_dir_all().unwrap();
+
+        let mut config = GitSafetyConfig {
+            policy: GitRepoPolicy::CleanOnlyIgnored,
+            ..Default::default()
+        };
+        config.repos.insert("lib".to_string(), GitRepoPolicy::Skip);
+        let policies = GitPolicies::from_config(&config).unwrap();
+        let items = vec![
+            clean_item(
+                app.child("build").path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::Other,
+            ),
+            clean_item(
+                app.child("target").path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::Other,
+            ),
+            clean_item(
+                vendored.child("dist").path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::Other,
+            ),
+            clean_item(
+                temp.child("vendor").path(),
+                ItemType::Directory,
+                0,
+                PatternCategory::Other,
+            ),
+        ];
+        let repos = vec![app.path().to_path_buf(), vendored.path().to_path_buf()];
+        let plan = policies.apply(items, &repos);
+
+        let kept: Vec<&Path> = plan.items.iter().map(|item| item.path.as_path()).collect();
+        assert_eq!(kept, [app.child("target").path()]);
+        assert_eq!(plan.skipped.len(), 3);
+        assert!(matches!(
+            plan.skipped[0].reason,
+            SkipReason::NotIgnored { .. }
+        ));
+        assert!(plan.warned.is_empty());
+    }
+
+    /// A `git clean -ndX` compatibility case: ignore files, tracked files and
+    /// untracked files, each given as `(path, contents)`.
+    struct CleanCase {
+        name: &'static str,
+        ignores: &'static [(&'static str, &'static str)],
+        tracked: &'static [&'static str],
+        untracked: &'static [&'static str],
+    }
+
+    const CLEAN_CASES: &[CleanCase] = &[
+        CleanCase {
+            name: "directories and globs",
+            ignores: &[(".gitignore", "build/\n*.log\n")],
+            tracked: &["src/main.rs"],
+            untracked: &["build/out.o", "debug.log", "src/app.log", "notes.txt"],
+        },
+        CleanCase {
+            name: "negation",
+            ignores: &[(".gitignore", "*.log\n!keep.log\n")],
+            tracked: &[],
+            untracked: &["a.log", "keep.log", "logs/b.log"],
+        },
+        CleanCase {
+            name: "no re-include below an ignored directory",
+            ignores: &[(".gitignore", "cache/\n!cache/keep\n")],
+            tracked: &[],
+            untracked: &["cache/keep", "cache/x"],
+        },
+        CleanCase {
+            name: "nested ignore files",
+            ignores: &[
+                (".gitignore", "*.tmp\n"),
+                ("sub/.gitignore", "!important.tmp\nlocal/\n"),
+            ],
+            tracked: &[],
+            untracked: &[
+                "x.tmp",
+                "sub/important.tmp",
+                "sub/y.tmp",
+                "sub/local/z",
+                "sub/other/w",
+            ],
+        },
+        CleanCase {
+            name: "anchored patterns",
+            ignores: &[(".gitignore", "/dist\n")],
+            tracked: &[],
+            untracked: &["dist/a.js", "web/dist/b.js"],
+        },
+        CleanCase {
+            name: "directory-only patterns",
+            ignores: &[(".gitignore", "out/\n")],
+            tracked: &[],
+            untracked: &["out", "other/out/x"],
+        },
+        CleanCase {
+            name: "tracked files stay",
+            ignores: &[(".gitignore", "*.lock\nvendor/\n")],
+            tracked: &["Cargo.lock", "vendor/keep.rs"],
+            untracked: &["vendor/tmp.rs", "other.lock", "vendor/sub/gen.rs"],
+        },
+        CleanCase {
+            name: "info/exclude",
+            ignores: &[(".git/info/exclude", "secret.env\n")],
+            tracked: &[],
+            untracked: &["secret.env", "public.env"],
+        },
+    ];
+
+    /// Runs git in `repo` and returns its output, or `None` if it failed.
+    fn git(repo: &Path, args: &[&str]) -> Option<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    #[test]
+    fn test_clean_only_ignored_matches_git_clean() {
+        for case in CLEAN_CASES {
+            for version in ["2", "4"] {
+                let temp = TempDir::new().unwrap();
+                let repo = temp.path().canonicalize().unwrap();
+                if git(&repo, &["init", "-q"]).is_none() {
+                    return;
+                }
+                for (file, contents) in case.ignores {
+                    temp.child(file).write_str(contents).unwrap();
+                }
+                for file in case.tracked.iter().chain(case.untracked) {
+                    temp.child(file).write_str("x").unwrap();
+                }
+                for file in case.tracked {
+                    git(&repo, &["add", "-f", file]).unwrap();
+                }
+                git(&repo, &["update-index", "--index-version", version]).unwrap();
+
+                // git lists an untracked directory holding only ignored files as the
+                // directory itself, so both sides are compared file by file.
+                let expected: BTreeSet<PathBuf> = git(&repo, &["clean", "-ndX"])
+                    .unwrap()
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("Would remove "))
+                    .flat_map(|path| files_under(&repo.join(path)))
+                    .collect();
+
+                let mut rules = IgnoreRules::new(&repo);
+                let removable: Vec<PathBuf> = walkdir::WalkDir::new(&repo)
+                    .min_depth(1)
+                    .into_iter()
+                    .filter_entry(|entry| entry.file_name() != ".git")
+                    .map(|entry| entry.unwrap())
+                    .filter(|entry| rules.is_removable(entry.path(), entry.file_type().is_dir()))
+                    .map(|entry| entry.into_path())
+                    .collect();
+                let actual: BTreeSet<PathBuf> = removable
+                    .iter()
+                    .flat_map(|path| files_under(path))
+                    .collect();
+
+                assert_eq!(
+                    actual, expected,
+                    "{} (index version {})",
+                    case.name, version
+                );
+            }
+        }
+    }
+
+    /// Returns `path` if it is a file, or every file below it if it is a directory.
+    fn files_under(path: &Path) -> Vec<PathBuf> {
+        walkdir::WalkDir::new(path)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(|entry| entry.into_path())
+            .collect()
+    }
+}
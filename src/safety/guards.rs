@@ -57,11 +57,11 @@ impl SafetyGuard {
         Ok(())
     }
 
-    /// Checks if a path is inside a git repository by looking for a `.git` directory
-    /// in the path's ancestors.
+    /// Checks if a path is inside a git repository by looking for a `.git` entry in
+    /// the path's ancestors. It is a file in linked worktrees and submodules.
     fn is_git_repo(&self, path: &Path) -> bool {
         log::debug!("Checking git repo at {}", path.display());
-        path.ancestors().any(|p| p.join(".git").exists())
+        super::git::enclosing_repo(path).is_some()
     }
 
     /// Checks that free disk space meets the configured minimum.
@@ -82,37 +82,13 @@ impl SafetyGuard {
         Ok(())
     }
 
-    /// Gets free disk space via statvfs on Unix.
-    #[cfg(unix)]
+    /// Gets free disk space, or `u64::MAX` if it cannot be determined so that an
+    /// unsupported platform never blocks cleaning.
     fn get_free_space(&self, path: &Path) -> Result<u64> {
-        use std::ffi::CString;
-        use std::mem::MaybeUninit;
-        use std::os::unix::ffi::OsStrExt;
-
-        let c_path = CString::new(path.as_os_str().as_bytes())
-            .map_err(|_| McError::Safety("Invalid path for statvfs".to_string()))?;
-
-        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
-        // SAFETY: c_path is a valid null-terminated C string, stat is properly aligned
-        let ret = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
-        if ret != 0 {
-            let err = std::io::Error::last_os_error();
-            log::warn!("statvfs failed for {}: {}", path.display(), err);
-            return Ok(u64::MAX); // Fail open — don't block cleaning
-        }
-
-        // SAFETY: statvfs returned 0, so stat is initialized
-        let stat = unsafe { stat.assume_init() };
-        #[allow(clippy::unnecessary_cast)]
-        let free_bytes = (stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64);
-        Ok(free_bytes)
-    }
-
-    /// Stub for Windows — returns u64::MAX with a warning.
-    #[cfg(windows)]
-    fn get_free_space(&self, _path: &Path) -> Result<u64> {
-        log::warn!("Disk space check not implemented on Windows");
-        Ok(u64::MAX)
+        Ok(crate::utils::fs::free_space(path).unwrap_or_else(|| {
+            log::warn!("Disk space check skipped for {}", path.display());
+            u64::MAX // Fail open — don't block cleaning
+        }))
     }
 }
 
@@ -141,6 +117,21 @@ mod tests {
         assert!(msg.contains("git repository"), "got: {}", msg);
     }
 
+    #[test]
+    fn test_is_git_repo_detects_worktree_git_file() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join(".git"),
+            "gitdir: /src/app/.git/worktrees/wt\n",
+        )
+        .unwrap();
+        std::fs::create_dir(temp.path().join("src")).unwrap();
+
+        let guard = SafetyGuard::new(true, 10, 0.0);
+        let result = guard.validate(&temp.path().join("src"));
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_is_git_repo_returns_false_without_git() {
         let temp = tempfile::TempDir::new().unwrap();
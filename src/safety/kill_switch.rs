@@ -0,0 +1,41 @@
+//! A fleet-wide kill switch that turns every clean into a dry run.
+//!
+//! Setting `MC_FORCE_DRY_RUN=1`, or creating the file `disable` in the mc
+//! configuration directory (such as `~/.config/mc/disable`), stops mc from deleting
+//! anything, whatever the command line or caller asked for. It is checked again
+//! before every clean, so long-running servers and scheduled runs notice it without
+//! being restarted. The file's contents, if any, are shown as the reason.
+
+use directories::ProjectDirs;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The environment variable that forces dry runs when set to `1`, `true` or `yes`.
+pub const FORCE_DRY_RUN_ENV: &str = "MC_FORCE_DRY_RUN";
+
+/// Returns why deleting is disabled, or `None` if it is not.
+pub fn engaged() -> Option<String> {
+    if let Ok(value) = env::var(FORCE_DRY_RUN_ENV) {
+        if matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        ) {
+            return Some(format!("{} is set", FORCE_DRY_RUN_ENV));
+        }
+    }
+    let sentinel = sentinel_path()?;
+    let contents = fs::read_to_string(&sentinel).ok().or_else(|| {
+        // An unreadable sentinel still counts; only a missing one does not.
+        sentinel.symlink_metadata().ok().map(|_| String::new())
+    })?;
+    Some(match contents.trim() {
+        "" => format!("{} exists", sentinel.display()),
+        reason => format!("{} exists: {}", sentinel.display(), reason),
+    })
+}
+
+/// Returns the path of the sentinel file, `disable` in the configuration directory.
+pub fn sentinel_path() -> Option<PathBuf> {
+    ProjectDirs::from("com", "mc", "mc").map(|dirs| dirs.config_dir().join("disable"))
+}
@@ -1,3 +1,7 @@
+pub mod git;
 pub mod guards;
+pub mod kill_switch;
+pub mod secrets;
 
 pub use guards::SafetyGuard;
+pub use secrets::SecretDetector;
@@ -0,0 +1,344 @@
+//! This module snapshots the cleaned file system before a clean, for
+//! `safety.snapshot_before`.
+//!
+//! On Btrfs, ZFS and APFS a snapshot is cheap and lets everything a clean removed be
+//! copied back. Snapshots are named `mc-<unix time>` and taken with the file system's
+//! own tools (`btrfs`, `zfs`, `tmutil`), which usually need root:
+//!
+//! - Btrfs: a read-only snapshot of the subvolume holding the path, kept in a
+//!   `.mc-snapshots` directory at the root of that subvolume.
+//! - ZFS: a snapshot of the dataset holding the path, `<dataset>@mc-<unix time>`.
+//! - APFS: a Time Machine local snapshot, named by the date `tmutil` reports. macOS
+//!   thins these out on its own, and they cannot be told apart from Time Machine's.
+//!
+//! `mc snapshots` lists and deletes them.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The directory holding Btrfs snapshots at the root of the snapshotted subvolume.
+pub const BTRFS_SNAPSHOT_DIR: &str = ".mc-snapshots";
+
+/// The prefix of the snapshots `mc` creates.
+pub const SNAPSHOT_PREFIX: &str = "mc-";
+
+/// A file system that can snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// A Btrfs subvolume.
+    Btrfs,
+    /// A ZFS dataset.
+    Zfs,
+    /// An APFS volume, through Time Machine local snapshots.
+    Apfs,
+}
+
+/// A snapshot taken before a clean.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The file system that holds the snapshot.
+    pub backend: Backend,
+    /// The name of the snapshot, e.g. `mc-1700000000`.
+    pub name: String,
+    /// Where the snapshot is: its directory on Btrfs, `<dataset>@<name>` on ZFS and
+    /// the snapshot's full name on APFS.
+    pub location: String,
+    /// A directory the files in the snapshot can be read from, with the same layout as
+    /// `root`. APFS snapshots have none until they are mounted.
+    contents: Option<PathBuf>,
+    /// The directory the snapshot was taken of.
+    root: PathBuf,
+}
+
+impl Snapshot {
+    /// Returns a shell command that copies `path`, as it was in the snapshot, back
+    /// into place. Files created since the snapshot are kept.
+    pub fn rollback_command(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        match &self.contents {
+            Some(contents) => format!(
+                "cp -a {} {}",
+                quote(&contents.join(relative).join(".")),
+                quote(path)
+            ),
+            None => format!(
+                "mkdir -p /tmp/mc-restore && mount_apfs -o nobrowse -s {} {} /tmp/mc-restore \
+                 && cp -a {} {}",
+                self.location,
+                quote(&self.root),
+                quote(&Path::new("/tmp/mc-restore").join(relative).join(".")),
+                quote(path)
+            ),
+        }
+    }
+}
+
+/// Returns the snapshot-capable file system holding `path`, if any.
+pub fn detect(path: &Path) -> Option<Backend> {
+    #[cfg(target_os = "linux")]
+    {
+        const BTRFS: i64 = 0x9123_683e;
+        const ZFS: i64 = 0x2fc1_2fc1;
+
+        match crate::utils::device::fs_magic(path)? {
+            BTRFS => Some(Backend::Btrfs),
+            ZFS => Some(Backend::Zfs),
+            _ => None,
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        match crate::utils::device::fs_type_name(path)?.as_str() {
+            "apfs" => Some(Backend::Apfs),
+            "zfs" => Some(Backend::Zfs),
+            _ => None,
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Snapshots the file system holding `path`.
+///
+/// # Errors
+///
+/// Returns an `Unsupported` error if `path` is not on Btrfs, ZFS or APFS, and an error
+/// if the snapshot tool fails.
+pub fn create(path: &Path) -> io::Result<Snapshot> {
+    let name = format!("{}{}", SNAPSHOT_PREFIX, unix_time());
+    match backend(path)? {
+        Backend::Btrfs => {
+            let subvolume = btrfs_subvolume(path)?;
+            let dir = subvolume.join(BTRFS_SNAPSHOT_DIR);
+            fs::create_dir_all(&dir)?;
+            let target = dir.join(&name);
+            run(Command::new("btrfs")
+                .args(["subvolume", "snapshot", "-r"])
+                .arg(&subvolume)
+                .arg(&target))?;
+            Ok(btrfs_snapshot(subvolume, name))
+        }
+        Backend::Zfs => {
+            let (dataset, mountpoint) = zfs_dataset(path)?;
+            let location = format!("{}@{}", dataset, name);
+            run(Command::new("zfs").arg("snapshot").arg(&location))?;
+            Ok(zfs_snapshot(&dataset, &mountpoint, name))
+        }
+        Backend::Apfs => {
+            let output = run(Command::new("tmutil").arg("localsnapshot"))?;
+            let date = output
+                .rsplit_once(':')
+                .map(|(_, date)| date.trim().to_string())
+                .filter(|date| !date.is_empty())
+                .ok_or_else(|| io::Error::other("tmutil did not report a snapshot date"))?;
+            Ok(apfs_snapshot(date))
+        }
+    }
+}
+
+/// Lists the snapshots `mc` can find for the file system holding `path`.
+///
+/// # Errors
+///
+/// Returns an `Unsupported` error if `path` is not on Btrfs, ZFS or APFS, and an error
+/// if the snapshot tool fails.
+pub fn list(path: &Path) -> io::Result<Vec<Snapshot>> {
+    let mut snapshots = match backend(path)? {
+        Backend::Btrfs => {
+            let subvolume = btrfs_subvolume(path)?;
+            match fs::read_dir(subvolume.join(BTRFS_SNAPSHOT_DIR)) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .filter(|name| name.starts_with(SNAPSHOT_PREFIX))
+                    .map(|name| btrfs_snapshot(subvolume.clone(), name))
+                    .collect(),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(err) => return Err(err),
+            }
+        }
+        Backend::Zfs => {
+            let (dataset, mountpoint) = zfs_dataset(path)?;
+            run(Command::new("zfs")
+                .args(["list", "-H", "-t", "snapshot", "-d", "1", "-o", "name"])
+                .arg(&dataset))?
+            .lines()
+            .filter_map(|line| line.split_once('@'))
+            .filter(|(_, name)| name.starts_with(SNAPSHOT_PREFIX))
+            .map(|(_, name)| zfs_snapshot(&dataset, &mountpoint, name.to_string()))
+            .collect()
+        }
+        Backend::Apfs => run(Command::new("tmutil").args(["listlocalsnapshotdates", "/"]))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_digit()))
+            .map(|date| apfs_snapshot(date.to_string()))
+            .collect(),
+    };
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
+
+/// Deletes the snapshot called `name` from the file system holding `path`.
+///
+/// # Errors
+///
+/// Returns a `NotFound` error if there is no such snapshot, and an error if the
+/// snapshot tool fails.
+pub fn delete(path: &Path, name: &str) -> io::Result<()> {
+    let snapshot = list(path)?
+        .into_iter()
+        .find(|snapshot| snapshot.name == name)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no snapshot named {}", name),
+            )
+        })?;
+    match snapshot.backend {
+        Backend::Btrfs => run(Command::new("btrfs")
+            .args(["subvolume", "delete"])
+            .arg(&snapshot.location))?,
+        Backend::Zfs => run(Command::new("zfs").arg("destroy").arg(&snapshot.location))?,
+        Backend::Apfs => run(Command::new("tmutil")
+            .arg("deletelocalsnapshots")
+            .arg(&snapshot.name))?,
+    };
+    Ok(())
+}
+
+/// Returns the backend for `path`, or an `Unsupported` error.
+fn backend(path: &Path) -> io::Result<Backend> {
+    detect(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} is not on Btrfs, ZFS or APFS", path.display()),
+        )
+    })
+}
+
+fn btrfs_snapshot(subvolume: PathBuf, name: String) -> Snapshot {
+    let dir = subvolume.join(BTRFS_SNAPSHOT_DIR).join(&name);
+    Snapshot {
+        backend: Backend::Btrfs,
+        location: dir.display().to_string(),
+        contents: Some(dir),
+        root: subvolume,
+        name,
+    }
+}
+
+fn zfs_snapshot(dataset: &str, mountpoint: &Path, name: String) -> Snapshot {
+    Snapshot {
+        backend: Backend::Zfs,
+        location: format!("{}@{}", dataset, name),
+        contents: Some(mountpoint.join(".zfs/snapshot").join(&name)),
+        root: mountpoint.to_path_buf(),
+        name,
+    }
+}
+
+fn apfs_snapshot(date: String) -> Snapshot {
+    Snapshot {
+        backend: Backend::Apfs,
+        location: format!("com.apple.TimeMachine.{}.local", date),
+        contents: None,
+        // User data lives on the data volume, mounted here behind firmlinks.
+        root: PathBuf::from("/System/Volumes/Data"),
+        name: date,
+    }
+}
+
+/// Returns the root of the Btrfs subvolume holding `path`: the nearest ancestor on the
+/// same device whose inode is 256, the inode of every subvolume root.
+#[cfg(unix)]
+fn btrfs_subvolume(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    const SUBVOLUME_ROOT_INODE: u64 = 256;
+
+    let path = fs::canonicalize(path)?;
+    let dev = fs::metadata(&path)?.dev();
+    for ancestor in path.ancestors() {
+        let metadata = fs::metadata(ancestor)?;
+        if metadata.dev() != dev {
+            break;
+        }
+        if metadata.ino() == SUBVOLUME_ROOT_INODE {
+            return Ok(ancestor.to_path_buf());
+        }
+    }
+    Err(io::Error::other(format!(
+        "cannot find the Btrfs subvolume holding {}",
+        path.display()
+    )))
+}
+
+#[cfg(not(unix))]
+fn btrfs_subvolume(_path: &Path) -> io::Result<PathBuf> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Returns the ZFS dataset holding `path` and its mount point.
+fn zfs_dataset(path: &Path) -> io::Result<(String, PathBuf)> {
+    let output = run(Command::new("zfs")
+        .args(["list", "-H", "-o", "name,mountpoint"])
+        .arg(path))?;
+    output
+        .lines()
+        .next()
+        .and_then(|line| line.split_once('\t'))
+        .map(|(dataset, mountpoint)| (dataset.to_string(), PathBuf::from(mountpoint)))
+        .ok_or_else(|| io::Error::other("zfs list did not report a dataset"))
+}
+
+/// Runs `command`, returning its standard output or an error with its standard error.
+fn run(command: &mut Command) -> io::Result<String> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{:?} failed: {}",
+            command.get_program(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Quotes `path` for a POSIX shell.
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback_copies_path_back_from_snapshot() {
+        let snapshot = btrfs_snapshot(PathBuf::from("/home"), "mc-1".to_string());
+        assert_eq!(
+            snapshot.rollback_command(Path::new("/home/me/it's")),
+            r"cp -a '/home/.mc-snapshots/mc-1/me/it'\''s/.' '/home/me/it'\''s'"
+        );
+
+        let snapshot = zfs_snapshot("tank/src", Path::new("/src"), "mc-2".to_string());
+        assert_eq!(snapshot.location, "tank/src@mc-2");
+        assert_eq!(
+            snapshot.rollback_command(Path::new("/src/app")),
+            "cp -a '/src/.zfs/snapshot/mc-2/app/.' '/src/app'"
+        );
+    }
+}
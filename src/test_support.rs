@@ -0,0 +1,28 @@
+//! Fixtures shared by the unit tests.
+
+use crate::types::{CleanItem, ItemType, PatternCategory, PatternMatch, PatternSource};
+use std::path::Path;
+
+/// Returns a clean item for `path` of `size` bytes, matched by a built-in pattern in
+/// `category` that is named after the path's file name.
+pub(crate) fn clean_item(
+    path: impl AsRef<Path>,
+    item_type: ItemType,
+    size: u64,
+    category: PatternCategory,
+) -> CleanItem {
+    let path = path.as_ref();
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    CleanItem {
+        path: path.to_path_buf(),
+        size,
+        item_type,
+        pattern: PatternMatch {
+            pattern: name.to_string_lossy().into_owned(),
+            priority: 0,
+            source: PatternSource::BuiltIn,
+            category,
+            rebuild: None,
+        },
+    }
+}
@@ -5,12 +5,17 @@
 //! are designed to be serializable with `serde` for potential use in structured
 //! output formats like JSON.
 
-use serde::Serialize;
-use std::path::PathBuf;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Represents an item on the file system that has been identified for cleaning.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CleanItem {
     /// The absolute path to the item.
     pub path: PathBuf,
@@ -22,8 +27,39 @@ pub struct CleanItem {
     pub pattern: PatternMatch,
 }
 
-/// An enumeration of the types of file system items that can be cleaned.
+/// One entry of a dry-run plan: a matched item and what a real run would do with it.
 #[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedItem {
+    /// The absolute path to the item.
+    pub path: PathBuf,
+    /// The size of the item in bytes.
+    pub size: u64,
+    /// Whether `size` is estimated from a sample, with `options.size_mode = "fast"`.
+    /// The contents of such an item were not checked either, so a clean may keep it,
+    /// or part of it, for secrets, scoped excludes or git policies.
+    pub estimated: bool,
+    /// The type of the file system item.
+    pub item_type: ItemType,
+    /// The category of the pattern that matched.
+    pub category: PatternCategory,
+    /// The pattern that matched.
+    pub pattern: String,
+    /// Where the matching pattern came from.
+    pub source: PatternSource,
+    /// Whole days since the item itself was last modified, if known.
+    pub age_days: Option<u64>,
+    /// Whole days since the item was last used (accessed or modified), if known.
+    pub unused_days: Option<u64>,
+    /// The nearest enclosing project directory, if any.
+    pub project: Option<PathBuf>,
+    /// The command that would run instead of deleting the item, if an action applies.
+    pub command: Option<String>,
+    /// What getting the item back takes, as in [`PatternMatch::rebuild`].
+    pub rebuild: Option<Arc<str>>,
+}
+
+/// An enumeration of the types of file system items that can be cleaned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ItemType {
     /// A directory.
     Directory,
@@ -34,7 +70,7 @@ pub enum ItemType {
 }
 
 /// Represents the details of a pattern match.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PatternMatch {
     /// The glob pattern that was matched.
     pub pattern: String,
@@ -44,21 +80,41 @@ pub struct PatternMatch {
     pub source: PatternSource,
     /// The category of the pattern for UI grouping.
     pub category: PatternCategory,
+    /// What getting the item back takes, as configured under `[patterns.rebuild]` for
+    /// the pattern or its category, such as `npm ci ~2min`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuild: Option<Arc<str>>,
 }
 
-/// An enumeration of the possible sources for a cleaning pattern.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+/// An enumeration of the possible sources for a cleaning pattern, from lowest to
+/// highest precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum PatternSource {
-    /// A pattern that is built into `mc`.
+    /// A pattern that is built into `mc`, including the opt-in pattern packs.
     BuiltIn,
-    /// A pattern from a `.mc.toml` configuration file.
+    /// A pattern from the global configuration file.
+    Global,
+    /// A pattern from a project `.mc.toml`, or a file passed with `--config`.
     Config,
     /// A pattern provided via a command-line argument.
     CLI,
 }
 
+impl PatternSource {
+    /// Returns the priority patterns from this source get unless one is set explicitly
+    /// under `[patterns.priority]`. When several patterns match, the highest wins.
+    pub fn default_priority(self) -> u32 {
+        match self {
+            PatternSource::BuiltIn => 100,
+            PatternSource::Global => 200,
+            PatternSource::Config => 300,
+            PatternSource::CLI => 400,
+        }
+    }
+}
+
 /// Categories for organizing matched patterns in the UI.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum PatternCategory {
     /// Dependencies (node_modules, vendor, .venv)
     Dependencies,
@@ -70,11 +126,32 @@ pub enum PatternCategory {
     IDE,
     /// Log files
     Logs,
+    /// Machine learning artifacts (wandb, mlruns, checkpoints)
+    ML,
     /// Other/uncategorized
     Other,
 }
 
 impl PatternCategory {
+    /// Every category, in the order they are shown.
+    pub const ALL: [PatternCategory; 7] = [
+        PatternCategory::Dependencies,
+        PatternCategory::BuildOutputs,
+        PatternCategory::Cache,
+        PatternCategory::IDE,
+        PatternCategory::Logs,
+        PatternCategory::ML,
+        PatternCategory::Other,
+    ];
+
+    /// Looks up a category by its name as written in configuration files, such as
+    /// `BuildOutputs`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|category| format!("{:?}", category) == name)
+    }
+
     /// Returns a human-readable label for the category.
     pub fn label(&self) -> &'static str {
         match self {
@@ -83,11 +160,216 @@ impl PatternCategory {
             PatternCategory::Cache => "Cache",
             PatternCategory::IDE => "IDE",
             PatternCategory::Logs => "Logs",
+            PatternCategory::ML => "ML",
             PatternCategory::Other => "Other",
         }
     }
 }
 
+/// The result of [`Scanner::scan`](crate::Scanner::scan).
+#[derive(Debug, Default, Serialize)]
+pub struct ScanOutcome {
+    /// The items that matched a cleaning pattern.
+    pub items: Vec<CleanItem>,
+    /// Errors encountered while walking the tree, sorted by path and capped by
+    /// [`Scanner::with_error_limit`](crate::Scanner::with_error_limit). They do not
+    /// stop the scan.
+    pub errors: Vec<ScanError>,
+    /// Every error of the scan, including those past the cap, grouped by cause.
+    pub error_groups: Vec<ScanErrorGroup>,
+    /// The number of errors left out of `errors` by the cap.
+    pub errors_omitted: usize,
+    /// Totals for the walk.
+    pub stats: ScanSummary,
+    /// What each include pattern matched, keyed by pattern. Patterns that matched
+    /// nothing are listed with zero counts.
+    pub pattern_stats: BTreeMap<String, PatternStats>,
+    /// The tops of the git repositories found below the root, including the root
+    /// itself if it is one, sorted.
+    pub git_repos: Vec<PathBuf>,
+    /// Matches that were excluded or held back by a safety filter, sorted by path.
+    /// Empty unless requested with
+    /// [`Scanner::with_include_skipped`](crate::Scanner::with_include_skipped).
+    pub skipped: Vec<SkippedItem>,
+    /// The matched directories whose size is an estimate, sorted. Empty unless
+    /// requested with [`Scanner::with_size_mode`](crate::Scanner::with_size_mode).
+    pub estimated_sizes: Vec<PathBuf>,
+}
+
+/// The kind of storage device a path lives on, as far as it can be told.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    /// A solid-state drive, or any non-rotational block device.
+    Ssd,
+    /// A spinning hard disk.
+    Rotational,
+    /// A network file system, such as NFS or SMB.
+    Network,
+    /// The device could not be identified.
+    Unknown,
+}
+
+impl std::fmt::Display for DeviceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeviceKind::Ssd => "SSD",
+            DeviceKind::Rotational => "rotational disk",
+            DeviceKind::Network => "network share",
+            DeviceKind::Unknown => "unknown device",
+        })
+    }
+}
+
+/// The worker count picked for `parallel_threads = "auto"`, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AutoThreads {
+    /// The device the cleaned path lives on.
+    pub device: DeviceKind,
+    /// The number of worker threads used.
+    pub threads: usize,
+}
+
+/// How much a single pattern matched during a scan.
+///
+/// Counts are taken before nested matches are pruned, so an item inside another
+/// matched directory counts for its own pattern too.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PatternStats {
+    /// The number of items the pattern matched.
+    pub items: usize,
+    /// The total size in bytes of those items.
+    pub bytes: u64,
+}
+
+/// The number of depths a [`DepthHistogram`] tells apart.
+pub const DEPTH_BUCKETS: usize = 32;
+
+/// The number of entries visited at each depth below the root, which is at depth 0.
+///
+/// Entries at depth [`DEPTH_BUCKETS`] - 1 or deeper share the last count, which keeps
+/// the histogram, and so [`ScanSummary`], `Copy`. It serializes as a list of counts
+/// ending at the deepest depth seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthHistogram {
+    counts: [usize; DEPTH_BUCKETS],
+}
+
+impl Default for DepthHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; DEPTH_BUCKETS],
+        }
+    }
+}
+
+impl DepthHistogram {
+    /// Counts an entry at `depth`.
+    pub fn add(&mut self, depth: usize) {
+        self.counts[depth.min(DEPTH_BUCKETS - 1)] += 1;
+    }
+
+    /// Adds the counts of `other`.
+    pub fn merge(&mut self, other: &DepthHistogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+    }
+
+    /// Returns the count at each depth, up to the deepest depth seen.
+    pub fn counts(&self) -> &[usize] {
+        let len = self
+            .counts
+            .iter()
+            .rposition(|&count| count > 0)
+            .map_or(0, |i| i + 1);
+        &self.counts[..len]
+    }
+}
+
+impl Serialize for DepthHistogram {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.counts().serialize(serializer)
+    }
+}
+
+/// Totals collected while walking the tree.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ScanSummary {
+    /// The number of entries visited, including the root and entries that failed.
+    pub entries_scanned: usize,
+    /// The number of directories visited.
+    pub dirs_visited: usize,
+    /// The number of files visited.
+    pub files_visited: usize,
+    /// The number of symbolic links visited, whether or not they were followed.
+    pub symlinks_visited: usize,
+    /// The number of scan errors, including those left out of the error list.
+    pub errors: usize,
+    /// The total size in bytes of the files visited, matched or not.
+    pub bytes_examined: u64,
+    /// The number of entries visited at each depth below the root.
+    pub depth_histogram: DepthHistogram,
+    /// The number of directories at the depth limit whose contents were not walked,
+    /// not counting those inside matched directories.
+    pub depth_limited_dirs: usize,
+    /// Whether the walk was stopped by the scan timeout, so the items cover only part
+    /// of the tree.
+    pub timed_out: bool,
+    /// The wall-clock duration of the walk.
+    pub duration: Duration,
+}
+
+impl ScanSummary {
+    /// Adds the totals of `other`, such as the scan of another root.
+    pub fn merge(&mut self, other: &ScanSummary) {
+        self.entries_scanned += other.entries_scanned;
+        self.dirs_visited += other.dirs_visited;
+        self.files_visited += other.files_visited;
+        self.symlinks_visited += other.symlinks_visited;
+        self.errors += other.errors;
+        self.bytes_examined += other.bytes_examined;
+        self.depth_histogram.merge(&other.depth_histogram);
+        self.depth_limited_dirs += other.depth_limited_dirs;
+        self.timed_out |= other.timed_out;
+        self.duration += other.duration;
+    }
+}
+
+/// How long each phase of a clean took.
+///
+/// Phases that did not run, such as the confirmation prompt under `--yes` or in a
+/// library call, are zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PhaseTimings {
+    /// Walking the tree for matches.
+    pub scan: Duration,
+    /// Pruning nested matches and applying the planning rules and git policies.
+    pub prune: Duration,
+    /// Waiting for the user to confirm the clean.
+    pub confirmation: Duration,
+    /// Deleting the items, or planning their deletion in a dry run.
+    pub clean: Duration,
+}
+
+impl PhaseTimings {
+    /// Returns the duration of all phases together.
+    pub fn total(&self) -> Duration {
+        self.scan + self.prune + self.confirmation + self.clean
+    }
+
+    /// Adds the durations of `other`, such as the phases of another root.
+    pub fn merge(&mut self, other: &PhaseTimings) {
+        self.scan += other.scan;
+        self.prune += other.prune;
+        self.confirmation += other.confirmation;
+        self.clean += other.clean;
+    }
+}
+
 /// A report summarizing the results of a cleaning operation.
 #[derive(Debug, Default, Serialize)]
 pub struct CleanReport {
@@ -95,14 +377,23 @@ pub struct CleanReport {
     pub items_deleted: usize,
     /// The total number of bytes freed.
     pub bytes_freed: u64,
+    /// The part of `bytes_freed` that is estimated rather than measured, in a dry run
+    /// with `options.size_mode = "fast"`.
+    pub bytes_estimated: u64,
     /// A list of errors that occurred during the cleaning process.
     pub errors: Vec<CleanError>,
     /// A list of errors that occurred during the scanning process.
     pub scan_errors: Vec<ScanError>,
+    /// Every scan error, including those left out of `scan_errors`, grouped by cause.
+    pub scan_error_groups: Vec<ScanErrorGroup>,
+    /// The number of scan errors left out of `scan_errors`.
+    pub scan_errors_omitted: usize,
     /// The total duration of the cleaning operation.
     pub duration: Duration,
     /// The duration of the scanning phase.
     pub scan_duration: Duration,
+    /// The duration of every phase, `scan_duration` and `duration` included.
+    pub timings: PhaseTimings,
     /// A flag indicating whether the operation was a dry run.
     pub dry_run: bool,
     /// Number of directories deleted.
@@ -111,6 +402,361 @@ pub struct CleanReport {
     pub files_deleted: usize,
     /// Total entries scanned during the scan phase.
     pub entries_scanned: usize,
+    /// Statistics for the scan phase.
+    pub scan: ScanSummary,
+    /// Directories at `safety.max_depth` whose contents were not scanned.
+    pub depth_limited_dirs: usize,
+    /// Number of action commands run (or, in a dry run, that would be run) in place of deletion.
+    pub commands_run: usize,
+    /// Number of items already removed by someone else before their deletion.
+    pub items_already_gone: usize,
+    /// Protected paths left in place inside matched directories, which are then only
+    /// partly deleted and not counted in `items_deleted`.
+    pub paths_kept: usize,
+    /// The bytes moved to the trash rather than freed, with `[trash]` enabled.
+    pub bytes_trashed: u64,
+    /// Where each item went with `[trash]` enabled: the trash, or the fallback for
+    /// items too large for the Recycle Bin. Empty when trash is off.
+    pub routes: Vec<RoutedItem>,
+    /// Whether the scan was stopped by its timeout, leaving part of the tree unscanned.
+    pub scan_timed_out: bool,
+    /// Whether the clean was stopped by its timeout, leaving items unattempted.
+    pub clean_timed_out: bool,
+    /// Number of items left alone because the clean timeout passed before their turn.
+    pub items_not_attempted: usize,
+    /// The full per-item plan of a dry run. Empty for real runs.
+    pub plan: Vec<PlannedItem>,
+    /// What each include pattern matched during the scan, keyed by pattern.
+    pub pattern_stats: BTreeMap<String, PatternStats>,
+    /// The worker count picked automatically, if `parallel_threads` is `"auto"`.
+    pub auto_threads: Option<AutoThreads>,
+    /// Free space on the cleaned file system before a real clean, if it could be probed.
+    pub free_space_before: Option<u64>,
+    /// Free space on the cleaned file system after a real clean, if it could be probed.
+    pub free_space_after: Option<u64>,
+    /// The archive the items were written to before deletion, if archiving is enabled.
+    pub archive: Option<PathBuf>,
+    /// The quarantine batch the items were moved to instead of being deleted, if
+    /// quarantine is enabled. `bytes_freed` then only counts purged batches.
+    pub quarantine: Option<PathBuf>,
+    /// The bytes moved into the quarantine rather than freed.
+    pub bytes_quarantined: u64,
+    /// Matches that were excluded or held back by a safety filter, if they were
+    /// collected.
+    pub skipped: Vec<SkippedItem>,
+    /// Totals per category of the items deleted, or planned in a dry run, largest
+    /// first.
+    pub by_category: Vec<CategoryTotal>,
+    /// Totals per project of the items deleted, or planned in a dry run, largest
+    /// first.
+    pub by_project: Vec<ProjectTotal>,
+    /// The largest items deleted, or planned in a dry run, largest first. A merged
+    /// report keeps those of every root.
+    pub largest_items: Vec<LargestItem>,
+    /// One section per root when several roots were cleaned in one run; the other
+    /// fields then hold the merged totals. Empty for a single root.
+    pub roots: Vec<RootReport>,
+}
+
+/// The results for one of several roots cleaned in one run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RootReport {
+    /// The root that was cleaned.
+    pub root: PathBuf,
+    /// The number of items deleted under the root.
+    pub items_deleted: usize,
+    /// The number of bytes freed under the root.
+    pub bytes_freed: u64,
+    /// The errors that occurred while cleaning the root.
+    pub errors: Vec<CleanError>,
+    /// The errors that occurred while scanning the root.
+    pub scan_errors: Vec<ScanError>,
+    /// How long scanning and cleaning the root took.
+    pub duration: Duration,
+    /// The free space the clean gained on the root's file system, if it was probed.
+    pub space_reclaimed: Option<i64>,
+    /// The archive the root's items were written to, if archiving is enabled.
+    pub archive: Option<PathBuf>,
+    /// The quarantine batch the root's items were moved to, if quarantine is enabled.
+    pub quarantine: Option<PathBuf>,
+}
+
+/// One of the largest items of a clean, in [`CleanReport::largest_items`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LargestItem {
+    /// The absolute path to the item.
+    pub path: PathBuf,
+    /// The bytes deleting it freed, or its size in a dry run.
+    pub bytes: u64,
+    /// The type of the item.
+    pub item_type: ItemType,
+    /// The category of the pattern that matched it.
+    pub category: PatternCategory,
+    /// What getting it back takes, as in [`PatternMatch::rebuild`].
+    pub rebuild: Option<Arc<str>>,
+}
+
+impl LargestItem {
+    /// Describes `item`, whose deletion freed `bytes`.
+    pub fn new(item: &CleanItem, bytes: u64) -> Self {
+        Self {
+            path: item.path.clone(),
+            bytes,
+            item_type: item.item_type.clone(),
+            category: item.pattern.category,
+            rebuild: item.pattern.rebuild.clone(),
+        }
+    }
+}
+
+/// What a clean did with the items of one pattern category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CategoryTotal {
+    /// The category.
+    pub category: PatternCategory,
+    /// The number of items deleted, or planned in a dry run.
+    pub items: usize,
+    /// The bytes freed, or that would be freed in a dry run.
+    pub bytes: u64,
+    /// The number of items that could not be deleted.
+    pub errors: usize,
+}
+
+/// What a clean did with the items of one project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectTotal {
+    /// The nearest enclosing project directory of the items, or `None` for items
+    /// outside any project.
+    pub project: Option<PathBuf>,
+    /// The number of items deleted, or planned in a dry run.
+    pub items: usize,
+    /// The bytes freed, or that would be freed in a dry run.
+    pub bytes: u64,
+    /// The number of items that could not be deleted.
+    pub errors: usize,
+}
+
+impl CleanReport {
+    /// Sets `by_category` and `by_project`, largest first, from the category and
+    /// project of each item and the bytes it freed, or `None` if it failed.
+    pub(crate) fn set_totals(
+        &mut self,
+        items: impl IntoIterator<Item = (PatternCategory, Option<PathBuf>, Option<u64>)>,
+    ) {
+        let mut by_category = HashMap::new();
+        let mut by_project = HashMap::new();
+        for (category, project, bytes) in items {
+            let (items, bytes, errors) = match bytes {
+                Some(bytes) => (1, bytes, 0),
+                None => (0, 0, 1),
+            };
+            let total = by_category.entry(category).or_insert(CategoryTotal {
+                category,
+                items: 0,
+                bytes: 0,
+                errors: 0,
+            });
+            total.items += items;
+            total.bytes += bytes;
+            total.errors += errors;
+            let total = by_project
+                .entry(project)
+                .or_insert_with_key(|project| ProjectTotal {
+                    project: project.clone(),
+                    items: 0,
+                    bytes: 0,
+                    errors: 0,
+                });
+            total.items += items;
+            total.bytes += bytes;
+            total.errors += errors;
+        }
+        self.by_category = by_category.into_values().collect();
+        self.by_project = by_project.into_values().collect();
+        self.sort_totals();
+    }
+
+    /// Orders `by_category` and `by_project` largest first.
+    pub(crate) fn sort_totals(&mut self) {
+        self.by_category
+            .sort_by_key(|t| (Reverse(t.bytes), t.category as u8));
+        self.by_project.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.project.cmp(&b.project))
+        });
+    }
+
+    /// Sorts the errors, planned and skipped items of the report by path, and the
+    /// largest items of the same size, so that runs over the same tree give the same
+    /// report. Sections of a multi-root report keep the order of their roots.
+    pub fn sort_by_path(&mut self) {
+        let by_path = |a: &CleanError, b: &CleanError| {
+            a.path()
+                .cmp(&b.path())
+                .then_with(|| a.to_string().cmp(&b.to_string()))
+        };
+        self.errors.sort_by(by_path);
+        self.scan_errors.sort_by(|a, b| a.path().cmp(b.path()));
+        self.scan_error_groups.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.ancestor.cmp(&b.ancestor))
+                .then_with(|| a.cause.cmp(&b.cause))
+        });
+        self.plan.sort_by(|a, b| a.path.cmp(&b.path));
+        self.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self.routes.sort_by(|a, b| a.path.cmp(&b.path));
+        self.largest_items
+            .sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+        for root in &mut self.roots {
+            root.errors.sort_by(by_path);
+            root.scan_errors.sort_by(|a, b| a.path().cmp(b.path()));
+        }
+    }
+
+    /// Returns the free space the clean actually gained on disk, if it was probed.
+    ///
+    /// This differs from `bytes_freed`, the sum of the item sizes, when deleted files
+    /// had other hard links, shared reflinked extents or were still held open. It also
+    /// includes whatever else wrote to or freed space on the file system meanwhile, so
+    /// it can be negative.
+    pub fn space_reclaimed(&self) -> Option<i64> {
+        let before = i128::from(self.free_space_before?);
+        let after = i128::from(self.free_space_after?);
+        Some((after - before).clamp(i64::MIN.into(), i64::MAX.into()) as i64)
+    }
+
+    /// Adds the report for `root` to this one, which holds the merged totals of a
+    /// run over several roots, and records it as a section in `roots`.
+    ///
+    /// Free space is only kept per root, since roots may share a file system.
+    pub fn merge(&mut self, root: &Path, report: &CleanReport) {
+        self.roots.push(RootReport {
+            root: root.to_path_buf(),
+            items_deleted: report.items_deleted,
+            bytes_freed: report.bytes_freed,
+            errors: report.errors.clone(),
+            scan_errors: report.scan_errors.clone(),
+            duration: report.scan_duration + report.duration,
+            space_reclaimed: report.space_reclaimed(),
+            archive: report.archive.clone(),
+            quarantine: report.quarantine.clone(),
+        });
+        self.items_deleted += report.items_deleted;
+        self.bytes_freed += report.bytes_freed;
+        self.bytes_estimated += report.bytes_estimated;
+        self.errors.extend(report.errors.iter().cloned());
+        self.scan_errors.extend(report.scan_errors.iter().cloned());
+        self.scan_error_groups
+            .extend(report.scan_error_groups.iter().cloned());
+        self.scan_errors_omitted += report.scan_errors_omitted;
+        self.duration += report.duration;
+        self.scan_duration += report.scan_duration;
+        self.timings.merge(&report.timings);
+        self.dry_run = report.dry_run;
+        self.dirs_deleted += report.dirs_deleted;
+        self.files_deleted += report.files_deleted;
+        self.entries_scanned += report.entries_scanned;
+        self.scan.merge(&report.scan);
+        self.depth_limited_dirs += report.depth_limited_dirs;
+        self.commands_run += report.commands_run;
+        self.items_already_gone += report.items_already_gone;
+        self.paths_kept += report.paths_kept;
+        self.bytes_quarantined += report.bytes_quarantined;
+        self.bytes_trashed += report.bytes_trashed;
+        self.routes.extend(report.routes.iter().cloned());
+        self.scan_timed_out |= report.scan_timed_out;
+        self.clean_timed_out |= report.clean_timed_out;
+        self.items_not_attempted += report.items_not_attempted;
+        self.plan.extend(report.plan.iter().cloned());
+        self.skipped.extend(report.skipped.iter().cloned());
+        for (pattern, stats) in &report.pattern_stats {
+            let merged = self.pattern_stats.entry(pattern.clone()).or_default();
+            merged.items += stats.items;
+            merged.bytes += stats.bytes;
+        }
+        self.auto_threads = self.auto_threads.or(report.auto_threads);
+        let mut by_category: HashMap<_, _> = self
+            .by_category
+            .drain(..)
+            .map(|total| (total.category, total))
+            .collect();
+        for total in &report.by_category {
+            let merged = by_category
+                .entry(total.category)
+                .or_insert_with(|| CategoryTotal {
+                    items: 0,
+                    bytes: 0,
+                    errors: 0,
+                    ..total.clone()
+                });
+            merged.items += total.items;
+            merged.bytes += total.bytes;
+            merged.errors += total.errors;
+        }
+        self.by_category = by_category.into_values().collect();
+        let mut by_project: HashMap<_, _> = self
+            .by_project
+            .drain(..)
+            .map(|total| (total.project.clone(), total))
+            .collect();
+        for total in &report.by_project {
+            let merged = by_project
+                .entry(total.project.clone())
+                .or_insert_with(|| ProjectTotal {
+                    items: 0,
+                    bytes: 0,
+                    errors: 0,
+                    ..total.clone()
+                });
+            merged.items += total.items;
+            merged.bytes += total.bytes;
+            merged.errors += total.errors;
+        }
+        self.by_project = by_project.into_values().collect();
+        self.sort_totals();
+        self.largest_items
+            .extend(report.largest_items.iter().cloned());
+        self.largest_items.sort_by_key(|item| Reverse(item.bytes));
+    }
+}
+
+/// Where an item of a clean with `[trash]` enabled went, in [`CleanReport::routes`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoutedItem {
+    /// The absolute path to the item.
+    pub path: PathBuf,
+    /// Where it went, or would go in a dry run.
+    pub route: TrashRoute,
+    /// Its size.
+    pub bytes: u64,
+}
+
+/// Where an item of a clean with `[trash]` enabled went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashRoute {
+    /// Moved to the trash.
+    Trash,
+    /// Too large for the Recycle Bin and deleted permanently (`trash.fallback`).
+    Deleted,
+    /// Too large for the Recycle Bin and moved into the quarantine.
+    Quarantined,
+    /// Too large for the Recycle Bin and left alone.
+    Kept,
+}
+
+impl TrashRoute {
+    /// Returns how a listing describes the route.
+    pub fn describe(self) -> &'static str {
+        match self {
+            TrashRoute::Trash => "moved to the trash",
+            TrashRoute::Deleted => "deleted permanently",
+            TrashRoute::Quarantined => "moved into the quarantine",
+            TrashRoute::Kept => "kept",
+        }
+    }
 }
 
 /// An error that can occur during the cleaning of a single item.
@@ -120,7 +766,28 @@ pub struct CleanReport {
 pub enum CleanError {
     /// An error indicating that a file or directory could not be accessed.
     #[error("Permission denied: {path}")]
-    PermissionDenied { path: PathBuf },
+    PermissionDenied { path: PathBuf, code: Option<i32> },
+
+    /// The item disappeared while it was being deleted.
+    #[error("Not found: {path}")]
+    NotFound { path: PathBuf, code: Option<i32> },
+
+    /// The item is in use, e.g. a mount point or a file locked by another process.
+    #[error("Resource busy: {path}")]
+    Busy { path: PathBuf, code: Option<i32> },
+
+    /// The item lives on a read-only file system.
+    #[error("Read-only file system: {path}")]
+    ReadOnlyFs { path: PathBuf, code: Option<i32> },
+
+    /// Deleting the item did not finish within the per-item timeout, e.g. on a stale
+    /// network mount, or the file system reported a timeout.
+    #[error("Timed out: {path}")]
+    TimedOut { path: PathBuf, code: Option<i32> },
+
+    /// The process ran out of file descriptors while deleting the item.
+    #[error("Too many open files while deleting {path}")]
+    TooManyOpenFiles { path: PathBuf, code: Option<i32> },
 
     /// A general I/O error that occurred during deletion.
     #[error("IO error at {path}: {message}")]
@@ -129,6 +796,69 @@ pub enum CleanError {
     /// An error related to parsing a glob pattern.
     #[error("Pattern error: {0}")]
     PatternError(String),
+
+    /// A command configured as the action for an item failed.
+    #[error("Command `{command}` failed: {message}")]
+    CommandFailed { command: String, message: String },
+
+    /// One of several roots cleaned in one run could not be scanned or cleaned; the
+    /// other roots were still cleaned.
+    #[error("Cleaning {root} failed: {message}")]
+    RootFailed { root: PathBuf, message: String },
+}
+
+impl CleanError {
+    /// Classifies an I/O error that occurred while deleting `path`.
+    ///
+    /// Well-known failure kinds map to dedicated variants that keep the OS error code;
+    /// anything else becomes [`CleanError::IoError`].
+    pub fn from_io(path: PathBuf, err: &io::Error) -> Self {
+        let code = err.raw_os_error();
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => CleanError::PermissionDenied { path, code },
+            io::ErrorKind::NotFound => CleanError::NotFound { path, code },
+            io::ErrorKind::ResourceBusy => CleanError::Busy { path, code },
+            io::ErrorKind::ReadOnlyFilesystem => CleanError::ReadOnlyFs { path, code },
+            io::ErrorKind::TimedOut => CleanError::TimedOut { path, code },
+            _ if is_too_many_open_files(code) => CleanError::TooManyOpenFiles { path, code },
+            _ => CleanError::IoError {
+                path,
+                message: err.to_string(),
+            },
+        }
+    }
+
+    /// Returns the path of the item the error is about, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            CleanError::PermissionDenied { path, .. }
+            | CleanError::NotFound { path, .. }
+            | CleanError::Busy { path, .. }
+            | CleanError::ReadOnlyFs { path, .. }
+            | CleanError::TimedOut { path, .. }
+            | CleanError::TooManyOpenFiles { path, .. }
+            | CleanError::IoError { path, .. } => Some(path),
+            CleanError::PatternError(_)
+            | CleanError::CommandFailed { .. }
+            | CleanError::RootFailed { .. } => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_too_many_open_files(code: Option<i32>) -> bool {
+    matches!(code, Some(libc::EMFILE) | Some(libc::ENFILE))
+}
+
+#[cfg(windows)]
+fn is_too_many_open_files(code: Option<i32>) -> bool {
+    // ERROR_TOO_MANY_OPEN_FILES
+    code == Some(4)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_too_many_open_files(_code: Option<i32>) -> bool {
+    false
 }
 
 /// An error that can occur during the scanning of the file system.
@@ -140,6 +870,252 @@ pub enum ScanError {
     /// A symbolic link cycle was detected.
     #[error("Symbolic link cycle detected at {path}")]
     SymlinkCycle { path: PathBuf },
+    /// A symbolic link points outside the scan root and was not followed.
+    #[error("Symbolic link {path} points outside the scan root")]
+    SymlinkOutsideRoot { path: PathBuf },
+    /// A matched item is owned by another user and was skipped (`safety.only_own_files`).
+    #[error("{path} is owned by another user and was skipped")]
+    NotOwned { path: PathBuf },
+    /// A matched item is or contains a file that looks like a secret and was skipped
+    /// (`safety.secrets`).
+    #[error("{path} was protected: {secret} looks like a secret ({reason})")]
+    Secret {
+        path: PathBuf,
+        secret: PathBuf,
+        reason: String,
+    },
+}
+
+impl ScanError {
+    /// Returns the path the error is about.
+    pub fn path(&self) -> &Path {
+        match self {
+            ScanError::IoError { path, .. }
+            | ScanError::SymlinkCycle { path }
+            | ScanError::SymlinkOutsideRoot { path }
+            | ScanError::NotOwned { path }
+            | ScanError::Secret { path, .. } => path,
+        }
+    }
+
+    /// Returns what went wrong, without the path, so errors can be grouped by cause.
+    pub fn cause(&self) -> String {
+        match self {
+            ScanError::IoError { path, message } => {
+                // walkdir puts the path in its messages; the cause is what follows.
+                let path = path.display().to_string();
+                match message.split_once(&path) {
+                    Some((_, rest)) => rest.trim_start_matches(&[':', ' '][..]).to_string(),
+                    None => message.clone(),
+                }
+            }
+            ScanError::SymlinkCycle { .. } => "symbolic link cycle".to_string(),
+            ScanError::SymlinkOutsideRoot { .. } => {
+                "symbolic link points outside the scan root".to_string()
+            }
+            ScanError::NotOwned { .. } => "owned by another user".to_string(),
+            ScanError::Secret { .. } => "protected as a secret".to_string(),
+        }
+    }
+}
+
+/// An item that matched an include pattern but was left alone, with the reason.
+///
+/// These are only collected on request, see
+/// [`Scanner::with_include_skipped`](crate::Scanner::with_include_skipped).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedItem {
+    /// The path that matched.
+    pub path: PathBuf,
+    /// The include pattern it matched.
+    pub pattern: String,
+    /// Why it was not cleaned.
+    pub reason: SkipReason,
+}
+
+/// Why an item that matched an include pattern was not cleaned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// An exclude entry covers it; `exclude` is the entry as configured.
+    Excluded { exclude: String },
+    /// It is owned by another user (`safety.only_own_files`).
+    NotOwned,
+    /// It is or contains a file that looks like a secret (`safety.secrets`).
+    Secret { secret: PathBuf, reason: String },
+    /// It was used within the last `unused_for_days` days; `unused_days` is `None`
+    /// when its last use could not be determined.
+    RecentlyUsed {
+        unused_days: Option<u64>,
+        unused_for_days: u64,
+    },
+    /// It is in or contains the git repository `repo`, whose `[safety.git]` policy
+    /// keeps it.
+    GitRepo { repo: PathBuf },
+    /// It is in the git repository `repo`, whose policy only cleans ignored files,
+    /// and git does not ignore it.
+    NotIgnored { repo: PathBuf },
+    /// It holds `files` modified or untracked files that git does not ignore, and the
+    /// clean could not ask before deleting them; see
+    /// [`Cleaner::with_allow_uncommitted`](crate::Cleaner::with_allow_uncommitted).
+    Uncommitted { files: usize },
+    /// It is named like a virtualenv (`venv`, `.venv`) but has no `pyvenv.cfg`, so it
+    /// may be an unrelated directory.
+    NotVirtualenv,
+    /// The filter given to [`Scanner::with_filter`](crate::Scanner::with_filter)
+    /// rejected it.
+    Filtered,
+    /// The scan's timeout passed before the walk finished the directory's contents, so
+    /// the checks that look inside it did not run (`--scan-timeout`).
+    ScanTimedOut,
+    /// The clean's timeout passed before it was attempted (`--clean-timeout`).
+    TimedOut,
+    /// The clean was cancelled before it was attempted.
+    Cancelled,
+    /// The policy script (`options.policy_script`) decided to keep it or defer it,
+    /// giving `reason` if any.
+    Policy {
+        decision: PolicyDecision,
+        reason: Option<String>,
+    },
+    /// The policy script could not be loaded or failed on it, so it was kept.
+    PolicyFailed { error: String },
+    /// It is larger than the `capacity` bytes its Recycle Bin holds, and
+    /// `trash.fallback` keeps such items.
+    TooLargeForTrash { capacity: u64 },
+}
+
+/// What the policy script decided for an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyDecision {
+    /// Clean it.
+    Delete,
+    /// Leave it alone.
+    Keep,
+    /// Leave it alone this time; a later run may clean it.
+    Defer,
+}
+
+/// What a clean did with one item, for callers that need per-item results rather
+/// than the totals of a [`CleanReport`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemOutcome {
+    /// The item.
+    pub item: CleanItem,
+    /// What happened to it.
+    pub status: ItemStatus,
+}
+
+/// What happened to an item in a clean.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum ItemStatus {
+    /// It was deleted, freeing `bytes_freed` bytes.
+    Deleted { bytes_freed: u64 },
+    /// It was gone before it could be deleted.
+    AlreadyGone,
+    /// It was handed to the action command `command`, which succeeded.
+    Handled { command: String },
+    /// It was not attempted.
+    Skipped(SkipReason),
+    /// Deleting it, or running its action command, failed.
+    Failed(CleanError),
+}
+
+/// Scan errors with the same cause under one directory, counted together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScanErrorGroup {
+    /// What went wrong, such as `Permission denied (os error 13)`.
+    pub cause: String,
+    /// The deepest directory containing every path of the group.
+    pub ancestor: PathBuf,
+    /// The number of errors in the group.
+    pub count: usize,
+}
+
+impl ScanErrorGroup {
+    /// Groups `errors` by cause and by the directory they occurred in, then merges
+    /// groups with the same cause that are nested in one another or share a parent
+    /// directory, so thousands of errors under one unreadable tree become one group.
+    /// Groups are returned largest first.
+    pub fn summarize(errors: &[ScanError]) -> Vec<ScanErrorGroup> {
+        let mut by_dir: BTreeMap<(String, PathBuf), usize> = BTreeMap::new();
+        for error in errors {
+            let path = error.path();
+            let dir = path.parent().unwrap_or(path).to_path_buf();
+            *by_dir.entry((error.cause(), dir)).or_default() += 1;
+        }
+        let mut groups: Vec<ScanErrorGroup> = by_dir
+            .into_iter()
+            .map(|((cause, ancestor), count)| ScanErrorGroup {
+                cause,
+                ancestor,
+                count,
+            })
+            .collect();
+
+        loop {
+            let before = groups.len();
+            groups = merge_siblings(fold_nested(groups));
+            if groups.len() == before {
+                break;
+            }
+        }
+        groups.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.ancestor.cmp(&b.ancestor))
+        });
+        groups
+    }
+}
+
+/// Folds each group into a group with the same cause whose directory contains it.
+fn fold_nested(mut groups: Vec<ScanErrorGroup>) -> Vec<ScanErrorGroup> {
+    // Within a cause, directories sort right after the directory containing them.
+    groups.sort_by(|a, b| (&a.cause, &a.ancestor).cmp(&(&b.cause, &b.ancestor)));
+    let mut folded: Vec<ScanErrorGroup> = Vec::new();
+    for group in groups {
+        match folded.last_mut() {
+            Some(last)
+                if last.cause == group.cause && group.ancestor.starts_with(&last.ancestor) =>
+            {
+                last.count += group.count;
+            }
+            _ => folded.push(group),
+        }
+    }
+    folded
+}
+
+/// Merges groups with the same cause and parent directory into one group at the parent.
+fn merge_siblings(groups: Vec<ScanErrorGroup>) -> Vec<ScanErrorGroup> {
+    let mut by_parent: BTreeMap<(String, PathBuf), Vec<ScanErrorGroup>> = BTreeMap::new();
+    for group in groups {
+        let parent = group
+            .ancestor
+            .parent()
+            .unwrap_or(&group.ancestor)
+            .to_path_buf();
+        by_parent
+            .entry((group.cause.clone(), parent))
+            .or_default()
+            .push(group);
+    }
+    let mut merged = Vec::new();
+    for ((cause, parent), mut siblings) in by_parent {
+        if siblings.len() == 1 {
+            merged.append(&mut siblings);
+        } else {
+            merged.push(ScanErrorGroup {
+                cause,
+                ancestor: parent,
+                count: siblings.iter().map(|group| group.count).sum(),
+            });
+        }
+    }
+    merged
 }
 
 /// The main error type for the `mc` crate.
@@ -158,6 +1134,10 @@ pub enum McError {
     #[error("Configuration parse error: {0}")]
     ConfigParse(#[from] toml::de::Error),
 
+    /// An invalid configuration, such as an `extends` cycle.
+    #[error("Configuration error: {0}")]
+    Config(String),
+
     /// An error that occurred while serializing a configuration to TOML.
     #[error("Configuration serialize error: {0}")]
     ConfigSerialize(#[from] toml::ser::Error),
@@ -178,13 +1158,50 @@ pub enum McError {
     #[error("Safety check failed: {0}")]
     Safety(String),
 
+    /// A user-configured hook command failed.
+    #[error("Hook failed: {0}")]
+    Hook(String),
+
+    /// Deleting with elevated privileges failed or is unavailable.
+    #[error("Elevated delete failed: {0}")]
+    Elevation(String),
+
+    /// Archiving items before deletion failed; nothing was deleted.
+    #[error("Archive failed: {0}")]
+    Archive(String),
+
+    /// The quarantine directory could not be prepared; nothing was moved.
+    #[error("Quarantine failed: {0}")]
+    Quarantine(String),
+
+    /// Some of several roots cleaned in one run failed; the report lists why.
+    #[error("{0} of the roots could not be cleaned")]
+    RootsFailed(usize),
+
     /// An error that occurred while building the rayon thread pool.
     #[error("Thread pool error: {0}")]
     ThreadPool(String),
 
+    /// An argument passed by a program embedding mc was unusable, such as a null or
+    /// non-UTF-8 string.
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// mc on another machine failed or could not be reached.
+    #[error("Remote error: {0}")]
+    Remote(String),
+
+    /// A WASM plugin could not be loaded.
+    #[error("Plugin error: {0}")]
+    Plugin(String),
+
     /// An error indicating that the user cancelled the operation.
     #[error("User cancelled operation")]
     Cancelled,
+
+    /// mc panicked inside a call of the C ABI; the message is the panic's.
+    #[error("mc panicked: {0}")]
+    Panicked(String),
 }
 
 /// A specialized `Result` type for the `mc` crate, using `McError` as the error type.
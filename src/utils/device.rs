@@ -0,0 +1,130 @@
+//! Device detection for `options.parallel_threads = "auto"`.
+//!
+//! The kind of device a path lives on decides how much concurrency pays off. SSDs
+//! serve many requests at once, so they get one worker per core. Spinning disks seek
+//! for every request, so extra workers only add head movement. Network shares are
+//! latency bound, so they get more workers than there are cores.
+
+use crate::types::{AutoThreads, DeviceKind};
+use std::path::Path;
+
+/// Workers used on rotational disks.
+const ROTATIONAL_THREADS: usize = 2;
+
+/// The most workers used on network shares.
+const MAX_NETWORK_THREADS: usize = 16;
+
+/// Picks a worker count for cleaning under `path` from the kind of device it is on.
+pub fn auto_threads(path: &Path) -> AutoThreads {
+    let device = detect(path);
+    let cores = super::available_parallelism();
+    let threads = match device {
+        DeviceKind::Rotational => ROTATIONAL_THREADS.min(cores),
+        DeviceKind::Network => (cores * 2).min(MAX_NETWORK_THREADS).max(cores),
+        DeviceKind::Ssd | DeviceKind::Unknown => cores,
+    };
+    AutoThreads { device, threads }
+}
+
+/// Returns the kind of device `path` is on.
+#[cfg(target_os = "linux")]
+pub fn detect(path: &Path) -> DeviceKind {
+    use std::os::unix::fs::MetadataExt;
+
+    if is_network_fs(path) {
+        return DeviceKind::Network;
+    }
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return DeviceKind::Unknown;
+    };
+    let dev = metadata.dev();
+    let block =
+        Path::new("/sys/dev/block").join(format!("{}:{}", libc::major(dev), libc::minor(dev)));
+    // Partitions keep their queue settings on the parent disk.
+    for queue in [block.join("queue"), block.join("../queue")] {
+        match std::fs::read_to_string(queue.join("rotational")).as_deref() {
+            Ok(flag) if flag.trim() == "1" => return DeviceKind::Rotational,
+            Ok(_) => return DeviceKind::Ssd,
+            Err(_) => continue,
+        }
+    }
+    DeviceKind::Unknown
+}
+
+/// Returns true if `path` is on NFS, SMB/CIFS or another network file system.
+#[cfg(target_os = "linux")]
+fn is_network_fs(path: &Path) -> bool {
+    const NFS: i64 = 0x6969;
+    const SMB: i64 = 0x517b;
+    const CIFS: i64 = 0xff53_4d42;
+    const SMB2: i64 = 0xfe53_4d42;
+    const FUSE: i64 = 0x6573_5546;
+    const AFS: i64 = 0x5346_414f;
+    const CEPH: i64 = 0x00c3_6400;
+
+    // FUSE is counted as network since sshfs and most cloud drives are FUSE mounts.
+    fs_magic(path).is_some_and(|magic| [NFS, SMB, CIFS, SMB2, FUSE, AFS, CEPH].contains(&magic))
+}
+
+/// Returns the `statfs` magic number of the file system holding `path`.
+#[cfg(target_os = "linux")]
+#[allow(clippy::unnecessary_cast)] // f_type's width differs between targets
+pub(crate) fn fs_magic(path: &Path) -> Option<i64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read after success.
+    if unsafe { libc::statfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    Some(unsafe { stat.assume_init() }.f_type as i64)
+}
+
+/// Returns the kind of device `path` is on. Only network shares are told apart.
+#[cfg(target_os = "macos")]
+pub fn detect(path: &Path) -> DeviceKind {
+    match fs_type_name(path).as_deref() {
+        Some("nfs" | "smbfs" | "afpfs" | "webdav") => DeviceKind::Network,
+        _ => DeviceKind::Unknown,
+    }
+}
+
+/// Returns the name of the file system type holding `path`, such as `apfs`.
+#[cfg(target_os = "macos")]
+pub(crate) fn fs_type_name(path: &Path) -> Option<String> {
+    use std::ffi::CStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read after success.
+    if unsafe { libc::statfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    let name = unsafe { CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Device detection is not implemented on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn detect(_path: &Path) -> DeviceKind {
+    DeviceKind::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_auto_threads_stays_within_limits() {
+        let temp = TempDir::new().unwrap();
+        let decision = auto_threads(temp.path());
+        assert!(decision.threads >= 1);
+        if decision.device != DeviceKind::Network {
+            assert!(decision.threads <= crate::utils::available_parallelism());
+        }
+    }
+}
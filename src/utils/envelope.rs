@@ -0,0 +1,197 @@
+//! A versioned envelope for the manifests mc writes and later applies.
+//!
+//! Applying a manifest deletes the paths in it without scanning again, so it must
+//! not be applied anywhere but where it was written. The envelope records the format
+//! version, the mc version and a fingerprint of the root, and [`Envelope::check`]
+//! refuses manifests that differ in any of them or are older than [`MAX_AGE`].
+//!
+//! The root fingerprint hashes the host name, the root path and the device and inode
+//! of the root directory, so a manifest copied from another machine, or kept after
+//! its root was deleted and recreated, no longer matches.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The current manifest format version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How long a manifest stays applicable: one week.
+pub const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// A manifest body together with what is needed to tell whether it still applies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    /// The manifest format version; 0 for manifests written before versioning.
+    #[serde(default)]
+    pub schema_version: u32,
+    /// The version of mc that wrote the manifest.
+    #[serde(default)]
+    pub mc_version: String,
+    /// The fingerprint of the root, see [`fingerprint`].
+    #[serde(default)]
+    pub root_fingerprint: String,
+    /// When the manifest was written, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// The manifest itself.
+    #[serde(flatten)]
+    pub body: T,
+}
+
+/// Why a manifest may not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The manifest was written in another format.
+    SchemaVersion { found: u32 },
+    /// The manifest was written by another version of mc.
+    McVersion { found: String },
+    /// The manifest was written on another machine or for another root.
+    Root,
+    /// The manifest is older than [`MAX_AGE`].
+    Stale { age: Duration },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::SchemaVersion { found: 0 } => {
+                write!(f, "it predates versioned manifests")
+            }
+            Mismatch::SchemaVersion { found } => write!(
+                f,
+                "it uses format version {} but this mc reads version {}",
+                found, SCHEMA_VERSION
+            ),
+            Mismatch::McVersion { found } => write!(
+                f,
+                "it was written by mc {} but this is mc {}",
+                found,
+                env!("CARGO_PKG_VERSION")
+            ),
+            Mismatch::Root => {
+                write!(
+                    f,
+                    "it was written on another machine or for a different root"
+                )
+            }
+            Mismatch::Stale { age } => {
+                write!(f, "it is {} days old", age.as_secs() / (24 * 60 * 60))
+            }
+        }
+    }
+}
+
+impl<T> Envelope<T> {
+    /// Wraps `body`, a manifest for `root`, stamped with the current versions and time.
+    pub fn new(root: &Path, body: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            mc_version: env!("CARGO_PKG_VERSION").to_string(),
+            root_fingerprint: fingerprint(root),
+            created_at: now_secs(),
+            body,
+        }
+    }
+
+    /// Checks that the manifest can be applied to `root` on this machine now.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first [`Mismatch`] found.
+    pub fn check(&self, root: &Path) -> Result<(), Mismatch> {
+        if self.schema_version != SCHEMA_VERSION {
+            return Err(Mismatch::SchemaVersion {
+                found: self.schema_version,
+            });
+        }
+        if self.mc_version != env!("CARGO_PKG_VERSION") {
+            return Err(Mismatch::McVersion {
+                found: self.mc_version.clone(),
+            });
+        }
+        if self.root_fingerprint != fingerprint(root) {
+            return Err(Mismatch::Root);
+        }
+        let age = Duration::from_secs(now_secs().saturating_sub(self.created_at));
+        if age > MAX_AGE {
+            return Err(Mismatch::Stale { age });
+        }
+        Ok(())
+    }
+}
+
+/// Returns a fingerprint of `root` on this machine.
+pub fn fingerprint(root: &Path) -> String {
+    let mut key = host_name().into_bytes();
+    key.push(0);
+    key.extend_from_slice(root.to_string_lossy().as_bytes());
+    #[cfg(unix)]
+    if let Ok(metadata) = std::fs::metadata(root) {
+        use std::os::unix::fs::MetadataExt;
+        key.extend_from_slice(&metadata.dev().to_le_bytes());
+        key.extend_from_slice(&metadata.ino().to_le_bytes());
+    }
+    format!("{:016x}", super::fs::fnv1a(&key))
+}
+
+/// Returns the name of this machine, or an empty string if it is unknown.
+#[cfg(unix)]
+fn host_name() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: `buf` is valid for writes of its whole length.
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Returns the name of this machine, or an empty string if it is unknown.
+#[cfg(not(unix))]
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_check_rejects_mismatched_envelopes() {
+        let temp = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        let envelope = Envelope::new(temp.path(), ());
+        assert_eq!(envelope.check(temp.path()), Ok(()));
+        assert_eq!(envelope.check(other.path()), Err(Mismatch::Root));
+
+        let mut old = envelope.clone();
+        old.created_at -= MAX_AGE.as_secs() + 1;
+        assert!(matches!(
+            old.check(temp.path()),
+            Err(Mismatch::Stale { .. })
+        ));
+
+        let mut legacy = envelope.clone();
+        legacy.schema_version = 0;
+        assert_eq!(
+            legacy.check(temp.path()),
+            Err(Mismatch::SchemaVersion { found: 0 })
+        );
+
+        let mut upgraded = envelope;
+        upgraded.mc_version = "0.0.1".to_string();
+        assert!(matches!(
+            upgraded.check(temp.path()),
+            Err(Mismatch::McVersion { .. })
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Formatting sizes and counts the same way wherever mc prints them.
+//!
+//! Sizes use decimal units (`kB`, `MB`, `GB`), as macOS and most disk tools show
+//! them, unless binary units (`KiB`, `MiB`, `GiB`) were chosen with [`set_units`],
+//! as for `--binary`. Digits are grouped and decimals separated as is usual for the
+//! locale in `LC_ALL`, `LC_NUMERIC` or `LANG`, so `1,200` and `3.2 GB` in English
+//! are `1.200` and `3,2 GB` in German.
+
+use humansize::{format_size, BINARY, DECIMAL};
+use once_cell::sync::Lazy;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether sizes are shown in binary units, as set by [`set_units`].
+static BINARY_UNITS: AtomicBool = AtomicBool::new(false);
+
+/// The separators of the locale of the process.
+static SEPARATORS: Lazy<Separators> = Lazy::new(Separators::from_env);
+
+/// The units sizes are shown in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1000: `kB`, `MB`, `GB`.
+    #[default]
+    Decimal,
+    /// Powers of 1024: `KiB`, `MiB`, `GiB`.
+    Binary,
+}
+
+/// How digits are grouped and decimals separated in a locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separators {
+    /// Between groups of three digits, as in `1,200`.
+    pub grouping: char,
+    /// Before the decimals, as in `3.2`.
+    pub decimal: char,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Self {
+            grouping: ',',
+            decimal: '.',
+        }
+    }
+}
+
+impl Separators {
+    /// Returns the separators for a locale name such as `de_DE.UTF-8`. Unknown
+    /// locales, `C` and `POSIX` use English ones.
+    pub fn for_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default();
+        match language {
+            "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "sl" | "hr"
+            | "sr" | "vi" => Self {
+                grouping: '.',
+                decimal: ',',
+            },
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "nn" | "no" | "fi" | "uk" | "hu"
+            | "bg" | "et" | "lt" | "lv" => Self {
+                grouping: '\u{a0}',
+                decimal: ',',
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Returns the separators for the first of `LC_ALL`, `LC_NUMERIC` and `LANG`
+    /// that is set.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map(|locale| Self::for_locale(&locale))
+            .unwrap_or_default()
+    }
+}
+
+/// Sets the units [`size`] shows, for the rest of the process.
+pub fn set_units(units: SizeUnits) {
+    BINARY_UNITS.store(units == SizeUnits::Binary, Ordering::Relaxed);
+}
+
+/// Returns the units [`size`] shows.
+pub fn units() -> SizeUnits {
+    if BINARY_UNITS.load(Ordering::Relaxed) {
+        SizeUnits::Binary
+    } else {
+        SizeUnits::Decimal
+    }
+}
+
+/// Formats `bytes` in the chosen units for the locale, as in `3.2 GB`.
+pub fn size(bytes: u64) -> String {
+    size_with(bytes, units(), *SEPARATORS)
+}
+
+/// Formats a transfer rate in the chosen units for the locale, as in `12.5 MB/s`.
+pub fn rate(bytes_per_sec: f64) -> String {
+    format!("{}/s", size(bytes_per_sec as u64))
+}
+
+/// Formats `n` with its digits grouped for the locale, as in `1,200`.
+pub fn count(n: u64) -> String {
+    count_with(n, *SEPARATORS)
+}
+
+/// Formats `bytes` in `units` with `separators`.
+pub fn size_with(bytes: u64, units: SizeUnits, separators: Separators) -> String {
+    let formatted = match units {
+        SizeUnits::Decimal => format_size(bytes, DECIMAL),
+        SizeUnits::Binary => format_size(bytes, BINARY),
+    };
+    match separators.decimal {
+        '.' => formatted,
+        decimal => formatted.replacen('.', &decimal.to_string(), 1),
+    }
+}
+
+/// Formats `n` with its digits grouped by `separators`.
+pub fn count_with(n: u64, separators: Separators) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3 * 2);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(separators.grouping);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Quotes `word` for a POSIX shell, leaving it as it is if nothing in it needs quoting.
+pub fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-+=:,@".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_and_locales() {
+        let english = Separators::for_locale("en_US.UTF-8");
+        let german = Separators::for_locale("de_DE.UTF-8");
+        assert_eq!(Separators::for_locale("C"), english);
+
+        assert_eq!(
+            size_with(3_200_000_000, SizeUnits::Decimal, english),
+            "3.20 GB"
+        );
+        assert_eq!(
+            size_with(3_200_000_000, SizeUnits::Binary, english),
+            "2.98 GiB"
+        );
+        assert_eq!(
+            size_with(3_200_000_000, SizeUnits::Decimal, german),
+            "3,20 GB"
+        );
+        assert_eq!(size_with(512, SizeUnits::Binary, german), "512 B");
+
+        assert_eq!(count_with(999, english), "999");
+        assert_eq!(count_with(1_200, english), "1,200");
+        assert_eq!(count_with(1_234_567, german), "1.234.567");
+        assert_eq!(
+            count_with(5_000, Separators::for_locale("fr_FR")),
+            "5\u{a0}000"
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/usr/local/bin/mc"), "/usr/local/bin/mc");
+        assert_eq!(shell_quote("my mc"), "'my mc'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,316 @@
+//! File system helpers shared by the planner and reporting code.
+
+use crate::types::ItemType;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Returns the total size in bytes of the regular files under `path`.
+///
+/// Symbolic links are not followed. Entries that cannot be read are skipped, so the
+/// result is a lower bound when permissions are restricted.
+pub fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Returns the size of `path`: the recursive size for directories, the length otherwise.
+pub fn item_size(path: &Path, item_type: &ItemType) -> u64 {
+    match item_type {
+        ItemType::Directory => dir_size(path),
+        _ => fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0),
+    }
+}
+
+/// Determines the `ItemType` of `path` without following symlinks.
+pub fn item_type_of(path: &Path) -> Option<ItemType> {
+    let file_type = fs::symlink_metadata(path).ok()?.file_type();
+    Some(if file_type.is_symlink() {
+        ItemType::Symlink
+    } else if file_type.is_dir() {
+        ItemType::Directory
+    } else {
+        ItemType::File
+    })
+}
+
+/// Returns the modification time of `path`, or the Unix epoch if unavailable.
+pub fn modified(path: &Path) -> SystemTime {
+    fs::symlink_metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Returns the whole days since `path` itself was last modified, or `None` if unknown.
+pub fn age_days(path: &Path) -> Option<u64> {
+    let modified = fs::symlink_metadata(path).ok()?.modified().ok()?;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or_default();
+    Some(age.as_secs() / 86_400)
+}
+
+/// Returns the time `days` whole days before now, or the Unix epoch if that is earlier,
+/// as it is for an age too large to represent.
+pub fn days_ago(days: u64) -> SystemTime {
+    let age = Duration::from_secs(days.saturating_mul(86_400));
+    SystemTime::now()
+        .checked_sub(age)
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Returns when `path` was last used: the latest access or modification time of a file
+/// or link, or for a directory, of anything inside it. `None` if unknown.
+///
+/// A directory's own access time is ignored, since listing it (as a scan does) updates
+/// it; its modification time still counts, as entries being added or removed is a
+/// use. Access times are only as fresh as the mount allows (`relatime`, `noatime`).
+pub fn last_used(path: &Path) -> Option<SystemTime> {
+    let used = |metadata: &fs::Metadata| {
+        let modified = metadata.modified().ok();
+        if metadata.is_dir() {
+            return modified;
+        }
+        modified.max(metadata.accessed().ok())
+    };
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| used(&entry.metadata().ok()?))
+        .max()
+}
+
+/// Returns the whole days since `path` was last used (see [`last_used`]), or `None` if
+/// unknown.
+pub fn unused_days(path: &Path) -> Option<u64> {
+    let age = SystemTime::now()
+        .duration_since(last_used(path)?)
+        .unwrap_or_default();
+    Some(age.as_secs() / 86_400)
+}
+
+/// Expands a leading `~/` in `path` to the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Some(dirs) = directories::BaseDirs::new() {
+            return dirs.home_dir().join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Files and directories whose presence marks a project root.
+pub(crate) const PROJECT_MARKERS: &[&str] = &[
+    ".git",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "Gemfile",
+    "composer.json",
+    "pom.xml",
+    "build.gradle",
+];
+
+/// Returns the nearest ancestor of `path` (excluding `path` itself) that contains a
+/// project marker such as `Cargo.toml`, `package.json`, or `.git`.
+pub fn project_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| {
+            PROJECT_MARKERS
+                .iter()
+                .any(|marker| dir.join(marker).exists())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Returns the newest modification time of the files at or below `path`.
+///
+/// Directory mtimes are ignored, since they change whenever an entry is added or
+/// removed rather than when contents are used.
+pub fn newest_modified(path: &Path) -> SystemTime {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Returns the total size of the regular files under `path` that have no other hard
+/// links, i.e. the space actually freed by deleting `path`.
+///
+/// On platforms without link counts this is the same as [`dir_size`].
+pub fn unshared_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| link_count(metadata) <= 1)
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(unix)]
+fn link_count(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn link_count(_metadata: &fs::Metadata) -> u64 {
+    1
+}
+
+/// Returns the bytes available to unprivileged users on the file system holding `path`,
+/// or `None` if it cannot be determined.
+#[cfg(unix)]
+pub fn free_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is a valid null-terminated C string, stat is properly aligned
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if ret != 0 {
+        let err = std::io::Error::last_os_error();
+        log::warn!("statvfs failed for {}: {}", path.display(), err);
+        return None;
+    }
+
+    // SAFETY: statvfs returned 0, so stat is initialized
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    let free_bytes = (stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64);
+    Some(free_bytes)
+}
+
+/// Free space is not probed on this platform.
+#[cfg(not(unix))]
+pub fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Returns `true` if `path` (not its link target) is owned by the user running `mc`.
+///
+/// Ownership is the file's uid on Unix and its owner SID on Windows. Paths whose owner
+/// cannot be determined are reported as not owned.
+pub fn is_owned_by_current_user(path: &Path) -> bool {
+    owned_by_current_user(path).unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn owned_by_current_user(path: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::symlink_metadata(path).ok()?;
+    // SAFETY: `geteuid` has no preconditions and cannot fail.
+    Some(metadata.uid() == unsafe { libc::geteuid() })
+}
+
+#[cfg(windows)]
+fn owned_by_current_user(path: &Path) -> Option<bool> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+    use windows_sys::Win32::Security::Authorization::{GetNamedSecurityInfoW, SE_FILE_OBJECT};
+    use windows_sys::Win32::Security::{
+        EqualSid, GetTokenInformation, TokenUser, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR,
+        PSID, TOKEN_QUERY, TOKEN_USER,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut owner: PSID = std::ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+    // SAFETY: `wide` is NUL-terminated and the out-pointers are valid. `owner` points into
+    // `descriptor`, which is freed with `LocalFree` once the comparison is done.
+    unsafe {
+        let status = GetNamedSecurityInfoW(
+            wide.as_ptr(),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION,
+            &mut owner,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut descriptor,
+        );
+        if status != 0 {
+            return None;
+        }
+
+        let mut token: HANDLE = std::ptr::null_mut();
+        let mut result = None;
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) != 0 {
+            // Large enough for a TOKEN_USER with the longest possible SID.
+            let mut buffer = [0u64; 16];
+            let mut len = 0;
+            if GetTokenInformation(
+                token,
+                TokenUser,
+                buffer.as_mut_ptr().cast(),
+                std::mem::size_of_val(&buffer) as u32,
+                &mut len,
+            ) != 0
+            {
+                let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+                result = Some(EqualSid(owner, user.User.Sid) != 0);
+            }
+            CloseHandle(token);
+        }
+        LocalFree(descriptor);
+        result
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn owned_by_current_user(_path: &Path) -> Option<bool> {
+    Some(true)
+}
+
+/// Returns the raw bytes of `path`, to store it exactly; lossy off Unix.
+#[cfg(unix)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Returns the path whose raw bytes are `bytes`, as from [`path_to_bytes`] or git.
+#[cfg(unix)]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// A stable 64-bit FNV-1a hash, used to derive file names and fingerprints from paths.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
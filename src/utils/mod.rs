@@ -1,7 +1,12 @@
+pub mod device;
+pub mod envelope;
+pub mod format;
+pub mod fs;
 pub mod progress;
 
 pub use progress::{
-    CategoryTracker, CompactDisplay, NoOpProgress, Progress, ProgressReporter, ScanStats,
+    CategoryTracker, CompactDisplay, MultiRootProgress, NoOpProgress, PlainProgress, Progress,
+    ProgressReporter, RootProgress, ScanStats,
 };
 
 /// Returns the number of available logical CPU cores.
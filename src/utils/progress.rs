@@ -5,20 +5,29 @@
 //! reporter for quiet mode. This decouples the core logic from the specifics of
 //! the UI representation.
 
-use crate::types::PatternCategory;
+use super::format;
+use crate::types::{CleanItem, PatternCategory};
 use colored::*;
 use dashmap::DashMap;
-use humansize::{format_size, DECIMAL};
-use indicatif::{ProgressBar, ProgressStyle};
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::time::Instant;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 /// A trait for progress reporters.
 ///
 /// This allows different parts of the application to report progress without being
 /// coupled to a specific implementation. The trait must be `Send + Sync` to allow
 /// it to be shared safely across threads.
+///
+/// A clean goes through phases: the scanner starts `"scan"`, in which each matched
+/// item is one step and the total is unknown, and the cleaner starts `"clean"`, in
+/// which each item deleted is one step out of the items to delete. Only `increment`,
+/// `set_message` and `finish` must be implemented; the phase methods do nothing by
+/// default, and [`item_completed`](Progress::item_completed) counts one step.
 pub trait Progress: Send + Sync {
     /// Increments the progress by a given amount.
     fn increment(&self, delta: u64);
@@ -26,6 +35,33 @@ pub trait Progress: Send + Sync {
     fn set_message(&self, msg: &str);
     /// Finishes the progress reporting, typically hiding the indicator.
     fn finish(&self);
+
+    /// Starts phase `name` with `total` steps, if known. Steps count from zero again.
+    fn begin_phase(&self, _name: &str, _total: Option<u64>) {}
+
+    /// Sets the number of steps of the current phase, once it is known or changes,
+    /// such as when items keep arriving from a streaming scan.
+    fn set_total(&self, _total: u64) {}
+
+    /// Reports that `item` was matched, while scanning, or deleted, while cleaning.
+    fn item_completed(&self, _item: &CleanItem) {
+        self.increment(1);
+    }
+
+    /// Reports that the scan entered the directory `path`. Called from the scanning
+    /// threads for every directory, so it should return quickly.
+    fn dir_visited(&self, _path: &Path) {}
+}
+
+/// How often [`PlainProgress`] prints a line.
+pub const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Returns whether progress can be drawn in place, which needs stderr to be a terminal,
+/// and stdout too, so that bars do not end up between lines written to a file.
+/// Otherwise, such as in CI logs or when either goes to a file, [`PlainProgress`]
+/// should be used instead of spinners and bars.
+pub fn is_interactive() -> bool {
+    io::stderr().is_terminal() && io::stdout().is_terminal()
 }
 
 /// Thread-safe statistics for scan operations.
@@ -37,10 +73,18 @@ pub struct ScanStats {
     pub dirs_scanned: AtomicUsize,
     /// Files examined
     pub files_scanned: AtomicUsize,
+    /// Symbolic links seen
+    pub symlinks_scanned: AtomicUsize,
+    /// Entries that could not be read
+    pub errors: AtomicUsize,
+    /// Bytes in the files examined, matched or not
+    pub bytes_examined: AtomicU64,
     /// Items matched for cleaning
     pub items_matched: AtomicUsize,
     /// Bytes matched for cleaning
     pub bytes_matched: AtomicU64,
+    /// Entries the walk will visit, from the pre-count (0 while unknown)
+    pub entries_total: AtomicUsize,
 }
 
 impl ScanStats {
@@ -63,6 +107,21 @@ impl ScanStats {
         self.files_scanned.fetch_add(1, Ordering::Relaxed);
     }
 
+    #[inline]
+    pub fn inc_symlink(&self) {
+        self.symlinks_scanned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn inc_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_bytes(&self, size: u64) {
+        self.bytes_examined.fetch_add(size, Ordering::Relaxed);
+    }
+
     #[inline]
     pub fn inc_matched(&self, size: u64) {
         self.items_matched.fetch_add(1, Ordering::Relaxed);
@@ -81,6 +140,18 @@ impl ScanStats {
         self.files_scanned.load(Ordering::Relaxed)
     }
 
+    pub fn symlinks(&self) -> usize {
+        self.symlinks_scanned.load(Ordering::Relaxed)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn examined_bytes(&self) -> u64 {
+        self.bytes_examined.load(Ordering::Relaxed)
+    }
+
     pub fn matched(&self) -> usize {
         self.items_matched.load(Ordering::Relaxed)
     }
@@ -88,6 +159,19 @@ impl ScanStats {
     pub fn matched_bytes(&self) -> u64 {
         self.bytes_matched.load(Ordering::Relaxed)
     }
+
+    /// Records the number of entries the walk will visit.
+    pub fn set_total(&self, total: usize) {
+        self.entries_total.store(total, Ordering::Relaxed);
+    }
+
+    /// Returns the number of entries the walk will visit, once known.
+    pub fn total(&self) -> Option<usize> {
+        match self.entries_total.load(Ordering::Relaxed) {
+            0 => None,
+            total => Some(total),
+        }
+    }
 }
 
 /// A progress reporter that displays a visual progress bar in the console.
@@ -125,6 +209,19 @@ impl Progress for ProgressReporter {
     fn finish(&self) {
         self.bar.finish_with_message("Complete");
     }
+
+    fn begin_phase(&self, name: &str, total: Option<u64>) {
+        self.bar.set_position(0);
+        match total {
+            Some(total) => self.bar.set_length(total),
+            None => self.bar.unset_length(),
+        }
+        self.bar.set_message(name.to_string());
+    }
+
+    fn set_total(&self, total: u64) {
+        self.bar.set_length(total);
+    }
 }
 
 /// A no-op progress reporter that does nothing.
@@ -139,6 +236,266 @@ impl Progress for NoOpProgress {
     fn finish(&self) {}
 }
 
+/// Progress as plain lines on stderr, for logs and files that cannot show spinners or
+/// bars redrawn in place.
+///
+/// A line such as `cleaned 1,200/5,000 items, 3.2 GB` is printed at most every
+/// interval, and a last one when the reporting finishes. Scanning prints the
+/// directories scanned and the items found instead.
+pub struct PlainProgress {
+    phase: Mutex<String>,
+    done: AtomicU64,
+    /// The steps of the phase, or `u64::MAX` while unknown.
+    total: AtomicU64,
+    bytes: AtomicU64,
+    dirs: AtomicU64,
+    interval: Duration,
+    start_time: Instant,
+    /// When the last line was printed, in milliseconds since `start_time`.
+    last_print: AtomicU64,
+}
+
+impl PlainProgress {
+    /// Creates a reporter for a clean of `total` items, printing every
+    /// [`PLAIN_INTERVAL`].
+    pub fn new(total: u64) -> Self {
+        Self::with_interval(total, PLAIN_INTERVAL)
+    }
+
+    /// Creates a reporter for a scan, whose number of steps is unknown.
+    pub fn new_for_scanning() -> Self {
+        let progress = Self::new(0);
+        progress.begin_phase("scan", None);
+        progress
+    }
+
+    /// Creates a reporter for a clean of `total` items, printing every `interval`.
+    pub fn with_interval(total: u64, interval: Duration) -> Self {
+        Self {
+            phase: Mutex::new("clean".to_string()),
+            done: AtomicU64::new(0),
+            total: AtomicU64::new(total),
+            bytes: AtomicU64::new(0),
+            dirs: AtomicU64::new(0),
+            interval,
+            start_time: Instant::now(),
+            last_print: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the line describing the progress so far.
+    pub fn line(&self) -> String {
+        let done = self.done.load(Ordering::Relaxed);
+        let total = match self.total.load(Ordering::Relaxed) {
+            u64::MAX => String::new(),
+            total => format!("/{}", format::count(total)),
+        };
+        match self
+            .phase
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_str()
+        {
+            "scan" => format!(
+                "scanned {} directories, {} items found",
+                format::count(self.dirs.load(Ordering::Relaxed)),
+                format::count(done)
+            ),
+            "clean" => format!(
+                "cleaned {}{} items, {}",
+                format::count(done),
+                total,
+                format::size(self.bytes.load(Ordering::Relaxed))
+            ),
+            phase => format!("{} {}{}", phase, format::count(done), total),
+        }
+    }
+
+    /// Prints a line if the last one is at least an interval old.
+    fn maybe_print(&self) {
+        let now_ms = self.start_time.elapsed().as_millis() as u64;
+        let last = self.last_print.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last) >= self.interval.as_millis() as u64
+            && self
+                .last_print
+                .compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.print();
+        }
+    }
+
+    fn print(&self) {
+        let _ = writeln!(io::stderr().lock(), "{}", self.line());
+    }
+}
+
+impl Progress for PlainProgress {
+    fn increment(&self, delta: u64) {
+        self.done.fetch_add(delta, Ordering::Relaxed);
+        self.maybe_print();
+    }
+
+    /// Messages are meant for a display redrawn in place, so they are not printed.
+    fn set_message(&self, _msg: &str) {}
+
+    fn finish(&self) {
+        self.print();
+    }
+
+    fn begin_phase(&self, name: &str, total: Option<u64>) {
+        *self.phase.lock().unwrap_or_else(|e| e.into_inner()) = name.to_string();
+        self.done.store(0, Ordering::Relaxed);
+        self.total
+            .store(total.unwrap_or(u64::MAX), Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+
+    fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn item_completed(&self, item: &CleanItem) {
+        self.bytes.fetch_add(item.size, Ordering::Relaxed);
+        self.increment(1);
+    }
+
+    fn dir_visited(&self, _path: &Path) {
+        self.dirs.fetch_add(1, Ordering::Relaxed);
+        self.maybe_print();
+    }
+}
+
+/// Progress for several roots cleaned at once: a bar per root above a bar for all of
+/// them, so a sweep over a workspace or a fleet stays readable.
+///
+/// Each root reports to its own [`RootProgress`], from
+/// [`add_root`](MultiRootProgress::add_root), which can be handed to anything taking
+/// a [`Progress`]. The overall bar counts the roots finished and the items cleaned.
+/// Roots cleaned one after another, which print and prompt in between, can
+/// [`hide`](MultiRootProgress::hide) the bars meanwhile.
+pub struct MultiRootProgress {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    cleaned: Arc<AtomicU64>,
+}
+
+impl MultiRootProgress {
+    /// Creates the display for `roots` roots, showing only the overall bar until the
+    /// first root is added.
+    pub fn new(roots: usize) -> Self {
+        let multi = MultiProgress::new();
+        let overall = multi.add(ProgressBar::new(roots as u64));
+        overall.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "{spinner:.green} {prefix:.bold} [{bar:30.green/dim}] {pos}/{len} • {msg}",
+                )
+                .unwrap()
+                .progress_chars("█░"),
+        );
+        overall.set_prefix("Roots");
+        overall.set_message("0 items cleaned");
+        overall.enable_steady_tick(Duration::from_millis(100));
+        Self {
+            multi,
+            overall,
+            cleaned: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Adds a bar for the root named `root` above the overall bar and returns the
+    /// progress to report the root to.
+    pub fn add_root(&self, root: &str) -> RootProgress {
+        let bar = self.multi.insert_before(&self.overall, ProgressBar::new(0));
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("  {spinner:.cyan} {prefix} [{bar:30.cyan/blue}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("█░"),
+        );
+        bar.set_prefix(root.to_string());
+        RootProgress {
+            bar,
+            overall: self.overall.clone(),
+            cleaned: Arc::clone(&self.cleaned),
+            cleaning: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    /// Takes the bars off the screen until [`show`](MultiRootProgress::show), so that
+    /// other output is not drawn over.
+    pub fn hide(&self) {
+        let _ = self.multi.clear();
+        self.multi.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    /// Draws the bars again after [`hide`](MultiRootProgress::hide).
+    pub fn show(&self) {
+        self.multi.set_draw_target(ProgressDrawTarget::stderr());
+    }
+
+    /// Prints `line` to stderr above the bars.
+    pub fn println(&self, line: &str) {
+        self.multi.suspend(|| eprintln!("{}", line));
+    }
+
+    /// Removes every bar, once all roots are done.
+    pub fn finish(&self) {
+        self.overall.finish_and_clear();
+        let _ = self.multi.clear();
+    }
+}
+
+/// The bar of one root in a [`MultiRootProgress`].
+pub struct RootProgress {
+    bar: ProgressBar,
+    overall: ProgressBar,
+    cleaned: Arc<AtomicU64>,
+    /// Whether the root is in its `clean` phase, whose steps count as items cleaned.
+    cleaning: AtomicBool,
+    /// Whether the root was counted as finished.
+    finished: AtomicBool,
+}
+
+impl Progress for RootProgress {
+    fn increment(&self, delta: u64) {
+        self.bar.inc(delta);
+        if self.cleaning.load(Ordering::Relaxed) {
+            let cleaned = self.cleaned.fetch_add(delta, Ordering::Relaxed) + delta;
+            self.overall
+                .set_message(format!("{} items cleaned", format::count(cleaned)));
+        }
+    }
+
+    fn set_message(&self, msg: &str) {
+        self.bar.set_message(msg.to_string());
+    }
+
+    /// Keeps the bar, at its final count, and counts the root as finished, once.
+    fn finish(&self) {
+        if !self.finished.swap(true, Ordering::Relaxed) {
+            self.bar.finish_with_message("done");
+            self.overall.inc(1);
+        }
+    }
+
+    fn begin_phase(&self, name: &str, total: Option<u64>) {
+        self.cleaning.store(name == "clean", Ordering::Relaxed);
+        self.bar.set_position(0);
+        match total {
+            Some(total) => self.bar.set_length(total),
+            None => self.bar.unset_length(),
+        }
+        self.bar.set_message(name.to_string());
+    }
+
+    fn set_total(&self, total: u64) {
+        self.bar.set_length(total);
+    }
+}
+
 /// Tracks statistics per category for compact display.
 #[derive(Default)]
 pub struct CategoryTracker {
@@ -203,22 +560,15 @@ impl CategoryTracker {
         let mut parts = Vec::new();
 
         // Only show categories that have items
-        for category in [
-            PatternCategory::Dependencies,
-            PatternCategory::BuildOutputs,
-            PatternCategory::Cache,
-            PatternCategory::IDE,
-            PatternCategory::Logs,
-            PatternCategory::Other,
-        ] {
+        for category in PatternCategory::ALL {
             let count = self.get_count(category);
             if count > 0 {
                 let size = self.get_size(category);
                 parts.push(format!(
                     "{}: {} ({})",
                     category.label().bright_cyan(),
-                    count.to_string().bright_white(),
-                    format_size(size, DECIMAL).bright_green()
+                    format::count(count as u64).bright_white(),
+                    format::size(size).bright_green()
                 ));
             }
         }
@@ -228,12 +578,20 @@ impl CategoryTracker {
 }
 
 /// A compact 3-line progress display for scanning and cleaning operations.
+///
+/// While scanning, the display reads its counters from the shared [`ScanStats`], which
+/// the scanner fills when given [`get_scan_stats`](Self::get_scan_stats). Set as the
+/// scanner's progress too, it redraws as directories are entered and items matched, at
+/// most every 50ms; a background ticker keeps it live when neither happens for a while.
 pub struct CompactDisplay {
     bar: ProgressBar,
     category_tracker: Arc<CategoryTracker>,
     scan_stats: Arc<ScanStats>,
     start_time: Instant,
     last_update: AtomicU64,
+    /// Whether this is the scanning display rather than the cleaning bar.
+    scanning: bool,
+    ticker: Mutex<Option<(Arc<AtomicBool>, JoinHandle<()>)>>,
 }
 
 impl CompactDisplay {
@@ -245,14 +603,34 @@ impl CompactDisplay {
                 .unwrap()
                 .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
         );
-        bar.enable_steady_tick(std::time::Duration::from_millis(80));
+        bar.enable_steady_tick(Duration::from_millis(80));
+
+        let scan_stats = Arc::new(ScanStats::new());
+        let start_time = Instant::now();
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (bar, stats, tracker, stop) = (
+                bar.clone(),
+                Arc::clone(&scan_stats),
+                Arc::clone(&category_tracker),
+                Arc::clone(&stop),
+            );
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    render_scan(&bar, &stats, &tracker, start_time);
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            })
+        };
 
         Self {
             bar,
             category_tracker,
-            scan_stats: Arc::new(ScanStats::new()),
-            start_time: Instant::now(),
+            scan_stats,
+            start_time,
             last_update: AtomicU64::new(0),
+            scanning: true,
+            ticker: Mutex::new(Some((stop, handle))),
         }
     }
 
@@ -271,6 +649,8 @@ impl CompactDisplay {
             scan_stats: Arc::new(ScanStats::new()),
             start_time: Instant::now(),
             last_update: AtomicU64::new(0),
+            scanning: false,
+            ticker: Mutex::new(None),
         }
     }
 
@@ -313,36 +693,19 @@ impl CompactDisplay {
 
     /// Updates the scanning display with current statistics
     fn update_scan_display(&self) {
-        let stats = &self.scan_stats;
-        let entries = stats.entries();
-        let dirs = stats.dirs();
-        let matched = self.category_tracker.total_count();
-        let matched_size = self.category_tracker.total_size();
-        let elapsed = self.start_time.elapsed().as_secs_f64();
-
-        // Calculate scan rate
-        let rate = if elapsed > 0.0 {
-            (entries as f64 / elapsed) as usize
-        } else {
-            0
-        };
-
-        let line1 = format!(
-            "{}  {} found ({}) • {} entries ({}/s)",
-            "Scanning".bright_blue(),
-            matched.to_string().bright_white(),
-            format_size(matched_size, DECIMAL).bright_green(),
-            dirs.to_string().dimmed(),
-            rate.to_string().dimmed()
+        render_scan(
+            &self.bar,
+            &self.scan_stats,
+            &self.category_tracker,
+            self.start_time,
         );
+    }
 
-        let line2 = self.category_tracker.format_breakdown();
-
-        // Combine into message
-        if line2.is_empty() {
-            self.bar.set_message(line1);
-        } else {
-            self.bar.set_message(format!("{}\n  {}", line1, line2));
+    /// Stops the scanning ticker, if one is running.
+    fn stop_ticker(&self) {
+        if let Some((stop, handle)) = self.ticker.lock().unwrap().take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
         }
     }
 
@@ -376,6 +739,97 @@ impl Progress for CompactDisplay {
     }
 
     fn finish(&self) {
+        self.stop_ticker();
         self.bar.finish_and_clear();
     }
+
+    /// Restarts the count and, if `total` is given, the length of the bar. The message
+    /// is kept: the scanning display renders its own, and the caller sets the cleaning
+    /// one.
+    fn begin_phase(&self, _name: &str, total: Option<u64>) {
+        self.bar.set_position(0);
+        if let Some(total) = total {
+            self.bar.set_length(total);
+        }
+    }
+
+    fn set_total(&self, total: u64) {
+        self.bar.set_length(total);
+    }
+
+    /// Counted by the scanner in the shared stats; this only redraws.
+    fn item_completed(&self, _item: &CleanItem) {
+        if self.scanning {
+            self.maybe_update_display();
+        } else {
+            self.bar.inc(1);
+        }
+    }
+
+    /// Counted by the scanner in the shared stats; this only redraws.
+    fn dir_visited(&self, _path: &Path) {
+        self.maybe_update_display();
+    }
+}
+
+impl Drop for CompactDisplay {
+    fn drop(&mut self) {
+        self.stop_ticker();
+    }
+}
+
+/// Renders the scanning message from the live counters.
+///
+/// Matches are counted by the scanner as they are found, while the category tracker is
+/// only filled once directory sizes are aggregated and nested matches are left out. The
+/// tracker is shown as soon as it is filled, since the live counts include nested items.
+fn render_scan(
+    bar: &ProgressBar,
+    stats: &ScanStats,
+    tracker: &CategoryTracker,
+    start_time: Instant,
+) {
+    let entries = stats.entries();
+    let (matched, matched_size) = if tracker.total_count() > 0 {
+        (tracker.total_count(), tracker.total_size())
+    } else {
+        (stats.matched(), stats.matched_bytes())
+    };
+    let elapsed = start_time.elapsed().as_secs_f64();
+
+    // Calculate scan rate
+    let rate = if elapsed > 0.0 {
+        (entries as f64 / elapsed) as usize
+    } else {
+        0
+    };
+
+    let entries = match stats.total() {
+        Some(total) => format!(
+            "{}/{} entries, {}%",
+            format::count(entries as u64),
+            format::count(total as u64),
+            (entries * 100 / total).min(100)
+        ),
+        None => format!("{} entries", format::count(entries as u64)),
+    };
+
+    let line1 = format!(
+        "{}  {} found ({}) • {} dirs scanned • {} ({}/s)",
+        "Scanning".bright_blue(),
+        format::count(matched as u64).bright_white(),
+        format::size(matched_size).bright_green(),
+        format::count(stats.dirs() as u64).bright_white(),
+        entries.dimmed(),
+        format::count(rate as u64).dimmed()
+    );
+
+    let line2 = tracker.format_breakdown();
+
+    // Combine into message
+    if line2.is_empty() {
+        bar.set_message(line1);
+    } else {
+        bar.set_message(format!("{}\n  {}", line1, line2));
+    }
 }
@@ -76,3 +76,971 @@ fn test_nonexistent_path_returns_error() {
         .assert()
         .failure();
 }
+
+#[cfg(unix)]
+#[test]
+fn test_check_runs_alert_hook_over_threshold() {
+    let temp = TempDir::new().unwrap();
+    temp.child("project/node_modules/pkg/index.js")
+        .write_str("module.exports = {};")
+        .unwrap();
+    let marker = temp.child("alerted");
+    let config = temp.child("mc.toml");
+    config
+        .write_str(&format!(
+            r#"
+[patterns]
+directories = ["node_modules"]
+files = []
+exclude = []
+
+[options]
+
+[safety]
+
+[alerts]
+threshold_gb = 0.0
+notify = false
+hook = "touch {}"
+"#,
+            marker.path().display()
+        ))
+        .unwrap();
+
+    mc_cmd()
+        .arg("--config")
+        .arg(config.path())
+        .arg(temp.child("project").path())
+        .arg("check")
+        .assert()
+        .success();
+
+    marker.assert(predicates::path::exists());
+    temp.child("project/node_modules")
+        .assert(predicates::path::exists());
+}
+
+#[test]
+fn test_max_depth_flag_limits_scan() {
+    let temp = TempDir::new().unwrap();
+    temp.child("a/b/node_modules/pkg").create_dir_all().unwrap();
+
+    mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check", "--max-depth", "2"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("node_modules").not());
+
+    mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check", "--max-depth", "3"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("node_modules"));
+}
+
+#[test]
+fn test_unused_for_skips_recently_used_items() {
+    let temp = TempDir::new().unwrap();
+    temp.child("node_modules/pkg/index.js").touch().unwrap();
+
+    mc_cmd()
+        .args([
+            "--dry-run",
+            "--json",
+            "--no-git-check",
+            "--unused-for",
+            "30d",
+        ])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("node_modules").not());
+
+    mc_cmd()
+        .args(["--dry-run", "--no-git-check", "--unused-for", "soon"])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid age"));
+
+    mc_cmd()
+        .args([
+            "--dry-run",
+            "--no-git-check",
+            "--unused-for",
+            "18446744073709551615y",
+        ])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("too large"));
+
+    mc_cmd()
+        .args(["--dry-run", "--no-git-check", "--unused-for", "6m"])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("6mo"));
+}
+
+#[test]
+fn test_list_plans_like_a_dry_run() {
+    let temp = TempDir::new().unwrap();
+    temp.child("node_modules/pkg/index.js").touch().unwrap();
+    temp.child("target/debug/app").touch().unwrap();
+
+    let output = mc_cmd()
+        .args(["--no-git-check", "--exclude", "target"])
+        .arg(temp.path())
+        .args(["list", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let items: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(items.len(), 1);
+    assert!(items[0]["path"].as_str().unwrap().ends_with("node_modules"));
+    assert_eq!(items[0]["unused_days"], 0);
+
+    mc_cmd()
+        .args(["--no-git-check", "--unused-for", "30d"])
+        .arg(temp.path())
+        .args(["list", "--json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("node_modules").not());
+}
+
+#[test]
+fn test_json_reports_entries_scanned() {
+    let temp = TempDir::new().unwrap();
+    temp.child("node_modules/pkg/index.js").touch().unwrap();
+    temp.child("src/main.rs").touch().unwrap();
+
+    let output = mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check"])
+        .arg(temp.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let entries = report["entries_scanned"].as_u64().unwrap();
+    assert!(entries >= 4, "{}", report);
+    assert_eq!(report["scan"]["entries"].as_u64(), Some(entries));
+}
+
+#[test]
+fn test_dry_run_json_times_each_phase() {
+    let temp = TempDir::new().unwrap();
+    temp.child("node_modules/pkg/index.js").touch().unwrap();
+
+    let output = mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check"])
+        .arg(temp.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let timings = &report["timings"];
+    let ms = |key: &str| {
+        timings[key]
+            .as_u64()
+            .unwrap_or_else(|| panic!("{} is not a non-negative integer: {}", key, timings))
+    };
+    let phases = ["scan_ms", "prune_ms", "confirmation_ms", "clean_ms"];
+    let sum: u64 = phases.iter().map(|phase| ms(phase)).sum();
+    assert!(ms("total_ms") >= sum, "{}", timings);
+    assert_eq!(ms("confirmation_ms"), 0);
+    assert_eq!(report["scan_duration_ms"].as_u64(), Some(ms("scan_ms")));
+}
+
+#[test]
+fn test_show_limits_the_dry_run_listing() {
+    let temp = TempDir::new().unwrap();
+    for name in ["a.log", "b.log", "c.log"] {
+        temp.child(name).touch().unwrap();
+    }
+
+    // Without a terminal the listing is printed directly, whatever $PAGER says.
+    mc_cmd()
+        .env("PAGER", "false")
+        .args(["--dry-run", "--no-git-check", "--show", "1"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("... and 2 more files"));
+
+    mc_cmd()
+        .env("PAGER", "false")
+        .args(["--dry-run", "--no-git-check", "--show-all"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains("c.log").and(predicates::str::contains("more files").not()),
+        );
+
+    mc_cmd()
+        .args(["--dry-run", "--show", "1", "--show-all"])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_timeouts_that_overflow_are_rejected() {
+    let temp = TempDir::new().unwrap();
+
+    mc_cmd()
+        .args([
+            "--dry-run",
+            "--no-git-check",
+            "--scan-timeout",
+            "18446744073709551615h",
+        ])
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("too large"));
+}
+
+#[test]
+fn test_summary_line_is_the_only_output() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app.log").write_str("12345").unwrap();
+
+    let line = r"^freed=\d+ items=1 errors=0 duration_ms=\d+\n$";
+    mc_cmd()
+        .args(["--quiet", "--yes", "--summary-line", "--no-git-check"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(line).unwrap());
+    temp.child("app.log").assert(predicates::path::missing());
+}
+
+#[test]
+fn test_progress_is_plain_lines_without_a_terminal() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app.log").write_str("12345").unwrap();
+
+    mc_cmd()
+        .args(["--yes", "--no-git-check"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("cleaned 1/1 items, 5 B\n"))
+        .stderr(predicates::str::contains("\r").not());
+}
+
+#[test]
+fn test_binary_units() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app.log").write_binary(&[0; 2048]).unwrap();
+
+    mc_cmd()
+        .env("LC_ALL", "C")
+        .arg("--binary")
+        .arg(temp.path())
+        .arg("plan")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 items, 2 KiB"));
+
+    let config = temp.child("mc.toml");
+    config
+        .write_str(
+            "[patterns]\ndirectories = []\nfiles = [\"*.log\"]\nexclude = []\n\n\
+             [options]\nbinary_units = true\n\n[safety]\n",
+        )
+        .unwrap();
+    let plan = |units: Option<&str>| {
+        let mut cmd = mc_cmd();
+        cmd.env("LC_ALL", "C").arg("--config").arg(config.path());
+        cmd.args(units).arg(temp.path()).arg("plan");
+        cmd.assert().success()
+    };
+    plan(None).stdout(predicates::str::contains("1 items, 2 KiB"));
+    plan(Some("--si")).stdout(predicates::str::contains("1 items, 2.05 kB"));
+}
+
+#[test]
+fn test_unknown_rebuild_keys_are_warned_about() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app.log").write_str("log").unwrap();
+    let config = temp.child("mc.toml");
+    config
+        .write_str(
+            "[patterns]\ndirectories = []\nfiles = [\"*.log\"]\nexclude = []\n\n\
+             [patterns.rebuild]\n\"*.log\" = \"restart the app\"\nLogs = \"wait\"\n\
+             Caches = \"rerun\"\n\n[options]\n\n[safety]\n",
+        )
+        .unwrap();
+
+    mc_cmd()
+        .arg("--config")
+        .arg(config.path())
+        .args(["--dry-run", "--no-git-check"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("restart the app"))
+        .stderr(predicates::str::contains("key `Caches`"))
+        .stderr(predicates::str::contains("key `Logs`").not())
+        .stderr(predicates::str::contains("key `*.log`").not());
+}
+
+#[test]
+fn test_projects_lists_reclaimable_space() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app/Cargo.toml").write_str("[package]").unwrap();
+    temp.child("app/target/debug/app")
+        .write_binary(&[0; 100])
+        .unwrap();
+
+    mc_cmd()
+        .arg(temp.path())
+        .args(["projects", "--json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "\"kinds\": [\n      \"rust\"\n    ]",
+        ))
+        .stdout(predicates::str::contains("\"reclaimable_items\": 1"));
+    temp.child("app/target").assert(predicates::path::exists());
+}
+
+#[test]
+fn test_recommend_keeps_recently_used_items() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app/node_modules/pkg/index.js")
+        .write_str("module.exports = 1")
+        .unwrap();
+
+    mc_cmd()
+        .arg(temp.path())
+        .args(["recommend", "--json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"suggested\": false"));
+    mc_cmd()
+        .args(["--yes"])
+        .arg(temp.path())
+        .args(["recommend", "--apply"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Suggested plan: 0 of 1 items"));
+    temp.child("app/node_modules")
+        .assert(predicates::path::exists());
+}
+
+#[test]
+fn test_recommend_applies_through_the_normal_clean() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.child("my root");
+    let blob = root.child("app/node_modules/pkg/blob.bin");
+    blob.touch().unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(blob.path())
+        .unwrap()
+        .set_len(50_000_000)
+        .unwrap();
+    // Unused for a year, so it is suggested.
+    let year_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(365 * 86_400);
+    let times = std::fs::FileTimes::new()
+        .set_accessed(year_ago)
+        .set_modified(year_ago);
+    for path in [
+        "app/node_modules/pkg/blob.bin",
+        "app/node_modules/pkg",
+        "app/node_modules",
+    ] {
+        std::fs::File::open(root.child(path).path())
+            .unwrap()
+            .set_times(times)
+            .unwrap();
+    }
+
+    let hint = format!("Run `mc '{}' recommend --apply`", root.path().display());
+    mc_cmd()
+        .arg(root.path())
+        .arg("recommend")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(hint));
+    mc_cmd()
+        .args(["--yes"])
+        .arg(root.path())
+        .args(["recommend", "--apply"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Suggested plan: 1 of 1 items"));
+    root.child("app/node_modules")
+        .assert(predicates::path::missing());
+}
+
+#[test]
+fn test_multiple_roots_report_each_root() {
+    let temp = TempDir::new().unwrap();
+    temp.child("a/node_modules/pkg/index.js").touch().unwrap();
+    temp.child("b/target/debug/app").touch().unwrap();
+
+    mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check"])
+        .arg(temp.child("a").path())
+        .arg(temp.child("b").path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"roots\""))
+        .stdout(predicates::str::contains("\"items_deleted\": 2"));
+
+    // A missing root fails on its own; the others are still cleaned and reported.
+    mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check"])
+        .arg(temp.child("a").path())
+        .arg(temp.child("missing").path())
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("\"items_deleted\": 1"))
+        .stdout(predicates::str::contains("RootFailed"))
+        .stderr(predicates::str::contains(
+            "1 of the roots could not be cleaned",
+        ));
+}
+
+#[test]
+fn test_max_depth_truncation_is_reported() {
+    let temp = TempDir::new().unwrap();
+    temp.child("a/b/node_modules/pkg").create_dir_all().unwrap();
+
+    mc_cmd()
+        .args(["--dry-run", "--no-git-check", "--max-depth", "2"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Scan depth limit reached in 1 places",
+        ));
+
+    mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check", "--max-depth", "3"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"depth_limited_dirs\": 0"));
+}
+
+#[test]
+fn test_include_skipped_explains_excluded_matches() {
+    let temp = TempDir::new().unwrap();
+    temp.child("node_modules/pkg").create_dir_all().unwrap();
+
+    mc_cmd()
+        .args(["--dry-run", "--json", "--no-git-check", "--include-skipped"])
+        .args(["--exclude", "node_modules"])
+        .arg(temp.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"kind\": \"excluded\""))
+        .stdout(predicates::str::contains("\"exclude\": \"node_modules\""));
+}
+
+#[test]
+fn test_uncommitted_files_need_confirmation() {
+    let temp = TempDir::new().unwrap();
+    let git_init = std::process::Command::new("git")
+        .args(["init", "-q"])
+        .arg(temp.path())
+        .status();
+    if !git_init.is_ok_and(|status| status.success()) {
+        return;
+    }
+    temp.child("build/dev.sqlite").write_str("data").unwrap();
+
+    // Without a terminal to confirm on, the clean stops and says how to go ahead.
+    mc_cmd()
+        .args(["--yes", "--json", "--no-git-check"])
+        .arg(temp.path())
+        .write_stdin("y\n")
+        .assert()
+        .failure()
+        .stdout(predicates::str::is_empty())
+        .stderr(predicates::str::contains("build/dev.sqlite"))
+        .stderr(predicates::str::contains("--allow-uncommitted"));
+    temp.child("build/dev.sqlite")
+        .assert(predicates::path::exists());
+
+    mc_cmd()
+        .args(["--yes", "--no-git-check", "--allow-uncommitted"])
+        .arg(temp.path())
+        .assert()
+        .success();
+    temp.child("build").assert(predicates::path::missing());
+}
+
+#[test]
+fn test_auto_yes_under_skips_prompt_for_small_cleans() {
+    let temp = TempDir::new().unwrap();
+    temp.child("project/dist/bundle.js").write_str("x").unwrap();
+    let config = temp.child("mc.toml");
+    let write_config = |limit: &str| {
+        config
+            .write_str(&format!(
+                r#"
+[patterns]
+directories = ["dist"]
+files = []
+exclude = []
+
+[options]
+auto_yes_under = "{}"
+
+[safety]
+"#,
+                limit
+            ))
+            .unwrap();
+    };
+
+    write_config("0B");
+    mc_cmd()
+        .arg("--config")
+        .arg(config.path())
+        .arg(temp.child("project").path())
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Cleaning cancelled").not())
+        .stderr(predicates::str::contains("Proceed with cleaning? [y/N]"))
+        .stderr(predicates::str::contains("Cleaning cancelled"));
+    temp.child("project/dist")
+        .assert(predicates::path::exists());
+
+    write_config("1MB");
+    mc_cmd()
+        .arg("--config")
+        .arg(config.path())
+        .arg(temp.child("project").path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("auto_yes_under").not())
+        .stderr(predicates::str::contains("auto_yes_under (1MB)"));
+    temp.child("project/dist")
+        .assert(predicates::path::missing());
+}
+
+#[test]
+fn test_resume_finishes_interrupted_clean() {
+    let temp = TempDir::new().unwrap();
+    temp.child("project/dist/bundle.js").write_str("x").unwrap();
+    temp.child("project/debug.log").write_str("x").unwrap();
+    let config = temp.child("mc.toml");
+    let write_config = |actions: &str| {
+        config
+            .write_str(&format!(
+                r#"
+[patterns]
+directories = ["dist"]
+files = ["*.log"]
+exclude = []
+
+[options]
+
+[safety]
+{}
+"#,
+                actions
+            ))
+            .unwrap();
+    };
+
+    // The action keeps the clean busy after `dist` is deleted, until it is killed.
+    write_config("[[actions]]\npattern = \"*.log\"\ncommand = \"sleep 20\"");
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("mc"))
+        .env("XDG_DATA_HOME", temp.child("data").path())
+        .args(["--yes", "--quiet", "--config"])
+        .arg(config.path())
+        .arg(temp.child("project").path())
+        .spawn()
+        .unwrap();
+    for _ in 0..100 {
+        if !temp.child("project/dist").exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    temp.child("project/dist")
+        .assert(predicates::path::missing());
+
+    write_config("");
+    mc_cmd()
+        .env("XDG_DATA_HOME", temp.child("data").path())
+        .arg("--config")
+        .arg(config.path())
+        .arg(temp.child("project").path())
+        .arg("resume")
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Cleaning cancelled"));
+    temp.child("project/debug.log")
+        .assert(predicates::path::exists());
+
+    // With --json, stdout carries the report and nothing else.
+    let output = mc_cmd()
+        .env("XDG_DATA_HOME", temp.child("data").path())
+        .args(["--yes", "--json", "--config"])
+        .arg(config.path())
+        .arg(temp.child("project").path())
+        .arg("resume")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["items_deleted"], 1);
+    temp.child("project/debug.log")
+        .assert(predicates::path::missing());
+
+    mc_cmd()
+        .env("XDG_DATA_HOME", temp.child("data").path())
+        .arg(temp.child("project").path())
+        .arg("resume")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Nothing to resume"));
+}
+
+#[test]
+fn test_serve_stdio_answers_json_rpc() {
+    let temp = TempDir::new().unwrap();
+    temp.child("project/dist/bundle.js").write_str("x").unwrap();
+    let root = temp.child("project");
+    let request = format!(
+        "{}\n",
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "plan",
+            "params": { "root": root.path() },
+        })
+    );
+
+    let output = mc_cmd()
+        .args(["serve", "--stdio"])
+        .write_stdin(request)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let response = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .find(|message| message["id"] == 7)
+        .unwrap();
+    assert_eq!(response["result"]["plan_id"], 1);
+    root.child("dist").assert(predicates::path::exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serve_stdio_keeps_hooks_off_the_stream() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    let temp = TempDir::new().unwrap();
+    temp.child("project/dist/bundle.js").write_str("x").unwrap();
+    let config = temp.child("mc.toml");
+    // The hook would answer on stdout and swallow the requests that follow.
+    config
+        .write_str(
+            r#"
+[patterns]
+directories = ["dist"]
+files = []
+exclude = []
+
+[options]
+
+[safety]
+
+[hooks]
+pre_clean = "echo from-the-hook; cat"
+"#,
+        )
+        .unwrap();
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("mc"))
+        .arg("--config")
+        .arg(config.path())
+        .args(["serve", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut call = |id: u64, method: &str, params: serde_json::Value| {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        writeln!(stdin, "{}", request).unwrap();
+        loop {
+            let line = stdout.next().unwrap().unwrap();
+            let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if message["id"] == id && message["method"].is_null() {
+                return message;
+            }
+        }
+    };
+
+    let plan = call(
+        1,
+        "plan",
+        serde_json::json!({ "root": temp.child("project").path() }),
+    );
+    let plan_id = plan["result"]["plan_id"].clone();
+    let report = call(2, "apply", serde_json::json!({ "plan_id": plan_id }));
+    assert_eq!(report["result"]["items_deleted"], 1);
+    let scan = call(3, "scan", serde_json::json!({ "root": temp.path() }));
+    assert!(scan["result"]["items"].is_array());
+
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("from-the-hook"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_remote_cleans_through_ssh() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    temp.child("project/dist/bundle.js").write_str("x").unwrap();
+    // Stands in for ssh: drops `--` and the host and runs the command locally.
+    let ssh = temp.child("fake-ssh");
+    ssh.write_str("#!/bin/sh\nshift 2\nexec sh -c \"$1\"\n")
+        .unwrap();
+    std::fs::set_permissions(ssh.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let target = format!("farm-01:{}", temp.child("project").path().display());
+    let remote_mc = assert_cmd::cargo::cargo_bin("mc");
+    let remote = |extra: &[&str]| {
+        let mut cmd = mc_cmd();
+        cmd.args(extra)
+            .args(["remote", &target, "--ssh"])
+            .arg(ssh.path());
+        cmd.arg("--remote-mc").arg(&remote_mc);
+        cmd
+    };
+
+    remote(&["--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Dry run complete!"));
+    temp.child("project/dist")
+        .assert(predicates::path::exists());
+
+    remote(&["--yes", "--json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"items_deleted\": 1"));
+    temp.child("project/dist")
+        .assert(predicates::path::missing());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fleet_reports_each_host() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    temp.child("a/dist/bundle.js").write_str("x").unwrap();
+    temp.child("b/dist/bundle.js").write_str("x").unwrap();
+    let ssh = temp.child("fake-ssh");
+    // Stands in for ssh: drops the options, `--` and the host and runs the command
+    // locally.
+    ssh.write_str(
+        "#!/bin/sh\nwhile [ \"$1\" != -- ]; do shift; done\nshift 2\nexec sh -c \"$1\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(ssh.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let hosts = temp.child("hosts.toml");
+    hosts
+        .write_str(&format!(
+            "ssh = {:?}\nremote_mc = {:?}\n\n\
+             [[hosts]]\nhost = \"farm-01\"\nroots = [{:?}, \"/nonexistent\"]\n\n\
+             [[hosts]]\nhost = \"farm-02\"\nroots = [{:?}]\n",
+            ssh.path(),
+            assert_cmd::cargo::cargo_bin("mc"),
+            temp.child("a").path(),
+            temp.child("b").path(),
+        ))
+        .unwrap();
+
+    mc_cmd()
+        .args(["--yes", "fleet", "--hosts"])
+        .arg(hosts.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("farm-01").and(predicates::str::contains("TOTAL")));
+    temp.child("a/dist").assert(predicates::path::missing());
+    temp.child("b/dist").assert(predicates::path::missing());
+
+    let output = mc_cmd()
+        .args(["--dry-run", "--json", "fleet", "--hosts"])
+        .arg(hosts.path())
+        .output()
+        .unwrap();
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(reports[0]["host"], "farm-01");
+    assert!(reports[0]["roots"][1]["error"].is_string());
+    assert_eq!(reports[1]["roots"][0]["items_deleted"], 0);
+
+    mc_cmd()
+        .args(["--dry-run", "--report-format", "csv", "fleet", "--hosts"])
+        .arg(hosts.path())
+        .assert()
+        .failure()
+        .stdout(predicates::str::is_empty());
+}
+
+#[test]
+fn test_import_from_crontab() {
+    let temp = TempDir::new().unwrap();
+
+    mc_cmd()
+        .current_dir(temp.path())
+        .args(["import", "--from", "npkill"])
+        .write_stdin("0 3 * * 0 npx npkill --target node_modules,.next --exclude legacy -D\n")
+        .assert()
+        .success();
+
+    let config = temp.child(".mc.toml");
+    config.assert(predicates::str::contains(
+        r#"directories = ["node_modules", ".next"]"#,
+    ));
+    config.assert(predicates::str::contains(r#""legacy""#));
+}
+
+#[test]
+fn test_plan_as_shell_script() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app/node_modules/pkg/index.js")
+        .write_str("x")
+        .unwrap();
+
+    let output = mc_cmd()
+        .arg(temp.path())
+        .args(["plan", "--output", "sh"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    temp.child("app/node_modules")
+        .assert(predicates::path::exists());
+
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(String::from_utf8(output.stdout).unwrap())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    temp.child("app/node_modules")
+        .assert(predicates::path::missing());
+}
+
+#[test]
+fn test_kill_switch_forces_dry_run() {
+    let temp = TempDir::new().unwrap();
+    temp.child("app/node_modules/pkg/index.js")
+        .write_str("x")
+        .unwrap();
+    let config_home = temp.child("config");
+    config_home
+        .child("mc/disable")
+        .write_str("incident 42\n")
+        .unwrap();
+
+    mc_cmd()
+        .env("MC_FORCE_DRY_RUN", "1")
+        .arg("--yes")
+        .arg(temp.child("app").path())
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("MC_FORCE_DRY_RUN is set"));
+    temp.child("app/node_modules")
+        .assert(predicates::path::exists());
+
+    mc_cmd()
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .arg("--yes")
+        .arg(temp.child("app").path())
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("incident 42"));
+    temp.child("app/node_modules")
+        .assert(predicates::path::exists());
+}
+
+#[test]
+fn test_quarantine_moves_items_and_purges_oldest() {
+    let temp = TempDir::new().unwrap();
+    temp.child("project/dist/bundle.js").write_str("x").unwrap();
+    let quarantine = temp.child("quarantine");
+    quarantine
+        .child("mc-1000/old.bin")
+        .write_binary(&[0; 10])
+        .unwrap();
+    let config = temp.child("mc.toml");
+    config
+        .write_str(&format!(
+            r#"
+[patterns]
+directories = ["dist"]
+files = []
+exclude = []
+
+[options]
+
+[safety]
+
+[quarantine]
+dir = "{}"
+max_total = 3
+"#,
+            quarantine.path().display()
+        ))
+        .unwrap();
+
+    mc_cmd()
+        .arg("--config")
+        .arg(config.path())
+        .arg("--yes")
+        .arg(temp.child("project").path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("moved to quarantine"));
+    temp.child("project/dist")
+        .assert(predicates::path::missing());
+    quarantine
+        .child("mc-1000")
+        .assert(predicates::path::missing());
+
+    let output = mc_cmd()
+        .arg("--config")
+        .arg(config.path())
+        .arg("stats")
+        .arg("--json")
+        .output()
+        .unwrap();
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["quarantine"]["bytes"], 1);
+    assert_eq!(stats["quarantine"]["max_total"], 3);
+    assert_eq!(stats["quarantine"]["batches"].as_array().unwrap().len(), 1);
+}